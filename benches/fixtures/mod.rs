@@ -0,0 +1,72 @@
+//! Fixture generators shared by the benchmarks.
+
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use tap::tree::{Tree, TreeNodeId};
+use tap::node::Node;
+use tap::value::Value;
+use tap::vfile::VFileBuilder;
+use tap::zerovfile::ZeroVFileBuilder;
+use tap::mappedvfile::{FileRanges, MappedVFileBuilder};
+
+/// Number of attributes added to each generated node.
+pub const ATTRIBUTE_COUNT : usize = 8;
+
+/// Return a [Node] named `name` containing [ATTRIBUTE_COUNT] attributes of different types.
+pub fn node(name : String) -> Node
+{
+  let node = Node::new(name);
+  node.value().add_attributes(vec![
+    ("size", Value::U64(0x1000), None),
+    ("offset", Value::U32(512), None),
+    ("flags", Value::U16(0x20), None),
+    ("deleted", Value::Bool(false), None),
+    ("name", Value::from(String::from("file.txt")), None),
+    ("extension", Value::from("txt"), None),
+    ("entries", Value::Seq(vec![Value::U8(1), Value::U8(2), Value::U8(3)]), None),
+    ("checksum", Value::Bytes(vec![0xde, 0xad, 0xbe, 0xef]), None),
+  ]);
+  node
+}
+
+/// Create a tree of `width` children per node on `depth` levels and return it with the path of the last created leaf.
+pub fn tree(width : usize, depth : usize) -> (Tree, String)
+{
+  let tree = Tree::new();
+  let mut parents = vec![(tree.root_id, String::from("/root"))];
+  let mut last_path = String::from("/root");
+
+  for _ in 0..depth
+  {
+    let mut next = Vec::new();
+    for (parent_id, parent_path) in parents
+    {
+      for index in 0..width
+      {
+        let name = format!("node_{}", index);
+        let path = format!("{}/{}", parent_path, name);
+        let node_id : TreeNodeId = tree.add_child(parent_id, node(name)).unwrap();
+        last_path = path.clone();
+        next.push((node_id, path));
+      }
+    }
+    parents = next;
+  }
+  (tree, last_path)
+}
+
+/// Return a [MappedVFileBuilder] of `chunk_count` chunks of `chunk_size` bytes mapped in reverse order over a zero builder.
+pub fn mapped_builder(chunk_count : u64, chunk_size : u64) -> Arc<dyn VFileBuilder>
+{
+  let parent : Arc<dyn VFileBuilder> = Arc::new(ZeroVFileBuilder::new(chunk_count * chunk_size));
+  let mut ranges = FileRanges::new();
+
+  for index in 0..chunk_count
+  {
+    let offset = (chunk_count - index - 1) * chunk_size;
+    ranges.push(index * chunk_size..(index + 1) * chunk_size, offset, parent.clone());
+  }
+  Arc::new(MappedVFileBuilder::new(ranges))
+}
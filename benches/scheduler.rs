@@ -0,0 +1,31 @@
+//! Benchmarks of the scheduler dispatch latency.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use serde_json::json;
+
+use tap::session::Session;
+use tap::plugin_dummy;
+
+fn dispatch(c : &mut Criterion)
+{
+  let mut session = Session::new();
+  session.plugins_db.register(Box::new(plugin_dummy::Plugin::new()));
+  let argument = json!({"parent" : session.tree.root_id, "file_name" : "/bench", "offset" : 0}).to_string();
+
+  c.bench_function("scheduler_run_latency", |b| b.iter(|| 
+  {
+    session.run("dummy", argument.clone(), true).unwrap()
+  }));
+
+  c.bench_function("scheduler_schedule_join_100", |b| b.iter(|| 
+  {
+    for _ in 0..100
+    {
+      session.schedule("dummy", argument.clone(), true).unwrap();
+    }
+    session.join();
+  }));
+}
+
+criterion_group!{ name = benches; config = Criterion::default().sample_size(20); targets = dispatch }
+criterion_main!(benches);
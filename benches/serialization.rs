@@ -0,0 +1,25 @@
+//! Benchmarks of the serialization of large trees.
+
+mod fixtures;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use tap::export::{self, ExportFormat};
+
+fn serialize_tree(c : &mut Criterion)
+{
+  let mut group = c.benchmark_group("tree_serialization");
+  group.sample_size(20);
+
+  for width in [10usize, 30]
+  {
+    let (tree, _) = fixtures::tree(width, 3);
+    group.throughput(Throughput::Elements(tree.count() as u64));
+    group.bench_with_input(BenchmarkId::new("json", tree.count()), &tree, |b, tree| b.iter(|| serde_json::to_vec(tree).unwrap()));
+    group.bench_with_input(BenchmarkId::new("delta_json", tree.count()), &tree, |b, tree| b.iter(|| export::delta(tree, 0, ExportFormat::Json).unwrap()));
+  }
+  group.finish();
+}
+
+criterion_group!(benches, serialize_tree);
+criterion_main!(benches);
@@ -0,0 +1,55 @@
+//! Benchmarks of node insertion, path lookup and attribute access.
+
+mod fixtures;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use tap::tree::Tree;
+
+fn insertion(c : &mut Criterion)
+{
+  let mut group = c.benchmark_group("tree_insertion");
+
+  for count in [1_000usize, 10_000]
+  {
+    group.throughput(Throughput::Elements(count as u64));
+    group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, &count| 
+    {
+      b.iter(|| 
+      {
+        let tree = Tree::new();
+        for index in 0..count
+        {
+          tree.add_child(tree.root_id, fixtures::node(format!("node_{}", index))).unwrap();
+        }
+        tree
+      })
+    });
+  }
+  group.finish();
+}
+
+fn path_lookup(c : &mut Criterion)
+{
+  let (tree, path) = fixtures::tree(10, 4);
+
+  c.bench_function("tree_get_node_id", |b| b.iter(|| tree.get_node_id(&path).unwrap()));
+  c.bench_function("tree_node_path", |b| 
+  {
+    let node_id = tree.get_node_id(&path).unwrap();
+    b.iter(|| tree.node_path(node_id).unwrap())
+  });
+}
+
+fn attribute_access(c : &mut Criterion)
+{
+  let (tree, path) = fixtures::tree(10, 3);
+  let node = tree.get_node(&path).unwrap();
+
+  c.bench_function("attribute_get_value_first", |b| b.iter(|| node.value().get_value("size").unwrap()));
+  c.bench_function("attribute_get_value_last", |b| b.iter(|| node.value().get_value("checksum").unwrap()));
+  c.bench_function("attribute_names", |b| b.iter(|| node.value().names()));
+}
+
+criterion_group!(benches, insertion, path_lookup, attribute_access);
+criterion_main!(benches);
@@ -0,0 +1,52 @@
+//! Benchmarks of [MappedVFile](tap::mappedvfile::MappedVFileBuilder) read patterns.
+
+mod fixtures;
+
+use std::io::{Read, Seek, SeekFrom};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const CHUNK_SIZE : u64 = 4096;
+const CHUNK_COUNT : u64 = 1024;
+
+fn sequential_read(c : &mut Criterion)
+{
+  let builder = fixtures::mapped_builder(CHUNK_COUNT, CHUNK_SIZE);
+  let mut group = c.benchmark_group("mapped_sequential_read");
+  group.throughput(Throughput::Bytes(builder.size()));
+
+  for buffer_size in [512usize, 64 * 1024]
+  {
+    group.bench_with_input(BenchmarkId::from_parameter(buffer_size), &buffer_size, |b, &buffer_size| 
+    {
+      let mut buffer = vec![0; buffer_size];
+      b.iter(|| 
+      {
+        let mut file = builder.open().unwrap();
+        while file.read(&mut buffer).unwrap() != 0 {}
+      })
+    });
+  }
+  group.finish();
+}
+
+fn random_read(c : &mut Criterion)
+{
+  let builder = fixtures::mapped_builder(CHUNK_COUNT, CHUNK_SIZE);
+  let mut file = builder.open().unwrap();
+  let mut buffer = vec![0; 512];
+  //deterministic pseudo random offsets so runs can be compared
+  let offsets : Vec<u64> = (0..1024u64).map(|index| (index * 7919 * 512) % (builder.size() - 512)).collect();
+
+  c.bench_function("mapped_random_read_512", |b| b.iter(|| 
+  {
+    for offset in offsets.iter()
+    {
+      file.seek(SeekFrom::Start(*offset)).unwrap();
+      file.read_exact(&mut buffer).unwrap();
+    }
+  }));
+}
+
+criterion_group!(benches, sequential_read, random_read);
+criterion_main!(benches);
@@ -0,0 +1,213 @@
+//! Access control hooks for backends serving several users.
+//!
+//! The user doing a call is set for the current thread with [as_user], the returned guard restore the previous user when dropped.
+//! When a user is set, the [Tree] mutation entry points, the [Session](crate::session::Session) methods
+//! modifying the case or scheduling tasks and the export APIs ask the [Authorizer] of the tree and fail
+//! with [RustructError::AccessDenied] if it's refused. Calls made without a user, like the ones made by the plugins
+//! on the workers threads, are not checked : tasks are authorized when they are scheduled.
+//! The default [Permissive] authorizer allow everything.
+
+use std::fmt;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::tree::{Tree, TreeNodeId};
+use crate::error::RustructError;
+
+use serde::{Serialize, Deserialize};
+
+/// Kind of access asked to an [Authorizer].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Access
+{
+  /// Add, remove or modify nodes.
+  Write,
+  /// Schedule or run a task.
+  Run,
+  /// Export nodes out of the session.
+  Export,
+}
+
+impl fmt::Display for Access
+{
+  fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result
+  {
+    match self
+    {
+      Access::Write => write!(f, "write"),
+      Access::Run => write!(f, "run"),
+      Access::Export => write!(f, "export"),
+    }
+  }
+}
+
+/// User doing the calls of the current thread.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct User
+{
+  pub name : String,
+  /// Roles of the user, interpreted by the [Authorizer].
+  pub roles : Vec<String>,
+}
+
+impl User
+{
+  pub fn new<S : Into<String>>(name : S) -> Self
+  {
+    User{ name : name.into(), roles : Vec::new() }
+  }
+
+  /// Return this user with the role `role` added.
+  pub fn with_role<S : Into<String>>(mut self, role : S) -> Self
+  {
+    self.roles.push(role.into());
+    self
+  }
+
+  /// Return true if the user has the role `role`.
+  pub fn has_role(&self, role : &str) -> bool
+  {
+    self.roles.iter().any(|user_role| user_role == role)
+  }
+}
+
+/**
+ * Policy deciding if a user can access a session, implemented by the embedders.
+ */
+pub trait Authorizer : Send + Sync
+{
+  /// Return true if `user` can perform `access` on the node `node_id` of `tree` and its descendants, or on the whole session if `node_id` is None.
+  fn authorize(&self, user : &User, access : Access, tree : &Tree, node_id : Option<TreeNodeId>) -> bool;
+}
+
+/// [Authorizer] allowing everything.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Permissive;
+
+impl Authorizer for Permissive
+{
+  fn authorize(&self, _user : &User, _access : Access, _tree : &Tree, _node_id : Option<TreeNodeId>) -> bool
+  {
+    true
+  }
+}
+
+thread_local!
+{
+  static CURRENT_USER : RefCell<Option<Arc<User>>> = const { RefCell::new(None) };
+}
+
+/// Restore the previous user of the thread when dropped, returned by [as_user].
+pub struct UserGuard
+{
+  previous : Option<Arc<User>>,
+  //the guard must be dropped on the thread where it was created
+  _thread : PhantomData<*const ()>,
+}
+
+impl Drop for UserGuard
+{
+  fn drop(&mut self)
+  {
+    CURRENT_USER.with(|current| *current.borrow_mut() = self.previous.take());
+  }
+}
+
+/// Set `user` as the user doing the calls of the current thread until the returned guard is dropped.
+#[must_use = "the user is unset when the guard is dropped"]
+pub fn as_user(user : User) -> UserGuard
+{
+  let previous = CURRENT_USER.with(|current| current.borrow_mut().replace(Arc::new(user)));
+  UserGuard{ previous, _thread : PhantomData }
+}
+
+/// Return the user doing the calls of the current thread.
+pub fn current_user() -> Option<Arc<User>>
+{
+  CURRENT_USER.with(|current| current.borrow().clone())
+}
+
+/// Ask `authorizer` if the current user can perform `access` on `node_id` of `tree`, calls without a user are allowed.
+pub(crate) fn check(authorizer : &dyn Authorizer, access : Access, tree : &Tree, node_id : Option<TreeNodeId>) -> anyhow::Result<()>
+{
+  match current_user()
+  {
+    Some(user) if !authorizer.authorize(&user, access, tree, node_id) =>
+    {
+      let target = match node_id
+      {
+        Some(node_id) => tree.node_path(node_id).unwrap_or_default(),
+        None => "session".to_string(),
+      };
+      Err(RustructError::AccessDenied(user.name.clone(), format!("{} {}", access, target)).into())
+    },
+    _ => Ok(()),
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{Access, Authorizer, User, as_user, current_user};
+  use crate::tree::{Tree, TreeNodeId};
+  use crate::node::Node;
+  use crate::session::Session;
+  use crate::export::{delta, ExportFormat};
+  use crate::plugin_dummy;
+
+  use std::sync::Arc;
+
+  /// Analysts can only write in their home node, auditors are read only.
+  struct HomePolicy;
+
+  impl Authorizer for HomePolicy
+  {
+    fn authorize(&self, user : &User, access : Access, tree : &Tree, node_id : Option<TreeNodeId>) -> bool
+    {
+      if user.has_role("auditor")
+      {
+        return access == Access::Export
+      }
+      match node_id
+      {
+        Some(node_id) if access == Access::Write => tree.node_path(node_id).is_some_and(|path| path.starts_with(&format!("/root/{}", user.name))),
+        _ => true,
+      }
+    }
+  }
+
+  #[test]
+  fn authorize_users()
+  {
+    let mut session = Session::new();
+    session.plugins_db.register(Box::new(plugin_dummy::Plugin::new()));
+    session.set_authorizer(Arc::new(HomePolicy));
+    let alice_id = session.tree.add_child(session.tree.root_id, Node::new("alice")).unwrap();
+
+    {
+      let _guard = as_user(User::new("alice"));
+      let case_id = session.tree.add_child(alice_id, Node::new("case")).unwrap();
+      assert!(session.tree.add_child(session.tree.root_id, Node::new("other")).is_err());
+      session.tree.remove(case_id).unwrap();
+      assert!(session.tree.remove(session.tree.root_id).is_err());
+
+      {
+        let _guard = as_user(User::new("bob").with_role("auditor"));
+        assert!(current_user().unwrap().name == "bob");
+        let err = session.tree.add_child(alice_id, Node::new("case")).unwrap_err();
+        assert!(err.to_string() == "User bob is not allowed to write /root/alice");
+        assert!(session.schedule("dummy", "{}".into(), false).is_err());
+        assert!(delta(&session.tree, 0, ExportFormat::Json).is_ok());
+      }
+      assert!(current_user().unwrap().name == "alice");
+      assert!(session.schedule("dummy", "{}".into(), false).is_ok());
+    }
+    assert!(current_user().is_none());
+    assert!(session.tree.add_child(session.tree.root_id, Node::new("other")).is_ok());
+
+    session.clear();
+    let _guard = as_user(User::new("bob").with_role("auditor"));
+    assert!(session.tree.add_child(session.tree.root_id, Node::new("other")).is_err());
+  }
+}
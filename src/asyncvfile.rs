@@ -0,0 +1,263 @@
+//! Asynchronous counterparts of [VFile] and [VFileBuilder] for the [tokio] runtime, available with the `tokio` feature.
+//!
+//! Server frontends can stream the content of a node without dedicating a thread to each download :
+//! [to_async] adapt any [VFileBuilder], the blocking reads of its files are run on the blocking thread pool of tokio
+//! with [spawn_blocking](tokio::task::spawn_blocking) and the async tasks are woken up when they complete.
+
+use std::future::Future;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use crate::vfile::{VFile, VFileBuilder};
+
+use anyhow::Result;
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+use tokio::task::{JoinHandle, spawn_blocking};
+
+/// Maximum size of the data read by each blocking read.
+pub const READ_SIZE : usize = 64 * 1024;
+
+/// A boxed future returned by the [AsyncVFileBuilder] methods.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/**
+ * A trait that implement [AsyncRead] + [AsyncSeek].
+ */
+pub trait AsyncVFile : AsyncRead + AsyncSeek + Send + Sync + Unpin
+{
+}
+
+impl<T : AsyncRead + AsyncSeek + Send + Sync + Unpin> AsyncVFile for T
+{
+}
+
+/**
+ * A trait that generate [AsyncVFile] trait object.
+ */
+pub trait AsyncVFileBuilder : Send + Sync
+{
+  /// Create and return an [AsyncVFile] trait object.
+  fn open(&self) -> BoxFuture<'_, Result<Box<dyn AsyncVFile>>>;
+  /// Return the size of the created [AsyncVFile].
+  fn size(&self) -> u64;
+}
+
+/// Return an [AsyncVFileBuilder] opening and reading `builder` on the blocking thread pool.
+pub fn to_async(builder : Arc<dyn VFileBuilder>) -> Arc<dyn AsyncVFileBuilder>
+{
+  Arc::new(BlockingVFileBuilder{ builder })
+}
+
+/**
+ * [AsyncVFileBuilder] adapting a [VFileBuilder], returned by [to_async].
+ */
+pub struct BlockingVFileBuilder
+{
+  builder : Arc<dyn VFileBuilder>,
+}
+
+impl AsyncVFileBuilder for BlockingVFileBuilder
+{
+  fn open(&self) -> BoxFuture<'_, Result<Box<dyn AsyncVFile>>>
+  {
+    let builder = self.builder.clone();
+    Box::pin(async move
+    {
+      let file = spawn_blocking(move || builder.open()).await??;
+      Ok(Box::new(BlockingVFile::new(file)) as Box<dyn AsyncVFile>)
+    })
+  }
+
+  fn size(&self) -> u64
+  {
+    self.builder.size()
+  }
+}
+
+/// Result of a blocking operation.
+enum Operation
+{
+  Read(io::Result<Vec<u8>>),
+  Seek(io::Result<u64>),
+}
+
+enum State
+{
+  Idle(Option<Box<dyn VFile>>),
+  /// A blocking operation is running, the file is returned with its result.
+  Busy(JoinHandle<(Box<dyn VFile>, Operation)>),
+}
+
+/**
+ * [AsyncVFile] running the reads and seeks of a [VFile] on the blocking thread pool, like [tokio::fs::File] does.
+ * Only one operation run at a time, starting a seek while a read is running return an error.
+ */
+pub struct BlockingVFile
+{
+  state : State,
+  /// Data read and not yet returned.
+  buffer : Vec<u8>,
+  buffer_pos : usize,
+  /// Position of the next byte returned.
+  pos : u64,
+}
+
+impl BlockingVFile
+{
+  pub fn new(file : Box<dyn VFile>) -> Self
+  {
+    BlockingVFile{ state : State::Idle(Some(file)), buffer : Vec::new(), buffer_pos : 0, pos : 0 }
+  }
+
+  /// Wait for the running operation and return its result.
+  fn poll_operation(&mut self, cx : &mut Context<'_>) -> Poll<io::Result<Option<Operation>>>
+  {
+    let handle = match &mut self.state
+    {
+      State::Idle(_) => return Poll::Ready(Ok(None)),
+      State::Busy(handle) => handle,
+    };
+    match Pin::new(handle).poll(cx)
+    {
+      Poll::Pending => Poll::Pending,
+      Poll::Ready(Ok((file, operation))) => { self.state = State::Idle(Some(file)); Poll::Ready(Ok(Some(operation))) },
+      Poll::Ready(Err(err)) => { self.state = State::Idle(None); Poll::Ready(Err(io::Error::other(err))) },
+    }
+  }
+
+  /// Return the idle file, or an error if it was lost by a panicking operation.
+  fn take_file(&mut self) -> io::Result<Box<dyn VFile>>
+  {
+    match &mut self.state
+    {
+      State::Idle(file) => file.take().ok_or_else(|| io::Error::other("the file was lost by a failed operation")),
+      State::Busy(_) => Err(io::Error::other("an other operation is running on the file")),
+    }
+  }
+}
+
+impl AsyncRead for BlockingVFile
+{
+  fn poll_read(self : Pin<&mut Self>, cx : &mut Context<'_>, buf : &mut ReadBuf<'_>) -> Poll<io::Result<()>>
+  {
+    let this = self.get_mut();
+    loop
+    {
+      if this.buffer_pos < this.buffer.len()
+      {
+        let size = buf.remaining().min(this.buffer.len() - this.buffer_pos);
+        buf.put_slice(&this.buffer[this.buffer_pos..this.buffer_pos + size]);
+        this.buffer_pos += size;
+        this.pos += size as u64;
+        return Poll::Ready(Ok(()))
+      }
+
+      match this.poll_operation(cx)
+      {
+        Poll::Pending => return Poll::Pending,
+        Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+        Poll::Ready(Ok(Some(Operation::Read(Ok(data))))) if data.is_empty() => return Poll::Ready(Ok(())),
+        Poll::Ready(Ok(Some(Operation::Read(Ok(data))))) => { this.buffer = data; this.buffer_pos = 0; continue },
+        Poll::Ready(Ok(Some(Operation::Read(Err(err))))) => return Poll::Ready(Err(err)),
+        Poll::Ready(Ok(Some(Operation::Seek(result)))) => { this.pos = result?; continue },
+        Poll::Ready(Ok(None)) => (),
+      }
+
+      if buf.remaining() == 0
+      {
+        return Poll::Ready(Ok(()))
+      }
+      let mut file = this.take_file()?;
+      let size = buf.remaining().min(READ_SIZE);
+      this.state = State::Busy(spawn_blocking(move ||
+      {
+        let mut data = vec![0; size];
+        let result = file.read(&mut data).map(|read| { data.truncate(read); data });
+        (file, Operation::Read(result))
+      }));
+    }
+  }
+}
+
+impl AsyncSeek for BlockingVFile
+{
+  fn start_seek(self : Pin<&mut Self>, position : SeekFrom) -> io::Result<()>
+  {
+    let this = self.get_mut();
+    let mut file = this.take_file()?;
+    //the file is ahead of the position by the buffered data
+    let position = match position
+    {
+      SeekFrom::Current(offset) => SeekFrom::Current(offset - (this.buffer.len() - this.buffer_pos) as i64),
+      position => position,
+    };
+    this.buffer.clear();
+    this.buffer_pos = 0;
+    this.state = State::Busy(spawn_blocking(move ||
+    {
+      let result = file.seek(position);
+      (file, Operation::Seek(result))
+    }));
+    Ok(())
+  }
+
+  fn poll_complete(self : Pin<&mut Self>, cx : &mut Context<'_>) -> Poll<io::Result<u64>>
+  {
+    let this = self.get_mut();
+    match this.poll_operation(cx)
+    {
+      Poll::Pending => Poll::Pending,
+      Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+      Poll::Ready(Ok(Some(Operation::Seek(result)))) => { this.pos = result?; Poll::Ready(Ok(this.pos)) },
+      Poll::Ready(Ok(Some(Operation::Read(result)))) =>
+      {
+        //a read was interrupted, its data is kept for the next read
+        this.buffer = result?;
+        this.buffer_pos = 0;
+        Poll::Ready(Ok(this.pos))
+      },
+      Poll::Ready(Ok(None)) => Poll::Ready(Ok(this.pos)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::to_async;
+  use crate::vfile::VFileBuilder;
+  use crate::patternvfile::RandomVFileBuilder;
+
+  use std::io::{Read, SeekFrom};
+  use std::sync::Arc;
+  use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+  #[test]
+  fn stream_async()
+  {
+    let builder : Arc<dyn VFileBuilder> = Arc::new(RandomVFileBuilder::new(3, 200000));
+    let mut content = Vec::new();
+    builder.open().unwrap().read_to_end(&mut content).unwrap();
+
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    runtime.block_on(async
+    {
+      let builder = to_async(builder);
+      assert!(builder.size() == 200000);
+      let mut file = builder.open().await.unwrap();
+      let mut data = Vec::new();
+      file.read_to_end(&mut data).await.unwrap();
+      assert!(data == content);
+
+      let mut buffer = [0; 100];
+      assert!(file.seek(SeekFrom::Start(1000)).await.unwrap() == 1000);
+      file.read_exact(&mut buffer[..10]).await.unwrap();
+      assert!(file.stream_position().await.unwrap() == 1010);
+      assert!(file.seek(SeekFrom::Current(-5)).await.unwrap() == 1005);
+      file.read_exact(&mut buffer).await.unwrap();
+      assert!(buffer[..] == content[1005..1105]);
+    });
+  }
+}
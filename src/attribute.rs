@@ -4,17 +4,22 @@
 
 use std::fmt;
 use std::borrow::Cow;
-use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::sync::{Arc, Weak, RwLock, RwLockReadGuard};
 
 use crate::value::{Value, ValueTypeId};
+use crate::tree::NodeState;
 
 use serde::{Serialize, Deserialize};
 use serde::ser::{Serializer, SerializeMap};
+use serde::de::{Deserializer, Visitor, MapAccess};
+use schemars::JsonSchema;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{Schema, SchemaObject, InstanceType, ObjectValidation};
 
 /**
  * An Attribute contain a `name`, a `value` and a `description`.
  */
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Attribute
 {
   name : Cow<'static, str>,
@@ -54,6 +59,23 @@ impl Attribute
     self.value.type_id()
   }
 
+  /// Return the approximate memory footprint of this [attribute](Attribute) in bytes, see [Value::deep_size].
+  pub fn deep_size(&self) -> usize
+  {
+    std::mem::size_of::<Attribute>() + self.heap_size()
+  }
+
+  /// Return the heap memory used by the name, description and value of this [attribute](Attribute).
+  fn heap_size(&self) -> usize
+  {
+    let owned = |string : &Cow<'static, str>| match string
+    {
+      Cow::Owned(string) => string.capacity(),
+      Cow::Borrowed(_) => 0,
+    };
+    owned(&self.name) + self.description.as_ref().map(owned).unwrap_or(0) + self.value.heap_size()
+  }
+
   /// Return the `description` of this [attribute](Attribute).
   pub fn description(&self) -> Option<&str>
   {
@@ -74,6 +96,30 @@ impl fmt::Display for Attribute
 }
 
 
+/**
+ * A weak reference to [Attributes] returned by [Attributes::downgrade].
+ */
+#[derive(Default, Clone)]
+pub struct WeakAttributes
+{
+  attributes : Weak<RwLock<Vec<Attribute>>>,
+  observer : Weak<RwLock<Option<AttributesObserver>>>,
+}
+
+impl WeakAttributes
+{
+  /// Return the [Attributes], or `None` if they were dropped.
+  pub fn upgrade(&self) -> Option<Attributes>
+  {
+    let observer = self.observer.upgrade().unwrap_or_default();
+    self.attributes.upgrade().map(|attributes| Attributes{ attributes, observer })
+  }
+}
+
+/// Function called with the name of an [attribute](Attribute) each time it's added, updated or removed,
+/// set by the [Tree](crate::tree::Tree) to track the changes made to the attributes of its nodes.
+pub type AttributesObserver = Arc<dyn Fn(&str, NodeState) + Send + Sync>;
+
 /**
  * [Attributes] is a container for [Attribute].
  */
@@ -81,6 +127,7 @@ impl fmt::Display for Attribute
 pub struct Attributes
 {
   attributes : Arc<RwLock<Vec<Attribute>>>,
+  observer : Arc<RwLock<Option<AttributesObserver>>>,
 }
 
 impl Attributes
@@ -88,7 +135,30 @@ impl Attributes
   /// Return a new [Attributes].
   pub fn new() -> Self
   {
-    Attributes{ attributes : Arc::new(RwLock::new(Vec::new())) }
+    Attributes{ attributes : Arc::new(RwLock::new(Vec::new())), observer : Arc::default() }
+  }
+
+  /// Set the function called each time an [attribute](Attribute) is added, updated or removed.
+  pub(crate) fn set_observer(&self, observer : Option<AttributesObserver>)
+  {
+    *self.observer.write().unwrap() = observer;
+  }
+
+  /// Call the observer, must be called after releasing the attributes lock.
+  fn notify(&self, name : &str, state : NodeState)
+  {
+    let observer = self.observer.read().unwrap().clone();
+    if let Some(observer) = observer
+    {
+      observer(name, state);
+    }
+  }
+
+  /// Return a [WeakAttributes] pointing to these attributes without keeping them alive,
+  /// used by functions stored in the attributes they read to avoid reference cycles.
+  pub fn downgrade(&self) -> WeakAttributes
+  {
+    WeakAttributes{ attributes : Arc::downgrade(&self.attributes), observer : Arc::downgrade(&self.observer) }
   }
 
   /// Return the `name` of all the attribute contained in this [attributes](Attributes).
@@ -101,19 +171,45 @@ impl Attributes
   pub fn add_attribute<S, V : Into<Value>>(&mut self, name : S, value : V, descr : Option<S>)
     where S: Into<Cow<'static, str>>
   {
-    self.attributes.write().unwrap().push(Attribute::new(name, value.into(), descr))
+    let attribute = Attribute::new(name, value.into(), descr);
+    let name = attribute.name.clone();
+    self.attributes.write().unwrap().push(attribute);
+    self.notify(&name, NodeState::Added);
   }
  
   /// Remove an [attribute](Attribute) by `name`.
   pub fn remove_attribute(&mut self, name : &str) -> bool
   {
     let mut attributes = self.attributes.write().unwrap();
-    if let Some(index) = attributes.iter().position(|attribute| attribute.name == name)
+    match attributes.iter().position(|attribute| attribute.name == name)
     {
-      attributes.swap_remove(index);
-      return true
+      Some(index) =>
+      {
+        attributes.swap_remove(index);
+        drop(attributes);
+        self.notify(name, NodeState::Removed);
+        true
+      },
+      None => false,
+    }
+  }
+
+  /// Replace the `value` of the [attribute](Attribute) `name`, keeping its position and description.
+  /// Return false if there is no attribute `name`.
+  pub fn set_value<V : Into<Value>>(&mut self, name : &str, value : V) -> bool
+  {
+    let mut attributes = self.attributes.write().unwrap();
+    match attributes.iter_mut().find(|attribute| attribute.name == name)
+    {
+      Some(attribute) =>
+      {
+        attribute.value = value.into();
+        drop(attributes);
+        self.notify(name, NodeState::Updated);
+        true
+      },
+      None => false,
     }
-    false
   }
 
   /*pub fn replace_attribute<S, V : Into<Value>>(&mut self, name : S, value : V, descr : Option<S>)
@@ -128,9 +224,17 @@ impl Attributes
     where S: Into<Cow<'static, str>>
   {
     let mut attributes = self.attributes.write().unwrap();
+    let mut names = Vec::with_capacity(attr.len());
     for (name, value, descr) in attr
     {
-      attributes.push(Attribute::new(name, value, descr));
+      let attribute = Attribute::new(name, value, descr);
+      names.push(attribute.name.clone());
+      attributes.push(attribute);
+    }
+    drop(attributes);
+    for name in names
+    {
+      self.notify(&name, NodeState::Added);
     }
   }
 
@@ -167,6 +271,17 @@ impl Attributes
   }*/
 
 
+  /// Return the approximate memory footprint of these [attributes](Attributes) in bytes, see [Value::deep_size].
+  pub fn deep_size(&self) -> usize
+  {
+    use std::mem::size_of;
+
+    let attributes = self.attributes.read().unwrap();
+    //the Arc allocation contain the strong and weak counters
+    size_of::<Attributes>() + 2 * size_of::<usize>() + size_of::<RwLock<Vec<Attribute>>>() +
+      attributes.capacity() * size_of::<Attribute>() + attributes.iter().map(|attribute| attribute.heap_size()).sum::<usize>()
+  }
+
   /// Return an iterator to the contained [Attributes](Attribute).
   pub fn attributes(&self) -> LockedAttributes<'_>
   {
@@ -206,6 +321,56 @@ impl Serialize for Attributes
   }
 }
 
+struct AttributesVisitor;
+
+impl<'de> Visitor<'de> for AttributesVisitor
+{
+  type Value = Attributes;
+
+  fn expecting(&self, formatter : &mut fmt::Formatter) -> fmt::Result
+  {
+    formatter.write_str("a map of attribute name and value")
+  }
+
+  fn visit_map<M>(self, mut access : M) -> Result<Self::Value, M::Error>
+    where M: MapAccess<'de>,
+  {
+    let mut attributes = Vec::with_capacity(access.size_hint().unwrap_or(0));
+
+    while let Some((name, value)) = access.next_entry::<String, Value>()?
+    {
+      attributes.push(Attribute::new(name, value, None));
+    }
+    Ok(Attributes{ attributes : Arc::new(RwLock::new(attributes)), observer : Arc::default() })
+  }
+}
+
+/// [Attributes] are deserialized from a map of attribute name and [Value], descriptions are not restored.
+impl<'de> Deserialize<'de> for Attributes
+{
+  fn deserialize<D>(deserializer : D) -> Result<Self, D::Error>
+    where D: Deserializer<'de>,
+  {
+    deserializer.deserialize_map(AttributesVisitor)
+  }
+}
+
+/// [Attributes] are serialized as an object of attribute name and [Value].
+impl JsonSchema for Attributes
+{
+  fn schema_name() -> String
+  {
+    "Attributes".into()
+  }
+
+  fn json_schema(gen : &mut SchemaGenerator) -> Schema
+  {
+    SchemaObject{ instance_type : Some(InstanceType::Object.into()),
+                  object : Some(Box::new(ObjectValidation{ additional_properties : Some(Box::new(gen.subschema_for::<Value>())), ..Default::default() })),
+                  ..Default::default() }.into()
+  }
+}
+
 impl fmt::Debug for Attributes 
 {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result 
@@ -279,4 +444,13 @@ mod tests
       assert!(vec[0].as_u32() == 0);
       assert!(vec[1].as_string() == "test");
     }
+
+    #[test]
+    fn deserialize_attributes()
+    {
+      let attributes : Attributes = serde_json::from_str(r#"{"size" : 4096, "name" : "file"}"#).unwrap();
+      assert!(attributes.count() == 2);
+      assert!(attributes.get_value("size").unwrap().to_u64() == Some(4096));
+      assert!(attributes.get_value("name").unwrap().as_string() == "file");
+    }
 }
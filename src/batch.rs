@@ -0,0 +1,152 @@
+//! Application of a plugin to a set of nodes : [Session::run_on](crate::session::Session::run_on) schedule a task for each node,
+//! injecting the node in the base argument, and return a [Batch] to wait for these tasks and collect their results.
+//!
+//! The node is injected in the `file` field of the plugins parsing the data of a node, as an [AttributePath] to its data attribute,
+//! or in the `parent` field of the plugins creating nodes under a parent.
+
+use crate::tree::{TreeNodeId, AttributePath};
+use crate::task_scheduler::{TaskScheduler, TaskGroup, TaskId, TaskResult, GroupProgress};
+use crate::plugin::{PluginInfo, Dependency};
+use crate::error::RustructError;
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+/// Field of a plugin argument where the node of each task is injected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target
+{
+  /// `file` field, set to the attribute of this name of the node.
+  File(String),
+  /// `parent` field, set to the node id.
+  Parent,
+}
+
+impl Target
+{
+  /// Return the target of `plugin_info` from the properties of its argument schema,
+  /// or a [NoTargetArgument](RustructError::NoTargetArgument) error if it has no `file` nor `parent` field.
+  pub fn of(plugin_info : &dyn PluginInfo) -> Result<Self>
+  {
+    let schema : Value = serde_json::from_str(&plugin_info.config()?)?;
+    let has_property = |name : &str| schema["properties"].get(name).is_some();
+    if has_property("file")
+    {
+      let data = plugin_info.dependencies().into_iter().find_map(|dependency| match dependency
+      {
+        Dependency::Data(name) => Some(name),
+        _ => None,
+      }).unwrap_or("data");
+      return Ok(Target::File(data.into()))
+    }
+    if has_property("parent")
+    {
+      return Ok(Target::Parent)
+    }
+    Err(RustructError::NoTargetArgument(plugin_info.name().into()).into())
+  }
+
+  /// Return `base` with the target field set to `node_id`.
+  pub fn argument(&self, base : &Map<String, Value>, node_id : TreeNodeId) -> Result<String>
+  {
+    let mut argument = base.clone();
+    match self
+    {
+      Target::File(attribute_name) =>
+        argument.insert("file".into(), serde_json::to_value(AttributePath{ node_id, attribute_name : attribute_name.clone() })?),
+      Target::Parent => argument.insert("parent".into(), serde_json::to_value(node_id)?),
+    };
+    Ok(Value::Object(argument).to_string())
+  }
+}
+
+/// Tasks scheduled by [Session::run_on](crate::session::Session::run_on).
+#[derive(Debug, Clone, Default)]
+pub struct Batch
+{
+  pub plugin : String,
+  /// Node and task applying the plugin to it.
+  pub tasks : Vec<(TreeNodeId, TaskId)>,
+  /// Group of the tasks.
+  pub group : TaskGroup,
+  /// Nodes for which no task was scheduled and the reason, like a task already run or a node not found.
+  pub skipped : Vec<(TreeNodeId, String)>,
+}
+
+impl Batch
+{
+  pub fn task_ids(&self) -> Vec<TaskId>
+  {
+    self.tasks.iter().map(|(_, task_id)| *task_id).collect()
+  }
+
+  /// Block until all the tasks of the batch are finished, the tasks scheduled by others are not waited.
+  pub fn join(&self, task_scheduler : &TaskScheduler)
+  {
+    self.group.join(task_scheduler)
+  }
+
+  /// Cancel the unfinished tasks of the batch, return the number of tasks cancelled.
+  pub fn cancel(&self, task_scheduler : &TaskScheduler) -> usize
+  {
+    self.group.cancel(task_scheduler)
+  }
+
+  pub fn progress(&self, task_scheduler : &TaskScheduler) -> GroupProgress
+  {
+    self.group.progress(task_scheduler)
+  }
+
+  /// Return the result of the task of each node, or a [TaskNotFinished](RustructError::TaskNotFinished) error for the tasks still running.
+  pub fn results(&self, task_scheduler : &TaskScheduler) -> Vec<(TreeNodeId, TaskResult)>
+  {
+    self.tasks.iter().map(|(node_id, task_id)| (*node_id, task_scheduler.result(*task_id))).collect()
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use crate::session::Session;
+  use crate::node::Node;
+  use crate::value::Value;
+  use crate::memoryvfile::MemoryVFileBuilder;
+  use crate::{plugin_dummy, plugin_fat, plugin_report};
+
+  use serde_json::json;
+
+  #[test]
+  fn run_on_nodes()
+  {
+    let mut session = Session::new();
+    session.plugins_db.register(Box::new(plugin_dummy::Plugin::new()));
+    session.plugins_db.register(Box::new(plugin_fat::Plugin::new()));
+    session.plugins_db.register(Box::new(plugin_report::Plugin::new()));
+
+    let mut volumes = Vec::new();
+    for name in ["a", "b", "c"]
+    {
+      let node = Node::new(name);
+      node.value().add_attribute("data", Value::VFileBuilder(MemoryVFileBuilder::from_buffer(plugin_fat::tests::fat12_image())), None);
+      volumes.push(session.tree.add_child(session.tree.root_id, node).unwrap());
+    }
+
+    let batch = session.run_on(volumes.clone(), "fat", "{}".into()).unwrap();
+    assert!(batch.plugin == "fat" && batch.tasks.len() == 3 && batch.skipped.is_empty());
+    batch.join(&session.task_scheduler);
+    assert!(batch.progress(&session.task_scheduler).succeeded == 3);
+    assert!(batch.results(&session.task_scheduler).iter().all(|(_, result)| result.is_ok()));
+    assert!(session.tree.get_node("/root/b/README.TXT").is_some());
+
+    //tasks already run are skipped
+    let batch = session.run_on(volumes.clone(), "fat", "{}".into()).unwrap();
+    assert!(batch.tasks.is_empty() && batch.skipped.len() == 3);
+
+    let batch = session.run_on(volumes[..2].to_vec(), "dummy", json!({ "file_name" : "/dummy", "offset" : 0 }).to_string()).unwrap();
+    batch.join(&session.task_scheduler);
+    let results = batch.results(&session.task_scheduler);
+    assert!(results.len() == 2 && results[1].0 == volumes[1] && results[1].1.is_ok());
+
+    assert!(session.run_on(volumes, "report", "{}".into()).is_err());
+  }
+}
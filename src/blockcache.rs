@@ -0,0 +1,303 @@
+//! A size-bounded LRU cache of the blocks read from [VFileBuilder], shared by a session so the data read
+//! by a plugin is not read again from the disk by the next one. Blocks are keyed by the builder identity and their index.
+//!
+//! [BlockCache::open] return a [VFile] reading a builder through the cache, and other [VFile] implementations
+//! can read their blocks with [BlockCache::block]. Hit and miss statistics are returned by [BlockCache::stats] for tuning.
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::{Arc, Weak, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::vfile::{VFile, VFileBuilder, builder_key};
+
+use lru::LruCache;
+use serde::{Serialize, Deserialize};
+
+/// Default size of the cached blocks.
+pub const BLOCK_SIZE : usize = 64 * 1024;
+
+/// Default maximum size of the cached data.
+pub const CACHE_CAPACITY : u64 = 64 * 1024 * 1024;
+
+/// Statistics of a [BlockCache].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CacheStats
+{
+  /// Number of blocks found in the cache.
+  pub hits : u64,
+  /// Number of blocks read from their builder.
+  pub misses : u64,
+  /// Number of blocks in the cache.
+  pub blocks : usize,
+  /// Size of the data in the cache.
+  pub bytes : u64,
+  /// Maximum size of the data in the cache.
+  pub capacity : u64,
+  /// Size of the blocks.
+  pub block_size : usize,
+}
+
+impl CacheStats
+{
+  /// Return the ratio of blocks found in the cache, or 0 if no block was read.
+  pub fn hit_rate(&self) -> f64
+  {
+    match self.hits + self.misses
+    {
+      0 => 0.0,
+      total => self.hits as f64 / total as f64,
+    }
+  }
+}
+
+/// Builder address, block size and block index.
+type BlockKey = (usize, usize, u64);
+
+struct Entry
+{
+  /// Keep the builder allocation alive, so its address is not reused by an other builder while the block is cached.
+  _builder : Weak<dyn VFileBuilder>,
+  data : Arc<[u8]>,
+}
+
+struct Blocks
+{
+  block_size : usize,
+  capacity : u64,
+  bytes : u64,
+  lru : LruCache<BlockKey, Entry>,
+}
+
+impl Blocks
+{
+  /// Remove the least recently used blocks until the cached data fit in the capacity.
+  fn evict(&mut self)
+  {
+    while self.bytes > self.capacity
+    {
+      match self.lru.pop_lru()
+      {
+        Some((_, entry)) => self.bytes -= entry.data.len() as u64,
+        None => break,
+      }
+    }
+  }
+}
+
+/**
+ * Cache of the blocks read from [VFileBuilder], evicting the least recently used blocks when the capacity is reached.
+ */
+pub struct BlockCache
+{
+  blocks : Mutex<Blocks>,
+  hits : AtomicU64,
+  misses : AtomicU64,
+}
+
+impl Default for BlockCache
+{
+  fn default() -> Self
+  {
+    BlockCache::new(BLOCK_SIZE, CACHE_CAPACITY)
+  }
+}
+
+impl BlockCache
+{
+  /// Return a cache of blocks of `block_size` bytes, keeping at most `capacity` bytes. A `capacity` of 0 disable the cache.
+  pub fn new(block_size : usize, capacity : u64) -> Self
+  {
+    let blocks = Blocks{ block_size : block_size.max(1), capacity, bytes : 0, lru : LruCache::unbounded() };
+    BlockCache{ blocks : Mutex::new(blocks), hits : AtomicU64::new(0), misses : AtomicU64::new(0) }
+  }
+
+  /// Set the maximum size of the cached data, evicting blocks if needed.
+  pub fn set_capacity(&self, capacity : u64)
+  {
+    let mut blocks = self.blocks.lock().unwrap();
+    blocks.capacity = capacity;
+    blocks.evict();
+  }
+
+  /// Return the maximum size of the cached data.
+  pub fn capacity(&self) -> u64
+  {
+    self.blocks.lock().unwrap().capacity
+  }
+
+  /// Set the size of the blocks read after this call, the cache is cleared.
+  pub fn set_block_size(&self, block_size : usize)
+  {
+    let mut blocks = self.blocks.lock().unwrap();
+    blocks.block_size = block_size.max(1);
+    blocks.lru.clear();
+    blocks.bytes = 0;
+  }
+
+  /// Return the size of the blocks.
+  pub fn block_size(&self) -> usize
+  {
+    self.blocks.lock().unwrap().block_size
+  }
+
+  /// Remove all the blocks from the cache, the statistics are kept.
+  pub fn clear(&self)
+  {
+    let mut blocks = self.blocks.lock().unwrap();
+    blocks.lru.clear();
+    blocks.bytes = 0;
+  }
+
+  /// Return the statistics of the cache.
+  pub fn stats(&self) -> CacheStats
+  {
+    let blocks = self.blocks.lock().unwrap();
+    CacheStats{ hits : self.hits.load(Ordering::Relaxed), misses : self.misses.load(Ordering::Relaxed), blocks : blocks.lru.len(),
+                bytes : blocks.bytes, capacity : blocks.capacity, block_size : blocks.block_size }
+  }
+
+  /// Return the block of `builder` containing `offset` and the offset of the block,
+  /// reading it with `file`, a file opened from `builder`, if it's not in the cache.
+  /// The block is shorter than the block size at the end of the builder, and empty past its end.
+  pub fn block(&self, builder : &Arc<dyn VFileBuilder>, file : &mut dyn VFile, offset : u64) -> io::Result<(u64, Arc<[u8]>)>
+  {
+    let block_size = self.block_size();
+    let index = offset / block_size as u64;
+    let start = index * block_size as u64;
+    let key = (builder_key(builder), block_size, index);
+
+    if let Some(entry) = self.blocks.lock().unwrap().lru.get(&key)
+    {
+      self.hits.fetch_add(1, Ordering::Relaxed);
+      return Ok((start, entry.data.clone()))
+    }
+    self.misses.fetch_add(1, Ordering::Relaxed);
+
+    let size = builder.size().saturating_sub(start).min(block_size as u64);
+    let mut data = Vec::with_capacity(size as usize);
+    file.seek(SeekFrom::Start(start))?;
+    file.take(size).read_to_end(&mut data)?;
+    let data : Arc<[u8]> = data.into();
+
+    let mut blocks = self.blocks.lock().unwrap();
+    if blocks.capacity >= data.len() as u64
+    {
+      blocks.bytes += data.len() as u64;
+      if let Some(replaced) = blocks.lru.put(key, Entry{ _builder : Arc::downgrade(builder), data : data.clone() })
+      {
+        blocks.bytes -= replaced.data.len() as u64;
+      }
+      blocks.evict();
+    }
+    Ok((start, data))
+  }
+
+  /// Return a [VFile] reading `builder` through this cache.
+  pub fn open(self : &Arc<Self>, builder : Arc<dyn VFileBuilder>) -> anyhow::Result<Box<dyn VFile>>
+  {
+    let file = builder.open()?;
+    Ok(Box::new(CachedVFile{ cache : self.clone(), size : builder.size(), builder, file, pos : 0 }))
+  }
+}
+
+/**
+ * [VFile] reading a builder by blocks through a [BlockCache], returned by [BlockCache::open].
+ */
+pub struct CachedVFile
+{
+  cache : Arc<BlockCache>,
+  builder : Arc<dyn VFileBuilder>,
+  file : Box<dyn VFile>,
+  size : u64,
+  pos : u64,
+}
+
+impl Read for CachedVFile
+{
+  fn read(&mut self, buf : &mut [u8]) -> io::Result<usize>
+  {
+    if self.pos >= self.size || buf.is_empty()
+    {
+      return Ok(0)
+    }
+
+    let (start, block) = self.cache.block(&self.builder, self.file.as_mut(), self.pos)?;
+    let offset = (self.pos - start) as usize;
+    if offset >= block.len()
+    {
+      return Ok(0)
+    }
+    let size = buf.len().min(block.len() - offset);
+    buf[..size].copy_from_slice(&block[offset..offset + size]);
+    self.pos += size as u64;
+    Ok(size)
+  }
+}
+
+impl Seek for CachedVFile
+{
+  fn seek(&mut self, pos : SeekFrom) -> io::Result<u64>
+  {
+    let pos = match pos
+    {
+      SeekFrom::Start(pos) => Some(pos),
+      SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+      SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+    };
+
+    match pos
+    {
+      Some(pos) => { self.pos = pos; Ok(pos) },
+      None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::BlockCache;
+  use crate::vfile::VFileBuilder;
+  use crate::memoryvfile::MemoryVFileBuilder;
+
+  use std::io::{Read, Seek, SeekFrom};
+  use std::sync::Arc;
+
+  #[test]
+  fn cache_blocks()
+  {
+    let content : Vec<u8> = (0..100u8).collect();
+    let builder : Arc<dyn VFileBuilder> = MemoryVFileBuilder::from_buffer(content.clone());
+    let cache = Arc::new(BlockCache::new(16, 64));
+
+    let mut file = cache.open(builder.clone()).unwrap();
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).unwrap();
+    assert!(data == content);
+    let stats = cache.stats();
+    assert!(stats.misses == 7 && stats.hits == 0);
+    assert!(stats.blocks == 4 && stats.bytes == 52);
+
+    let mut buffer = [0; 8];
+    let mut file = cache.open(builder.clone()).unwrap();
+    file.seek(SeekFrom::Start(90)).unwrap();
+    file.read_exact(&mut buffer[..4]).unwrap();
+    file.seek(SeekFrom::Start(0)).unwrap();
+    file.read_exact(&mut buffer).unwrap();
+    assert!(buffer == content[..8]);
+    let stats = cache.stats();
+    assert!(stats.hits == 1 && stats.misses == 8 && stats.hit_rate() > 0.1);
+
+    let other : Arc<dyn VFileBuilder> = MemoryVFileBuilder::from_buffer(vec![0xff; 16]);
+    cache.open(other).unwrap().read_exact(&mut buffer).unwrap();
+    assert!(buffer == [0xff; 8]);
+
+    cache.set_capacity(16);
+    assert!(cache.stats().bytes <= 16);
+    cache.set_block_size(32);
+    assert!(cache.stats().blocks == 0);
+    cache.set_capacity(0);
+    cache.open(builder).unwrap().read_exact(&mut buffer).unwrap();
+    assert!(cache.stats().blocks == 0 && buffer == content[..8]);
+  }
+}
@@ -0,0 +1,224 @@
+//! A [VFileBuilder] wrapper reading ahead an other [VFileBuilder] on a background thread,
+//! so sequential consumers like hashing or carving plugins are not stalled waiting for I/O.
+//!
+//! The next windows are read while the current one is consumed, a seek outside of the prefetched data restarts the read-ahead.
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Arc;
+use std::thread;
+
+use crate::vfile::{VFile, VFileBuilder, Extent};
+
+use crossbeam::crossbeam_channel::{bounded, Receiver};
+use serde::{Serialize, Deserialize};
+
+/// Number of windows read in advance.
+const WINDOWS_AHEAD : usize = 2;
+
+/**
+ * Implement a [VFileBuilder] generating files reading `inner` by windows of `window` bytes on a background thread.
+ */
+#[derive(Serialize, Deserialize)]
+pub struct BufferedVFileBuilder
+{
+  inner : Arc<dyn VFileBuilder>,
+  window : usize,
+}
+
+impl BufferedVFileBuilder
+{
+  /// Return a builder reading `inner` ahead by windows of `window` bytes.
+  pub fn new(inner : Arc<dyn VFileBuilder>, window : usize) -> Arc<BufferedVFileBuilder>
+  {
+    Arc::new(BufferedVFileBuilder{ inner, window : window.max(1) })
+  }
+
+  /// Return the builder read ahead.
+  pub fn inner(&self) -> &Arc<dyn VFileBuilder>
+  {
+    &self.inner
+  }
+
+  /// Return the size of the windows read ahead.
+  pub fn window(&self) -> usize
+  {
+    self.window
+  }
+}
+
+#[typetag::serde]
+impl VFileBuilder for BufferedVFileBuilder
+{
+  fn open(&self) -> anyhow::Result<Box<dyn VFile>>
+  {
+    Ok(Box::new(BufferedVFile{ inner : self.inner.clone(), window : self.window, size : self.inner.size(), pos : 0,
+                               buffer : Vec::new(), buffer_start : 0, prefetch : None }))
+  }
+
+  fn size(&self) -> u64
+  {
+    self.inner.size()
+  }
+
+  fn extents(&self) -> Vec<Extent>
+  {
+    self.inner.extents()
+  }
+}
+
+/// Windows read by the background thread and the offset of the next one.
+struct Prefetch
+{
+  receiver : Receiver<io::Result<Vec<u8>>>,
+  next : u64,
+}
+
+/// Read `file` from `start` by windows of `window` bytes and send them until the end of the file or until the receiver is dropped.
+fn read_ahead(file : Box<dyn VFile>, start : u64, window : usize) -> Prefetch
+{
+  let (sender, receiver) = bounded(WINDOWS_AHEAD);
+  thread::spawn(move ||
+  {
+    let mut file = file;
+    if let Err(err) = file.seek(SeekFrom::Start(start))
+    {
+      let _ = sender.send(Err(err));
+      return
+    }
+    loop
+    {
+      let mut data = Vec::with_capacity(window);
+      let result = (&mut file).take(window as u64).read_to_end(&mut data);
+      let end = matches!(result, Ok(0) | Err(_));
+      if sender.send(result.map(|_| data)).is_err() || end
+      {
+        break
+      }
+    }
+  });
+  Prefetch{ receiver, next : start }
+}
+
+/**
+ * [VFile] reading ahead the inner file of a [BufferedVFileBuilder].
+ */
+pub struct BufferedVFile
+{
+  inner : Arc<dyn VFileBuilder>,
+  window : usize,
+  size : u64,
+  pos : u64,
+  buffer : Vec<u8>,
+  buffer_start : u64,
+  prefetch : Option<Prefetch>,
+}
+
+impl Read for BufferedVFile
+{
+  fn read(&mut self, buf : &mut [u8]) -> io::Result<usize>
+  {
+    if self.pos >= self.size || buf.is_empty()
+    {
+      return Ok(0)
+    }
+
+    if self.pos < self.buffer_start || self.pos >= self.buffer_start + self.buffer.len() as u64
+    {
+      let prefetch = match self.prefetch.take()
+      {
+        Some(prefetch) if prefetch.next <= self.pos && self.pos < prefetch.next + (self.window * WINDOWS_AHEAD) as u64 => prefetch,
+        //dropping the previous receiver stop its thread
+        _ => read_ahead(self.inner.open().map_err(|err| io::Error::other(err.to_string()))?, self.pos, self.window),
+      };
+      let prefetch = self.prefetch.insert(prefetch);
+
+      loop
+      {
+        let data = prefetch.receiver.recv().map_err(|_| io::Error::new(io::ErrorKind::UnexpectedEof, "read-ahead thread stopped"))??;
+        self.buffer_start = prefetch.next;
+        prefetch.next += data.len() as u64;
+        self.buffer = data;
+        if self.buffer.is_empty()
+        {
+          self.prefetch = None;
+          return Ok(0)
+        }
+        if self.pos < prefetch.next
+        {
+          break
+        }
+      }
+    }
+
+    let offset = (self.pos - self.buffer_start) as usize;
+    let size = buf.len().min(self.buffer.len() - offset);
+    buf[..size].copy_from_slice(&self.buffer[offset..offset + size]);
+    self.pos += size as u64;
+    Ok(size)
+  }
+}
+
+impl Seek for BufferedVFile
+{
+  fn seek(&mut self, pos : SeekFrom) -> io::Result<u64>
+  {
+    let pos = match pos
+    {
+      SeekFrom::Start(pos) => Some(pos),
+      SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+      SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+    };
+
+    match pos
+    {
+      Some(pos) => { self.pos = pos; Ok(pos) },
+      None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::BufferedVFileBuilder;
+  use crate::vfile::VFileBuilder;
+  use crate::memoryvfile::MemoryVFileBuilder;
+  use crate::tempvfile::TempVFileBuilder;
+  use crate::fsvfile::FsVFileBuilder;
+
+  use std::io::{Read, Seek, SeekFrom};
+  use std::sync::Arc;
+
+  #[test]
+  fn read_ahead()
+  {
+    let content : Vec<u8> = (0..100000u32).map(|i| (i % 253) as u8).collect();
+    let builder = BufferedVFileBuilder::new(MemoryVFileBuilder::from_buffer(content.clone()), 4096);
+    assert!(builder.size() == content.len() as u64);
+
+    let mut file = builder.open().unwrap();
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).unwrap();
+    assert!(data == content);
+
+    let mut buffer = [0; 100];
+    file.seek(SeekFrom::Start(50000)).unwrap();
+    file.read_exact(&mut buffer).unwrap();
+    assert!(buffer[..] == content[50000..50100]);
+    file.seek(SeekFrom::Current(5000)).unwrap();
+    file.read_exact(&mut buffer).unwrap();
+    assert!(buffer[..] == content[55100..55200]);
+    file.seek(SeekFrom::Start(10)).unwrap();
+    file.read_exact(&mut buffer).unwrap();
+    assert!(buffer[..] == content[10..110]);
+    file.seek(SeekFrom::End(-10)).unwrap();
+    assert!(file.read(&mut buffer).unwrap() == 10 && file.read(&mut buffer).unwrap() == 0);
+
+    let temp = TempVFileBuilder::new(&content).unwrap();
+    let builder : Arc<dyn VFileBuilder> = BufferedVFileBuilder::new(FsVFileBuilder::new(temp.path()).unwrap(), 1000);
+    let builder : Box<dyn VFileBuilder> = serde_json::from_str(&serde_json::to_string(&builder).unwrap()).unwrap();
+    let mut data = Vec::new();
+    builder.open().unwrap().read_to_end(&mut data).unwrap();
+    assert!(data == content);
+  }
+}
@@ -0,0 +1,92 @@
+//! Cooperative cancellation of the tasks : [TaskScheduler::cancel](crate::task_scheduler::TaskScheduler::cancel) cancel the
+//! [CancellationToken] passed to the plugin in its [PluginEnvironment](crate::plugin::PluginEnvironment),
+//! the plugin check it between two steps of its work or register a callback to interrupt a blocking operation.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::RustructError;
+
+use anyhow::Result;
+
+type Callback = Box<dyn FnOnce() + Send>;
+
+struct Inner
+{
+  cancelled : AtomicBool,
+  callbacks : Mutex<Vec<Callback>>,
+}
+
+/**
+ * A flag shared between a task and the code that may cancel it, clones share the same flag.
+ */
+#[derive(Clone)]
+pub struct CancellationToken
+{
+  inner : Arc<Inner>,
+}
+
+impl Default for CancellationToken
+{
+  fn default() -> Self
+  {
+    CancellationToken{ inner : Arc::new(Inner{ cancelled : AtomicBool::new(false), callbacks : Mutex::new(Vec::new()) }) }
+  }
+}
+
+impl fmt::Debug for CancellationToken
+{
+  fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result
+  {
+    f.debug_struct("CancellationToken").field("cancelled", &self.is_cancelled()).finish()
+  }
+}
+
+impl CancellationToken
+{
+  pub fn new() -> Self
+  {
+    CancellationToken::default()
+  }
+
+  /// Cancel the token and call the registered callbacks, return false if it was already cancelled.
+  pub fn cancel(&self) -> bool
+  {
+    if self.inner.cancelled.swap(true, Ordering::SeqCst)
+    {
+      return false
+    }
+    let callbacks = std::mem::take(&mut *self.inner.callbacks.lock().unwrap());
+    callbacks.into_iter().for_each(|callback| callback());
+    true
+  }
+
+  /// Return true if the token was cancelled.
+  pub fn is_cancelled(&self) -> bool
+  {
+    self.inner.cancelled.load(Ordering::SeqCst)
+  }
+
+  /// Return a [Cancelled](RustructError::Cancelled) error if the token was cancelled, to stop a plugin with `?`.
+  pub fn check(&self) -> Result<()>
+  {
+    match self.is_cancelled()
+    {
+      true => Err(RustructError::Cancelled.into()),
+      false => Ok(()),
+    }
+  }
+
+  /// Call `callback` when the token is cancelled, or now if it's already cancelled.
+  pub fn on_cancel<F : FnOnce() + Send + 'static>(&self, callback : F)
+  {
+    let mut callbacks = self.inner.callbacks.lock().unwrap();
+    //checked with the callbacks locked so a concurrent cancel either see the callback or is seen here
+    match self.is_cancelled()
+    {
+      true => { drop(callbacks); callback() },
+      false => callbacks.push(Box::new(callback)),
+    }
+  }
+}
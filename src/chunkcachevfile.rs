@@ -0,0 +1,350 @@
+//! A [VFileBuilder] caching the chunks read from a slow parent builder (network share, HTTP, decrypted volume) in a file on disk,
+//! for parents too big to be copied in memory like a [MemoryVFileBuilder](crate::memoryvfile::MemoryVFileBuilder) does.
+//!
+//! Chunks are read from the parent the first time they're accessed and written to a cache file created in the system temporary
+//! directory or in a chosen cache directory. The cache file never grows past the capacity of the builder :
+//! when it's full the least recently used chunk is evicted and its place reused. The cache file is removed when the builder is dropped.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::vfile::{VFile, VFileBuilder, Extent};
+
+use lru::LruCache;
+use serde::{Serialize, Deserialize};
+
+/// Default size of the cached chunks.
+pub const CHUNK_SIZE : usize = 1024 * 1024;
+
+/// Statistics of a [ChunkedCacheVFileBuilder].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkCacheStats
+{
+  /// Number of chunks read from the cache file.
+  pub hits : u64,
+  /// Number of chunks read from the parent.
+  pub misses : u64,
+  /// Number of chunks removed to make room for other chunks.
+  pub evictions : u64,
+  /// Number of chunks in the cache.
+  pub chunks : usize,
+  /// Size of the chunks in the cache.
+  pub bytes : u64,
+}
+
+/// Cache file and the place of the chunks in it.
+struct ChunkStore
+{
+  dir : PathBuf,
+  /// Cache file and its path, created on the first chunk cached.
+  file : Option<(File, PathBuf)>,
+  /// Slot and size of the cached chunks by chunk index, by order of use.
+  chunks : LruCache<u64, (u64, usize)>,
+  /// Slots of the evicted chunks.
+  free : Vec<u64>,
+  /// Number of slots used in the cache file.
+  slots : u64,
+  max_slots : u64,
+  stats : ChunkCacheStats,
+}
+
+impl ChunkStore
+{
+  fn file(&mut self) -> io::Result<&mut File>
+  {
+    if self.file.is_none()
+    {
+      fs::create_dir_all(&self.dir)?;
+      let path = self.dir.join(format!("tap-cache-{}", uuid::Uuid::new_v4()));
+      let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+      self.file = Some((file, path));
+    }
+    Ok(&mut self.file.as_mut().unwrap().0)
+  }
+
+  /// Return the chunk `index` read from the cache file, `chunk_size` is the size of the slots.
+  fn get(&mut self, index : u64, chunk_size : usize) -> io::Result<Option<Vec<u8>>>
+  {
+    let (slot, size) = match self.chunks.get(&index)
+    {
+      Some(place) => *place,
+      None => return Ok(None),
+    };
+    let mut data = vec![0; size];
+    let file = self.file()?;
+    file.seek(SeekFrom::Start(slot * chunk_size as u64))?;
+    file.read_exact(&mut data)?;
+    self.stats.hits += 1;
+    Ok(Some(data))
+  }
+
+  /// Write the chunk `index` to the cache file, evicting the least recently used chunk if the cache is full.
+  fn put(&mut self, index : u64, data : &[u8], chunk_size : usize) -> io::Result<()>
+  {
+    if self.chunks.contains(&index)
+    {
+      return Ok(())
+    }
+
+    let slot = match self.free.pop()
+    {
+      Some(slot) => slot,
+      None if self.slots < self.max_slots => { self.slots += 1; self.slots - 1 },
+      None => match self.chunks.pop_lru()
+      {
+        Some((_, (slot, size))) =>
+        {
+          self.stats.evictions += 1;
+          self.stats.chunks -= 1;
+          self.stats.bytes -= size as u64;
+          slot
+        },
+        None => return Ok(()),
+      },
+    };
+
+    let file = self.file()?;
+    let written = file.seek(SeekFrom::Start(slot * chunk_size as u64)).and_then(|_| file.write_all(data));
+    if let Err(err) = written
+    {
+      self.free.push(slot);
+      return Err(err)
+    }
+    self.chunks.put(index, (slot, data.len()));
+    self.stats.chunks += 1;
+    self.stats.bytes += data.len() as u64;
+    Ok(())
+  }
+}
+
+impl Drop for ChunkStore
+{
+  fn drop(&mut self)
+  {
+    if let Some((_, path)) = self.file.take()
+    {
+      let _ = fs::remove_file(path);
+    }
+  }
+}
+
+/**
+ * Implement a [VFileBuilder] reading `parent` through a cache of chunks of `chunk_size` bytes on disk, keeping at most `capacity` bytes.
+ * The cache is shared by all the files opened from this builder, it's not serialized.
+ */
+#[derive(Serialize)]
+pub struct ChunkedCacheVFileBuilder
+{
+  parent : Arc<dyn VFileBuilder>,
+  chunk_size : usize,
+  capacity : u64,
+  dir : Option<PathBuf>,
+  #[serde(skip)]
+  store : Arc<Mutex<ChunkStore>>,
+}
+
+impl ChunkedCacheVFileBuilder
+{
+  /// Return a builder caching up to `capacity` bytes of `parent` by chunks of [CHUNK_SIZE] in the system temporary directory.
+  pub fn new(parent : Arc<dyn VFileBuilder>, capacity : u64) -> Arc<ChunkedCacheVFileBuilder>
+  {
+    Arc::new(ChunkedCacheVFileBuilder::with_options(parent, CHUNK_SIZE, capacity, None))
+  }
+
+  /// Return a builder caching up to `capacity` bytes of `parent` by chunks of `chunk_size`, in `dir` or in the system temporary directory.
+  pub fn with_options(parent : Arc<dyn VFileBuilder>, chunk_size : usize, capacity : u64, dir : Option<PathBuf>) -> ChunkedCacheVFileBuilder
+  {
+    let chunk_size = chunk_size.max(1);
+    let store = ChunkStore{ dir : dir.clone().unwrap_or_else(std::env::temp_dir), file : None, chunks : LruCache::unbounded(),
+                            free : Vec::new(), slots : 0, max_slots : capacity / chunk_size as u64, stats : ChunkCacheStats::default() };
+    ChunkedCacheVFileBuilder{ parent, chunk_size, capacity, dir, store : Arc::new(Mutex::new(store)) }
+  }
+
+  /// Return the cached builder.
+  pub fn parent(&self) -> &Arc<dyn VFileBuilder>
+  {
+    &self.parent
+  }
+
+  /// Return the statistics of the cache.
+  pub fn stats(&self) -> ChunkCacheStats
+  {
+    self.store.lock().unwrap().stats.clone()
+  }
+}
+
+#[typetag::serde]
+impl VFileBuilder for ChunkedCacheVFileBuilder
+{
+  fn open(&self) -> anyhow::Result<Box<dyn VFile>>
+  {
+    Ok(Box::new(ChunkedCacheVFile{ parent : self.parent.clone(), file : None, store : self.store.clone(), chunk_size : self.chunk_size,
+                                   size : self.parent.size(), pos : 0, current : None }))
+  }
+
+  fn size(&self) -> u64
+  {
+    self.parent.size()
+  }
+
+  fn extents(&self) -> Vec<Extent>
+  {
+    self.parent.extents()
+  }
+}
+
+impl<'de> Deserialize<'de> for ChunkedCacheVFileBuilder
+{
+  fn deserialize<D>(deserializer: D) -> std::result::Result<ChunkedCacheVFileBuilder, D::Error>
+  where
+    D: serde::de::Deserializer<'de>,
+  {
+    #[derive(Deserialize)]
+    struct Fields
+    {
+      parent : Arc<dyn VFileBuilder>,
+      chunk_size : usize,
+      capacity : u64,
+      dir : Option<PathBuf>,
+    }
+
+    let fields = Fields::deserialize(deserializer)?;
+    Ok(ChunkedCacheVFileBuilder::with_options(fields.parent, fields.chunk_size, fields.capacity, fields.dir))
+  }
+}
+
+/**
+ * [VFile] reading the parent of a [ChunkedCacheVFileBuilder] through its cache, the parent is opened on the first miss.
+ */
+pub struct ChunkedCacheVFile
+{
+  parent : Arc<dyn VFileBuilder>,
+  file : Option<Box<dyn VFile>>,
+  store : Arc<Mutex<ChunkStore>>,
+  chunk_size : usize,
+  size : u64,
+  pos : u64,
+  /// Index and data of the last chunk read.
+  current : Option<(u64, Vec<u8>)>,
+}
+
+impl ChunkedCacheVFile
+{
+  /// Return the chunk `index` from the cache, or read it from the parent and cache it.
+  fn chunk(&mut self, index : u64) -> io::Result<Vec<u8>>
+  {
+    if let Some(data) = self.store.lock().unwrap().get(index, self.chunk_size)?
+    {
+      return Ok(data)
+    }
+
+    //the parent is read without locking the cache so the other files can use it
+    let file = match &mut self.file
+    {
+      Some(file) => file,
+      None => self.file.insert(self.parent.open().map_err(|err| io::Error::other(err.to_string()))?),
+    };
+    let start = index * self.chunk_size as u64;
+    let mut data = Vec::with_capacity(self.chunk_size);
+    file.seek(SeekFrom::Start(start))?;
+    file.take(self.chunk_size as u64).read_to_end(&mut data)?;
+
+    let mut store = self.store.lock().unwrap();
+    store.stats.misses += 1;
+    //a complete chunk or the end of the parent is cached, a short read is not
+    if data.len() == self.chunk_size || start + data.len() as u64 == self.size
+    {
+      store.put(index, &data, self.chunk_size)?;
+    }
+    Ok(data)
+  }
+}
+
+impl Read for ChunkedCacheVFile
+{
+  fn read(&mut self, buf : &mut [u8]) -> io::Result<usize>
+  {
+    if self.pos >= self.size || buf.is_empty()
+    {
+      return Ok(0)
+    }
+
+    let index = self.pos / self.chunk_size as u64;
+    let data = match self.current.take()
+    {
+      Some((current, data)) if current == index => data,
+      _ => self.chunk(index)?,
+    };
+    let offset = (self.pos - index * self.chunk_size as u64) as usize;
+    let size = buf.len().min(data.len().saturating_sub(offset));
+    buf[..size].copy_from_slice(&data[offset..offset + size]);
+    self.pos += size as u64;
+    self.current = Some((index, data));
+    Ok(size)
+  }
+}
+
+impl Seek for ChunkedCacheVFile
+{
+  fn seek(&mut self, pos : SeekFrom) -> io::Result<u64>
+  {
+    let pos = match pos
+    {
+      SeekFrom::Start(pos) => Some(pos),
+      SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+      SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+    };
+
+    match pos
+    {
+      Some(pos) => { self.pos = pos; Ok(pos) },
+      None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::ChunkedCacheVFileBuilder;
+  use crate::vfile::VFileBuilder;
+  use crate::patternvfile::RandomVFileBuilder;
+
+  use std::io::{Read, Seek, SeekFrom};
+  use std::sync::Arc;
+
+  #[test]
+  fn cache_chunks_on_disk()
+  {
+    let parent : Arc<dyn VFileBuilder> = Arc::new(RandomVFileBuilder::new(7, 10000));
+    let mut content = Vec::new();
+    parent.open().unwrap().read_to_end(&mut content).unwrap();
+
+    let dir = std::env::temp_dir().join(format!("tap-cache-test-{}", uuid::Uuid::new_v4()));
+    let builder = ChunkedCacheVFileBuilder::with_options(parent, 1000, 3000, Some(dir.clone()));
+    let mut data = Vec::new();
+    builder.open().unwrap().read_to_end(&mut data).unwrap();
+    assert!(data == content);
+    let stats = builder.stats();
+    assert!(stats.misses == 10 && stats.hits == 0 && stats.evictions == 7 && stats.chunks == 3 && stats.bytes == 3000);
+    let cache_file = std::fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+    assert!(std::fs::metadata(&cache_file).unwrap().len() <= 3000);
+
+    //the last chunks are read from the cache
+    let mut file = builder.open().unwrap();
+    let mut buffer = [0; 1500];
+    file.seek(SeekFrom::Start(8000)).unwrap();
+    file.read_exact(&mut buffer).unwrap();
+    assert!(buffer[..] == content[8000..9500] && builder.stats().hits == 2 && builder.stats().misses == 10);
+    file.seek(SeekFrom::Start(10)).unwrap();
+    file.read_exact(&mut buffer[..10]).unwrap();
+    assert!(buffer[..10] == content[10..20] && builder.stats().misses == 11);
+
+    drop(file);
+    drop(builder);
+    assert!(!cache_file.exists());
+    std::fs::remove_dir(dir).unwrap();
+  }
+}
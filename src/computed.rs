@@ -0,0 +1,658 @@
+//! Computed attributes are virtual attributes defined by an expression on the other attributes of a node,
+//! like `total_size = size + slack_size` or `age_days = days(now - modified)`, so users can enrich the tree without writing a plugin.
+//!
+//! A [ComputedAttribute] is added to the nodes matching its [Query] as a [Value::Func] evaluated each time it's read.
+//! Like the tag rules, [ComputedAttributes] are live : they are added to the nodes created by the tasks launched later.
+//!
+//! Expressions support numbers, `"strings"`, attribute paths with `.` between the keys, `now`,
+//! the `+ - * / %` operators and parentheses. Dates can be subtracted to a duration and durations added to dates.
+//! Numeric strings are parsed with [Value::parse_numeric]. Available functions are `now()`, `days(d)`, `hours(d)`,
+//! `minutes(d)`, `seconds(d)` converting a duration, `abs(x)`, `round(x)`, `min(a, b)`, `max(a, b)` and `len(x)`.
+//! Expressions that can't be evaluated, because an attribute is missing or has an unexpected type, return [Value::Unit].
+
+use std::sync::{Arc, RwLock};
+use std::cmp::Ordering;
+
+use crate::tree::{Tree, TreeNodeId};
+use crate::attribute::Attributes;
+use crate::value::{Value, NumericOptions};
+use crate::value::numeric::parse_numeric;
+use crate::tag::Query;
+use crate::error::RustructError;
+
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Serialize, Deserialize};
+use serde::de::{Deserializer};
+
+/// Binary operators of an [Expr].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator
+{
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Rem,
+}
+
+/// Parsed expression of a [ComputedAttribute].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr
+{
+  /// A number or a string.
+  Literal(Value),
+  /// Path of an attribute, as passed to [Value::get_path].
+  Attribute(String),
+  /// Current time.
+  Now,
+  /// Negation of an expression.
+  Neg(Box<Expr>),
+  /// Binary operation.
+  Binary(Operator, Box<Expr>, Box<Expr>),
+  /// Function call with its arguments.
+  Call(String, Vec<Expr>),
+}
+
+/// Functions available in expressions and their number of arguments.
+const FUNCTIONS : &[(&str, usize)] = &[("now", 0), ("days", 1), ("hours", 1), ("minutes", 1), ("seconds", 1),
+                                       ("abs", 1), ("round", 1), ("min", 2), ("max", 2), ("len", 1)];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token
+{
+  Number(Value),
+  Str(String),
+  Ident(String),
+  Op(char),
+  Open,
+  Close,
+  Comma,
+}
+
+fn syntax_error<S : Into<String>>(message : S) -> anyhow::Error
+{
+  RustructError::InvalidArgument("expression".into(), message.into()).into()
+}
+
+fn tokenize(source : &str) -> Result<Vec<Token>>
+{
+  let numbers = NumericOptions{ separators : vec!['_'], ..NumericOptions::default() };
+  let mut tokens = Vec::new();
+  let mut chars = source.char_indices().peekable();
+
+  while let Some((start, c)) = chars.next()
+  {
+    match c
+    {
+      c if c.is_whitespace() => (),
+      '+' | '-' | '*' | '/' | '%' => tokens.push(Token::Op(c)),
+      '(' => tokens.push(Token::Open),
+      ')' => tokens.push(Token::Close),
+      ',' => tokens.push(Token::Comma),
+      '"' =>
+      {
+        let mut string = String::new();
+        loop
+        {
+          match chars.next()
+          {
+            Some((_, '"')) => break,
+            Some((_, '\\')) => string.extend(chars.next().map(|(_, c)| c)),
+            Some((_, c)) => string.push(c),
+            None => return Err(syntax_error("unterminated string")),
+          }
+        }
+        tokens.push(Token::Str(string));
+      },
+      c if c.is_alphanumeric() || c == '_' =>
+      {
+        let mut end = start + c.len_utf8();
+        while let Some((index, c)) = chars.peek().cloned()
+        {
+          if !(c.is_alphanumeric() || c == '_' || c == '.')
+          {
+            break
+          }
+          end = index + c.len_utf8();
+          chars.next();
+        }
+        let word = &source[start..end];
+        match c.is_ascii_digit()
+        {
+          true => tokens.push(Token::Number(parse_numeric(word, &numbers).ok_or_else(|| syntax_error(format!("invalid number {}", word)))?)),
+          false => tokens.push(Token::Ident(word.to_string())),
+        }
+      },
+      c => return Err(syntax_error(format!("unexpected character {:?}", c))),
+    }
+  }
+  Ok(tokens)
+}
+
+/// Recursive descent parser of the tokens of an expression.
+struct Parser
+{
+  tokens : Vec<Token>,
+  pos : usize,
+}
+
+impl Parser
+{
+  fn peek(&self) -> Option<&Token>
+  {
+    self.tokens.get(self.pos)
+  }
+
+  fn next(&mut self) -> Option<Token>
+  {
+    let token = self.tokens.get(self.pos).cloned();
+    self.pos += 1;
+    token
+  }
+
+  fn expect(&mut self, token : Token) -> Result<()>
+  {
+    match self.next()
+    {
+      Some(next) if next == token => Ok(()),
+      next => Err(syntax_error(format!("expected {:?}, found {:?}", token, next))),
+    }
+  }
+
+  fn sum(&mut self) -> Result<Expr>
+  {
+    let mut expr = self.product()?;
+    while let Some(Token::Op(op @ ('+' | '-'))) = self.peek().cloned()
+    {
+      self.pos += 1;
+      let op = if op == '+' { Operator::Add } else { Operator::Sub };
+      expr = Expr::Binary(op, Box::new(expr), Box::new(self.product()?));
+    }
+    Ok(expr)
+  }
+
+  fn product(&mut self) -> Result<Expr>
+  {
+    let mut expr = self.unary()?;
+    while let Some(Token::Op(op @ ('*' | '/' | '%'))) = self.peek().cloned()
+    {
+      self.pos += 1;
+      let op = match op { '*' => Operator::Mul, '/' => Operator::Div, _ => Operator::Rem };
+      expr = Expr::Binary(op, Box::new(expr), Box::new(self.unary()?));
+    }
+    Ok(expr)
+  }
+
+  fn unary(&mut self) -> Result<Expr>
+  {
+    match self.peek()
+    {
+      Some(Token::Op('-')) => { self.pos += 1; Ok(Expr::Neg(Box::new(self.unary()?))) },
+      _ => self.primary(),
+    }
+  }
+
+  fn primary(&mut self) -> Result<Expr>
+  {
+    match self.next()
+    {
+      Some(Token::Number(value)) => Ok(Expr::Literal(value)),
+      Some(Token::Str(string)) => Ok(Expr::Literal(Value::String(string))),
+      Some(Token::Open) =>
+      {
+        let expr = self.sum()?;
+        self.expect(Token::Close)?;
+        Ok(expr)
+      },
+      Some(Token::Ident(name)) if self.peek() == Some(&Token::Open) =>
+      {
+        self.pos += 1;
+        let mut arguments = Vec::new();
+        if self.peek() == Some(&Token::Close)
+        {
+          self.pos += 1;
+        }
+        else
+        {
+          loop
+          {
+            arguments.push(self.sum()?);
+            match self.next()
+            {
+              Some(Token::Comma) => (),
+              Some(Token::Close) => break,
+              token => return Err(syntax_error(format!("expected , or ), found {:?}", token))),
+            }
+          }
+        }
+
+        match FUNCTIONS.iter().find(|(function, _)| *function == name)
+        {
+          Some((_, count)) if *count == arguments.len() => Ok(Expr::Call(name, arguments)),
+          Some((_, count)) => Err(syntax_error(format!("{} expect {} arguments", name, count))),
+          None => Err(syntax_error(format!("unknown function {}", name))),
+        }
+      },
+      Some(Token::Ident(name)) if name == "now" => Ok(Expr::Now),
+      Some(Token::Ident(name)) => Ok(Expr::Attribute(name.replace('.', "/"))),
+      token => Err(syntax_error(format!("unexpected {:?}", token))),
+    }
+  }
+}
+
+impl Expr
+{
+  /// Parse `source` as an expression.
+  pub fn parse(source : &str) -> Result<Expr>
+  {
+    let mut parser = Parser{ tokens : tokenize(source)?, pos : 0 };
+    let expr = parser.sum()?;
+    match parser.next()
+    {
+      None => Ok(expr),
+      Some(token) => Err(syntax_error(format!("unexpected {:?}", token))),
+    }
+  }
+
+  /// Return the path of the attributes used by the expression.
+  pub fn attributes(&self) -> Vec<&str>
+  {
+    match self
+    {
+      Expr::Attribute(path) => vec![path.as_str()],
+      Expr::Neg(expr) => expr.attributes(),
+      Expr::Binary(_, left, right) => left.attributes().into_iter().chain(right.attributes()).collect(),
+      Expr::Call(_, arguments) => arguments.iter().flat_map(|argument| argument.attributes()).collect(),
+      Expr::Literal(_) | Expr::Now => Vec::new(),
+    }
+  }
+
+  /// Evaluate the expression on `attributes`, return [Value::Unit] if it can't be evaluated.
+  pub fn evaluate(&self, attributes : &Attributes) -> Value
+  {
+    self.eval(&Value::Attributes(attributes.clone())).unwrap_or(Value::Unit)
+  }
+
+  fn eval(&self, attributes : &Value) -> Option<Value>
+  {
+    match self
+    {
+      Expr::Literal(value) => Some(value.clone()),
+      Expr::Attribute(path) => match attributes.get_path(path)?
+      {
+        Value::Func(func) => Some(func()),
+        Value::FuncArg(func, arg) => Some(func(Value::Newtype(arg))),
+        value => Some(value),
+      },
+      Expr::Now => Some(Value::DateTime(Utc::now())),
+      Expr::Neg(expr) => binary(Operator::Sub, Value::I64(0), expr.eval(attributes)?),
+      Expr::Binary(op, left, right) => binary(*op, left.eval(attributes)?, right.eval(attributes)?),
+      Expr::Call(name, arguments) =>
+      {
+        let arguments = arguments.iter().map(|argument| argument.eval(attributes)).collect::<Option<Vec<Value>>>()?;
+        call(name, arguments)
+      },
+    }
+  }
+}
+
+/// Return `value` as a number, numeric strings are parsed.
+fn number(value : &Value) -> Option<Value>
+{
+  value.parse_numeric(&NumericOptions::default())
+}
+
+/// Return the smallest of `U64`, `I64` or `I128` containing `value`.
+fn integer(value : i128) -> Value
+{
+  match (u64::try_from(value), i64::try_from(value))
+  {
+    (Ok(value), _) => Value::U64(value),
+    (_, Ok(value)) => Value::I64(value),
+    _ => Value::I128(value),
+  }
+}
+
+fn binary(op : Operator, left : Value, right : Value) -> Option<Value>
+{
+  match (op, &left, &right)
+  {
+    (Operator::Sub, Value::DateTime(left), Value::DateTime(right)) => return Some(Value::Duration(*left - *right)),
+    (Operator::Add, Value::DateTime(date), Value::Duration(duration)) => return date.checked_add_signed(*duration).map(Value::DateTime),
+    (Operator::Sub, Value::DateTime(date), Value::Duration(duration)) => return date.checked_sub_signed(*duration).map(Value::DateTime),
+    (Operator::Add, Value::Duration(left), Value::Duration(right)) => return left.checked_add(right).map(Value::Duration),
+    (Operator::Sub, Value::Duration(left), Value::Duration(right)) => return left.checked_sub(right).map(Value::Duration),
+    (Operator::Add, Value::String(_) | Value::Str(_), Value::String(_) | Value::Str(_)) if number(&left).is_none() || number(&right).is_none() =>
+      return Some(Value::String(left.as_string() + &right.as_string())),
+    _ => (),
+  }
+
+  let (left, right) = (number(&left)?, number(&right)?);
+  let float = matches!(left, Value::F32(_) | Value::F64(_)) || matches!(right, Value::F32(_) | Value::F64(_));
+  if !float
+  {
+    let (left, right) = (left.to_i128()?, right.to_i128()?);
+    return match op
+    {
+      Operator::Add => left.checked_add(right).map(integer),
+      Operator::Sub => left.checked_sub(right).map(integer),
+      Operator::Mul => left.checked_mul(right).map(integer),
+      //division of integers stay an integer if exact
+      Operator::Div if right != 0 && left % right == 0 => Some(integer(left / right)),
+      Operator::Div if right != 0 => Some(Value::F64(left as f64 / right as f64)),
+      Operator::Rem if right != 0 => Some(integer(left % right)),
+      Operator::Div | Operator::Rem => None,
+    }
+  }
+
+  let (left, right) = (left.to_f64()?, right.to_f64()?);
+  let result = match op
+  {
+    Operator::Add => left + right,
+    Operator::Sub => left - right,
+    Operator::Mul => left * right,
+    Operator::Div => left / right,
+    Operator::Rem => left % right,
+  };
+  result.is_finite().then_some(Value::F64(result))
+}
+
+fn call(name : &str, mut arguments : Vec<Value>) -> Option<Value>
+{
+  let duration = |value : &Value, unit : i64| match value
+  {
+    Value::Duration(duration) => Some(Value::I64(duration.num_seconds() / unit)),
+    _ => None,
+  };
+
+  match name
+  {
+    "now" => Some(Value::DateTime(Utc::now())),
+    "days" => duration(&arguments[0], 86400),
+    "hours" => duration(&arguments[0], 3600),
+    "minutes" => duration(&arguments[0], 60),
+    "seconds" => duration(&arguments[0], 1),
+    "abs" => match number(&arguments[0])?
+    {
+      Value::F32(value) => Some(Value::F64((value as f64).abs())),
+      Value::F64(value) => Some(Value::F64(value.abs())),
+      value => value.to_i128()?.checked_abs().map(integer),
+    },
+    "round" => number(&arguments[0])?.to_f64().map(|value| value.round()).filter(|value| value.abs() < i128::MAX as f64).map(|value| integer(value as i128)),
+    "min" | "max" =>
+    {
+      let right = arguments.pop()?;
+      let left = arguments.pop()?;
+      let ordering = match (&left, &right)
+      {
+        (Value::DateTime(a), Value::DateTime(b)) => a.cmp(b),
+        (Value::Duration(a), Value::Duration(b)) => a.cmp(b),
+        _ => number(&left)?.compare_numeric(&number(&right)?)?,
+      };
+      match (name, ordering)
+      {
+        ("min", Ordering::Greater) | ("max", Ordering::Less) => Some(right),
+        _ => Some(left),
+      }
+    },
+    "len" => match &arguments[0]
+    {
+      Value::String(string) => Some(integer(string.chars().count() as i128)),
+      Value::Str(string) => Some(integer(string.chars().count() as i128)),
+      Value::Seq(values) => Some(integer(values.len() as i128)),
+      Value::Bytes(bytes) => Some(integer(bytes.len() as i128)),
+      Value::VFileBuilder(builder) => Some(Value::U64(builder.size())),
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+/**
+ * An attribute named `name` computed from the `expression` added to the nodes matching `query`.
+ * It's serialized as its name, expression source and query.
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct ComputedAttribute
+{
+  /// Name of the attribute.
+  pub name : String,
+  /// Source of the expression.
+  pub expression : String,
+  /// Nodes to which the attribute is added.
+  pub query : Query,
+  #[serde(skip)]
+  parsed : Arc<Expr>,
+}
+
+impl ComputedAttribute
+{
+  /// Return an attribute `name` computed from `expression`, added to all nodes.
+  /// Return an error if the expression is invalid or use the attribute `name` itself.
+  pub fn new<S : Into<String>>(name : S, expression : &str) -> Result<Self>
+  {
+    let name = name.into();
+    let parsed = Expr::parse(expression)?;
+    if parsed.attributes().iter().any(|path| path.split('/').next() == Some(name.as_str()))
+    {
+      return Err(syntax_error(format!("{} is computed from itself", name)))
+    }
+    Ok(ComputedAttribute{ name, expression : expression.to_string(), query : Query::new(), parsed : Arc::new(parsed) })
+  }
+
+  /// Parse a definition like `total_size = size + slack_size`.
+  pub fn parse(definition : &str) -> Result<Self>
+  {
+    match definition.split_once('=')
+    {
+      Some((name, expression)) if !name.trim().is_empty() => ComputedAttribute::new(name.trim(), expression.trim()),
+      _ => Err(syntax_error(format!("{} is not a definition like name = expression", definition))),
+    }
+  }
+
+  /// Add the attribute only to the nodes matching `query`.
+  pub fn matching(mut self, query : Query) -> Self
+  {
+    self.query = query;
+    self
+  }
+
+  /// Return the parsed expression.
+  pub fn expr(&self) -> &Expr
+  {
+    &self.parsed
+  }
+
+  /// Return a [Value::Func] evaluating the expression on `attributes` when called.
+  /// The function doesn't keep `attributes` alive, so it can be added to them.
+  pub fn value(&self, attributes : &Attributes) -> Value
+  {
+    let attributes = attributes.downgrade();
+    let expr = self.parsed.clone();
+    Value::Func(Arc::new(Box::new(move || match attributes.upgrade()
+    {
+      Some(attributes) => expr.evaluate(&attributes),
+      None => Value::Unit,
+    })))
+  }
+}
+
+impl<'de> Deserialize<'de> for ComputedAttribute
+{
+  fn deserialize<D>(deserializer: D) -> std::result::Result<ComputedAttribute, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    #[derive(Deserialize)]
+    struct Fields
+    {
+      name : String,
+      expression : String,
+      #[serde(default)]
+      query : Query,
+    }
+
+    let fields = Fields::deserialize(deserializer)?;
+    let computed = ComputedAttribute::new(fields.name, &fields.expression).map_err(serde::de::Error::custom)?;
+    Ok(computed.matching(fields.query))
+  }
+}
+
+/**
+ * Hold the [ComputedAttribute] added to the nodes created by the tasks.
+ */
+#[derive(Default)]
+pub struct ComputedAttributes
+{
+  rules : RwLock<Vec<ComputedAttribute>>,
+}
+
+impl ComputedAttributes
+{
+  /// Return a new [ComputedAttributes] without rules.
+  pub fn new() -> Self
+  {
+    ComputedAttributes::default()
+  }
+
+  /// Add `computed` to all the nodes of `tree` matching its query, and to the nodes created later by the tasks.
+  /// Return the number of attributes added.
+  pub fn add(&self, tree : &Tree, computed : ComputedAttribute) -> usize
+  {
+    self.rules.write().unwrap().push(computed.clone());
+    let nodes : Vec<TreeNodeId> = tree.root_id.descendants(&tree.arena()).collect();
+    ComputedAttributes::apply_rules(tree, &nodes, &[computed])
+  }
+
+  /// Return the computed attributes.
+  pub fn rules(&self) -> Vec<ComputedAttribute>
+  {
+    self.rules.read().unwrap().clone()
+  }
+
+  /// Stop adding the computed attribute `name` to new nodes, attributes already added are kept.
+  pub fn remove(&self, name : &str) -> bool
+  {
+    let mut rules = self.rules.write().unwrap();
+    let count = rules.len();
+    rules.retain(|rule| rule.name != name);
+    rules.len() != count
+  }
+
+  /// Add the computed attributes to the `nodes` of `tree` matching their query, nodes already having an attribute with the same name are skipped.
+  /// Return the number of attributes added. It's called by the workers on the nodes created by a task.
+  pub fn apply(&self, tree : &Tree, nodes : &[TreeNodeId]) -> usize
+  {
+    ComputedAttributes::apply_rules(tree, nodes, &self.rules())
+  }
+
+  fn apply_rules(tree : &Tree, nodes : &[TreeNodeId], rules : &[ComputedAttribute]) -> usize
+  {
+    let mut count = 0;
+
+    for node_id in nodes
+    {
+      let node = match tree.get_node_from_id(*node_id)
+      {
+        Some(node) => node,
+        None => continue,
+      };
+
+      let mut added = false;
+      for rule in rules.iter()
+      {
+        if node.value().get_attribute(&rule.name).is_none() && rule.query.matches(tree, *node_id)
+        {
+          node.value().add_attribute(rule.name.clone(), rule.value(&node.value()), None);
+          added = true;
+          count += 1;
+        }
+      }
+      if added
+      {
+        tree.touch(*node_id);
+      }
+    }
+    count
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{ComputedAttribute, ComputedAttributes, Expr};
+  use crate::tree::Tree;
+  use crate::node::Node;
+  use crate::value::Value;
+  use crate::attribute::Attributes;
+  use crate::tag::Query;
+  use crate::validation::Check;
+
+  use chrono::{Duration, Utc};
+
+  #[test]
+  fn evaluate_expressions()
+  {
+    let mut attributes = Attributes::new();
+    attributes.add_attribute("size", Value::U32(1000), None);
+    attributes.add_attribute("slack", Value::from("24".to_string()), None);
+    attributes.add_attribute("modified", Value::DateTime(Utc::now() - Duration::days(3)), None);
+    let mut record = Attributes::new();
+    record.add_attribute("offset", Value::I64(-8), None);
+    attributes.add_attribute("record", Value::Attributes(record), None);
+
+    let evaluate = |source : &str| Expr::parse(source).unwrap().evaluate(&attributes);
+    assert!(evaluate("size + slack") == Value::U64(1024));
+    assert!(evaluate("(size + slack) / 1024") == Value::U64(1));
+    assert!(evaluate("size / 8") == Value::U64(125) && evaluate("size / 16") == Value::F64(62.5));
+    assert!(evaluate("-record.offset * 2 + 0x10") == Value::U64(32));
+    assert!(evaluate("days(now - modified)") == Value::I64(3));
+    assert!(evaluate("max(size, 2KB) - min(1.5, 2)") == Value::F64(2046.5));
+    assert!(evaluate("\"size: \" + \"big\"") == Value::from("size: big".to_string()));
+    assert!(evaluate("len(\"abc\")") == Value::U64(3));
+    assert!(evaluate("missing + 1") == Value::Unit);
+    assert!(evaluate("size / 0") == Value::Unit);
+
+    assert!(Expr::parse("size +").is_err());
+    assert!(Expr::parse("unknown(size)").is_err());
+    assert!(Expr::parse("max(size)").is_err());
+    assert!(Expr::parse("(size").is_err());
+    assert!(Expr::parse("size $ 2").is_err());
+  }
+
+  #[test]
+  fn computed_attributes()
+  {
+    let tree = Tree::new();
+    let files_id = tree.add_child(tree.root_id, Node::new("files")).unwrap();
+    let file = Node::new("file");
+    file.value().add_attribute("size", Value::U64(100), None);
+    file.value().add_attribute("slack_size", Value::U64(412), None);
+    let file_id = tree.add_child(files_id, file).unwrap();
+    let other = Node::new("other");
+    other.value().add_attribute("size", Value::U64(1), None);
+    let other_id = tree.add_child(tree.root_id, other).unwrap();
+
+    let computed = ComputedAttributes::new();
+    let total = ComputedAttribute::parse("total_size = size + slack_size").unwrap()
+                  .matching(Query::new().under("/root/files").with("size", Check::Exists));
+    assert!(computed.add(&tree, total) == 1);
+    assert!(computed.apply(&tree, &[file_id, other_id]) == 0);
+
+    let node = tree.get_node_from_id(file_id).unwrap();
+    assert!(matches!(node.value().get_value("total_size"), Some(Value::Func(_))));
+    let json = serde_json::to_value(node.value()).unwrap();
+    assert!(json["total_size"] == 512);
+    node.value().set_value("slack_size", Value::U64(0));
+    assert!(serde_json::to_value(node.value()).unwrap()["total_size"] == 100);
+    assert!(tree.get_node_from_id(other_id).unwrap().value().get_value("total_size").is_none());
+
+    assert!(ComputedAttribute::parse("size = size + 1").is_err());
+    assert!(ComputedAttribute::parse("= 1").is_err());
+    let json = serde_json::to_string(&computed.rules()[0]).unwrap();
+    let rule : ComputedAttribute = serde_json::from_str(&json).unwrap();
+    assert!(rule.name == "total_size" && rule.query.path.as_deref() == Some("/root/files"));
+    assert!(computed.remove("total_size") && computed.rules().is_empty());
+  }
+}
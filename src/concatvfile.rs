@@ -0,0 +1,240 @@
+//! A [VFileBuilder] concatenating an ordered list of [VFileBuilder], used to read split images (`image.001`, `image.002`, ...) as one file.
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::vfile::{VFile, VFileBuilder};
+use crate::fsvfile::FsVFileBuilder;
+use crate::error::RustructError;
+
+use serde::{Serialize, Deserialize};
+use serde::de::{Deserializer};
+
+/**
+ * Implement a [VFileBuilder] generating files containing the data of each segment one after the other.
+ * Segments are opened only when read.
+ */
+#[derive(Serialize)]
+pub struct ConcatVFileBuilder
+{
+  segments : Vec<Arc<dyn VFileBuilder>>,
+  /// Offset of the start of each segment in the concatenated file.
+  #[serde(skip)]
+  starts : Arc<Vec<u64>>,
+  #[serde(skip)]
+  size : u64,
+}
+
+impl ConcatVFileBuilder
+{
+  /// Return a builder concatenating `segments` in order.
+  pub fn new(segments : Vec<Arc<dyn VFileBuilder>>) -> Arc<ConcatVFileBuilder>
+  {
+    Arc::new(ConcatVFileBuilder::with_segments(segments))
+  }
+
+  fn with_segments(segments : Vec<Arc<dyn VFileBuilder>>) -> ConcatVFileBuilder
+  {
+    let mut starts = Vec::with_capacity(segments.len());
+    let mut size = 0;
+    for segment in segments.iter()
+    {
+      starts.push(size);
+      size += segment.size();
+    }
+    ConcatVFileBuilder{ segments, starts : Arc::new(starts), size }
+  }
+
+  /// Return a builder concatenating the host files of a split image, starting with `first` (`image.001`, `image.000` or `image.1`)
+  /// and continuing with the files having the next extension number, with the same number of digits, until one is missing.
+  pub fn from_split_files<P : AsRef<Path>>(first : P) -> anyhow::Result<Arc<ConcatVFileBuilder>>
+  {
+    let first = first.as_ref();
+    let extension = first.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    let mut number = match extension.parse::<u64>()
+    {
+      Ok(number) if extension.chars().all(|c| c.is_ascii_digit()) => number,
+      _ => return Err(RustructError::OpenFile(format!("{} : not a numbered segment", first.display())).into()),
+    };
+
+    let mut segments : Vec<Arc<dyn VFileBuilder>> = vec![FsVFileBuilder::new(first)?];
+    loop
+    {
+      number += 1;
+      let path = first.with_extension(format!("{:0width$}", number, width = extension.len()));
+      if !path.is_file()
+      {
+        break;
+      }
+      segments.push(FsVFileBuilder::new(path)?);
+    }
+    Ok(ConcatVFileBuilder::new(segments))
+  }
+
+  /// Return the segments.
+  pub fn segments(&self) -> &[Arc<dyn VFileBuilder>]
+  {
+    &self.segments
+  }
+}
+
+#[typetag::serde]
+impl VFileBuilder for ConcatVFileBuilder
+{
+  fn open(&self) -> anyhow::Result<Box<dyn VFile>>
+  {
+    Ok(Box::new(ConcatVFile{ segments : self.segments.clone(), starts : self.starts.clone(), size : self.size, pos : 0, current : None }))
+  }
+
+  fn size(&self) -> u64
+  {
+    self.size
+  }
+}
+
+impl<'de> Deserialize<'de> for ConcatVFileBuilder
+{
+  fn deserialize<D>(deserializer: D) -> std::result::Result<ConcatVFileBuilder, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    #[derive(Deserialize)]
+    struct Fields
+    {
+      segments : Vec<Arc<dyn VFileBuilder>>,
+    }
+
+    let fields = Fields::deserialize(deserializer)?;
+    Ok(ConcatVFileBuilder::with_segments(fields.segments))
+  }
+}
+
+/**
+ * [VFile] reading the segments of a [ConcatVFileBuilder], the segment containing the current position is found with a binary search.
+ */
+pub struct ConcatVFile
+{
+  segments : Vec<Arc<dyn VFileBuilder>>,
+  starts : Arc<Vec<u64>>,
+  size : u64,
+  pos : u64,
+  /// Index and file of the opened segment.
+  current : Option<(usize, Box<dyn VFile>)>,
+}
+
+impl ConcatVFile
+{
+  /// Return the index of the segment containing `pos`, empty segments are skipped.
+  fn segment(&self, pos : u64) -> usize
+  {
+    self.starts.partition_point(|start| *start <= pos) - 1
+  }
+}
+
+impl Read for ConcatVFile
+{
+  fn read(&mut self, buf : &mut [u8]) -> io::Result<usize>
+  {
+    if self.pos >= self.size || buf.is_empty()
+    {
+      return Ok(0)
+    }
+
+    let index = self.segment(self.pos);
+    let offset = self.pos - self.starts[index];
+    let file = match &mut self.current
+    {
+      Some((current, file)) if *current == index => file,
+      _ =>
+      {
+        let file = self.segments[index].open().map_err(|err| io::Error::other(err.to_string()))?;
+        &mut self.current.insert((index, file)).1
+      },
+    };
+    file.seek(SeekFrom::Start(offset))?;
+
+    let remaining = self.segments[index].size() - offset;
+    let size = buf.len().min(remaining.min(usize::MAX as u64) as usize);
+    let readed = file.read(&mut buf[..size])?;
+    self.pos += readed as u64;
+    Ok(readed)
+  }
+}
+
+impl Seek for ConcatVFile
+{
+  fn seek(&mut self, pos : SeekFrom) -> io::Result<u64>
+  {
+    let pos = match pos
+    {
+      SeekFrom::Start(pos) => Some(pos),
+      SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+      SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+    };
+
+    match pos
+    {
+      Some(pos) => { self.pos = pos; Ok(pos) },
+      None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::ConcatVFileBuilder;
+  use crate::vfile::VFileBuilder;
+  use crate::memoryvfile::MemoryVFileBuilder;
+
+  use std::io::{Read, Seek, SeekFrom};
+  use std::sync::Arc;
+
+  #[test]
+  fn read_segments()
+  {
+    let segments : Vec<Arc<dyn VFileBuilder>> = vec![MemoryVFileBuilder::from_buffer((0..10u8).collect()),
+                                                     MemoryVFileBuilder::from_buffer(Vec::new()),
+                                                     MemoryVFileBuilder::from_buffer((10..25u8).collect()),
+                                                     MemoryVFileBuilder::from_buffer((25..30u8).collect())];
+    let builder = ConcatVFileBuilder::new(segments);
+    assert!(builder.size() == 30);
+
+    let mut file = builder.open().unwrap();
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).unwrap();
+    assert!(content == (0..30u8).collect::<Vec<u8>>());
+
+    let mut buffer = [0; 4];
+    file.seek(SeekFrom::Start(8)).unwrap();
+    file.read_exact(&mut buffer).unwrap();
+    assert!(buffer == [8, 9, 10, 11]);
+    file.seek(SeekFrom::End(-2)).unwrap();
+    assert!(file.read(&mut buffer).unwrap() == 2 && buffer[0..2] == [28, 29]);
+    assert!(file.read(&mut buffer).unwrap() == 0);
+  }
+
+  #[test]
+  fn open_split_files()
+  {
+    let dir = std::env::temp_dir().join(format!("tap-split-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir(&dir).unwrap();
+    std::fs::write(dir.join("image.001"), b"first ").unwrap();
+    std::fs::write(dir.join("image.002"), b"second ").unwrap();
+    std::fs::write(dir.join("image.003"), b"third").unwrap();
+    std::fs::write(dir.join("image.005"), b"ignored").unwrap();
+
+    let builder = ConcatVFileBuilder::from_split_files(dir.join("image.001")).unwrap();
+    assert!(builder.segments().len() == 3);
+    let mut content = String::new();
+    builder.open().unwrap().read_to_string(&mut content).unwrap();
+    assert!(content == "first second third");
+
+    let json = serde_json::to_string(&(builder as Arc<dyn VFileBuilder>)).unwrap();
+    let builder : Box<dyn VFileBuilder> = serde_json::from_str(&json).unwrap();
+    assert!(builder.size() == 18);
+    assert!(ConcatVFileBuilder::from_split_files(dir.join("image.raw")).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
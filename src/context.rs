@@ -0,0 +1,112 @@
+//! [CaseContext] describe the system an artifact comes from (timezone, codepages, operating system, hostname),
+//! so all plugins interpret local times and legacy strings of the same case consistently.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc, NaiveDateTime, FixedOffset, TimeZone};
+use serde::{Serialize, Deserialize};
+
+/// High half (0x80-0xff) of the cp437 codepage, used by MS-DOS and FAT short names.
+const CP437_HIGH : &str = "ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒáíóúñÑªº¿⌐¬½¼¡«»░▒▓│┤╡╢╖╕╣║╗╝╜╛┐└┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀αßΓπΣσµτΦΘΩδ∞φε∩≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}";
+
+/// Characters 0x80-0x9f of the windows-1252 codepage, undefined values are mapped to the replacement character.
+const WINDOWS_1252_C1 : &str = "€\u{fffd}‚ƒ„…†‡ˆ‰Š‹Œ\u{fffd}Ž\u{fffd}\u{fffd}‘’“”•–—˜™š›œ\u{fffd}žŸ";
+
+/**
+ * Assumptions about the system an artifact was acquired from.
+ * The context is set on the [Session](crate::session::Session) and passed to plugins through the
+ * [PluginEnvironment](crate::plugin::PluginEnvironment).
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaseContext
+{
+  /// Offset in seconds east of UTC of the system local time, daylight saving time is not handled.
+  pub utc_offset : i32,
+  /// Codepage used for ANSI strings : `windows-1252`, `iso-8859-1`, `cp437` or `utf-8`.
+  pub codepage : String,
+  /// Codepage used for OEM strings, like FAT short names.
+  pub oem_codepage : String,
+  /// Operating system of the acquired system.
+  pub os : Option<String>,
+  /// Hostname of the acquired system.
+  pub hostname : Option<String>,
+  /// Free form hints for plugins.
+  pub hints : BTreeMap<String, String>,
+}
+
+impl Default for CaseContext
+{
+  /// Return a context using UTC, the `windows-1252` ANSI codepage and the `cp437` OEM codepage.
+  fn default() -> Self
+  {
+    CaseContext{ utc_offset : 0, codepage : "windows-1252".into(), oem_codepage : "cp437".into(), os : None, hostname : None, hints : BTreeMap::new() }
+  }
+}
+
+impl CaseContext
+{
+  /// Convert `time`, a local time of the acquired system, to UTC.
+  pub fn local_to_utc(&self, time : &NaiveDateTime) -> DateTime<Utc>
+  {
+    match FixedOffset::east_opt(self.utc_offset).and_then(|offset| offset.from_local_datetime(time).single())
+    {
+      Some(time) => time.with_timezone(&Utc),
+      None => Utc.from_utc_datetime(time),
+    }
+  }
+
+  /// Decode an ANSI string using the context codepage.
+  pub fn decode(&self, bytes : &[u8]) -> String
+  {
+    decode_codepage(&self.codepage, bytes)
+  }
+
+  /// Decode an OEM string using the context OEM codepage.
+  pub fn decode_oem(&self, bytes : &[u8]) -> String
+  {
+    decode_codepage(&self.oem_codepage, bytes)
+  }
+}
+
+/// Decode `bytes` with `codepage`, unknown codepages are decoded as lossy UTF-8.
+pub fn decode_codepage(codepage : &str, bytes : &[u8]) -> String
+{
+  match codepage.to_ascii_lowercase().as_str()
+  {
+    "iso-8859-1" | "latin1" => bytes.iter().map(|byte| *byte as char).collect(),
+    "windows-1252" | "cp1252" => bytes.iter().map(|byte| match byte
+    {
+      0x80..=0x9f => WINDOWS_1252_C1.chars().nth((byte - 0x80) as usize).unwrap_or('\u{fffd}'),
+      _ => *byte as char,
+    }).collect(),
+    "cp437" | "ibm437" => bytes.iter().map(|byte| match byte
+    {
+      0x80..=0xff => CP437_HIGH.chars().nth((byte - 0x80) as usize).unwrap_or('\u{fffd}'),
+      _ => *byte as char,
+    }).collect(),
+    _ => String::from_utf8_lossy(bytes).into_owned(),
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::CaseContext;
+  use chrono::{NaiveDate, TimeZone, Utc};
+
+  #[test]
+  fn case_context()
+  {
+    let mut context = CaseContext::default();
+    assert!(context.decode(b"caf\xe9 \x80") == "café €");
+    assert!(context.decode_oem(b"\x82t\x82") == "été");
+
+    let time = NaiveDate::from_ymd_opt(2021, 3, 4).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    assert!(context.local_to_utc(&time) == Utc.with_ymd_and_hms(2021, 3, 4, 12, 0, 0).unwrap());
+    context.utc_offset = 3600;
+    assert!(context.local_to_utc(&time) == Utc.with_ymd_and_hms(2021, 3, 4, 11, 0, 0).unwrap());
+
+    context.codepage = "utf-8".into();
+    assert!(context.decode("été".as_bytes()) == "été");
+  }
+}
@@ -0,0 +1,417 @@
+//! A [VFileBuilder] decrypting a sector encrypted volume read from an other [VFileBuilder], used by BitLocker or LUKS like plugins
+//! to expose the decrypted volume without materializing it.
+//!
+//! Keys are never serialized : a builder keep the id of its key, and the key is requested to a [KeyProvider]
+//! when the builder is created, or to the providers registered with [register_key_provider] when it's deserialized.
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::vfile::{VFile, VFileBuilder};
+use crate::error::RustructError;
+
+use aes::{Aes128, Aes256, Block};
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::cipher::consts::U16;
+use xts_mode::{Xts128, get_tweak_default};
+use serde::{Serialize, Deserialize};
+use serde::de::{Deserializer};
+
+/**
+ * Return the keys used to decrypt [CryptVFileBuilder].
+ */
+pub trait KeyProvider : Sync + Send
+{
+  /// Return the key identified by `key_id`, or `None` if this provider doesn't know it.
+  fn key(&self, key_id : &str) -> Option<Vec<u8>>;
+}
+
+/// A [KeyProvider] holding keys in memory.
+#[derive(Default)]
+pub struct KeyRing
+{
+  keys : RwLock<HashMap<String, Vec<u8>>>,
+}
+
+impl KeyRing
+{
+  /// Return an empty key ring.
+  pub fn new() -> Self
+  {
+    KeyRing::default()
+  }
+
+  /// Add `key` as `key_id`, replacing any previous key with the same id.
+  pub fn add<S : Into<String>>(&self, key_id : S, key : Vec<u8>)
+  {
+    self.keys.write().unwrap().insert(key_id.into(), key);
+  }
+}
+
+impl KeyProvider for KeyRing
+{
+  fn key(&self, key_id : &str) -> Option<Vec<u8>>
+  {
+    self.keys.read().unwrap().get(key_id).cloned()
+  }
+}
+
+fn providers() -> &'static RwLock<Vec<Arc<dyn KeyProvider>>>
+{
+  static PROVIDERS : OnceLock<RwLock<Vec<Arc<dyn KeyProvider>>>> = OnceLock::new();
+  PROVIDERS.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+/// Register a [KeyProvider] used to find the keys of deserialized [CryptVFileBuilder].
+pub fn register_key_provider(provider : Arc<dyn KeyProvider>)
+{
+  providers().write().unwrap().push(provider);
+}
+
+/// Return the key `key_id` from the first registered [KeyProvider] knowing it.
+pub fn find_key(key_id : &str) -> Option<Vec<u8>>
+{
+  providers().read().unwrap().iter().find_map(|provider| provider.key(key_id))
+}
+
+/// Cipher and IV generation of a [CryptVFileBuilder], AES-128 or AES-256 is selected from the key size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Cipher
+{
+  /// AES-XTS with the sector number as tweak (`aes-xts-plain64`), the key contains both AES keys (32 or 64 bytes).
+  AesXts,
+  /// AES-CBC with the sector number as IV (`aes-cbc-plain64`), 16 or 32 bytes key.
+  AesCbcPlain64,
+  /// AES-CBC with the byte offset of the sector encrypted with the key as IV, as used by BitLocker without diffuser.
+  AesCbcEncryptedOffset,
+}
+
+/// Parameters of a [CryptVFileBuilder].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CryptParams
+{
+  /// Cipher used to encrypt the volume.
+  pub cipher : Cipher,
+  /// Size of the encrypted sectors, a multiple of 16.
+  pub sector_size : u32,
+  /// Number of the first sector of the parent, added to the sector numbers used as IV.
+  pub first_sector : u64,
+}
+
+impl CryptParams
+{
+  /// Return parameters for `cipher` with 512 bytes sectors starting at sector 0.
+  pub fn new(cipher : Cipher) -> Self
+  {
+    CryptParams{ cipher, sector_size : 512, first_sector : 0 }
+  }
+}
+
+/// Cipher instances created from the key.
+enum Decryptor
+{
+  Xts128(Box<Xts128<Aes128>>),
+  Xts256(Box<Xts128<Aes256>>),
+  Cbc128(Box<Aes128>),
+  Cbc256(Box<Aes256>),
+}
+
+impl Decryptor
+{
+  fn new(cipher : Cipher, key : &[u8]) -> anyhow::Result<Decryptor>
+  {
+    let invalid_key = || RustructError::InvalidArgument("CryptVFileBuilder".into(), format!("invalid key size {} for {:?}", key.len(), cipher));
+    Ok(match (cipher, key.len())
+    {
+      (Cipher::AesXts, 32) => Decryptor::Xts128(Box::new(Xts128::new(Aes128::new_from_slice(&key[..16]).map_err(|_| invalid_key())?,
+                                                            Aes128::new_from_slice(&key[16..]).map_err(|_| invalid_key())?))),
+      (Cipher::AesXts, 64) => Decryptor::Xts256(Box::new(Xts128::new(Aes256::new_from_slice(&key[..32]).map_err(|_| invalid_key())?,
+                                                            Aes256::new_from_slice(&key[32..]).map_err(|_| invalid_key())?))),
+      (Cipher::AesCbcPlain64 | Cipher::AesCbcEncryptedOffset, 16) => Decryptor::Cbc128(Box::new(Aes128::new_from_slice(key).map_err(|_| invalid_key())?)),
+      (Cipher::AesCbcPlain64 | Cipher::AesCbcEncryptedOffset, 32) => Decryptor::Cbc256(Box::new(Aes256::new_from_slice(key).map_err(|_| invalid_key())?)),
+      _ => return Err(invalid_key().into()),
+    })
+  }
+
+  /// Decrypt `sector`, `number` is the sector number including the first sector of the parameters.
+  fn decrypt(&self, params : &CryptParams, number : u64, sector : &mut [u8])
+  {
+    match self
+    {
+      Decryptor::Xts128(xts) => xts.decrypt_sector(sector, get_tweak_default(number as u128)),
+      Decryptor::Xts256(xts) => xts.decrypt_sector(sector, get_tweak_default(number as u128)),
+      Decryptor::Cbc128(aes) => cbc_decrypt(aes.as_ref(), iv(aes.as_ref(), params, number), sector),
+      Decryptor::Cbc256(aes) => cbc_decrypt(aes.as_ref(), iv(aes.as_ref(), params, number), sector),
+    }
+  }
+}
+
+/// Return the CBC IV of sector `number`.
+fn iv<C : BlockEncrypt<BlockSize = U16>>(aes : &C, params : &CryptParams, number : u64) -> Block
+{
+  match params.cipher
+  {
+    Cipher::AesCbcEncryptedOffset =>
+    {
+      let mut iv = Block::from((number as u128 * params.sector_size as u128).to_le_bytes());
+      aes.encrypt_block(&mut iv);
+      iv
+    },
+    _ => Block::from((number as u128).to_le_bytes()),
+  }
+}
+
+/// Decrypt `data` in place with AES-CBC.
+fn cbc_decrypt<C : BlockDecrypt<BlockSize = U16>>(aes : &C, mut previous : Block, data : &mut [u8])
+{
+  for chunk in data.chunks_exact_mut(16)
+  {
+    let cipher_block = Block::clone_from_slice(chunk);
+    let block = Block::from_mut_slice(chunk);
+    aes.decrypt_block(block);
+    block.iter_mut().zip(previous.iter()).for_each(|(byte, iv)| *byte ^= iv);
+    previous = cipher_block;
+  }
+}
+
+/**
+ * Implement a [VFileBuilder] decrypting a parent [VFileBuilder] encrypted by sectors.
+ * Sectors are decrypted when read, a trailing incomplete sector of the parent is ignored.
+ * It's serialized as its parent, parameters and key id.
+ */
+#[derive(Serialize)]
+pub struct CryptVFileBuilder
+{
+  parent : Arc<dyn VFileBuilder>,
+  params : CryptParams,
+  key_id : String,
+  #[serde(skip)]
+  decryptor : Arc<Decryptor>,
+}
+
+impl CryptVFileBuilder
+{
+  /// Return a builder decrypting `parent` with `params` and the key `key_id` returned by `provider`.
+  /// Return an error if the key is not found or its size doesn't match the cipher, or if the sector size is invalid.
+  pub fn new<S : Into<String>>(parent : Arc<dyn VFileBuilder>, params : CryptParams, key_id : S, provider : &dyn KeyProvider) -> anyhow::Result<Arc<CryptVFileBuilder>>
+  {
+    let key_id = key_id.into();
+    let key = provider.key(&key_id).ok_or_else(|| RustructError::InvalidArgument("CryptVFileBuilder".into(), format!("key {} not found", key_id)))?;
+    Ok(Arc::new(CryptVFileBuilder::with_key(parent, params, key_id, &key)?))
+  }
+
+  fn with_key(parent : Arc<dyn VFileBuilder>, params : CryptParams, key_id : String, key : &[u8]) -> anyhow::Result<CryptVFileBuilder>
+  {
+    if params.sector_size < 16 || !params.sector_size.is_multiple_of(16)
+    {
+      return Err(RustructError::InvalidArgument("CryptVFileBuilder".into(), format!("invalid sector size {}", params.sector_size)).into())
+    }
+    let decryptor = Arc::new(Decryptor::new(params.cipher, key)?);
+    Ok(CryptVFileBuilder{ parent, params, key_id, decryptor })
+  }
+
+  /// Return the encrypted parent builder.
+  pub fn parent(&self) -> &Arc<dyn VFileBuilder>
+  {
+    &self.parent
+  }
+
+  /// Return the decryption parameters.
+  pub fn params(&self) -> &CryptParams
+  {
+    &self.params
+  }
+}
+
+#[typetag::serde]
+impl VFileBuilder for CryptVFileBuilder
+{
+  fn open(&self) -> anyhow::Result<Box<dyn VFile>>
+  {
+    Ok(Box::new(CryptVFile{ file : self.parent.open()?, decryptor : self.decryptor.clone(), params : self.params, size : self.size(),
+                            pos : 0, sector : None, buffer : vec![0; self.params.sector_size as usize] }))
+  }
+
+  fn size(&self) -> u64
+  {
+    self.parent.size() - self.parent.size() % self.params.sector_size as u64
+  }
+}
+
+impl<'de> Deserialize<'de> for CryptVFileBuilder
+{
+  fn deserialize<D>(deserializer: D) -> std::result::Result<CryptVFileBuilder, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    #[derive(Deserialize)]
+    struct Fields
+    {
+      parent : Arc<dyn VFileBuilder>,
+      params : CryptParams,
+      key_id : String,
+    }
+
+    let fields = Fields::deserialize(deserializer)?;
+    let key = find_key(&fields.key_id).ok_or_else(|| serde::de::Error::custom(format!("key {} not found", fields.key_id)))?;
+    CryptVFileBuilder::with_key(fields.parent, fields.params, fields.key_id, &key).map_err(serde::de::Error::custom)
+  }
+}
+
+/**
+ * [VFile] decrypting the sectors of its parent when read, the last decrypted sector is kept.
+ */
+pub struct CryptVFile
+{
+  file : Box<dyn VFile>,
+  decryptor : Arc<Decryptor>,
+  params : CryptParams,
+  size : u64,
+  pos : u64,
+  /// Index of the sector decrypted in `buffer`.
+  sector : Option<u64>,
+  buffer : Vec<u8>,
+}
+
+impl Read for CryptVFile
+{
+  fn read(&mut self, buf : &mut [u8]) -> io::Result<usize>
+  {
+    if self.pos >= self.size || buf.is_empty()
+    {
+      return Ok(0)
+    }
+
+    let sector_size = self.params.sector_size as u64;
+    let sector = self.pos / sector_size;
+    if self.sector != Some(sector)
+    {
+      self.sector = None;
+      self.file.seek(SeekFrom::Start(sector * sector_size))?;
+      self.file.read_exact(&mut self.buffer)?;
+      self.decryptor.decrypt(&self.params, self.params.first_sector + sector, &mut self.buffer);
+      self.sector = Some(sector);
+    }
+
+    let offset = (self.pos % sector_size) as usize;
+    let size = buf.len().min(self.buffer.len() - offset);
+    buf[..size].copy_from_slice(&self.buffer[offset..offset + size]);
+    self.pos += size as u64;
+    Ok(size)
+  }
+}
+
+impl Seek for CryptVFile
+{
+  fn seek(&mut self, pos : SeekFrom) -> io::Result<u64>
+  {
+    let pos = match pos
+    {
+      SeekFrom::Start(pos) => Some(pos),
+      SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+      SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+    };
+
+    match pos
+    {
+      Some(pos) => { self.pos = pos; Ok(pos) },
+      None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{CryptVFileBuilder, CryptParams, Cipher, KeyRing, register_key_provider, iv};
+  use crate::vfile::VFileBuilder;
+  use crate::memoryvfile::MemoryVFileBuilder;
+  use crate::tempvfile::TempVFileBuilder;
+  use crate::fsvfile::FsVFileBuilder;
+
+  use std::io::{Read, Seek, SeekFrom};
+  use std::sync::Arc;
+  use aes::{Aes128, Aes256, Block};
+  use aes::cipher::{BlockEncrypt, KeyInit};
+  use xts_mode::{Xts128, get_tweak_default};
+
+  /// Encrypt `data` with AES-CBC by sectors of 512 bytes.
+  fn cbc_encrypt(aes : &Aes128, params : &CryptParams, data : &mut [u8])
+  {
+    for (number, sector) in data.chunks_exact_mut(512).enumerate()
+    {
+      let mut previous = iv(aes, params, params.first_sector + number as u64);
+      for chunk in sector.chunks_exact_mut(16)
+      {
+        let block = Block::from_mut_slice(chunk);
+        block.iter_mut().zip(previous.iter()).for_each(|(byte, iv)| *byte ^= iv);
+        aes.encrypt_block(block);
+        previous = *block;
+      }
+    }
+  }
+
+  #[test]
+  fn decrypt_volume()
+  {
+    let plain : Vec<u8> = (0..2048u32).map(|i| (i % 253) as u8).collect();
+    let keys = Arc::new(KeyRing::new());
+
+    let xts_key : Vec<u8> = (0..64).collect();
+    keys.add("test-xts", xts_key.clone());
+    let xts = Xts128::new(Aes256::new_from_slice(&xts_key[..32]).unwrap(), Aes256::new_from_slice(&xts_key[32..]).unwrap());
+    let mut encrypted = plain.clone();
+    xts.encrypt_area(&mut encrypted, 512, 0, get_tweak_default);
+    encrypted.extend_from_slice(&[0; 100]);
+
+    let builder = CryptVFileBuilder::new(MemoryVFileBuilder::from_buffer(encrypted), CryptParams::new(Cipher::AesXts), "test-xts", keys.as_ref()).unwrap();
+    assert!(builder.size() == 2048);
+    let mut file = builder.open().unwrap();
+    let mut decrypted = Vec::new();
+    file.read_to_end(&mut decrypted).unwrap();
+    assert!(decrypted == plain);
+    let mut buffer = [0; 32];
+    file.seek(SeekFrom::Start(500)).unwrap();
+    file.read_exact(&mut buffer).unwrap();
+    assert!(buffer == plain[500..532]);
+
+    let cbc_key = [7u8; 16];
+    keys.add("test-cbc", cbc_key.to_vec());
+    for cipher in [Cipher::AesCbcPlain64, Cipher::AesCbcEncryptedOffset]
+    {
+      let params = CryptParams{ cipher, sector_size : 512, first_sector : 8 };
+      let mut encrypted = plain.clone();
+      cbc_encrypt(&Aes128::new_from_slice(&cbc_key).unwrap(), &params, &mut encrypted);
+      let builder = CryptVFileBuilder::new(MemoryVFileBuilder::from_buffer(encrypted), params, "test-cbc", keys.as_ref()).unwrap();
+      let mut decrypted = Vec::new();
+      builder.open().unwrap().read_to_end(&mut decrypted).unwrap();
+      assert!(decrypted == plain);
+    }
+
+    let parent = MemoryVFileBuilder::from_buffer(vec![0; 512]);
+    assert!(CryptVFileBuilder::new(parent.clone(), CryptParams::new(Cipher::AesXts), "missing", keys.as_ref()).is_err());
+    assert!(CryptVFileBuilder::new(parent.clone(), CryptParams::new(Cipher::AesXts), "test-cbc", keys.as_ref()).is_err());
+    assert!(CryptVFileBuilder::new(parent, CryptParams{ sector_size : 100, ..CryptParams::new(Cipher::AesCbcPlain64) }, "test-cbc", keys.as_ref()).is_err());
+
+    let json = serde_json::to_string(&(builder as Arc<dyn VFileBuilder>)).unwrap();
+    assert!(!json.contains("key\"") && json.contains("test-xts"));
+  }
+
+  #[test]
+  fn deserialize_with_registered_key()
+  {
+    let keys = Arc::new(KeyRing::new());
+    keys.add("test-registered", vec![1; 32]);
+    let temp = TempVFileBuilder::new(&[0; 512]).unwrap();
+    let parent = FsVFileBuilder::new(temp.path()).unwrap();
+    let builder : Arc<dyn VFileBuilder> = CryptVFileBuilder::new(parent, CryptParams::new(Cipher::AesXts), "test-registered", keys.as_ref()).unwrap();
+    let json = serde_json::to_string(&builder).unwrap();
+    assert!(serde_json::from_str::<Box<dyn VFileBuilder>>(&json).is_err());
+
+    register_key_provider(keys);
+    let builder : Box<dyn VFileBuilder> = serde_json::from_str(&json).unwrap();
+    let mut buffer = [0; 16];
+    builder.open().unwrap().read_exact(&mut buffer).unwrap();
+  }
+}
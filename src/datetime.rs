@@ -1,9 +1,10 @@
-//! Convert a Windows 64bits timestamp to a [DateTime].
+//! Convert a Windows 64bits timestamp or a MS-DOS date and time to a [DateTime].
 
 use crate::error::RustructError;
+use crate::context::CaseContext;
 use anyhow::Result;
 
-use chrono::{DateTime, Utc, NaiveDateTime};
+use chrono::{DateTime, Utc, NaiveDateTime, NaiveDate, TimeZone};
 
 /// Convert an u64 (Windows 64bits timestamp) to a [DateTime].
 pub struct WindowsTimestamp(pub u64);
@@ -28,3 +29,35 @@ impl WindowsTimestamp
     Ok(DateTime::<Utc>::from_utc(time, Utc))
   }
 }
+
+/// Convert a MS-DOS date and time (as used by FAT file systems) to a [DateTime].
+/// MS-DOS times are local times, [DosDateTime::to_datetime] consider them as UTC while [DosDateTime::to_datetime_with] use the case timezone.
+pub struct DosDateTime(pub u16, pub u16);
+
+impl DosDateTime
+{
+  /// Return a [DateTime]::<[Utc]> from a MS-DOS date (first field) and time (second field).
+  pub fn to_datetime(&self) -> Result<DateTime::<Utc>>
+  {
+    Ok(Utc.from_utc_datetime(&self.to_naive()?))
+  }
+
+  /// Return a [DateTime]::<[Utc]> from a MS-DOS date and time, which is a local time of the system described by `context`.
+  pub fn to_datetime_with(&self, context : &CaseContext) -> Result<DateTime::<Utc>>
+  {
+    Ok(context.local_to_utc(&self.to_naive()?))
+  }
+
+  fn to_naive(&self) -> Result<NaiveDateTime>
+  {
+    let (date, time) = (self.0, self.1);
+    let year = 1980 + (date >> 9) as i32;
+    let month = ((date >> 5) & 0x0f) as u32;
+    let day = (date & 0x1f) as u32;
+
+    let time = NaiveDate::from_ymd_opt(year, month, day)
+               .and_then(|date| date.and_hms_opt((time >> 11) as u32, ((time >> 5) & 0x3f) as u32, (time & 0x1f) as u32 * 2))
+               .ok_or_else(|| RustructError::Unknown(format!("Can't convert to datetime, invalid dos date {:#x} time {:#x}", self.0, self.1)))?;
+    Ok(time)
+  }
+}
@@ -0,0 +1,283 @@
+//! A [VFileBuilder] exposing the decompressed content of an other [VFileBuilder] (gzip, zlib, deflate, lz4 and zstd).
+//!
+//! Compressed streams can't be seeked, so the parent is decompressed once when the builder is created, or on first use when deserialized,
+//! and the decompressed data is kept in memory if small or spilled to a temporary file, then read with seek support.
+
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::vfile::{VFile, VFileBuilder};
+use crate::memoryvfile::MemoryVFileBuilder;
+use crate::tempvfile::TempVFileWriter;
+use crate::error::RustructError;
+
+use serde::{Serialize, Deserialize};
+use serde::de::{Deserializer};
+
+/// Size of the decompressed data from which it's spilled to a temporary file rather than kept in memory.
+pub const SPILL_THRESHOLD : u64 = 16 * 1024 * 1024;
+/// Maximum size of the decompressed data, protect against decompression bombs.
+pub const MAX_DECOMPRESSED_SIZE : u64 = 16 * 1024 * 1024 * 1024;
+
+/// Compression format of a [DecompressVFileBuilder] parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Compression
+{
+  /// Gzip members, concatenated members are decompressed one after the other.
+  Gzip,
+  /// Zlib stream.
+  Zlib,
+  /// Raw deflate stream without header.
+  Deflate,
+  /// LZ4 frame.
+  Lz4,
+  /// Zstandard frames, require the `zstd` feature.
+  Zstd,
+}
+
+impl Compression
+{
+  /// Return the compression format identified by the magic at the start of `header`.
+  /// Raw deflate streams have no magic and are never detected.
+  pub fn detect(header : &[u8]) -> Option<Compression>
+  {
+    match header
+    {
+      [0x1f, 0x8b, ..] => Some(Compression::Gzip),
+      [0x04, 0x22, 0x4d, 0x18, ..] => Some(Compression::Lz4),
+      [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Compression::Zstd),
+      [cmf, flg, ..] if cmf & 0x0f == 8 && cmf >> 4 <= 7 && (((*cmf as u16) << 8) | *flg as u16).is_multiple_of(31) => Some(Compression::Zlib),
+      _ => None,
+    }
+  }
+
+  /// Return a reader decompressing `reader`.
+  fn decoder<'a>(&self, reader : Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>>
+  {
+    Ok(match self
+    {
+      Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+      Compression::Zlib => Box::new(flate2::read::ZlibDecoder::new(reader)),
+      Compression::Deflate => Box::new(flate2::read::DeflateDecoder::new(reader)),
+      Compression::Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(reader)),
+      #[cfg(feature = "zstd")]
+      Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+      #[cfg(not(feature = "zstd"))]
+      Compression::Zstd => return Err(io::Error::new(io::ErrorKind::Unsupported, "zstd support is not enabled")),
+    })
+  }
+}
+
+/**
+ * Implement a [VFileBuilder] generating files containing the decompressed content of a parent [VFileBuilder].
+ * It's serialized as its parent and compression, and decompressed again the first time it's used after being deserialized.
+ */
+#[derive(Serialize)]
+pub struct DecompressVFileBuilder
+{
+  parent : Arc<dyn VFileBuilder>,
+  compression : Compression,
+  #[serde(skip)]
+  data : Mutex<Option<Arc<dyn VFileBuilder>>>,
+}
+
+impl DecompressVFileBuilder
+{
+  /// Decompress `parent` with `compression` and return a builder reading the decompressed data.
+  /// Return [RustructError::InvalidEncoding] if `parent` is not a valid stream or is bigger than [MAX_DECOMPRESSED_SIZE] once decompressed.
+  pub fn new(parent : Arc<dyn VFileBuilder>, compression : Compression) -> anyhow::Result<Arc<DecompressVFileBuilder>>
+  {
+    let data = decompress(parent.as_ref(), compression, MAX_DECOMPRESSED_SIZE)?;
+    Ok(Arc::new(DecompressVFileBuilder{ parent, compression, data : Mutex::new(Some(data)) }))
+  }
+
+  /// Detect the compression of `parent` from its magic and return a builder reading the decompressed data,
+  /// or `None` if the compression is not recognized.
+  pub fn detect(parent : Arc<dyn VFileBuilder>) -> anyhow::Result<Option<Arc<DecompressVFileBuilder>>>
+  {
+    let mut header = Vec::with_capacity(4);
+    parent.open()?.take(4).read_to_end(&mut header)?;
+    match Compression::detect(&header)
+    {
+      Some(compression) => Ok(Some(DecompressVFileBuilder::new(parent, compression)?)),
+      None => Ok(None),
+    }
+  }
+
+  /// Return the compressed parent builder.
+  pub fn parent(&self) -> &Arc<dyn VFileBuilder>
+  {
+    &self.parent
+  }
+
+  /// Return the compression of the parent.
+  pub fn compression(&self) -> Compression
+  {
+    self.compression
+  }
+
+  /// Return the builder of the decompressed data, decompressing the parent if it was not done yet.
+  fn data(&self) -> anyhow::Result<Arc<dyn VFileBuilder>>
+  {
+    let mut data = self.data.lock().unwrap();
+    if let Some(data) = data.as_ref()
+    {
+      return Ok(data.clone())
+    }
+
+    let decompressed = decompress(self.parent.as_ref(), self.compression, MAX_DECOMPRESSED_SIZE)?;
+    *data = Some(decompressed.clone());
+    Ok(decompressed)
+  }
+}
+
+/// Decompress `parent` in memory, or to a temporary file once the decompressed data reach [SPILL_THRESHOLD].
+/// Return an error if the decompressed data is bigger than `max_size`.
+fn decompress(parent : &dyn VFileBuilder, compression : Compression, max_size : u64) -> anyhow::Result<Arc<dyn VFileBuilder>>
+{
+  let invalid = |err : io::Error| RustructError::InvalidEncoding(format!("{:?} stream : {}", compression, err));
+  let too_big = || RustructError::InvalidEncoding(format!("{:?} stream is bigger than {} bytes once decompressed", compression, max_size));
+  let mut decoder = compression.decoder(Box::new(parent.open()?)).map_err(invalid)?.take(max_size.saturating_add(1));
+
+  let mut buffer = Vec::new();
+  (&mut decoder).take(SPILL_THRESHOLD).read_to_end(&mut buffer).map_err(invalid)?;
+  if (buffer.len() as u64) < SPILL_THRESHOLD
+  {
+    return match buffer.len() as u64 > max_size
+    {
+      true => Err(too_big().into()),
+      false => Ok(MemoryVFileBuilder::from_buffer(buffer)),
+    }
+  }
+
+  let mut writer = TempVFileWriter::new()?;
+  writer.write_all(&buffer)?;
+  let size = buffer.len() as u64 + io::copy(&mut decoder, &mut writer).map_err(invalid)?;
+  if size > max_size
+  {
+    return Err(too_big().into())
+  }
+  Ok(writer.into_builder()?)
+}
+
+#[typetag::serde]
+impl VFileBuilder for DecompressVFileBuilder
+{
+  fn open(&self) -> anyhow::Result<Box<dyn VFile>>
+  {
+    self.data()?.open()
+  }
+
+  /// Return 0 if the parent can't be decompressed.
+  fn size(&self) -> u64
+  {
+    self.data().map_or(0, |data| data.size())
+  }
+}
+
+impl<'de> Deserialize<'de> for DecompressVFileBuilder
+{
+  fn deserialize<D>(deserializer: D) -> std::result::Result<DecompressVFileBuilder, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    #[derive(Deserialize)]
+    struct Fields
+    {
+      parent : Arc<dyn VFileBuilder>,
+      compression : Compression,
+    }
+
+    let fields = Fields::deserialize(deserializer)?;
+    Ok(DecompressVFileBuilder{ parent : fields.parent, compression : fields.compression, data : Mutex::new(None) })
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{DecompressVFileBuilder, Compression, SPILL_THRESHOLD, decompress};
+  use crate::vfile::VFileBuilder;
+  use crate::memoryvfile::MemoryVFileBuilder;
+  use crate::fsvfile::FsVFileBuilder;
+  use crate::tempvfile::TempVFileBuilder;
+
+  use std::io::{Read, Write, Seek, SeekFrom};
+  use std::sync::Arc;
+
+  #[test]
+  fn decompress_streams()
+  {
+    let content : Vec<u8> = (0..20000u32).flat_map(|i| (i % 251).to_le_bytes()).collect();
+
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(&content).unwrap();
+    let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    zlib.write_all(&content).unwrap();
+    let mut lz4 = lz4_flex::frame::FrameEncoder::new(Vec::new());
+    lz4.write_all(&content).unwrap();
+    let streams = vec![(gzip.finish().unwrap(), Compression::Gzip), (zlib.finish().unwrap(), Compression::Zlib), (lz4.finish().unwrap(), Compression::Lz4)];
+
+    for (compressed, compression) in streams
+    {
+      assert!(Compression::detect(&compressed) == Some(compression));
+      let builder = DecompressVFileBuilder::detect(MemoryVFileBuilder::from_buffer(compressed)).unwrap().unwrap();
+      assert!(builder.size() == content.len() as u64);
+
+      let mut file = builder.open().unwrap();
+      let mut buffer = [0; 8];
+      file.seek(SeekFrom::Start(4000)).unwrap();
+      file.read_exact(&mut buffer).unwrap();
+      assert!(buffer == content[4000..4008]);
+      file.seek(SeekFrom::Start(0)).unwrap();
+      let mut decompressed = Vec::new();
+      file.read_to_end(&mut decompressed).unwrap();
+      assert!(decompressed == content);
+    }
+
+    assert!(DecompressVFileBuilder::detect(MemoryVFileBuilder::from_buffer(b"plain text".to_vec())).unwrap().is_none());
+    assert!(DecompressVFileBuilder::new(MemoryVFileBuilder::from_buffer(vec![0x1f, 0x8b, 0, 0]), Compression::Gzip).is_err());
+
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(b"serialized").unwrap();
+    let temp = TempVFileBuilder::new(&gzip.finish().unwrap()).unwrap();
+    let builder : Arc<dyn VFileBuilder> = DecompressVFileBuilder::new(FsVFileBuilder::new(temp.path()).unwrap(), Compression::Gzip).unwrap();
+    let builder : Box<dyn VFileBuilder> = serde_json::from_str(&serde_json::to_string(&builder).unwrap()).unwrap();
+    let mut content = String::new();
+    builder.open().unwrap().read_to_string(&mut content).unwrap();
+    assert!(content == "serialized");
+  }
+
+  #[test]
+  fn decompress_limits()
+  {
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(&vec![0; SPILL_THRESHOLD as usize + 10]).unwrap();
+    let parent = MemoryVFileBuilder::from_buffer(gzip.finish().unwrap());
+
+    let spilled = decompress(parent.as_ref(), Compression::Gzip, SPILL_THRESHOLD * 2).unwrap();
+    assert!(spilled.size() == SPILL_THRESHOLD + 10);
+    assert!(decompress(parent.as_ref(), Compression::Gzip, SPILL_THRESHOLD).is_err());
+    assert!(decompress(parent.as_ref(), Compression::Gzip, 1000).is_err());
+
+    //deserialized builders are decompressed on first use
+    let temp = TempVFileBuilder::new(&[0x78, 0x9c, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01]).unwrap();
+    let builder : Arc<dyn VFileBuilder> = DecompressVFileBuilder::new(FsVFileBuilder::new(temp.path()).unwrap(), Compression::Zlib).unwrap();
+    let serialized = serde_json::to_string(&builder).unwrap();
+    std::fs::write(temp.path(), [0x78, 0x9c, 0xff, 0xff]).unwrap();
+    let builder : Box<dyn VFileBuilder> = serde_json::from_str(&serialized).unwrap();
+    assert!(builder.open().is_err() && builder.size() == 0);
+  }
+
+  #[cfg(feature = "zstd")]
+  #[test]
+  fn decompress_zstd()
+  {
+    let compressed = zstd::stream::encode_all(&b"zstandard"[..], 3).unwrap();
+    let builder = DecompressVFileBuilder::detect(MemoryVFileBuilder::from_buffer(compressed)).unwrap().unwrap();
+    assert!(builder.compression() == Compression::Zstd);
+    let mut content = Vec::new();
+    builder.open().unwrap().read_to_end(&mut content).unwrap();
+    assert!(content == b"zstandard");
+  }
+}
@@ -0,0 +1,272 @@
+//! Content-defined deduplication of the blobs of a [BlobStore](crate::stagingvfile::BlobStore).
+//!
+//! The content of a blob is cut into chunks where a rolling hash of the last bytes match a pattern, so identical data
+//! shared by different artifacts (carved files overlapping, extracted copies) is cut into the same chunks whatever its offset.
+//! Each chunk is stored once, named by its [blake3] digest, and a blob is stored as a [Manifest] listing its chunks.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::error::RustructError;
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+
+/// Minimum size of a chunk.
+pub const MIN_CHUNK_SIZE : usize = 2 * 1024;
+/// Maximum size of a chunk.
+pub const MAX_CHUNK_SIZE : usize = 64 * 1024;
+/// A cut is made when the masked bits of the rolling hash are zero, for chunks of 8KiB in average.
+const CUT_MASK : u64 = (1 << 13) - 1;
+
+/// Random values of the gear rolling hash, generated with splitmix64.
+const GEAR : [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256]
+{
+  let mut table = [0; 256];
+  let mut state : u64 = 0x7461_7064_6564_7570;
+  let mut index = 0;
+  while index < 256
+  {
+    state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut value = state;
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    table[index] = value ^ (value >> 31);
+    index += 1;
+  }
+  table
+}
+
+/// Return the size of the first chunk of `data`, `data` must hold [MAX_CHUNK_SIZE] bytes unless it's the end of the content.
+fn cut(data : &[u8]) -> usize
+{
+  let end = data.len().min(MAX_CHUNK_SIZE);
+  if end <= MIN_CHUNK_SIZE
+  {
+    return end
+  }
+
+  let mut hash : u64 = 0;
+  for (index, byte) in data[..end].iter().enumerate().skip(MIN_CHUNK_SIZE - 64)
+  {
+    hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+    if index >= MIN_CHUNK_SIZE && hash & CUT_MASK == 0
+    {
+      return index + 1
+    }
+  }
+  end
+}
+
+/// Read `reader` until its end and pass each content-defined chunk to `callback`, return the number of bytes read.
+pub fn split<R, F>(reader : &mut R, mut callback : F) -> Result<u64>
+  where R : Read + ?Sized,
+        F : FnMut(&[u8]) -> Result<()>
+{
+  let mut pending = Vec::with_capacity(MAX_CHUNK_SIZE * 2);
+  let mut buffer = vec![0; MAX_CHUNK_SIZE];
+  let mut total = 0;
+
+  loop
+  {
+    let size = match reader.read(&mut buffer)
+    {
+      Ok(size) => size,
+      Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+      Err(err) => return Err(err.into()),
+    };
+    total += size as u64;
+    pending.extend_from_slice(&buffer[..size]);
+
+    while pending.len() >= MAX_CHUNK_SIZE || (size == 0 && !pending.is_empty())
+    {
+      let chunk_size = cut(&pending);
+      callback(&pending[..chunk_size])?;
+      pending.drain(..chunk_size);
+    }
+    if size == 0
+    {
+      return Ok(total)
+    }
+  }
+}
+
+/// A chunk of a blob, identified by the hexadecimal [blake3] digest of its content.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunk
+{
+  pub hash : String,
+  pub size : u64,
+}
+
+impl Chunk
+{
+  /// Return the chunk of `data`.
+  pub fn new(data : &[u8]) -> Self
+  {
+    Chunk{ hash : blake3::hash(data).to_hex().to_string(), size : data.len() as u64 }
+  }
+}
+
+/// List of the chunks of a deduplicated blob.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest
+{
+  pub size : u64,
+  pub chunks : Vec<Chunk>,
+}
+
+impl Manifest
+{
+  /// Read the manifest at `path`.
+  pub fn load(path : &Path) -> Result<Self>
+  {
+    let data = fs::read(path).map_err(|err| RustructError::OpenFile(format!("{} : {}", path.display(), err)))?;
+    Ok(serde_json::from_slice(&data)?)
+  }
+
+  /// Write the manifest at `path`.
+  pub fn save(&self, path : &Path) -> Result<()>
+  {
+    fs::write(path, serde_json::to_vec(self)?)?;
+    Ok(())
+  }
+
+  /// Add `chunk` at the end of the blob.
+  pub fn push(&mut self, chunk : Chunk)
+  {
+    self.size += chunk.size;
+    self.chunks.push(chunk);
+  }
+}
+
+/// Deduplication statistics of a [BlobStore](crate::stagingvfile::BlobStore).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DedupStats
+{
+  /// Number of deduplicated blobs.
+  pub blobs : usize,
+  /// Number of chunks stored.
+  pub chunks : usize,
+  /// Size of the content of the deduplicated blobs.
+  pub logical_bytes : u64,
+  /// Size of the chunks stored.
+  pub stored_bytes : u64,
+}
+
+impl DedupStats
+{
+  /// Return the size saved by the deduplication.
+  pub fn saved_bytes(&self) -> u64
+  {
+    self.logical_bytes.saturating_sub(self.stored_bytes)
+  }
+}
+
+/// Chunks removed by [BlobStore::gc](crate::stagingvfile::BlobStore::gc).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GcReport
+{
+  pub chunks : usize,
+  pub bytes : u64,
+}
+
+/**
+ * [VFile](crate::vfile::VFile) reading a deduplicated blob from its chunks.
+ */
+pub struct ChunkedVFile
+{
+  dir : PathBuf,
+  chunks : Vec<Chunk>,
+  /// Offset of each chunk in the blob.
+  starts : Vec<u64>,
+  size : u64,
+  pos : u64,
+  current : Option<(usize, File)>,
+}
+
+impl ChunkedVFile
+{
+  /// Return a file reading the chunks of `manifest` stored in `dir`.
+  pub fn new(dir : PathBuf, manifest : &Manifest) -> Self
+  {
+    let starts = manifest.chunks.iter().scan(0, |start, chunk| { let current = *start; *start += chunk.size; Some(current) }).collect();
+    ChunkedVFile{ dir, chunks : manifest.chunks.clone(), starts, size : manifest.size, pos : 0, current : None }
+  }
+}
+
+impl Read for ChunkedVFile
+{
+  fn read(&mut self, buf : &mut [u8]) -> io::Result<usize>
+  {
+    if self.pos >= self.size || buf.is_empty()
+    {
+      return Ok(0)
+    }
+
+    let index = self.starts.partition_point(|start| *start <= self.pos) - 1;
+    let file = match &mut self.current
+    {
+      Some((current, file)) if *current == index => file,
+      _ => &mut self.current.insert((index, File::open(self.dir.join(&self.chunks[index].hash))?)).1,
+    };
+    let offset = self.pos - self.starts[index];
+    file.seek(SeekFrom::Start(offset))?;
+    let size = (buf.len() as u64).min(self.chunks[index].size - offset) as usize;
+    let size = file.read(&mut buf[..size])?;
+    if size == 0
+    {
+      return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("chunk {} is truncated", self.chunks[index].hash)))
+    }
+    self.pos += size as u64;
+    Ok(size)
+  }
+}
+
+impl Seek for ChunkedVFile
+{
+  fn seek(&mut self, pos : SeekFrom) -> io::Result<u64>
+  {
+    let pos = match pos
+    {
+      SeekFrom::Start(pos) => Some(pos),
+      SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+      SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+    };
+
+    match pos
+    {
+      Some(pos) => { self.pos = pos; Ok(pos) },
+      None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{split, MIN_CHUNK_SIZE, MAX_CHUNK_SIZE};
+
+  #[test]
+  fn split_content_defined()
+  {
+    let mut seed : u32 = 1;
+    let content : Vec<u8> = (0..300000).map(|_| { seed = seed.wrapping_mul(1103515245).wrapping_add(12345); (seed >> 16) as u8 }).collect();
+
+    let mut chunks = Vec::new();
+    let size = split(&mut content.as_slice(), |chunk| { chunks.push(chunk.to_vec()); Ok(()) }).unwrap();
+    assert!(size == 300000 && chunks.concat() == content);
+    assert!(chunks[..chunks.len() - 1].iter().all(|chunk| chunk.len() >= MIN_CHUNK_SIZE && chunk.len() <= MAX_CHUNK_SIZE));
+
+    //the same data shifted by an insertion is cut into the same chunks after the first cut
+    let mut shifted = b"inserted".to_vec();
+    shifted.extend_from_slice(&content);
+    let mut shifted_chunks = Vec::new();
+    split(&mut shifted.as_slice(), |chunk| { shifted_chunks.push(chunk.to_vec()); Ok(()) }).unwrap();
+    let shared = shifted_chunks.iter().filter(|chunk| chunks.contains(chunk)).count();
+    assert!(shared >= chunks.len() - 2);
+  }
+}
@@ -0,0 +1,140 @@
+//! Detection of the type of the content of a node, to find the plugins able to parse it.
+//!
+//! Plugins advertise the [signatures](Signature) of the contents they parse with [PluginInfo::signatures](crate::plugin::PluginInfo::signatures),
+//! [detect] match them against the start of the data, the name of its node and its size,
+//! and [Session::auto_parse](crate::session::Session::auto_parse) schedule the plugins with the best score.
+
+use std::io::Read;
+
+use crate::plugins_db::PluginsDB;
+use crate::vfile::VFileBuilder;
+
+use anyhow::Result;
+
+/// Maximum number of bytes read at the start of the data to match the magics.
+pub const MAX_HEAD_SIZE : u64 = 64 * 1024;
+
+/// A pattern identifying a type of content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signature
+{
+  /// `bytes` found at `offset`, matching a magic scores its length.
+  Magic{ offset : u64, bytes : &'static [u8] },
+  /// Extension of the node name compared case insensitively, matching an extension scores 1.
+  Extension(&'static str),
+  /// Size of the data between `min` included and `max` excluded, a data of another size never match.
+  Size{ min : u64, max : u64 },
+}
+
+/// A plugin whose [signatures](Signature) matched a content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Detection
+{
+  pub plugin : &'static str,
+  /// Best score of the magics and extension that matched.
+  pub score : usize,
+}
+
+/// Return the score of `signatures` for the content starting with `head` of `size` bytes of the node `name`,
+/// or `None` if no magic nor extension match or if a size doesn't match.
+pub fn score(signatures : &[Signature], head : &[u8], size : u64, name : &str) -> Option<usize>
+{
+  let extension = name.rsplit_once('.').map(|(_, extension)| extension);
+  let mut best = None;
+  for signature in signatures
+  {
+    match *signature
+    {
+      Signature::Magic{ offset, bytes } =>
+      {
+        let start = offset.min(MAX_HEAD_SIZE) as usize;
+        if head.get(start..start + bytes.len()) == Some(bytes)
+        {
+          best = best.max(Some(bytes.len()));
+        }
+      },
+      Signature::Extension(expected) =>
+      {
+        if extension.is_some_and(|extension| extension.eq_ignore_ascii_case(expected))
+        {
+          best = best.max(Some(1));
+        }
+      },
+      Signature::Size{ min, max } =>
+      {
+        if size < min || size >= max
+        {
+          return None
+        }
+      },
+    }
+  }
+  best
+}
+
+/// Return the plugins of `plugins_db` whose signatures match the `data` of the node `name`, sorted by decreasing score.
+pub fn detect(plugins_db : &PluginsDB, name : &str, data : &dyn VFileBuilder) -> Result<Vec<Detection>>
+{
+  let candidates : Vec<(&'static str, Vec<Signature>)> = plugins_db.iter().map(|plugin_info| (plugin_info.name(), plugin_info.signatures()))
+    .filter(|(_, signatures)| !signatures.is_empty()).collect();
+
+  //read only the bytes needed by the magics
+  let head_size = candidates.iter().flat_map(|(_, signatures)| signatures.iter()).filter_map(|signature| match signature
+  {
+    Signature::Magic{ offset, bytes } => Some(offset.saturating_add(bytes.len() as u64)),
+    _ => None,
+  }).max().unwrap_or(0).min(MAX_HEAD_SIZE);
+  let mut head = Vec::new();
+  if head_size != 0
+  {
+    data.open()?.take(head_size).read_to_end(&mut head)?;
+  }
+
+  let mut detections : Vec<Detection> = candidates.iter().filter_map(|(plugin, signatures)|
+    score(signatures, &head, data.size(), name).map(|score| Detection{ plugin, score })).collect();
+  detections.sort_by_key(|detection| std::cmp::Reverse(detection.score));
+  Ok(detections)
+}
+
+/// Return the plugins of `detections` with the best score, a magic shared by several types like the boot sector signature
+/// is so superseded by a longer magic identifying a more specific type.
+pub fn best_matches(detections : &[Detection]) -> Vec<&'static str>
+{
+  let best = detections.iter().map(|detection| detection.score).max();
+  detections.iter().filter(|detection| Some(detection.score) == best).map(|detection| detection.plugin).collect()
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{Signature, detect, best_matches, score};
+  use crate::plugins_db::PluginsDB;
+  use crate::plugin_partition;
+  use crate::plugin_fat;
+  use crate::memoryvfile::MemoryVFileBuilder;
+
+  #[test]
+  fn detect_content()
+  {
+    let mut plugins_db = PluginsDB::new();
+    plugins_db.register(Box::new(plugin_partition::Plugin::new()));
+    plugins_db.register(Box::new(plugin_fat::Plugin::new()));
+
+    let mut disk = vec![0; 4096];
+    disk[510..512].copy_from_slice(&[0x55, 0xaa]);
+    let detections = detect(&plugins_db, "disk.img", MemoryVFileBuilder::from_buffer(disk.clone()).as_ref()).unwrap();
+    assert!(best_matches(&detections) == vec!["partition"]);
+
+    //a FAT boot sector also end with the MBR signature
+    disk[54..62].copy_from_slice(b"FAT16   ");
+    let detections = detect(&plugins_db, "volume", MemoryVFileBuilder::from_buffer(disk).as_ref()).unwrap();
+    assert!(detections.len() == 2 && best_matches(&detections) == vec!["fat"]);
+    assert!(detect(&plugins_db, "empty", MemoryVFileBuilder::from_buffer(vec![0; 4096]).as_ref()).unwrap().is_empty());
+
+    let signatures = [Signature::Extension("zip"), Signature::Magic{ offset : 0, bytes : b"PK\x03\x04" }, Signature::Size{ min : 22, max : u64::MAX }];
+    assert!(score(&signatures, b"", 100, "archive.ZIP") == Some(1));
+    assert!(score(&signatures, b"PK\x03\x04", 100, "archive") == Some(4));
+    assert!(score(&signatures, b"PK\x03\x04", 10, "archive.zip").is_none());
+    assert!(score(&signatures, b"PK", 100, "archive.zip.txt").is_none());
+  }
+}
@@ -0,0 +1,213 @@
+//! Health-check of a [Session] returned by [Session::diagnostics], aggregating the state of the scheduler,
+//! the contention of the main locks, the memory used, the broken references and the last errors in one serializable structure.
+
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use crate::session::Session;
+use crate::task_scheduler::{TaskId, TaskState};
+use crate::tree::TreeStats;
+use crate::result_store::ResultStats;
+use crate::blockcache::CacheStats;
+use crate::reference::BrokenReference;
+
+use serde::{Serialize, Deserialize};
+
+/// Options of [Session::diagnostics].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagnosticsOptions
+{
+  /// Duration after which a running task is reported as stuck.
+  pub stuck_after : Duration,
+  /// Maximum number of errors reported, the most recent are kept.
+  pub recent_errors : usize,
+}
+
+impl Default for DiagnosticsOptions
+{
+  fn default() -> Self
+  {
+    DiagnosticsOptions{ stuck_after : Duration::from_secs(600), recent_errors : 20 }
+  }
+}
+
+/// Contention of a lock measured when the diagnostics were collected.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockStats
+{
+  /// Name of the lock.
+  pub name : String,
+  /// True if the lock was held for writing.
+  pub write_locked : bool,
+  /// Time waited to acquire the lock for reading.
+  pub wait : Duration,
+}
+
+/// Measure the contention of `lock` by acquiring it for reading.
+pub(crate) fn probe_lock<T>(name : &str, lock : &RwLock<T>) -> LockStats
+{
+  let write_locked = lock.try_read().is_err();
+  let start = Instant::now();
+  drop(lock.read());
+  LockStats{ name : name.to_string(), write_locked, wait : start.elapsed() }
+}
+
+/// A task running for longer than [DiagnosticsOptions::stuck_after].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StuckTask
+{
+  /// Id of the task.
+  pub id : TaskId,
+  /// Name of the plugin run by the task.
+  pub plugin_name : String,
+  /// Time since the task was launched.
+  pub running_for : Duration,
+}
+
+/// A task finished with an error.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskError
+{
+  /// Id of the task.
+  pub id : TaskId,
+  /// Name of the plugin run by the task.
+  pub plugin_name : String,
+  /// Error returned by the task.
+  pub error : String,
+}
+
+/// State of the [TaskScheduler](crate::task_scheduler::TaskScheduler).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SchedulerDiagnostics
+{
+  /// Number of tasks waiting to be launched.
+  pub waiting : usize,
+  /// Number of running tasks.
+  pub running : usize,
+  /// Number of tasks finished successfully.
+  pub finished : usize,
+  /// Number of tasks finished with an error.
+  pub failed : usize,
+  /// Number of tasks waiting for a free worker.
+  pub queue_depth : usize,
+  /// Tasks running for longer than [DiagnosticsOptions::stuck_after].
+  pub stuck : Vec<StuckTask>,
+}
+
+/**
+ * Health report of a [Session] returned by [Session::diagnostics].
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostics
+{
+  /// State of the scheduler.
+  pub scheduler : SchedulerDiagnostics,
+  /// Contention of the tree and tasks locks.
+  pub locks : Vec<LockStats>,
+  /// Memory used by the tree.
+  pub tree : TreeStats,
+  /// Memory and disk used by the task results.
+  pub results : ResultStats,
+  /// Hit rate and size of the block cache.
+  pub cache : CacheStats,
+  /// References to nodes missing from the tree.
+  pub broken_references : Vec<BrokenReference>,
+  /// Last errors returned by the tasks, by decreasing task id.
+  pub recent_errors : Vec<TaskError>,
+}
+
+impl Diagnostics
+{
+  /// Return true if no task is stuck and no reference is broken.
+  pub fn is_healthy(&self) -> bool
+  {
+    self.scheduler.stuck.is_empty() && self.broken_references.is_empty()
+  }
+}
+
+impl Session
+{
+  /// Return the [Diagnostics] of the session, collected with `options`.
+  pub fn diagnostics(&self, options : &DiagnosticsOptions) -> Diagnostics
+  {
+    let locks = vec![self.tree.lock_stats(), self.task_scheduler.lock_stats()];
+
+    let mut scheduler = SchedulerDiagnostics{ queue_depth : self.task_scheduler.queue_len(), ..SchedulerDiagnostics::default() };
+    let mut recent_errors = Vec::new();
+    for state in self.task_scheduler.to_vec()
+    {
+      match state
+      {
+        TaskState::Waiting(_) => scheduler.waiting += 1,
+        TaskState::Launched(_) => scheduler.running += 1,
+        TaskState::Finished(_, Ok(_)) => scheduler.finished += 1,
+        TaskState::Finished(task, Err(error)) =>
+        {
+          scheduler.failed += 1;
+          recent_errors.push(TaskError{ id : task.id, plugin_name : task.plugin_name, error : error.to_string() });
+        },
+      }
+    }
+    recent_errors.sort_unstable_by_key(|error| std::cmp::Reverse(error.id));
+    recent_errors.truncate(options.recent_errors);
+
+    for (id, running_for) in self.task_scheduler.running()
+    {
+      if running_for >= options.stuck_after
+      {
+        if let Some(TaskState::Launched(task)) = self.task_scheduler.task(id)
+        {
+          scheduler.stuck.push(StuckTask{ id, plugin_name : task.plugin_name, running_for });
+        }
+      }
+    }
+
+    Diagnostics{ scheduler, locks, tree : self.tree.stats(), results : self.task_scheduler.result_stats(),
+                 cache : self.task_scheduler.block_cache().stats(),
+                 broken_references : self.tree.check_references().broken, recent_errors }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::DiagnosticsOptions;
+  use crate::session::Session;
+  use crate::plugin_dummy;
+  use crate::node::Node;
+  use crate::value::Value;
+
+  use std::time::Duration;
+  use serde_json::json;
+
+  #[test]
+  fn session_diagnostics()
+  {
+    let mut session = Session::new();
+    session.plugins_db.register(Box::new(plugin_dummy::Plugin::new()));
+
+    let argument = json!({"parent" : session.tree.root_id, "file_name" : "/home/user/test.txt", "offset" : 0}).to_string();
+    session.run("dummy", argument.clone(), false).unwrap();
+    session.schedule("dummy", argument, true).unwrap();
+    session.join();
+
+    let removed_id = session.tree.add_child(session.tree.root_id, Node::new("removed")).unwrap();
+    let link = Node::new("link");
+    link.value().add_attribute("target", Value::NodeId(removed_id), None);
+    session.tree.add_child(session.tree.root_id, link).unwrap();
+    session.tree.remove(removed_id).unwrap();
+
+    let diagnostics = session.diagnostics(&DiagnosticsOptions::default());
+    assert!(diagnostics.scheduler.finished + diagnostics.scheduler.failed == 2);
+    assert!(diagnostics.scheduler.running == 0 && diagnostics.scheduler.stuck.is_empty());
+    assert!(diagnostics.recent_errors.len() == diagnostics.scheduler.failed);
+    assert!(diagnostics.locks.len() == 2 && diagnostics.locks.iter().all(|lock| !lock.write_locked));
+    assert!(diagnostics.tree.nodes > 1);
+    assert!(diagnostics.broken_references.len() == 1 && diagnostics.broken_references[0].target == removed_id);
+    assert!(!diagnostics.is_healthy());
+
+    let options = DiagnosticsOptions{ stuck_after : Duration::ZERO, recent_errors : 0 };
+    let json = serde_json::to_string(&session.diagnostics(&options)).unwrap();
+    assert!(json.contains("queue_depth") && json.contains("hits"));
+  }
+}
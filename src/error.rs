@@ -9,9 +9,30 @@ pub enum RustructError
   #[error("Plugin {name} not found")]
   PluginNotFound { name : String, },
 
+  #[error("Plugin {0} is incompatible : {1}")]
+  IncompatiblePlugin(String, String),
+
   #[error("Same plugin with same argument already runned")]
   PluginAlreadyRunned,
 
+  #[error("Invalid version {0}")]
+  InvalidVersion(String),
+
+  #[error("No version of plugin {0} match {1}")]
+  VersionNotFound(String, String),
+
+  #[error("Preset {1} of plugin {0} not found")]
+  PresetNotFound(String, String),
+
+  #[error("Plugins dependencies form a cycle : {0}")]
+  DependencyCycle(String),
+
+  #[error("Dependency {0} failed : {1}")]
+  DependencyFailed(String, String),
+
+  #[error("Plugin {0} has no file nor parent argument to apply it to a node")]
+  NoTargetArgument(String),
+
   #[error("Plugin {0} error {1}")]
   PluginError(&'static str, &'static str),
 
@@ -21,6 +42,27 @@ pub enum RustructError
   #[error("Task {0} not found")] 
   TaskNotFound(u32),
 
+  #[error("Task {0} was not finished when the session was saved")]
+  TaskInterrupted(u32),
+
+  #[error("Task was cancelled")]
+  Cancelled,
+
+  #[error("Task {0} timed out after {1:?}")]
+  TimedOut(u32, std::time::Duration),
+
+  #[error("Plugin {0} exceeded its {1:?} quota")]
+  QuotaExceeded(String, crate::quota::Resource),
+
+  #[error("Service {0} not found")]
+  ServiceNotFound(String),
+
+  #[error("Worker pool {0} not found")]
+  PoolNotFound(String),
+
+  #[error("Node {0} not found")]
+  NodeNotFound(String),
+
   #[error("Result for task {0} not found")]
   ResultNotFound(u32),
 
@@ -39,6 +81,36 @@ pub enum RustructError
   #[error("Error opening file {0}")]
   OpenFile(String),
 
+  #[error("Node {0} has no refreshable source")]
+  NodeNotRefreshable(String),
+
+  #[error("External tool {0} error : {1}")]
+  ExternalTool(String, String),
+
+  #[error("Invalid encoded data : {0}")]
+  InvalidEncoding(String),
+
+  #[error("Can't parse {0} : {1}")]
+  Parse(&'static str, String),
+
+  #[error("Query {0} not found")]
+  QueryNotFound(String),
+
+  #[error("Field {0} is read only")]
+  ReadOnlyField(String),
+
+  #[error("Invalid argument for {0} : {1}")]
+  InvalidArgument(String, String),
+
+  #[error("Replica at version {0} can't apply changes since version {1}")]
+  ReplicaOutOfSync(u64, u64),
+
+  #[error("User {0} is not allowed to {1}")]
+  AccessDenied(String, String),
+
+  #[error("Serialization error : {0}")]
+  Serialize(String),
+
   #[error("Error {0}")]
   Unknown(String),
 }
@@ -1,22 +1,65 @@
 //! Events let send and receive data trough channel.
+//!
+//! Each event sent is numbered and the last ones are kept in a bounded history,
+//! so subscribers attaching late or reconnecting can replay the events they missed with [EventChannel::subscribe_from].
+
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
 
 use crossbeam::crossbeam_channel::{unbounded, Sender, Receiver};
+use serde::{Serialize, Deserialize};
+
+/// Default number of events kept in the history of an [EventChannel].
+pub const HISTORY_SIZE : usize = 1024;
+
+/// An event and its sequence number, starting at 1 for the first event sent on a channel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sequenced<T>
+{
+  pub seq : u64,
+  pub event : T,
+}
+
+/// Last events sent on a channel and the subscribers receiving them with their sequence number.
+struct History<T>
+{
+  capacity : usize,
+  last_seq : u64,
+  events : VecDeque<Sequenced<T>>,
+  subscribers : Vec<Sender<Sequenced<T>>>,
+}
 
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct EventChannel<T>
 {
   pub registered : Vec<Sender<T>>,
+  history : Arc<RwLock<History<T>>>,
+}
+
+impl<T : Clone> Default for EventChannel<T>
+{
+  fn default() -> Self
+  {
+    EventChannel::with_history(HISTORY_SIZE)
+  }
 }
 
 impl<T : Clone> EventChannel<T>
 {
   pub fn new() -> Self
   {
-    EventChannel::<T>{ registered : Vec::new() }
+    EventChannel::default()
+  }
+
+  /// Return a new channel keeping the last `capacity` events in its history.
+  pub fn with_history(capacity : usize) -> Self
+  {
+    let history = History{ capacity, last_seq : 0, events : VecDeque::new(), subscribers : Vec::new() };
+    EventChannel::<T>{ registered : Vec::new(), history : Arc::new(RwLock::new(history)) }
   }
- 
+
   /// Return a new events receiver
-  pub fn register(&mut self) -> Events<T> 
+  pub fn register(&mut self) -> Events<T>
   {
     let (sender, receiver) = unbounded();
     self.registered.push(sender);
@@ -24,6 +67,40 @@ impl<T : Clone> EventChannel<T>
     Events{ receiver }
   }
 
+  /// Return a receiver of the events sent after this call with their sequence number.
+  pub fn subscribe(&self) -> Events<Sequenced<T>>
+  {
+    self.subscribe_from(u64::MAX)
+  }
+
+  /// Return a receiver of the events with a sequence number greater or equal to `seq` : the events still in the history
+  /// are received first, then the events sent after this call. If the first event received has a sequence number
+  /// greater than `seq`, the events in between were dropped from the history.
+  pub fn subscribe_from(&self, seq : u64) -> Events<Sequenced<T>>
+  {
+    let (sender, receiver) = unbounded();
+    let mut history = self.history.write().unwrap();
+    for event in history.events.iter().filter(|event| event.seq >= seq)
+    {
+      let _ = sender.send(event.clone());
+    }
+    history.subscribers.push(sender);
+
+    Events{ receiver }
+  }
+
+  /// Return the sequence number of the last event sent, or 0 if no event was sent.
+  pub fn last_seq(&self) -> u64
+  {
+    self.history.read().unwrap().last_seq
+  }
+
+  /// Return the events of the history with a sequence number greater or equal to `seq`.
+  pub fn history_from(&self, seq : u64) -> Vec<Sequenced<T>>
+  {
+    self.history.read().unwrap().events.iter().filter(|event| event.seq >= seq).cloned().collect()
+  }
+
   /// Send event
   pub fn update(&self, event : T)
   {
@@ -31,11 +108,25 @@ impl<T : Clone> EventChannel<T>
     {
       handler.send(event.clone()).unwrap()
     }
+
+    let mut history = self.history.write().unwrap();
+    history.last_seq += 1;
+    let event = Sequenced{ seq : history.last_seq, event };
+    //subscribers that were dropped are removed
+    history.subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    if history.capacity > 0
+    {
+      if history.events.len() == history.capacity
+      {
+        history.events.pop_front();
+      }
+      history.events.push_back(event);
+    }
   }
 }
 
 /**
- *  Events receiver 
+ *  Events receiver
  **/
 pub struct Events<T>
 {
@@ -55,3 +146,36 @@ impl<T> Events<T>
     events
   }
 }
+
+#[cfg(test)]
+mod tests
+{
+  use super::EventChannel;
+
+  #[test]
+  fn replay_history()
+  {
+    let mut channel = EventChannel::with_history(3);
+    let live = channel.subscribe();
+    let events = channel.register();
+    for event in 1..=5u32
+    {
+      channel.update(event);
+    }
+    assert!(channel.last_seq() == 5);
+    assert!(events.events() == vec![1, 2, 3, 4, 5]);
+    assert!(live.events().iter().map(|event| event.seq).collect::<Vec<u64>>() == vec![1, 2, 3, 4, 5]);
+
+    let late = channel.subscribe_from(4);
+    let lost = channel.subscribe_from(1);
+    channel.update(6);
+    assert!(late.events().iter().map(|event| event.event).collect::<Vec<u32>>() == vec![4, 5, 6]);
+    assert!(lost.events()[0].seq == 3);
+    assert!(channel.subscribe().events().is_empty());
+    assert!(channel.history_from(0).len() == 3);
+
+    drop(live);
+    channel.update(7);
+    assert!(late.events().len() == 1);
+  }
+}
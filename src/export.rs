@@ -0,0 +1,258 @@
+//! Export let you serialize the nodes of a [Tree] that changed since a given version,
+//! so remote clients can keep a mirror of the tree up to date without transfering it entirely.
+//! Exported values are truncated according to the global [DisplayLimits] unless the export is explicitly [untruncated](DisplayLimits::unlimited).
+
+use crate::tree::{Tree, TreeNodeId, NodeState};
+use crate::attribute::Attributes;
+use crate::access::Access;
+use crate::value::{Value, DisplayLimits, display_limits};
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+/// Format used to encode an export.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ExportFormat
+{
+  /// Export as a JSON document.
+  Json,
+}
+
+/**
+ * A [node](crate::node::Node) added or updated in the tree.
+ * The node `id` is its [uuid](crate::node::Node::uuid), which stay the same when the node is encoded and decoded,
+ * and can be used by remote mirrors to identify the node.
+ * An added node contain all its attributes in `added`, an updated node only the attributes that changed.
+ */
+#[derive(Serialize)]
+pub struct NodeDelta
+{
+  /// Uuid of the node.
+  pub id : Uuid,
+  /// Uuid of the parent of the node.
+  pub parent : Option<Uuid>,
+  /// Name of the node.
+  pub name : String,
+  /// Original bytes of the name if they are different from `name`, see [Node::raw_name](crate::node::Node::raw_name).
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub raw_name : Option<Vec<u8>>,
+  /// Tree version at which the node was last modified.
+  pub version : u64,
+  /// Attributes added since version `since`.
+  pub added : Attributes,
+  /// Attributes which value was updated since version `since`.
+  pub updated : Attributes,
+  /// Name of the attributes removed since version `since`.
+  pub removed : Vec<String>,
+}
+
+/**
+ * A changeset containing all the nodes added, updated and removed between version `since` and `version` of a [Tree].
+ */
+#[derive(Serialize)]
+pub struct Delta
+{
+  /// Version from which the changes were collected.
+  pub since : u64,
+  /// Version of the tree when the delta was generated, must be passed as `since` to get the next delta.
+  pub version : u64,
+  /// Nodes added since version `since`.
+  pub added : Vec<NodeDelta>,
+  /// Nodes updated since version `since`.
+  pub updated : Vec<NodeDelta>,
+  /// Uuid of the nodes removed since version `since`.
+  pub removed : Vec<Uuid>,
+}
+
+impl Delta
+{
+  /// Collect all the changes made to `tree` since version `since`.
+  pub fn new(tree : &Tree, since : u64) -> Self
+  {
+    let version = tree.version();
+    let mut delta = Delta{ since, version, added : Vec::new(), updated : Vec::new(), removed : Vec::new() };
+
+    for (node_id, node_version, state) in tree.changed_since(since)
+    {
+      //node can have been removed by an other thread since we get the changes
+      let node = match tree.get_node_from_id(node_id)
+      {
+        Some(node) if state != NodeState::Removed => node,
+        _ => { delta.removed.extend(tree.node_uuid(node_id)); continue },
+      };
+
+      let mut node_delta = NodeDelta{ id : node.uuid(), parent : tree.parent_id(node_id).and_then(|parent_id| tree.node_uuid(parent_id)),
+                                     name : node.name(), raw_name : node.raw_name().map(|raw| raw.to_vec()), version : node_version,
+                                     added : Attributes::new(), updated : Attributes::new(), removed : Vec::new() };
+      match state
+      {
+        NodeState::Added =>
+        {
+          node_delta.added = node.value();
+          delta.added.push(node_delta);
+        },
+        _ =>
+        {
+          Self::attributes_changes(tree, node_id, &node.value(), since, &mut node_delta);
+          delta.updated.push(node_delta);
+        },
+      }
+    }
+    delta
+  }
+
+  /// Fill `node_delta` with the attributes of `node_id` changed since version `since`.
+  fn attributes_changes(tree : &Tree, node_id : TreeNodeId, attributes : &Attributes, since : u64, node_delta : &mut NodeDelta)
+  {
+    for (name, _, state) in tree.changed_attributes_since(node_id, since)
+    {
+      let changes = match state
+      {
+        NodeState::Added => &mut node_delta.added,
+        NodeState::Updated => &mut node_delta.updated,
+        NodeState::Removed => { node_delta.removed.push(name); continue },
+      };
+      //attribute can have been removed since we get the changes
+      if let Some(attribute) = attributes.get_attribute(&name)
+      {
+        changes.add_attribute(name, attribute.value().clone(), None);
+      }
+    }
+  }
+
+  /// Return true if there is no change in this delta.
+  pub fn is_empty(&self) -> bool
+  {
+    self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+  }
+
+  /// Truncate the values of the attributes according to `limits`, see [Value::truncated].
+  pub fn truncate(&mut self, limits : &DisplayLimits)
+  {
+    let truncate = |attributes : &Attributes| Value::Attributes(attributes.clone()).truncated(limits).as_attributes();
+    for node in self.added.iter_mut().chain(self.updated.iter_mut())
+    {
+      node.added = truncate(&node.added);
+      node.updated = truncate(&node.updated);
+    }
+  }
+}
+
+/// Return an encoded changeset of all nodes and attributes of `tree` that changed since version `since_version`.
+/// Values are truncated according to the global [DisplayLimits], use [delta_with_limits] to export them entirely.
+pub fn delta(tree : &Tree, since_version : u64, format : ExportFormat) -> Result<Vec<u8>>
+{
+  delta_with_limits(tree, since_version, format, &display_limits())
+}
+
+/// Return an encoded changeset like [delta] with values truncated according to `limits`,
+/// values are exported entirely with [DisplayLimits::unlimited].
+pub fn delta_with_limits(tree : &Tree, since_version : u64, format : ExportFormat, limits : &DisplayLimits) -> Result<Vec<u8>>
+{
+  tree.authorize(Access::Export, Some(tree.root_id))?;
+  let mut delta = Delta::new(tree, since_version);
+  if *limits != DisplayLimits::unlimited()
+  {
+    delta.truncate(limits);
+  }
+
+  match format
+  {
+    ExportFormat::Json => Ok(serde_json::to_vec(&delta)?),
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{delta, delta_with_limits, Delta, ExportFormat};
+  use crate::tree::Tree;
+  use crate::node::Node;
+  use crate::value::{Value, DisplayLimits};
+
+  #[test]
+  fn delta_since_version()
+  {
+    let tree = Tree::new();
+    let first_id = tree.add_child(tree.root_id, Node::new("first")).unwrap();
+    let version = tree.version();
+
+    let second = Node::new("second");
+    second.value().add_attribute("size", Value::U64(0x1000), None);
+    let second_id = tree.add_child(first_id, second).unwrap();
+
+    let changes = Delta::new(&tree, version);
+    assert!(changes.added.len() == 1);
+    assert!(changes.added[0].id == tree.get_node_from_id(second_id).unwrap().uuid());
+    assert!(changes.added[0].parent == tree.node_uuid(first_id));
+    assert!(changes.updated.is_empty() && changes.removed.is_empty());
+    assert!(Delta::new(&tree, changes.version).is_empty());
+
+    let json : serde_json::Value = serde_json::from_slice(&delta(&tree, version, ExportFormat::Json).unwrap()).unwrap();
+    assert!(json["added"][0]["name"] == "second");
+    assert!(json["added"][0]["added"]["size"] == 0x1000);
+
+    let uuids = [tree.node_uuid(first_id).unwrap(), tree.node_uuid(second_id).unwrap()];
+    let version = tree.version();
+    tree.remove(first_id).unwrap();
+    let changes = Delta::new(&tree, version);
+    assert!(changes.removed.len() == 2 && uuids.iter().all(|uuid| changes.removed.contains(uuid)));
+  }
+
+  #[test]
+  fn delta_attributes_changes()
+  {
+    let tree = Tree::new();
+    let node = Node::new("file");
+    node.value().add_attribute("size", Value::U64(0x1000), None);
+    node.value().add_attribute("deleted", Value::Bool(false), None);
+    let node_id = tree.add_child(tree.root_id, node).unwrap();
+    let version = tree.version();
+
+    //attributes changes are tracked without touching the node
+    let mut attributes = tree.get_node_from_id(node_id).unwrap().value();
+    attributes.add_attribute("name", Value::from("file.txt"), None);
+    attributes.set_value("size", Value::U64(0x2000));
+    attributes.remove_attribute("deleted");
+
+    let changes = Delta::new(&tree, version);
+    assert!(changes.added.is_empty() && changes.updated.len() == 1);
+    let updated = &changes.updated[0];
+    assert!(updated.id == tree.node_uuid(node_id).unwrap());
+    assert!(updated.added.names() == vec!["name"]);
+    assert!(matches!(updated.updated.get_value("size"), Some(Value::U64(0x2000))) && updated.updated.count() == 1);
+    assert!(updated.removed == vec!["deleted"]);
+
+    let json : serde_json::Value = serde_json::from_slice(&delta(&tree, version, ExportFormat::Json).unwrap()).unwrap();
+    assert!(json["updated"][0]["id"] == updated.id.to_string());
+    assert!(json["updated"][0]["updated"]["size"] == 0x2000);
+    assert!(json["updated"][0]["removed"][0] == "deleted");
+    assert!(Delta::new(&tree, changes.version).is_empty());
+  }
+
+  #[test]
+  fn delta_truncated()
+  {
+    let limits = DisplayLimits::default();
+    let tree = Tree::new();
+    let node = Node::new("file");
+    node.value().add_attribute("data", Value::Bytes(vec![0x41; limits.max_bytes + 10]), None);
+    node.value().add_attribute("name", Value::String("a".repeat(limits.max_string_length + 5)), None);
+    let node_id = tree.add_child(tree.root_id, node).unwrap();
+
+    let json : serde_json::Value = serde_json::from_slice(&delta(&tree, 0, ExportFormat::Json).unwrap()).unwrap();
+    let attributes = &json["added"][0]["added"];
+    assert!(attributes["data"].as_array().unwrap().len() == limits.max_bytes + 1);
+    assert!(attributes["data"][limits.max_bytes] == "... (10 more)");
+    assert!(attributes["name"].as_str().unwrap().ends_with("a... (5 more)"));
+
+    //the tree is not modified by the truncation
+    assert!(matches!(tree.get_node_from_id(node_id).unwrap().value().get_value("data"), Some(Value::Bytes(data)) if data.len() == limits.max_bytes + 10));
+
+    let json : serde_json::Value = serde_json::from_slice(&delta_with_limits(&tree, 0, ExportFormat::Json, &DisplayLimits::unlimited()).unwrap()).unwrap();
+    let attributes = &json["added"][0]["added"];
+    assert!(attributes["data"].as_array().unwrap().len() == limits.max_bytes + 10);
+    assert!(attributes["name"].as_str().unwrap().len() == limits.max_string_length + 5);
+  }
+}
@@ -0,0 +1,282 @@
+//! [ExternalTool] let plugins run an external decoder as a subprocess in a consistent and safe way :
+//! the input [VFile](crate::vfile::VFile) is streamed to the tool stdin or to a temporary file,
+//! outputs are captured up to a size limit and the tool is killed if it runs longer than a timeout.
+
+use std::io::{self, Read, Write};
+use std::fs::File;
+use std::path::PathBuf;
+use std::process::{Command, Stdio, ExitStatus};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::vfile::VFileBuilder;
+use crate::memoryvfile::MemoryVFileBuilder;
+use crate::attribute::Attributes;
+use crate::value::Value;
+use crate::error::RustructError;
+
+use anyhow::Result;
+
+/// Placeholder replaced by the path of the temporary input file in the tool arguments.
+pub const INPUT_PLACEHOLDER : &str = "{input}";
+
+/// How the input file is passed to the tool.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToolInput
+{
+  /// The input is streamed to the tool stdin.
+  Stdin,
+  /// The input is copied to a temporary file which path replace [INPUT_PLACEHOLDER] in the arguments.
+  TempFile,
+}
+
+/**
+ * Description of an external tool, created with [ExternalTool::new] and configured with the builder methods.
+ */
+#[derive(Debug, Clone)]
+pub struct ExternalTool
+{
+  program : String,
+  args : Vec<String>,
+  input : ToolInput,
+  timeout : Duration,
+  max_output : usize,
+}
+
+impl ExternalTool
+{
+  /// Return a new [ExternalTool] running `program` with its input on stdin, a 60 seconds timeout and outputs limited to 64MiB.
+  pub fn new<S : Into<String>>(program : S) -> Self
+  {
+    ExternalTool{ program : program.into(), args : Vec::new(), input : ToolInput::Stdin, timeout : Duration::from_secs(60), max_output : 64 * 1024 * 1024 }
+  }
+
+  /// Add an argument.
+  pub fn arg<S : Into<String>>(mut self, arg : S) -> Self
+  {
+    self.args.push(arg.into());
+    self
+  }
+
+  /// Add multiple arguments.
+  pub fn args<S : Into<String>, I : IntoIterator<Item = S>>(mut self, args : I) -> Self
+  {
+    self.args.extend(args.into_iter().map(|arg| arg.into()));
+    self
+  }
+
+  /// Set how the input is passed to the tool.
+  pub fn input(mut self, input : ToolInput) -> Self
+  {
+    self.input = input;
+    self
+  }
+
+  /// Set the maximum running time of the tool, it's killed after that.
+  pub fn timeout(mut self, timeout : Duration) -> Self
+  {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Set the maximum size captured for stdout and stderr, output above that limit is discarded.
+  pub fn max_output(mut self, max_output : usize) -> Self
+  {
+    self.max_output = max_output;
+    self
+  }
+
+  /// Run the tool on the content of the file created by `builder` and return its [output](ToolOutput).
+  /// Return an error if the tool can't be launched or if it times out.
+  pub fn run(&self, builder : Arc<dyn VFileBuilder>) -> Result<ToolOutput>
+  {
+    let temp_file = match self.input
+    {
+      ToolInput::TempFile => Some(TempFile::new(&builder)?),
+      ToolInput::Stdin => None,
+    };
+
+    let mut command = Command::new(&self.program);
+    for arg in self.args.iter()
+    {
+      match &temp_file
+      {
+        Some(temp_file) => command.arg(arg.replace(INPUT_PLACEHOLDER, &temp_file.path.to_string_lossy())),
+        None => command.arg(arg),
+      };
+    }
+    command.stdin(if temp_file.is_some() { Stdio::null() } else { Stdio::piped() });
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    let mut child = command.spawn().map_err(|err| RustructError::ExternalTool(self.program.clone(), err.to_string()))?;
+
+    let stdin = child.stdin.take().map(|mut stdin| thread::spawn(move ||
+    {
+      //the tool can exit without reading all its input, so write error are ignored
+      if let Ok(mut file) = builder.open()
+      {
+        let _ = io::copy(&mut file, &mut stdin);
+      }
+    }));
+    let max_output = self.max_output;
+    let stdout = child.stdout.take().map(|stdout| thread::spawn(move || capture(stdout, max_output)));
+    let stderr = child.stderr.take().map(|stderr| thread::spawn(move || capture(stderr, max_output)));
+
+    let start = Instant::now();
+    let status = loop
+    {
+      if let Some(status) = child.try_wait()?
+      {
+        break status;
+      }
+      if start.elapsed() > self.timeout
+      {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(RustructError::ExternalTool(self.program.clone(), format!("timeout after {:?}", self.timeout)).into())
+      }
+      thread::sleep(Duration::from_millis(10));
+    };
+
+    if let Some(stdin) = stdin
+    {
+      let _ = stdin.join();
+    }
+    let (stdout, stdout_truncated) = stdout.map(|stdout| stdout.join().unwrap_or_default()).unwrap_or_default();
+    let (stderr, stderr_truncated) = stderr.map(|stderr| stderr.join().unwrap_or_default()).unwrap_or_default();
+
+    Ok(ToolOutput{ status, stdout, stdout_truncated, stderr, stderr_truncated })
+  }
+}
+
+/// Read `reader` until the end and return at most `max` bytes, and true if the output was truncated.
+fn capture<R : Read>(mut reader : R, max : usize) -> (Vec<u8>, bool)
+{
+  let mut output = Vec::new();
+  let _ = (&mut reader).take(max as u64).read_to_end(&mut output);
+  //drain the pipe so the tool doesn't block writing its output
+  let discarded = io::copy(&mut reader, &mut io::sink()).unwrap_or(0);
+  (output, discarded > 0)
+}
+
+/// Temporary copy of the input file, removed when dropped.
+struct TempFile
+{
+  path : PathBuf,
+}
+
+impl TempFile
+{
+  fn new(builder : &Arc<dyn VFileBuilder>) -> Result<Self>
+  {
+    let path = std::env::temp_dir().join(format!("tap-{}", uuid::Uuid::new_v4()));
+    let temp_file = TempFile{ path };
+    let mut file = File::create(&temp_file.path)?;
+    io::copy(&mut builder.open()?, &mut file)?;
+    file.flush()?;
+    Ok(temp_file)
+  }
+}
+
+impl Drop for TempFile
+{
+  fn drop(&mut self)
+  {
+    let _ = std::fs::remove_file(&self.path);
+  }
+}
+
+/// Output of an [ExternalTool] run.
+#[derive(Debug, Clone)]
+pub struct ToolOutput
+{
+  /// Exit status of the tool.
+  pub status : ExitStatus,
+  /// Captured standard output.
+  pub stdout : Vec<u8>,
+  /// True if the standard output was bigger than the limit.
+  pub stdout_truncated : bool,
+  /// Captured standard error.
+  pub stderr : Vec<u8>,
+  /// True if the standard error was bigger than the limit.
+  pub stderr_truncated : bool,
+}
+
+impl ToolOutput
+{
+  /// Return true if the tool exited successfully.
+  pub fn success(&self) -> bool
+  {
+    self.status.success()
+  }
+
+  /// Return the standard output as a lossy UTF-8 string.
+  pub fn stdout_string(&self) -> String
+  {
+    String::from_utf8_lossy(&self.stdout).into_owned()
+  }
+
+  /// Return the standard error as a lossy UTF-8 string.
+  pub fn stderr_string(&self) -> String
+  {
+    String::from_utf8_lossy(&self.stderr).into_owned()
+  }
+
+  /// Deserialize the standard output as JSON.
+  pub fn stdout_json<T : serde::de::DeserializeOwned>(&self) -> Result<T>
+  {
+    Ok(serde_json::from_slice(&self.stdout)?)
+  }
+
+  /// Return a [VFileBuilder] containing the standard output, to expose data extracted by the tool.
+  pub fn stdout_builder(&self) -> Arc<dyn VFileBuilder>
+  {
+    MemoryVFileBuilder::from_buffer(self.stdout.clone())
+  }
+
+  /// Return the exit code, the standard error and the truncation flags as [Attributes].
+  pub fn to_attributes(&self) -> Attributes
+  {
+    let mut attributes = Attributes::new();
+    attributes.add_attribute("exit_code", Value::Option(self.status.code().map(|code| Box::new(Value::I32(code)))), None);
+    attributes.add_attribute("stderr", Value::String(self.stderr_string()), None);
+    attributes.add_attribute("stdout_truncated", Value::Bool(self.stdout_truncated), None);
+    attributes.add_attribute("stderr_truncated", Value::Bool(self.stderr_truncated), None);
+    attributes
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{ExternalTool, ToolInput};
+  use crate::memoryvfile::MemoryVFileBuilder;
+
+  use std::time::Duration;
+  use std::io::Read;
+
+  #[test]
+  fn external_tool()
+  {
+    let builder = MemoryVFileBuilder::from_buffer(b"external data".to_vec());
+
+    let output = ExternalTool::new("cat").run(builder.clone()).unwrap();
+    assert!(output.success());
+    assert!(output.stdout == b"external data");
+
+    let output = ExternalTool::new("cat").arg("{input}").input(ToolInput::TempFile).max_output(8).run(builder.clone()).unwrap();
+    assert!(output.stdout == b"external" && output.stdout_truncated);
+
+    let mut content = String::new();
+    output.stdout_builder().open().unwrap().read_to_string(&mut content).unwrap();
+    assert!(content == "external");
+
+    let output = ExternalTool::new("sh").args(["-c", "echo error >&2; exit 3"]).run(builder.clone()).unwrap();
+    assert!(!output.success());
+    assert!(output.to_attributes().get_value("stderr").unwrap().as_string() == "error\n");
+
+    assert!(ExternalTool::new("sleep").arg("5").timeout(Duration::from_millis(100)).run(builder.clone()).is_err());
+    assert!(ExternalTool::new("/nonexistent/tool").run(builder).is_err());
+  }
+}
@@ -0,0 +1,126 @@
+//! A [VFileBuilder] reading a file of the host file system.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::vfile::{VFile, VFileBuilder};
+use crate::error::RustructError;
+
+use serde::{Serialize, Deserialize};
+use serde::de::{Deserializer};
+
+/**
+ * Implement a [VFileBuilder] opening a file of the host file system.
+ * The size is read once when the builder is created, it's not updated if the file is modified later.
+ * It's serialized as its path and size, and the size is read again if missing when deserialized.
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct FsVFileBuilder
+{
+  path : PathBuf,
+  size : u64,
+}
+
+impl FsVFileBuilder
+{
+  /// Return a builder opening the file at `path`, or [RustructError::OpenFile] if it's not a readable file.
+  pub fn new<P : AsRef<Path>>(path : P) -> anyhow::Result<Arc<FsVFileBuilder>>
+  {
+    let path = path.as_ref().to_path_buf();
+    let size = file_size(&path)?;
+    Ok(Arc::new(FsVFileBuilder{ path, size }))
+  }
+
+  /// Return the path of the file.
+  pub fn path(&self) -> &Path
+  {
+    &self.path
+  }
+}
+
+/// Return the size of the file at `path`.
+fn file_size(path : &Path) -> anyhow::Result<u64>
+{
+  match fs::metadata(path)
+  {
+    Ok(metadata) if metadata.is_file() => Ok(metadata.len()),
+    Ok(_) => Err(RustructError::OpenFile(format!("{} : not a file", path.display())).into()),
+    Err(err) => Err(RustructError::OpenFile(format!("{} : {}", path.display(), err)).into()),
+  }
+}
+
+#[typetag::serde]
+impl VFileBuilder for FsVFileBuilder
+{
+  fn open(&self) -> anyhow::Result<Box<dyn VFile>>
+  {
+    match File::open(&self.path)
+    {
+      Ok(file) => Ok(Box::new(file)),
+      Err(err) => Err(RustructError::OpenFile(format!("{} : {}", self.path.display(), err)).into()),
+    }
+  }
+
+  fn size(&self) -> u64
+  {
+    self.size
+  }
+}
+
+impl<'de> Deserialize<'de> for FsVFileBuilder
+{
+  fn deserialize<D>(deserializer: D) -> std::result::Result<FsVFileBuilder, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    #[derive(Deserialize)]
+    struct Fields
+    {
+      path : PathBuf,
+      size : Option<u64>,
+    }
+
+    let fields = Fields::deserialize(deserializer)?;
+    let size = match fields.size
+    {
+      Some(size) => size,
+      None => file_size(&fields.path).map_err(serde::de::Error::custom)?,
+    };
+    Ok(FsVFileBuilder{ path : fields.path, size })
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::FsVFileBuilder;
+  use crate::vfile::VFileBuilder;
+  use crate::tempvfile::TempVFileBuilder;
+  use crate::error::RustructError;
+  use std::io::Read;
+  use std::sync::Arc;
+
+  #[test]
+  fn open_host_file()
+  {
+    let temp = TempVFileBuilder::new(b"host file").unwrap();
+    let builder = FsVFileBuilder::new(temp.path()).unwrap();
+    assert!(builder.size() == 9);
+
+    let mut content = Vec::new();
+    builder.open().unwrap().read_to_end(&mut content).unwrap();
+    assert!(content == b"host file");
+
+    let json = serde_json::to_string(&(builder as Arc<dyn VFileBuilder>)).unwrap();
+    let builder : Box<dyn VFileBuilder> = serde_json::from_str(&json).unwrap();
+    assert!(builder.size() == 9);
+    let json = format!("{{\"type\":\"FsVFileBuilder\",\"path\":{}}}", serde_json::to_string(temp.path()).unwrap());
+    let builder : Box<dyn VFileBuilder> = serde_json::from_str(&json).unwrap();
+    assert!(builder.size() == 9);
+
+    let err = FsVFileBuilder::new(std::env::temp_dir()).unwrap_err();
+    assert!(matches!(err.downcast_ref::<RustructError>(), Some(RustructError::OpenFile(_))));
+    assert!(FsVFileBuilder::new(temp.path().with_extension("missing")).is_err());
+  }
+}
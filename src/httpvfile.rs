@@ -0,0 +1,349 @@
+//! A [VFileBuilder] reading a remote file with HTTP range requests, so images stored on a web server
+//! or in an object storage can be analyzed without downloading them first. It requires the `http` feature.
+//!
+//! S3 objects are read through their HTTPS endpoint : public objects with [HttpVFileBuilder::s3],
+//! and private objects by passing a presigned URL to [HttpVFileBuilder::new], as requests are not signed.
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::vfile::{VFile, VFileBuilder};
+use crate::error::RustructError;
+
+use serde::{Serialize, Deserialize};
+use serde::de::{Deserializer};
+
+/// Options of the requests made by a [HttpVFileBuilder].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpOptions
+{
+  /// Minimum size of each range request, the data read in advance is kept to serve the next reads.
+  pub read_ahead : u64,
+  /// Number of times a request is retried after a network error or a server error.
+  pub retries : u32,
+  /// Delay before the first retry, doubled after each retry.
+  pub retry_delay : Duration,
+  /// Timeout of each request.
+  pub timeout : Duration,
+}
+
+impl Default for HttpOptions
+{
+  fn default() -> Self
+  {
+    HttpOptions{ read_ahead : 1024 * 1024, retries : 3, retry_delay : Duration::from_millis(500), timeout : Duration::from_secs(30) }
+  }
+}
+
+/// HTTP client making the range requests of a builder and its files.
+#[derive(Clone)]
+struct Client
+{
+  url : String,
+  options : HttpOptions,
+  agent : ureq::Agent,
+}
+
+impl Client
+{
+  fn new(url : String, options : HttpOptions) -> Self
+  {
+    let agent = ureq::AgentBuilder::new().timeout(options.timeout).build();
+    Client{ url, options, agent }
+  }
+
+  /// Request the bytes from `start` to `end` included, retrying on network errors, server errors and throttling.
+  fn get(&self, start : u64, end : u64) -> Result<ureq::Response, Box<ureq::Error>>
+  {
+    let mut delay = self.options.retry_delay;
+    for _ in 0..self.options.retries
+    {
+      match self.agent.get(&self.url).set("Range", &format!("bytes={}-{}", start, end)).call()
+      {
+        Err(ureq::Error::Transport(_)) => (),
+        Err(ureq::Error::Status(status, _)) if status >= 500 || status == 429 => (),
+        result => return result.map_err(Box::new),
+      }
+      thread::sleep(delay);
+      delay *= 2;
+    }
+    self.agent.get(&self.url).set("Range", &format!("bytes={}-{}", start, end)).call().map_err(Box::new)
+  }
+
+  /// Return the size of the remote file, or an error if the server doesn't support range requests.
+  fn size(&self) -> anyhow::Result<u64>
+  {
+    let error = |message : String| RustructError::OpenFile(format!("{} : {}", self.url, message));
+
+    let response = match self.get(0, 0)
+    {
+      Ok(response) if response.status() == 206 => response,
+      Ok(response) => return Err(error(format!("range requests not supported, status {}", response.status())).into()),
+      //an empty file can't satisfy any range
+      Err(err) => match *err
+      {
+        ureq::Error::Status(416, response) => response,
+        err => return Err(error(err.to_string()).into()),
+      },
+    };
+
+    //Content-Range is `bytes 0-0/size` or `bytes */size`
+    response.header("Content-Range").and_then(|range| range.rsplit_once('/')).and_then(|(_, size)| size.trim().parse::<u64>().ok())
+            .ok_or_else(|| error("missing or invalid Content-Range".into()).into())
+  }
+
+  /// Read the bytes from `start` to `end` excluded.
+  fn read_range(&self, start : u64, end : u64) -> io::Result<Vec<u8>>
+  {
+    let response = self.get(start, end - 1).map_err(|err| io::Error::other(format!("{} : {}", self.url, err)))?;
+    if response.status() != 206
+    {
+      return Err(io::Error::other(format!("{} : unexpected status {}", self.url, response.status())))
+    }
+
+    let mut data = Vec::with_capacity((end - start) as usize);
+    response.into_reader().take(end - start).read_to_end(&mut data)?;
+    if data.len() as u64 != end - start
+    {
+      return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("{} : truncated response", self.url)))
+    }
+    Ok(data)
+  }
+}
+
+/**
+ * Implement a [VFileBuilder] reading a remote file with HTTP range requests.
+ * The size is requested once when the builder is created.
+ * It's serialized as its URL, options and size, no request is made when it's deserialized.
+ */
+#[derive(Serialize)]
+pub struct HttpVFileBuilder
+{
+  url : String,
+  options : HttpOptions,
+  size : u64,
+  #[serde(skip)]
+  client : Client,
+}
+
+impl HttpVFileBuilder
+{
+  /// Return a builder reading the file at `url`.
+  /// Return [RustructError::OpenFile] if the file can't be reached or the server doesn't support range requests.
+  pub fn new<S : Into<String>>(url : S, options : HttpOptions) -> anyhow::Result<Arc<HttpVFileBuilder>>
+  {
+    let url = url.into();
+    let client = Client::new(url.clone(), options.clone());
+    let size = client.size()?;
+    Ok(Arc::new(HttpVFileBuilder{ url, options, size, client }))
+  }
+
+  /// Return a builder reading the public S3 object `key` of `bucket` in `region`.
+  pub fn s3(bucket : &str, key : &str, region : &str, options : HttpOptions) -> anyhow::Result<Arc<HttpVFileBuilder>>
+  {
+    HttpVFileBuilder::new(format!("https://{}.s3.{}.amazonaws.com/{}", bucket, region, key.trim_start_matches('/')), options)
+  }
+
+  /// Return the URL of the file.
+  pub fn url(&self) -> &str
+  {
+    &self.url
+  }
+
+  /// Return the options of the requests.
+  pub fn options(&self) -> &HttpOptions
+  {
+    &self.options
+  }
+}
+
+#[typetag::serde]
+impl VFileBuilder for HttpVFileBuilder
+{
+  fn open(&self) -> anyhow::Result<Box<dyn VFile>>
+  {
+    Ok(Box::new(HttpVFile{ client : self.client.clone(), size : self.size, pos : 0, buffer : Vec::new(), buffer_start : 0 }))
+  }
+
+  fn size(&self) -> u64
+  {
+    self.size
+  }
+}
+
+impl<'de> Deserialize<'de> for HttpVFileBuilder
+{
+  fn deserialize<D>(deserializer: D) -> std::result::Result<HttpVFileBuilder, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    #[derive(Deserialize)]
+    struct Fields
+    {
+      url : String,
+      #[serde(default)]
+      options : HttpOptions,
+      size : u64,
+    }
+
+    let fields = Fields::deserialize(deserializer)?;
+    let client = Client::new(fields.url.clone(), fields.options.clone());
+    Ok(HttpVFileBuilder{ url : fields.url, options : fields.options, size : fields.size, client })
+  }
+}
+
+/**
+ * [VFile] reading a [HttpVFileBuilder], each request reads at least [HttpOptions::read_ahead] bytes kept in a buffer.
+ */
+pub struct HttpVFile
+{
+  client : Client,
+  size : u64,
+  pos : u64,
+  buffer : Vec<u8>,
+  /// Offset of the first byte of `buffer` in the file.
+  buffer_start : u64,
+}
+
+impl Read for HttpVFile
+{
+  fn read(&mut self, buf : &mut [u8]) -> io::Result<usize>
+  {
+    if self.pos >= self.size || buf.is_empty()
+    {
+      return Ok(0)
+    }
+
+    let buffer_end = self.buffer_start + self.buffer.len() as u64;
+    if self.pos < self.buffer_start || self.pos >= buffer_end
+    {
+      let length = (buf.len() as u64).max(self.client.options.read_ahead);
+      let end = self.pos.saturating_add(length).min(self.size);
+      self.buffer = self.client.read_range(self.pos, end)?;
+      self.buffer_start = self.pos;
+    }
+
+    let offset = (self.pos - self.buffer_start) as usize;
+    let size = buf.len().min(self.buffer.len() - offset);
+    buf[..size].copy_from_slice(&self.buffer[offset..offset + size]);
+    self.pos += size as u64;
+    Ok(size)
+  }
+}
+
+impl Seek for HttpVFile
+{
+  fn seek(&mut self, pos : SeekFrom) -> io::Result<u64>
+  {
+    let pos = match pos
+    {
+      SeekFrom::Start(pos) => Some(pos),
+      SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+      SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+    };
+
+    match pos
+    {
+      Some(pos) => { self.pos = pos; Ok(pos) },
+      None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{HttpVFileBuilder, HttpOptions};
+  use crate::vfile::VFileBuilder;
+
+  use std::io::{Read, Write, Seek, SeekFrom, BufRead, BufReader};
+  use std::net::TcpListener;
+  use std::sync::Arc;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::thread;
+  use std::time::Duration;
+
+  /// Serve `content` with range requests on a local port, the first request fails with a server error.
+  fn serve(content : Vec<u8>, requests : Arc<AtomicUsize>) -> String
+  {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/image.dd", listener.local_addr().unwrap());
+    thread::spawn(move ||
+    {
+      for stream in listener.incoming()
+      {
+        let mut stream = stream.unwrap();
+        let mut range = None;
+        for line in BufReader::new(&stream).lines()
+        {
+          let line = line.unwrap();
+          if line.is_empty()
+          {
+            break
+          }
+          if let Some(value) = line.to_ascii_lowercase().strip_prefix("range: bytes=")
+          {
+            let (start, end) = value.split_once('-').unwrap();
+            range = Some((start.parse::<usize>().unwrap(), end.parse::<usize>().unwrap()));
+          }
+        }
+
+        let response = match (requests.fetch_add(1, Ordering::SeqCst), range)
+        {
+          (0, _) => b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+          (_, Some((start, end))) =>
+          {
+            let end = end.min(content.len() - 1);
+            let mut response = format!("HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                                       start, end, content.len(), end + 1 - start).into_bytes();
+            response.extend_from_slice(&content[start..=end]);
+            response
+          },
+          (_, None) => b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+        };
+        stream.write_all(&response).unwrap();
+      }
+    });
+    url
+  }
+
+  #[test]
+  fn read_http_ranges()
+  {
+    let content : Vec<u8> = (0..10000u32).map(|i| (i % 251) as u8).collect();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let url = serve(content.clone(), requests.clone());
+    let options = HttpOptions{ read_ahead : 4096, retry_delay : Duration::from_millis(1), ..HttpOptions::default() };
+
+    let builder = HttpVFileBuilder::new(url, options).unwrap();
+    assert!(builder.size() == 10000);
+    assert!(requests.load(Ordering::SeqCst) == 2);
+
+    let mut file = builder.open().unwrap();
+    let mut buffer = [0; 16];
+    file.seek(SeekFrom::Start(100)).unwrap();
+    file.read_exact(&mut buffer).unwrap();
+    assert!(buffer == content[100..116]);
+    file.read_exact(&mut buffer).unwrap();
+    assert!(buffer == content[116..132]);
+    assert!(requests.load(Ordering::SeqCst) == 3);
+
+    let mut data = Vec::new();
+    file.seek(SeekFrom::Start(0)).unwrap();
+    file.read_to_end(&mut data).unwrap();
+    assert!(data == content);
+
+    let json = serde_json::to_string(&(builder as Arc<dyn VFileBuilder>)).unwrap();
+    let builder : Box<dyn VFileBuilder> = serde_json::from_str(&json).unwrap();
+    let mut file = builder.open().unwrap();
+    file.seek(SeekFrom::End(-4)).unwrap();
+    let mut end = Vec::new();
+    file.read_to_end(&mut end).unwrap();
+    assert!(end == content[9996..]);
+
+    assert!(HttpVFileBuilder::new("http://127.0.0.1:1/missing", HttpOptions{ retries : 0, ..HttpOptions::default() }).is_err());
+  }
+}
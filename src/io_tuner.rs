@@ -0,0 +1,221 @@
+//! [IoTuner] choose the size of the chunks read from a [VFile](crate::vfile::VFile) by measuring the read throughput,
+//! remote builders are faster with large reads while local or mapped files don't need them.
+//! [read_chunks] read a whole reader with a tuner, it's used by the helpers that need to read all the data of a file.
+
+use std::io::{Read, ErrorKind};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+/// Smallest chunk size used by default.
+pub const MIN_CHUNK_SIZE : usize = 4 * 1024;
+/// Biggest chunk size used by default.
+pub const MAX_CHUNK_SIZE : usize = 16 * 1024 * 1024;
+/// First chunk size used by default.
+pub const DEFAULT_CHUNK_SIZE : usize = 64 * 1024;
+/// Default maximum duration of a read, bigger chunks are not tried when reads take longer so callers stay responsive.
+pub const MAX_READ_LATENCY : Duration = Duration::from_millis(250);
+/// Number of reads measured before changing the chunk size.
+const SAMPLES : u32 = 3;
+/// Minimal throughput improvement to continue changing the chunk size in the same direction.
+const IMPROVEMENT : f64 = 1.05;
+
+/**
+ * Tune the chunk size of successive reads of a source.
+ * The chunk size is doubled or halved every few reads, and the direction is reversed when the throughput stops improving,
+ * so it keep oscillating around the best size and adapt if the source latency change.
+ */
+#[derive(Debug, Clone)]
+pub struct IoTuner
+{
+  chunk_size : usize,
+  min_chunk_size : usize,
+  max_chunk_size : usize,
+  max_latency : Duration,
+  growing : bool,
+  /// Throughput in bytes per second measured for the previous chunk size.
+  previous_throughput : Option<f64>,
+  bytes : u64,
+  elapsed : Duration,
+  reads : u32,
+}
+
+impl Default for IoTuner
+{
+  fn default() -> Self
+  {
+    IoTuner::new(DEFAULT_CHUNK_SIZE, MIN_CHUNK_SIZE, MAX_CHUNK_SIZE)
+  }
+}
+
+impl IoTuner
+{
+  /// Return a new [IoTuner] starting at `chunk_size` and tuning it between `min_chunk_size` and `max_chunk_size`.
+  pub fn new(chunk_size : usize, min_chunk_size : usize, max_chunk_size : usize) -> Self
+  {
+    let min_chunk_size = min_chunk_size.max(1);
+    let max_chunk_size = max_chunk_size.max(min_chunk_size);
+    IoTuner{ chunk_size : chunk_size.clamp(min_chunk_size, max_chunk_size), min_chunk_size, max_chunk_size, max_latency : MAX_READ_LATENCY,
+             growing : true, previous_throughput : None, bytes : 0, elapsed : Duration::ZERO, reads : 0 }
+  }
+
+  /// Return a tuner that always use `chunk_size`.
+  pub fn fixed(chunk_size : usize) -> Self
+  {
+    IoTuner::new(chunk_size, chunk_size, chunk_size)
+  }
+
+  /// Set the maximum duration of a read.
+  pub fn with_max_latency(mut self, max_latency : Duration) -> Self
+  {
+    self.max_latency = max_latency;
+    self
+  }
+
+  /// Return the size of the next read.
+  pub fn chunk_size(&self) -> usize
+  {
+    self.chunk_size
+  }
+
+  /// Return the average throughput in bytes per second measured for the previous chunk size.
+  pub fn throughput(&self) -> Option<f64>
+  {
+    self.previous_throughput
+  }
+
+  /// Record a read of `bytes` that took `elapsed` and update the chunk size.
+  /// Short reads at the end of a source are ignored as they don't reflect the throughput.
+  pub fn record(&mut self, bytes : usize, elapsed : Duration)
+  {
+    if bytes < self.chunk_size
+    {
+      return
+    }
+
+    self.bytes += bytes as u64;
+    self.elapsed += elapsed;
+    self.reads += 1;
+    if self.reads < SAMPLES
+    {
+      return
+    }
+
+    let latency = self.elapsed / self.reads;
+    let throughput = self.bytes as f64 / self.elapsed.as_secs_f64().max(1e-9);
+    self.bytes = 0;
+    self.elapsed = Duration::ZERO;
+    self.reads = 0;
+
+    if latency > self.max_latency
+    {
+      self.growing = false;
+    }
+    else if let Some(previous) = self.previous_throughput
+    {
+      if throughput < previous * IMPROVEMENT
+      {
+        self.growing = !self.growing;
+      }
+    }
+    self.previous_throughput = Some(throughput);
+
+    let chunk_size = match self.growing
+    {
+      true => self.chunk_size.saturating_mul(2),
+      false => self.chunk_size / 2,
+    };
+    self.chunk_size = chunk_size.clamp(self.min_chunk_size, self.max_chunk_size);
+  }
+}
+
+/// Fill `buffer` from `reader`, return the number of bytes read, that is lower than the buffer size only at the end of the reader.
+pub(crate) fn read_full<R : Read + ?Sized>(reader : &mut R, buffer : &mut [u8]) -> Result<usize>
+{
+  let mut readed = 0;
+  while readed < buffer.len()
+  {
+    match reader.read(&mut buffer[readed..])
+    {
+      Ok(0) => break,
+      Ok(size) => readed += size,
+      Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+      Err(err) => return Err(err.into()),
+    }
+  }
+  Ok(readed)
+}
+
+/// Read `reader` until its end by chunks sized by `tuner` and pass each chunk to `callback`.
+/// Return the number of bytes read.
+pub fn read_chunks<R, F>(reader : &mut R, tuner : &mut IoTuner, mut callback : F) -> Result<u64>
+  where R : Read + ?Sized,
+        F : FnMut(&[u8]) -> Result<()>
+{
+  let mut buffer = Vec::new();
+  let mut total = 0;
+
+  loop
+  {
+    buffer.resize(tuner.chunk_size(), 0);
+    let start = Instant::now();
+    let size = read_full(reader, &mut buffer)?;
+    tuner.record(size, start.elapsed());
+
+    if size == 0
+    {
+      return Ok(total)
+    }
+    callback(&buffer[..size])?;
+    total += size as u64;
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{IoTuner, read_chunks};
+  use std::time::Duration;
+
+  /// Return the duration of a read of `size` bytes from a source with `latency` and a bandwidth of 100MB/s.
+  fn read_time(size : usize, latency : Duration) -> Duration
+  {
+    latency + Duration::from_secs_f64(size as f64 / 100_000_000.0)
+  }
+
+  #[test]
+  fn tune_chunk_size()
+  {
+    //high latency source need big reads
+    let mut tuner = IoTuner::new(64 * 1024, 4 * 1024, 16 * 1024 * 1024);
+    for _ in 0..100
+    {
+      tuner.record(tuner.chunk_size(), read_time(tuner.chunk_size(), Duration::from_millis(20)));
+    }
+    assert!(tuner.chunk_size() >= 4 * 1024 * 1024);
+
+    //low latency source stay around the first sizes, and reads never exceed the maximum latency
+    let mut tuner = IoTuner::default().with_max_latency(Duration::from_millis(5));
+    for _ in 0..100
+    {
+      tuner.record(tuner.chunk_size(), read_time(tuner.chunk_size(), Duration::from_micros(1)));
+      assert!(read_time(tuner.chunk_size(), Duration::from_micros(1)) <= Duration::from_millis(11));
+    }
+
+    let mut tuner = IoTuner::fixed(1024);
+    tuner.record(1024, Duration::from_secs(1));
+    assert!(tuner.chunk_size() == 1024);
+  }
+
+  #[test]
+  fn read_all_chunks()
+  {
+    let data : Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+    let mut readed = Vec::new();
+    let mut tuner = IoTuner::new(1024, 1024, 8192);
+
+    let size = read_chunks(&mut data.as_slice(), &mut tuner, |chunk| { readed.extend_from_slice(chunk); Ok(()) }).unwrap();
+    assert!(size == 100_000);
+    assert!(readed == data);
+  }
+}
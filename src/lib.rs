@@ -4,19 +4,72 @@
 
 pub mod session;
 pub mod node;
+pub mod name;
 pub mod tree;
 pub mod event;
 pub mod value;
 pub mod attribute;
 pub mod reflect;
 pub mod plugins_db;
+pub mod version;
+pub mod detection;
+pub mod pipeline;
+pub mod batch;
 pub mod task_scheduler; 
+pub mod cancellation;
+pub mod quota;
+pub mod task_log;
+pub mod services;
+pub mod result_store;
 pub mod vfile;
 pub mod mappedvfile;
 pub mod zerovfile;
+pub mod patternvfile;
 pub mod memoryvfile;
+pub mod tempvfile;
+pub mod fsvfile;
+pub mod slicevfile;
+pub mod overlayvfile;
+pub mod concatvfile;
+pub mod stagingvfile;
+pub mod dedup;
+pub mod blockcache;
+pub mod chunkcachevfile;
+pub mod bufferedvfile;
+pub mod meteredvfile;
+pub mod decompressvfile;
+pub mod cryptvfile;
+#[cfg(feature = "http")]
+pub mod httpvfile;
+#[cfg(feature = "tokio")]
+pub mod asyncvfile;
 pub mod error;
 pub mod plugin;
 pub mod plugin_dummy;
 pub mod plugin_dummy_singleton;
+pub mod plugin_partition;
+pub mod plugin_fat;
+pub mod plugin_report;
 pub mod datetime;
+pub mod export;
+pub mod replica;
+pub mod summary;
+pub mod profiler;
+pub mod refresh;
+pub mod external_tool;
+pub mod context;
+pub mod validation;
+pub mod io_tuner;
+pub mod tag;
+pub mod computed;
+pub mod reference;
+pub mod diagnostics;
+pub mod preview;
+pub mod access;
+pub mod runtime;
+
+pub use runtime::runtime_manifest;
+
+#[cfg(feature = "auto_register")]
+#[doc(hidden)]
+pub use inventory;
@@ -1,23 +1,31 @@
 //! [MappedVFileBuilder] is a file system developement helper, you can use it to create a generator of `Reader`.
 //! You don't need to implement [Read] or [Seek] method but just to add different pointer (offset and size) to [chunk](FileRanges) of data from an existing `Reader` to the container.
+//! Streaming parsers can also [append](MappedVFileBuilder::append) ranges to a builder as they discover them, files already opened see the file growing.
+//!
+//! A [MappedVFileBuilder] is serialized as its table of ranges with the list of its parent builders, each parent is
+//! serialized once, or by name if it was [registered](crate::vfile::register_builder) so it can be found when the session is loaded.
+//! The opened parent files are shared by the ranges reading the same [builder](builder_key).
 
 use std::io::Read; 
 use std::io::Seek;
 use std::io::SeekFrom;
 use std::io::{Error, ErrorKind};
-use std::sync::{Arc};
+use std::sync::{Arc, RwLock};
 
 use serde::{Serialize, Deserialize};
 use serde::de::{Deserializer};
 use serde::ser::{Serializer, SerializeMap};
 
 use crate::error::{RustructError};
-use crate::vfile::{VFile, VFileBuilder};
+use crate::vfile::{VFile, VFileBuilder, Extent, builder_registry, builder_key};
+use crate::zerovfile::ZeroVFileBuilder;
 
 use anyhow::Result;
-use intervaltree::IntervalTree;
 use lru::LruCache;
 
+/// Default number of parent files kept open by each [MappedVFile].
+pub const OPEN_FILES : usize = 10;
+
 /**
  *  [FileRanges] contain a [Vec](Vec)<([Range](std::ops::Range)<u64>, [FileOffset])>.
  *  Each [range](std::ops::Range) is slice a of data representating a new futur generated file
@@ -29,14 +37,13 @@ use lru::LruCache;
 pub struct FileRanges
 {
   pub ranges : Vec<(std::ops::Range<u64>, FileOffset)>,
-  pub id : u32,
 }
 
 impl FileRanges
 {
   pub fn new() -> Self
   {
-    FileRanges{ranges : Vec::new(), id : 0}
+    FileRanges{ranges : Vec::new()}
   }
 
   //return error if mapping offset is > as file size, or mapping overlap ?
@@ -44,10 +51,135 @@ impl FileRanges
   /// and the offset `builder_offset` from where to read the data in the parent [VFileBuilder] `builder`.
   pub fn push(&mut self, offset_range : std::ops::Range<u64>, builder_offset : u64, builder : Arc<dyn VFileBuilder>)
   {
-    let file_offset = FileOffset{ builder, offset : builder_offset, id : self.id }; 
-    self.id += 1;
+    let file_offset = FileOffset{ builder, offset : builder_offset, hole : false }; 
+    self.ranges.push((offset_range, file_offset));
+  }
+
+  /// Add a new [`offset_range`](std::ops::Range) of the futur file that is a hole, reading as zeros without reading a parent file.
+  pub fn push_hole(&mut self, offset_range : std::ops::Range<u64>)
+  {
+    let size = offset_range.end.saturating_sub(offset_range.start);
+    let file_offset = FileOffset{ builder : Arc::new(ZeroVFileBuilder::new(size)), offset : 0, hole : true };
     self.ranges.push((offset_range, file_offset));
   }
+
+  /// Check the ranges and return the [overlaps, gaps and invalid ranges](RangesReport) found.
+  pub fn validate(&self) -> RangesReport
+  {
+    let mut report = RangesReport::default();
+    let mut ranges : Vec<std::ops::Range<u64>> = Vec::new();
+    for (range, _) in self.ranges.iter()
+    {
+      match range.start >= range.end
+      {
+        true => report.invalid.push(range.clone()),
+        false => ranges.push(range.clone()),
+      }
+    }
+    ranges.sort_by_key(|range| (range.start, range.end));
+
+    //end of the data seen so far, and the range ending there
+    let mut covered = 0..0;
+    for range in ranges
+    {
+      if range.start < covered.end
+      {
+        report.overlaps.push((covered.clone(), range.clone()));
+      }
+      else if range.start > covered.end
+      {
+        report.gaps.push(covered.end..range.start);
+      }
+      if range.end > covered.end
+      {
+        covered = range;
+      }
+    }
+    report
+  }
+
+  /// Add a hole for each gap between the ranges and return the number of holes added.
+  pub fn fill_gaps(&mut self) -> usize
+  {
+    let gaps = self.validate().gaps;
+    for gap in gaps.iter()
+    {
+      self.push_hole(gap.clone());
+    }
+    gaps.len()
+  }
+
+  /// Validate the ranges and return a [MappedVFileBuilder] reading them, the gaps are filled with holes if `fill_gaps` is true.
+  /// Return an error if ranges overlap or are empty, or if there is gaps that are not filled.
+  pub fn build(mut self, fill_gaps : bool) -> Result<MappedVFileBuilder>
+  {
+    if fill_gaps
+    {
+      self.fill_gaps();
+    }
+    let report = self.validate();
+    match report.is_valid()
+    {
+      true => Ok(MappedVFileBuilder::new(self)),
+      false => Err(RustructError::InvalidArgument("FileRanges".into(), report.to_string()).into()),
+    }
+  }
+
+  /// Return the runs of data and of holes of the ranges sorted by offset, adjacent runs of the same kind are merged.
+  pub fn extents(&self) -> Vec<Extent>
+  {
+    merge_extents(self.ranges.iter().map(|(range, offset)| (range.clone(), offset.hole)).collect())
+  }
+}
+
+/// Sort `ranges` and merge the adjacent ranges that are both holes or both data.
+pub(crate) fn merge_extents(mut ranges : Vec<(std::ops::Range<u64>, bool)>) -> Vec<Extent>
+{
+  ranges.sort_by_key(|(range, _)| range.start);
+
+  let mut extents : Vec<Extent> = Vec::new();
+  for (range, hole) in ranges.into_iter().filter(|(range, _)| !range.is_empty())
+  {
+    match extents.last_mut()
+    {
+      Some(last) if last.hole == hole && last.range.end == range.start => last.range.end = range.end,
+      _ => extents.push(Extent{ range, hole }),
+    }
+  }
+  extents
+}
+
+/// Problems found by [FileRanges::validate].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RangesReport
+{
+  /// Pairs of overlapping ranges.
+  pub overlaps : Vec<(std::ops::Range<u64>, std::ops::Range<u64>)>,
+  /// Offsets not covered by a range before the end of the last range.
+  pub gaps : Vec<std::ops::Range<u64>>,
+  /// Empty or inverted ranges.
+  pub invalid : Vec<std::ops::Range<u64>>,
+}
+
+impl RangesReport
+{
+  /// Return true if no problem was found.
+  pub fn is_valid(&self) -> bool
+  {
+    self.overlaps.is_empty() && self.gaps.is_empty() && self.invalid.is_empty()
+  }
+}
+
+impl std::fmt::Display for RangesReport
+{
+  fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+  {
+    let mut problems = Vec::new();
+    problems.extend(self.overlaps.iter().map(|(first, second)| format!("{:#x}..{:#x} overlaps {:#x}..{:#x}", second.start, second.end, first.start, first.end)));
+    problems.extend(self.gaps.iter().map(|gap| format!("gap {:#x}..{:#x}", gap.start, gap.end)));
+    problems.extend(self.invalid.iter().map(|range| format!("invalid range {:#x}..{:#x}", range.start, range.end)));
+    write!(f, "{}", problems.join(", "))
+  }
 }
 
 /**
@@ -56,7 +188,8 @@ impl FileRanges
  */
 pub struct MappedVFileBuilder
 {
- mapper : Arc< Mapper > //Is it better to clone or too slow for a file with lot of chunk ?
+ mapper : Arc<RwLock<Mapper>>,
+ open_files : usize,
 }
 
 impl MappedVFileBuilder
@@ -64,7 +197,29 @@ impl MappedVFileBuilder
   /// Return a new [VFileBuilder] from a [range](FileRanges) which contain [Range](std::ops::Range) and [FileOffset] helping build new file.
   pub fn new(file_ranges : FileRanges) -> Self
   {
-    MappedVFileBuilder{mapper : Arc::new(Mapper::new(file_ranges))}
+    MappedVFileBuilder{mapper : Arc::new(RwLock::new(Mapper::new(file_ranges))), open_files : OPEN_FILES}
+  }
+
+  /// Add the range `offset_range` of the file, read from `builder` at `builder_offset`. The files already opened see the new data.
+  /// Return an error if the range is empty or overlap a range of the file.
+  pub fn append(&self, offset_range : std::ops::Range<u64>, builder_offset : u64, builder : Arc<dyn VFileBuilder>) -> Result<()>
+  {
+    self.mapper.write().unwrap().insert(offset_range, builder_offset, builder, false)
+  }
+
+  /// Add the range `offset_range` of the file as a hole reading as zeros. The files already opened see the new data.
+  /// Return an error if the range is empty or overlap a range of the file.
+  pub fn append_hole(&self, offset_range : std::ops::Range<u64>) -> Result<()>
+  {
+    let size = offset_range.end.saturating_sub(offset_range.start);
+    self.mapper.write().unwrap().insert(offset_range, 0, Arc::new(ZeroVFileBuilder::new(size)), true)
+  }
+
+  /// Set the number of parent files kept open by each opened file, default to [OPEN_FILES].
+  pub fn with_open_files(mut self, open_files : usize) -> Self
+  {
+    self.open_files = open_files.max(1);
+    self
   }
 }
 
@@ -74,35 +229,114 @@ impl VFileBuilder for MappedVFileBuilder
   /// When open is called it create a [VFile] from a clone of the internal `mapper`.
   fn open(&self) -> Result<Box<dyn VFile>>
   {
-    Ok(Box::new(MappedVFile::new(self.mapper.clone())))
+    Ok(Box::new(MappedVFile::new(self.mapper.clone(), self.open_files)))
   }
 
   /// Return the size of the mapped file.
   fn size(&self) -> u64
   {
-    self.mapper.size()
+    self.mapper.read().unwrap().size()
+  }
+
+  /// Return the runs of data and of the holes added with [FileRanges::push_hole].
+  fn extents(&self) -> Vec<Extent>
+  {
+    self.mapper.read().unwrap().extents()
   }
 }
 
+/// Parent builder of the ranges of a serialized [MappedVFileBuilder].
+#[derive(Serialize, Deserialize)]
+enum SerializedParent
+{
+  /// Name of a [registered](crate::vfile::register_builder) builder.
+  Named(String),
+  Builder(Arc<dyn VFileBuilder>),
+}
+
+/// Range of a serialized [MappedVFileBuilder], reading `parent` from `offset` or a hole if `parent` is None.
+#[derive(Serialize, Deserialize)]
+struct SerializedRange
+{
+  start : u64,
+  end : u64,
+  offset : u64,
+  parent : Option<usize>,
+}
+
 impl Serialize for MappedVFileBuilder
 {
   fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> 
     where S: Serializer,
   {
-     let mut map = serializer.serialize_map(Some(1))?;
+    let mapper = self.mapper.read().unwrap();
+    let mut parents : Vec<&Arc<dyn VFileBuilder>> = Vec::new();
+    let mut ranges = Vec::with_capacity(mapper.ranges.len());
+
+    for (range, offset) in mapper.ranges.iter()
+    {
+      let parent = match offset.hole
+      {
+        true => None,
+        false => Some(match parents.iter().position(|parent| Arc::ptr_eq(parent, &offset.builder))
+        {
+          Some(index) => index,
+          None => { parents.push(&offset.builder); parents.len() - 1 },
+        }),
+      };
+      ranges.push(SerializedRange{ start : range.start, end : range.end, offset : offset.offset, parent });
+    }
 
-     map.serialize_entry("size", &self.size())?;
-     map.end()
+    let parents : Vec<SerializedParent> = parents.into_iter().map(|parent| match builder_registry().name(parent)
+    {
+      Some(name) => SerializedParent::Named(name),
+      None => SerializedParent::Builder(parent.clone()),
+    }).collect();
+
+    let mut map = serializer.serialize_map(Some(4))?;
+    map.serialize_entry("size", &mapper.size())?;
+    map.serialize_entry("open_files", &self.open_files)?;
+    map.serialize_entry("parents", &parents)?;
+    map.serialize_entry("ranges", &ranges)?;
+    map.end()
   }
 }
 
 impl<'de> Deserialize<'de> for MappedVFileBuilder
 {
-  fn deserialize<D>(_deserializer: D) -> std::result::Result<MappedVFileBuilder, D::Error>
+  fn deserialize<D>(deserializer: D) -> std::result::Result<MappedVFileBuilder, D::Error>
   where
     D: Deserializer<'de>,
   {
-    Err(serde::de::Error::custom("MappedVFileBuilder::deserialize not implemented")) 
+    #[derive(Deserialize)]
+    struct Fields
+    {
+      open_files : usize,
+      parents : Vec<SerializedParent>,
+      ranges : Vec<SerializedRange>,
+    }
+
+    let fields = Fields::deserialize(deserializer)?;
+    let parents = fields.parents.into_iter().map(|parent| match parent
+    {
+      SerializedParent::Named(name) => builder_registry().get(&name).ok_or_else(|| serde::de::Error::custom(format!("builder {} is not registered", name))),
+      SerializedParent::Builder(builder) => Ok(builder),
+    }).collect::<std::result::Result<Vec<_>, D::Error>>()?;
+
+    let mut file_ranges = FileRanges::new();
+    for range in fields.ranges
+    {
+      match range.parent
+      {
+        None => file_ranges.push_hole(range.start..range.end),
+        Some(index) =>
+        {
+          let parent = parents.get(index).ok_or_else(|| serde::de::Error::custom(format!("invalid parent index {}", index)))?;
+          file_ranges.push(range.start..range.end, range.offset, parent.clone());
+        },
+      }
+    }
+    Ok(MappedVFileBuilder::new(file_ranges).with_open_files(fields.open_files))
   }
 }
 
@@ -113,21 +347,25 @@ impl<'de> Deserialize<'de> for MappedVFileBuilder
  */
 struct MappedVFile
 {
-  pub mapper : Arc<Mapper>,
-  pub size : u64,
+  pub mapper : Arc<RwLock<Mapper>>,
   pub pos : u64,
-  pub cache : LruCache<u32, Box<dyn VFile>>,
+  pub cache : LruCache<usize, Box<dyn VFile>>,
 }
 
 impl MappedVFile
 {
-  /// Return a new [MappedVFile] from a [Arc]<[Mapper]>.
+  /// Return a new [MappedVFile] from a [Arc]<[Mapper]> keeping at most `open_files` parent files open.
   /// This is used by [MappedVFileBuilder].
-  fn new(mapper : Arc<Mapper>) -> Self
+  fn new(mapper : Arc<RwLock<Mapper>>, open_files : usize) -> Self
+  {
+    let cache = LruCache::new(open_files);
+    MappedVFile{ mapper, pos : 0, cache  }
+  }
+
+  /// Return the current size of the file, it grows when ranges are appended.
+  fn size(&self) -> u64
   {
-    let size = mapper.size();
-    let cache = LruCache::new(10); //get mapper number of vfile ?
-    MappedVFile{ mapper, size, pos : 0, cache  }
+    self.mapper.read().unwrap().size()
   }
 
   // Return the current position of the cursor in the file
@@ -140,43 +378,53 @@ impl MappedVFile
   fn fill(&mut self, buf : &mut [u8]) -> Result<u64>
   {
     let mut readed = 0;
+    let size = self.size();
 
-    let to_read : u64 = match self.size - self.pos <  buf.len() as u64
+    let to_read : u64 = match size.saturating_sub(self.pos) <  buf.len() as u64
     {
-      true => self.size - self.pos,
+      true => size.saturating_sub(self.pos),
       false => buf.len() as u64,
     };
 
-    while readed < to_read && (readed as u64) < self.size
+    while readed < to_read && readed < size
     {
-      let elements: Vec<_> = self.mapper.tree.query_point(self.pos).collect();
+      //the range is copied so the mapper is not locked while reading the parent file
+      let element = self.mapper.read().unwrap().find(self.pos)?;
 
-      match elements.len()
+      match element
       {
-        len if len == 0 => return Ok(readed as u64),//must check if we're at end of a file ex: we read a block of 512 by default but the file size is only 20 so we must return 20 not error, 
+        None => return Ok(readed),//must check if we're at end of a file ex: we read a block of 512 by default but the file size is only 20 so we must return 20 not error, 
         //XXX ret error  if we didn't find the elem XXX?
-        len if len > 1 => return Err(RustructError::Unknown("Chunk overlap".into()).into()),
-        _ => {
-            let element = elements[0];
+        Some((range, value)) => {
+            //holes are zeroed without reading a parent file
+            if value.hole
+            {
+              let size = (to_read - readed).min(range.end - self.pos);
+              buf[readed as usize..(readed + size) as usize].fill(0);
+              readed += size;
+              self.pos += size;
+              continue
+            }
             //shift = current_offset in virtual file  - start of the currently found chunk
             //this give us the number of byte that we must skip inside this chunk
-            let shift = self.pos - element.range.start;
+            let shift = self.pos - range.start;
 
             //we check if the builder returned by query point is opened and in cache
-            let file = match self.cache.get_mut(&element.value.id)
+            let key = builder_key(&value.builder);
+            let file = match self.cache.get_mut(&key)
             {
                Some(vfile) => vfile, 
                None =>
                {
-                 let file = element.value.builder.open()?;
-                 self.cache.put(element.value.id, file);
-                 self.cache.get_mut(&element.value.id).unwrap() 
+                 let file = value.builder.open()?;
+                 self.cache.put(key, file);
+                 self.cache.get_mut(&key).unwrap() 
                },
             };
 
             //we seek to the offset that correspond inside the builder and we add the shift to go to the right position relatively to the start 
-            let seeked = file.seek(SeekFrom::Start(element.value.offset + shift))?; //avoid seeking each time ? //check seek == end ! 
-            if seeked !=  element.value.offset + shift
+            let seeked = file.seek(SeekFrom::Start(value.offset + shift))?; //avoid seeking each time ? //check seek == end ! 
+            if seeked !=  value.offset + shift
             {
               return Ok(readed as u64) //ok or error ?
             }
@@ -187,9 +435,9 @@ impl MappedVFile
             //if there is enough byte to read in this chunk we read of left
             //else we must read as much as we can until this range is finish
             //so at the next iteration the next builder will be opened and we will fill the buff from this one
-            let size_to_read : u64 = if left > (element.range.end - self.pos)
+            let size_to_read : u64 = if left > (range.end - self.pos)
             {
-                element.range.end - self.pos
+                range.end - self.pos
             }
             else 
             {
@@ -233,45 +481,47 @@ impl Seek for MappedVFile
       SeekFrom::Start(pos) => pos,
       SeekFrom::End(pos) => 
       { 
-        if self.size as i64 + pos < 0 
+        if self.size() as i64 + pos < 0 
           { return Err(Error::new(ErrorKind::Other, "MappedVFile::Seek : Can't seek past end of file")) };
-        (self.size as i64 + pos) as u64 
+        (self.size() as i64 + pos) as u64 
       },
       SeekFrom::Current(pos) => (pos + self.pos as i64) as u64,
     };
 
-    if pos <= self.size
+    if pos <= self.size()
     {
       self.pos = pos;
       return Ok(self.pos);
     }
   
-    Err(Error::new(ErrorKind::Other, format!("MappedVFile::Seek : Can't seek to {} past end of file of size {}", pos, self.size)))
+    Err(Error::other(format!("MappedVFile::Seek : Can't seek to {} past end of file of size {}", pos, self.size())))
   }
 }
 
 /**
- * [FileOffset] contain a [`builder`](VFileBuilder), the `offset` from where we start reading the data of the builder, and a unique `id`.
+ * [FileOffset] contain a [`builder`](VFileBuilder) and the `offset` from where we start reading the data of the builder.
  */
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FileOffset
 { 
   /// [Builder](VFileBuilder) from which data will be read.
   pub builder : Arc<dyn VFileBuilder>, 
   /// Offset of the data in the [VFileBuilder] `builder`.
   pub offset  : u64,
-  /// Unique id for each file [FileOffset] in a [FileRanges], used by the cache to identify cached FileOffset.
-  pub id : u32, 
+  /// True if the range is a hole reading as zeros.
+  pub hole : bool,
 }
 
 /**
- *  [Mapper] contain an [interval tree](IntervalTree) containing the different [FileOffset] and the final `size` of the mapped file.
+ *  [Mapper] contain the ranges sorted by offset with their [FileOffset] and the final `size` of the mapped file.
  *  it's an helper used internally by [MappedVFile]. Mapper can be used to get data from the different chunk composing the [MappedVFile] easily.
  */
 struct Mapper
 {
-  tree : IntervalTree<u64, FileOffset>,
+  ranges : Vec<(std::ops::Range<u64>, FileOffset)>,
   size : u64,
+  /// True if ranges overlap, reading the overlapping data fail.
+  overlap : bool,
 }
 
 impl Mapper
@@ -285,9 +535,12 @@ impl Mapper
 
     for file_range in file_ranges.ranges.iter()
     {
-      size += file_range.0.end - file_range.0.start;
+      size += file_range.0.end.saturating_sub(file_range.0.start);
     }
-    Mapper{tree : file_ranges.ranges.into_iter().collect(), size}
+    let overlap = !file_ranges.validate().overlaps.is_empty();
+    let mut ranges = file_ranges.ranges;
+    ranges.sort_by_key(|(range, _)| range.start);
+    Mapper{ ranges, size, overlap }
   }
 
   /// Return the size of the mapped data.
@@ -295,4 +548,175 @@ impl Mapper
   {
     self.size
   }
+
+  /// Return the runs of data and of holes.
+  fn extents(&self) -> Vec<Extent>
+  {
+    merge_extents(self.ranges.iter().map(|(range, offset)| (range.clone(), offset.hole)).collect())
+  }
+
+  /// Return the range containing `pos` and its [FileOffset].
+  fn find(&self, pos : u64) -> Result<Option<(std::ops::Range<u64>, FileOffset)>>
+  {
+    if self.overlap && self.ranges.iter().filter(|(range, _)| range.contains(&pos)).count() > 1
+    {
+      return Err(RustructError::Unknown("Chunk overlap".into()).into())
+    }
+    let index = self.ranges.partition_point(|(range, _)| range.start <= pos);
+    Ok(match index.checked_sub(1).map(|index| &self.ranges[index])
+    {
+      Some((range, offset)) if range.contains(&pos) => Some((range.clone(), offset.clone())),
+      _ => None,
+    })
+  }
+
+  /// Insert `range` read from `builder` at `offset`, or a hole.
+  fn insert(&mut self, range : std::ops::Range<u64>, offset : u64, builder : Arc<dyn VFileBuilder>, hole : bool) -> Result<()>
+  {
+    let invalid = |reason : String| RustructError::InvalidArgument("MappedVFileBuilder".into(), reason);
+    if range.start >= range.end
+    {
+      return Err(invalid(format!("invalid range {:#x}..{:#x}", range.start, range.end)).into())
+    }
+
+    let index = self.ranges.partition_point(|(current, _)| current.start <= range.start);
+    let previous = index.checked_sub(1).map(|index| &self.ranges[index].0).filter(|previous| previous.end > range.start);
+    let next = self.ranges.get(index).map(|(next, _)| next).filter(|next| next.start < range.end);
+    if let Some(overlapped) = previous.or(next)
+    {
+      return Err(invalid(format!("{:#x}..{:#x} overlaps {:#x}..{:#x}", range.start, range.end, overlapped.start, overlapped.end)).into())
+    }
+
+    self.size += range.end - range.start;
+    self.ranges.insert(index, (range, FileOffset{ builder, offset, hole }));
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{FileRanges, MappedVFileBuilder};
+  use crate::vfile::{VFileBuilder, Extent, register_builder, unregister_builder};
+  use crate::memoryvfile::MemoryVFileBuilder;
+  use crate::tempvfile::TempVFileBuilder;
+  use crate::fsvfile::FsVFileBuilder;
+  use crate::slicevfile::SliceVFileBuilder;
+
+  use std::io::{Read, Seek, SeekFrom};
+  use std::sync::Arc;
+
+  #[test]
+  fn sparse_extents()
+  {
+    let parent = MemoryVFileBuilder::from_buffer(vec![0xaa; 100]);
+    let mut ranges = FileRanges::new();
+    ranges.push(0..10, 0, parent.clone());
+    ranges.push_hole(10..50);
+    ranges.push_hole(50..60);
+    ranges.push(60..100, 10, parent.clone());
+    ranges.push(100..120, 50, parent);
+    assert!(ranges.extents() == vec![Extent{ range : 0..10, hole : false }, Extent{ range : 10..60, hole : true }, Extent{ range : 60..120, hole : false }]);
+
+    let builder = MappedVFileBuilder::new(ranges);
+    assert!(builder.size() == 120 && builder.extents().len() == 3);
+    let mut file = builder.open().unwrap();
+    let mut data = vec![0xff; 120];
+    file.read_exact(&mut data).unwrap();
+    assert!(data[..10] == [0xaa; 10] && data[10..60] == [0; 50] && data[60..] == [0xaa; 60]);
+
+    let mut buffer = [0xff; 8];
+    file.seek(SeekFrom::Start(56)).unwrap();
+    file.read_exact(&mut buffer).unwrap();
+    assert!(buffer == [0, 0, 0, 0, 0xaa, 0xaa, 0xaa, 0xaa]);
+
+    let slice = SliceVFileBuilder::new(Arc::new(builder), 5, 60).unwrap();
+    assert!(slice.extents() == vec![Extent{ range : 0..5, hole : false }, Extent{ range : 5..55, hole : true }, Extent{ range : 55..60, hole : false }]);
+    let parent = MemoryVFileBuilder::from_buffer(vec![1; 4]);
+    assert!(parent.extents() == vec![Extent{ range : 0..4, hole : false }]);
+  }
+
+  #[test]
+  fn validate_ranges()
+  {
+    let parent = MemoryVFileBuilder::from_buffer(vec![0xaa; 100]);
+    let mut ranges = FileRanges::new();
+    ranges.push(20..30, 0, parent.clone());
+    ranges.push(0..10, 0, parent.clone());
+    ranges.push(40..50, 0, parent.clone());
+    let report = ranges.validate();
+    assert!(report.gaps == vec![10..20, 30..40] && report.overlaps.is_empty() && !report.is_valid());
+
+    ranges.push(45..60, 0, parent.clone());
+    ranges.push(5..5, 0, parent.clone());
+    let report = ranges.validate();
+    assert!(report.overlaps == vec![(40..50, 45..60)] && report.invalid.len() == 1 && report.invalid[0] == (5..5));
+    let err = ranges.build(true).err().unwrap().to_string();
+    assert!(err.contains("0x2d..0x3c overlaps 0x28..0x32") && err.contains("invalid range 0x5..0x5"));
+
+    let mut ranges = FileRanges::new();
+    ranges.push(20..30, 0, parent.clone());
+    ranges.push(0..10, 0, parent);
+    let mut ranges_copy = FileRanges::new();
+    ranges.ranges.iter().for_each(|(range, offset)| ranges_copy.push(range.clone(), offset.offset, offset.builder.clone()));
+    assert!(ranges_copy.build(false).is_err());
+
+    let builder = ranges.build(true).unwrap();
+    assert!(builder.size() == 30 && builder.extents()[1] == Extent{ range : 10..20, hole : true });
+    let mut data = Vec::new();
+    builder.open().unwrap().read_to_end(&mut data).unwrap();
+    assert!(data[..10] == [0xaa; 10] && data[10..20] == [0; 10] && data[20..] == [0xaa; 10]);
+  }
+
+  #[test]
+  fn append_ranges()
+  {
+    let parent = MemoryVFileBuilder::from_buffer((0..100).collect());
+    let builder = MappedVFileBuilder::new(FileRanges::new());
+    builder.append(0..10, 0, parent.clone()).unwrap();
+    let mut file = builder.open().unwrap();
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).unwrap();
+    assert!(data == (0..10).collect::<Vec<u8>>());
+
+    //the file opened before grows with the appended ranges
+    builder.append(20..30, 50, parent.clone()).unwrap();
+    builder.append_hole(10..20).unwrap();
+    assert!(builder.size() == 30 && builder.extents().len() == 3);
+    file.read_to_end(&mut data).unwrap();
+    assert!(data.len() == 30 && data[10..20] == [0; 10] && data[20..] == (50..60).collect::<Vec<u8>>());
+    assert!(file.seek(SeekFrom::End(-5)).unwrap() == 25);
+
+    assert!(builder.append(25..35, 0, parent.clone()).is_err());
+    assert!(builder.append(5..6, 0, parent.clone()).is_err());
+    assert!(builder.append(40..40, 0, parent).is_err());
+    assert!(builder.size() == 30);
+  }
+
+  #[test]
+  fn serialize_ranges()
+  {
+    let temp = TempVFileBuilder::new(&(0..100).collect::<Vec<u8>>()).unwrap();
+    let disk : Arc<dyn VFileBuilder> = FsVFileBuilder::new(temp.path()).unwrap();
+    let memory : Arc<dyn VFileBuilder> = MemoryVFileBuilder::from_buffer(vec![0xaa; 10]);
+    register_builder("serialize_ranges_memory", memory.clone());
+
+    let mut ranges = FileRanges::new();
+    ranges.push(0..10, 90, disk.clone());
+    ranges.push_hole(10..20);
+    ranges.push(20..30, 0, memory);
+    ranges.push(30..40, 0, disk);
+    let builder : Arc<dyn VFileBuilder> = Arc::new(MappedVFileBuilder::new(ranges).with_open_files(2));
+    let json = serde_json::to_string(&builder).unwrap();
+    assert!(json.contains("\"Named\":\"serialize_ranges_memory\"") && json.matches("FsVFileBuilder").count() == 1);
+
+    let loaded : Box<dyn VFileBuilder> = serde_json::from_str(&json).unwrap();
+    let (mut data, mut expected) = (Vec::new(), Vec::new());
+    loaded.open().unwrap().read_to_end(&mut data).unwrap();
+    builder.open().unwrap().read_to_end(&mut expected).unwrap();
+    assert!(data == expected && loaded.extents() == builder.extents() && serde_json::to_string(&loaded).unwrap() == json);
+
+    unregister_builder("serialize_ranges_memory");
+    assert!(serde_json::from_str::<Box<dyn VFileBuilder>>(&json).is_err());
+  }
 }
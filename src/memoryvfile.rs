@@ -1,10 +1,10 @@
 //! A [VFileBuilder] that cache in memory the content of an other [VFileBuilder].
 
-use std::io::{Read, Seek, SeekFrom}; 
+use std::io::{Read, Write, Seek, SeekFrom, Cursor}; 
 use std::io::{Error, ErrorKind};
 use std::sync::Arc;
 
-use crate::vfile::{VFile, VFileBuilder};
+use crate::vfile::{VFile, VFileBuilder, VFileWriter};
 
 use serde::{Serialize, Deserialize};
 use serde::de::{Deserializer};
@@ -30,6 +30,12 @@ impl MemoryVFileBuilder
 
     Ok(Arc::new(MemoryVFileBuilder{ buffer : Arc::new(buffer) }))
   }
+
+  /// Return a [MemoryVFileBuilder] generating files containing `buffer`.
+  pub fn from_buffer(buffer : Vec<u8>) -> Arc<MemoryVFileBuilder>
+  {
+    Arc::new(MemoryVFileBuilder{ buffer : Arc::new(buffer) })
+  }
 }
 
 #[typetag::serde]
@@ -68,6 +74,53 @@ impl<'de> Deserialize<'de> for MemoryVFileBuilder
   }
 }
 
+/**
+ * [VFileWriter] writing to a buffer in memory, finished as a [MemoryVFileBuilder].
+ */
+#[derive(Default)]
+pub struct MemoryVFileWriter
+{
+  buffer : Cursor<Vec<u8>>,
+}
+
+impl MemoryVFileWriter
+{
+  /// Return a writer with an empty buffer.
+  pub fn new() -> MemoryVFileWriter
+  {
+    MemoryVFileWriter::default()
+  }
+}
+
+impl Write for MemoryVFileWriter
+{
+  fn write(&mut self, buf : &[u8]) -> std::io::Result<usize>
+  {
+    self.buffer.write(buf)
+  }
+
+  fn flush(&mut self) -> std::io::Result<()>
+  {
+    Ok(())
+  }
+}
+
+impl Seek for MemoryVFileWriter
+{
+  fn seek(&mut self, pos : SeekFrom) -> std::io::Result<u64>
+  {
+    self.buffer.seek(pos)
+  }
+}
+
+impl VFileWriter for MemoryVFileWriter
+{
+  fn finish(self : Box<Self>) -> anyhow::Result<Arc<dyn VFileBuilder>>
+  {
+    Ok(MemoryVFileBuilder::from_buffer(self.buffer.into_inner()))
+  }
+}
+
 /**
  * [MemoryVFile] implement [VFile] [Read] + [Seek] trait for a [Vec]<[u8]>.
  */
@@ -137,3 +190,27 @@ impl Seek for MemoryVFile
     }
   }
 }
+
+#[cfg(test)]
+mod tests
+{
+  use super::MemoryVFileWriter;
+  use crate::vfile::VFileWriter;
+
+  use std::io::{Read, Write, Seek, SeekFrom};
+
+  #[test]
+  fn write_memory_file()
+  {
+    let mut writer = Box::new(MemoryVFileWriter::new());
+    writer.write_all(b"hello world").unwrap();
+    writer.seek(SeekFrom::Start(6)).unwrap();
+    writer.write_all(b"WORLD").unwrap();
+
+    let builder = writer.finish().unwrap();
+    assert!(builder.size() == 11);
+    let mut content = String::new();
+    builder.open().unwrap().read_to_string(&mut content).unwrap();
+    assert!(content == "hello WORLD");
+  }
+}
@@ -0,0 +1,420 @@
+//! A [VFileBuilder] wrapper accounting the I/O done on an other [VFileBuilder] by each consumer, and optionally limiting their throughput.
+//!
+//! The consumer of a file is the name set for the thread opening it with [as_consumer], the workers of the
+//! [TaskScheduler](crate::task_scheduler::TaskScheduler) set it to the name of the plugin they run, so the [Meter] of a slow
+//! image shows which plugin reads it. The statistics of a consumer are sent on the [events](Meter::events) of the meter
+//! when one of its files is closed, or when [Meter::publish] is called.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::vfile::{VFile, VFileBuilder, Extent};
+use crate::event::EventChannel;
+
+use serde::{Serialize, Deserialize};
+
+/// Name of the consumer of the files opened by threads without a consumer.
+pub const UNKNOWN_CONSUMER : &str = "unknown";
+
+thread_local!
+{
+  static CURRENT_CONSUMER : RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Restore the previous consumer of the thread when dropped, returned by [as_consumer].
+pub struct ConsumerGuard
+{
+  previous : Option<String>,
+  //the guard must be dropped on the thread where it was created
+  _thread : PhantomData<*const ()>,
+}
+
+impl Drop for ConsumerGuard
+{
+  fn drop(&mut self)
+  {
+    CURRENT_CONSUMER.with(|current| *current.borrow_mut() = self.previous.take());
+  }
+}
+
+/// Set `consumer` as the consumer of the files opened by the current thread until the returned guard is dropped.
+#[must_use = "the consumer is unset when the guard is dropped"]
+pub fn as_consumer<S : Into<String>>(consumer : S) -> ConsumerGuard
+{
+  let previous = CURRENT_CONSUMER.with(|current| current.borrow_mut().replace(consumer.into()));
+  ConsumerGuard{ previous, _thread : PhantomData }
+}
+
+/// Return the consumer of the files opened by the current thread, or [UNKNOWN_CONSUMER].
+pub fn current_consumer() -> String
+{
+  CURRENT_CONSUMER.with(|current| current.borrow().clone()).unwrap_or_else(|| UNKNOWN_CONSUMER.into())
+}
+
+/// I/O statistics of a consumer of a [MeteredVFileBuilder].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeterStats
+{
+  pub opens : u64,
+  pub reads : u64,
+  pub seeks : u64,
+  pub bytes_read : u64,
+  /// Time spent in the read calls of the inner files.
+  pub read_time : Duration,
+  /// Longest read call.
+  pub max_read_time : Duration,
+  /// Time the reads were delayed by the rate limit.
+  pub throttled_time : Duration,
+}
+
+impl MeterStats
+{
+  /// Return the mean duration of a read call.
+  pub fn mean_read_time(&self) -> Duration
+  {
+    match self.reads
+    {
+      0 => Duration::ZERO,
+      reads => Duration::from_nanos((self.read_time.as_nanos() / reads as u128) as u64),
+    }
+  }
+
+  /// Return the number of bytes read by second spent reading.
+  pub fn throughput(&self) -> f64
+  {
+    match self.read_time.as_secs_f64()
+    {
+      time if time > 0.0 => self.bytes_read as f64 / time,
+      _ => 0.0,
+    }
+  }
+}
+
+/// Statistics of a consumer sent by a [Meter].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeterEvent
+{
+  /// Name of the [MeteredVFileBuilder].
+  pub builder : String,
+  pub consumer : String,
+  pub stats : MeterStats,
+}
+
+/// Statistics and throttling window of a consumer.
+#[derive(Default)]
+struct Consumer
+{
+  stats : MeterStats,
+  window_start : Option<Instant>,
+  window_bytes : u64,
+}
+
+/**
+ * Statistics of the consumers of a [MeteredVFileBuilder], shared by the files it opens.
+ */
+pub struct Meter
+{
+  name : String,
+  consumers : Mutex<HashMap<String, Consumer>>,
+  events : EventChannel<MeterEvent>,
+}
+
+impl Meter
+{
+  fn new(name : String) -> Self
+  {
+    Meter{ name, consumers : Mutex::new(HashMap::new()), events : EventChannel::new() }
+  }
+
+  /// Return the statistics of each consumer, sorted by consumer.
+  pub fn stats(&self) -> BTreeMap<String, MeterStats>
+  {
+    self.consumers.lock().unwrap().iter().map(|(name, consumer)| (name.clone(), consumer.stats.clone())).collect()
+  }
+
+  /// Return the statistics of `consumer`.
+  pub fn consumer_stats(&self, consumer : &str) -> Option<MeterStats>
+  {
+    self.consumers.lock().unwrap().get(consumer).map(|consumer| consumer.stats.clone())
+  }
+
+  /// Return the sum of the statistics of all the consumers.
+  pub fn total(&self) -> MeterStats
+  {
+    self.consumers.lock().unwrap().values().fold(MeterStats::default(), |mut total, consumer|
+    {
+      total.opens += consumer.stats.opens;
+      total.reads += consumer.stats.reads;
+      total.seeks += consumer.stats.seeks;
+      total.bytes_read += consumer.stats.bytes_read;
+      total.read_time += consumer.stats.read_time;
+      total.max_read_time = total.max_read_time.max(consumer.stats.max_read_time);
+      total.throttled_time += consumer.stats.throttled_time;
+      total
+    })
+  }
+
+  /// Return the channel the statistics of the consumers are sent on.
+  pub fn events(&self) -> &EventChannel<MeterEvent>
+  {
+    &self.events
+  }
+
+  /// Send the statistics of all the consumers.
+  pub fn publish(&self)
+  {
+    for (consumer, stats) in self.stats()
+    {
+      self.events.update(MeterEvent{ builder : self.name.clone(), consumer, stats });
+    }
+  }
+
+  /// Remove the statistics of all the consumers.
+  pub fn reset(&self)
+  {
+    self.consumers.lock().unwrap().clear();
+  }
+
+  /// Send the statistics of `consumer`.
+  fn publish_consumer(&self, consumer : &str)
+  {
+    if let Some(stats) = self.consumer_stats(consumer)
+    {
+      self.events.update(MeterEvent{ builder : self.name.clone(), consumer : consumer.into(), stats });
+    }
+  }
+
+  fn update<F>(&self, consumer : &str, update : F)
+    where F : FnOnce(&mut Consumer)
+  {
+    update(self.consumers.lock().unwrap().entry(consumer.into()).or_default());
+  }
+
+  /// Account a read of `size` bytes by `consumer`, and return the time to wait to keep the throughput of `consumer` under `rate_limit` bytes per second.
+  fn read(&self, consumer : &str, size : usize, time : Duration, rate_limit : Option<u64>) -> Duration
+  {
+    let mut delay = Duration::ZERO;
+    self.update(consumer, |consumer|
+    {
+      consumer.stats.reads += 1;
+      consumer.stats.bytes_read += size as u64;
+      consumer.stats.read_time += time;
+      consumer.stats.max_read_time = consumer.stats.max_read_time.max(time);
+
+      if let Some(rate_limit) = rate_limit.filter(|rate_limit| *rate_limit > 0)
+      {
+        //the throughput is computed on windows of one second so a consumer that was idle don't read a burst
+        let now = Instant::now();
+        let start = *consumer.window_start.get_or_insert(now);
+        if now.duration_since(start) > Duration::from_secs(1)
+        {
+          consumer.window_start = Some(now);
+          consumer.window_bytes = 0;
+        }
+        consumer.window_bytes += size as u64;
+        let expected = Duration::from_secs_f64(consumer.window_bytes as f64 / rate_limit as f64);
+        delay = expected.saturating_sub(now.duration_since(consumer.window_start.unwrap_or(now)));
+        consumer.stats.throttled_time += delay;
+      }
+    });
+    delay
+  }
+}
+
+/**
+ * Implement a [VFileBuilder] generating files reading `inner` and accounting their I/O in a [Meter],
+ * the reads of each consumer can be limited to `rate_limit` bytes per second.
+ * The statistics are not serialized.
+ */
+#[derive(Serialize)]
+pub struct MeteredVFileBuilder
+{
+  inner : Arc<dyn VFileBuilder>,
+  name : String,
+  rate_limit : Option<u64>,
+  #[serde(skip)]
+  meter : Arc<Meter>,
+}
+
+impl MeteredVFileBuilder
+{
+  /// Return a builder accounting the reads of `inner`, named `name` in the [events](MeterEvent).
+  pub fn new<S : Into<String>>(inner : Arc<dyn VFileBuilder>, name : S) -> Arc<MeteredVFileBuilder>
+  {
+    Arc::new(MeteredVFileBuilder::with_rate_limit(inner, name, None))
+  }
+
+  /// Return a builder accounting the reads of `inner` and limiting each consumer to `rate_limit` bytes per second.
+  pub fn with_rate_limit<S : Into<String>>(inner : Arc<dyn VFileBuilder>, name : S, rate_limit : Option<u64>) -> MeteredVFileBuilder
+  {
+    let name = name.into();
+    MeteredVFileBuilder{ inner, meter : Arc::new(Meter::new(name.clone())), name, rate_limit }
+  }
+
+  /// Return the metered builder.
+  pub fn inner(&self) -> &Arc<dyn VFileBuilder>
+  {
+    &self.inner
+  }
+
+  pub fn name(&self) -> &str
+  {
+    &self.name
+  }
+
+  /// Return the maximum number of bytes read by second by each consumer.
+  pub fn rate_limit(&self) -> Option<u64>
+  {
+    self.rate_limit
+  }
+
+  /// Return the statistics of the files opened by this builder.
+  pub fn meter(&self) -> &Arc<Meter>
+  {
+    &self.meter
+  }
+}
+
+#[typetag::serde]
+impl VFileBuilder for MeteredVFileBuilder
+{
+  fn open(&self) -> anyhow::Result<Box<dyn VFile>>
+  {
+    let consumer = current_consumer();
+    let file = self.inner.open()?;
+    self.meter.update(&consumer, |consumer| consumer.stats.opens += 1);
+    Ok(Box::new(MeteredVFile{ file, meter : self.meter.clone(), consumer, rate_limit : self.rate_limit }))
+  }
+
+  fn size(&self) -> u64
+  {
+    self.inner.size()
+  }
+
+  fn extents(&self) -> Vec<Extent>
+  {
+    self.inner.extents()
+  }
+}
+
+impl<'de> Deserialize<'de> for MeteredVFileBuilder
+{
+  fn deserialize<D>(deserializer: D) -> std::result::Result<MeteredVFileBuilder, D::Error>
+  where
+    D: serde::de::Deserializer<'de>,
+  {
+    #[derive(Deserialize)]
+    struct Fields
+    {
+      inner : Arc<dyn VFileBuilder>,
+      name : String,
+      rate_limit : Option<u64>,
+    }
+
+    let fields = Fields::deserialize(deserializer)?;
+    Ok(MeteredVFileBuilder::with_rate_limit(fields.inner, fields.name, fields.rate_limit))
+  }
+}
+
+/**
+ * [VFile] accounting the reads of the inner file of a [MeteredVFileBuilder] for the consumer that opened it.
+ */
+pub struct MeteredVFile
+{
+  file : Box<dyn VFile>,
+  meter : Arc<Meter>,
+  consumer : String,
+  rate_limit : Option<u64>,
+}
+
+impl Read for MeteredVFile
+{
+  fn read(&mut self, buf : &mut [u8]) -> io::Result<usize>
+  {
+    let start = Instant::now();
+    let size = self.file.read(buf)?;
+    let delay = self.meter.read(&self.consumer, size, start.elapsed(), self.rate_limit);
+    if !delay.is_zero()
+    {
+      thread::sleep(delay);
+    }
+    Ok(size)
+  }
+}
+
+impl Seek for MeteredVFile
+{
+  fn seek(&mut self, pos : SeekFrom) -> io::Result<u64>
+  {
+    self.meter.update(&self.consumer, |consumer| consumer.stats.seeks += 1);
+    self.file.seek(pos)
+  }
+}
+
+impl Drop for MeteredVFile
+{
+  fn drop(&mut self)
+  {
+    self.meter.publish_consumer(&self.consumer);
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{MeteredVFileBuilder, as_consumer, current_consumer, UNKNOWN_CONSUMER};
+  use crate::vfile::VFileBuilder;
+  use crate::memoryvfile::MemoryVFileBuilder;
+  use crate::tempvfile::TempVFileBuilder;
+  use crate::fsvfile::FsVFileBuilder;
+
+  use std::io::{Read, Seek, SeekFrom};
+  use std::sync::Arc;
+  use std::time::{Duration, Instant};
+
+  #[test]
+  fn meter_consumers()
+  {
+    let inner = MemoryVFileBuilder::from_buffer(vec![7; 1000]);
+    let builder = MeteredVFileBuilder::new(inner, "image");
+    let events = builder.meter().events().subscribe();
+
+    {
+      let _consumer = as_consumer("carver");
+      let mut file = builder.open().unwrap();
+      let mut data = Vec::new();
+      file.read_to_end(&mut data).unwrap();
+      file.seek(SeekFrom::Start(10)).unwrap();
+      assert!(data.len() == 1000 && current_consumer() == "carver");
+    }
+    assert!(current_consumer() == UNKNOWN_CONSUMER);
+    builder.open().unwrap().read_exact(&mut [0; 10]).unwrap();
+
+    let carver = builder.meter().consumer_stats("carver").unwrap();
+    assert!(carver.opens == 1 && carver.bytes_read == 1000 && carver.seeks == 1 && carver.reads >= 2 && carver.throttled_time.is_zero());
+    assert!(builder.meter().stats().len() == 2 && builder.meter().total().bytes_read == 1010);
+    let events = events.events();
+    assert!(events.len() == 2 && events[0].event.consumer == "carver" && events[0].event.builder == "image" && events[1].event.stats.bytes_read == 10);
+
+    //4000 bytes at 10000 bytes/s take 400ms
+    let temp = TempVFileBuilder::new(&[7; 4000]).unwrap();
+    let limited : Arc<dyn VFileBuilder> = Arc::new(MeteredVFileBuilder::with_rate_limit(FsVFileBuilder::new(temp.path()).unwrap(), "slow", Some(10000)));
+    let start = Instant::now();
+    let mut file = limited.open().unwrap();
+    let mut buffer = [0; 1000];
+    for _ in 0..4
+    {
+      file.read_exact(&mut buffer).unwrap();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(350));
+
+    let json = serde_json::to_string(&limited).unwrap();
+    let loaded : Box<dyn VFileBuilder> = serde_json::from_str(&json).unwrap();
+    assert!(loaded.size() == 4000 && json.contains("\"rate_limit\":10000"));
+  }
+}
@@ -0,0 +1,150 @@
+//! Decoding of raw byte names found in file systems and artifacts that may not be valid UTF-8.
+//!
+//! [Node](crate::node::Node) names are strings used to build paths. When a name is read as bytes,
+//! a [NameDecoder] is used to produce the display name and the original bytes are kept on the node
+//! if the display name doesn't contain them exactly, see [Node::from_raw_name](crate::node::Node::from_raw_name).
+//! Bytes that can't be decoded and path separators are escaped as `\xNN`, so escaped names can be converted back with [unescape_name].
+
+use crate::context::decode_codepage;
+
+/**
+ * Decode a raw byte name to a display name.
+ */
+pub trait NameDecoder : Sync + Send
+{
+  /// Return the decoded name, or `None` if `raw` can't be decoded with this decoder.
+  fn decode(&self, raw : &[u8]) -> Option<String>;
+}
+
+/// Decode valid UTF-8 names, invalid names are escaped.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Utf8NameDecoder;
+
+impl NameDecoder for Utf8NameDecoder
+{
+  fn decode(&self, raw : &[u8]) -> Option<String>
+  {
+    std::str::from_utf8(raw).ok().map(|name| name.to_string())
+  }
+}
+
+/// Decode little endian UTF-16 names, as used by NTFS and FAT long names.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Utf16NameDecoder;
+
+impl NameDecoder for Utf16NameDecoder
+{
+  fn decode(&self, raw : &[u8]) -> Option<String>
+  {
+    if !raw.len().is_multiple_of(2)
+    {
+      return None
+    }
+    let units = raw.chunks_exact(2).map(|unit| u16::from_le_bytes([unit[0], unit[1]]));
+    std::char::decode_utf16(units).collect::<Result<String, _>>().ok()
+  }
+}
+
+/// Decode names with a codepage supported by [decode_codepage], like the names of FAT short entries.
+#[derive(Debug, Clone, Default)]
+pub struct CodepageNameDecoder
+{
+  codepage : String,
+}
+
+impl CodepageNameDecoder
+{
+  /// Return a decoder for `codepage`.
+  pub fn new<S : Into<String>>(codepage : S) -> Self
+  {
+    CodepageNameDecoder{ codepage : codepage.into() }
+  }
+}
+
+impl NameDecoder for CodepageNameDecoder
+{
+  fn decode(&self, raw : &[u8]) -> Option<String>
+  {
+    Some(decode_codepage(&self.codepage, raw))
+  }
+}
+
+/// Escape the path separator `/` and the escape character `\` of a decoded name.
+pub fn escape_separators(name : &str) -> String
+{
+  let mut escaped = String::with_capacity(name.len());
+  for c in name.chars()
+  {
+    match c
+    {
+      '/' => escaped.push_str("\\x2f"),
+      '\\' => escaped.push_str("\\x5c"),
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+/// Return `raw` as a string, the invalid UTF-8 sequences, `/` and `\` are escaped as `\xNN`.
+pub fn escape_name(raw : &[u8]) -> String
+{
+  let mut escaped = String::with_capacity(raw.len());
+  for chunk in raw.utf8_chunks()
+  {
+    escaped.push_str(&escape_separators(chunk.valid()));
+    for byte in chunk.invalid()
+    {
+      escaped.push_str(&format!("\\x{:02x}", byte));
+    }
+  }
+  escaped
+}
+
+/// Return the bytes of a name escaped by [escape_name], or `None` if `name` contains an invalid escape sequence.
+pub fn unescape_name(name : &str) -> Option<Vec<u8>>
+{
+  let mut raw = Vec::with_capacity(name.len());
+  let mut rest = name;
+  while let Some(index) = rest.find('\\')
+  {
+    raw.extend_from_slice(&rest.as_bytes()[..index]);
+    let byte = rest.get(index + 2..index + 4).filter(|_| rest[index + 1..].starts_with('x'))?;
+    raw.push(u8::from_str_radix(byte, 16).ok()?);
+    rest = &rest[index + 4..];
+  }
+  raw.extend_from_slice(rest.as_bytes());
+  Some(raw)
+}
+
+/// Return the display name of `raw` decoded with `decoder`, with path separators escaped,
+/// or `raw` escaped by [escape_name] if it can't be decoded.
+pub fn decode_name(raw : &[u8], decoder : &dyn NameDecoder) -> String
+{
+  match decoder.decode(raw)
+  {
+    Some(name) => escape_separators(&name),
+    None => escape_name(raw),
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{decode_name, escape_name, unescape_name, Utf8NameDecoder, Utf16NameDecoder, CodepageNameDecoder};
+
+  #[test]
+  fn decode_raw_names()
+  {
+    let raw = b"caf\xe9/a\\b.txt";
+    let escaped = escape_name(raw);
+    assert!(escaped == "caf\\xe9\\x2fa\\x5cb.txt");
+    assert!(unescape_name(&escaped).unwrap() == raw);
+    assert!(unescape_name("été").unwrap() == "été".as_bytes());
+    assert!(unescape_name("bad\\xz").is_none());
+
+    assert!(decode_name(raw, &Utf8NameDecoder) == escaped);
+    assert!(decode_name(raw, &CodepageNameDecoder::new("latin1")) == "café\\x2fa\\x5cb.txt");
+    assert!(decode_name(&[0x61, 0x00, 0xe9, 0x00], &Utf16NameDecoder) == "aé");
+    assert!(decode_name(&[0x00, 0xd8, 0x61, 0x00], &Utf16NameDecoder) == "\0\\xd8a\0");
+  }
+}
@@ -4,13 +4,19 @@ use std::borrow::Cow;
 
 use crate::value::{Value};
 use crate::attribute::{Attribute, Attributes};
+use crate::name::{NameDecoder, decode_name};
 
 use serde::ser::{Serialize, Serializer};
+use uuid::Uuid;
 
 /// [Node] is used as a [tree](crate::tree::Tree) item. It's an abstraction layer above an Attribute.
 pub struct Node
 {
   attribute : Attribute,
+  /// Original bytes of the name when the display name doesn't contain them exactly.
+  raw_name : Option<Box<[u8]>>,
+  /// Identify the node across processes and sessions, unlike the [TreeNodeId](crate::tree::TreeNodeId) which is only valid in its tree.
+  uuid : Uuid,
 }
 
 impl Node 
@@ -19,7 +25,54 @@ impl Node
   pub fn new<S>(name : S) -> Self 
     where S: Into<Cow<'static, str>>
   {
-    Node{ attribute : Attribute::new(name.into(), Value::Attributes(Attributes::new()), None) }
+    Node{ attribute : Attribute::new(name.into(), Value::Attributes(Attributes::new()), None), raw_name : None, uuid : Uuid::new_v4() }
+  }
+
+  /// Return this [Node] with `uuid` as [uuid](Node::uuid), used to restore a saved node.
+  pub fn with_uuid(mut self, uuid : Uuid) -> Self
+  {
+    self.uuid = uuid;
+    self
+  }
+
+  /// Return the unique id of the node, generated at creation and kept when the node is encoded or exported.
+  pub fn uuid(&self) -> Uuid
+  {
+    self.uuid
+  }
+
+  /// Return a [Node] named `name` read from the original bytes `raw`, `raw` is kept if it's different from the bytes of `name`.
+  pub fn with_raw_name<S>(name : S, raw : &[u8]) -> Self
+    where S: Into<Cow<'static, str>>
+  {
+    let mut node = Node::new(name);
+    if node.attribute.name().as_bytes() != raw
+    {
+      node.raw_name = Some(raw.into());
+    }
+    node
+  }
+
+  /// Return a [Node] which name is `raw` decoded with `decoder`, see [decode_name].
+  pub fn from_raw_name(raw : &[u8], decoder : &dyn NameDecoder) -> Self
+  {
+    Node::with_raw_name(decode_name(raw, decoder), raw)
+  }
+
+  /// Return the original bytes of the name if they are different from the display [name](Node::name).
+  pub fn raw_name(&self) -> Option<&[u8]>
+  {
+    self.raw_name.as_deref()
+  }
+
+  /// Return the original bytes of the name, or the bytes of the display [name](Node::name).
+  pub fn name_bytes(&self) -> Cow<'_, [u8]>
+  {
+    match &self.raw_name
+    {
+      Some(raw) => Cow::Borrowed(raw),
+      None => Cow::Owned(self.attribute.name().as_bytes().to_vec()),
+    }
   }
 
   /// Return the underlying [attribute](Attribute).
@@ -34,6 +87,12 @@ impl Node
     self.attribute.value().as_attributes()
   }
 
+  /// Return the approximate memory footprint of this [Node] and its attributes in bytes, see [Value::deep_size].
+  pub fn deep_size(&self) -> usize
+  {
+    self.attribute.deep_size() + self.raw_name.as_ref().map_or(0, |raw| raw.len())
+  }
+
   /// Return the [Node] name
   pub fn name(&self) -> String 
   {
@@ -73,8 +132,9 @@ mod tests
     use std::sync::{Arc};
 
     use super::Node;
+    use crate::name::Utf8NameDecoder;
     use crate::value::{Value, ValueTypeId};
-    use crate::reflect::ReflectStruct;
+    use crate::reflect::{ReflectStruct, FieldInfo};
 
     #[test]
     fn create_node()
@@ -83,6 +143,17 @@ mod tests
       assert!(node.name() == "test");
     }
 
+    #[test]
+    fn create_node_with_raw_name()
+    {
+      let node = Node::from_raw_name(b"caf\xe9", &Utf8NameDecoder);
+      assert!(node.name() == "caf\\xe9");
+      assert!(node.raw_name() == Some(&b"caf\xe9"[..]));
+
+      let node = Node::from_raw_name(b"cafe", &Utf8NameDecoder);
+      assert!(node.raw_name().is_none() && node.name_bytes().as_ref() == b"cafe");
+    }
+
     #[test]
     fn create_node_with_static_attributes()
     {
@@ -137,9 +208,9 @@ mod tests
            "Test"
          }
 
-         fn infos(&self) -> Vec<(&'static str, Option<&'static str>) >
+         fn field_infos(&self) -> Vec<FieldInfo>
          {
-            vec![("string1", None), ("string2", None), ("calc", None)]
+            vec![FieldInfo::field("string1", None), FieldInfo::field("string2", None), FieldInfo::field("calc", None)]
          }
 
          fn get_value(&self, name : &str) -> Option<Value>
@@ -0,0 +1,522 @@
+//! A copy-on-write [VFileBuilder] layering patches over a read-only parent builder.
+//!
+//! Writes never reach the parent : they're stored in memory, or in a file created in a directory for big patches,
+//! and the files opened from the overlay read the patched content. It's used to test fixes of corrupted structures
+//! or to simulate repairs without modifying the evidence. Writing past the end of the parent grow the file,
+//! the gap read as zeros.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::vfile::{VFile, VFileBuilder, VFileWriter, Extent};
+use crate::mappedvfile::merge_extents;
+
+use serde::{Serialize, Deserialize};
+use serde::ser::Serializer;
+
+/// Storage of the data written to an overlay, the data is only appended : overwritten patches keep their place until [OverlayVFileBuilder::clear].
+enum PatchStore
+{
+  Memory(Vec<u8>),
+  /// File created in `dir` on the first write, and its size.
+  Disk{ dir : PathBuf, file : Option<(File, PathBuf)>, size : u64 },
+}
+
+impl PatchStore
+{
+  /// Append `data` and return its offset in the store.
+  fn append(&mut self, data : &[u8]) -> io::Result<u64>
+  {
+    match self
+    {
+      PatchStore::Memory(buffer) =>
+      {
+        buffer.extend_from_slice(data);
+        Ok((buffer.len() - data.len()) as u64)
+      },
+      PatchStore::Disk{ dir, file, size } =>
+      {
+        if file.is_none()
+        {
+          fs::create_dir_all(&*dir)?;
+          let path = dir.join(format!("tap-overlay-{}", uuid::Uuid::new_v4()));
+          *file = Some((OpenOptions::new().read(true).write(true).create_new(true).open(&path)?, path));
+        }
+        let (file, _) = file.as_mut().unwrap();
+        file.seek(SeekFrom::Start(*size))?;
+        file.write_all(data)?;
+        *size += data.len() as u64;
+        Ok(*size - data.len() as u64)
+      },
+    }
+  }
+
+  /// Read `buf.len()` bytes at `offset` of the store.
+  fn read_exact_at(&mut self, offset : u64, buf : &mut [u8]) -> io::Result<()>
+  {
+    match self
+    {
+      PatchStore::Memory(buffer) =>
+      {
+        buf.copy_from_slice(&buffer[offset as usize..offset as usize + buf.len()]);
+        Ok(())
+      },
+      PatchStore::Disk{ file : Some((file, _)), .. } =>
+      {
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(buf)
+      },
+      PatchStore::Disk{ file : None, .. } => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "empty overlay store")),
+    }
+  }
+
+  fn clear(&mut self) -> io::Result<()>
+  {
+    match self
+    {
+      PatchStore::Memory(buffer) => buffer.clear(),
+      PatchStore::Disk{ file, size, .. } =>
+      {
+        if let Some((file, _)) = file
+        {
+          file.set_len(0)?;
+        }
+        *size = 0;
+      },
+    }
+    Ok(())
+  }
+}
+
+impl Drop for PatchStore
+{
+  fn drop(&mut self)
+  {
+    if let PatchStore::Disk{ file : Some((_, path)), .. } = self
+    {
+      let _ = fs::remove_file(path);
+    }
+  }
+}
+
+/// Patched range of an overlay.
+#[derive(Debug, Clone, Copy)]
+struct Patch
+{
+  size : u64,
+  /// Offset of the data in the store.
+  data : u64,
+}
+
+/// Patches of an overlay, sorted by offset and never overlapping.
+struct Overlay
+{
+  patches : BTreeMap<u64, Patch>,
+  store : PatchStore,
+  /// Size of the parent, or end of the last patch written past it.
+  size : u64,
+}
+
+/// Part of an overlay read next by a [OverlayVFile].
+enum Source
+{
+  Patch(u64),
+  Parent,
+  Zero,
+}
+
+impl Overlay
+{
+  fn write(&mut self, offset : u64, data : &[u8]) -> io::Result<()>
+  {
+    let end = offset.checked_add(data.len() as u64).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "overlay write overflow"))?;
+    if data.is_empty()
+    {
+      return Ok(())
+    }
+    let position = self.store.append(data)?;
+
+    //cut the patches overlapped by the new one, keeping the parts before and after it
+    let overlapped : Vec<(u64, Patch)> = self.patches.range(..end).rev().take_while(|(start, patch)| *start + patch.size > offset)
+                                             .map(|(start, patch)| (*start, *patch)).collect();
+    for (start, patch) in overlapped
+    {
+      self.patches.remove(&start);
+      if start < offset
+      {
+        self.patches.insert(start, Patch{ size : offset - start, data : patch.data });
+      }
+      if start + patch.size > end
+      {
+        self.patches.insert(end, Patch{ size : start + patch.size - end, data : patch.data + (end - start) });
+      }
+    }
+    self.patches.insert(offset, Patch{ size : data.len() as u64, data : position });
+    self.size = self.size.max(end);
+    Ok(())
+  }
+
+  /// Return where to read the data at `pos` and the size available there, that's at most `len`.
+  fn source(&self, pos : u64, len : u64, parent_size : u64) -> (Source, u64)
+  {
+    if let Some((start, patch)) = self.patches.range(..=pos).next_back()
+    {
+      if start + patch.size > pos
+      {
+        return (Source::Patch(patch.data + (pos - start)), len.min(start + patch.size - pos))
+      }
+    }
+
+    let next = self.patches.range(pos..).next().map(|(start, _)| *start).unwrap_or(self.size);
+    match pos < parent_size
+    {
+      true => (Source::Parent, len.min(next.min(parent_size) - pos)),
+      false => (Source::Zero, len.min(next - pos)),
+    }
+  }
+
+  fn ranges(&self) -> Vec<Range<u64>>
+  {
+    self.patches.iter().map(|(start, patch)| *start..start + patch.size).collect()
+  }
+}
+
+/**
+ * Implement a [VFileBuilder] reading `parent` with the patches written with [write_at](OverlayVFileBuilder::write_at)
+ * or with an [OverlayVFileWriter]. The patches are shared by all the opened files and visible to them as soon as written.
+ */
+pub struct OverlayVFileBuilder
+{
+  parent : Arc<dyn VFileBuilder>,
+  /// Directory of the store file, the patches are kept in memory if it's None.
+  dir : Option<PathBuf>,
+  overlay : Arc<Mutex<Overlay>>,
+}
+
+impl OverlayVFileBuilder
+{
+  /// Return an overlay of `parent` keeping the patches in memory.
+  pub fn new(parent : Arc<dyn VFileBuilder>) -> Arc<OverlayVFileBuilder>
+  {
+    Arc::new(OverlayVFileBuilder::with_dir(parent, None))
+  }
+
+  /// Return an overlay of `parent` keeping the patches in a file created in `dir`, or in memory if `dir` is None.
+  pub fn with_dir(parent : Arc<dyn VFileBuilder>, dir : Option<PathBuf>) -> OverlayVFileBuilder
+  {
+    let store = match &dir
+    {
+      Some(dir) => PatchStore::Disk{ dir : dir.clone(), file : None, size : 0 },
+      None => PatchStore::Memory(Vec::new()),
+    };
+    let overlay = Overlay{ patches : BTreeMap::new(), store, size : parent.size() };
+    OverlayVFileBuilder{ parent, dir, overlay : Arc::new(Mutex::new(overlay)) }
+  }
+
+  /// Return the patched builder.
+  pub fn parent(&self) -> &Arc<dyn VFileBuilder>
+  {
+    &self.parent
+  }
+
+  /// Write `data` at `offset` over the parent content.
+  pub fn write_at(&self, offset : u64, data : &[u8]) -> anyhow::Result<()>
+  {
+    Ok(self.overlay.lock().unwrap().write(offset, data)?)
+  }
+
+  /// Return the patched ranges sorted by offset, adjacent patches are returned separately.
+  pub fn patches(&self) -> Vec<Range<u64>>
+  {
+    self.overlay.lock().unwrap().ranges()
+  }
+
+  /// Remove all the patches, the files read the parent content again.
+  pub fn clear(&self) -> anyhow::Result<()>
+  {
+    let mut overlay = self.overlay.lock().unwrap();
+    overlay.store.clear()?;
+    overlay.patches.clear();
+    overlay.size = self.parent.size();
+    Ok(())
+  }
+
+  /// Return a writer patching this overlay starting at offset 0, [finish](VFileWriter::finish) return the overlay.
+  pub fn writer(self : &Arc<Self>) -> OverlayVFileWriter
+  {
+    OverlayVFileWriter{ builder : self.clone(), pos : 0 }
+  }
+}
+
+#[typetag::serde]
+impl VFileBuilder for OverlayVFileBuilder
+{
+  fn open(&self) -> anyhow::Result<Box<dyn VFile>>
+  {
+    Ok(Box::new(OverlayVFile{ file : self.parent.open()?, parent_size : self.parent.size(), overlay : self.overlay.clone(), pos : 0 }))
+  }
+
+  fn size(&self) -> u64
+  {
+    self.overlay.lock().unwrap().size
+  }
+
+  /// Return the extents of the parent, the patches written over its holes or past its end are data.
+  fn extents(&self) -> Vec<Extent>
+  {
+    let overlay = self.overlay.lock().unwrap();
+    let parent_size = self.parent.size();
+    let mut ranges : Vec<(Range<u64>, bool)> = self.parent.extents().into_iter().map(|extent| (extent.range, extent.hole)).collect();
+    ranges.push((parent_size..overlay.size, true));
+
+    let patches = overlay.ranges();
+    let mut extents = Vec::new();
+    for (range, hole) in ranges
+    {
+      if !hole
+      {
+        extents.push((range, false));
+        continue
+      }
+      let mut pos = range.start;
+      for patch in patches.iter().filter(|patch| patch.start < range.end && patch.end > range.start)
+      {
+        let patch = patch.start.max(range.start)..patch.end.min(range.end);
+        extents.push((pos..patch.start, true));
+        pos = patch.end;
+        extents.push((patch, false));
+      }
+      extents.push((pos..range.end, true));
+    }
+    merge_extents(extents)
+  }
+}
+
+/// The patches are serialized with their data, the store directory is kept but not the store file.
+impl Serialize for OverlayVFileBuilder
+{
+  fn serialize<S>(&self, serializer : S) -> std::result::Result<S::Ok, S::Error>
+    where S : Serializer,
+  {
+    #[derive(Serialize)]
+    struct Fields<'a>
+    {
+      parent : &'a Arc<dyn VFileBuilder>,
+      dir : &'a Option<PathBuf>,
+      patches : Vec<(u64, Vec<u8>)>,
+    }
+
+    let mut overlay = self.overlay.lock().unwrap();
+    let mut patches = Vec::new();
+    for (start, patch) in overlay.patches.clone()
+    {
+      let mut data = vec![0; patch.size as usize];
+      overlay.store.read_exact_at(patch.data, &mut data).map_err(serde::ser::Error::custom)?;
+      patches.push((start, data));
+    }
+    Fields{ parent : &self.parent, dir : &self.dir, patches }.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for OverlayVFileBuilder
+{
+  fn deserialize<D>(deserializer: D) -> std::result::Result<OverlayVFileBuilder, D::Error>
+  where
+    D: serde::de::Deserializer<'de>,
+  {
+    #[derive(Deserialize)]
+    struct Fields
+    {
+      parent : Arc<dyn VFileBuilder>,
+      dir : Option<PathBuf>,
+      patches : Vec<(u64, Vec<u8>)>,
+    }
+
+    let fields = Fields::deserialize(deserializer)?;
+    let builder = OverlayVFileBuilder::with_dir(fields.parent, fields.dir);
+    for (offset, data) in fields.patches
+    {
+      builder.write_at(offset, &data).map_err(serde::de::Error::custom)?;
+    }
+    Ok(builder)
+  }
+}
+
+/**
+ * [VFile] reading a parent file and the patches of an [OverlayVFileBuilder].
+ */
+pub struct OverlayVFile
+{
+  file : Box<dyn VFile>,
+  parent_size : u64,
+  overlay : Arc<Mutex<Overlay>>,
+  pos : u64,
+}
+
+impl Read for OverlayVFile
+{
+  fn read(&mut self, buf : &mut [u8]) -> io::Result<usize>
+  {
+    let mut overlay = self.overlay.lock().unwrap();
+    if self.pos >= overlay.size || buf.is_empty()
+    {
+      return Ok(0)
+    }
+
+    let (source, size) = overlay.source(self.pos, buf.len() as u64, self.parent_size);
+    let buf = &mut buf[..size as usize];
+    let readed = match source
+    {
+      Source::Patch(offset) => { overlay.store.read_exact_at(offset, buf)?; buf.len() },
+      Source::Zero => { buf.fill(0); buf.len() },
+      Source::Parent =>
+      {
+        //the parent is read without locking the patches so the other files can use them
+        drop(overlay);
+        self.file.seek(SeekFrom::Start(self.pos))?;
+        self.file.read(buf)?
+      },
+    };
+    self.pos += readed as u64;
+    Ok(readed)
+  }
+}
+
+impl Seek for OverlayVFile
+{
+  fn seek(&mut self, pos : SeekFrom) -> io::Result<u64>
+  {
+    let pos = match pos
+    {
+      SeekFrom::Start(pos) => Some(pos),
+      SeekFrom::End(offset) => self.overlay.lock().unwrap().size.checked_add_signed(offset),
+      SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+    };
+
+    match pos
+    {
+      Some(pos) => { self.pos = pos; Ok(pos) },
+      None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+    }
+  }
+}
+
+/**
+ * [VFileWriter] writing patches to an [OverlayVFileBuilder], returned by [OverlayVFileBuilder::writer].
+ */
+pub struct OverlayVFileWriter
+{
+  builder : Arc<OverlayVFileBuilder>,
+  pos : u64,
+}
+
+impl Write for OverlayVFileWriter
+{
+  fn write(&mut self, buf : &[u8]) -> io::Result<usize>
+  {
+    self.builder.overlay.lock().unwrap().write(self.pos, buf)?;
+    self.pos += buf.len() as u64;
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()>
+  {
+    Ok(())
+  }
+}
+
+impl Seek for OverlayVFileWriter
+{
+  fn seek(&mut self, pos : SeekFrom) -> io::Result<u64>
+  {
+    let pos = match pos
+    {
+      SeekFrom::Start(pos) => Some(pos),
+      SeekFrom::End(offset) => self.builder.size().checked_add_signed(offset),
+      SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+    };
+
+    match pos
+    {
+      Some(pos) => { self.pos = pos; Ok(pos) },
+      None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+    }
+  }
+}
+
+impl VFileWriter for OverlayVFileWriter
+{
+  fn finish(self : Box<Self>) -> anyhow::Result<Arc<dyn VFileBuilder>>
+  {
+    Ok(self.builder)
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::OverlayVFileBuilder;
+  use crate::vfile::{VFileBuilder, VFileWriter, Extent};
+  use crate::patternvfile::PatternVFileBuilder;
+  use crate::zerovfile::ZeroVFileBuilder;
+
+  use std::io::{Read, Write, Seek, SeekFrom};
+  use std::sync::Arc;
+
+  fn read_all(builder : &dyn VFileBuilder) -> Vec<u8>
+  {
+    let mut data = Vec::new();
+    builder.open().unwrap().read_to_end(&mut data).unwrap();
+    data
+  }
+
+  #[test]
+  fn overlay_patches()
+  {
+    let parent : Arc<dyn VFileBuilder> = Arc::new(PatternVFileBuilder::counter(100));
+    let original = read_all(parent.as_ref());
+    let dir = std::env::temp_dir().join(format!("tap-overlay-test-{}", uuid::Uuid::new_v4()));
+
+    for builder in [OverlayVFileBuilder::new(parent.clone()), Arc::new(OverlayVFileBuilder::with_dir(parent.clone(), Some(dir.clone())))]
+    {
+      let mut file = builder.open().unwrap();
+      builder.write_at(10, &[0xaa; 20]).unwrap();
+      builder.write_at(20, &[0xbb; 5]).unwrap();
+      builder.write_at(5, &[0xcc; 7]).unwrap();
+      assert!(builder.patches() == vec![5..12, 12..20, 20..25, 25..30]);
+
+      //the file opened before see the patches
+      let mut data = Vec::new();
+      file.read_to_end(&mut data).unwrap();
+      let mut expected = original.clone();
+      expected[10..30].fill(0xaa);
+      expected[20..25].fill(0xbb);
+      expected[5..12].fill(0xcc);
+      assert!(data == expected && read_all(parent.as_ref()) == original);
+
+      let mut writer = Box::new(builder.writer());
+      writer.seek(SeekFrom::End(10)).unwrap();
+      writer.write_all(b"tail").unwrap();
+      let patched = writer.finish().unwrap();
+      let data = read_all(patched.as_ref());
+      assert!(patched.size() == 114 && data[..100] == expected[..] && data[100..110] == [0; 10] && &data[110..] == b"tail");
+      assert!(patched.extents() == vec![Extent{ range : 0..100, hole : false }, Extent{ range : 100..110, hole : true }, Extent{ range : 110..114, hole : false }]);
+
+      let json = serde_json::to_string(&patched).unwrap();
+      let loaded : Arc<dyn VFileBuilder> = serde_json::from_str(&json).unwrap();
+      assert!(read_all(loaded.as_ref()) == data);
+
+      builder.clear().unwrap();
+      assert!(builder.size() == 100 && read_all(builder.as_ref()) == original);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let sparse = OverlayVFileBuilder::new(Arc::new(ZeroVFileBuilder::new(50)));
+    sparse.write_at(10, b"data").unwrap();
+    assert!(sparse.extents() == vec![Extent{ range : 0..10, hole : true }, Extent{ range : 10..14, hole : false }, Extent{ range : 14..50, hole : true }]);
+  }
+}
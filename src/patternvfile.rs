@@ -0,0 +1,220 @@
+//! [VFileBuilder] generating deterministic content of a given size without storing it, for the tests of mappings, caches and parsers.
+//!
+//! A [PatternVFileBuilder] repeat a sequence of bytes or write a counter, a [RandomVFileBuilder] generate pseudo random bytes from a seed.
+//! The content is computed from the offset so the files can be read in any order and are identical each time they're opened.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::vfile::{VFile, VFileBuilder};
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+
+/// Content of a [PatternVFileBuilder].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Pattern
+{
+  /// Repeat these bytes, an empty pattern generate zeros.
+  Repeat(Vec<u8>),
+  /// Each 8 bytes word hold its offset in little endian, so data read through a mapping tells where it comes from.
+  Counter,
+}
+
+impl Pattern
+{
+  /// Return the byte at `offset`.
+  fn byte(&self, offset : u64) -> u8
+  {
+    match self
+    {
+      Pattern::Repeat(bytes) if bytes.is_empty() => 0,
+      Pattern::Repeat(bytes) => bytes[(offset % bytes.len() as u64) as usize],
+      Pattern::Counter => (offset - offset % 8).to_le_bytes()[(offset % 8) as usize],
+    }
+  }
+}
+
+/**
+ * Implement a [VFileBuilder] generating files of `size` bytes filled with a [Pattern].
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatternVFileBuilder
+{
+  pattern : Pattern,
+  size : u64,
+}
+
+impl PatternVFileBuilder
+{
+  pub fn new(pattern : Pattern, size : u64) -> Self
+  {
+    PatternVFileBuilder{ pattern, size }
+  }
+
+  /// Return a builder of `size` bytes repeating `bytes`.
+  pub fn repeat<B : Into<Vec<u8>>>(bytes : B, size : u64) -> Self
+  {
+    PatternVFileBuilder::new(Pattern::Repeat(bytes.into()), size)
+  }
+
+  /// Return a builder of `size` bytes of [counter](Pattern::Counter).
+  pub fn counter(size : u64) -> Self
+  {
+    PatternVFileBuilder::new(Pattern::Counter, size)
+  }
+
+  pub fn pattern(&self) -> &Pattern
+  {
+    &self.pattern
+  }
+}
+
+#[typetag::serde]
+impl VFileBuilder for PatternVFileBuilder
+{
+  fn open(&self) -> Result<Box<dyn VFile>>
+  {
+    let pattern = self.pattern.clone();
+    Ok(Box::new(GeneratedVFile{ generate : move |offset| pattern.byte(offset), size : self.size, pos : 0 }))
+  }
+
+  fn size(&self) -> u64
+  {
+    self.size
+  }
+}
+
+/// Return the pseudo random byte at `offset` of the content generated from `seed`, each 8 bytes word is a splitmix64 output.
+fn random_byte(seed : u64, offset : u64) -> u8
+{
+  let mut value = seed.wrapping_add((offset / 8).wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+  value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+  value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+  (value ^ (value >> 31)).to_le_bytes()[(offset % 8) as usize]
+}
+
+/**
+ * Implement a [VFileBuilder] generating files of `size` pseudo random bytes, the same `seed` always generate the same content.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RandomVFileBuilder
+{
+  seed : u64,
+  size : u64,
+}
+
+impl RandomVFileBuilder
+{
+  pub fn new(seed : u64, size : u64) -> Self
+  {
+    RandomVFileBuilder{ seed, size }
+  }
+
+  pub fn seed(&self) -> u64
+  {
+    self.seed
+  }
+}
+
+#[typetag::serde]
+impl VFileBuilder for RandomVFileBuilder
+{
+  fn open(&self) -> Result<Box<dyn VFile>>
+  {
+    let seed = self.seed;
+    Ok(Box::new(GeneratedVFile{ generate : move |offset| random_byte(seed, offset), size : self.size, pos : 0 }))
+  }
+
+  fn size(&self) -> u64
+  {
+    self.size
+  }
+}
+
+/**
+ * [VFile] returning the bytes computed by `generate` from their offset.
+ */
+struct GeneratedVFile<F>
+{
+  generate : F,
+  size : u64,
+  pos : u64,
+}
+
+impl<F : Fn(u64) -> u8> Read for GeneratedVFile<F>
+{
+  fn read(&mut self, buf : &mut [u8]) -> io::Result<usize>
+  {
+    let size = (buf.len() as u64).min(self.size.saturating_sub(self.pos)) as usize;
+    for (index, byte) in buf[..size].iter_mut().enumerate()
+    {
+      *byte = (self.generate)(self.pos + index as u64);
+    }
+    self.pos += size as u64;
+    Ok(size)
+  }
+}
+
+impl<F> Seek for GeneratedVFile<F>
+{
+  fn seek(&mut self, pos : SeekFrom) -> io::Result<u64>
+  {
+    let pos = match pos
+    {
+      SeekFrom::Start(pos) => Some(pos),
+      SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+      SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+    };
+
+    match pos
+    {
+      Some(pos) => { self.pos = pos; Ok(pos) },
+      None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{PatternVFileBuilder, RandomVFileBuilder};
+  use crate::vfile::VFileBuilder;
+
+  use std::io::{Read, Seek, SeekFrom};
+
+  fn content(builder : &dyn VFileBuilder) -> Vec<u8>
+  {
+    let mut data = Vec::new();
+    builder.open().unwrap().read_to_end(&mut data).unwrap();
+    data
+  }
+
+  #[test]
+  fn generated_content()
+  {
+    assert!(content(&PatternVFileBuilder::repeat(b"abc".to_vec(), 7)) == b"abcabca");
+    assert!(content(&PatternVFileBuilder::repeat(Vec::new(), 3)) == [0; 3]);
+
+    let counter = PatternVFileBuilder::counter(4096);
+    let mut file = counter.open().unwrap();
+    let mut word = [0; 8];
+    file.seek(SeekFrom::Start(808)).unwrap();
+    file.read_exact(&mut word).unwrap();
+    assert!(u64::from_le_bytes(word) == 808);
+    assert!(file.seek(SeekFrom::End(-4)).unwrap() == 4092 && file.read(&mut word).unwrap() == 4);
+
+    let random = RandomVFileBuilder::new(42, 100000);
+    let data = content(&random);
+    assert!(data.len() == 100000 && data == content(&random) && data != content(&RandomVFileBuilder::new(43, 100000)));
+    let zeros = data.iter().filter(|byte| **byte == 0).count();
+    assert!(zeros > 200 && zeros < 600);
+    let mut middle = [0; 13];
+    let mut file = random.open().unwrap();
+    file.seek(SeekFrom::Start(50003)).unwrap();
+    file.read_exact(&mut middle).unwrap();
+    assert!(middle == data[50003..50016]);
+
+    let json = serde_json::to_string(&(Box::new(random) as Box<dyn VFileBuilder>)).unwrap();
+    assert!(content(serde_json::from_str::<Box<dyn VFileBuilder>>(&json).unwrap().as_ref()) == data);
+  }
+}
@@ -0,0 +1,202 @@
+//! Recursive extraction : a [PipelineConfig] drive rounds of [auto parsing](Session::auto_parse) of the nodes under a start node,
+//! each round parse the nodes created by the tasks of the previous round, until no more plugin apply.
+
+use std::collections::HashSet;
+
+use crate::session::Session;
+use crate::tree::TreeNodeId;
+use crate::task_scheduler::{TaskId, TaskState, TaskGroup};
+use crate::plugin::PluginInfo;
+use crate::tag::Query;
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+
+/// Which plugins a [run] apply and on which nodes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PipelineConfig
+{
+  /// Categories of the plugins applied, all the detected plugins are applied if empty.
+  #[serde(default)]
+  pub categories : Vec<String>,
+  /// Name of plugins never applied.
+  #[serde(default)]
+  pub excluded : Vec<String>,
+  /// Maximum depth of the parsed nodes relatively to the start node.
+  #[serde(default)]
+  pub max_depth : Option<usize>,
+  /// Only the nodes matching this query are parsed.
+  #[serde(default)]
+  pub filter : Option<Query>,
+  /// Maximum number of rounds.
+  #[serde(default)]
+  pub max_rounds : Option<usize>,
+}
+
+impl PipelineConfig
+{
+  /// Return true if the plugin can be applied.
+  pub fn accepts(&self, plugin_info : &dyn PluginInfo) -> bool
+  {
+    (self.categories.is_empty() || self.categories.iter().any(|category| category == plugin_info.category()))
+      && !self.excluded.iter().any(|name| name == plugin_info.name())
+  }
+}
+
+/// Summary of a pipeline [run].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PipelineReport
+{
+  /// Number of rounds that scheduled tasks.
+  pub rounds : usize,
+  /// Tasks scheduled by the pipeline.
+  pub tasks : Vec<TaskId>,
+  /// Tasks that finished with an error.
+  pub failed : Vec<TaskId>,
+  /// Number of nodes that were auto parsed.
+  pub nodes : usize,
+  /// False if the run stopped at `max_rounds` before all nodes were parsed.
+  pub complete : bool,
+}
+
+/// Auto parse `start_id` and its descendants according to `config`, waiting for the tasks of each round before parsing the new nodes.
+/// Only the tasks scheduled by the pipeline are waited, others tasks of the session may run concurrently.
+pub fn run(session : &Session, start_id : TreeNodeId, config : &PipelineConfig) -> Result<PipelineReport>
+{
+  let mut report = PipelineReport::default();
+  let mut parsed = HashSet::new();
+  let group = TaskGroup::new();
+  loop
+  {
+    //the nodes are collected before scheduling, so the nodes created by a task finishing during the walk are parsed by the next round
+    let mut round_nodes = Vec::new();
+    let mut walk = session.tree.walk(start_id);
+    while let Some((node_id, depth)) = walk.next()
+    {
+      if config.max_depth == Some(depth)
+      {
+        walk.skip_children();
+      }
+      if !parsed.insert(node_id) || config.filter.as_ref().is_some_and(|filter| !filter.matches(&session.tree, node_id))
+      {
+        continue
+      }
+      round_nodes.push(node_id);
+    }
+
+    let mut round_tasks = Vec::new();
+    for node_id in round_nodes
+    {
+      report.nodes += 1;
+      round_tasks.extend(session.auto_parse_with(node_id, |plugin_info| config.accepts(plugin_info))?);
+    }
+
+    if round_tasks.is_empty()
+    {
+      report.complete = true;
+      return Ok(report)
+    }
+    round_tasks.iter().for_each(|id| group.add(*id));
+    group.join(&session.task_scheduler);
+    report.rounds += 1;
+    report.failed.extend(round_tasks.iter().filter(|id| matches!(session.task_scheduler.task(**id), Some(TaskState::Finished(_, Err(_))))));
+    report.tasks.extend(round_tasks);
+    if config.max_rounds.is_some_and(|max_rounds| report.rounds >= max_rounds)
+    {
+      return Ok(report)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{PipelineConfig, run};
+  use crate::session::Session;
+  use crate::node::Node;
+  use crate::value::Value;
+  use crate::memoryvfile::MemoryVFileBuilder;
+  use crate::{plugin_partition, plugin_fat};
+  use crate::tree::{Tree, TreeNodeId};
+  use crate::access::{Access, Authorizer, User, as_user};
+
+  use std::sync::Arc;
+  use std::time::{Duration, Instant};
+
+  /// Return a session with the partition and fat plugins and a disk node containing a FAT12 partition.
+  fn disk_session() -> Session
+  {
+    let mut session = Session::new();
+    session.plugins_db.register(Box::new(plugin_partition::Plugin::new()));
+    session.plugins_db.register(Box::new(plugin_fat::Plugin::new()));
+
+    let mut disk = vec![0; 128 * 512];
+    plugin_partition::tests::mbr_entry(&mut disk, 0, 0x01, 8, 64);
+    disk[8 * 512..72 * 512].copy_from_slice(&plugin_fat::tests::fat12_image());
+    let node = Node::new("disk");
+    node.value().add_attribute("data", Value::VFileBuilder(MemoryVFileBuilder::from_buffer(disk)), None);
+    session.tree.add_child(session.tree.root_id, node).unwrap();
+    session
+  }
+
+  #[test]
+  fn pipeline_extract()
+  {
+    let session = disk_session();
+    let report = run(&session, session.tree.root_id, &PipelineConfig::default()).unwrap();
+    assert!(report.complete && report.rounds == 2 && report.tasks.len() == 2 && report.failed.is_empty());
+    assert!(session.tree.get_node("/root/disk/partition_1/DOCS/NOTE.TXT").is_some());
+    //a new run find nothing to do
+    assert!(run(&session, session.tree.root_id, &PipelineConfig::default()).unwrap().tasks.is_empty());
+
+    let session = disk_session();
+    let config = PipelineConfig{ categories : vec!["Volume".into()], ..Default::default() };
+    let report = run(&session, session.tree.root_id, &config).unwrap();
+    assert!(report.complete && report.rounds == 1 && session.tree.get_node("/root/disk/partition_1/README.TXT").is_none());
+
+    //the partition is at depth 2 from the root
+    let session = disk_session();
+    let config = PipelineConfig{ max_depth : Some(1), ..Default::default() };
+    assert!(run(&session, session.tree.root_id, &config).unwrap().tasks.len() == 1);
+
+    let session = disk_session();
+    let config = PipelineConfig{ max_rounds : Some(1), ..Default::default() };
+    let report = run(&session, session.tree.root_id, &config).unwrap();
+    assert!(!report.complete && report.rounds == 1);
+  }
+
+  /// Allow everything, but block the run access to `node` until `disk` has two children.
+  struct WaitPartition
+  {
+    disk : TreeNodeId,
+    node : TreeNodeId,
+  }
+
+  impl Authorizer for WaitPartition
+  {
+    fn authorize(&self, _user : &User, access : Access, tree : &Tree, node_id : Option<TreeNodeId>) -> bool
+    {
+      let start = Instant::now();
+      while access == Access::Run && node_id == Some(self.node) && tree.children_id(self.disk).len() < 2 && start.elapsed() < Duration::from_secs(10)
+      {
+        std::thread::sleep(Duration::from_millis(1));
+      }
+      true
+    }
+  }
+
+  #[test]
+  fn pipeline_round_race()
+  {
+    //the partition is created after the notes of the disk while the first round is walked, it must still be parsed by the second round
+    let session = disk_session();
+    let disk = session.tree.get_node_id("/root/disk").unwrap();
+    let notes = session.tree.add_child(disk, Node::new("notes")).unwrap();
+    session.set_authorizer(Arc::new(WaitPartition{ disk, node : notes }));
+    let _user = as_user(User::new("examiner"));
+
+    let report = run(&session, session.tree.root_id, &PipelineConfig::default()).unwrap();
+    assert!(report.complete && report.rounds == 2 && report.tasks.len() == 2);
+    assert!(session.tree.get_node("/root/disk/partition_1/DOCS/NOTE.TXT").is_some());
+  }
+}
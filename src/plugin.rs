@@ -1,8 +1,24 @@
 //! This module contain the different trait that Plugin must implement.
 
-use crate::tree::Tree;
-use crate::task_scheduler::TaskState;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::tree::{Tree, AttributePath};
+use crate::node::Node;
+use crate::value::{Value, ValueTypeId};
+use crate::task_scheduler::{Task, TaskState, TaskProgress};
+use crate::detection::Signature;
+use crate::cancellation::CancellationToken;
+use crate::quota::ResourceUsage;
+use crate::task_log::TaskLogger;
+use crate::services::Services;
+use crate::external_tool::ExternalTool;
+use crate::context::CaseContext;
+use crate::stagingvfile::{BlobStore, StagingVFileWriter};
+use crate::blockcache::BlockCache;
+use crate::error::RustructError;
 use crossbeam::crossbeam_channel::{Sender};
+use serde::Serialize;
 
 /// JSON String containing [Plugin](PluginInfo) configuration
 pub type PluginConfig = String;
@@ -11,6 +27,96 @@ pub type PluginArgument = String;
 /// JSON String containg [PluginInstance] result
 pub type PluginResult = String;
 
+/**
+ * Version of the interface between the core and the plugins.
+ * It covers the [PluginInfo] and [PluginInstance] traits, [PluginEnvironment], the [Tree] and the
+ * [Value](crate::value::Value) types passed to plugins, and is incremented each time one of them change in an incompatible way.
+ * Plugins built against an other ABI version are refused.
+ */
+pub const ABI_VERSION : u32 = 1;
+
+/// Minimal interval between two progress reports sent to the scheduler, more frequent reports are only kept for the next one.
+pub const PROGRESS_INTERVAL : Duration = Duration::from_millis(100);
+
+/// Version of the core crate the plugins are built against.
+pub const CORE_VERSION : &str = env!("CARGO_PKG_VERSION");
+
+/// Return the optional subsystems available in this build of the core, plugins can require them with [PluginInfo::requires_features].
+pub fn core_features() -> Vec<&'static str>
+{
+  let mut features = vec!["blob_store", "result_store", "validation", "external_tool", "crypto"];
+  if cfg!(feature = "profiler")
+  {
+    features.push("profiler");
+  }
+  if cfg!(feature = "http")
+  {
+    features.push("http");
+  }
+  if cfg!(feature = "zstd")
+  {
+    features.push("zstd");
+  }
+  if cfg!(feature = "auto_register")
+  {
+    features.push("auto_register");
+  }
+  if cfg!(feature = "tokio")
+  {
+    features.push("tokio");
+  }
+  features
+}
+
+/// Return true if core versions `a` and `b` are semver compatible, versions `0.x` are compatible only with the same minor version.
+fn semver_compatible(a : &str, b : &str) -> bool
+{
+  let numbers = |version : &str| -> Vec<u64> { version.split(['.', '-', '+']).take(3).map(|number| number.parse().unwrap_or(0)).collect() };
+  let (a, b) = (numbers(a), numbers(b));
+  match (a.first(), b.first())
+  {
+    (Some(0), Some(0)) => a.get(1) == b.get(1),
+    (major_a, major_b) => major_a == major_b,
+  }
+}
+
+/// Check that `plugin` was built against a compatible core and that the features it requires are available.
+/// Return the warnings for a compatible plugin built against an other core version,
+/// or an [IncompatiblePlugin](RustructError::IncompatiblePlugin) error.
+pub fn check_compatibility(plugin : &dyn PluginInfo) -> anyhow::Result<Vec<String>>
+{
+  let incompatible = |reason : String| RustructError::IncompatiblePlugin(plugin.name().to_string(), reason).into();
+  let mut warnings = Vec::new();
+
+  if plugin.abi_version() != ABI_VERSION
+  {
+    return Err(incompatible(format!("built for ABI version {}, core ABI version is {}", plugin.abi_version(), ABI_VERSION)))
+  }
+
+  if plugin.core_version() != CORE_VERSION
+  {
+    if !semver_compatible(plugin.core_version(), CORE_VERSION)
+    {
+      return Err(incompatible(format!("built against core {}, running core is {}", plugin.core_version(), CORE_VERSION)))
+    }
+    warnings.push(format!("plugin {} was built against core {}, running core is {}", plugin.name(), plugin.core_version(), CORE_VERSION));
+  }
+
+  let features = core_features();
+  let missing : Vec<&str> = plugin.requires_features().into_iter().filter(|feature| !features.contains(feature)).collect();
+  if !missing.is_empty()
+  {
+    return Err(incompatible(format!("missing features {}", missing.join(", "))))
+  }
+
+  let extra : Vec<&str> = plugin.compiled_features().into_iter().filter(|feature| !features.contains(feature)).collect();
+  if !extra.is_empty()
+  {
+    warnings.push(format!("plugin {} was built with features {} not available in this core", plugin.name(), extra.join(", ")));
+  }
+  Ok(warnings)
+}
+
 /**
  * Contain structure needed by Plugin to interact with the core 
  */
@@ -18,13 +124,157 @@ pub struct PluginEnvironment
 {
   pub tree: Tree,
   pub channel : Option<Sender<TaskState>>,   
+  /// Assumptions about the acquired system, used to interpret local times and strings.
+  pub context : CaseContext,
+  /// Store of the derived artifacts staged by the plugin.
+  pub blob_store : BlobStore,
+  /// Block cache shared by the plugins of the session.
+  pub block_cache : Arc<BlockCache>,
+  /// Cancelled when the task running the plugin is [cancelled](crate::task_scheduler::TaskScheduler::cancel).
+  pub cancellation : CancellationToken,
+  /// Resources used by the plugin and their quota.
+  pub resources : ResourceUsage,
+  /// Keep the log of the task running the plugin.
+  pub logger : TaskLogger,
+  /// Services shared by the plugins of the session.
+  pub services : Services,
+  /// Task running the plugin, its progress and when it was last sent on the `channel`.
+  progress : Option<Mutex<(Task, TaskProgress, Option<Instant>)>>,
 }
 
 impl PluginEnvironment
 {
   pub fn new(tree : Tree, channel : Option<Sender<TaskState>>) -> Self
   {
-    PluginEnvironment{ tree, channel }
+    PluginEnvironment{ tree, channel, context : CaseContext::default(), blob_store : BlobStore::default(),
+                       block_cache : Arc::new(BlockCache::default()), cancellation : CancellationToken::new(), resources : ResourceUsage::default(),
+                       logger : TaskLogger::default(), services : Services::default(), progress : None }
+  }
+
+  /// Set the [CancellationToken] of the task running the plugin.
+  pub fn with_cancellation(mut self, cancellation : CancellationToken) -> Self
+  {
+    self.cancellation = cancellation;
+    self
+  }
+
+  /// Set the [ResourceUsage] accounting the resources used by the plugin.
+  pub fn with_resources(mut self, resources : ResourceUsage) -> Self
+  {
+    self.resources = resources;
+    self
+  }
+
+  /// Set the [TaskLogger] keeping the log of the task running the plugin.
+  pub fn with_logger(mut self, logger : TaskLogger) -> Self
+  {
+    self.logger = logger;
+    self
+  }
+
+  /// Set the [Services] shared by the plugins.
+  pub fn with_services(mut self, services : Services) -> Self
+  {
+    self.services = services;
+    self
+  }
+
+  /// Return true if the task running the plugin was cancelled, long running plugins should check it regularly and stop.
+  pub fn is_cancelled(&self) -> bool
+  {
+    self.cancellation.is_cancelled()
+  }
+
+  /// Call `callback` when the task running the plugin is cancelled, to interrupt a blocking operation.
+  pub fn on_cancel<F : FnOnce() + Send + 'static>(&self, callback : F)
+  {
+    self.cancellation.on_cancel(callback)
+  }
+
+  /// Set the [Task] running the plugin, its progress is sent on the `channel`.
+  pub fn with_task(mut self, task : Task) -> Self
+  {
+    self.progress = Some(Mutex::new((task, TaskProgress::default(), None)));
+    self
+  }
+
+  /// Report the progress of the task, with the `percent` of the work done and a `message` describing the current step.
+  pub fn progress<S : Into<String>>(&self, percent : f32, message : S)
+  {
+    let percent = percent.clamp(0.0, 100.0);
+    let message = message.into();
+    self.update_progress(percent >= 100.0, |progress|
+    {
+      progress.percent = percent;
+      progress.message = Some(message);
+    });
+  }
+
+  /// Report the progress of the task as the number of items processed on the `total` number of items.
+  pub fn report_items(&self, done : u64, total : u64)
+  {
+    self.update_progress(done >= total, |progress|
+    {
+      progress.items = Some((done, total));
+      progress.percent = match total
+      {
+        0 => 100.0,
+        total => (done.min(total) as f64 * 100.0 / total as f64) as f32,
+      };
+    });
+  }
+
+  /// Update the progress and send it to the scheduler if the last report is older than [PROGRESS_INTERVAL] or if it's `last`.
+  fn update_progress<F : FnOnce(&mut TaskProgress)>(&self, last : bool, update : F)
+  {
+    let (progress, channel) = match (&self.progress, &self.channel)
+    {
+      (Some(progress), Some(channel)) => (progress, channel),
+      _ => return,
+    };
+    let mut progress = progress.lock().unwrap();
+    let (task, current, sent) = &mut *progress;
+    update(current);
+    if !last && sent.is_some_and(|sent| sent.elapsed() < PROGRESS_INTERVAL)
+    {
+      return
+    }
+    *sent = Some(Instant::now());
+    let task = Task{ progress : Some(current.clone()), ..task.clone() };
+    let _ = channel.send(TaskState::Launched(task));
+  }
+
+  /// Set the [CaseContext] passed to the plugin.
+  pub fn with_context(mut self, context : CaseContext) -> Self
+  {
+    self.context = context;
+    self
+  }
+
+  /// Set the [BlobStore] where the plugin stages derived artifacts.
+  pub fn with_blob_store(mut self, blob_store : BlobStore) -> Self
+  {
+    self.blob_store = blob_store;
+    self
+  }
+
+  /// Set the [BlockCache] shared with the other plugins.
+  pub fn with_block_cache(mut self, block_cache : Arc<BlockCache>) -> Self
+  {
+    self.block_cache = block_cache;
+    self
+  }
+
+  /// Return a writer to a new artifact of the [BlobStore], its finished builder can be added as the data of a node.
+  pub fn stage(&self) -> anyhow::Result<StagingVFileWriter>
+  {
+    self.blob_store.writer()
+  }
+
+  /// Return a new [ExternalTool] running `program`, used by plugins to wrap external decoders.
+  pub fn external_tool<S : Into<String>>(&self, program : S) -> ExternalTool
+  {
+    ExternalTool::new(program)
   }
 }
 
@@ -44,6 +294,106 @@ pub trait PluginInfo
   fn help(&self) -> &'static str;
   ///Return a JSON [String] with structure taken as argument
   fn config(&self) -> anyhow::Result<PluginConfig>; 
+  /// Return the [ABI_VERSION] the plugin was built against, the default is evaluated when the plugin is compiled.
+  fn abi_version(&self) -> u32
+  {
+    ABI_VERSION
+  }
+  /// Return the [CORE_VERSION] the plugin was built against.
+  fn core_version(&self) -> &'static str
+  {
+    CORE_VERSION
+  }
+  /// Return the semver [version](crate::version::Version) of the plugin, the [PluginsDB](crate::plugins_db::PluginsDB) use the newest registered version unless pinned.
+  fn version(&self) -> &'static str
+  {
+    "0.0.0"
+  }
+  /// Return the [core features](core_features) enabled when the plugin was built.
+  fn compiled_features(&self) -> Vec<&'static str>
+  {
+    core_features()
+  }
+  /// Return the [core features](core_features) the plugin need to run.
+  fn requires_features(&self) -> Vec<&'static str>
+  {
+    Vec::new()
+  }
+  /// Return the [prerequisites](Dependency) of the plugin, resolved by [PluginsDB::resolve](crate::plugins_db::PluginsDB::resolve).
+  fn dependencies(&self) -> Vec<Dependency>
+  {
+    Vec::new()
+  }
+  /// Return the [signatures](Signature) of the contents the plugin parse, used by [Session::auto_parse](crate::session::Session::auto_parse).
+  fn signatures(&self) -> Vec<Signature>
+  {
+    Vec::new()
+  }
+  /// Return the argument to parse the [VFileBuilder](crate::vfile::VFileBuilder) at `file` when its content is detected,
+  /// the default is the `{"file" : file}` argument of the plugins parsing a single file.
+  fn detected_argument(&self, file : &AttributePath) -> PluginArgument
+  {
+    serde_json::json!({ "file" : file }).to_string()
+  }
+  /// Return the [PluginMetadata] describing the plugin to the frontends, the default only list its version and the inputs of its [data dependencies](Dependency::Data).
+  fn metadata(&self) -> PluginMetadata
+  {
+    PluginMetadata{ version : self.version(), ..PluginMetadata::from_dependencies(&self.dependencies()) }
+  }
+}
+
+/// Description of a plugin capabilities returned by [PluginInfo::metadata], queried with
+/// [PluginsDB::find_by_tag](crate::plugins_db::PluginsDB::find_by_tag) and [PluginsDB::find_accepting](crate::plugins_db::PluginsDB::find_accepting).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PluginMetadata
+{
+  pub version : &'static str,
+  pub author : &'static str,
+  pub tags : Vec<&'static str>,
+  /// Types of the attributes the plugin take as input.
+  pub inputs : Vec<ValueTypeId>,
+  /// Kinds of nodes the plugin create, like `file` or `partition`.
+  pub outputs : Vec<&'static str>,
+  /// Name of the [worker pool](crate::task_scheduler::TaskSchedulerBuilder::pool) running the tasks of the plugin,
+  /// the default pool if `None` or if the scheduler has no such pool.
+  pub pool : Option<&'static str>,
+}
+
+impl PluginMetadata
+{
+  /// Return the metadata of a plugin with `dependencies`, taking a [VFileBuilder](crate::vfile::VFileBuilder) as input if it has a data dependency.
+  pub fn from_dependencies(dependencies : &[Dependency]) -> Self
+  {
+    let mut metadata = PluginMetadata::default();
+    if dependencies.iter().any(|dependency| matches!(dependency, Dependency::Data(_)))
+    {
+      metadata.inputs.push(ValueTypeId::VFileBuilder);
+    }
+    metadata
+  }
+}
+
+/// A prerequisite of a plugin, declared by [PluginInfo::dependencies].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dependency
+{
+  /// The plugin must run after the plugin with this name, on the same argument.
+  Plugin(&'static str),
+  /// The plugin need a node with an attribute of this name holding a [VFileBuilder](crate::vfile::VFileBuilder).
+  Data(&'static str),
+}
+
+impl Dependency
+{
+  /// Return true if `node` satisfy the dependency, plugin dependencies don't depend on the node and are always satisfied.
+  pub fn is_satisfied_by(&self, node : &Node) -> bool
+  {
+    match self
+    {
+      Dependency::Plugin(_) => true,
+      Dependency::Data(name) => matches!(node.value().get_value(name), Some(Value::VFileBuilder(_))),
+    }
+  }
 }
 
 /** 
@@ -57,6 +407,80 @@ pub trait PluginInstance
   /// Run the plugin and pass it JSON `argument` [String].
   /// Return the result as a JSON `String` or an Error.
   fn run(&mut self, argument : PluginArgument, env : PluginEnvironment) -> anyhow::Result<PluginResult>;
+  /// Called once by the [Worker](crate::task_scheduler::Worker) before [run](PluginInstance::run), to load the resources used by the instance like a signature database.
+  /// An error fail the task without running the plugin.
+  fn init(&mut self, _env : &PluginEnvironment) -> anyhow::Result<()>
+  {
+    Ok(())
+  }
+  /// Called once by the [Worker](crate::task_scheduler::Worker) after [run](PluginInstance::run) returned, if [init](PluginInstance::init) succeeded, to release the resources of the instance.
+  fn teardown(&mut self)
+  {
+  }
+  /// Return the name of the worker pool running the tasks of the plugin, see [PluginMetadata::pool].
+  fn pool(&self) -> Option<&'static str>
+  {
+    None
+  }
+  /// Run the plugin with a typed `argument` and return its result as a [Value], keeping types like builders that don't survive JSON.
+  /// Plugins created with [plugin!](crate::plugin) convert them directly from and to their argument and result types,
+  /// the default implementation is an adapter calling [run](PluginInstance::run) with their JSON serialization.
+  fn run_value(&mut self, argument : Value, env : PluginEnvironment) -> anyhow::Result<Value>
+  {
+    let result = self.run(serde_json::to_string(&argument)?, env)?;
+    Ok(serde_json::from_str(&result)?)
+  }
+}
+
+/**
+ * A plugin registered at compile time with [register_plugin!](crate::register_plugin),
+ * all registered plugins are added by [PluginsDB::with_registered](crate::plugins_db::PluginsDB::with_registered).
+ */
+#[cfg(feature = "auto_register")]
+pub struct PluginRegistration
+{
+  /// Return a new [PluginInfo] of the plugin.
+  pub constructor : fn() -> Box<dyn PluginInfo + Sync + Send>,
+}
+
+#[cfg(feature = "auto_register")]
+inventory::collect!(PluginRegistration);
+
+/// Return a new [PluginInfo] of each plugin registered with [register_plugin!](crate::register_plugin).
+#[cfg(feature = "auto_register")]
+pub fn registered_plugins() -> Vec<Box<dyn PluginInfo + Sync + Send>>
+{
+  inventory::iter::<PluginRegistration>.into_iter().map(|registration| (registration.constructor)()).collect()
+}
+
+/// Register at compile time a [PluginInfo] type having a `new` constructor, like the `Plugin` created by [plugin!](crate::plugin).
+/// Plugins are registered only when the `auto_register` feature is enabled.
+#[macro_export]
+#[cfg(feature = "auto_register")]
+macro_rules! register_plugin
+{
+    ( $plugin_info:ty ) =>
+    {
+      $crate::inventory::submit!{
+        $crate::plugin::PluginRegistration{ constructor :
+        {
+          fn constructor() -> Box<dyn $crate::plugin::PluginInfo + Sync + Send>
+          {
+            Box::new(<$plugin_info>::new())
+          }
+          constructor
+        }}
+      }
+    };
+}
+
+/// Register at compile time a [PluginInfo] type having a `new` constructor, like the `Plugin` created by [plugin!](crate::plugin).
+/// Plugins are registered only when the `auto_register` feature is enabled.
+#[macro_export]
+#[cfg(not(feature = "auto_register"))]
+macro_rules! register_plugin
+{
+    ( $plugin_info:ty ) => {};
 }
 
 #[macro_export]
@@ -72,10 +496,13 @@ macro_rules! config_schema
 }
 
 /// Macro to help creation of plugin. 
+/// The optional `init` and `teardown` name methods of the plugin type called by the [PluginInstance::init] and [PluginInstance::teardown] hooks.
 #[macro_export]
 macro_rules! plugin 
 {
-    ( $name:expr, $category:expr, $help:expr, $plugin_type:ty , $plugin_argument:ty) => 
+    ( $name:expr, $category:expr, $help:expr, $plugin_type:ty , $plugin_argument:ty
+      $(, requires : [$($feature:expr),*])? $(, depends : [$($dependency:expr),*])? $(, signatures : [$($signature:expr),*])?
+      $(, metadata : { $($field:ident : $value:expr),* })? $(, init : $init:ident)? $(, teardown : $teardown:ident)?) => 
     {
         #[derive(Default)]
         pub struct Plugin
@@ -118,6 +545,36 @@ macro_rules! plugin
                 let schema = config_schema!($plugin_argument);
                 Ok(serde_json::to_string(&schema)?)
             }
+
+            fn requires_features(&self) -> Vec<&'static str>
+            {
+              vec![$($($feature),*)?]
+            }
+
+            fn dependencies(&self) -> Vec<$crate::plugin::Dependency>
+            {
+              vec![$($($dependency),*)?]
+            }
+
+            fn signatures(&self) -> Vec<$crate::detection::Signature>
+            {
+              vec![$($($signature),*)?]
+            }
+
+            fn version(&self) -> &'static str
+            {
+              self.metadata().version
+            }
+
+            fn metadata(&self) -> $crate::plugin::PluginMetadata
+            {
+              //version and author of the crate defining the plugin
+              #[allow(unused_mut)]
+              let mut metadata = $crate::plugin::PluginMetadata{ version : env!("CARGO_PKG_VERSION"), author : env!("CARGO_PKG_AUTHORS"),
+                                                                 ..$crate::plugin::PluginMetadata::from_dependencies(&self.dependencies()) };
+              $($( metadata.$field = $value; )*)?
+              metadata
+            }
         }
 
         impl PluginInstance for $plugin_type
@@ -133,6 +590,29 @@ macro_rules! plugin
                  let result = self.run(arg, env)?;
                  Ok(serde_json::to_string(&result)?)
             }
+
+            fn init(&mut self, _env : &PluginEnvironment) -> anyhow::Result<()>
+            {
+              $( self.$init(_env)?; )?
+              Ok(())
+            }
+
+            fn teardown(&mut self)
+            {
+              $( self.$teardown(); )?
+            }
+
+            fn pool(&self) -> Option<&'static str>
+            {
+              Plugin::new().metadata().pool
+            }
+
+            fn run_value(&mut self, argument : $crate::value::Value, env : PluginEnvironment) -> anyhow::Result<$crate::value::Value>
+            {
+                 let arg = $crate::value::from_value(argument)?;
+                 let result = self.run(arg, env)?;
+                 $crate::value::to_value(&result)
+            }
         }
     }    
 }
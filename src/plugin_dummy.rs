@@ -1,13 +1,14 @@
 //! The `dummy plugin` is an exemple of how to write a plugin.
 
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
 
 use crate::config_schema;
 use crate::plugin::{PluginInfo, PluginInstance, PluginConfig, PluginArgument, PluginResult, PluginEnvironment};
-use crate::reflect::ReflectStruct;
+use crate::reflect::{ReflectStruct, FieldInfo};
 use crate::node::Node;
 use crate::tree::{TreeNodeId, TreeNodeIdSchema};
-use crate::value::Value;
+use crate::value::{Value, ValueTypeId};
 use crate::tree::Tree;
 use crate::error::{RustructError};
 
@@ -75,7 +76,7 @@ impl DummyStatic
 #[derive(Debug)]
 struct DummyDynamic 
 {
-  a : u32,
+  a : AtomicU32,
   b : u64,
 }
 
@@ -83,12 +84,12 @@ impl DummyDynamic
 {
   pub fn new() -> Self
   {
-    DummyDynamic{a : 1, b : 2}
+    DummyDynamic{a : AtomicU32::new(1), b : 2}
   }
 
   pub fn field_c(&self) -> u64
   {
-    self.a as u64 + self.b
+    self.a.load(Ordering::Relaxed) as u64 + self.b
   }
 }
 
@@ -99,21 +100,38 @@ impl ReflectStruct for DummyDynamic
     "DummyDynamic"
   }
 
-  fn infos(&self) -> Vec<(&'static str, Option<&'static str>) >
+  fn field_infos(&self) -> Vec<FieldInfo>
   {
-    vec![("a", None), ("b", None), ("c", None)]
+    vec![FieldInfo::field("a", None).with_type(ValueTypeId::U32),
+         FieldInfo::field("b", None).with_type(ValueTypeId::U64),
+         FieldInfo::method("c", Some("sum of a and b")).with_type(ValueTypeId::U64)]
   }
 
   fn get_value(&self, name : &str) -> Option<Value>
   {
     match name
     {
-      "a" => Some(Value::from(self.a)),
+      "a" => Some(Value::from(self.a.load(Ordering::Relaxed))),
       "b" => Some(Value::from(self.b)),
       "c" => Some(Value::from(self.field_c())),
       _ => None,
     }
   }
+
+  fn writable(&self) -> Vec<&'static str>
+  {
+    vec!["a"]
+  }
+
+  fn set_value(&self, name : &str, value : Value) -> Result<()>
+  {
+    match name
+    {
+      "a" => self.a.store(u32::try_from(value)?, Ordering::Relaxed),
+      _ => return Err(RustructError::ReadOnlyField(name.to_string()).into()),
+    }
+    Ok(())
+  }
 }
 
 pub struct DummyDynamicValue
@@ -189,7 +207,8 @@ impl Dummy
             None => return Err(RustructError::ArgumentNotFound("parent").into()),
         };
         self.create_nodes(parent, env.tree)?;
-        info!("\tdummy finished");
+        //kept in the log of the task
+        env.logger.info(format!("dummy finished with counter {}", self.count));
 
         Ok(Results{count : self.count})
     }
@@ -335,10 +354,31 @@ mod tests
       assert!(dummy_dynamic_node_attributes.get_value("dummy_dynamic").unwrap().as_reflect_struct().get_value("b").unwrap().as_u64() == 2);
       assert!(dummy_dynamic_node_attributes.get_value("dummy_dynamic").unwrap().as_reflect_struct().get_value("c").unwrap().as_u64() == 3);
 
+      let dummy_dynamic = dummy_dynamic_node_attributes.get_value("dummy_dynamic").unwrap().as_reflect_struct();
+      assert!(dummy_dynamic.is_writable("a") && !dummy_dynamic.is_writable("c"));
+      dummy_dynamic.set_value("a", crate::value::Value::U64(10)).unwrap();
+      assert!(dummy_dynamic.set_value("c", crate::value::Value::U64(10)).is_err());
+      assert!(dummy_dynamic_node_attributes.get_value("dummy_dynamic").unwrap().as_reflect_struct().get_value("c").unwrap().as_u64() == 12);
+
       let dummy_dynamic_value_node = tree.get_node("/root/Dummy/DummyDynamicValue").unwrap();
       let dummy_dynamic_value_node_attributes = dummy_dynamic_value_node.value();
 
       assert!(dummy_dynamic_value_node_attributes.get_value("calc_void").unwrap().to_string() == "ABCDEFGH1234567890");
       assert!(dummy_dynamic_value_node_attributes.get_value("calc_with_value").unwrap().to_string() == "ABCDEFGH1234567890");
     }
+
+    #[test]
+    fn dummy_plugin_run_value()
+    {
+      let tree = Tree::new();
+      let mut arguments = crate::attribute::Attributes::new();
+      arguments.add_attribute("parent", crate::value::Value::NodeId(tree.root_id), None);
+      arguments.add_attribute("file_name", crate::value::Value::from("/home/user/test.txt"), None);
+      arguments.add_attribute("offset", crate::value::Value::U32(0), None);
+
+      let mut dummy = Plugin::new().instantiate();
+      let result = dummy.run_value(crate::value::Value::Attributes(arguments), PluginEnvironment::new(tree.clone(), None)).unwrap();
+      assert!(result.get_path("count").unwrap() == crate::value::Value::U32(1));
+      assert!(tree.get_node("/root/Dummy").is_some());
+    }
 }
@@ -1,18 +1,16 @@
-//! The `dummy singleton plugin` is an exemple of how to write a singleton/static plugin.
-//! This plugin instantiate method will always return the same object.
+//! The `dummy singleton plugin` is an exemple of how to share state between the instances of a plugin.
+//! Each instance increment the same counter, kept as a [service](crate::services::Services) of the session rather than in a static singleton.
 
 use crate::config_schema;
 use crate::plugin::{PluginInfo, PluginInstance, PluginConfig, PluginArgument, PluginResult, PluginEnvironment};
 
+use std::sync::atomic::{AtomicU32, Ordering};
+
 use anyhow::Result;
-use owned_singleton::Singleton;
 use serde::{Serialize, Deserialize};
 use schemars::{JsonSchema};
 use log::info;
 
-#[Singleton(Send,Sync)]
-static mut OwnedDummySingleton : DummySingleton = DummySingleton{ count : 0  };
-
 #[derive(Default)]
 pub struct DummySingletonInfo
 {
@@ -51,20 +49,20 @@ impl PluginInfo for DummySingletonInfo
 
     fn instantiate(&self) -> Box<dyn PluginInstance + Send + Sync>
     {
-        unsafe 
-        {
-          Box::new(OwnedDummySingleton::new())
-        }
+        Box::new(DummySingleton{})
     }
 }
 
+/// Counter shared by the instances of the plugin.
+#[derive(Default)]
+pub struct DummyCounter(AtomicU32);
+
 #[derive(Default)]
 pub struct DummySingleton
 {
-    count : u32,
 }
 
-impl PluginInstance for OwnedDummySingleton
+impl PluginInstance for DummySingleton
 {
     fn name(&self) -> &'static str
     {
@@ -92,18 +90,18 @@ pub struct Results
     count : u32
 }
 
-impl OwnedDummySingleton
+impl DummySingleton
 {
-    fn run(&mut self, argument : Arguments, _env : PluginEnvironment) -> Result< Results>
+    fn run(&mut self, argument : Arguments, env : PluginEnvironment) -> Result< Results>
     {
         info!("\tdummy_singleton run({:?})", argument);
 
         info!("\tdummy_singleton parser is running on file : {:?}", argument.file_name);
-        self.count += 1;
-        info!("\tdummy_singleton counter : {}", self.count);
+        let count = env.services.get_or_insert_with(DummyCounter::default).0.fetch_add(1, Ordering::SeqCst) + 1;
+        info!("\tdummy_singleton counter : {}", count);
         info!("\tdummy_singleton finished");
 
-        Ok(Results{count : self.count})
+        Ok(Results{count})
     }
 }
 
@@ -115,18 +113,20 @@ mod tests
     use serde_json::json;
     use crate::plugin::{PluginInfo, PluginEnvironment};
     use crate::plugin_dummy_singleton::DummySingletonInfo;
+    use crate::services::Services;
     use crate::tree::Tree;
 
     #[test]
     fn dummy_plugin_singleton_test_instances()
     {
        let tree = Tree::new();
+       let services = Services::new();
        let dummy_singleton_info = DummySingletonInfo::new();
        let mut dummy_singleton = dummy_singleton_info.instantiate();
        //let args = dummy_singleton_info.config().unwrap();
 
        let args = json!({"file_name" : "test", "offset" : 0}).to_string();
-       match dummy_singleton.run(args.to_string(), PluginEnvironment::new(tree.clone(), None))
+       match dummy_singleton.run(args.to_string(), PluginEnvironment::new(tree.clone(), None).with_services(services.clone()))
        {
          Ok(res) => {
                       let res : Value = serde_json::from_str(&res).unwrap();
@@ -139,7 +139,7 @@ mod tests
          Err(_err) => assert!(false),
        }
 
-       match dummy_singleton.run(args.to_string(), PluginEnvironment::new(tree.clone(), None))
+       match dummy_singleton.run(args.to_string(), PluginEnvironment::new(tree.clone(), None).with_services(services.clone()))
        {
          Ok(res) => {
                       let res : Value = serde_json::from_str(&res).unwrap();
@@ -153,7 +153,7 @@ mod tests
        }
 
        let mut dummy_singleton_new = dummy_singleton_info.instantiate();
-       match dummy_singleton_new.run(args.to_string(), PluginEnvironment::new(tree, None))
+       match dummy_singleton_new.run(args.to_string(), PluginEnvironment::new(tree.clone(), None).with_services(services.clone()))
        {
          Ok(res) => {
                       let res : Value = serde_json::from_str(&res).unwrap();
@@ -166,4 +166,21 @@ mod tests
          Err(err) => { eprintln!("{}", err); assert!(false) },
        }
     }
+
+    #[test]
+    fn dummy_plugin_singleton_session_services()
+    {
+       use crate::session::Session;
+       use crate::plugin_dummy_singleton::DummyCounter;
+       use std::sync::atomic::Ordering;
+
+       let mut session = Session::new();
+       session.plugins_db.register(Box::new(DummySingletonInfo::new()));
+       let args = json!({"file_name" : "test", "offset" : 0}).to_string();
+       session.run("dummy_singleton", args.clone(), false).unwrap();
+       session.run("dummy_singleton", args, true).unwrap();
+       assert!(session.services().require::<DummyCounter>().unwrap().0.load(Ordering::SeqCst) == 2);
+       session.clear();
+       assert!(session.services().get::<DummyCounter>().is_some());
+    }
 }
@@ -0,0 +1,441 @@
+//! The `fat plugin` is a reference plugin reading FAT12 and FAT16 file systems.
+//! Directories and files are created as child nodes of the parsed file, the content of each file
+//! is exposed in a `data` attribute created with a [MappedVFileBuilder] following the cluster chain.
+
+use std::io::{Read, Seek, SeekFrom};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::config_schema;
+use crate::detection::Signature;
+use crate::plugin::{PluginInfo, PluginInstance, PluginConfig, PluginArgument, PluginResult, PluginEnvironment, Dependency};
+use crate::node::Node;
+use crate::tree::{Tree, TreeNodeId, AttributePath};
+use crate::value::Value;
+use crate::vfile::{VFile, VFileBuilder};
+use crate::mappedvfile::{MappedVFileBuilder, FileRanges};
+use crate::datetime::DosDateTime;
+use crate::context::CaseContext;
+use crate::error::RustructError;
+
+use serde::{Serialize, Deserialize};
+use schemars::{JsonSchema};
+use byteorder::{ByteOrder, LittleEndian};
+use log::warn;
+use anyhow::Result;
+
+use crate::{plugin, register_plugin};
+
+plugin!("fat", "FileSystem", "Read FAT12 and FAT16 file systems", Fat, Arguments, depends : [Dependency::Data("data")],
+        signatures : [Signature::Magic{ offset : 54, bytes : b"FAT12   " }, Signature::Magic{ offset : 54, bytes : b"FAT16   " }],
+        metadata : { tags : vec!["filesystem", "fat"], outputs : vec!["file", "directory"] });
+register_plugin!(Plugin);
+
+/// Maximum depth of directories, protect against directory loops.
+const MAX_DEPTH : usize = 32;
+
+const ATTRIBUTE_READ_ONLY : u8 = 0x01;
+const ATTRIBUTE_HIDDEN : u8 = 0x02;
+const ATTRIBUTE_SYSTEM : u8 = 0x04;
+const ATTRIBUTE_VOLUME : u8 = 0x08;
+const ATTRIBUTE_DIRECTORY : u8 = 0x10;
+const ATTRIBUTE_LONG_NAME : u8 = 0x0f;
+
+/// The fat plugin
+#[derive(Default)]
+pub struct Fat
+{
+}
+
+/// The argument struct that will be passed to the run method of the plugin.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Arguments
+{
+  /// Attribute containing the [VFileBuilder] of the file system to parse.
+  file : AttributePath,
+}
+
+/// The results class that will be returned from the plugin.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Results
+{
+  /// Type of the file system : `FAT12` or `FAT16`.
+  fat_type : String,
+  /// Number of file nodes created.
+  files : u32,
+  /// Number of directory nodes created.
+  directories : u32,
+}
+
+/// A directory entry.
+struct DirectoryEntry
+{
+  name : String,
+  attributes : u8,
+  cluster : u32,
+  size : u32,
+  created : (u16, u16),
+  modified : (u16, u16),
+  accessed : u16,
+}
+
+/// Location of a directory content : the fixed root directory or a cluster chain.
+enum Directory
+{
+  Root,
+  Cluster(u32),
+}
+
+/// A parsed FAT file system.
+struct FileSystem
+{
+  builder : Arc<dyn VFileBuilder>,
+  fat12 : bool,
+  cluster_size : u64,
+  root_offset : u64,
+  root_size : u64,
+  data_offset : u64,
+  /// Next cluster of each cluster.
+  fat : Vec<u32>,
+  /// Context used to decode names and times.
+  context : CaseContext,
+}
+
+impl FileSystem
+{
+  /// Parse the boot sector and the first FAT of the file system created by `builder`.
+  fn new(builder : Arc<dyn VFileBuilder>, context : CaseContext) -> Result<Self>
+  {
+    let mut file = builder.open()?;
+    let boot = read_at(&mut file, 0, 512)?;
+
+    let bytes_per_sector = LittleEndian::read_u16(&boot[11..13]) as u64;
+    let sectors_per_cluster = boot[13] as u64;
+    let reserved = LittleEndian::read_u16(&boot[14..16]) as u64;
+    let fats = boot[16] as u64;
+    let root_entries = LittleEndian::read_u16(&boot[17..19]) as u64;
+    let fat_size = LittleEndian::read_u16(&boot[22..24]) as u64;
+    let total_sectors = match LittleEndian::read_u16(&boot[19..21])
+    {
+      0 => LittleEndian::read_u32(&boot[32..36]) as u64,
+      total => total as u64,
+    };
+
+    if boot[510..512] != [0x55, 0xaa] || !bytes_per_sector.is_power_of_two() || bytes_per_sector < 512 ||
+       !sectors_per_cluster.is_power_of_two() || fats == 0 || fat_size == 0
+    {
+      return Err(RustructError::Parse("FAT", "invalid boot sector".into()).into())
+    }
+
+    let root_offset = (reserved + fats * fat_size) * bytes_per_sector;
+    let root_size = root_entries * 32;
+    let data_offset = root_offset + root_size.div_ceil(bytes_per_sector) * bytes_per_sector;
+    let data_sectors = (total_sectors * bytes_per_sector).saturating_sub(data_offset) / bytes_per_sector;
+    let cluster_count = data_sectors / sectors_per_cluster;
+    if cluster_count >= 65525
+    {
+      return Err(RustructError::Parse("FAT", "FAT32 is not supported".into()).into())
+    }
+    if data_offset > builder.size()
+    {
+      return Err(RustructError::Parse("FAT", "file system is bigger than the file".into()).into())
+    }
+
+    let fat12 = cluster_count < 4085;
+    let table = read_at(&mut file, reserved * bytes_per_sector, (fat_size * bytes_per_sector) as usize)?;
+    let fat = (0..cluster_count as usize + 2).map(|cluster|
+    {
+      if fat12
+      {
+        let offset = cluster * 3 / 2;
+        match table.get(offset..offset + 2)
+        {
+          Some(entry) if cluster % 2 == 1 => (LittleEndian::read_u16(entry) >> 4) as u32,
+          Some(entry) => (LittleEndian::read_u16(entry) & 0xfff) as u32,
+          None => 0,
+        }
+      }
+      else
+      {
+        table.get(cluster * 2..cluster * 2 + 2).map(|entry| LittleEndian::read_u16(entry) as u32).unwrap_or(0)
+      }
+    }).collect();
+
+    Ok(FileSystem{ builder, fat12, cluster_size : sectors_per_cluster * bytes_per_sector, root_offset, root_size, data_offset, fat, context })
+  }
+
+  /// Return the clusters of the chain starting at `cluster`.
+  fn chain(&self, mut cluster : u32) -> Vec<u32>
+  {
+    let end = if self.fat12 { 0xff7 } else { 0xfff7 };
+    let mut clusters = Vec::new();
+
+    while cluster >= 2 && cluster < end && (cluster as usize) < self.fat.len() && clusters.len() < self.fat.len()
+    {
+      clusters.push(cluster);
+      cluster = self.fat[cluster as usize];
+    }
+    clusters
+  }
+
+  /// Return [FileRanges] mapping the first `size` bytes of the cluster chain starting at `cluster`.
+  fn ranges(&self, cluster : u32, size : u64) -> FileRanges
+  {
+    let mut ranges = FileRanges::new();
+    let mut offset = 0;
+
+    for cluster in self.chain(cluster)
+    {
+      if offset >= size
+      {
+        break;
+      }
+      let length = self.cluster_size.min(size - offset);
+      let cluster_offset = self.data_offset + (cluster as u64 - 2) * self.cluster_size;
+      if cluster_offset + length > self.builder.size()
+      {
+        warn!("fat : cluster {} is outside of the file system", cluster);
+        break;
+      }
+      ranges.push(offset..offset + length, cluster_offset, self.builder.clone());
+      offset += length;
+    }
+    ranges
+  }
+
+  /// Read and return the entries of `directory`.
+  fn entries(&self, directory : &Directory) -> Result<Vec<DirectoryEntry>>
+  {
+    let mut content = Vec::new();
+    match directory
+    {
+      Directory::Root => content = read_at(&mut self.builder.open()?, self.root_offset, self.root_size as usize)?,
+      Directory::Cluster(cluster) =>
+      {
+        let size = self.chain(*cluster).len() as u64 * self.cluster_size;
+        MappedVFileBuilder::new(self.ranges(*cluster, size)).open()?.read_to_end(&mut content)?;
+      },
+    };
+
+    let mut entries = Vec::new();
+    for entry in content.chunks_exact(32)
+    {
+      match entry[0]
+      {
+        0x00 => break,
+        0xe5 => continue,
+        _ => (),
+      }
+      let attributes = entry[11];
+      if attributes & ATTRIBUTE_LONG_NAME == ATTRIBUTE_LONG_NAME || attributes & ATTRIBUTE_VOLUME != 0
+      {
+        continue;
+      }
+
+      let mut base = entry[0..8].to_vec();
+      if base[0] == 0x05
+      {
+        base[0] = 0xe5;
+      }
+      let base = self.context.decode_oem(&base).trim_end().to_string();
+      let extension = self.context.decode_oem(&entry[8..11]).trim_end().to_string();
+      if base == "." || base == ".."
+      {
+        continue;
+      }
+
+      let date_time = |offset : usize| (LittleEndian::read_u16(&entry[offset + 2..offset + 4]), LittleEndian::read_u16(&entry[offset..offset + 2]));
+      entries.push(DirectoryEntry{
+        name : if extension.is_empty() { base } else { format!("{}.{}", base, extension) },
+        attributes,
+        cluster : LittleEndian::read_u16(&entry[26..28]) as u32,
+        size : LittleEndian::read_u32(&entry[28..32]),
+        created : date_time(14),
+        modified : date_time(22),
+        accessed : LittleEndian::read_u16(&entry[18..20]),
+      });
+    }
+    Ok(entries)
+  }
+}
+
+/// Read `size` bytes at `offset` in `file`.
+fn read_at(file : &mut Box<dyn VFile>, offset : u64, size : usize) -> Result<Vec<u8>>
+{
+  let mut buffer = vec![0; size];
+  file.seek(SeekFrom::Start(offset))?;
+  file.read_exact(&mut buffer)?;
+  Ok(buffer)
+}
+
+/// Create the nodes of a [FileSystem] in the tree.
+struct NodeCreator<'a>
+{
+  fs : &'a FileSystem,
+  tree : &'a Tree,
+  /// First cluster of the visited directories.
+  visited : HashSet<u32>,
+  results : Results,
+}
+
+impl NodeCreator<'_>
+{
+  /// Create the nodes of the entries of `directory` under `parent_id`.
+  fn create_nodes(&mut self, parent_id : TreeNodeId, directory : Directory, depth : usize) -> Result<()>
+  {
+    let fs = self.fs;
+    for entry in fs.entries(&directory)?
+    {
+      let node = Node::new(entry.name.clone());
+      let is_directory = entry.attributes & ATTRIBUTE_DIRECTORY != 0;
+
+      if !is_directory
+      {
+        let data = MappedVFileBuilder::new(fs.ranges(entry.cluster, entry.size as u64));
+        if data.size() != entry.size as u64
+        {
+          warn!("fat : file {} is truncated", entry.name);
+        }
+        node.value().add_attribute("data", Value::VFileBuilder(Arc::new(data)), None);
+        node.value().add_attribute("size", Value::U32(entry.size), None);
+      }
+
+      for (name, date, time) in [("created", entry.created.0, entry.created.1), ("modified", entry.modified.0, entry.modified.1), ("accessed", entry.accessed, 0)]
+      {
+        if let Ok(datetime) = DosDateTime(date, time).to_datetime_with(&fs.context)
+        {
+          node.value().add_attribute(name, Value::DateTime(datetime), None);
+        }
+      }
+      node.value().add_attribute("read_only", Value::Bool(entry.attributes & ATTRIBUTE_READ_ONLY != 0), None);
+      node.value().add_attribute("hidden", Value::Bool(entry.attributes & ATTRIBUTE_HIDDEN != 0), None);
+      node.value().add_attribute("system", Value::Bool(entry.attributes & ATTRIBUTE_SYSTEM != 0), None);
+
+      let node_id = match self.tree.add_child(parent_id, node)
+      {
+        Ok(node_id) => node_id,
+        Err(err) => { warn!("fat : can't add {} : {}", entry.name, err); continue },
+      };
+
+      if !is_directory
+      {
+        self.results.files += 1;
+        continue;
+      }
+      self.results.directories += 1;
+
+      if depth + 1 >= MAX_DEPTH || !self.visited.insert(entry.cluster)
+      {
+        warn!("fat : directory {} loops or is too deep", entry.name);
+        continue;
+      }
+      self.create_nodes(node_id, Directory::Cluster(entry.cluster), depth + 1)?;
+    }
+    Ok(())
+  }
+}
+
+impl Fat
+{
+  fn run(&mut self, argument : Arguments, env : PluginEnvironment) -> Result<Results>
+  {
+    let builder = argument.file.get_value(&env.tree).ok_or(RustructError::ValueNotFound("file"))?
+                          .try_as_vfile_builder().ok_or(RustructError::ValueTypeMismatch)?;
+
+    let fs = FileSystem::new(builder, env.context)?;
+    let results = Results{ fat_type : if fs.fat12 { "FAT12".into() } else { "FAT16".into() }, ..Default::default() };
+    let mut creator = NodeCreator{ fs : &fs, tree : &env.tree, visited : HashSet::new(), results };
+    creator.create_nodes(argument.file.node_id, Directory::Root, 0)?;
+
+    Ok(creator.results)
+  }
+}
+
+#[cfg(test)]
+pub(crate) mod tests
+{
+  use crate::plugin::{PluginInfo, PluginEnvironment};
+  use crate::plugin_fat::Plugin;
+  use crate::plugin_partition::tests::{mbr_entry, run_partition};
+  use crate::tree::{Tree, AttributePath};
+
+  use byteorder::{ByteOrder, LittleEndian};
+  use chrono::{TimeZone, Utc};
+  use serde_json::json;
+  use std::io::Read;
+
+  /// Write a directory entry in `directory`.
+  fn directory_entry(directory : &mut [u8], index : usize, name : &[u8; 11], attributes : u8, cluster : u16, size : u32)
+  {
+    let entry = &mut directory[index * 32..(index + 1) * 32];
+    entry[0..11].copy_from_slice(name);
+    entry[11] = attributes;
+    //2021-03-04 05:06:08
+    LittleEndian::write_u16(&mut entry[22..24], (5 << 11) | (6 << 5) | 4);
+    LittleEndian::write_u16(&mut entry[24..26], (41 << 9) | (3 << 5) | 4);
+    LittleEndian::write_u16(&mut entry[26..28], cluster);
+    LittleEndian::write_u32(&mut entry[28..32], size);
+  }
+
+  /// Return a FAT12 image of 64 sectors with one sector per cluster.
+  pub(crate) fn fat12_image() -> Vec<u8>
+  {
+    let mut image = vec![0; 64 * 512];
+    let boot = &mut image[0..512];
+    LittleEndian::write_u16(&mut boot[11..13], 512);
+    boot[13] = 1;
+    LittleEndian::write_u16(&mut boot[14..16], 1);
+    boot[16] = 2;
+    LittleEndian::write_u16(&mut boot[17..19], 16);
+    LittleEndian::write_u16(&mut boot[19..21], 64);
+    LittleEndian::write_u16(&mut boot[22..24], 1);
+    boot[54..62].copy_from_slice(b"FAT12   ");
+    boot[510] = 0x55;
+    boot[511] = 0xaa;
+
+    //clusters 2 -> 3, 4 and 5 are end of chain
+    image[512..521].copy_from_slice(&[0xf8, 0xff, 0xff, 0x03, 0xf0, 0xff, 0xff, 0xff, 0xff]);
+
+    let root = 512 * 3;
+    directory_entry(&mut image[root..], 0, b"VOLUME     ", 0x08, 0, 0);
+    directory_entry(&mut image[root..], 1, b"README  TXT", 0x01, 2, 600);
+    directory_entry(&mut image[root..], 2, b"\xe5ELETED TXT", 0x00, 6, 10);
+    directory_entry(&mut image[root..], 3, b"DOCS       ", 0x10, 4, 0);
+
+    let data = 512 * 4;
+    image[data..data + 600].copy_from_slice(&[b'r'; 600]);
+    directory_entry(&mut image[data + 2 * 512..], 0, b".          ", 0x10, 4, 0);
+    directory_entry(&mut image[data + 2 * 512..], 1, b"..         ", 0x10, 0, 0);
+    directory_entry(&mut image[data + 2 * 512..], 2, b"NOTE    TXT", 0x00, 5, 4);
+    image[data + 3 * 512..data + 3 * 512 + 4].copy_from_slice(b"note");
+    image
+  }
+
+  #[test]
+  fn fat12_in_partition()
+  {
+    let mut disk = vec![0; 128 * 512];
+    mbr_entry(&mut disk, 0, 0x01, 8, 64);
+    disk[8 * 512..72 * 512].copy_from_slice(&fat12_image());
+
+    let tree = Tree::new();
+    run_partition(&tree, "disk", disk);
+    let args = json!({"file" : AttributePath::new(&tree, "/root/disk/partition_1:data").unwrap()}).to_string();
+    let result = Plugin::new().instantiate().run(args, PluginEnvironment::new(tree.clone(), None)).unwrap();
+    let result : serde_json::Value = serde_json::from_str(&result).unwrap();
+    assert!(result["fat_type"] == "FAT12" && result["files"] == 2 && result["directories"] == 1);
+    assert!(tree.children_name(tree.get_node_id("/root/disk/partition_1").unwrap()).len() == 2);
+
+    let readme = tree.get_node("/root/disk/partition_1/README.TXT").unwrap();
+    assert!(readme.value().get_value("read_only").unwrap().as_bool());
+    assert!(readme.value().get_value("modified").unwrap().as_date_time() == Utc.with_ymd_and_hms(2021, 3, 4, 5, 6, 8).unwrap());
+    let mut content = Vec::new();
+    readme.value().get_value("data").unwrap().as_vfile_builder().open().unwrap().read_to_end(&mut content).unwrap();
+    assert!(content == vec![b'r'; 600]);
+
+    let note = tree.get_node("/root/disk/partition_1/DOCS/NOTE.TXT").unwrap();
+    let mut content = String::new();
+    note.value().get_value("data").unwrap().as_vfile_builder().open().unwrap().read_to_string(&mut content).unwrap();
+    assert!(content == "note");
+  }
+}
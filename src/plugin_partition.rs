@@ -0,0 +1,409 @@
+//! The `partition plugin` is a reference plugin parsing MBR and GPT partition tables.
+//! Each partition is exposed as a child node of the parsed file with a `data` attribute,
+//! created with a [SliceVFileBuilder] pointing to the partition content inside the parent file.
+
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+use crate::config_schema;
+use crate::detection::Signature;
+use crate::plugin::{PluginInfo, PluginInstance, PluginConfig, PluginArgument, PluginResult, PluginEnvironment, Dependency};
+use crate::reflect::EnumVariant;
+use crate::node::Node;
+use crate::tree::AttributePath;
+use crate::value::Value;
+use crate::vfile::VFile;
+use crate::slicevfile::SliceVFileBuilder;
+use crate::error::RustructError;
+
+use serde::{Serialize, Deserialize};
+use schemars::{JsonSchema};
+use byteorder::{ByteOrder, LittleEndian};
+use log::warn;
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::{plugin, register_plugin};
+
+plugin!("partition", "Volume", "Parse MBR and GPT partition tables", Partition, Arguments, depends : [Dependency::Data("data")],
+        signatures : [Signature::Magic{ offset : 510, bytes : &[0x55, 0xaa] }, Signature::Magic{ offset : 512, bytes : b"EFI PART" }],
+        metadata : { tags : vec!["disk", "volume"], outputs : vec!["partition"] });
+register_plugin!(Plugin);
+
+/// Size of a sector, partition tables address data in sectors.
+pub const SECTOR_SIZE : u64 = 512;
+/// Maximum number of logical partitions read from an extended partition, protect against loops in the EBR chain.
+const MAX_LOGICAL_PARTITIONS : usize = 128;
+/// Maximum number of GPT entries read.
+const MAX_GPT_ENTRIES : u32 = 1024;
+
+/// The partition plugin
+#[derive(Default)]
+pub struct Partition
+{
+}
+
+/// The argument struct that will be passed to the run method of the plugin.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Arguments
+{
+  /// Attribute containing the [VFileBuilder] of the disk to parse.
+  file : AttributePath,
+}
+
+/// The results class that will be returned from the plugin.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Results
+{
+  /// Partitioning scheme found : `MBR` or `GPT`.
+  scheme : String,
+  /// Number of partition nodes created.
+  partitions : u32,
+}
+
+/// A partition found in a partition table.
+#[derive(Debug)]
+struct PartitionEntry
+{
+  offset : u64,
+  size : u64,
+  attributes : Vec<(&'static str, Value)>,
+}
+
+/// Return the name of a MBR partition type.
+fn mbr_type_name(code : u8) -> &'static str
+{
+  match code
+  {
+    0x01 => "FAT12",
+    0x04 | 0x06 | 0x0e => "FAT16",
+    0x05 | 0x0f | 0x85 => "Extended",
+    0x07 => "NTFS",
+    0x0b | 0x0c => "FAT32",
+    0x82 => "LinuxSwap",
+    0x83 => "Linux",
+    0x8e => "LinuxLVM",
+    0xee => "GPTProtective",
+    0xef => "EFISystem",
+    _ => "Unknown",
+  }
+}
+
+/// Read `size` bytes at `offset` in `file`.
+fn read_at(file : &mut Box<dyn VFile>, offset : u64, size : usize) -> Result<Vec<u8>>
+{
+  let mut buffer = vec![0; size];
+  file.seek(SeekFrom::Start(offset))?;
+  file.read_exact(&mut buffer)?;
+  Ok(buffer)
+}
+
+/// Parse the 16 bytes MBR partition `entry`, which start is relative to sector `base`.
+fn mbr_entry(entry : &[u8], base : u64) -> PartitionEntry
+{
+  let code = entry[4];
+  let offset = (base + LittleEndian::read_u32(&entry[8..12]) as u64) * SECTOR_SIZE;
+  let size = LittleEndian::read_u32(&entry[12..16]) as u64 * SECTOR_SIZE;
+
+  PartitionEntry{ offset, size, attributes : vec![
+    ("type", Value::Enum(Arc::new(EnumVariant::new("MbrPartitionType", mbr_type_name(code), code as i64)))),
+    ("bootable", Value::Bool(entry[0] == 0x80)),
+  ]}
+}
+
+/// Parse a MBR partition table, or the GPT if the MBR is protective, of a disk of `disk_size` bytes.
+fn parse_mbr(file : &mut Box<dyn VFile>, disk_size : u64) -> Result<(&'static str, Vec<PartitionEntry>)>
+{
+  let sector = read_at(file, 0, SECTOR_SIZE as usize)?;
+  if sector[510..512] != [0x55, 0xaa]
+  {
+    return Err(RustructError::Parse("MBR", "invalid signature".into()).into())
+  }
+
+  let mut partitions = Vec::new();
+  for index in 0..4
+  {
+    let entry = &sector[446 + index * 16..446 + (index + 1) * 16];
+    match entry[4]
+    {
+      0x00 => continue,
+      0xee => return Ok(("GPT", parse_gpt(file, disk_size)?)),
+      0x05 | 0x0f | 0x85 =>
+      {
+        let start = LittleEndian::read_u32(&entry[8..12]) as u64;
+        partitions.extend(parse_ebr(file, start)?);
+      },
+      _ => partitions.push(mbr_entry(entry, 0)),
+    }
+  }
+  Ok(("MBR", partitions))
+}
+
+/// Parse the chain of EBR of an extended partition starting at sector `start` and return the logical partitions.
+fn parse_ebr(file : &mut Box<dyn VFile>, start : u64) -> Result<Vec<PartitionEntry>>
+{
+  let mut partitions = Vec::new();
+  let mut next = 0;
+
+  while partitions.len() < MAX_LOGICAL_PARTITIONS
+  {
+    let ebr = start + next;
+    let sector = read_at(file, ebr * SECTOR_SIZE, SECTOR_SIZE as usize)?;
+    if sector[510..512] != [0x55, 0xaa]
+    {
+      warn!("partition : invalid EBR signature at sector {}", ebr);
+      break;
+    }
+
+    if sector[446 + 4] != 0
+    {
+      partitions.push(mbr_entry(&sector[446..462], ebr));
+    }
+
+    next = LittleEndian::read_u32(&sector[462 + 8..462 + 12]) as u64;
+    if sector[462 + 4] == 0 || next == 0
+    {
+      break;
+    }
+  }
+  Ok(partitions)
+}
+
+/// Parse the GPT header located at sector 1 and its partition entries, the table must fit in the `disk_size` bytes of the disk.
+fn parse_gpt(file : &mut Box<dyn VFile>, disk_size : u64) -> Result<Vec<PartitionEntry>>
+{
+  let header = read_at(file, SECTOR_SIZE, 92)?;
+  if &header[0..8] != b"EFI PART"
+  {
+    return Err(RustructError::Parse("GPT", "invalid header signature".into()).into())
+  }
+
+  let entries_lba = LittleEndian::read_u64(&header[72..80]);
+  let count = LittleEndian::read_u32(&header[80..84]).min(MAX_GPT_ENTRIES);
+  let entry_size = LittleEndian::read_u32(&header[84..88]) as usize;
+  if entry_size < 128 || entry_size > SECTOR_SIZE as usize
+  {
+    return Err(RustructError::Parse("GPT", format!("invalid entry size {}", entry_size)).into())
+  }
+
+  let table_size = count as u64 * entry_size as u64;
+  let table_offset = match entries_lba.checked_mul(SECTOR_SIZE)
+  {
+    Some(offset) if offset.checked_add(table_size).is_some_and(|end| end <= disk_size) => offset,
+    _ => return Err(RustructError::Parse("GPT", format!("partition entries at sector {} are outside of the disk", entries_lba)).into()),
+  };
+
+  let entries = read_at(file, table_offset, table_size as usize)?;
+  let mut partitions = Vec::new();
+  for entry in entries.chunks(entry_size)
+  {
+    let type_guid = Uuid::from_bytes_le(entry[0..16].try_into()?);
+    if type_guid.is_nil()
+    {
+      continue;
+    }
+
+    let first_lba = LittleEndian::read_u64(&entry[32..40]);
+    let last_lba = LittleEndian::read_u64(&entry[40..48]);
+    if last_lba < first_lba
+    {
+      warn!("partition : invalid GPT entry range {}-{}", first_lba, last_lba);
+      continue;
+    }
+
+    let sectors = (last_lba - first_lba).checked_add(1);
+    let (Some(offset), Some(size)) = (first_lba.checked_mul(SECTOR_SIZE), sectors.and_then(|sectors| sectors.checked_mul(SECTOR_SIZE))) else
+    {
+      warn!("partition : GPT entry range {}-{} overflows", first_lba, last_lba);
+      continue;
+    };
+
+    let name : Vec<u16> = entry[56..128].chunks(2).map(LittleEndian::read_u16).take_while(|c| *c != 0).collect();
+    partitions.push(PartitionEntry{ offset, size, attributes : vec![
+      ("type_guid", Value::Uuid(type_guid)),
+      ("guid", Value::Uuid(Uuid::from_bytes_le(entry[16..32].try_into()?))),
+      ("name", Value::String(String::from_utf16_lossy(&name))),
+      ("flags", Value::U64(LittleEndian::read_u64(&entry[48..56]))),
+    ]});
+  }
+  Ok(partitions)
+}
+
+impl Partition
+{
+  fn run(&mut self, argument : Arguments, env : PluginEnvironment) -> Result<Results>
+  {
+    let builder = argument.file.get_value(&env.tree).ok_or(RustructError::ValueNotFound("file"))?
+                          .try_as_vfile_builder().ok_or(RustructError::ValueTypeMismatch)?;
+
+    let mut file = builder.open()?;
+    let (scheme, partitions) = parse_mbr(&mut file, builder.size())?;
+
+    let mut count = 0;
+    for partition in partitions
+    {
+      if partition.size == 0 || partition.offset.checked_add(partition.size).is_none_or(|end| end > builder.size())
+      {
+        warn!("partition : partition at offset {} of size {} is outside of the disk", partition.offset, partition.size);
+        continue;
+      }
+
+      count += 1;
+      let node = Node::new(format!("partition_{}", count));
+      node.value().add_attribute("data", Value::VFileBuilder(SliceVFileBuilder::new(builder.clone(), partition.offset, partition.size)?), None);
+      node.value().add_attribute("offset", Value::U64(partition.offset), None);
+      node.value().add_attribute("size", Value::U64(partition.size), None);
+      for (name, value) in partition.attributes
+      {
+        node.value().add_attribute(name, value, None);
+      }
+      env.tree.add_child(argument.file.node_id, node)?;
+    }
+
+    Ok(Results{ scheme : scheme.into(), partitions : count })
+  }
+}
+
+#[cfg(test)]
+pub(crate) mod tests
+{
+  use crate::plugin::{PluginInfo, PluginEnvironment};
+  use crate::plugin_partition::{Plugin, SECTOR_SIZE};
+  use crate::memoryvfile::MemoryVFileBuilder;
+  use crate::node::Node;
+  use crate::tree::{Tree, TreeNodeId, AttributePath};
+  use crate::value::Value;
+
+  use byteorder::{ByteOrder, LittleEndian};
+  use serde_json::json;
+  use std::io::Read;
+
+  /// Write a MBR partition entry of type `code` in `disk`.
+  pub(crate) fn mbr_entry(disk : &mut [u8], index : usize, code : u8, lba : u32, count : u32)
+  {
+    let entry = &mut disk[446 + index * 16..446 + (index + 1) * 16];
+    entry[4] = code;
+    LittleEndian::write_u32(&mut entry[8..12], lba);
+    LittleEndian::write_u32(&mut entry[12..16], count);
+    disk[510] = 0x55;
+    disk[511] = 0xaa;
+  }
+
+  /// Add a node `name` containing `disk` to `tree` and run the partition plugin on it.
+  pub(crate) fn run_partition(tree : &Tree, name : &'static str, disk : Vec<u8>) -> serde_json::Value
+  {
+    let node = Node::new(name);
+    node.value().add_attribute("data", Value::VFileBuilder(MemoryVFileBuilder::from_buffer(disk)), None);
+    let node_id : TreeNodeId = tree.add_child(tree.root_id, node).unwrap();
+
+    let args = json!({"file" : AttributePath{ node_id, attribute_name : "data".into() }}).to_string();
+    let result = Plugin::new().instantiate().run(args, PluginEnvironment::new(tree.clone(), None)).unwrap();
+    serde_json::from_str(&result).unwrap()
+  }
+
+  fn read_data(tree : &Tree, path : &str) -> Vec<u8>
+  {
+    let mut data = Vec::new();
+    tree.get_node(path).unwrap().value().get_value("data").unwrap().as_vfile_builder().open().unwrap().read_to_end(&mut data).unwrap();
+    data
+  }
+
+  #[test]
+  fn partition_mbr()
+  {
+    let mut disk = vec![0; 64 * SECTOR_SIZE as usize];
+    mbr_entry(&mut disk, 0, 0x83, 1, 4);
+    mbr_entry(&mut disk, 1, 0x05, 8, 16);
+    disk[512..517].copy_from_slice(b"linux");
+    //logical partition at sector 8 + 2, with no next EBR
+    mbr_entry(&mut disk[8 * 512..], 0, 0x01, 2, 4);
+    disk[10 * 512..10 * 512 + 3].copy_from_slice(b"fat");
+
+    let tree = Tree::new();
+    let result = run_partition(&tree, "disk", disk);
+    assert!(result["scheme"] == "MBR" && result["partitions"] == 2);
+
+    let partition = tree.get_node("/root/disk/partition_1").unwrap();
+    assert!(partition.value().get_value("size").unwrap().as_u64() == 4 * SECTOR_SIZE);
+    assert!(partition.value().get_value("type").unwrap().to_string() == "Linux");
+    assert!(&read_data(&tree, "/root/disk/partition_1")[0..5] == b"linux");
+
+    let logical = tree.get_node("/root/disk/partition_2").unwrap();
+    assert!(logical.value().get_value("offset").unwrap().as_u64() == 10 * SECTOR_SIZE);
+    assert!(logical.value().get_value("type").unwrap().as_enum().discriminant() == 0x01);
+    assert!(&read_data(&tree, "/root/disk/partition_2")[0..3] == b"fat");
+  }
+
+  #[test]
+  fn partition_gpt()
+  {
+    let mut disk = vec![0; 64 * SECTOR_SIZE as usize];
+    mbr_entry(&mut disk, 0, 0xee, 1, 63);
+
+    let header = &mut disk[512..1024];
+    header[0..8].copy_from_slice(b"EFI PART");
+    LittleEndian::write_u64(&mut header[72..80], 2);
+    LittleEndian::write_u32(&mut header[80..84], 4);
+    LittleEndian::write_u32(&mut header[84..88], 128);
+
+    let entry = &mut disk[1024..1024 + 128];
+    entry[0] = 0xaf;
+    LittleEndian::write_u64(&mut entry[32..40], 34);
+    LittleEndian::write_u64(&mut entry[40..48], 41);
+    for (index, c) in "data".encode_utf16().enumerate()
+    {
+      LittleEndian::write_u16(&mut entry[56 + index * 2..58 + index * 2], c);
+    }
+
+    let tree = Tree::new();
+    let result = run_partition(&tree, "disk", disk);
+    assert!(result["scheme"] == "GPT" && result["partitions"] == 1);
+
+    let partition = tree.get_node("/root/disk/partition_1").unwrap();
+    assert!(partition.value().get_value("name").unwrap().as_string() == "data");
+    assert!(partition.value().get_value("size").unwrap().as_u64() == 8 * SECTOR_SIZE);
+  }
+
+  /// Write a GPT header in `disk` with `count` entries of `entry_size` bytes at sector `entries_lba`.
+  fn gpt_header(disk : &mut [u8], entries_lba : u64, count : u32, entry_size : u32)
+  {
+    mbr_entry(disk, 0, 0xee, 1, 63);
+    let header = &mut disk[512..1024];
+    header[0..8].copy_from_slice(b"EFI PART");
+    LittleEndian::write_u64(&mut header[72..80], entries_lba);
+    LittleEndian::write_u32(&mut header[80..84], count);
+    LittleEndian::write_u32(&mut header[84..88], entry_size);
+  }
+
+  #[test]
+  fn partition_gpt_invalid()
+  {
+    for (entries_lba, count, entry_size) in [(2, 4, 64), (2, 4, 1 << 20), (2, 1024, 512), (u64::MAX / 256, 4, 128)]
+    {
+      let mut disk = vec![0; 64 * SECTOR_SIZE as usize];
+      gpt_header(&mut disk, entries_lba, count, entry_size);
+
+      let node = Node::new("disk");
+      node.value().add_attribute("data", Value::VFileBuilder(MemoryVFileBuilder::from_buffer(disk)), None);
+      let tree = Tree::new();
+      let node_id = tree.add_child(tree.root_id, node).unwrap();
+      let args = json!({"file" : AttributePath{ node_id, attribute_name : "data".into() }}).to_string();
+      assert!(Plugin::new().instantiate().run(args, PluginEnvironment::new(tree.clone(), None)).is_err());
+    }
+
+    //entries which range overflows are skipped
+    let mut disk = vec![0; 64 * SECTOR_SIZE as usize];
+    gpt_header(&mut disk, 2, 2, 128);
+    for (index, (first_lba, last_lba)) in [(u64::MAX / 256, u64::MAX / 256 + 1), (0, u64::MAX)].into_iter().enumerate()
+    {
+      let entry = &mut disk[1024 + index * 128..1024 + (index + 1) * 128];
+      entry[0] = 0xaf;
+      LittleEndian::write_u64(&mut entry[32..40], first_lba);
+      LittleEndian::write_u64(&mut entry[40..48], last_lba);
+    }
+
+    let tree = Tree::new();
+    let result = run_partition(&tree, "disk", disk);
+    assert!(result["scheme"] == "GPT" && result["partitions"] == 0);
+  }
+}
@@ -0,0 +1,240 @@
+//! The `report plugin` create a case overview node summarizing the nodes of the tree :
+//! counts per kind of node, most frequent file extensions, total size of the data, timestamps range and tags.
+//! The overview is stored as attributes so frontends and exports can include it without walking the tree again.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::config_schema;
+use crate::plugin::{PluginInfo, PluginInstance, PluginConfig, PluginArgument, PluginResult, PluginEnvironment};
+use crate::node::Node;
+use crate::tree::{Tree, TreeNodeId};
+use crate::attribute::Attributes;
+use crate::value::Value;
+use crate::value::visit::{PathSegment, VisitControl};
+use crate::tag::tags;
+use crate::error::RustructError;
+
+use serde::{Serialize, Deserialize};
+use schemars::{JsonSchema};
+use chrono::{DateTime, Utc};
+use anyhow::Result;
+
+use crate::{plugin, register_plugin};
+
+plugin!("report", "Report", "Create a summary node of the case", Report, Arguments,
+        metadata : { tags : vec!["report"], outputs : vec!["report"] });
+register_plugin!(Plugin);
+
+/// Name of the node created by the plugin.
+pub const REPORT_NODE : &str = "report";
+/// Number of extensions reported by default.
+const TOP_EXTENSIONS : usize = 10;
+
+/// The report plugin
+#[derive(Default)]
+pub struct Report
+{
+}
+
+/// The argument struct that will be passed to the run method of the plugin.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Arguments
+{
+  /// Path of the node to summarize, the report node is created under it. Default to `/root`.
+  #[serde(default)]
+  path : Option<String>,
+  /// Number of most frequent extensions reported.
+  #[serde(default)]
+  top_extensions : Option<usize>,
+}
+
+/// The results class that will be returned from the plugin.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Results
+{
+  /// Number of nodes summarized.
+  nodes : u64,
+  /// Number of file nodes summarized.
+  files : u64,
+}
+
+/// Summary of the nodes under a node.
+#[derive(Debug, Default)]
+struct CaseSummary
+{
+  nodes : u64,
+  files : u64,
+  directories : u64,
+  others : u64,
+  total_size : u64,
+  extensions : HashMap<String, u64>,
+  first_timestamp : Option<DateTime<Utc>>,
+  last_timestamp : Option<DateTime<Utc>>,
+  tags : BTreeMap<String, u64>,
+}
+
+impl CaseSummary
+{
+  /// Summarize the descendants of `root_id`.
+  fn new(tree : &Tree, root_id : TreeNodeId) -> Self
+  {
+    let mut summary = CaseSummary::default();
+    for (node_id, _) in tree.walk(root_id).skip(1)
+    {
+      let node = match tree.get_node_from_id(node_id)
+      {
+        Some(node) => node,
+        None => continue,
+      };
+      summary.nodes += 1;
+
+      let mut size = None;
+      for attribute in node.value().attributes().iter()
+      {
+        if let Value::VFileBuilder(builder) = attribute.value()
+        {
+          *size.get_or_insert(0) += builder.size();
+        }
+        attribute.value().walk(&mut |_path : &[PathSegment], value : &Value|
+        {
+          if let Value::DateTime(timestamp) = value
+          {
+            summary.first_timestamp = Some(summary.first_timestamp.map_or(*timestamp, |first| first.min(*timestamp)));
+            summary.last_timestamp = Some(summary.last_timestamp.map_or(*timestamp, |last| last.max(*timestamp)));
+          }
+          VisitControl::Continue
+        });
+      }
+
+      match size
+      {
+        Some(size) =>
+        {
+          summary.files += 1;
+          summary.total_size += size;
+          if let Some((_, extension)) = node.name().rsplit_once('.')
+          {
+            *summary.extensions.entry(extension.to_lowercase()).or_insert(0) += 1;
+          }
+        },
+        None if tree.has_children(node_id) => summary.directories += 1,
+        None => summary.others += 1,
+      }
+
+      for tag in tags(tree, node_id)
+      {
+        *summary.tags.entry(tag).or_insert(0) += 1;
+      }
+    }
+    summary
+  }
+
+  /// Return the `count` most frequent extensions, by decreasing frequency then name.
+  fn top_extensions(&self, count : usize) -> Vec<(String, u64)>
+  {
+    let mut extensions : Vec<(String, u64)> = self.extensions.iter().map(|(extension, count)| (extension.clone(), *count)).collect();
+    extensions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    extensions.truncate(count);
+    extensions
+  }
+
+  /// Return a new node named [REPORT_NODE] containing the summary as attributes.
+  fn to_node(&self, top_extensions : usize) -> Node
+  {
+    let node = Node::new(REPORT_NODE);
+    let extensions = self.top_extensions(top_extensions).into_iter().map(|(extension, count)|
+    {
+      let mut attributes = Attributes::new();
+      attributes.add_attribute("extension", Value::from(extension), None);
+      attributes.add_attribute("count", Value::U64(count), None);
+      Value::Attributes(attributes)
+    }).collect();
+
+    node.value().add_attributes(vec![
+      ("nodes", Value::U64(self.nodes), Some("Number of nodes")),
+      ("files", Value::U64(self.files), Some("Number of nodes with data")),
+      ("directories", Value::U64(self.directories), Some("Number of nodes without data having children")),
+      ("others", Value::U64(self.others), Some("Number of nodes without data and children")),
+      ("total_size", Value::U64(self.total_size), Some("Total size of the files data")),
+      ("extensions", Value::Seq(extensions), Some("Most frequent file extensions")),
+      ("tags", Value::Map(self.tags.iter().map(|(tag, count)| (tag.clone(), Value::U64(*count))).collect()), Some("Number of nodes per tag")),
+    ]);
+    if let (Some(first), Some(last)) = (self.first_timestamp, self.last_timestamp)
+    {
+      node.value().add_attribute("first_timestamp", Value::DateTime(first), Some("Oldest timestamp"));
+      node.value().add_attribute("last_timestamp", Value::DateTime(last), Some("Newest timestamp"));
+    }
+    node
+  }
+}
+
+impl Report
+{
+  fn run(&mut self, argument : Arguments, env : PluginEnvironment) -> Result<Results>
+  {
+    let path = argument.path.unwrap_or_else(|| "/root".into());
+    let root_id = env.tree.get_node_id(&path).ok_or_else(|| RustructError::NodeNotFound(path.clone()))?;
+
+    //replace the previous report so it's not summarized
+    if let Some(report_id) = env.tree.find_node_from_id(root_id, REPORT_NODE)
+    {
+      env.tree.remove(report_id)?;
+    }
+
+    let summary = CaseSummary::new(&env.tree, root_id);
+    env.tree.add_child(root_id, summary.to_node(argument.top_extensions.unwrap_or(TOP_EXTENSIONS)))?;
+    Ok(Results{ nodes : summary.nodes, files : summary.files })
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use crate::plugin::{PluginInfo, PluginEnvironment};
+  use crate::plugin_report::Plugin;
+  use crate::memoryvfile::MemoryVFileBuilder;
+  use crate::tag::{Tagger, Query};
+  use crate::validation::Check;
+  use crate::node::Node;
+  use crate::tree::Tree;
+  use crate::value::Value;
+
+  use chrono::{TimeZone, Utc};
+  use serde_json::json;
+
+  #[test]
+  fn report_case()
+  {
+    let tree = Tree::new();
+    let dir_id = tree.add_child(tree.root_id, Node::new("dir")).unwrap();
+    for (name, size, year) in [("a.TXT", 10, 2001), ("b.txt", 20, 2005), ("c.exe", 30, 2003)]
+    {
+      let node = Node::new(name);
+      node.value().add_attribute("data", Value::VFileBuilder(MemoryVFileBuilder::from_buffer(vec![0; size])), None);
+      node.value().add_attribute("modified", Value::DateTime(Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap()), None);
+      tree.add_child(dir_id, node).unwrap();
+    }
+    tree.add_child(tree.root_id, Node::new("empty")).unwrap();
+    let tagger = Tagger::new();
+    tagger.save_query("exe", Query::new().with("data", Check::Exists).under("/root/dir/c"));
+    tagger.tag_by_query(&tree, "exe", "executable").unwrap();
+
+    for _ in 0..2
+    {
+      let args = json!({"top_extensions" : 1}).to_string();
+      let result = Plugin::new().instantiate().run(args, PluginEnvironment::new(tree.clone(), None)).unwrap();
+      let result : serde_json::Value = serde_json::from_str(&result).unwrap();
+      assert!(result["nodes"] == 5 && result["files"] == 3);
+    }
+
+    let report = Value::Attributes(tree.get_node("/root/report").unwrap().value());
+    assert!(report.get_path("directories").unwrap().as_u64() == 1 && report.get_path("others").unwrap().as_u64() == 1);
+    assert!(report.get_path("total_size").unwrap().as_u64() == 60);
+    assert!(report.get_path("extensions").unwrap().as_vec().len() == 1);
+    assert!(report.get_path("extensions/0/extension").unwrap().as_string() == "txt");
+    assert!(report.get_path("extensions/0/count").unwrap().as_u64() == 2);
+    assert!(report.get_path("tags/executable").unwrap().as_u64() == 1);
+    assert!(report.get_path("first_timestamp").unwrap() == Value::DateTime(Utc.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).unwrap()));
+    assert!(report.get_path("last_timestamp").unwrap() == Value::DateTime(Utc.with_ymd_and_hms(2005, 1, 1, 0, 0, 0).unwrap()));
+  }
+}
@@ -1,14 +1,49 @@
 //! [PluginsDB] is the database containing all the registred plugins 
 //! it provides you with helper function to manipulate plugins. 
 
-use crate::plugin::{PluginInfo, PluginInstance, PluginConfig};
+use std::collections::{HashMap, BTreeMap};
+
+use crate::plugin::{PluginInfo, PluginInstance, PluginConfig, PluginArgument, Dependency, check_compatibility};
 use crate::error::RustructError;
+use crate::node::Node;
+use crate::value::ValueTypeId;
+use crate::version::{Version, VersionReq};
 use anyhow::Result;
+use log::warn;
+use serde_json::{Map, Value};
+
+/// Arguments fields overriding the defaults of a plugin.
+pub type Overrides = Map<String, Value>;
 
 #[derive(Default)]
 pub struct PluginsDB
 {
   plugins_info : Vec<Box<dyn PluginInfo + Sync + Send> >,
+  /// Default arguments fields of each plugin.
+  defaults : HashMap<String, Overrides>,
+  /// Named sets of arguments fields of each plugin.
+  presets : HashMap<String, BTreeMap<String, Overrides>>,
+  /// Registered versions of each plugin other than the one used, which is in `plugins_info`.
+  versions : HashMap<String, Vec<Box<dyn PluginInfo + Sync + Send> >>,
+  /// Requirement on the version used of each pinned plugin.
+  pins : HashMap<String, VersionReq>,
+}
+
+/// A plugin registered with several versions, returned by [PluginsDB::conflicts].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionConflict
+{
+  pub plugin : &'static str,
+  /// Version used.
+  pub active : &'static str,
+  /// All the registered versions, newest first.
+  pub versions : Vec<&'static str>,
+}
+
+/// Return the parsed version of `plugin_info`, registered plugins always have a valid version.
+fn version(plugin_info : &(dyn PluginInfo + Sync + Send)) -> Version
+{
+  Version::parse(plugin_info.version()).unwrap_or_default()
 }
 
 /// A database containing all the registred plugins
@@ -21,6 +56,37 @@ impl PluginsDB
     Default::default()
   }
 
+  /// Return a new [PluginsDB] containing all the plugins registered at compile time with [register_plugin!](crate::register_plugin).
+  #[cfg(feature = "auto_register")]
+  pub fn with_registered() -> PluginsDB
+  {
+    let mut plugins_db = PluginsDB::new();
+    plugins_db.autodiscover();
+    plugins_db
+  }
+
+  /// Add the plugins registered at compile time with [register_plugin!](crate::register_plugin) that are not yet in the DB,
+  /// including the ones of the plugin crates linked to the binary, and return their names.
+  #[cfg(feature = "auto_register")]
+  pub fn autodiscover(&mut self) -> Vec<&'static str>
+  {
+    let mut added = Vec::new();
+    for plugin_info in crate::plugin::registered_plugins()
+    {
+      let name = plugin_info.name();
+      if self.find(name).is_some()
+      {
+        continue
+      }
+      match self.register(plugin_info)
+      {
+        true => added.push(name),
+        false => warn!("Registered plugin {} was not added", name),
+      }
+    }
+    added
+  }
+
   /// Return the number of Plugins in the DB.
   pub fn len(&self) -> usize
   {
@@ -49,13 +115,103 @@ impl PluginsDB
   }
 
   /// Return the configuration that you should pass to a Plugin run method.
+  /// The [defaults](PluginsDB::set_defaults) of the plugin are set as the default of the properties of its schema.
   pub fn config(&self, name : &str) -> Result<PluginConfig>
   {
-    match self.plugins_info.iter().find(|x| {x.name() == name})
+    self.config_with_preset(name, None)
+  }
+
+  /// Return the [configuration](PluginsDB::config) of the plugin with the fields of `preset` as defaults.
+  pub fn config_with_preset(&self, name : &str, preset : Option<&str>) -> Result<PluginConfig>
+  {
+    let config = match self.plugins_info.iter().find(|x| {x.name() == name})
+    {
+      Some(plugin_info) => plugin_info.config()?,
+      None =>  return Err(RustructError::PluginNotFound{ name : name.to_string() }.into()),
+    };
+    let overrides = self.overrides(name, preset)?;
+    if overrides.is_empty()
+    {
+      return Ok(config)
+    }
+
+    let mut schema : Value = serde_json::from_str(&config)?;
+    if let Some(properties) = schema.get_mut("properties").and_then(Value::as_object_mut)
+    {
+      for (field, value) in overrides
+      {
+        if let Some(Value::Object(property)) = properties.get_mut(&field)
+        {
+          property.insert("default".into(), value);
+        }
+      }
+    }
+    Ok(serde_json::to_string(&schema)?)
+  }
+
+  /// Set the `defaults` fields of the arguments of plugin `name`, used for the fields missing from the arguments
+  /// [resolved](PluginsDB::argument) for the plugin.
+  pub fn set_defaults(&mut self, name : &str, defaults : Overrides) -> Result<()>
+  {
+    let name = self.find(name).ok_or_else(|| RustructError::PluginNotFound{ name : name.to_string() })?.name();
+    self.defaults.insert(name.to_string(), defaults);
+    Ok(())
+  }
+
+  /// Return the default fields of the arguments of plugin `name`.
+  pub fn defaults(&self, name : &str) -> Option<&Overrides>
+  {
+    self.defaults.get(name)
+  }
+
+  /// Add the `preset` named set of fields of the arguments of plugin `name`, replacing the preset with the same name.
+  pub fn add_preset(&mut self, name : &str, preset : &str, fields : Overrides) -> Result<()>
+  {
+    let name = self.find(name).ok_or_else(|| RustructError::PluginNotFound{ name : name.to_string() })?.name();
+    self.presets.entry(name.to_string()).or_default().insert(preset.to_string(), fields);
+    Ok(())
+  }
+
+  /// Remove `preset` of plugin `name`, return false if there was no such preset.
+  pub fn remove_preset(&mut self, name : &str, preset : &str) -> bool
+  {
+    self.presets.get_mut(name).is_some_and(|presets| presets.remove(preset).is_some())
+  }
+
+  /// Return the name of the presets of plugin `name`.
+  pub fn presets(&self, name : &str) -> Vec<String>
+  {
+    self.presets.get(name).map(|presets| presets.keys().cloned().collect()).unwrap_or_default()
+  }
+
+  /// Return the defaults of plugin `name` overridden by the fields of `preset`.
+  fn overrides(&self, name : &str, preset : Option<&str>) -> Result<Overrides>
+  {
+    let mut overrides = self.defaults.get(name).cloned().unwrap_or_default();
+    if let Some(preset) = preset
     {
-      Some(plugin_info) => Ok(plugin_info.config()?),
-      None =>  Err(RustructError::PluginNotFound{ name : name.to_string() }.into()),
+      let fields = self.presets.get(name).and_then(|presets| presets.get(preset))
+                       .ok_or_else(|| RustructError::PresetNotFound(name.to_string(), preset.to_string()))?;
+      overrides.extend(fields.clone());
     }
+    Ok(overrides)
+  }
+
+  /// Return the `argument` of plugin `name` completed with the fields of `preset` and the defaults of the plugin,
+  /// the fields of `argument` take precedence over the preset that take precedence over the defaults.
+  /// `argument` is returned unchanged if there is no field to add.
+  pub fn argument(&self, name : &str, preset : Option<&str>, argument : PluginArgument) -> Result<PluginArgument>
+  {
+    let overrides = self.overrides(name, preset)?;
+    if overrides.is_empty()
+    {
+      return Ok(argument)
+    }
+
+    let fields : Overrides = serde_json::from_str(&argument).map_err(|err| RustructError::InvalidArgument(name.to_string(), err.to_string()))?;
+    let mut merged = overrides;
+    merged.extend(fields);
+    Ok(Value::Object(merged).to_string())
   }
 
   /// Instantiate a new Plugin. 
@@ -65,14 +221,170 @@ impl PluginsDB
   }
 
   /// Register a new Plugin.
+  /// Plugins not [compatible](check_compatibility) with the core are refused.
   pub fn register(&mut self, plugin_info: Box< dyn PluginInfo + Sync + Send >) -> bool 
   {
+    match check_compatibility(plugin_info.as_ref())
+    {
+      Ok(warnings) => warnings.iter().for_each(|warning| warn!("{}", warning)),
+      Err(err) => { warn!("{}", err); return false },
+    }
+
+    let name = plugin_info.name();
+    let new_version = match Version::parse(plugin_info.version())
+    {
+      Ok(new_version) => new_version,
+      Err(err) => { warn!("Plugin {} not added : {}", name, err); return false },
+    };
+
     //try to find if a plugins with the same name is already registred 
-    match self.find(plugin_info.name())
-    { 
-      Some(_) => false,
-      None => { self.plugins_info.push(plugin_info); true }
+    if self.find(name).is_none()
+    {
+      self.plugins_info.push(plugin_info);
+      return true
+    }
+    if self.all_versions(name).any(|registered| version(registered) == new_version)
+    {
+      return false
+    }
+    self.versions.entry(name.to_string()).or_default().push(plugin_info);
+    self.select(name);
+    warn!("Plugin {} is registered with versions {}, version {} is used", name, self.versions(name).join(", "), self.find(name).map_or("", |info| info.version()));
+    true
+  }
+
+  /// Return all the registered versions of the plugin `name`.
+  fn all_versions<'a>(&'a self, name : &str) -> impl Iterator<Item = &'a (dyn PluginInfo + Sync + Send)> + 'a
+  {
+    self.find(name).into_iter().chain(self.versions.get(name).into_iter().flatten()).map(|plugin_info| plugin_info.as_ref())
+  }
+
+  /// Use the newest version of the plugin `name` matching its pin.
+  fn select(&mut self, name : &str)
+  {
+    let pin = self.pins.get(name).cloned().unwrap_or_else(VersionReq::any);
+    let (Some(position), Some(others)) = (self.plugins_info.iter().position(|info| info.name() == name), self.versions.get_mut(name)) else { return };
+    let active = &mut self.plugins_info[position];
+    let rank = |plugin_info : &(dyn PluginInfo + Sync + Send)| { let version = version(plugin_info); (pin.matches(&version), version) };
+    if let Some(best) = others.iter_mut().max_by_key(|plugin_info| rank(plugin_info.as_ref()))
+    {
+      if rank(best.as_ref()) > rank(active.as_ref())
+      {
+        std::mem::swap(active, best);
+      }
+    }
+  }
+
+  /// Return the registered versions of the plugin `name`, newest first.
+  pub fn versions(&self, name : &str) -> Vec<&'static str>
+  {
+    let mut versions : Vec<(Version, &'static str)> = self.all_versions(name).map(|plugin_info| (version(plugin_info), plugin_info.version())).collect();
+    versions.sort_by_key(|(version, _)| std::cmp::Reverse(*version));
+    versions.into_iter().map(|(_, version)| version).collect()
+  }
+
+  /// Return the newest registered version of the plugin `name` matching `requirement`.
+  /// Return an error if `requirement` is invalid.
+  pub fn find_version(&self, name : &str, requirement : &str) -> Result<Option<&(dyn PluginInfo + Sync + Send)>>
+  {
+    let requirement = VersionReq::parse(requirement)?;
+    Ok(self.all_versions(name).filter(|plugin_info| requirement.matches(&version(*plugin_info))).max_by_key(|plugin_info| version(*plugin_info)))
+  }
+
+  /// Use the newest version of the plugin `name` matching `requirement`, even when a newer version is registered later, and return it.
+  /// Return an error and keep the current version if no version match.
+  pub fn pin(&mut self, name : &str, requirement : &str) -> Result<&'static str>
+  {
+    if self.find_version(name, requirement)?.is_none()
+    {
+      return Err(RustructError::VersionNotFound(name.into(), requirement.into()).into())
+    }
+    self.pins.insert(name.to_string(), VersionReq::parse(requirement)?);
+    self.select(name);
+    Ok(self.find(name).map_or("", |plugin_info| plugin_info.version()))
+  }
+
+  /// Remove the pin of the plugin `name` and use its newest version, return false if it wasn't pinned.
+  pub fn unpin(&mut self, name : &str) -> bool
+  {
+    let pinned = self.pins.remove(name).is_some();
+    self.select(name);
+    pinned
+  }
+
+  /// Return the plugins registered with several versions.
+  pub fn conflicts(&self) -> Vec<VersionConflict>
+  {
+    self.plugins_info.iter().filter(|plugin_info| self.versions.get(plugin_info.name()).is_some_and(|others| !others.is_empty()))
+      .map(|plugin_info| VersionConflict{ plugin : plugin_info.name(), active : plugin_info.version(), versions : self.versions(plugin_info.name()) }).collect()
+  }
+
+  /// Return the plugins `names` and the plugins they [depend](Dependency::Plugin) on, each plugin after its dependencies.
+  /// Return an error if a plugin is not found or if the dependencies form a cycle.
+  pub fn resolve(&self, names : &[&str]) -> Result<Vec<&'static str>>
+  {
+    let mut order = Vec::new();
+    for name in names
+    {
+      self.visit(name, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+  }
+
+  /// Add plugin `name` to `order` after its dependencies, `path` is the chain of plugins depending on it.
+  fn visit(&self, name : &str, path : &mut Vec<&'static str>, order : &mut Vec<&'static str>) -> Result<()>
+  {
+    let plugin_info = self.find(name).ok_or_else(|| RustructError::PluginNotFound{ name : name.to_string() })?;
+    let name = plugin_info.name();
+    if order.contains(&name)
+    {
+      return Ok(())
+    }
+    if path.contains(&name)
+    {
+      let cycle : Vec<&str> = path.iter().skip_while(|plugin| **plugin != name).copied().chain(std::iter::once(name)).collect();
+      return Err(RustructError::DependencyCycle(cycle.join(" -> ")).into())
+    }
+
+    path.push(name);
+    for dependency in plugin_info.dependencies()
+    {
+      if let Dependency::Plugin(dependency) = dependency
+      {
+        self.visit(dependency, path, order)?;
+      }
     }
+    path.pop();
+    order.push(name);
+    Ok(())
+  }
+
+  /// Return the plugins that declare [data dependencies](Dependency::Data) all satisfied by `node`.
+  pub fn applicable(&self, node : &Node) -> Vec<&'static str>
+  {
+    self.plugins_info.iter().filter(|plugin_info|
+    {
+      let dependencies = plugin_info.dependencies();
+      dependencies.iter().any(|dependency| matches!(dependency, Dependency::Data(_))) && dependencies.iter().all(|dependency| dependency.is_satisfied_by(node))
+    }).map(|plugin_info| plugin_info.name()).collect()
+  }
+
+  /// Return the plugins whose [metadata](crate::plugin::PluginMetadata) has `tag`.
+  pub fn find_by_tag(&self, tag : &str) -> Vec<&'static str>
+  {
+    self.plugins_info.iter().filter(|plugin_info| plugin_info.metadata().tags.contains(&tag)).map(|plugin_info| plugin_info.name()).collect()
+  }
+
+  /// Return the plugins taking an attribute of type `type_id` as input.
+  pub fn find_accepting(&self, type_id : ValueTypeId) -> Vec<&'static str>
+  {
+    self.plugins_info.iter().filter(|plugin_info| plugin_info.metadata().inputs.contains(&type_id)).map(|plugin_info| plugin_info.name()).collect()
+  }
+
+  /// Return the plugins of `category`.
+  pub fn find_by_category(&self, category : &str) -> Vec<&'static str>
+  {
+    self.plugins_info.iter().filter(|plugin_info| plugin_info.category() == category).map(|plugin_info| plugin_info.name()).collect()
   }
 
   /// Unregister a Plugin.
@@ -80,7 +392,13 @@ impl PluginsDB
   {
     match self.find(name)
     {
-      Some(_) => { self.plugins_info.retain(|info| info.name() != name); true}
+      Some(_) =>
+      {
+        self.plugins_info.retain(|info| info.name() != name);
+        self.versions.remove(name);
+        self.pins.remove(name);
+        true
+      },
       None => false
     }
   }
@@ -98,14 +416,14 @@ mod tests
     #[test]
     fn plugins_db_test_register()
     {
-        let mut plugins_db = PluginsDB{ plugins_info : Vec::new() };
+        let mut plugins_db = PluginsDB{ plugins_info : Vec::new(), ..Default::default() };
         assert!(plugins_db.register(Box::new(plugin_dummy::Plugin::new())));
     }
 
     #[test]
     fn plugins_db_test_register_twice()
     {
-        let mut plugins_db = PluginsDB{ plugins_info : Vec::new() };
+        let mut plugins_db = PluginsDB{ plugins_info : Vec::new(), ..Default::default() };
 
         assert!(plugins_db.register(Box::new(plugin_dummy::Plugin::new())));
         /*plugin already registred must return false */
@@ -177,4 +495,199 @@ mod tests
             assert_eq!(plugin_info.name(), instance.name())
         }
     }
+
+    #[test]
+    fn plugins_db_refuse_incompatible()
+    {
+        use crate::plugin::{PluginInfo, PluginInstance, PluginConfig, check_compatibility};
+
+        struct Incompatible
+        {
+          abi_version : u32,
+          core_version : &'static str,
+          requires : Vec<&'static str>,
+        }
+
+        impl PluginInfo for Incompatible
+        {
+          fn name(&self) -> &'static str { "incompatible" }
+          fn category(&self) -> &'static str { "test" }
+          fn instantiate(&self) -> Box<dyn PluginInstance + Send + Sync> { plugin_dummy::Plugin::new().instantiate() }
+          fn help(&self) -> &'static str { "" }
+          fn config(&self) -> anyhow::Result<PluginConfig> { Ok(String::new()) }
+          fn abi_version(&self) -> u32 { self.abi_version }
+          fn core_version(&self) -> &'static str { self.core_version }
+          fn requires_features(&self) -> Vec<&'static str> { self.requires.clone() }
+        }
+
+        let mut plugins_db = PluginsDB::new();
+        let plugin = Incompatible{ abi_version : 0, core_version : crate::plugin::CORE_VERSION, requires : vec![] };
+        assert!(!plugins_db.register(Box::new(plugin)));
+        let plugin = Incompatible{ abi_version : crate::plugin::ABI_VERSION, core_version : "1.0.0", requires : vec![] };
+        assert!(!plugins_db.register(Box::new(plugin)));
+        let plugin = Incompatible{ abi_version : crate::plugin::ABI_VERSION, core_version : crate::plugin::CORE_VERSION, requires : vec!["fuse"] };
+        assert!(!plugins_db.register(Box::new(plugin)));
+
+        let plugin = Incompatible{ abi_version : crate::plugin::ABI_VERSION, core_version : "0.1.99", requires : vec!["blob_store"] };
+        assert!(check_compatibility(&plugin).unwrap().len() == 1);
+        assert!(plugins_db.register(Box::new(plugin)));
+        assert!(plugins_db.len() == 1);
+    }
+
+    #[test]
+    #[cfg(feature = "auto_register")]
+    fn plugins_db_with_registered()
+    {
+        let plugins_db = PluginsDB::with_registered();
+        assert!(plugins_db.find("partition").is_some());
+        assert!(plugins_db.find("fat").is_some());
+        assert!(plugins_db.find("report").is_some());
+        assert!(plugins_db.find("dummy").is_none());
+
+        let mut plugins_db = PluginsDB::new();
+        plugins_db.register(Box::new(crate::plugin_fat::Plugin::new()));
+        let added = plugins_db.autodiscover();
+        assert!(added.contains(&"partition") && !added.contains(&"fat") && plugins_db.len() == added.len() + 1);
+        assert!(plugins_db.autodiscover().is_empty());
+    }
+
+    #[test]
+    fn plugins_db_resolve_dependencies()
+    {
+        use crate::plugin::{PluginInfo, PluginInstance, PluginConfig, Dependency};
+        use crate::node::Node;
+        use crate::value::Value;
+        use crate::memoryvfile::MemoryVFileBuilder;
+        use crate::error::RustructError;
+
+        struct Chained
+        {
+          name : &'static str,
+          dependencies : Vec<Dependency>,
+        }
+
+        impl PluginInfo for Chained
+        {
+          fn name(&self) -> &'static str { self.name }
+          fn category(&self) -> &'static str { "test" }
+          fn instantiate(&self) -> Box<dyn PluginInstance + Send + Sync> { plugin_dummy::Plugin::new().instantiate() }
+          fn help(&self) -> &'static str { "" }
+          fn config(&self) -> anyhow::Result<PluginConfig> { Ok(String::new()) }
+          fn dependencies(&self) -> Vec<Dependency> { self.dependencies.clone() }
+        }
+
+        let mut plugins_db = PluginsDB::new();
+        plugins_db.register(Box::new(Chained{ name : "disk", dependencies : vec![Dependency::Data("data")] }));
+        plugins_db.register(Box::new(Chained{ name : "volume", dependencies : vec![Dependency::Plugin("disk"), Dependency::Data("data")] }));
+        plugins_db.register(Box::new(Chained{ name : "files", dependencies : vec![Dependency::Plugin("volume"), Dependency::Plugin("disk")] }));
+        plugins_db.register(Box::new(Chained{ name : "loop_a", dependencies : vec![Dependency::Plugin("loop_b")] }));
+        plugins_db.register(Box::new(Chained{ name : "loop_b", dependencies : vec![Dependency::Plugin("loop_a")] }));
+
+        assert!(plugins_db.resolve(&["files"]).unwrap() == vec!["disk", "volume", "files"]);
+        assert!(plugins_db.resolve(&["volume", "disk", "files"]).unwrap() == vec!["disk", "volume", "files"]);
+        let err = plugins_db.resolve(&["loop_a"]).unwrap_err();
+        assert!(matches!(err.downcast_ref::<RustructError>(), Some(RustructError::DependencyCycle(cycle)) if cycle == "loop_a -> loop_b -> loop_a"));
+        assert!(plugins_db.resolve(&["missing"]).is_err());
+
+        let node = Node::new("disk");
+        assert!(plugins_db.applicable(&node).is_empty());
+        node.value().add_attribute("data", Value::VFileBuilder(MemoryVFileBuilder::from_buffer(vec![0; 512])), None);
+        assert!(plugins_db.applicable(&node) == vec!["disk", "volume"]);
+    }
+
+    #[test]
+    fn plugins_db_presets()
+    {
+        use crate::error::RustructError;
+        use serde_json::{json, Value};
+
+        let mut plugins_db = PluginsDB::new();
+        plugins_db.register(Box::new(plugin_dummy::Plugin::new()));
+        let fields = |value : Value| value.as_object().unwrap().clone();
+        plugins_db.set_defaults("dummy", fields(json!({ "offset" : 512, "file_name" : "/default" }))).unwrap();
+        plugins_db.add_preset("dummy", "deep", fields(json!({ "offset" : 0 }))).unwrap();
+        assert!(plugins_db.set_defaults("missing", fields(json!({}))).is_err());
+        assert!(plugins_db.presets("dummy") == vec!["deep"]);
+
+        let argument = |preset, argument : Value| -> Value
+          { serde_json::from_str(&plugins_db.argument("dummy", preset, argument.to_string()).unwrap()).unwrap() };
+        assert!(argument(None, json!({ "parent" : null })) == json!({ "offset" : 512, "file_name" : "/default", "parent" : null }));
+        assert!(argument(Some("deep"), json!({ "parent" : null })) == json!({ "offset" : 0, "file_name" : "/default", "parent" : null }));
+        assert!(argument(Some("deep"), json!({ "file_name" : "/file", "offset" : 7 })) == json!({ "offset" : 7, "file_name" : "/file" }));
+        let err = plugins_db.argument("dummy", Some("fast"), "{}".into()).unwrap_err();
+        assert!(matches!(err.downcast_ref::<RustructError>(), Some(RustructError::PresetNotFound(_, preset)) if preset == "fast"));
+
+        let config : Value = serde_json::from_str(&plugins_db.config_with_preset("dummy", Some("deep")).unwrap()).unwrap();
+        assert!(config["properties"]["offset"]["default"] == 0 && config["properties"]["file_name"]["default"] == "/default");
+        assert!(plugins_db.remove_preset("dummy", "deep") && !plugins_db.remove_preset("dummy", "deep"));
+        //plugins without overrides keep their argument as is
+        assert!(plugins_db.argument("other", None, "not json".into()).unwrap() == "not json");
+    }
+
+    #[test]
+    fn plugins_db_metadata()
+    {
+        use crate::value::ValueTypeId;
+        use crate::{plugin_fat, plugin_partition, plugin_report};
+
+        let mut plugins_db = PluginsDB::new();
+        plugins_db.register(Box::new(plugin_dummy::Plugin::new()));
+        plugins_db.register(Box::new(plugin_partition::Plugin::new()));
+        plugins_db.register(Box::new(plugin_fat::Plugin::new()));
+        plugins_db.register(Box::new(plugin_report::Plugin::new()));
+
+        let metadata = plugins_db.find("fat").unwrap().metadata();
+        assert!(metadata.version == env!("CARGO_PKG_VERSION") && metadata.inputs == vec![ValueTypeId::VFileBuilder]);
+        assert!(metadata.outputs == vec!["file", "directory"]);
+        assert!(plugins_db.find("dummy").unwrap().metadata().tags.is_empty());
+        assert!(plugins_db.find_by_tag("filesystem") == vec!["fat"]);
+        assert!(plugins_db.find_by_tag("disk") == vec!["partition"]);
+        assert!(plugins_db.find_accepting(ValueTypeId::VFileBuilder) == vec!["partition", "fat"]);
+        assert!(plugins_db.find_accepting(ValueTypeId::String).is_empty());
+        assert!(plugins_db.find_by_category("Report") == vec!["report"]);
+    }
+
+    #[test]
+    fn plugins_db_versions()
+    {
+        use crate::plugin::{PluginInfo, PluginInstance, PluginConfig};
+        use crate::error::RustructError;
+
+        struct Versioned(&'static str);
+
+        impl PluginInfo for Versioned
+        {
+          fn name(&self) -> &'static str { "versioned" }
+          fn category(&self) -> &'static str { "test" }
+          fn instantiate(&self) -> Box<dyn PluginInstance + Send + Sync> { plugin_dummy::Plugin::new().instantiate() }
+          fn help(&self) -> &'static str { "" }
+          fn config(&self) -> anyhow::Result<PluginConfig> { Ok(String::new()) }
+          fn version(&self) -> &'static str { self.0 }
+        }
+
+        let mut plugins_db = PluginsDB::new();
+        assert!(plugins_db.register(Box::new(Versioned("1.2.0"))) && plugins_db.conflicts().is_empty());
+        assert!(plugins_db.register(Box::new(Versioned("1.10.0"))) && plugins_db.register(Box::new(Versioned("0.9.1"))));
+        assert!(!plugins_db.register(Box::new(Versioned("1.10.0"))) && !plugins_db.register(Box::new(Versioned("latest"))));
+        assert!(plugins_db.len() == 1 && plugins_db.find("versioned").unwrap().version() == "1.10.0");
+        assert!(plugins_db.versions("versioned") == vec!["1.10.0", "1.2.0", "0.9.1"]);
+        let conflicts = plugins_db.conflicts();
+        assert!(conflicts.len() == 1 && conflicts[0].active == "1.10.0" && conflicts[0].versions.len() == 3);
+
+        assert!(plugins_db.find_version("versioned", "<1.10").unwrap().unwrap().version() == "1.2.0");
+        assert!(plugins_db.find_version("versioned", "^2").unwrap().is_none() && plugins_db.find_version("versioned", "new").is_err());
+
+        //a pinned plugin keep its version when a newer one is registered
+        assert!(plugins_db.pin("versioned", "~1.2").unwrap() == "1.2.0");
+        assert!(plugins_db.register(Box::new(Versioned("1.11.0"))) && plugins_db.find("versioned").unwrap().version() == "1.2.0");
+        let err = plugins_db.pin("versioned", "3").unwrap_err();
+        assert!(matches!(err.downcast_ref::<RustructError>(), Some(RustructError::VersionNotFound(..))));
+        assert!(plugins_db.find("versioned").unwrap().version() == "1.2.0");
+        assert!(plugins_db.unpin("versioned") && plugins_db.find("versioned").unwrap().version() == "1.11.0");
+
+        assert!(plugins_db.unregister("versioned") && plugins_db.versions("versioned").is_empty() && plugins_db.conflicts().is_empty());
+        assert!(plugins_db.find("dummy").is_none() && plugins_db.versions("dummy").is_empty());
+        plugins_db.register(Box::new(plugin_dummy::Plugin::new()));
+        assert!(plugins_db.versions("dummy") == vec![env!("CARGO_PKG_VERSION")]);
+    }
 }
@@ -0,0 +1,348 @@
+//! Small previews of the data of the nodes (hex dump of the head, text snippet with its detected encoding, thumbnails),
+//! generated on demand by [Session::preview] and cached in the session [BlobStore](crate::stagingvfile::BlobStore),
+//! so GUI consumers don't have to reimplement them.
+//!
+//! Each [PreviewKind] is generated by a [PreviewGenerator] registered on the [Previewer].
+//! No thumbnail generator is provided, they can be registered by image decoding plugins.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::{Arc, Weak, RwLock};
+
+use crate::session::Session;
+use crate::tree::TreeNodeId;
+use crate::vfile::{VFileBuilder, preview};
+use crate::context::CaseContext;
+use crate::error::RustructError;
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+/// Kind of preview returned by [Session::preview].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PreviewKind
+{
+  /// Hex dump of the first bytes.
+  Hex,
+  /// Start of the text with its detected encoding.
+  Text,
+  /// Thumbnail of an image.
+  Thumbnail,
+}
+
+/// A preview of the data of a node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Preview
+{
+  /// Hex dump of the first bytes.
+  Hex(String),
+  /// Text decoded with `encoding`.
+  Text{ encoding : String, text : String },
+  /// Image encoded in `format`, like `png`.
+  Image{ format : String, data : Vec<u8> },
+}
+
+/**
+ * Generate a kind of [Preview] from the data of a node.
+ */
+pub trait PreviewGenerator : Sync + Send
+{
+  /// Return the preview of `data`, or `None` if this kind of preview doesn't apply to it.
+  fn generate(&self, data : &dyn VFileBuilder, context : &CaseContext) -> Result<Option<Preview>>;
+}
+
+/// Read the first `size` bytes of `data`.
+fn read_head(data : &dyn VFileBuilder, size : usize) -> Result<Vec<u8>>
+{
+  let mut head = Vec::with_capacity(size.min(data.size() as usize));
+  data.open()?.take(size as u64).read_to_end(&mut head)?;
+  Ok(head)
+}
+
+/// Generate a hex dump of the first `bytes` bytes.
+#[derive(Debug, Clone)]
+pub struct HexPreview
+{
+  pub bytes : usize,
+}
+
+impl Default for HexPreview
+{
+  fn default() -> Self
+  {
+    HexPreview{ bytes : 256 }
+  }
+}
+
+impl PreviewGenerator for HexPreview
+{
+  fn generate(&self, data : &dyn VFileBuilder, _context : &CaseContext) -> Result<Option<Preview>>
+  {
+    Ok(Some(Preview::Hex(preview(data, 0, self.bytes)?.to_string())))
+  }
+}
+
+/// Generate a text snippet from the first `bytes` bytes, if they look like text.
+#[derive(Debug, Clone)]
+pub struct TextPreview
+{
+  pub bytes : usize,
+}
+
+impl Default for TextPreview
+{
+  fn default() -> Self
+  {
+    TextPreview{ bytes : 4096 }
+  }
+}
+
+impl PreviewGenerator for TextPreview
+{
+  fn generate(&self, data : &dyn VFileBuilder, context : &CaseContext) -> Result<Option<Preview>>
+  {
+    let head = read_head(data, self.bytes)?;
+    Ok(detect_text(&head, (head.len() as u64) < data.size(), context).map(|(encoding, text)| Preview::Text{ encoding, text }))
+  }
+}
+
+fn decode_utf16(bytes : &[u8], little_endian : bool) -> String
+{
+  let units = bytes.chunks_exact(2).map(|unit| match little_endian
+  {
+    true => u16::from_le_bytes([unit[0], unit[1]]),
+    false => u16::from_be_bytes([unit[0], unit[1]]),
+  });
+  char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)).collect()
+}
+
+/// Detect the encoding of `bytes` and decode them, return `None` if they don't look like text.
+/// Byte order marks, UTF-8 and UTF-16 are detected, other text is decoded with the `context` codepage.
+/// If `truncated` is true, `bytes` is the start of a longer text and may end in the middle of a character.
+pub fn detect_text(bytes : &[u8], truncated : bool, context : &CaseContext) -> Option<(String, String)>
+{
+  let (encoding, text) = match bytes
+  {
+    [0xef, 0xbb, 0xbf, rest @ ..] => ("utf-8".to_string(), String::from_utf8_lossy(rest).into_owned()),
+    [0xff, 0xfe, rest @ ..] => ("utf-16le".to_string(), decode_utf16(rest, true)),
+    [0xfe, 0xff, rest @ ..] => ("utf-16be".to_string(), decode_utf16(rest, false)),
+    _ =>
+    {
+      let utf8 = match std::str::from_utf8(bytes)
+      {
+        Ok(text) => Some(text),
+        Err(err) if truncated && err.error_len().is_none() => std::str::from_utf8(&bytes[..err.valid_up_to()]).ok(),
+        Err(_) => None,
+      };
+      let zeros = |parity : usize| bytes.iter().skip(parity).step_by(2).filter(|byte| **byte == 0).count();
+      let pairs = bytes.len() / 2;
+      match (utf8.filter(|_| !bytes.contains(&0)), zeros(1), zeros(0))
+      {
+        (Some(text), _, _) => ("utf-8".to_string(), text.to_string()),
+        (_, odd, even) if pairs > 0 && odd * 10 >= pairs * 3 && even * 10 < pairs => ("utf-16le".to_string(), decode_utf16(bytes, true)),
+        (_, odd, even) if pairs > 0 && even * 10 >= pairs * 3 && odd * 10 < pairs => ("utf-16be".to_string(), decode_utf16(bytes, false)),
+        _ => (context.codepage.clone(), context.decode(bytes)),
+      }
+    },
+  };
+
+  let chars = text.chars().count();
+  let controls = text.chars().filter(|c| (c.is_control() && !c.is_whitespace()) || *c == char::REPLACEMENT_CHARACTER).count();
+  match controls * 10 <= chars
+  {
+    true => Some((encoding, text)),
+    false => None,
+  }
+}
+
+/// Cached preview of a node, `blob` is `None` if the preview doesn't apply to the node data.
+struct CachedPreview
+{
+  data : Weak<dyn VFileBuilder>,
+  blob : Option<Uuid>,
+}
+
+/**
+ * Generate the previews of the nodes with the registered [PreviewGenerator] and cache them.
+ * A cached preview is generated again if the data of the node changed.
+ */
+pub struct Previewer
+{
+  generators : RwLock<HashMap<PreviewKind, Arc<dyn PreviewGenerator>>>,
+  cache : RwLock<HashMap<(TreeNodeId, PreviewKind), CachedPreview>>,
+}
+
+impl Default for Previewer
+{
+  fn default() -> Self
+  {
+    let mut generators : HashMap<PreviewKind, Arc<dyn PreviewGenerator>> = HashMap::new();
+    generators.insert(PreviewKind::Hex, Arc::new(HexPreview::default()));
+    generators.insert(PreviewKind::Text, Arc::new(TextPreview::default()));
+    Previewer{ generators : RwLock::new(generators), cache : RwLock::new(HashMap::new()) }
+  }
+}
+
+impl Previewer
+{
+  /// Return a previewer generating hex and text previews.
+  pub fn new() -> Self
+  {
+    Previewer::default()
+  }
+
+  /// Generate the previews of `kind` with `generator`, the cached previews of that kind are discarded.
+  pub fn set_generator(&self, kind : PreviewKind, generator : Arc<dyn PreviewGenerator>)
+  {
+    self.generators.write().unwrap().insert(kind, generator);
+    self.cache.write().unwrap().retain(|(_, cached_kind), _| *cached_kind != kind);
+  }
+
+  /// Discard all the cached previews.
+  pub fn clear(&self)
+  {
+    self.cache.write().unwrap().clear();
+  }
+
+  /// Return the number of cached previews.
+  pub fn cached(&self) -> usize
+  {
+    self.cache.read().unwrap().len()
+  }
+}
+
+impl Session
+{
+  /// Return the [Previewer] generating the node previews.
+  pub fn previewer(&self) -> &Previewer
+  {
+    &self.previews
+  }
+
+  /// Return the preview of `kind` of the `data` of node `node_id`, or `None` if this kind of preview doesn't apply to the data.
+  /// Previews are cached in the session [BlobStore](crate::stagingvfile::BlobStore).
+  pub fn preview(&self, node_id : TreeNodeId, kind : PreviewKind) -> Result<Option<Preview>>
+  {
+    let node = self.tree.get_node_from_id(node_id).ok_or_else(|| RustructError::NodeNotFound(format!("{:?}", node_id)))?;
+    let data = match node.value().get_value("data")
+    {
+      Some(value) => value.try_as_vfile_builder().or_else(|| value.try_as_blob()).ok_or(RustructError::ValueTypeMismatch)?,
+      None => return Err(RustructError::ValueNotFound("data").into()),
+    };
+
+    let blob_store = self.blob_store();
+    let cached = self.previews.cache.read().unwrap().get(&(node_id, kind)).filter(|cached| cached.data.as_ptr() as *const () == Arc::as_ptr(&data) as *const ())
+                                                                     .map(|cached| cached.blob);
+    if let Some(blob) = cached
+    {
+      let blob = match blob
+      {
+        Some(blob) => blob,
+        None => return Ok(None),
+      };
+      if let Ok(builder) = blob_store.get(blob)
+      {
+        return Ok(Some(serde_json::from_reader(builder.open()?)?))
+      }
+    }
+
+    let generator = match self.previews.generators.read().unwrap().get(&kind)
+    {
+      Some(generator) => generator.clone(),
+      None => return Ok(None),
+    };
+    let preview = generator.generate(data.as_ref(), &self.context())?;
+
+    let blob = match &preview
+    {
+      Some(preview) =>
+      {
+        let mut writer = blob_store.writer()?;
+        writer.write_all(&serde_json::to_vec(preview)?)?;
+        Some(writer.into_builder()?.id())
+      },
+      None => None,
+    };
+    let cached = CachedPreview{ data : Arc::downgrade(&data), blob };
+    if let Some(old) = self.previews.cache.write().unwrap().insert((node_id, kind), cached)
+    {
+      if let Some(blob) = old.blob
+      {
+        blob_store.remove(blob);
+      }
+    }
+    Ok(preview)
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{PreviewKind, Preview, PreviewGenerator, detect_text};
+  use crate::session::Session;
+  use crate::node::Node;
+  use crate::value::Value;
+  use crate::vfile::VFileBuilder;
+  use crate::memoryvfile::MemoryVFileBuilder;
+  use crate::context::CaseContext;
+  use crate::stagingvfile::BlobStore;
+
+  use std::sync::Arc;
+
+  struct SizeThumbnail;
+
+  impl PreviewGenerator for SizeThumbnail
+  {
+    fn generate(&self, data : &dyn VFileBuilder, _context : &CaseContext) -> anyhow::Result<Option<Preview>>
+    {
+      Ok(Some(Preview::Image{ format : "raw".into(), data : data.size().to_le_bytes().to_vec() }))
+    }
+  }
+
+  #[test]
+  fn detect_encodings()
+  {
+    let context = CaseContext::default();
+    assert!(detect_text("héllo".as_bytes(), false, &context) == Some(("utf-8".into(), "héllo".into())));
+    assert!(detect_text(&"héllo".as_bytes()[..2], true, &context) == Some(("utf-8".into(), "h".into())));
+    assert!(detect_text(b"h\x00\xe9\x00l\x00l\x00o\x00", false, &context) == Some(("utf-16le".into(), "héllo".into())));
+    assert!(detect_text(b"\xfe\xff\x00h\x00i", false, &context) == Some(("utf-16be".into(), "hi".into())));
+    assert!(detect_text(b"caf\xe9", false, &context) == Some(("windows-1252".into(), "café".into())));
+    assert!(detect_text(&[0x7f, 0x45, 0x4c, 0x46, 0x02, 0x01, 0x01, 0x00, 0x00, 0x00, 0x03, 0x00], false, &context).is_none());
+  }
+
+  #[test]
+  fn session_previews()
+  {
+    let session = Session::new();
+    session.set_blob_store(BlobStore::new(std::env::temp_dir().join(format!("tap-preview-test-{}", uuid::Uuid::new_v4()))));
+    let node = Node::new("readme");
+    node.value().add_attribute("data", Value::VFileBuilder(MemoryVFileBuilder::from_buffer(b"read me\n".to_vec())), None);
+    let node_id = session.tree.add_child(session.tree.root_id, node).unwrap();
+    let empty_id = session.tree.add_child(session.tree.root_id, Node::new("empty")).unwrap();
+
+    assert!(session.preview(node_id, PreviewKind::Text).unwrap() == Some(Preview::Text{ encoding : "utf-8".into(), text : "read me\n".into() }));
+    assert!(session.preview(node_id, PreviewKind::Text).unwrap().is_some());
+    assert!(session.blob_store().ids().len() == 1);
+    match session.preview(node_id, PreviewKind::Hex).unwrap()
+    {
+      Some(Preview::Hex(dump)) => assert!(dump.contains("|read me.|")),
+      _ => panic!("expected hex preview"),
+    }
+    assert!(session.preview(node_id, PreviewKind::Thumbnail).unwrap().is_none());
+    assert!(session.preview(empty_id, PreviewKind::Hex).is_err());
+
+    session.previewer().set_generator(PreviewKind::Thumbnail, Arc::new(SizeThumbnail));
+    assert!(session.preview(node_id, PreviewKind::Thumbnail).unwrap() == Some(Preview::Image{ format : "raw".into(), data : 8u64.to_le_bytes().to_vec() }));
+
+    let node = session.tree.get_node_from_id(node_id).unwrap();
+    node.value().set_value("data", Value::VFileBuilder(MemoryVFileBuilder::from_buffer(vec![0; 4])));
+    assert!(session.preview(node_id, PreviewKind::Text).unwrap().is_none());
+    assert!(session.previewer().cached() == 3);
+
+    let dir = session.blob_store().dir().to_path_buf();
+    session.blob_store().ids().into_iter().for_each(|id| { session.blob_store().remove(id); });
+    std::fs::remove_dir(dir).unwrap();
+  }
+}
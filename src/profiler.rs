@@ -0,0 +1,141 @@
+//! Sampling profiler used to profile plugins when run by the [TaskScheduler](crate::task_scheduler::TaskScheduler) workers.
+//! Profiling is only available when the `profiler` feature is enabled, it produce a flamegraph for each profiled task.
+
+use std::sync::RwLock;
+use std::collections::HashMap;
+
+use crate::task_scheduler::TaskId;
+
+/// Sampling running for a task, it's dropped when the task is finished.
+#[cfg(feature = "profiler")]
+pub(crate) type Sampling = pprof::ProfilerGuard<'static>;
+#[cfg(not(feature = "profiler"))]
+pub(crate) enum Sampling {}
+
+/**
+ * Keep the profiling configuration and the flamegraph of each profiled task.
+ * Sampling is done for the whole process and only one sampling can run at a time,
+ * so tasks launched while an other task is profiled are not profiled.
+ * Samples of other workers are removed from the task flamegraph.
+ */
+#[derive(Default)]
+pub struct Profiler
+{
+  /// Sampling frequency in Hz, profiling is disabled if `None`.
+  frequency : RwLock<Option<i32>>,
+  /// Flamegraph in SVG format of each profiled task.
+  profiles : RwLock<HashMap<TaskId, Vec<u8>>>,
+}
+
+impl Profiler
+{
+  /// Return a new disabled [Profiler].
+  pub fn new() -> Self
+  {
+    Profiler::default()
+  }
+
+  /// Enable profiling of the next tasks with a sampling `frequency` in Hz, or disable it if `None`.
+  pub fn set_frequency(&self, frequency : Option<i32>)
+  {
+    *self.frequency.write().unwrap() = frequency;
+  }
+
+  /// Return the flamegraph in SVG format of task `task_id` if it was profiled.
+  pub fn profile(&self, task_id : TaskId) -> Option<Vec<u8>>
+  {
+    self.profiles.read().unwrap().get(&task_id).cloned()
+  }
+
+  /// Start sampling if profiling is enabled and no other task is sampled.
+  #[cfg(feature = "profiler")]
+  pub(crate) fn start(&self) -> Option<Sampling>
+  {
+    let frequency = (*self.frequency.read().unwrap())?;
+    pprof::ProfilerGuard::new(frequency).ok()
+  }
+
+  #[cfg(not(feature = "profiler"))]
+  pub(crate) fn start(&self) -> Option<Sampling>
+  {
+    None
+  }
+
+  /// Stop `sampling` and generate the flamegraph of task `task_id` from the samples of the current thread.
+  #[cfg(feature = "profiler")]
+  pub(crate) fn finish(&self, task_id : TaskId, sampling : Sampling)
+  {
+    let mut report = match sampling.report().build()
+    {
+      Ok(report) => report,
+      Err(err) => { log::info!("can't build profile of task {} : {}", task_id, err); return },
+    };
+    drop(sampling);
+
+    let thread_name = std::thread::current().name().unwrap_or_default().to_string();
+    report.data.retain(|frames, _| frames.thread_name == thread_name);
+
+    let mut flamegraph = Vec::new();
+    match report.flamegraph(&mut flamegraph)
+    {
+      Ok(()) => { self.profiles.write().unwrap().insert(task_id, flamegraph); },
+      Err(err) => log::info!("can't generate flamegraph of task {} : {}", task_id, err),
+    }
+  }
+
+  #[cfg(not(feature = "profiler"))]
+  pub(crate) fn finish(&self, _task_id : TaskId, sampling : Sampling)
+  {
+    match sampling {}
+  }
+}
+
+#[cfg(all(test, feature = "profiler"))]
+mod tests
+{
+  use std::time::{Duration, Instant};
+
+  use crate::task_scheduler::TaskScheduler;
+  use crate::plugin::{PluginInstance, PluginArgument, PluginResult, PluginEnvironment};
+  use crate::tree::Tree;
+
+  /// Plugin keeping the worker busy long enough to be sampled.
+  struct BusyPlugin;
+
+  impl PluginInstance for BusyPlugin
+  {
+    fn name(&self) -> &'static str
+    {
+      "busy"
+    }
+
+    fn run(&mut self, _argument : PluginArgument, _env : PluginEnvironment) -> anyhow::Result<PluginResult>
+    {
+      let start = Instant::now();
+      let mut hash = 0u64;
+      while start.elapsed() < Duration::from_millis(300)
+      {
+        hash = std::hint::black_box(hash.wrapping_mul(31).wrapping_add(7));
+      }
+      Ok(format!("{{\"hash\":{}}}", hash))
+    }
+  }
+
+  #[test]
+  fn profile_task()
+  {
+    let scheduler = TaskScheduler::new(Tree::new());
+    scheduler.set_profiling(Some(1000));
+    let id = scheduler.schedule(Box::new(BusyPlugin), "{}".into(), false).unwrap();
+    scheduler.join();
+
+    let profile = scheduler.profile(id).unwrap();
+    assert!(!profile.is_empty());
+    assert!(String::from_utf8_lossy(&profile).contains("<svg"));
+
+    scheduler.set_profiling(None);
+    let id = scheduler.schedule(Box::new(BusyPlugin), "{}".into(), true).unwrap();
+    scheduler.join();
+    assert!(scheduler.profile(id).is_none());
+  }
+}
@@ -0,0 +1,312 @@
+//! Soft resource quotas of the plugins : the [ResourceUsage] of a task, passed in its [PluginEnvironment](crate::plugin::PluginEnvironment),
+//! account the memory the plugin declare with [allocate](ResourceUsage::allocate) and the files it open with [open](ResourceUsage::open).
+//! Exceeding a [Quota] doesn't stop the plugin, a [QuotaEvent] is sent on the events of the
+//! [TaskScheduler](crate::task_scheduler::TaskScheduler::quota_events) and the plugin can [check](ResourceUsage::check) it to stop by itself.
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::vfile::{VFile, VFileBuilder};
+use crate::event::EventChannel;
+use crate::error::RustructError;
+
+use anyhow::Result;
+use log::warn;
+use serde::{Serialize, Deserialize};
+
+/// Limits of the resources used by a plugin instance, unlimited if `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quota
+{
+  /// Bytes of memory declared by the plugin.
+  pub memory : Option<u64>,
+  /// Files opened at the same time.
+  pub open_files : Option<u64>,
+}
+
+/// A resource accounted by [ResourceUsage].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Resource
+{
+  Memory,
+  OpenFiles,
+}
+
+/// Sent when a plugin instance exceed its [Quota], once for each resource.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaEvent
+{
+  pub plugin : String,
+  /// Id of the task running the plugin, if run by a scheduler.
+  pub task : Option<u32>,
+  pub resource : Resource,
+  pub used : u64,
+  pub limit : u64,
+}
+
+/// Current and peak usage of a resource.
+#[derive(Default)]
+struct Counter
+{
+  used : AtomicU64,
+  peak : AtomicU64,
+}
+
+impl Counter
+{
+  fn add(&self, amount : u64) -> u64
+  {
+    let used = self.used.fetch_add(amount, Ordering::SeqCst) + amount;
+    self.peak.fetch_max(used, Ordering::SeqCst);
+    used
+  }
+
+  fn sub(&self, amount : u64)
+  {
+    let _ = self.used.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| Some(used.saturating_sub(amount)));
+  }
+}
+
+struct Inner
+{
+  plugin : String,
+  task : Option<u32>,
+  quota : Quota,
+  memory : Counter,
+  open_files : Counter,
+  /// Resources whose quota was exceeded.
+  exceeded : Mutex<Vec<Resource>>,
+  events : Option<EventChannel<QuotaEvent>>,
+}
+
+/**
+ * Resources used by a plugin instance, clones share the same counters.
+ */
+#[derive(Clone)]
+pub struct ResourceUsage
+{
+  inner : Arc<Inner>,
+}
+
+impl Default for ResourceUsage
+{
+  fn default() -> Self
+  {
+    ResourceUsage::new(crate::meteredvfile::UNKNOWN_CONSUMER, None, Quota::default(), None)
+  }
+}
+
+impl ResourceUsage
+{
+  /// Return the usage of `plugin` run by task `task` limited by `quota`, the [QuotaEvent] are sent on `events`.
+  pub fn new<S : Into<String>>(plugin : S, task : Option<u32>, quota : Quota, events : Option<EventChannel<QuotaEvent>>) -> Self
+  {
+    let inner = Inner{ plugin : plugin.into(), task, quota, memory : Counter::default(), open_files : Counter::default(),
+                       exceeded : Mutex::new(Vec::new()), events };
+    ResourceUsage{ inner : Arc::new(inner) }
+  }
+
+  pub fn quota(&self) -> Quota
+  {
+    self.inner.quota
+  }
+
+  /// Declare `bytes` of memory used until the returned [Allocation] is dropped.
+  pub fn allocate(&self, bytes : u64) -> Allocation
+  {
+    self.add(Resource::Memory, bytes);
+    Allocation{ usage : self.clone(), bytes }
+  }
+
+  /// Return a vector of `len` zero bytes accounted until it's dropped.
+  pub fn buffer(&self, len : usize) -> (Vec<u8>, Allocation)
+  {
+    (vec![0; len], self.allocate(len as u64))
+  }
+
+  /// Open `builder` and account the file until it's dropped.
+  pub fn open(&self, builder : &dyn VFileBuilder) -> Result<Box<dyn VFile>>
+  {
+    let file = builder.open()?;
+    self.add(Resource::OpenFiles, 1);
+    Ok(Box::new(CountedVFile{ inner : file, usage : self.clone() }))
+  }
+
+  /// Return the current and the peak number of bytes of memory declared.
+  pub fn memory(&self) -> (u64, u64)
+  {
+    (self.inner.memory.used.load(Ordering::SeqCst), self.inner.memory.peak.load(Ordering::SeqCst))
+  }
+
+  /// Return the current and the peak number of open files.
+  pub fn open_files(&self) -> (u64, u64)
+  {
+    (self.inner.open_files.used.load(Ordering::SeqCst), self.inner.open_files.peak.load(Ordering::SeqCst))
+  }
+
+  /// Return the resources whose quota was exceeded.
+  pub fn exceeded(&self) -> Vec<Resource>
+  {
+    self.inner.exceeded.lock().unwrap().clone()
+  }
+
+  /// Return a [QuotaExceeded](RustructError::QuotaExceeded) error if a quota is currently exceeded, to stop a plugin with `?`.
+  pub fn check(&self) -> Result<()>
+  {
+    let quota = self.inner.quota;
+    let over = |limit : Option<u64>, (used, _) : (u64, u64)| limit.is_some_and(|limit| used > limit);
+    if over(quota.memory, self.memory())
+    {
+      return Err(RustructError::QuotaExceeded(self.inner.plugin.clone(), Resource::Memory).into())
+    }
+    if over(quota.open_files, self.open_files())
+    {
+      return Err(RustructError::QuotaExceeded(self.inner.plugin.clone(), Resource::OpenFiles).into())
+    }
+    Ok(())
+  }
+
+  fn counter(&self, resource : Resource) -> (&Counter, Option<u64>)
+  {
+    match resource
+    {
+      Resource::Memory => (&self.inner.memory, self.inner.quota.memory),
+      Resource::OpenFiles => (&self.inner.open_files, self.inner.quota.open_files),
+    }
+  }
+
+  fn add(&self, resource : Resource, amount : u64)
+  {
+    let (counter, limit) = self.counter(resource);
+    let used = counter.add(amount);
+    let limit = match limit
+    {
+      Some(limit) if used > limit => limit,
+      _ => return,
+    };
+
+    let mut exceeded = self.inner.exceeded.lock().unwrap();
+    if exceeded.contains(&resource)
+    {
+      return
+    }
+    exceeded.push(resource);
+    warn!("plugin {} exceed its {:?} quota : {} > {}", self.inner.plugin, resource, used, limit);
+    if let Some(events) = &self.inner.events
+    {
+      events.update(QuotaEvent{ plugin : self.inner.plugin.clone(), task : self.inner.task, resource, used, limit });
+    }
+  }
+
+  fn sub(&self, resource : Resource, amount : u64)
+  {
+    self.counter(resource).0.sub(amount);
+  }
+}
+
+/// Memory declared with [ResourceUsage::allocate], released when dropped.
+pub struct Allocation
+{
+  usage : ResourceUsage,
+  bytes : u64,
+}
+
+impl Allocation
+{
+  pub fn bytes(&self) -> u64
+  {
+    self.bytes
+  }
+
+  /// Change the size of the allocation to `bytes`, when the memory it account grow or shrink.
+  pub fn resize(&mut self, bytes : u64)
+  {
+    match bytes >= self.bytes
+    {
+      true => self.usage.add(Resource::Memory, bytes - self.bytes),
+      false => self.usage.sub(Resource::Memory, self.bytes - bytes),
+    }
+    self.bytes = bytes;
+  }
+}
+
+impl Drop for Allocation
+{
+  fn drop(&mut self)
+  {
+    self.usage.sub(Resource::Memory, self.bytes);
+  }
+}
+
+/// File opened with [ResourceUsage::open].
+struct CountedVFile
+{
+  inner : Box<dyn VFile>,
+  usage : ResourceUsage,
+}
+
+impl Read for CountedVFile
+{
+  fn read(&mut self, buf : &mut [u8]) -> io::Result<usize>
+  {
+    self.inner.read(buf)
+  }
+}
+
+impl Seek for CountedVFile
+{
+  fn seek(&mut self, pos : SeekFrom) -> io::Result<u64>
+  {
+    self.inner.seek(pos)
+  }
+}
+
+impl Drop for CountedVFile
+{
+  fn drop(&mut self)
+  {
+    self.usage.sub(Resource::OpenFiles, 1);
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{ResourceUsage, Quota, Resource};
+  use crate::event::EventChannel;
+  use crate::memoryvfile::MemoryVFileBuilder;
+
+  use std::io::Read;
+
+  #[test]
+  fn quota_usage()
+  {
+    let events = EventChannel::new();
+    let receiver = events.subscribe().receiver;
+    let usage = ResourceUsage::new("parser", Some(3), Quota{ memory : Some(1000), open_files : Some(1) }, Some(events));
+
+    let mut allocation = usage.allocate(600);
+    let (buffer, _buffer_allocation) = usage.buffer(300);
+    assert!(buffer.len() == 300 && usage.memory() == (900, 900) && usage.check().is_ok());
+    allocation.resize(800);
+    assert!(usage.memory() == (1100, 1100) && usage.check().is_err() && usage.exceeded() == vec![Resource::Memory]);
+    let event = receiver.try_recv().unwrap().event;
+    assert!(event.plugin == "parser" && event.task == Some(3) && event.resource == Resource::Memory && event.used == 1100 && event.limit == 1000);
+    drop(allocation);
+    assert!(usage.memory() == (300, 1100) && usage.check().is_ok());
+
+    let data = MemoryVFileBuilder::from_buffer(b"content".to_vec());
+    let mut first = usage.open(data.as_ref()).unwrap();
+    let mut content = String::new();
+    first.read_to_string(&mut content).unwrap();
+    assert!(content == "content" && usage.open_files() == (1, 1));
+    {
+      let _second = usage.clone().open(data.as_ref()).unwrap();
+      assert!(usage.open_files() == (2, 2) && usage.check().is_err());
+    }
+    assert!(usage.open_files() == (1, 2) && usage.exceeded().len() == 2);
+    //an exceeded resource is reported once
+    assert!(receiver.try_recv().unwrap().event.resource == Resource::OpenFiles && receiver.try_recv().is_err());
+  }
+}
@@ -0,0 +1,158 @@
+//! [ReferenceIndex] keep the location of the attributes containing [NodeId](Value::NodeId) or [AttributePath](Value::AttributePath) values,
+//! so references stored in the tree can be checked and updated when node ids change (subtree imports, compaction, merges).
+//! References that can't be updated are invalidated, they are replaced by [Value::Unit] and listed in a [ReferenceReport].
+
+use std::sync::RwLock;
+use std::collections::{HashMap, BTreeSet};
+
+use crate::tree::{TreeNodeId, AttributePath};
+use crate::attribute::Attributes;
+use crate::value::Value;
+use crate::value::visit::{PathSegment, VisitControl, path_to_string};
+
+use serde::{Serialize, Deserialize};
+
+/// A reference to a node stored in the attributes of another node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reference
+{
+  /// Id of the node holding the reference.
+  pub node_id : TreeNodeId,
+  /// Path of the reference in the node attributes, as passed to [Value::get_path].
+  pub attribute : String,
+  /// Id of the referenced node.
+  pub target : TreeNodeId,
+}
+
+/// A reference to a node that doesn't exist anymore.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrokenReference
+{
+  /// Id of the node holding the reference.
+  pub node_id : TreeNodeId,
+  /// Path of the node holding the reference.
+  pub path : String,
+  /// Name of the attribute containing the reference.
+  pub attribute : String,
+  /// Id of the missing node.
+  pub target : TreeNodeId,
+}
+
+/// Result of a references check or update.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReferenceReport
+{
+  /// Number of references checked.
+  pub checked : u64,
+  /// Number of references pointing to a new node id.
+  pub updated : u64,
+  /// References to missing nodes, they are replaced by [Value::Unit] when updating.
+  pub broken : Vec<BrokenReference>,
+}
+
+/**
+ * Index of the attributes containing references for each node of a [Tree](crate::tree::Tree).
+ * Nodes are indexed when added to the tree or [touched](crate::tree::Tree::touch),
+ * so references must be added before the node is added or the node must be touched after.
+ */
+#[derive(Default)]
+pub struct ReferenceIndex
+{
+  locations : RwLock<HashMap<TreeNodeId, BTreeSet<String>>>,
+}
+
+impl ReferenceIndex
+{
+  /// Return a new empty [ReferenceIndex].
+  pub fn new() -> Self
+  {
+    ReferenceIndex::default()
+  }
+
+  /// Index the `attributes` of node `node_id`, replacing its previous locations.
+  pub fn index(&self, node_id : TreeNodeId, attributes : &Attributes)
+  {
+    let names : BTreeSet<String> = attributes.attributes().iter()
+      .filter(|attribute| !targets(attribute.value()).is_empty())
+      .map(|attribute| attribute.name().to_string())
+      .collect();
+
+    let mut locations = self.locations.write().unwrap();
+    match names.is_empty()
+    {
+      true => { locations.remove(&node_id); },
+      false => { locations.insert(node_id, names); },
+    }
+  }
+
+  /// Remove the locations of node `node_id`.
+  pub fn forget(&self, node_id : TreeNodeId)
+  {
+    self.locations.write().unwrap().remove(&node_id);
+  }
+
+  /// Return the id of the nodes holding references.
+  pub fn holders(&self) -> Vec<TreeNodeId>
+  {
+    self.locations.read().unwrap().keys().cloned().collect()
+  }
+
+  /// Return the name of the attributes of node `node_id` containing references.
+  pub fn locations(&self, node_id : TreeNodeId) -> Vec<String>
+  {
+    self.locations.read().unwrap().get(&node_id).map(|names| names.iter().cloned().collect()).unwrap_or_default()
+  }
+}
+
+/// Return the path relative to `value` and the target of the references it contains.
+/// Fields of `ReflectStruct` are computed and can't be updated, so they are not traversed.
+pub fn targets(value : &Value) -> Vec<(String, TreeNodeId)>
+{
+  let mut targets = Vec::new();
+  value.walk(&mut |path : &[PathSegment], value : &Value|
+  {
+    match value
+    {
+      Value::NodeId(node_id) => targets.push((path_to_string(path), *node_id)),
+      Value::AttributePath(attribute_path) => targets.push((path_to_string(path), attribute_path.node_id)),
+      Value::ReflectStruct(_) => return VisitControl::SkipChildren,
+      _ => (),
+    }
+    VisitControl::Continue
+  });
+  targets
+}
+
+/// Return a copy of `value` where references are updated with `resolve`, which return the new id of a node or `None` if it's missing.
+/// Missing references are replaced by [Value::Unit] and their target is added to `broken`, return the number of updated references.
+pub fn rewrite<F>(value : &Value, resolve : &F, broken : &mut Vec<TreeNodeId>) -> (Value, u64)
+  where F : Fn(TreeNodeId) -> Option<TreeNodeId>
+{
+  let mut updated = 0;
+  let value = value.map(&mut |value : &Value|
+  {
+    let target = match value
+    {
+      Value::NodeId(node_id) => *node_id,
+      Value::AttributePath(attribute_path) => attribute_path.node_id,
+      Value::ReflectStruct(_) => return Some(value.clone()),
+      _ => return None,
+    };
+
+    match resolve(target)
+    {
+      None => { broken.push(target); Some(Value::Unit) },
+      Some(node_id) if node_id == target => None,
+      Some(node_id) =>
+      {
+        updated += 1;
+        match value
+        {
+          Value::AttributePath(attribute_path) => Some(Value::AttributePath(AttributePath{ node_id, attribute_name : attribute_path.attribute_name.clone() })),
+          _ => Some(Value::NodeId(node_id)),
+        }
+      },
+    }
+  });
+  (value, updated)
+}
@@ -1,36 +1,124 @@
 //! A reflection trait for Rust struct, that permit to access struct member as [Attribute].
 //! [ReflectStruct] can be used with tap_derive macro to automatically generate [Attribute] from Struct.
+//! [ReflectEnum] give access to the symbolic name, numeric value and payload of an enum variant.
 
+use std::any::Any;
 use std::fmt::Debug;
-use crate::value::Value;
-use crate::attribute::Attribute;
-use serde::{Serialize};
+use std::sync::Arc;
+use std::collections::HashMap;
+use crate::value::{Value, ValueTypeId};
+use crate::attribute::{Attribute, Attributes};
+use crate::error::RustructError;
+use serde::{Serialize, Deserialize};
 use serde::ser::{Serializer, SerializeStruct};
 
+pub mod registry;
+
+/// Kind of a [ReflectStruct] member.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FieldKind
+{
+  /// Value stored in the struct.
+  Field,
+  /// Value computed by a method when requested.
+  Method,
+}
+
+/// Informations about a [ReflectStruct] member, returned by [ReflectStruct::field_infos].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldInfo
+{
+  /// Name of the member.
+  pub name : &'static str,
+  /// Description of the member.
+  pub description : Option<&'static str>,
+  /// Stored field or computed method.
+  pub kind : FieldKind,
+  /// Type of the returned [Value], `None` if unknown.
+  pub value_type : Option<ValueTypeId>,
+}
+
+impl FieldInfo
+{
+  /// Return informations about field `name`.
+  pub fn field(name : &'static str, description : Option<&'static str>) -> Self
+  {
+    FieldInfo{ name, description, kind : FieldKind::Field, value_type : None }
+  }
+
+  /// Return informations about method `name`.
+  pub fn method(name : &'static str, description : Option<&'static str>) -> Self
+  {
+    FieldInfo{ name, description, kind : FieldKind::Method, value_type : None }
+  }
+
+  /// Set the type of the returned [Value].
+  pub fn with_type(mut self, value_type : ValueTypeId) -> Self
+  {
+    self.value_type = Some(value_type);
+    self
+  }
+}
+
+/// Old `(name, description)` form of the informations, converted as a field of unknown type.
+impl From<(&'static str, Option<&'static str>)> for FieldInfo
+{
+  fn from((name, description) : (&'static str, Option<&'static str>)) -> Self
+  {
+    FieldInfo::field(name, description)
+  }
+}
+
 /** 
  *  [ReflectStruct] is a trait used to wrapper a struct and give dynamic reflection information and access to the value of their a members. 
  **/
-pub trait ReflectStruct : Sync + Send + Debug
+pub trait ReflectStruct : AsAny + Sync + Send + Debug
 {
   /// Return the name of the [ReflectStruct].
   fn name(&self) -> &'static str;//We should add a TypeId describing the structure type
   
   /// Return a tuple containing the name and description of each field of the [ReflectStruct].
-  fn infos(&self) -> Vec<(&'static str, Option<&'static str>) >;
+  #[deprecated(note = "implement and use field_infos")]
+  fn infos(&self) -> Vec<(&'static str, Option<&'static str>) >
+  {
+    self.field_infos().into_iter().map(|info| (info.name, info.description)).collect()
+  }
+
+  /// Return the [FieldInfo] of each member of the [ReflectStruct].
+  fn field_infos(&self) -> Vec<FieldInfo>;
 
   /// Return field `name` [Value].
   fn get_value(&self, name : &str) -> Option<Value>;
 
+  /// Return the name of the fields that can be changed with [set_value](ReflectStruct::set_value).
+  fn writable(&self) -> Vec<&'static str>
+  {
+    Vec::new()
+  }
+
+  /// Return true if field `name` can be changed.
+  fn is_writable(&self, name : &str) -> bool
+  {
+    self.writable().contains(&name)
+  }
+
+  /// Set field `name` to `value`, fields depending on it will return an updated value.
+  /// Structs are shared so they must use interior mutability, fields are read only by default.
+  fn set_value(&self, name : &str, _value : Value) -> anyhow::Result<()>
+  {
+    Err(RustructError::ReadOnlyField(name.to_string()).into())
+  }
+
   /// Return name of all the member field of the struct.
   fn names(&self) -> Vec<&'static str> 
   {
-    self.infos().iter().map(|x| x.0).collect()
+    self.field_infos().iter().map(|x| x.name).collect()
   }
 
   /// Return description of all the member field of the struct.
   fn descriptions(&self) -> Vec<Option<&'static str>>
   {
-    self.infos().iter().map(|x| x.1).collect()
+    self.field_infos().iter().map(|x| x.description).collect()
   }
 
   /// Return a Vector of Attribute containing a tuple name, value, description of the all the field of the struct.
@@ -39,11 +127,11 @@ pub trait ReflectStruct : Sync + Send + Debug
   {
     let mut attributes = Vec::new();
    
-    for info in self.infos()
+    for info in self.field_infos()
     {
-      if let Some(value) = self.get_value(info.0)
+      if let Some(value) = self.get_value(info.name)
       {
-         attributes.push(Attribute::new(info.0, value, info.1));
+         attributes.push(Attribute::new(info.name, value, info.description));
       }
     }
     attributes
@@ -52,10 +140,107 @@ pub trait ReflectStruct : Sync + Send + Debug
   /// Return the number of field in the Struct.
   fn count(&self) -> usize
   {
-    self.infos().len()
+    self.field_infos().len()
+  }
+
+  /// Return the [ReflectStruct] contained in the fields, directly or in a sequence, with their path relative to this struct.
+  /// Path of structs in a sequence is the field name followed by their index (`headers/0`) as used by [Value::get_path].
+  fn children(&self) -> Vec<(String, Arc<dyn ReflectStruct + Sync + Send>)>
+  {
+    let mut children = Vec::new();
+    for info in self.field_infos()
+    {
+      if let Some(value) = self.get_value(info.name)
+      {
+        push_children(info.name.to_string(), &value, &mut children);
+      }
+    }
+    children
+  }
+
+  /// Return the fields as [Attributes], converting recursively the [ReflectStruct] contained in the fields
+  /// (directly, in a sequence, a map, an option or a newtype) to [Attributes].
+  /// Structs nested deeper than [MAX_DEPTH] are kept as [ReflectStruct].
+  fn to_attributes_deep(&self) -> Attributes
+  {
+    attributes_deep(self.attributes(), 1)
   }
 } 
 
+/// Give access to a [ReflectStruct] as [Any], it's implemented for all types so the concrete type can be recovered.
+pub trait AsAny
+{
+  /// Return self as [Any].
+  fn as_any(&self) -> &dyn Any;
+}
+
+impl<T : Any> AsAny for T
+{
+  fn as_any(&self) -> &dyn Any
+  {
+    self
+  }
+}
+
+impl dyn ReflectStruct
+{
+  /// Return the concrete type behind this [ReflectStruct] if it's a `T`.
+  pub fn downcast_ref<T : Any>(&self) -> Option<&T>
+  {
+    self.as_any().downcast_ref::<T>()
+  }
+}
+
+impl dyn ReflectStruct + Sync + Send
+{
+  /// Return the concrete type behind this [ReflectStruct] if it's a `T`.
+  pub fn downcast_ref<T : Any>(&self) -> Option<&T>
+  {
+    self.as_any().downcast_ref::<T>()
+  }
+}
+
+/// Maximum depth of [ReflectStruct] converted by [ReflectStruct::to_attributes_deep], structs can reference themselves.
+pub const MAX_DEPTH : usize = 32;
+
+/// Push the [ReflectStruct] of `value` or of its elements to `children`.
+fn push_children(path : String, value : &Value, children : &mut Vec<(String, Arc<dyn ReflectStruct + Sync + Send>)>)
+{
+  match value
+  {
+    Value::ReflectStruct(reflect) => children.push((path, reflect.clone())),
+    Value::Seq(values) => values.iter().enumerate().for_each(|(index, value)| push_children(format!("{}/{}", path, index), value, children)),
+    Value::Option(Some(value)) | Value::Newtype(value) => push_children(path, value, children),
+    _ => (),
+  }
+}
+
+/// Return `attributes` as [Attributes] with their [ReflectStruct] converted recursively.
+fn attributes_deep(attributes : Vec<Attribute>, depth : usize) -> Attributes
+{
+  let mut result = Attributes::new();
+  for attribute in attributes
+  {
+    let value = value_deep(attribute.value(), depth);
+    result.add_attribute(attribute.name().to_string(), value, attribute.description().map(|description| description.to_string()));
+  }
+  result
+}
+
+/// Return `value` with its [ReflectStruct] converted recursively to [Attributes].
+fn value_deep(value : &Value, depth : usize) -> Value
+{
+  match value
+  {
+    Value::ReflectStruct(reflect) if depth < MAX_DEPTH => Value::Attributes(attributes_deep(reflect.attributes(), depth + 1)),
+    Value::Seq(values) => Value::Seq(values.iter().map(|value| value_deep(value, depth)).collect()),
+    Value::Map(values) => Value::Map(values.iter().map(|(key, value)| (key.clone(), value_deep(value, depth))).collect()),
+    Value::Option(Some(value)) => Value::Option(Some(Box::new(value_deep(value, depth)))),
+    Value::Newtype(value) => Value::Newtype(Box::new(value_deep(value, depth))),
+    value => value.clone(),
+  }
+}
+
 impl Serialize for dyn ReflectStruct + Sync + Send
 {
   fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -63,13 +248,297 @@ impl Serialize for dyn ReflectStruct + Sync + Send
   {
       let mut state = serializer.serialize_struct(self.name(), self.count())?;
 
-      for info in self.infos()
+      for info in self.field_infos()
       {
-        if let Some(value) = self.get_value(info.0)
+        if let Some(value) = self.get_value(info.name)
         {
-          state.serialize_field(info.0, &value)?;
+          state.serialize_field(info.name, &value)?;
         }
       }
       state.end()
   }
 }
+
+/**
+ *  [ReflectEnum] is a trait used to wrap an enum and give dynamic access to its current variant,
+ *  so parsers can expose flags or record types with their symbolic name rather than a raw integer.
+ **/
+pub trait ReflectEnum : Sync + Send + Debug
+{
+  /// Return the name of the enum type.
+  fn name(&self) -> &str;
+
+  /// Return the name of the current variant.
+  fn variant(&self) -> &str;
+
+  /// Return the numeric value of the current variant.
+  fn discriminant(&self) -> i64;
+
+  /// Return the [Value] carried by the current variant, if any.
+  fn payload(&self) -> Option<Value>
+  {
+    None
+  }
+
+  /// Return the name and numeric value of all the variants of the enum, if known.
+  fn variants(&self) -> Vec<(&'static str, i64)>
+  {
+    Vec::new()
+  }
+}
+
+impl Serialize for dyn ReflectEnum + Sync + Send
+{
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+      where S: Serializer,
+  {
+      let payload = self.payload();
+      let mut state = serializer.serialize_struct("ReflectEnum", if payload.is_some() { 3 } else { 2 })?;
+
+      state.serialize_field("variant", self.variant())?;
+      state.serialize_field("value", &self.discriminant())?;
+      if let Some(payload) = payload
+      {
+        state.serialize_field("payload", &payload)?;
+      }
+      state.end()
+  }
+}
+
+/**
+ * A generic [ReflectEnum] holding an enum variant, used for enums that don't have a Rust type
+ * (like values read from a file format description) or when a [ReflectEnum] is deserialized.
+ */
+#[derive(Debug, Clone)]
+pub struct EnumVariant
+{
+  name : String,
+  variant : String,
+  discriminant : i64,
+  payload : Option<Value>,
+}
+
+impl EnumVariant
+{
+  /// Create a new variant `variant` of enum `name` with numeric value `discriminant`.
+  pub fn new<N : Into<String>, V : Into<String>>(name : N, variant : V, discriminant : i64) -> Self
+  {
+    EnumVariant{ name : name.into(), variant : variant.into(), discriminant, payload : None }
+  }
+
+  /// Set the [Value] carried by the variant.
+  pub fn with_payload(mut self, payload : Value) -> Self
+  {
+    self.payload = Some(payload);
+    self
+  }
+}
+
+impl ReflectEnum for EnumVariant
+{
+  fn name(&self) -> &str
+  {
+    &self.name
+  }
+
+  fn variant(&self) -> &str
+  {
+    &self.variant
+  }
+
+  fn discriminant(&self) -> i64
+  {
+    self.discriminant
+  }
+
+  fn payload(&self) -> Option<Value>
+  {
+    self.payload.clone()
+  }
+}
+
+/**
+ * A generic [ReflectStruct] holding the values of a deserialized struct.
+ * When a struct is serialized, only its values are kept, [ReflectBag] rehydrate them using the field informations
+ * of the struct type found in the [registry], so the struct can still be accessed as a [ReflectStruct].
+ */
+#[derive(Debug)]
+pub struct ReflectBag
+{
+  name : &'static str,
+  infos : Vec<FieldInfo>,
+  values : HashMap<&'static str, Value>,
+}
+
+impl ReflectBag
+{
+  /// Create a new [ReflectBag] for struct type `name` from its field `values`.
+  /// Return `None` if `name` is not registered, values of unknown fields are ignored.
+  pub fn new(name : &str, values : Vec<(String, Value)>) -> Option<Self>
+  {
+    let (name, infos) = registry::infos(name)?;
+    let mut bag = ReflectBag{ name, infos, values : HashMap::new() };
+
+    for (field, value) in values
+    {
+      if let Some(info) = bag.infos.iter().find(|info| info.name == field)
+      {
+        bag.values.insert(info.name, value);
+      }
+    }
+    Some(bag)
+  }
+}
+
+impl ReflectStruct for ReflectBag
+{
+  fn name(&self) -> &'static str
+  {
+    self.name
+  }
+
+  fn field_infos(&self) -> Vec<FieldInfo>
+  {
+    self.infos.clone()
+  }
+
+  fn get_value(&self, name : &str) -> Option<Value>
+  {
+    self.values.get(name).cloned()
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{ReflectStruct, FieldInfo};
+  use crate::value::Value;
+
+  use std::sync::Arc;
+
+  #[derive(Debug)]
+  struct Header
+  {
+    kind : u32,
+  }
+
+  impl ReflectStruct for Header
+  {
+    fn name(&self) -> &'static str
+    {
+      "Header"
+    }
+
+    fn field_infos(&self) -> Vec<FieldInfo>
+    {
+      vec![FieldInfo::field("kind", None)]
+    }
+
+    fn get_value(&self, name : &str) -> Option<Value>
+    {
+      match name
+      {
+        "kind" => Some(Value::U32(self.kind)),
+        _ => None,
+      }
+    }
+  }
+
+  #[derive(Debug)]
+  struct Record
+  {
+    headers : Vec<Arc<Header>>,
+  }
+
+  impl ReflectStruct for Record
+  {
+    fn name(&self) -> &'static str
+    {
+      "Record"
+    }
+
+    fn field_infos(&self) -> Vec<FieldInfo>
+    {
+      vec![FieldInfo::field("first", None), FieldInfo::field("headers", Some("attribute headers"))]
+    }
+
+    fn get_value(&self, name : &str) -> Option<Value>
+    {
+      match name
+      {
+        "first" => Some(Value::ReflectStruct(self.headers[0].clone())),
+        "headers" => Some(Value::Seq(self.headers.iter().map(|header| Value::ReflectStruct(header.clone())).collect())),
+        _ => None,
+      }
+    }
+  }
+
+  #[test]
+  fn nested_reflect_struct()
+  {
+    let record = Record{ headers : vec![Arc::new(Header{ kind : 0x10 }), Arc::new(Header{ kind : 0x30 })] };
+
+    let children = record.children();
+    let paths : Vec<&str> = children.iter().map(|(path, _)| path.as_str()).collect();
+    assert!(paths == vec!["first", "headers/0", "headers/1"]);
+    assert!(children[2].1.get_value("kind").unwrap().as_u32() == 0x30);
+
+    let attributes = record.to_attributes_deep();
+    assert!(attributes.get_attribute("headers").unwrap().description() == Some("attribute headers"));
+    let value = Value::Attributes(attributes);
+    assert!(matches!(value.get_path("headers/1").unwrap(), Value::Attributes(_)));
+    assert!(value.get_path("headers/1/kind").unwrap().as_u32() == 0x30);
+    assert!(value.get_path("first/kind").unwrap().as_u32() == 0x10);
+  }
+
+  #[test]
+  fn field_infos()
+  {
+    use super::FieldKind;
+    use crate::value::ValueTypeId;
+
+    #[derive(Debug)]
+    struct Checksum
+    {
+      data : Vec<u8>,
+    }
+
+    impl ReflectStruct for Checksum
+    {
+      fn name(&self) -> &'static str
+      {
+        "Checksum"
+      }
+
+      fn field_infos(&self) -> Vec<FieldInfo>
+      {
+        vec![FieldInfo::field("data", None).with_type(ValueTypeId::Bytes),
+             FieldInfo::method("sum", Some("sum of the bytes")).with_type(ValueTypeId::U64)]
+      }
+
+      fn get_value(&self, name : &str) -> Option<Value>
+      {
+        match name
+        {
+          "data" => Some(Value::Bytes(self.data.clone())),
+          "sum" => Some(Value::U64(self.data.iter().map(|byte| *byte as u64).sum())),
+          _ => None,
+        }
+      }
+    }
+
+    let checksum = Checksum{ data : vec![1, 2, 3] };
+    let infos = checksum.field_infos();
+    assert!(infos[1].kind == FieldKind::Method && infos[1].value_type == Some(ValueTypeId::U64));
+    assert!(checksum.names() == vec!["data", "sum"]);
+    #[allow(deprecated)]
+    let tuples = checksum.infos();
+    assert!(tuples == vec![("data", None), ("sum", Some("sum of the bytes"))]);
+
+    let header = Header{ kind : 1 };
+    assert!(FieldInfo::from(("kind", None)) == FieldInfo::field("kind", None));
+    #[allow(deprecated)]
+    let tuples = header.infos();
+    assert!(tuples == vec![("kind", None)]);
+  }
+}
@@ -0,0 +1,138 @@
+//! Registry of [ReflectStruct] types.
+//! Registering a type keep its name and field informations, so serialized values of that type
+//! can be rehydrated as a [ReflectBag](super::ReflectBag) when deserialized.
+//! Types can also register a [Factory] recreating the concrete type, and their JSON schema,
+//! the concrete type can then be recovered with `downcast_ref`.
+//! [register_deserializable] register all of them for types implementing [DeserializeOwned] and [JsonSchema], it's meant to be called by derive macros.
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::reflect::{ReflectStruct, ReflectBag, FieldInfo};
+use crate::value::Value;
+
+use log::warn;
+use serde::de::DeserializeOwned;
+use schemars::JsonSchema;
+use schemars::schema::RootSchema;
+
+type Infos = Vec<FieldInfo>;
+
+/// Function creating a [ReflectStruct] from its serialized fields.
+pub type Factory = fn(Vec<(String, Value)>) -> anyhow::Result<Arc<dyn ReflectStruct + Sync + Send>>;
+
+/// Function returning the JSON schema of a type.
+pub type SchemaGenerator = fn() -> RootSchema;
+
+/// Informations registered for a type.
+#[derive(Clone)]
+struct Entry
+{
+  infos : Infos,
+  factory : Option<Factory>,
+  schema : Option<SchemaGenerator>,
+}
+
+fn types() -> &'static RwLock<HashMap<&'static str, Entry>>
+{
+  static TYPES : OnceLock<RwLock<HashMap<&'static str, Entry>>> = OnceLock::new();
+  TYPES.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Register the type of `reflect` using its [name](ReflectStruct::name) and [field informations](ReflectStruct::field_infos).
+/// Registering the same name twice replace the previous informations.
+pub fn register(reflect : &dyn ReflectStruct)
+{
+  types().write().unwrap().insert(reflect.name(), Entry{ infos : reflect.field_infos(), factory : None, schema : None });
+}
+
+/// Register the type of `reflect` with a `factory` used to recreate the concrete type when deserialized.
+pub fn register_factory(reflect : &dyn ReflectStruct, factory : Factory)
+{
+  types().write().unwrap().insert(reflect.name(), Entry{ infos : reflect.field_infos(), factory : Some(factory), schema : None });
+}
+
+/// Create a `T` from its serialized fields.
+fn deserialize<T>(fields : Vec<(String, Value)>) -> anyhow::Result<Arc<dyn ReflectStruct + Sync + Send>>
+  where T : ReflectStruct + DeserializeOwned + 'static
+{
+  let mut map = serde_json::Map::new();
+  for (name, value) in fields
+  {
+    map.insert(name, serde_json::to_value(&value)?);
+  }
+  Ok(Arc::new(serde_json::from_value::<T>(serde_json::Value::Object(map))?))
+}
+
+/// Return the JSON schema of `T`.
+fn schema<T : JsonSchema>() -> RootSchema
+{
+  schemars::schema_for!(T)
+}
+
+/// Register the type of `sample` with a factory deserializing `T` from its fields and its JSON schema.
+pub fn register_deserializable<T>(sample : &T)
+  where T : ReflectStruct + DeserializeOwned + JsonSchema + 'static
+{
+  types().write().unwrap().insert(sample.name(), Entry{ infos : sample.field_infos(), factory : Some(deserialize::<T>), schema : Some(schema::<T>) });
+}
+
+/// Return true if a type named `name` is registered.
+pub fn is_registered(name : &str) -> bool
+{
+  types().read().unwrap().contains_key(name)
+}
+
+/// Return the registered name and field informations of type `name`.
+pub fn infos(name : &str) -> Option<(&'static str, Infos)>
+{
+  types().read().unwrap().get_key_value(name).map(|(name, entry)| (*name, entry.infos.clone()))
+}
+
+/// Return the JSON schema of type `name` if it was registered with [register_deserializable].
+pub fn schema_of(name : &str) -> Option<RootSchema>
+{
+  let schema = types().read().unwrap().get(name)?.schema?;
+  Some(schema())
+}
+
+/// Recreate a struct of type `name` from its serialized `fields`.
+/// The registered [Factory] is used if any, otherwise a [ReflectBag] is returned.
+/// Return `None` if `name` is not registered.
+pub fn rehydrate(name : &str, fields : Vec<(String, Value)>) -> Option<Arc<dyn ReflectStruct + Sync + Send>>
+{
+  let factory = types().read().unwrap().get(name)?.factory;
+  if let Some(factory) = factory
+  {
+    match factory(fields.clone())
+    {
+      Ok(reflect) => return Some(reflect),
+      Err(err) => warn!("Can't create {} with its factory : {}", name, err),
+    }
+  }
+  ReflectBag::new(name, fields).map(|bag| Arc::new(bag) as Arc<dyn ReflectStruct + Sync + Send>)
+}
+
+/// Return the name of the only registered type which fields are exactly `fields` in any order,
+/// used to recognize the serialized structs which don't contain their type name.
+pub fn find_by_fields(fields : &[&str]) -> Option<&'static str>
+{
+  if fields.is_empty()
+  {
+    return None
+  }
+
+  let types = types().read().unwrap();
+  let mut found = types.iter().filter(|(_, entry)| entry.infos.len() == fields.len() && entry.infos.iter().all(|info| fields.contains(&info.name)));
+  match (found.next(), found.next())
+  {
+    (Some((name, _)), None) => Some(*name),
+    _ => None,
+  }
+}
+
+/// Return the name of all registered types.
+pub fn names() -> Vec<&'static str>
+{
+  types().read().unwrap().keys().copied().collect()
+}
@@ -0,0 +1,37 @@
+//! Refresh let source plugins mounted on a live source (a directory or a growing capture file)
+//! re-scan it and apply incremental updates to the [Tree] instead of requiring a full re-mount.
+
+use crate::tree::{Tree, TreeNodeId, NodeState};
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+
+/// A change applied to a [node](crate::node::Node) of the [Tree] by a [Refreshable] source.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NodeChange
+{
+  /// Id of the node that changed.
+  pub node_id : TreeNodeId,
+  /// Kind of change.
+  pub state : NodeState,
+}
+
+impl NodeChange
+{
+  /// Return a new [NodeChange].
+  pub fn new(node_id : TreeNodeId, state : NodeState) -> Self
+  {
+    NodeChange{ node_id, state }
+  }
+}
+
+/**
+ * Implemented by plugins whose source can change after it was mounted.
+ * A plugin register its [Refreshable] on the node it mounted with [Tree::set_refreshable],
+ * [Session::refresh](crate::session::Session::refresh) can then be called on that node or any of its descendants.
+ */
+pub trait Refreshable : Sync + Send
+{
+  /// Re-scan the source of `node_id`, add, remove or update its descendants in `tree` and return the changes applied.
+  fn refresh(&self, tree : &Tree, node_id : TreeNodeId) -> Result<Vec<NodeChange>>;
+}
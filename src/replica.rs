@@ -0,0 +1,375 @@
+//! Replication of a [Tree] to an other process, for frontends running separately from the analysis.
+//!
+//! The analysis side use a [ReplicaPublisher] to stream the [changes](Tree::changed_since) of its tree as [ReplicaDelta] frames
+//! over a channel or a socket, and the frontend apply them to a [ReplicaTree] which keep a read-only mirror of the tree.
+//! Attributes are sent as [TaggedValue] so values keep their exact type, and node ids referenced in attributes
+//! are translated to the ids of the mirror.
+
+use std::io::{Read, Write, ErrorKind};
+use std::collections::HashMap;
+
+use crate::tree::{Tree, TreeNode, TreeNodeId, NodeState};
+use crate::node::Node;
+use crate::value::Value;
+use crate::value::tagged::TaggedValue;
+use crate::reference;
+use crate::error::RustructError;
+
+use anyhow::Result;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use serde::{Serialize, Deserialize};
+
+/// Maximum size of a frame read by [ReplicaTree::read_from], protect against corrupted streams.
+pub const MAX_FRAME_SIZE : u32 = 1024 * 1024 * 1024;
+
+/// A node added or updated in the replicated tree.
+#[derive(Serialize, Deserialize)]
+pub struct ReplicaNode
+{
+  /// Id of the node in the replicated tree.
+  pub id : TreeNodeId,
+  /// Id of the parent of the node in the replicated tree.
+  pub parent : Option<TreeNodeId>,
+  /// Name of the node.
+  pub name : String,
+  /// Original bytes of the name if they are different from `name`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub raw_name : Option<Vec<u8>>,
+  /// Attributes of the node.
+  pub attributes : TaggedValue,
+}
+
+impl ReplicaNode
+{
+  /// Return the replicated `node` of id `node_id` in `tree`.
+  fn new(tree : &Tree, node_id : TreeNodeId, node : &Node) -> Self
+  {
+    ReplicaNode{ id : node_id, parent : tree.parent_id(node_id), name : node.name(), raw_name : node.raw_name().map(|raw| raw.to_vec()),
+                 attributes : TaggedValue::from(&Value::Attributes(node.value())) }
+  }
+}
+
+/// Serializable [Delta] sent to a [ReplicaTree].
+#[derive(Serialize, Deserialize)]
+pub struct ReplicaDelta
+{
+  /// Version from which the changes were collected.
+  pub since : u64,
+  /// Version of the replicated tree when the delta was generated.
+  pub version : u64,
+  /// Id of the root of the replicated tree.
+  pub root : TreeNodeId,
+  /// Nodes added or updated.
+  pub nodes : Vec<ReplicaNode>,
+  /// Id of the nodes removed.
+  pub removed : Vec<TreeNodeId>,
+}
+
+impl ReplicaDelta
+{
+  /// Collect all the changes made to `tree` since version `since`.
+  pub fn new(tree : &Tree, since : u64) -> Self
+  {
+    let version = tree.version();
+    let mut delta = ReplicaDelta{ since, version, root : tree.root_id, nodes : Vec::new(), removed : Vec::new() };
+
+    for (node_id, _, state) in tree.changed_since(since)
+    {
+      //node can have been removed by an other thread since we get the changes
+      match tree.get_node_from_id(node_id)
+      {
+        Some(node) if state != NodeState::Removed => delta.nodes.push(ReplicaNode::new(tree, node_id, &node)),
+        _ => delta.removed.push(node_id),
+      }
+    }
+    delta
+  }
+
+  /// Return true if there is no change in this delta.
+  pub fn is_empty(&self) -> bool
+  {
+    self.nodes.is_empty() && self.removed.is_empty()
+  }
+}
+
+/// Write `delta` to `writer` as a frame : its size as an u32 followed by its JSON serialization.
+pub fn write_frame<W : Write>(writer : &mut W, delta : &ReplicaDelta) -> Result<()>
+{
+  let data = serde_json::to_vec(delta)?;
+  writer.write_u32::<LittleEndian>(data.len() as u32)?;
+  writer.write_all(&data)?;
+  writer.flush()?;
+  Ok(())
+}
+
+/// Read a frame written by [write_frame], return `None` at the end of the stream.
+pub fn read_frame<R : Read>(reader : &mut R) -> Result<Option<ReplicaDelta>>
+{
+  let size = match reader.read_u32::<LittleEndian>()
+  {
+    Ok(size) => size,
+    Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+    Err(err) => return Err(err.into()),
+  };
+  if size > MAX_FRAME_SIZE
+  {
+    return Err(RustructError::InvalidEncoding(format!("replica frame of {} bytes is too big", size)).into())
+  }
+
+  let mut data = vec![0; size as usize];
+  reader.read_exact(&mut data)?;
+  Ok(Some(serde_json::from_slice(&data)?))
+}
+
+/**
+ * Collect the changes of a [Tree] since the last published version, to send them to a [ReplicaTree].
+ */
+pub struct ReplicaPublisher
+{
+  tree : Tree,
+  version : u64,
+}
+
+impl ReplicaPublisher
+{
+  /// Return a publisher of the changes of `tree`, the first delta contain the whole tree.
+  pub fn new(tree : Tree) -> Self
+  {
+    ReplicaPublisher{ tree, version : 0 }
+  }
+
+  /// Return the changes since the last call, or `None` if the tree didn't change.
+  pub fn poll(&mut self) -> Option<ReplicaDelta>
+  {
+    let delta = ReplicaDelta::new(&self.tree, self.version);
+    self.version = delta.version;
+    match delta.is_empty()
+    {
+      true => None,
+      false => Some(delta),
+    }
+  }
+
+  /// Write the changes since the last call to `writer`, return false if the tree didn't change.
+  pub fn publish<W : Write>(&mut self, writer : &mut W) -> Result<bool>
+  {
+    match self.poll()
+    {
+      Some(delta) => { write_frame(writer, &delta)?; Ok(true) },
+      None => Ok(false),
+    }
+  }
+}
+
+/**
+ * Read-only mirror of a remote [Tree] updated by applying [ReplicaDelta].
+ * The mirror has its own node ids, [local_id](ReplicaTree::local_id) and [remote_id](ReplicaTree::remote_id) translate them.
+ * The [tree](ReplicaTree::tree) can be queried like the remote tree but must not be modified.
+ */
+pub struct ReplicaTree
+{
+  tree : Tree,
+  version : u64,
+  local_ids : HashMap<TreeNodeId, TreeNodeId>,
+  remote_ids : HashMap<TreeNodeId, TreeNodeId>,
+}
+
+impl Default for ReplicaTree
+{
+  fn default() -> Self
+  {
+    Self::new()
+  }
+}
+
+impl ReplicaTree
+{
+  /// Return an empty replica.
+  pub fn new() -> Self
+  {
+    ReplicaTree{ tree : Tree::new(), version : 0, local_ids : HashMap::new(), remote_ids : HashMap::new() }
+  }
+
+  /// Return the mirrored tree.
+  pub fn tree(&self) -> &Tree
+  {
+    &self.tree
+  }
+
+  /// Return the version of the remote tree mirrored.
+  pub fn version(&self) -> u64
+  {
+    self.version
+  }
+
+  /// Return the id in the mirror of the remote node `remote_id`.
+  pub fn local_id(&self, remote_id : TreeNodeId) -> Option<TreeNodeId>
+  {
+    self.local_ids.get(&remote_id).cloned()
+  }
+
+  /// Return the id in the remote tree of the mirror node `local_id`.
+  pub fn remote_id(&self, local_id : TreeNodeId) -> Option<TreeNodeId>
+  {
+    self.remote_ids.get(&local_id).cloned()
+  }
+
+  /// Return the node at `path`.
+  pub fn get_node(&self, path : &str) -> Option<TreeNode>
+  {
+    self.tree.get_node(path)
+  }
+
+  /// Apply the changes of `delta`, deltas must be applied in order without gap.
+  pub fn apply(&mut self, delta : ReplicaDelta) -> Result<()>
+  {
+    if delta.since > self.version
+    {
+      return Err(RustructError::ReplicaOutOfSync(self.version, delta.since).into())
+    }
+
+    for remote_id in delta.removed
+    {
+      if let Some(local_id) = self.local_ids.remove(&remote_id)
+      {
+        self.remote_ids.remove(&local_id);
+        if self.tree.get_node_from_id(local_id).is_some()
+        {
+          self.tree.remove(local_id)?;
+        }
+      }
+    }
+
+    self.local_ids.insert(delta.root, self.tree.root_id);
+    self.remote_ids.insert(self.tree.root_id, delta.root);
+
+    //nodes are sorted by modification version so a child can come before its parent,
+    //all nodes are created before being attached and before translating the references of their attributes
+    let mut created = Vec::new();
+    for node in delta.nodes.iter()
+    {
+      if !self.local_ids.contains_key(&node.id)
+      {
+        let local_node = match &node.raw_name
+        {
+          Some(raw) => Node::with_raw_name(node.name.clone(), raw),
+          None => Node::new(node.name.clone()),
+        };
+        let local_id = self.tree.new_node(local_node);
+        self.local_ids.insert(node.id, local_id);
+        self.remote_ids.insert(local_id, node.id);
+        created.push(node);
+      }
+    }
+
+    for node in created
+    {
+      let parent_id = node.parent.and_then(|parent| self.local_id(parent))
+                          .ok_or_else(|| RustructError::NodeNotFound(format!("parent of {}", node.name)))?;
+      self.tree.add_child_from_id(parent_id, self.local_ids[&node.id])?;
+    }
+
+    for node in delta.nodes
+    {
+      let local_id = self.local_ids[&node.id];
+      let attributes = match Value::from(node.attributes)
+      {
+        Value::Attributes(attributes) => attributes,
+        _ => return Err(RustructError::ValueTypeMismatch.into()),
+      };
+
+      let mut broken = Vec::new();
+      let attributes = match reference::rewrite(&Value::Attributes(attributes), &|remote_id| self.local_id(remote_id), &mut broken).0
+      {
+        Value::Attributes(attributes) => attributes,
+        _ => return Err(RustructError::ValueTypeMismatch.into()),
+      };
+
+      let mut current = self.tree.get_node_from_id(local_id).ok_or_else(|| RustructError::NodeNotFound(node.name.clone()))?.value();
+      for name in current.names()
+      {
+        current.remove_attribute(&name);
+      }
+      for attribute in attributes.attributes().iter()
+      {
+        current.add_attribute(attribute.name().to_string(), attribute.value().clone(), None);
+      }
+      self.tree.touch(local_id);
+    }
+
+    self.version = delta.version;
+    Ok(())
+  }
+
+  /// Read a frame from `reader` and apply it, return false at the end of the stream.
+  pub fn read_from<R : Read>(&mut self, reader : &mut R) -> Result<bool>
+  {
+    match read_frame(reader)?
+    {
+      Some(delta) => { self.apply(delta)?; Ok(true) },
+      None => Ok(false),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{ReplicaPublisher, ReplicaTree};
+  use crate::tree::Tree;
+  use crate::node::Node;
+  use crate::value::Value;
+
+  use std::io::Cursor;
+
+  #[test]
+  fn replicate_tree()
+  {
+    let tree = Tree::new();
+    let dir_id = tree.add_child(tree.root_id, Node::new("dir")).unwrap();
+    let file = Node::new("file");
+    file.value().add_attribute("size", Value::U16(512), None);
+    let file_id = tree.add_child(dir_id, file).unwrap();
+    let link = Node::new("link");
+    link.value().add_attribute("target", Value::NodeId(file_id), None);
+    tree.add_child(tree.root_id, link).unwrap();
+    tree.add_child(dir_id, Node::with_raw_name("caf\\xe9", b"caf\xe9")).unwrap();
+    //parent modified after its child was added
+    tree.get_node_from_id(dir_id).unwrap().value().add_attribute("kind", Value::from("directory"), None);
+    tree.touch(dir_id);
+
+    let mut publisher = ReplicaPublisher::new(tree.clone());
+    let mut stream = Vec::new();
+    assert!(publisher.publish(&mut stream).unwrap());
+    assert!(!publisher.publish(&mut stream).unwrap());
+
+    tree.get_node_from_id(file_id).unwrap().value().add_attribute("name", Value::from("file.txt"), None);
+    tree.add_child(dir_id, Node::new("other")).unwrap();
+    assert!(publisher.publish(&mut stream).unwrap());
+
+    let mut replica = ReplicaTree::new();
+    let mut reader = Cursor::new(stream);
+    while replica.read_from(&mut reader).unwrap() {}
+    assert!(replica.version() == tree.version());
+
+    let file = replica.get_node("/root/dir/file").unwrap();
+    assert!(matches!(file.value().get_value("size").unwrap(), Value::U16(512)));
+    assert!(file.value().get_value("name").unwrap().as_string() == "file.txt");
+    assert!(replica.get_node("/root/dir").unwrap().value().get_value("kind").is_some());
+    assert!(replica.tree().children_name(replica.tree().get_node_id("/root/dir").unwrap()).len() == 3);
+    assert!(replica.get_node("/root/dir/caf\\xe9").unwrap().raw_name() == Some(&b"caf\xe9"[..]));
+
+    let local_file_id = replica.tree().get_node_id("/root/dir/file").unwrap();
+    assert!(replica.remote_id(local_file_id) == Some(file_id));
+    assert!(matches!(replica.get_node("/root/link").unwrap().value().get_value("target").unwrap(), Value::NodeId(id) if id == local_file_id));
+
+    tree.remove(dir_id).unwrap();
+    replica.apply(publisher.poll().unwrap()).unwrap();
+    assert!(replica.get_node("/root/dir").is_none());
+    assert!(replica.local_id(file_id).is_none());
+
+    let mut late = ReplicaTree::new();
+    tree.add_child(tree.root_id, Node::new("new")).unwrap();
+    assert!(late.apply(publisher.poll().unwrap()).is_err());
+  }
+}
@@ -0,0 +1,172 @@
+//! [ResultStore] keep big [plugin results](PluginResult) on disk rather than in the task map,
+//! only their size is kept in memory and results are read back when the task state is requested.
+
+use std::io::Read;
+use std::sync::{Arc, RwLock};
+use std::collections::HashMap;
+
+use crate::task_scheduler::{TaskId, TaskState};
+use crate::plugin::PluginResult;
+use crate::tempvfile::TempVFileBuilder;
+use crate::vfile::VFileBuilder;
+
+use anyhow::Result;
+use log::warn;
+use serde::{Serialize, Deserialize};
+
+/// Default size from which results are stored on disk.
+pub const RESULT_THRESHOLD : usize = 1024 * 1024;
+
+/// Size statistics of the task results returned by [TaskScheduler::result_stats](crate::task_scheduler::TaskScheduler::result_stats).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResultStats
+{
+  /// Number of results kept in memory.
+  pub in_memory : usize,
+  /// Total size of the results kept in memory.
+  pub in_memory_bytes : u64,
+  /// Number of results stored on disk.
+  pub stored : usize,
+  /// Total size of the results stored on disk.
+  pub stored_bytes : u64,
+}
+
+/**
+ * Store results bigger than a threshold in temporary files, removed when the store is dropped.
+ */
+pub struct ResultStore
+{
+  threshold : RwLock<Option<usize>>,
+  results : RwLock<HashMap<TaskId, Arc<TempVFileBuilder>>>,
+}
+
+impl Default for ResultStore
+{
+  fn default() -> Self
+  {
+    ResultStore{ threshold : RwLock::new(Some(RESULT_THRESHOLD)), results : RwLock::new(HashMap::new()) }
+  }
+}
+
+impl ResultStore
+{
+  /// Return a new [ResultStore] storing results bigger than [RESULT_THRESHOLD].
+  pub fn new() -> Self
+  {
+    ResultStore::default()
+  }
+
+  /// Set the size from which results are stored on disk, or keep all results in memory if `None`.
+  pub fn set_threshold(&self, threshold : Option<usize>)
+  {
+    *self.threshold.write().unwrap() = threshold;
+  }
+
+  /// Store `result` of task `task_id` on disk if it's bigger than the threshold.
+  /// Return true if the result was stored, and so doesn't need to be kept in memory.
+  pub fn store(&self, task_id : TaskId, result : &str) -> Result<bool>
+  {
+    match *self.threshold.read().unwrap()
+    {
+      Some(threshold) if result.len() >= threshold => (),
+      _ => return Ok(false),
+    }
+
+    let builder = TempVFileBuilder::new(result.as_bytes())?;
+    self.results.write().unwrap().insert(task_id, builder);
+    Ok(true)
+  }
+
+  /// Move the result of a finished `task_state` to the store if it's too big to be kept in memory,
+  /// the returned state then only contain an empty result.
+  pub fn store_state(&self, task_state : TaskState) -> TaskState
+  {
+    match task_state
+    {
+      TaskState::Finished(task, Ok(result)) => match self.store(task.id, &result)
+      {
+        Ok(true) => TaskState::Finished(task, Ok(String::new())),
+        Ok(false) => TaskState::Finished(task, Ok(result)),
+        Err(err) =>
+        {
+          warn!("Can't store result of task {} : {}", task.id, err);
+          TaskState::Finished(task, Ok(result))
+        },
+      },
+      task_state => task_state,
+    }
+  }
+
+  /// Return `task_state` with its result read back from the store if it was stored.
+  pub fn load_state(&self, task_state : TaskState) -> TaskState
+  {
+    match task_state
+    {
+      TaskState::Finished(task, Ok(result)) => match self.load(task.id)
+      {
+        Some(stored) => TaskState::Finished(task, stored.map_err(Arc::new)),
+        None => TaskState::Finished(task, Ok(result)),
+      },
+      task_state => task_state,
+    }
+  }
+
+  /// Return true if the result of task `task_id` is stored on disk.
+  pub fn contains(&self, task_id : TaskId) -> bool
+  {
+    self.results.read().unwrap().contains_key(&task_id)
+  }
+
+  /// Read the stored result of task `task_id`, return `None` if it was not stored.
+  pub fn load(&self, task_id : TaskId) -> Option<Result<PluginResult>>
+  {
+    let builder = self.results.read().unwrap().get(&task_id)?.clone();
+    let mut result = String::new();
+
+    Some(builder.open().and_then(|mut file| Ok(file.read_to_string(&mut result).map(|_| result)?)))
+  }
+
+  /// Return the number and total size of the stored results.
+  pub fn stats(&self) -> (usize, u64)
+  {
+    let results = self.results.read().unwrap();
+    (results.len(), results.values().map(|builder| builder.size()).sum())
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::ResultStore;
+  use crate::task_scheduler::{Task, TaskState, Priority};
+
+  #[test]
+  fn store_big_results()
+  {
+    let store = ResultStore::new();
+    store.set_threshold(Some(8));
+
+    assert!(!store.store(1, "small").unwrap());
+    assert!(store.store(2, "big result").unwrap());
+    assert!(!store.contains(1) && store.contains(2));
+    assert!(store.load(1).is_none());
+    assert!(store.load(2).unwrap().unwrap() == "big result");
+    assert!(store.stats() == (1, 10));
+
+    store.set_threshold(None);
+    assert!(!store.store(3, "big result").unwrap());
+  }
+
+  #[test]
+  fn store_task_states()
+  {
+    let store = ResultStore::new();
+    store.set_threshold(Some(8));
+
+    let task = Task{ id : 1, plugin_name : "dummy".into(), argument : "{}".into(), summary : None, progress : None, timeout : None, priority : Priority::Normal, pool : None };
+    let stored = store.store_state(TaskState::Finished(task.clone(), Ok("big result".into())));
+    assert!(matches!(&stored, TaskState::Finished(_, Ok(result)) if result.is_empty()));
+    assert!(matches!(store.load_state(stored), TaskState::Finished(_, Ok(result)) if result == "big result"));
+    assert!(matches!(store.load_state(TaskState::Waiting(task)), TaskState::Waiting(_)));
+  }
+}
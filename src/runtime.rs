@@ -0,0 +1,121 @@
+//! Capabilities of this build of TAP, returned as one serializable [RuntimeManifest]
+//! so remote clients can adapt to the features, plugins and types available on the server they talk to.
+
+use crate::plugin::{ABI_VERSION, CORE_VERSION, core_features};
+use crate::plugins_db::PluginsDB;
+use crate::value::ValueTypeId;
+use crate::vfile;
+use crate::reflect::registry;
+
+use serde::{Serialize, Deserialize};
+
+/// Version of the core and of the platform it was built for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VersionInfo
+{
+  pub core_version : String,
+  pub abi_version : u32,
+  pub os : String,
+  pub arch : String,
+}
+
+/// Description of a plugin of a [RuntimeManifest].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginManifest
+{
+  pub name : String,
+  pub category : String,
+  /// Version of the plugin used by the [PluginsDB].
+  #[serde(default)]
+  pub version : String,
+  pub help : String,
+  /// JSON schema of the plugin argument, or None if it can't be generated.
+  pub schema : Option<serde_json::Value>,
+  pub requires_features : Vec<String>,
+}
+
+/**
+ * Capabilities of this build of TAP : enabled features, types of [VFileBuilder](crate::vfile::VFileBuilder) that can be
+ * deserialized, plugins with their argument schema, [Value](crate::value::Value) types and registered reflect types.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuntimeManifest
+{
+  pub version : VersionInfo,
+  /// [Core features](core_features) available in this build.
+  pub features : Vec<String>,
+  pub builder_types : Vec<String>,
+  pub plugins : Vec<PluginManifest>,
+  pub value_types : Vec<String>,
+  /// Names of the types registered in the [reflect registry](crate::reflect::registry).
+  pub reflect_types : Vec<String>,
+}
+
+impl RuntimeManifest
+{
+  /// Return the manifest of this build listing the plugins of `plugins_db`.
+  pub fn new(plugins_db : &PluginsDB) -> Self
+  {
+    let version = VersionInfo{ core_version : CORE_VERSION.into(), abi_version : ABI_VERSION,
+                               os : std::env::consts::OS.into(), arch : std::env::consts::ARCH.into() };
+
+    let plugins = plugins_db.iter().map(|plugin| PluginManifest{
+      name : plugin.name().into(),
+      category : plugin.category().into(),
+      version : plugin.version().into(),
+      help : plugin.help().into(),
+      schema : plugin.config().ok().and_then(|config| serde_json::from_str(&config).ok()),
+      requires_features : plugin.requires_features().into_iter().map(String::from).collect(),
+    }).collect();
+
+    let value_types = (0..=u8::MAX).map_while(|id| ValueTypeId::try_from(id).ok()).map(|id| format!("{:?}", id)).collect();
+    let mut reflect_types : Vec<String> = registry::names().into_iter().map(String::from).collect();
+    reflect_types.sort();
+
+    RuntimeManifest{ version, features : core_features().into_iter().map(String::from).collect(),
+                     builder_types : vfile::builder_types().into_iter().map(String::from).collect(),
+                     plugins, value_types, reflect_types }
+  }
+
+  /// Return true if the core feature `feature` is available.
+  pub fn has_feature(&self, feature : &str) -> bool
+  {
+    self.features.iter().any(|available| available == feature)
+  }
+}
+
+/// Return the [RuntimeManifest] of this build, listing the plugins registered at compile time when the `auto_register` feature is enabled.
+pub fn runtime_manifest() -> RuntimeManifest
+{
+  #[cfg(feature = "auto_register")]
+  let plugins_db = PluginsDB::with_registered();
+  #[cfg(not(feature = "auto_register"))]
+  let plugins_db = PluginsDB::new();
+
+  RuntimeManifest::new(&plugins_db)
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{RuntimeManifest, runtime_manifest};
+  use crate::plugins_db::PluginsDB;
+  use crate::plugin_dummy;
+
+  #[test]
+  fn manifest_capabilities()
+  {
+    let manifest = runtime_manifest();
+    assert!(manifest.has_feature("crypto") && manifest.has_feature("http") == cfg!(feature = "http"));
+    assert!(manifest.builder_types.contains(&"MemoryVFileBuilder".to_string()));
+    assert!(manifest.value_types.first().unwrap() == "Attributes" && manifest.value_types.contains(&"Method".to_string()));
+
+    let mut plugins_db = PluginsDB::new();
+    plugins_db.register(Box::new(plugin_dummy::Plugin::new()));
+    let manifest = RuntimeManifest::new(&plugins_db);
+    assert!(manifest.plugins.len() == 1 && manifest.plugins[0].name == "dummy" && manifest.plugins[0].schema.is_some());
+
+    let json = serde_json::to_string(&manifest).unwrap();
+    assert!(serde_json::from_str::<RuntimeManifest>(&json).unwrap() == manifest);
+  }
+}
@@ -0,0 +1,116 @@
+//! Registry of the services shared by the plugins, like a set of known hashes or a rule set.
+//!
+//! The [Services] of the [Session](crate::session::Session) are passed to each plugin in its [PluginEnvironment](crate::plugin::PluginEnvironment),
+//! a service is found by its type with [get](Services::get), so a plugin share state between its instances without a global singleton.
+
+use std::any::{Any, TypeId, type_name};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::error::RustructError;
+
+use anyhow::Result;
+
+type Service = Arc<dyn Any + Send + Sync>;
+
+/**
+ * Services indexed by their type, clones share the same services.
+ */
+#[derive(Clone, Default)]
+pub struct Services
+{
+  services : Arc<RwLock<HashMap<TypeId, (&'static str, Service)>>>,
+}
+
+impl Services
+{
+  pub fn new() -> Self
+  {
+    Services::default()
+  }
+
+  /// Add `service`, return the service of the same type it replace.
+  pub fn insert<T : Any + Send + Sync>(&self, service : T) -> Option<Arc<T>>
+  {
+    self.insert_arc(Arc::new(service))
+  }
+
+  /// Add a `service` already shared with the caller, return the service of the same type it replace.
+  pub fn insert_arc<T : Any + Send + Sync>(&self, service : Arc<T>) -> Option<Arc<T>>
+  {
+    let previous = self.services.write().unwrap().insert(TypeId::of::<T>(), (type_name::<T>(), service));
+    previous.and_then(|(_, previous)| previous.downcast().ok())
+  }
+
+  /// Return the service of type `T`.
+  pub fn get<T : Any + Send + Sync>(&self) -> Option<Arc<T>>
+  {
+    self.services.read().unwrap().get(&TypeId::of::<T>()).and_then(|(_, service)| service.clone().downcast().ok())
+  }
+
+  /// Return the service of type `T` or a [ServiceNotFound](RustructError::ServiceNotFound) error, to stop a plugin with `?`.
+  pub fn require<T : Any + Send + Sync>(&self) -> Result<Arc<T>>
+  {
+    self.get().ok_or_else(|| RustructError::ServiceNotFound(type_name::<T>().into()).into())
+  }
+
+  /// Return the service of type `T`, adding the one returned by `init` if there is none.
+  pub fn get_or_insert_with<T : Any + Send + Sync, F : FnOnce() -> T>(&self, init : F) -> Arc<T>
+  {
+    let mut services = self.services.write().unwrap();
+    let (_, service) = services.entry(TypeId::of::<T>()).or_insert_with(|| (type_name::<T>(), Arc::new(init())));
+    service.clone().downcast().expect("services are indexed by their type")
+  }
+
+  /// Remove the service of type `T`, return false if there was none.
+  pub fn remove<T : Any + Send + Sync>(&self) -> bool
+  {
+    self.services.write().unwrap().remove(&TypeId::of::<T>()).is_some()
+  }
+
+  /// Return the name of the types of the services.
+  pub fn names(&self) -> Vec<&'static str>
+  {
+    let mut names : Vec<&'static str> = self.services.read().unwrap().values().map(|(name, _)| *name).collect();
+    names.sort_unstable();
+    names
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::Services;
+  use crate::error::RustructError;
+
+  use std::collections::HashSet;
+  use std::sync::Arc;
+  use std::sync::atomic::{AtomicU32, Ordering};
+
+  struct KnownHashes(HashSet<&'static str>);
+
+  #[test]
+  fn services_registry()
+  {
+    let services = Services::new();
+    assert!(services.get::<KnownHashes>().is_none());
+    let err = services.require::<KnownHashes>().err().unwrap();
+    assert!(matches!(err.downcast_ref::<RustructError>(), Some(RustructError::ServiceNotFound(name)) if name.ends_with("KnownHashes")));
+
+    assert!(services.insert(KnownHashes(HashSet::from(["d41d8cd98f00b204e9800998ecf8427e"]))).is_none());
+    let shared = services.clone();
+    assert!(shared.require::<KnownHashes>().unwrap().0.contains("d41d8cd98f00b204e9800998ecf8427e"));
+    let previous = shared.insert(KnownHashes(HashSet::new())).unwrap();
+    assert!(previous.0.len() == 1 && services.get::<KnownHashes>().unwrap().0.is_empty());
+
+    let counter = services.get_or_insert_with(|| AtomicU32::new(0));
+    counter.fetch_add(1, Ordering::SeqCst);
+    assert!(shared.get_or_insert_with(|| AtomicU32::new(10)).load(Ordering::SeqCst) == 1);
+    assert!(services.names().len() == 2);
+
+    let rules = Arc::new(vec!["rule"]);
+    services.insert_arc(rules.clone());
+    assert!(Arc::ptr_eq(&services.get::<Vec<&'static str>>().unwrap(), &rules));
+    assert!(services.remove::<KnownHashes>() && !services.remove::<KnownHashes>() && shared.get::<KnownHashes>().is_none());
+  }
+}
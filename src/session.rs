@@ -3,11 +3,29 @@
 //! (plugins, taskmanager, the attributes and data tree, ...). 
 
 use std::sync::{Arc};
+use std::collections::HashSet;
 
-use crate::tree::{Tree};
+use crate::tree::{Tree, TreeNodeId, NodeState, AttributePath};
+use crate::value::Value;
+use crate::task_scheduler::TaskState;
+use crate::event::EventChannel;
+use crate::refresh::NodeChange;
 use crate::plugins_db::PluginsDB;
-use crate::task_scheduler::{TaskScheduler, TaskId};
-use crate::plugin::{PluginArgument,PluginResult};
+use crate::task_scheduler::{TaskScheduler, TaskSchedulerBuilder, TaskId, Priority, FailurePolicy};
+use crate::plugin::{PluginInfo, PluginArgument, PluginResult};
+use crate::context::CaseContext;
+use crate::stagingvfile::BlobStore;
+use crate::blockcache::BlockCache;
+use crate::services::Services;
+use crate::preview::Previewer;
+use crate::validation::{Validator, ValidationReport};
+use crate::tag::{Tagger, Query};
+use crate::computed::{ComputedAttributes, ComputedAttribute};
+use crate::access::{Access, Authorizer};
+use crate::runtime::RuntimeManifest;
+use crate::vfile::{BuilderRegistry, builder_registry};
+use crate::detection::{detect, best_matches};
+use crate::batch::{Batch, Target};
 use crate::error::RustructError;
 
 /**
@@ -21,58 +39,537 @@ pub struct Session
   pub tree : Tree,
   /// A [TaskScheduler] instance
   pub task_scheduler : TaskScheduler,
+  /// Send the [changes](NodeChange) applied to the tree by [refresh](Session::refresh)
+  pub changes : EventChannel<NodeChange>,
+  /// Generate and cache the node previews
+  pub(crate) previews : Previewer,
 }
 
 impl Session
 {
   /// Return a new [Session]
   pub fn new() -> Session
+  {
+    Session::with_scheduler(TaskSchedulerBuilder::new())
+  }
+
+  /// Return a new [Session] whose [TaskScheduler] workers are configured by `builder`.
+  pub fn with_scheduler(builder : TaskSchedulerBuilder) -> Session
   {
     let tree = Tree::new();
-    let task_scheduler = TaskScheduler::new(tree.clone());
-    Session{ plugins_db : PluginsDB::new(), tree, task_scheduler }
+    let task_scheduler = builder.build(tree.clone());
+    Session{ plugins_db : PluginsDB::new(), tree, task_scheduler, changes : EventChannel::new(), previews : Previewer::new() }
+  }
+
+  /// Return a new [Session] whose [PluginsDB] contains all the plugins registered at compile time with [register_plugin!](crate::register_plugin).
+  #[cfg(feature = "auto_register")]
+  pub fn with_registered() -> Session
+  {
+    let mut session = Session::new();
+    session.plugins_db.autodiscover();
+    session
   }
 
-  /// Replace [tree](Tree) and [task_scheduler](TaskScheduler) by a new intance.
+  /// Replace [tree](Tree) and [task_scheduler](TaskScheduler) by a new intance, the worker pools, the [CaseContext], the [BlobStore], the block cache configuration, the [Services], the validation rules, the saved queries, the tag rules, the computed attributes and the [Authorizer] are kept.
   pub fn clear(&mut self) 
   {
+    let authorizer = self.tree.authorizer();
+    let context = self.task_scheduler.context();
+    let blob_store = self.task_scheduler.blob_store();
+    let block_cache = self.task_scheduler.block_cache();
+    let services = self.task_scheduler.services();
+    let validator = self.task_scheduler.validator();
+    let tagger = self.task_scheduler.tagger();
+    let computed = self.task_scheduler.computed();
     self.tree = Tree::new();
-    self.task_scheduler = TaskScheduler::new(self.tree.clone());
+    self.tree.set_authorizer(authorizer);
+    self.previews.clear();
+    self.task_scheduler = self.task_scheduler.builder().build(self.tree.clone());
+    self.task_scheduler.set_context(context);
+    self.task_scheduler.set_blob_store(blob_store);
+    self.task_scheduler.block_cache().set_block_size(block_cache.block_size());
+    self.task_scheduler.block_cache().set_capacity(block_cache.capacity());
+    self.task_scheduler.set_services(services);
+    validator.rules().into_iter().for_each(|rule| self.task_scheduler.validator().add_rule(rule));
+    self.task_scheduler.validator().set_live(validator.is_live());
+    for (name, query) in tagger.queries()
+    {
+      self.task_scheduler.tagger().save_query(name, query);
+    }
+    for rule in tagger.rules()
+    {
+      let _ = self.task_scheduler.tagger().tag_by_query(&self.tree, &rule.query, &rule.tag);
+    }
+    computed.rules().into_iter().for_each(|rule| { self.task_scheduler.computed().add(&self.tree, rule); });
+  }
+
+  /// Set the [Authorizer] checking the access of the [users](crate::access::User) to the session.
+  pub fn set_authorizer(&self, authorizer : Arc<dyn Authorizer>)
+  {
+    self.tree.set_authorizer(authorizer);
+  }
+
+  /// Return the [RuntimeManifest] of this build listing the plugins of the session.
+  pub fn runtime_manifest(&self) -> RuntimeManifest
+  {
+    RuntimeManifest::new(&self.plugins_db)
+  }
+
+  /// Return the [BuilderRegistry] naming the builders referenced when the tree is saved and loaded, it's shared by the sessions of the process.
+  pub fn builders(&self) -> &'static BuilderRegistry
+  {
+    builder_registry()
+  }
+
+  /// Return the [Validator] holding the validation rules and the violations found.
+  pub fn validator(&self) -> Arc<Validator>
+  {
+    self.task_scheduler.validator()
+  }
+
+  /// Return the [Tagger] holding the saved queries and tag rules.
+  pub fn tagger(&self) -> Arc<Tagger>
+  {
+    self.task_scheduler.tagger()
+  }
+
+  /// Return the [ComputedAttributes] added to the nodes.
+  pub fn computed(&self) -> Arc<ComputedAttributes>
+  {
+    self.task_scheduler.computed()
+  }
+
+  /// Add the `computed` attribute to the nodes matching its query and to the nodes created later by the tasks.
+  /// Return the number of attributes added.
+  pub fn add_computed(&self, computed : ComputedAttribute) -> Result<usize, anyhow::Error>
+  {
+    self.tree.authorize(Access::Write, None)?;
+    Ok(self.task_scheduler.computed().add(&self.tree, computed))
+  }
+
+  /// Save `query` as `name`.
+  pub fn save_query<S : Into<String>>(&self, name : S, query : Query)
+  {
+    self.task_scheduler.tagger().save_query(name, query);
+  }
+
+  /// Tag all the nodes matching saved query `query` with `tag`, and the nodes created later by the tasks.
+  /// Return the id of the nodes tagged.
+  pub fn tag_by_query(&self, query : &str, tag : &str) -> Result<Vec<TreeNodeId>, anyhow::Error>
+  {
+    self.tree.authorize(Access::Write, None)?;
+    self.task_scheduler.tagger().tag_by_query(&self.tree, query, tag)
+  }
+
+  /// Check the validation rules on all the nodes of the tree.
+  pub fn validate(&self) -> ValidationReport
+  {
+    self.task_scheduler.validator().validate(&self.tree)
+  }
+
+  /// Set the [CaseContext] describing the acquired system, passed to the plugins launched after this call.
+  pub fn set_context(&self, context : CaseContext)
+  {
+    self.task_scheduler.set_context(context);
+  }
+
+  /// Return the [CaseContext] of the session.
+  pub fn context(&self) -> CaseContext
+  {
+    self.task_scheduler.context()
+  }
+
+  /// Set the [BlobStore] where the plugins launched after this call stage their artifacts.
+  pub fn set_blob_store(&self, blob_store : BlobStore)
+  {
+    self.task_scheduler.set_blob_store(blob_store);
+  }
+
+  /// Return the [BlobStore] of the session.
+  pub fn blob_store(&self) -> BlobStore
+  {
+    self.task_scheduler.blob_store()
+  }
+
+  /// Return the [BlockCache] shared by the plugins of the session.
+  pub fn block_cache(&self) -> Arc<BlockCache>
+  {
+    self.task_scheduler.block_cache()
+  }
+
+  /// Return the [Services] shared by the plugins of the session, services added to it are seen by the plugins launched after.
+  pub fn services(&self) -> Services
+  {
+    self.task_scheduler.services()
   }
 
   /// Create a [crate::plugin::PluginInstance] from `plugin_name` and `argument` add it to the scheduler and return it's task id.
   pub fn schedule(&self, plugin_name : &str, argument : PluginArgument, relaunch : bool) -> Result<TaskId, anyhow::Error>
   {
+    self.tree.authorize(Access::Run, None)?;
     let plugin = match self.plugins_db.find(plugin_name)
     {
       Some(plugin) => plugin,
       None => return Err(RustructError::PluginNotFound{ name : plugin_name.into()}.into()),
     };
     let plugin = plugin.instantiate();
+    let argument = self.plugins_db.argument(plugin_name, None, argument)?;
         
     self.task_scheduler.schedule(plugin, argument, relaunch)
   }
 
+  /// [Schedule](Session::schedule) plugin `plugin_name` launched before the waiting tasks of lower `priority`, like a request of a user waiting for the result.
+  pub fn schedule_with_priority(&self, plugin_name : &str, argument : PluginArgument, relaunch : bool, priority : Priority) -> Result<TaskId, anyhow::Error>
+  {
+    self.tree.authorize(Access::Run, None)?;
+    let plugin = match self.plugins_db.find(plugin_name)
+    {
+      Some(plugin) => plugin.instantiate(),
+      None => return Err(RustructError::PluginNotFound{ name : plugin_name.into()}.into()),
+    };
+    let argument = self.plugins_db.argument(plugin_name, None, argument)?;
+
+    self.task_scheduler.schedule_with_priority(plugin, argument, relaunch, priority)
+  }
+
+  /// [Schedule](Session::schedule) plugin `plugin_name` launched once the tasks `dependencies` are finished, see [TaskScheduler::schedule_after].
+  pub fn schedule_after(&self, dependencies : &[TaskId], plugin_name : &str, argument : PluginArgument, relaunch : bool, policy : FailurePolicy) -> Result<TaskId, anyhow::Error>
+  {
+    self.tree.authorize(Access::Run, None)?;
+    let plugin = match self.plugins_db.find(plugin_name)
+    {
+      Some(plugin) => plugin.instantiate(),
+      None => return Err(RustructError::PluginNotFound{ name : plugin_name.into()}.into()),
+    };
+    let argument = self.plugins_db.argument(plugin_name, None, argument)?;
+
+    self.task_scheduler.schedule_after(dependencies, plugin, argument, relaunch, policy)
+  }
+
+  /// [Schedule](Session::schedule) plugin `plugin_name` with the fields of its `preset` added to `argument`.
+  pub fn schedule_preset(&self, plugin_name : &str, preset : &str, argument : PluginArgument, relaunch : bool) -> Result<TaskId, anyhow::Error>
+  {
+    self.tree.authorize(Access::Run, None)?;
+    let plugin = match self.plugins_db.find(plugin_name)
+    {
+      Some(plugin) => plugin.instantiate(),
+      None => return Err(RustructError::PluginNotFound{ name : plugin_name.into()}.into()),
+    };
+    let argument = self.plugins_db.argument(plugin_name, Some(preset), argument)?;
+
+    self.task_scheduler.schedule(plugin, argument, relaunch)
+  }
+
+  /// Detect the type of the `data` attribute of node `node_id` and schedule the plugins whose [signatures](crate::detection::Signature) match it best,
+  /// return the ids of the scheduled tasks. Plugins already run on this data are not scheduled again, so it can be called on every new node
+  /// to extract the content recursively.
+  pub fn auto_parse(&self, node_id : TreeNodeId) -> Result<Vec<TaskId>, anyhow::Error>
+  {
+    self.auto_parse_with(node_id, |_| true)
+  }
+
+  /// [Auto parse](Session::auto_parse) node `node_id` with the best matching plugins accepted by `filter`,
+  /// plugins refused by the filter are not replaced by plugins with a lower score.
+  pub fn auto_parse_with<F>(&self, node_id : TreeNodeId, filter : F) -> Result<Vec<TaskId>, anyhow::Error>
+    where F : Fn(&dyn PluginInfo) -> bool
+  {
+    self.tree.authorize(Access::Run, Some(node_id))?;
+    let node = self.tree.get_node_from_id(node_id).ok_or_else(|| RustructError::NodeNotFound(format!("{:?}", node_id)))?;
+    let data = match node.value().get_value("data")
+    {
+      Some(Value::VFileBuilder(data)) => data,
+      _ => return Ok(Vec::new()),
+    };
+
+    let file = AttributePath{ node_id, attribute_name : "data".into() };
+    let mut task_ids = Vec::new();
+    for plugin in best_matches(&detect(&self.plugins_db, &node.name(), data.as_ref())?)
+    {
+      let plugin_info = self.plugins_db.find(plugin).unwrap();
+      if !filter(plugin_info.as_ref())
+      {
+        continue
+      }
+      match self.task_scheduler.schedule(plugin_info.instantiate(), plugin_info.detected_argument(&file), false)
+      {
+        Ok(task_id) => task_ids.push(task_id),
+        Err(err) if matches!(err.downcast_ref(), Some(RustructError::PluginAlreadyRunned)) => (),
+        Err(err) => return Err(err),
+      }
+    }
+    Ok(task_ids)
+  }
+
+  /// Schedule plugin `plugin_name` on each node of `ids`, with the defaults of the plugin and `base_args` where the node is injected as the [Target] of the plugin.
+  /// Nodes that can't be parsed, like a node not found or already parsed by the plugin with the same argument, are returned as skipped in the [Batch].
+  /// Return an error if the plugin is not found or has no target argument.
+  pub fn run_on(&self, ids : Vec<TreeNodeId>, plugin_name : &str, base_args : PluginArgument) -> Result<Batch, anyhow::Error>
+  {
+    self.tree.authorize(Access::Run, None)?;
+    let plugin_info = self.plugins_db.find(plugin_name).ok_or_else(|| RustructError::PluginNotFound{ name : plugin_name.into() })?;
+    let target = Target::of(plugin_info.as_ref())?;
+    let base : serde_json::Map<String, serde_json::Value> = serde_json::from_str(&self.plugins_db.argument(plugin_name, None, base_args)?)
+      .map_err(|err| RustructError::InvalidArgument(plugin_name.into(), err.to_string()))?;
+
+    let mut batch = Batch{ plugin : plugin_name.into(), ..Default::default() };
+    for node_id in ids
+    {
+      let scheduled = match self.tree.get_node_from_id(node_id)
+      {
+        Some(_) => self.tree.authorize(Access::Run, Some(node_id)).and_then(|_|
+          self.task_scheduler.schedule(plugin_info.instantiate(), target.argument(&base, node_id)?, false)),
+        None => Err(RustructError::NodeNotFound(format!("{:?}", node_id)).into()),
+      };
+      match scheduled
+      {
+        Ok(task_id) =>
+        {
+          batch.group.add(task_id);
+          batch.tasks.push((node_id, task_id));
+        },
+        Err(err) => batch.skipped.push((node_id, err.to_string())),
+      }
+    }
+    Ok(batch)
+  }
+
   /// Create a [crate::plugin::PluginInstance], add it to an available worker, wait for it to be executed  and return the results.
   /// This function is blocking the [TaskScheduler], so must be avoided in multithreaded code.
   pub fn run(&self, plugin_name : &str, argument : PluginArgument, relaunch : bool) -> Result<PluginResult, Arc<anyhow::Error>>
   {
+    self.tree.authorize(Access::Run, None).map_err(Arc::new)?;
     let plugin = match self.plugins_db.find(plugin_name)
     {
       Some(plugin) => plugin,
       None => return Err(Arc::new(RustructError::PluginNotFound{ name : plugin_name.into()}.into())), 
     };
     let plugin = plugin.instantiate();
+    let argument = self.plugins_db.argument(plugin_name, None, argument).map_err(Arc::new)?;
 
     self.task_scheduler.run(plugin, argument, relaunch)
   }
    
+  /// Ask the [Refreshable](crate::refresh::Refreshable) source of `node_id` to re-scan its source and apply the changes to the tree.
+  /// The changes are returned and sent to the receivers registered on [changes](Session::changes).
+  pub fn refresh(&self, node_id : TreeNodeId) -> Result<Vec<NodeChange>, anyhow::Error>
+  {
+    self.tree.authorize(Access::Write, Some(node_id))?;
+    let source = match self.tree.refreshable(node_id)
+    {
+      Some((_, source)) => source,
+      None => return Err(RustructError::NodeNotRefreshable(self.tree.node_path(node_id).unwrap_or_default()).into()),
+    };
+
+    let changes = source.refresh(&self.tree, node_id)?;
+    for change in changes.iter()
+    {
+      //make updates visible to tree exports
+      if change.state == NodeState::Updated
+      {
+        self.tree.touch(change.node_id);
+      }
+      self.changes.update(*change);
+    }
+    Ok(changes)
+  }
+
   /// Join on all scheduled task.
   /// This function is blocking the [TaskScheduler], so must be avoided in multithreaded code.
   pub fn join(&self) 
   {
     self.task_scheduler.join();
   }
+
+  /// Join on the tasks `ids` only, the tasks scheduled by others are not waited.
+  pub fn join_tasks(&self, ids : &[TaskId])
+  {
+    self.task_scheduler.join_tasks(ids);
+  }
+}
+
+/**
+ * Inconsistencies found by [Session::validate_after_load] between the tasks, the plugins and the tree of a reloaded session.
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadReport
+{
+  /// Tasks that were waiting or running when the session was saved and will never finish.
+  pub orphan_tasks : Vec<TaskId>,
+  /// Tasks which plugin is not registered, with the plugin name.
+  pub missing_plugins : Vec<(TaskId, String)>,
+  /// Tasks which argument contain an id of a node not found in the tree.
+  pub unresolved_arguments : Vec<(TaskId, TreeNodeId)>,
+  /// Node and name of the attributes containing a node id or an attribute path that can't be resolved.
+  pub unresolved_attributes : Vec<(TreeNodeId, String)>,
+}
+
+impl LoadReport
+{
+  /// Return true if no inconsistency was found.
+  pub fn is_valid(&self) -> bool
+  {
+    self.orphan_tasks.is_empty() && self.missing_plugins.is_empty() && self.unresolved_arguments.is_empty() && self.unresolved_attributes.is_empty()
+  }
+}
+
+/// Repairs applied by [Session::repair] for each kind of inconsistency of a [LoadReport].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RepairOptions
+{
+  /// Mark orphan tasks as finished with an error.
+  pub fail_orphan_tasks : bool,
+  /// Mark unfinished tasks which plugin is not registered as finished with an error, finished tasks keep their result.
+  pub fail_missing_plugins : bool,
+  /// Mark unfinished tasks with unresolved node ids in their argument as finished with an error, finished tasks keep their result.
+  pub fail_unresolved_arguments : bool,
+  /// Remove attributes containing unresolved node ids or attribute paths.
+  pub remove_unresolved_attributes : bool,
+}
+
+impl RepairOptions
+{
+  /// Return options that apply all repairs.
+  pub fn all() -> Self
+  {
+    RepairOptions{ fail_orphan_tasks : true, fail_missing_plugins : true, fail_unresolved_arguments : true, remove_unresolved_attributes : true }
+  }
+}
+
+/// Push all node ids found in a JSON `argument` to `ids`.
+fn argument_node_ids(argument : &serde_json::Value, ids : &mut Vec<TreeNodeId>)
+{
+  match argument
+  {
+    serde_json::Value::Object(map) =>
+    {
+      if map.len() == 2 && map.contains_key("index1") && map.contains_key("stamp")
+      {
+        if let Ok(node_id) = serde_json::from_value(argument.clone())
+        {
+          ids.push(node_id);
+          return
+        }
+      }
+      map.values().for_each(|value| argument_node_ids(value, ids));
+    },
+    serde_json::Value::Array(values) => values.iter().for_each(|value| argument_node_ids(value, ids)),
+    _ => (),
+  }
+}
+
+/// Return true if all the node ids and attribute paths contained in `value` exist in `tree`.
+/// Functions are not evaluated.
+fn value_is_resolved(tree : &Tree, value : &Value) -> bool
+{
+  match value
+  {
+    Value::NodeId(node_id) => tree.get_node_from_id(*node_id).is_some(),
+    Value::AttributePath(path) => path.get_value(tree).is_some(),
+    Value::Seq(values) => values.iter().all(|value| value_is_resolved(tree, value)),
+    Value::Map(values) => values.values().all(|value| value_is_resolved(tree, value)),
+    Value::Option(Some(value)) | Value::Newtype(value) => value_is_resolved(tree, value),
+    Value::Attributes(attributes) => attributes.attributes().iter().all(|attribute| value_is_resolved(tree, attribute.value())),
+    _ => true,
+  }
+}
+
+impl Session
+{
+  /// Check the consistency of a reloaded session and return a [LoadReport] listing the tasks that will never finish,
+  /// the tasks which plugin is not registered, and the node ids of task arguments and attributes that don't exist in the tree.
+  pub fn validate_after_load(&self) -> LoadReport
+  {
+    let mut report = LoadReport::default();
+
+    for state in self.task_scheduler.to_vec()
+    {
+      let task = match &state
+      {
+        TaskState::Waiting(task) | TaskState::Launched(task) => { report.orphan_tasks.push(task.id); task },
+        TaskState::Finished(task, _) => task,
+      };
+
+      if self.plugins_db.find(&task.plugin_name).is_none()
+      {
+        report.missing_plugins.push((task.id, task.plugin_name.clone()));
+      }
+
+      if let Ok(argument) = serde_json::from_str::<serde_json::Value>(&task.argument)
+      {
+        let mut ids = Vec::new();
+        argument_node_ids(&argument, &mut ids);
+        for node_id in ids.into_iter().filter(|node_id| self.tree.get_node_from_id(*node_id).is_none())
+        {
+          report.unresolved_arguments.push((task.id, node_id));
+        }
+      }
+    }
+
+    for (node_id, _) in self.tree.walk(self.tree.root_id)
+    {
+      if let Some(node) = self.tree.get_node_from_id(node_id)
+      {
+        for attribute in node.value().attributes().iter()
+        {
+          if !value_is_resolved(&self.tree, attribute.value())
+          {
+            report.unresolved_attributes.push((node_id, attribute.name().to_string()));
+          }
+        }
+      }
+    }
+
+    report.orphan_tasks.sort_unstable();
+    report.missing_plugins.sort_by_key(|missing| missing.0);
+    report.unresolved_arguments.sort_by_key(|unresolved| unresolved.0);
+    report
+  }
+
+  /// Apply the repairs selected in `options` to the inconsistencies found in `report`.
+  pub fn repair(&self, report : &LoadReport, options : &RepairOptions) -> Result<(), anyhow::Error>
+  {
+    self.tree.authorize(Access::Write, None)?;
+    let mut failed = HashSet::new();
+
+    if options.fail_orphan_tasks
+    {
+      for id in report.orphan_tasks.iter().filter(|id| failed.insert(**id))
+      {
+        self.task_scheduler.fail_task(*id, RustructError::TaskInterrupted(*id).into())?;
+      }
+    }
+
+    //finished tasks keep their result, their missing plugin or arguments are only reported
+    let unfinished = |id : &TaskId| report.orphan_tasks.contains(id);
+    if options.fail_missing_plugins
+    {
+      for (id, name) in report.missing_plugins.iter().filter(|(id, _)| unfinished(id) && failed.insert(*id))
+      {
+        self.task_scheduler.fail_task(*id, RustructError::PluginNotFound{ name : name.clone() }.into())?;
+      }
+    }
+
+    if options.fail_unresolved_arguments
+    {
+      for (id, node_id) in report.unresolved_arguments.iter().filter(|(id, _)| unfinished(id) && failed.insert(*id))
+      {
+        self.task_scheduler.fail_task(*id, RustructError::NodeNotFound(format!("{:?}", node_id)).into())?;
+      }
+    }
+
+    if options.remove_unresolved_attributes
+    {
+      for (node_id, name) in report.unresolved_attributes.iter()
+      {
+        if let Some(node) = self.tree.get_node_from_id(*node_id)
+        {
+          node.value().remove_attribute(name);
+          self.tree.touch(*node_id);
+        }
+      }
+    }
+    Ok(())
+  }
 }
 
 impl Default for Session
@@ -86,12 +583,55 @@ impl Default for Session
 #[cfg(test)]
 mod tests
 {
-  use super::Session;
+  use super::{Session, RepairOptions};
+  use crate::validation::{Rule, Check};
+  use crate::tag::{Query, tags};
+  use crate::computed::ComputedAttribute;
+  use crate::value::Value;
+  use crate::task_scheduler::{Task, TaskState};
   use crate::plugin_dummy;
-  use crate::tree::AttributePath;
+  use crate::tree::{Tree, TreeNodeId, AttributePath, NodeState};
+  use crate::node::Node;
+  use crate::refresh::{Refreshable, NodeChange};
 
+  use std::sync::{Arc, RwLock};
   use serde_json::json;
 
+  /// Directory like source which content can change.
+  struct LiveDirectory
+  {
+    files : Arc<RwLock<Vec<&'static str>>>,
+  }
+
+  impl Refreshable for LiveDirectory
+  {
+    fn refresh(&self, tree : &Tree, node_id : TreeNodeId) -> anyhow::Result<Vec<NodeChange>>
+    {
+      let files = self.files.read().unwrap();
+      let mut changes = Vec::new();
+
+      for child in tree.children_id_name(node_id)
+      {
+        if !files.contains(&child.name.as_str())
+        {
+          tree.remove(child.id)?;
+          changes.push(NodeChange::new(child.id, NodeState::Removed));
+        }
+      }
+
+      let names = tree.children_name(node_id);
+      for file in files.iter()
+      {
+        if !names.iter().any(|name| name == file)
+        {
+          let child_id = tree.add_child(node_id, Node::new(*file))?;
+          changes.push(NodeChange::new(child_id, NodeState::Added));
+        }
+      }
+      Ok(changes)
+    }
+  }
+
   #[test]
   fn schedule_dummy_plugin()
   {
@@ -106,6 +646,36 @@ mod tests
     session.task_scheduler.task(id).unwrap();
   }
  
+  #[test]
+  fn auto_parse_disk()
+  {
+    use crate::{plugin_partition, plugin_fat};
+    use crate::memoryvfile::MemoryVFileBuilder;
+
+    let mut session = Session::new();
+    session.plugins_db.register(Box::new(plugin_partition::Plugin::new()));
+    session.plugins_db.register(Box::new(plugin_fat::Plugin::new()));
+
+    let mut disk = vec![0; 128 * 512];
+    plugin_partition::tests::mbr_entry(&mut disk, 0, 0x01, 8, 64);
+    disk[8 * 512..72 * 512].copy_from_slice(&plugin_fat::tests::fat12_image());
+    let node = Node::new("disk");
+    node.value().add_attribute("data", Value::VFileBuilder(MemoryVFileBuilder::from_buffer(disk)), None);
+    let disk_id = session.tree.add_child(session.tree.root_id, node).unwrap();
+
+    let task_ids = session.auto_parse(disk_id).unwrap();
+    assert!(task_ids.len() == 1 && matches!(session.task_scheduler.task(task_ids[0]), Some(TaskState::Waiting(task) | TaskState::Launched(task) | TaskState::Finished(task, _)) if task.plugin_name == "partition"));
+    session.join();
+    assert!(session.auto_parse(disk_id).unwrap().is_empty());
+
+    //the content of the created nodes is parsed in turn
+    let partition_id = session.tree.get_node_id("/root/disk/partition_1").unwrap();
+    assert!(session.auto_parse(partition_id).unwrap().len() == 1);
+    session.join();
+    assert!(session.tree.get_node("/root/disk/partition_1/README.TXT").is_some());
+    assert!(session.auto_parse(session.tree.root_id).unwrap().is_empty());
+  }
+
   #[test]
   fn run_dummy()
   {
@@ -132,4 +702,117 @@ mod tests
     assert!(dynamic_attribute_path.get_node(&session.tree).unwrap().name() == "DummyDynamicValue");
     assert!(dynamic_attribute_path.get_value(&session.tree).unwrap().to_string() == "ABCDEFGH1234567890");
   }
+
+  #[test]
+  fn refresh_live_source()
+  {
+    let mut session = Session::new();
+    let events = session.changes.register();
+    let files = Arc::new(RwLock::new(vec!["a", "b"]));
+
+    let mount_id = session.tree.add_child(session.tree.root_id, Node::new("live")).unwrap();
+    assert!(session.refresh(mount_id).is_err());
+    session.tree.set_refreshable(mount_id, Arc::new(LiveDirectory{ files : files.clone() }));
+
+    let changes = session.refresh(mount_id).unwrap();
+    assert!(changes.len() == 2 && changes.iter().all(|change| change.state == NodeState::Added));
+
+    *files.write().unwrap() = vec!["b", "c"];
+    let changes = session.refresh(mount_id).unwrap();
+    assert!(changes.len() == 2);
+    assert!(session.tree.get_node("/root/live/a").is_none());
+    assert!(session.tree.get_node("/root/live/c").is_some());
+    assert!(session.refresh(mount_id).unwrap().is_empty());
+    assert!(events.events().len() == 4);
+  }
+
+  #[test]
+  fn validate_after_load()
+  {
+    let mut session = Session::new();
+    session.plugins_db.register(Box::new(plugin_dummy::Plugin::new()));
+
+    let removed_id = session.tree.add_child(session.tree.root_id, Node::new("removed")).unwrap();
+    let node = Node::new("links");
+    node.value().add_attribute("parent", Value::NodeId(session.tree.root_id), None);
+    node.value().add_attribute("link", Value::NodeId(removed_id), None);
+    let links_id = session.tree.add_child(session.tree.root_id, node).unwrap();
+    session.tree.remove(removed_id).unwrap();
+
+    let argument = |node_id| json!({"parent" : node_id, "file_name" : "test.txt", "offset" : 0}).to_string();
+    session.task_scheduler.restore(vec![
+      TaskState::Finished(Task{ id : 1, plugin_name : "dummy".into(), argument : argument(session.tree.root_id), summary : None, progress : None, timeout : None, priority : Default::default(), pool : None }, Ok("{}".into())),
+      TaskState::Launched(Task{ id : 2, plugin_name : "dummy".into(), argument : argument(session.tree.root_id), summary : None, progress : None, timeout : None, priority : Default::default(), pool : None }),
+      TaskState::Finished(Task{ id : 3, plugin_name : "unknown".into(), argument : argument(removed_id), summary : None, progress : None, timeout : None, priority : Default::default(), pool : None }, Ok("{}".into())),
+      TaskState::Waiting(Task{ id : 4, plugin_name : "unknown".into(), argument : argument(session.tree.root_id), summary : None, progress : None, timeout : None, priority : Default::default(), pool : None }),
+    ]);
+    //restored tasks are never run so they are not joined
+    session.join();
+
+    let report = session.validate_after_load();
+    assert!(report.orphan_tasks == vec![2, 4]);
+    assert!(report.missing_plugins == vec![(3, "unknown".to_string()), (4, "unknown".to_string())]);
+    assert!(report.unresolved_arguments == vec![(3, removed_id)]);
+    assert!(report.unresolved_attributes == vec![(links_id, "link".to_string())]);
+
+    //threads waiting on a restored task are woken up when it's repaired
+    std::thread::scope(|scope|
+    {
+      let waiting = scope.spawn(|| session.task_scheduler.wait(4, None));
+      session.repair(&report, &RepairOptions::all()).unwrap();
+      assert!(waiting.join().unwrap().is_err());
+    });
+    assert!(matches!(session.task_scheduler.task(2), Some(TaskState::Finished(_, Err(_)))));
+    assert!(matches!(session.task_scheduler.task(3), Some(TaskState::Finished(_, Ok(_)))));
+    assert!(matches!(session.task_scheduler.task(4), Some(TaskState::Finished(_, Err(_)))));
+    assert!(session.tree.get_node_from_id(links_id).unwrap().value().get_value("link").is_none());
+    assert!(session.validate_after_load().orphan_tasks.is_empty());
+  }
+
+  #[test]
+  fn live_validation()
+  {
+    let mut session = Session::new();
+    session.plugins_db.register(Box::new(plugin_dummy::Plugin::new()));
+    session.validator().add_rule(Rule::new("small_offset", "offset", Check::Max(Value::U64(0x100))).for_plugin("dummy"));
+    session.validator().set_live(true);
+
+    session.run("dummy", json!({"parent" : session.tree.root_id, "file_name" : "/home/user/test.txt", "offset" : 0}).to_string(), false).unwrap();
+
+    let violations = session.validator().violations();
+    assert!(violations.len() == 1 && violations[0].rule == "small_offset");
+    assert!(session.tree.get_node_from_id(violations[0].node_id).unwrap().value().get_value("warnings").is_some());
+    assert!(session.validate().violations.is_empty());
+  }
+
+  #[test]
+  fn live_tags()
+  {
+    let mut session = Session::new();
+    session.plugins_db.register(Box::new(plugin_dummy::Plugin::new()));
+    session.save_query("dummy", Query::new().with("offset", Check::Exists));
+    assert!(session.tag_by_query("dummy", "dummy").unwrap().is_empty());
+
+    session.run("dummy", json!({"parent" : session.tree.root_id, "file_name" : "/home/user/test.txt", "offset" : 0}).to_string(), false).unwrap();
+    assert!(tags(&session.tree, session.tree.get_node_id("/root/Dummy").unwrap()) == vec!["dummy"]);
+
+    session.clear();
+    assert!(session.tagger().rules().len() == 1 && session.tagger().query("dummy").is_some());
+  }
+
+  #[test]
+  fn live_computed()
+  {
+    let mut session = Session::new();
+    session.plugins_db.register(Box::new(plugin_dummy::Plugin::new()));
+    let computed = ComputedAttribute::parse("next_offset = offset + 0x200").unwrap().matching(Query::new().with("offset", Check::Exists));
+    assert!(session.add_computed(computed).unwrap() == 0);
+
+    session.run("dummy", json!({"parent" : session.tree.root_id, "file_name" : "/home/user/test.txt", "offset" : 0}).to_string(), false).unwrap();
+    let node = session.tree.get_node("/root/Dummy").unwrap();
+    assert!(serde_json::to_value(node.value()).unwrap()["next_offset"] == 0x1200);
+
+    session.clear();
+    assert!(session.computed().rules().len() == 1);
+  }
 }
@@ -0,0 +1,163 @@
+//! A [VFileBuilder] exposing a window of an other [VFileBuilder], used to expose partitions or records of a parent file.
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+use crate::vfile::{VFile, VFileBuilder, Extent};
+use crate::error::RustructError;
+
+use serde::{Serialize, Deserialize};
+
+/**
+ * Implement a [VFileBuilder] generating files containing `size` bytes of a parent [VFileBuilder] starting at `offset`.
+ */
+#[derive(Serialize, Deserialize)]
+pub struct SliceVFileBuilder
+{
+  parent : Arc<dyn VFileBuilder>,
+  offset : u64,
+  size : u64,
+}
+
+impl SliceVFileBuilder
+{
+  /// Return a builder for the window of `size` bytes of `parent` starting at `offset`.
+  /// Return an error if the window is not inside `parent`.
+  pub fn new(parent : Arc<dyn VFileBuilder>, offset : u64, size : u64) -> anyhow::Result<Arc<SliceVFileBuilder>>
+  {
+    match offset.checked_add(size)
+    {
+      Some(end) if end <= parent.size() => Ok(Arc::new(SliceVFileBuilder{ parent, offset, size })),
+      _ => Err(RustructError::InvalidArgument("SliceVFileBuilder".into(),
+                format!("window {:#x}+{:#x} is outside of the parent of size {:#x}", offset, size, parent.size())).into()),
+    }
+  }
+
+  /// Return the parent builder.
+  pub fn parent(&self) -> &Arc<dyn VFileBuilder>
+  {
+    &self.parent
+  }
+
+  /// Return the offset of the window in the parent.
+  pub fn offset(&self) -> u64
+  {
+    self.offset
+  }
+}
+
+#[typetag::serde]
+impl VFileBuilder for SliceVFileBuilder
+{
+  fn open(&self) -> anyhow::Result<Box<dyn VFile>>
+  {
+    let mut file = self.parent.open()?;
+    file.seek(SeekFrom::Start(self.offset))?;
+    Ok(Box::new(SliceVFile{ file, offset : self.offset, size : self.size, pos : 0 }))
+  }
+
+  fn size(&self) -> u64
+  {
+    self.size
+  }
+
+  /// Return the extents of the parent inside the window.
+  fn extents(&self) -> Vec<Extent>
+  {
+    let end = self.offset + self.size;
+    self.parent.extents().into_iter().filter(|extent| extent.range.start < end && extent.range.end > self.offset)
+        .map(|extent| Extent{ range : extent.range.start.max(self.offset) - self.offset..extent.range.end.min(end) - self.offset, hole : extent.hole })
+        .collect()
+  }
+}
+
+/**
+ * [VFile] reading a window of a parent [VFile], reads are clamped to the end of the window.
+ */
+pub struct SliceVFile
+{
+  file : Box<dyn VFile>,
+  offset : u64,
+  size : u64,
+  pos : u64,
+}
+
+impl Read for SliceVFile
+{
+  fn read(&mut self, buf : &mut [u8]) -> io::Result<usize>
+  {
+    if self.pos >= self.size
+    {
+      return Ok(0)
+    }
+
+    let size = buf.len().min((self.size - self.pos).min(usize::MAX as u64) as usize);
+    let readed = self.file.read(&mut buf[..size])?;
+    self.pos += readed as u64;
+    Ok(readed)
+  }
+}
+
+impl Seek for SliceVFile
+{
+  fn seek(&mut self, pos : SeekFrom) -> io::Result<u64>
+  {
+    let pos = match pos
+    {
+      SeekFrom::Start(pos) => Some(pos),
+      SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+      SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+    };
+
+    match pos
+    {
+      Some(pos) =>
+      {
+        //seeking past the end is allowed, next reads will return 0
+        self.file.seek(SeekFrom::Start(self.offset + pos.min(self.size)))?;
+        self.pos = pos;
+        Ok(pos)
+      },
+      None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::SliceVFileBuilder;
+  use crate::vfile::VFileBuilder;
+  use crate::memoryvfile::MemoryVFileBuilder;
+
+  use std::io::{Read, Seek, SeekFrom};
+  use std::sync::Arc;
+
+  #[test]
+  fn read_slice()
+  {
+    let parent : Arc<dyn VFileBuilder> = MemoryVFileBuilder::from_buffer((0..100u8).collect());
+    assert!(SliceVFileBuilder::new(parent.clone(), 90, 20).is_err());
+
+    let builder = SliceVFileBuilder::new(parent.clone(), 10, 20).unwrap();
+    assert!(builder.size() == 20);
+    let mut file = builder.open().unwrap();
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).unwrap();
+    assert!(content == (10..30u8).collect::<Vec<u8>>());
+
+    assert!(file.seek(SeekFrom::End(-5)).unwrap() == 15);
+    let mut buffer = [0; 10];
+    assert!(file.read(&mut buffer).unwrap() == 5);
+    assert!(buffer[0..5] == [25, 26, 27, 28, 29]);
+    assert!(file.seek(SeekFrom::Current(10)).unwrap() == 30);
+    assert!(file.read(&mut buffer).unwrap() == 0);
+    assert!(file.seek(SeekFrom::Current(-31)).is_err());
+
+    //slices of slices
+    let inner = SliceVFileBuilder::new(builder, 5, 2).unwrap();
+    let mut content = Vec::new();
+    inner.open().unwrap().read_to_end(&mut content).unwrap();
+    assert!(content == vec![15, 16]);
+  }
+}
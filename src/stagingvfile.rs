@@ -0,0 +1,534 @@
+//! Staging of the derived artifacts created by plugins (normalized logs, converted images) in a [BlobStore].
+//!
+//! Plugins get a [StagingVFileWriter] from [PluginEnvironment::stage](crate::plugin::PluginEnvironment::stage),
+//! write the artifact with the standard [Write] and [Seek] API, then [finish](VFileWriter::finish) it
+//! and add the returned [StagingVFileBuilder] as the data of a node. Unlike temporary files,
+//! staged blobs are kept in the store directory when the builders are dropped, so they can be serialized with the tree.
+//!
+//! A store created [with deduplication](BlobStore::with_dedup) cut the finished blobs into content-defined chunks
+//! stored once in its `chunks` directory (see [dedup](crate::dedup)). The chunks are reference counted by the stores of the process
+//! and removed with the last blob using them, [BlobStore::gc] recount the references and remove the chunks left by failed writes.
+
+use std::fs::{self, File};
+use std::io::{self, Write, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::collections::HashMap;
+
+use crate::vfile::{VFile, VFileBuilder, VFileWriter};
+use crate::dedup::{self, Chunk, Manifest, ChunkedVFile, DedupStats, GcReport};
+use crate::error::RustructError;
+
+use serde::{Serialize, Deserialize};
+use serde::de::{Deserializer};
+use uuid::Uuid;
+
+/**
+ * Directory holding the staged blobs, each blob is a file named by its [Uuid].
+ * The directory is created when the first blob is written.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobStore
+{
+  dir : PathBuf,
+  #[serde(default)]
+  dedup : bool,
+}
+
+/// Extension of the manifest of the deduplicated blobs.
+const MANIFEST_EXTENSION : &str = "chunks";
+
+/// Number of blobs referencing each chunk, by chunks directory.
+fn chunk_refs() -> &'static Mutex<HashMap<PathBuf, HashMap<String, usize>>>
+{
+  static REFS : OnceLock<Mutex<HashMap<PathBuf, HashMap<String, usize>>>> = OnceLock::new();
+  REFS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+impl Default for BlobStore
+{
+  /// Return a store in the `tap-staging` directory of the system temporary directory.
+  fn default() -> Self
+  {
+    BlobStore::new(std::env::temp_dir().join("tap-staging"))
+  }
+}
+
+impl BlobStore
+{
+  /// Return a store keeping the blobs in `dir`.
+  pub fn new<P : Into<PathBuf>>(dir : P) -> Self
+  {
+    BlobStore{ dir : dir.into(), dedup : false }
+  }
+
+  /// Return this store deduplicating the blobs finished after this call if `dedup` is true.
+  pub fn with_dedup(mut self, dedup : bool) -> Self
+  {
+    self.dedup = dedup;
+    self
+  }
+
+  /// Return true if the blobs are deduplicated.
+  pub fn is_dedup(&self) -> bool
+  {
+    self.dedup
+  }
+
+  /// Return the directory of the store.
+  pub fn dir(&self) -> &Path
+  {
+    &self.dir
+  }
+
+  /// Return a writer to a new blob.
+  pub fn writer(&self) -> anyhow::Result<StagingVFileWriter>
+  {
+    fs::create_dir_all(&self.dir).map_err(|err| RustructError::OpenFile(format!("{} : {}", self.dir.display(), err)))?;
+    let id = Uuid::new_v4();
+    let partial = self.dir.join(format!("{}.part", id));
+    let file = File::create(&partial).map_err(|err| RustructError::OpenFile(format!("{} : {}", partial.display(), err)))?;
+    Ok(StagingVFileWriter{ id, path : self.dir.join(id.to_string()), partial, file : Some(file), store : self.clone() })
+  }
+
+  /// Return a builder reading the finished blob `id`.
+  pub fn get(&self, id : Uuid) -> anyhow::Result<Arc<StagingVFileBuilder>>
+  {
+    let manifest = self.manifest_path(id);
+    match manifest.exists()
+    {
+      true => StagingVFileBuilder::new(id, manifest),
+      false => StagingVFileBuilder::new(id, self.dir.join(id.to_string())),
+    }
+  }
+
+  /// Return the id of the finished blobs of the store.
+  pub fn ids(&self) -> Vec<Uuid>
+  {
+    match fs::read_dir(&self.dir)
+    {
+      Ok(entries) => entries.flatten().filter_map(|entry| entry.file_name().to_str().and_then(|name|
+      {
+        let name = name.strip_suffix(&format!(".{}", MANIFEST_EXTENSION)).unwrap_or(name);
+        Uuid::parse_str(name).ok()
+      })).collect(),
+      Err(_) => Vec::new(),
+    }
+  }
+
+  /// Remove the blob `id`, builders already opened are not readable anymore.
+  /// The chunks of a deduplicated blob are removed if no other blob use them.
+  pub fn remove(&self, id : Uuid) -> bool
+  {
+    let path = self.manifest_path(id);
+    if !path.exists()
+    {
+      return fs::remove_file(self.dir.join(id.to_string())).is_ok()
+    }
+
+    self.with_refs(|refs|
+    {
+      let manifest = match Manifest::load(&path)
+      {
+        Ok(manifest) => manifest,
+        Err(_) => return false,
+      };
+      if fs::remove_file(&path).is_err()
+      {
+        return false
+      }
+      for chunk in manifest.chunks
+      {
+        if let Some(count) = refs.get_mut(&chunk.hash)
+        {
+          *count -= 1;
+          if *count == 0
+          {
+            refs.remove(&chunk.hash);
+            let _ = fs::remove_file(self.chunks_dir().join(&chunk.hash));
+          }
+        }
+      }
+      true
+    })
+  }
+
+  /// Return the directory of the chunks of the deduplicated blobs.
+  pub fn chunks_dir(&self) -> PathBuf
+  {
+    self.dir.join("chunks")
+  }
+
+  fn manifest_path(&self, id : Uuid) -> PathBuf
+  {
+    self.dir.join(format!("{}.{}", id, MANIFEST_EXTENSION))
+  }
+
+  /// Return the manifest of the deduplicated blobs.
+  fn manifests(&self) -> Vec<Manifest>
+  {
+    match fs::read_dir(&self.dir)
+    {
+      Ok(entries) => entries.flatten().map(|entry| entry.path())
+                            .filter(|path| path.extension().is_some_and(|extension| extension == MANIFEST_EXTENSION))
+                            .filter_map(|path| Manifest::load(&path).ok()).collect(),
+      Err(_) => Vec::new(),
+    }
+  }
+
+  /// Count the blobs using each chunk.
+  fn count_refs(&self) -> HashMap<String, usize>
+  {
+    let mut refs = HashMap::new();
+    for chunk in self.manifests().into_iter().flat_map(|manifest| manifest.chunks)
+    {
+      *refs.entry(chunk.hash).or_insert(0) += 1;
+    }
+    refs
+  }
+
+  /// Call `callback` with the references of the chunks of the store, counted on first use.
+  fn with_refs<T, F>(&self, callback : F) -> T
+    where F : FnOnce(&mut HashMap<String, usize>) -> T
+  {
+    let mut stores = chunk_refs().lock().unwrap();
+    let refs = stores.entry(self.chunks_dir()).or_insert_with(|| self.count_refs());
+    callback(refs)
+  }
+
+  /// Cut the finished blob `id` at `path` into chunks, store the new ones and replace the blob by its manifest.
+  fn deduplicate(&self, id : Uuid, path : &Path) -> anyhow::Result<Arc<StagingVFileBuilder>>
+  {
+    let chunks_dir = self.chunks_dir();
+    fs::create_dir_all(&chunks_dir).map_err(|err| RustructError::OpenFile(format!("{} : {}", chunks_dir.display(), err)))?;
+    let mut file = File::open(path)?;
+    let mut manifest = Manifest::default();
+
+    self.with_refs(|refs| -> anyhow::Result<()>
+    {
+      dedup::split(&mut file, |data|
+      {
+        let chunk = Chunk::new(data);
+        let chunk_path = chunks_dir.join(&chunk.hash);
+        if !chunk_path.exists()
+        {
+          let partial = chunks_dir.join(format!("{}.part", chunk.hash));
+          fs::write(&partial, data)?;
+          fs::rename(&partial, &chunk_path)?;
+        }
+        *refs.entry(chunk.hash.clone()).or_insert(0) += 1;
+        manifest.push(chunk);
+        Ok(())
+      })?;
+      manifest.save(&self.manifest_path(id))
+    })?;
+
+    fs::remove_file(path)?;
+    StagingVFileBuilder::new(id, self.manifest_path(id))
+  }
+
+  /// Recount the references of the chunks and remove the chunks not used by any blob, like the ones left by a failed write.
+  pub fn gc(&self) -> GcReport
+  {
+    self.with_refs(|refs|
+    {
+      *refs = self.count_refs();
+      let mut report = GcReport::default();
+      if let Ok(entries) = fs::read_dir(self.chunks_dir())
+      {
+        for entry in entries.flatten()
+        {
+          let name = entry.file_name().to_string_lossy().to_string();
+          if !refs.contains_key(&name)
+          {
+            let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            if fs::remove_file(entry.path()).is_ok()
+            {
+              report.chunks += 1;
+              report.bytes += size;
+            }
+          }
+        }
+      }
+      report
+    })
+  }
+
+  /// Return the [DedupStats] of the store.
+  pub fn dedup_stats(&self) -> DedupStats
+  {
+    let manifests = self.manifests();
+    let mut stats = DedupStats{ blobs : manifests.len(), logical_bytes : manifests.iter().map(|manifest| manifest.size).sum(), ..Default::default() };
+    if let Ok(entries) = fs::read_dir(self.chunks_dir())
+    {
+      for entry in entries.flatten()
+      {
+        stats.chunks += 1;
+        stats.stored_bytes += entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+      }
+    }
+    stats
+  }
+}
+
+/**
+ * [VFileWriter] writing a new blob of a [BlobStore], finished as a [StagingVFileBuilder].
+ * The data is written to a `.part` file renamed when finished, and removed if the writer is dropped before.
+ */
+pub struct StagingVFileWriter
+{
+  id : Uuid,
+  path : PathBuf,
+  partial : PathBuf,
+  file : Option<File>,
+  store : BlobStore,
+}
+
+impl StagingVFileWriter
+{
+  /// Return the id of the blob.
+  pub fn id(&self) -> Uuid
+  {
+    self.id
+  }
+
+  /// Flush the written data and return a [StagingVFileBuilder] reading it.
+  pub fn into_builder(mut self) -> anyhow::Result<Arc<StagingVFileBuilder>>
+  {
+    if let Some(mut file) = self.file.take()
+    {
+      file.flush()?;
+      file.sync_all()?;
+    }
+    if self.store.dedup
+    {
+      let result = self.store.deduplicate(self.id, &self.partial);
+      let _ = fs::remove_file(&self.partial);
+      return result
+    }
+    fs::rename(&self.partial, &self.path)?;
+    StagingVFileBuilder::new(self.id, self.path.clone())
+  }
+
+  fn file(&mut self) -> &mut File
+  {
+    self.file.as_mut().expect("file is only taken when finished")
+  }
+}
+
+impl Drop for StagingVFileWriter
+{
+  fn drop(&mut self)
+  {
+    if self.file.is_some()
+    {
+      let _ = fs::remove_file(&self.partial);
+    }
+  }
+}
+
+impl Write for StagingVFileWriter
+{
+  fn write(&mut self, buf : &[u8]) -> io::Result<usize>
+  {
+    self.file().write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()>
+  {
+    self.file().flush()
+  }
+}
+
+impl Seek for StagingVFileWriter
+{
+  fn seek(&mut self, pos : SeekFrom) -> io::Result<u64>
+  {
+    self.file().seek(pos)
+  }
+}
+
+impl VFileWriter for StagingVFileWriter
+{
+  fn finish(self : Box<Self>) -> anyhow::Result<Arc<dyn VFileBuilder>>
+  {
+    Ok(self.into_builder()?)
+  }
+}
+
+/**
+ * Implement a [VFileBuilder] reading a finished blob of a [BlobStore].
+ * It's serialized as its id, path and size, the blob must still exist when deserialized.
+ * The path of a deduplicated blob is the path of its [Manifest].
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct StagingVFileBuilder
+{
+  id : Uuid,
+  path : PathBuf,
+  size : u64,
+  #[serde(skip)]
+  manifest : Option<Arc<Manifest>>,
+}
+
+impl StagingVFileBuilder
+{
+  fn new(id : Uuid, path : PathBuf) -> anyhow::Result<Arc<StagingVFileBuilder>>
+  {
+    if path.extension().is_some_and(|extension| extension == MANIFEST_EXTENSION)
+    {
+      let manifest = Manifest::load(&path)?;
+      return Ok(Arc::new(StagingVFileBuilder{ id, path, size : manifest.size, manifest : Some(Arc::new(manifest)) }))
+    }
+    let size = fs::metadata(&path).map_err(|err| RustructError::OpenFile(format!("{} : {}", path.display(), err)))?.len();
+    Ok(Arc::new(StagingVFileBuilder{ id, path, size, manifest : None }))
+  }
+
+  /// Return the id of the blob.
+  pub fn id(&self) -> Uuid
+  {
+    self.id
+  }
+
+  /// Return the path of the blob.
+  pub fn path(&self) -> &Path
+  {
+    &self.path
+  }
+}
+
+#[typetag::serde]
+impl VFileBuilder for StagingVFileBuilder
+{
+  fn open(&self) -> anyhow::Result<Box<dyn VFile>>
+  {
+    if let Some(manifest) = &self.manifest
+    {
+      let dir = self.path.parent().map(|dir| dir.join("chunks")).unwrap_or_default();
+      return Ok(Box::new(ChunkedVFile::new(dir, manifest)))
+    }
+    match File::open(&self.path)
+    {
+      Ok(file) => Ok(Box::new(file)),
+      Err(err) => Err(RustructError::OpenFile(format!("{} : {}", self.path.display(), err)).into()),
+    }
+  }
+
+  fn size(&self) -> u64
+  {
+    self.size
+  }
+}
+
+impl<'de> Deserialize<'de> for StagingVFileBuilder
+{
+  fn deserialize<D>(deserializer: D) -> std::result::Result<StagingVFileBuilder, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    #[derive(Deserialize)]
+    struct Fields
+    {
+      id : Uuid,
+      path : PathBuf,
+    }
+
+    let fields = Fields::deserialize(deserializer)?;
+    let builder = StagingVFileBuilder::new(fields.id, fields.path).map_err(serde::de::Error::custom)?;
+    Ok(Arc::try_unwrap(builder).unwrap_or_else(|_| unreachable!()))
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::BlobStore;
+  use crate::vfile::{VFileBuilder, VFileWriter};
+
+  use std::io::{Read, Write, Seek, SeekFrom};
+  use std::sync::Arc;
+
+  #[test]
+  fn stage_blobs()
+  {
+    let store = BlobStore::new(std::env::temp_dir().join(format!("tap-staging-test-{}", uuid::Uuid::new_v4())));
+    assert!(store.ids().is_empty());
+
+    let mut writer = store.writer().unwrap();
+    let id = writer.id();
+    writer.write_all(b"normalized log").unwrap();
+    writer.seek(SeekFrom::Start(0)).unwrap();
+    writer.write_all(b"N").unwrap();
+    let builder : Arc<dyn VFileBuilder> = Box::new(writer).finish().unwrap();
+    assert!(builder.size() == 14 && store.ids() == vec![id]);
+
+    let json = serde_json::to_string(&builder).unwrap();
+    drop(builder);
+    let builder : Box<dyn VFileBuilder> = serde_json::from_str(&json).unwrap();
+    let mut content = String::new();
+    builder.open().unwrap().read_to_string(&mut content).unwrap();
+    assert!(content == "Normalized log");
+    assert!(store.get(id).unwrap().size() == 14);
+
+    let mut writer = store.writer().unwrap();
+    writer.write_all(b"dropped").unwrap();
+    drop(writer);
+    assert!(store.ids() == vec![id]);
+    assert!(std::fs::read_dir(store.dir()).unwrap().count() == 1);
+
+    assert!(store.remove(id) && store.get(id).is_err());
+    assert!(serde_json::from_str::<Box<dyn VFileBuilder>>(&json).is_err());
+    std::fs::remove_dir(store.dir()).unwrap();
+  }
+
+  #[test]
+  fn dedup_blobs()
+  {
+    let store = BlobStore::new(std::env::temp_dir().join(format!("tap-staging-test-{}", uuid::Uuid::new_v4()))).with_dedup(true);
+    let mut seed : u32 = 7;
+    let content : Vec<u8> = (0..200000).map(|_| { seed = seed.wrapping_mul(1103515245).wrapping_add(12345); (seed >> 16) as u8 }).collect();
+    let mut carved = content[50000..150000].to_vec();
+    carved.extend_from_slice(b"trailer");
+
+    let mut ids = Vec::new();
+    for data in [&content, &carved]
+    {
+      let mut writer = store.writer().unwrap();
+      writer.write_all(data).unwrap();
+      ids.push(writer.id());
+      let builder : Arc<dyn VFileBuilder> = Box::new(writer).finish().unwrap();
+      let builder : Box<dyn VFileBuilder> = serde_json::from_str(&serde_json::to_string(&builder).unwrap()).unwrap();
+      let mut read = Vec::new();
+      builder.open().unwrap().read_to_end(&mut read).unwrap();
+      assert!(builder.size() == data.len() as u64 && read == *data);
+    }
+
+    let mut ids_found = store.ids();
+    ids_found.sort();
+    let mut ids_sorted = ids.clone();
+    ids_sorted.sort();
+    assert!(ids_found == ids_sorted);
+    let stats = store.dedup_stats();
+    assert!(stats.blobs == 2 && stats.logical_bytes == 300007);
+    assert!(stats.stored_bytes < 230000 && stats.saved_bytes() > 70000);
+
+    let mut file = store.get(ids[0]).unwrap().open().unwrap();
+    let mut buffer = [0; 10];
+    file.seek(SeekFrom::Start(123456)).unwrap();
+    file.read_exact(&mut buffer).unwrap();
+    assert!(buffer[..] == content[123456..123466]);
+
+    let chunks = stats.chunks;
+    std::fs::write(store.chunks_dir().join("orphan"), b"left by a failed write").unwrap();
+    assert!(store.gc().chunks == 1 && store.dedup_stats().chunks == chunks);
+
+    assert!(store.remove(ids[0]));
+    let stats = store.dedup_stats();
+    assert!(stats.blobs == 1 && stats.chunks < chunks && stats.stored_bytes >= 100007);
+    let mut read = Vec::new();
+    store.get(ids[1]).unwrap().open().unwrap().read_to_end(&mut read).unwrap();
+    assert!(read == carved);
+    assert!(store.remove(ids[1]) && store.dedup_stats() == Default::default());
+    std::fs::remove_dir_all(store.dir()).unwrap();
+  }
+}
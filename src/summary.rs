@@ -0,0 +1,140 @@
+//! [RunSummary] describe what a plugin added to the [Tree] during a run,
+//! it let users audit what each task contributed without diffing trees.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::tree::{Tree, TreeNodeId};
+use crate::node::Node;
+use crate::value::Value;
+use crate::validation::WARNINGS_ATTRIBUTE;
+
+use serde::{Serialize, Deserialize};
+
+/**
+ * Summary of the nodes created by a plugin during a run.
+ * Only nodes added through the plugin environment [tree](Tree) are accounted,
+ * attributes added later to nodes created by other tasks are not.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunSummary
+{
+  /// Number of nodes created.
+  pub nodes_created : u64,
+  /// Number of attributes added per attribute name.
+  pub attributes : BTreeMap<String, u64>,
+  /// Total size of the data exposed by the [VFileBuilder](crate::vfile::VFileBuilder) attributes of the created nodes.
+  pub bytes_exposed : u64,
+  /// Approximate memory used by the created nodes and their attributes, see [Value::deep_size].
+  #[serde(default)]
+  pub memory : u64,
+  /// Number of warnings in the [WARNINGS_ATTRIBUTE] of the created nodes.
+  #[serde(default)]
+  pub warnings : u64,
+}
+
+impl RunSummary
+{
+  /// Create a summary from the `nodes` id created in `tree`.
+  pub fn new(tree : &Tree, nodes : &[TreeNodeId]) -> Self
+  {
+    let mut summary = RunSummary::default();
+
+    for node_id in nodes
+    {
+      //node can have been removed by the plugin after creation
+      let node = match tree.get_node_from_id(*node_id)
+      {
+        Some(node) => node,
+        None => continue,
+      };
+      summary.nodes_created += 1;
+      summary.memory += node.deep_size() as u64;
+
+      for attribute in node.value().attributes().iter()
+      {
+        *summary.attributes.entry(attribute.name().to_string()).or_insert(0) += 1;
+        if let Value::VFileBuilder(builder) = attribute.value()
+        {
+          summary.bytes_exposed += builder.size();
+        }
+      }
+    }
+    summary.count_warnings(tree, nodes);
+    summary
+  }
+
+  /// Count the warnings of the `nodes`, must be called again if they are validated after the summary was created.
+  pub fn count_warnings(&mut self, tree : &Tree, nodes : &[TreeNodeId])
+  {
+    self.warnings = nodes.iter().filter_map(|node_id| tree.get_node_from_id(*node_id))
+      .map(|node| match node.value().get_value(WARNINGS_ATTRIBUTE)
+      {
+        Some(Value::Seq(warnings)) => warnings.len() as u64,
+        Some(_) => 1,
+        None => 0,
+      })
+      .sum();
+  }
+
+  /// Return the total number of attributes added.
+  pub fn attributes_count(&self) -> u64
+  {
+    self.attributes.values().sum()
+  }
+
+  /// Return a new [Node] named `name` containing the summary as attributes.
+  pub fn to_node(&self, name : String) -> Node
+  {
+    let node = Node::new(name);
+    let attributes : HashMap<String, Value> = self.attributes.iter().map(|(name, count)| (name.clone(), Value::U64(*count))).collect();
+
+    node.value().add_attributes(vec![
+      ("nodes_created", Value::U64(self.nodes_created), None),
+      ("attributes_count", Value::U64(self.attributes_count()), None),
+      ("attributes", Value::Map(attributes), None),
+      ("bytes_exposed", Value::U64(self.bytes_exposed), None),
+      ("memory", Value::U64(self.memory), None),
+      ("warnings", Value::U64(self.warnings), None),
+    ]);
+    node
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::RunSummary;
+  use crate::tree::Tree;
+  use crate::node::Node;
+  use crate::value::Value;
+
+  #[test]
+  fn summary_of_recorded_nodes()
+  {
+    let tree = Tree::new();
+    tree.add_child(tree.root_id, Node::new("not_recorded")).unwrap();
+
+    let (recording_tree, recorder) = tree.recorder();
+    let node = Node::new("first");
+    node.value().add_attribute("size", Value::U64(10), None);
+    let first_id = recording_tree.add_child(recording_tree.root_id, node).unwrap();
+
+    let node = Node::new("second");
+    node.value().add_attribute("size", Value::U64(20), None);
+    node.value().add_attribute("name", Value::from("second"), None);
+    node.value().add_attribute("warnings", Value::Seq(vec![Value::from("bad size"), Value::from("bad name")]), None);
+    recording_tree.clone().add_child(first_id, node).unwrap();
+
+    let summary = RunSummary::new(&tree, &recorder.nodes());
+    assert!(summary.nodes_created == 2);
+    assert!(summary.attributes["size"] == 2);
+    assert!(summary.attributes["name"] == 1);
+    assert!(summary.attributes_count() == 4);
+    assert!(summary.bytes_exposed == 0);
+    assert!(summary.memory > 0);
+    assert!(summary.warnings == 2);
+
+    let node = summary.to_node("report".into());
+    assert!(node.value().get_value("nodes_created").unwrap().as_u64() == 2);
+  }
+}
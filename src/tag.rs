@@ -0,0 +1,267 @@
+//! [Tagger] tag the nodes matching saved [queries](Query), so triage rules can be replayed on other cases.
+//! Tag rules are live : nodes created by the tasks launched after a rule was added are tagged when the task finish.
+//! Tags are added to the [TAGS_ATTRIBUTE] of the nodes and the tagger keep which queries produced each tag.
+
+use std::sync::RwLock;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::tree::{Tree, TreeNodeId};
+use crate::value::Value;
+use crate::validation::Check;
+use crate::error::RustructError;
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+
+/// Name of the attribute listing the tags of a node.
+pub const TAGS_ATTRIBUTE : &str = "tags";
+
+/// Select nodes by path and attribute values, all the conditions must be true.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Query
+{
+  /// Nodes which path start with this prefix.
+  #[serde(default)]
+  pub path : Option<String>,
+  /// Path of an attribute value, as passed to [Value::get_path], and the [Check] it must pass.
+  /// Nodes without the attribute don't match.
+  #[serde(default)]
+  pub conditions : Vec<(String, Check)>,
+}
+
+impl Query
+{
+  /// Return a new [Query] matching all nodes.
+  pub fn new() -> Self
+  {
+    Query::default()
+  }
+
+  /// Match only the nodes under `path`.
+  pub fn under<S : Into<String>>(mut self, path : S) -> Self
+  {
+    self.path = Some(path.into());
+    self
+  }
+
+  /// Match only the nodes which `attribute` pass `check`.
+  pub fn with<S : Into<String>>(mut self, attribute : S, check : Check) -> Self
+  {
+    self.conditions.push((attribute.into(), check));
+    self
+  }
+
+  /// Return true if node `node_id` of `tree` match the query.
+  pub fn matches(&self, tree : &Tree, node_id : TreeNodeId) -> bool
+  {
+    let node = match tree.get_node_from_id(node_id)
+    {
+      Some(node) => node,
+      None => return false,
+    };
+
+    if let Some(prefix) = &self.path
+    {
+      if !tree.node_path(node_id).unwrap_or_default().starts_with(prefix.as_str())
+      {
+        return false
+      }
+    }
+
+    let attributes = Value::Attributes(node.value());
+    self.conditions.iter().all(|(attribute, check)| check.matches(attributes.get_path(attribute).as_ref()))
+  }
+}
+
+/// Tag applied to the nodes matching a saved query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagRule
+{
+  /// Name of the saved query.
+  pub query : String,
+  /// Tag added to the matching nodes.
+  pub tag : String,
+}
+
+/**
+ * Hold the saved [queries](Query), the [tag rules](TagRule) and the queries that produced each tag.
+ */
+#[derive(Default)]
+pub struct Tagger
+{
+  queries : RwLock<BTreeMap<String, Query>>,
+  rules : RwLock<Vec<TagRule>>,
+  sources : RwLock<BTreeMap<String, BTreeSet<String>>>,
+}
+
+impl Tagger
+{
+  /// Return a new [Tagger] without queries.
+  pub fn new() -> Self
+  {
+    Tagger::default()
+  }
+
+  /// Save `query` as `name`, replacing the query with the same name.
+  pub fn save_query<S : Into<String>>(&self, name : S, query : Query)
+  {
+    self.queries.write().unwrap().insert(name.into(), query);
+  }
+
+  /// Return the saved query `name`.
+  pub fn query(&self, name : &str) -> Option<Query>
+  {
+    self.queries.read().unwrap().get(name).cloned()
+  }
+
+  /// Return the saved queries and their name.
+  pub fn queries(&self) -> BTreeMap<String, Query>
+  {
+    self.queries.read().unwrap().clone()
+  }
+
+  /// Return the tag rules.
+  pub fn rules(&self) -> Vec<TagRule>
+  {
+    self.rules.read().unwrap().clone()
+  }
+
+  /// Stop tagging new nodes matching `query` with `tag`, tags already added are kept.
+  pub fn remove_rule(&self, query : &str, tag : &str) -> bool
+  {
+    let mut rules = self.rules.write().unwrap();
+    let count = rules.len();
+    rules.retain(|rule| rule.query != query || rule.tag != tag);
+    rules.len() != count
+  }
+
+  /// Return the name of the queries that produced `tag`.
+  pub fn tag_sources(&self, tag : &str) -> Vec<String>
+  {
+    self.sources.read().unwrap().get(tag).map(|queries| queries.iter().cloned().collect()).unwrap_or_default()
+  }
+
+  /// Tag with `tag` all the nodes of `tree` matching saved query `query`, and the nodes created later by the tasks.
+  /// Return the id of the nodes tagged.
+  pub fn tag_by_query(&self, tree : &Tree, query : &str, tag : &str) -> Result<Vec<TreeNodeId>>
+  {
+    let saved = self.query(query).ok_or_else(|| RustructError::QueryNotFound(query.to_string()))?;
+
+    let rule = TagRule{ query : query.to_string(), tag : tag.to_string() };
+    {
+      let mut rules = self.rules.write().unwrap();
+      if !rules.contains(&rule)
+      {
+        rules.push(rule);
+      }
+    }
+
+    let nodes : Vec<TreeNodeId> = tree.root_id.descendants(&tree.arena()).collect();
+    Ok(self.apply(tree, &nodes, query, &saved, tag))
+  }
+
+  /// Apply the tag rules to `nodes` of `tree`, it's called by the workers on the nodes created by a task.
+  pub fn tag_nodes(&self, tree : &Tree, nodes : &[TreeNodeId])
+  {
+    for rule in self.rules()
+    {
+      if let Some(query) = self.query(&rule.query)
+      {
+        self.apply(tree, nodes, &rule.query, &query, &rule.tag);
+      }
+    }
+  }
+
+  /// Tag the `nodes` matching `query` and return their id.
+  fn apply(&self, tree : &Tree, nodes : &[TreeNodeId], name : &str, query : &Query, tag : &str) -> Vec<TreeNodeId>
+  {
+    let matches : Vec<TreeNodeId> = nodes.iter().filter(|node_id| query.matches(tree, **node_id)).cloned().collect();
+
+    for node_id in matches.iter()
+    {
+      add_tag(tree, *node_id, tag);
+    }
+    if !matches.is_empty()
+    {
+      self.sources.write().unwrap().entry(tag.to_string()).or_default().insert(name.to_string());
+    }
+    matches
+  }
+}
+
+/// Add `tag` to the [TAGS_ATTRIBUTE] of node `node_id` if not already present.
+fn add_tag(tree : &Tree, node_id : TreeNodeId, tag : &str)
+{
+  let node = match tree.get_node_from_id(node_id)
+  {
+    Some(node) => node,
+    None => return,
+  };
+
+  let mut tags = match node.value().get_value(TAGS_ATTRIBUTE)
+  {
+    Some(Value::Seq(tags)) => tags,
+    _ => Vec::new(),
+  };
+  let tag = Value::from(tag.to_string());
+  if tags.contains(&tag)
+  {
+    return
+  }
+
+  tags.push(tag);
+  node.value().remove_attribute(TAGS_ATTRIBUTE);
+  node.value().add_attribute(TAGS_ATTRIBUTE, Value::Seq(tags), None);
+  tree.touch(node_id);
+}
+
+/// Return the tags of node `node_id`.
+pub fn tags(tree : &Tree, node_id : TreeNodeId) -> Vec<String>
+{
+  match tree.get_node_from_id(node_id).and_then(|node| node.value().get_value(TAGS_ATTRIBUTE))
+  {
+    Some(Value::Seq(tags)) => tags.iter().map(|tag| tag.as_string()).collect(),
+    _ => Vec::new(),
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{Tagger, Query, tags};
+  use crate::tree::Tree;
+  use crate::node::Node;
+  use crate::value::Value;
+  use crate::validation::Check;
+
+  #[test]
+  fn tag_by_query()
+  {
+    let tree = Tree::new();
+    let node = Node::new("big.exe");
+    node.value().add_attribute("size", Value::U64(4096), None);
+    let big_id = tree.add_child(tree.root_id, node).unwrap();
+    let node = Node::new("small.exe");
+    node.value().add_attribute("size", Value::U64(10), None);
+    let small_id = tree.add_child(tree.root_id, node).unwrap();
+
+    let tagger = Tagger::new();
+    assert!(tagger.tag_by_query(&tree, "big", "suspicious").is_err());
+
+    tagger.save_query("big", Query::new().under("/root").with("size", Check::Min(Value::U64(1024))));
+    tagger.save_query("named", Query::new().with("size", Check::Exists));
+    assert!(tagger.tag_by_query(&tree, "big", "suspicious").unwrap() == vec![big_id]);
+    assert!(tagger.tag_by_query(&tree, "named", "suspicious").unwrap().len() == 2);
+    assert!(tags(&tree, big_id) == vec!["suspicious"]);
+    assert!(tags(&tree, small_id) == vec!["suspicious"]);
+    assert!(tagger.tag_sources("suspicious") == vec!["big", "named"]);
+
+    //new nodes matching the rules are tagged
+    let node = Node::new("other.exe");
+    node.value().add_attribute("size", Value::U64(2048), None);
+    let other_id = tree.add_child(tree.root_id, node).unwrap();
+    tagger.remove_rule("named", "suspicious");
+    tagger.tag_nodes(&tree, &[other_id]);
+    assert!(tags(&tree, other_id) == vec!["suspicious"]);
+  }
+}
@@ -0,0 +1,285 @@
+//! Capture of the log of each task : the [TaskLogger] of a task, passed in its [PluginEnvironment](crate::plugin::PluginEnvironment),
+//! keep the records logged by its plugin with their level and time, returned by [TaskScheduler::task_logs](crate::task_scheduler::TaskScheduler::task_logs).
+//!
+//! Records logged with the `log` macros are captured too when the global logger is [installed](install) :
+//! it keeps the records logged by the worker threads while they run a task and forward all records to the application logger.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+use crate::task_scheduler::TaskId;
+
+use chrono::{DateTime, Utc};
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use serde::{Serialize, Deserialize};
+
+/// Maximum number of records kept for a task, the next records are counted as dropped.
+pub const MAX_RECORDS : usize = 10_000;
+/// Maximum number of tasks which log is kept, the log of the oldest launched task is dropped first.
+pub const MAX_TASK_LOGS : usize = 1024;
+
+thread_local!
+{
+  static CURRENT_LOGGER : RefCell<Option<TaskLogger>> = const { RefCell::new(None) };
+  /// Set while a [TaskLogger] forward a record it already kept to the global logger.
+  static FORWARDING : Cell<bool> = const { Cell::new(false) };
+}
+
+/// A line logged by a task.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogRecord
+{
+  pub level : Level,
+  pub time : DateTime<Utc>,
+  /// Module or plugin that logged the record.
+  pub target : String,
+  pub message : String,
+}
+
+/// Records logged by a task.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskLog
+{
+  pub records : Vec<LogRecord>,
+  /// Number of records dropped after the first [MAX_RECORDS].
+  pub dropped : u64,
+}
+
+/**
+ * Keep the records logged by a task, clones share the same records.
+ */
+#[derive(Clone)]
+pub struct TaskLogger
+{
+  plugin : String,
+  log : Arc<Mutex<TaskLog>>,
+}
+
+impl Default for TaskLogger
+{
+  fn default() -> Self
+  {
+    TaskLogger::new(crate::meteredvfile::UNKNOWN_CONSUMER)
+  }
+}
+
+impl TaskLogger
+{
+  /// Return a new logger of a task running `plugin`, used as the target of its records.
+  pub fn new<S : Into<String>>(plugin : S) -> Self
+  {
+    TaskLogger{ plugin : plugin.into(), log : Arc::new(Mutex::new(TaskLog::default())) }
+  }
+
+  /// Keep `message` and forward it to the global logger.
+  pub fn log<S : Into<String>>(&self, level : Level, message : S)
+  {
+    let message = message.into();
+    FORWARDING.with(|forwarding| forwarding.set(true));
+    log::log!(target : &self.plugin, level, "{}", message);
+    FORWARDING.with(|forwarding| forwarding.set(false));
+    self.push(LogRecord{ level, time : Utc::now(), target : self.plugin.clone(), message });
+  }
+
+  pub fn error<S : Into<String>>(&self, message : S)
+  {
+    self.log(Level::Error, message)
+  }
+
+  pub fn warn<S : Into<String>>(&self, message : S)
+  {
+    self.log(Level::Warn, message)
+  }
+
+  pub fn info<S : Into<String>>(&self, message : S)
+  {
+    self.log(Level::Info, message)
+  }
+
+  pub fn debug<S : Into<String>>(&self, message : S)
+  {
+    self.log(Level::Debug, message)
+  }
+
+  /// Return the records kept.
+  pub fn task_log(&self) -> TaskLog
+  {
+    self.log.lock().unwrap().clone()
+  }
+
+  fn push(&self, record : LogRecord)
+  {
+    let mut log = self.log.lock().unwrap();
+    match log.records.len() < MAX_RECORDS
+    {
+      true => log.records.push(record),
+      false => log.dropped += 1,
+    }
+  }
+}
+
+/**
+ * [TaskLogger] of the last [MAX_TASK_LOGS] launched tasks.
+ */
+#[derive(Default)]
+pub struct TaskLogs
+{
+  loggers : HashMap<TaskId, TaskLogger>,
+  /// Task ids in launch order.
+  launched : VecDeque<TaskId>,
+}
+
+impl TaskLogs
+{
+  /// Keep `logger` of task `id`, dropping the log of the oldest launched task if [MAX_TASK_LOGS] are kept.
+  pub fn insert(&mut self, id : TaskId, logger : TaskLogger)
+  {
+    if self.loggers.insert(id, logger).is_none()
+    {
+      self.launched.push_back(id);
+    }
+    while self.launched.len() > MAX_TASK_LOGS
+    {
+      if let Some(oldest) = self.launched.pop_front()
+      {
+        self.loggers.remove(&oldest);
+      }
+    }
+  }
+
+  /// Return the logger of task `id` if its log is still kept.
+  pub fn get(&self, id : TaskId) -> Option<&TaskLogger>
+  {
+    self.loggers.get(&id)
+  }
+}
+
+/// Restore the previous logger of the thread when dropped, returned by [capture_as].
+pub struct CaptureGuard
+{
+  previous : Option<TaskLogger>,
+  //the guard must be dropped on the thread where it was created
+  _thread : PhantomData<*const ()>,
+}
+
+impl Drop for CaptureGuard
+{
+  fn drop(&mut self)
+  {
+    CURRENT_LOGGER.with(|current| *current.borrow_mut() = self.previous.take());
+  }
+}
+
+/// Keep the records logged by the current thread in `logger` until the returned guard is dropped.
+#[must_use = "the capture stop when the guard is dropped"]
+pub fn capture_as(logger : TaskLogger) -> CaptureGuard
+{
+  let previous = CURRENT_LOGGER.with(|current| current.borrow_mut().replace(logger));
+  CaptureGuard{ previous, _thread : PhantomData }
+}
+
+/// Global logger keeping the records of the threads [capturing](capture_as) their log and forwarding all records to `inner`.
+struct CaptureLogger
+{
+  inner : Box<dyn Log>,
+}
+
+impl Log for CaptureLogger
+{
+  fn enabled(&self, _metadata : &Metadata) -> bool
+  {
+    true
+  }
+
+  fn log(&self, record : &Record)
+  {
+    if !FORWARDING.with(|forwarding| forwarding.get())
+    {
+      CURRENT_LOGGER.with(|current|
+      {
+        if let Some(logger) = current.borrow().as_ref()
+        {
+          logger.push(LogRecord{ level : record.level(), time : Utc::now(), target : record.target().to_string(), message : record.args().to_string() });
+        }
+      });
+    }
+    if self.inner.enabled(record.metadata())
+    {
+      self.inner.log(record)
+    }
+  }
+
+  fn flush(&self)
+  {
+    self.inner.flush()
+  }
+}
+
+/// Install the global logger capturing the records of the tasks up to `max_level`, and forwarding the records to the application logger `inner`.
+/// Return an error if a global logger was already set.
+pub fn install(inner : Box<dyn Log>, max_level : LevelFilter) -> Result<(), SetLoggerError>
+{
+  log::set_boxed_logger(Box::new(CaptureLogger{ inner }))?;
+  log::set_max_level(max_level);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{TaskLogger, TaskLogs, MAX_RECORDS, MAX_TASK_LOGS, capture_as, install};
+  use crate::task_scheduler::TaskId;
+
+  use log::{Level, LevelFilter, Log, Metadata, Record};
+
+  struct NoLogger;
+
+  impl Log for NoLogger
+  {
+    fn enabled(&self, _metadata : &Metadata) -> bool { false }
+    fn log(&self, _record : &Record) {}
+    fn flush(&self) {}
+  }
+
+  #[test]
+  fn capture_task_log()
+  {
+    install(Box::new(NoLogger), LevelFilter::Debug).unwrap();
+    let logger = TaskLogger::new("parser");
+    logger.warn("bad header");
+    {
+      let _guard = capture_as(logger.clone());
+      log::info!("sector {}", 12);
+      //records of the logger are kept once
+      logger.error("can't read");
+    }
+    log::info!("not captured");
+
+    let log = logger.task_log();
+    assert!(log.records.len() == 3 && log.dropped == 0);
+    assert!(log.records[0].level == Level::Warn && log.records[0].target == "parser" && log.records[0].message == "bad header");
+    assert!(log.records[1].level == Level::Info && log.records[1].message == "sector 12" && log.records[1].target.ends_with("task_log::tests"));
+    assert!(log.records[2].level == Level::Error && log.records[1].time <= log.records[2].time);
+
+    for _ in 0..MAX_RECORDS
+    {
+      logger.debug("line");
+    }
+    let log = logger.task_log();
+    assert!(log.records.len() == MAX_RECORDS && log.dropped == 3);
+  }
+
+  #[test]
+  fn drop_oldest_task_logs()
+  {
+    let mut logs = TaskLogs::default();
+    for id in 0..MAX_TASK_LOGS as TaskId + 2
+    {
+      logs.insert(id, TaskLogger::new("dummy"));
+    }
+    assert!(logs.get(0).is_none() && logs.get(1).is_none());
+    assert!(logs.get(2).is_some() && logs.get(MAX_TASK_LOGS as TaskId + 1).is_some());
+  }
+}
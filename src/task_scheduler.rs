@@ -2,22 +2,51 @@
 
 use std::fmt;
 use std::thread;
-use std::sync::{Arc, RwLock};
-use std::collections::HashMap;
+use std::cmp::{Ordering, Reverse};
+use std::sync::{Arc, Mutex, RwLock, Condvar};
+use std::collections::{HashMap, HashSet, BTreeMap, BinaryHeap};
+use std::time::{Duration, Instant};
 
 use crate::error::{RustructError};
-use crate::tree::Tree;
+use crate::tree::{Tree, TreeNodeId};
+use crate::node::Node;
+use crate::summary::RunSummary;
+use crate::context::CaseContext;
+use crate::stagingvfile::BlobStore;
+use crate::blockcache::BlockCache;
+use crate::profiler::Profiler;
+use crate::result_store::{ResultStore, ResultStats};
+use crate::validation::Validator;
+use crate::tag::Tagger;
+use crate::computed::ComputedAttributes;
+use crate::diagnostics::{LockStats, probe_lock};
+use crate::event::EventChannel;
+use crate::cancellation::CancellationToken;
+use crate::quota::{Quota, QuotaEvent, ResourceUsage};
+use crate::services::Services;
+use crate::task_log::{TaskLogger, TaskLogs, TaskLog, capture_as};
+use crate::meteredvfile::as_consumer;
 use crate::plugin::{PluginInstance, PluginArgument, PluginEnvironment, PluginResult};
+use crate::plugins_db::PluginsDB;
 
-use log::info;
+use log::{info, warn};
 use anyhow::{Result, Error};
-use crossbeam::crossbeam_channel::{unbounded, bounded, Sender, Receiver};
+use crossbeam::crossbeam_channel::{unbounded, bounded, Sender, Receiver, RecvTimeoutError};
 use serde::{Serialize, Deserialize};
 use std::panic::AssertUnwindSafe;
 
 pub type TaskId = u32;
 pub type TaskResult = Result<PluginResult, Arc<Error>>;
 
+/// Interval at which the watchdog check the time limits of the running tasks.
+const WATCHDOG_INTERVAL : Duration = Duration::from_millis(50);
+
+/// Default time given to a timed out task to stop after its cancellation, before the scheduler give up waiting for it.
+pub const TIMEOUT_GRACE : Duration = Duration::from_secs(5);
+
+/// Name of the pool running the tasks not routed to another pool.
+pub const DEFAULT_POOL : &str = "default";
+
 ///Enum indicating state of a plugin (Waiting, Launched, Finished).
 #[derive(Debug, Clone)] 
 pub enum TaskState
@@ -40,6 +69,53 @@ pub struct Task
   pub plugin_name : String,
   /// Argument to the plugin
   pub argument : PluginArgument,
+  /// Summary of what the plugin added to the tree, set when the task is finished
+  #[serde(default)]
+  pub summary : Option<RunSummary>,
+  /// Last progress reported by the plugin
+  #[serde(default)]
+  pub progress : Option<TaskProgress>,
+  /// Maximum running time of the task, it's cancelled when exceeded
+  #[serde(default)]
+  pub timeout : Option<Duration>,
+  /// Waiting tasks are launched by order of priority then by order of creation
+  #[serde(default)]
+  pub priority : Priority,
+  /// Worker pool running the task, the [default pool](DEFAULT_POOL) if `None`
+  #[serde(default)]
+  pub pool : Option<String>,
+}
+
+/// Priority of a [Task], an interactive request is launched before the background tasks already waiting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Priority
+{
+  Low,
+  #[default]
+  Normal,
+  High,
+}
+
+/// What a task [scheduled after](TaskScheduler::schedule_after) other tasks does when one of them fails or is cancelled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FailurePolicy
+{
+  /// The task is finished with a [DependencyFailed](RustructError::DependencyFailed) error without being run, and so are the tasks scheduled after it.
+  #[default]
+  Propagate,
+  /// The task is run once all its dependencies are finished, whatever their result.
+  Ignore,
+}
+
+/// Progress of a running task, reported by its plugin with [PluginEnvironment::progress] or [PluginEnvironment::report_items].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskProgress
+{
+  /// Percentage of the work done, between 0 and 100.
+  pub percent : f32,
+  pub message : Option<String>,
+  /// Number of items processed and total number of items, if the plugin counts them.
+  pub items : Option<(u64, u64)>,
 }
 
 impl fmt::Display for Task
@@ -50,6 +126,49 @@ impl fmt::Display for Task
    }
 }
 
+/// Transition of a task sent to the subscribers of [TaskScheduler::events].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TaskEvent
+{
+  /// The task was added to the scheduler.
+  Waiting(TaskId),
+  /// The task was launched on a worker.
+  Launched(TaskId),
+  /// The task is finished, `success` is false if it returned an error.
+  Finished{ id : TaskId, success : bool },
+  /// The running task reported its progress, returned by [TaskScheduler::progress].
+  Progress(TaskId),
+}
+
+impl From<&TaskState> for TaskEvent
+{
+  fn from(state : &TaskState) -> Self
+  {
+    match state
+    {
+      TaskState::Waiting(task) => TaskEvent::Waiting(task.id),
+      TaskState::Launched(task) => TaskEvent::Launched(task.id),
+      TaskState::Finished(task, result) => TaskEvent::Finished{ id : task.id, success : result.is_ok() },
+    }
+  }
+}
+
+/// Reason why a task is or is not running, returned by [TaskScheduler::explain].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TaskExplanation
+{
+  /// The task wait for a free worker of its pool : `position` tasks were queued before it and `running` tasks use the `workers` of the pool.
+  Queued{ position : usize, running : usize, workers : usize },
+  /// The task is running on a worker since `running_for`.
+  Running{ running_for : Duration },
+  /// The task wait for the `dependencies` it was [scheduled after](TaskScheduler::schedule_after) to finish.
+  Blocked{ dependencies : Vec<TaskId> },
+  /// The task is finished, with the `error` it returned if it failed.
+  Finished{ error : Option<String> },
+  /// The task was restored from a saved session while waiting or running, it will never be run.
+  Orphaned,
+}
+
 /// Launch in a thread and used to managed tasks state.Wait to receive a message from Worker and update the task state accordingly.
 struct TasksHandler
 {
@@ -59,66 +178,805 @@ struct TasksHandler
   task_update : Sender<TaskId>,
   /// This is the map of TaskState that is updated via the pool of worker message.
   tasks : Arc<RwLock<HashMap<TaskId, TaskState>>>,
+  /// Store big results on disk rather than in the tasks map.
+  results : Arc<ResultStore>,
+  /// Time at which the running tasks were launched.
+  started : Arc<RwLock<HashMap<TaskId, Instant>>>,
+  /// Send the tasks transitions.
+  events : EventChannel<TaskEvent>,
+  /// Cancellation tokens of the tasks not finished.
+  cancellations : Arc<RwLock<HashMap<TaskId, CancellationToken>>>,
+  /// Tasks released when the tasks they were scheduled after are finished.
+  deferred : Arc<DeferredTasks>,
+  /// Claims of the running tasks, released when the worker finished the task.
+  claims : Claims,
 }
 
 impl TasksHandler
 {
-  /// Return a new task handler.
-  pub fn new(task_state : Receiver<TaskState>, task_update : Sender<TaskId>, tasks : Arc<RwLock<HashMap<TaskId, TaskState>>>) -> Self
-  {
-    TasksHandler{ task_state, task_update, tasks }
-  }
-
   /// Update the task mask when arrive a new message from the worker pool.
   fn update(&self) 
   {
     //wait blocking for new task
     for task_state in self.task_state.iter()
     {
-       let task = match &task_state
+      self.handle(task_state);
+    }
+  }
+
+  /// Update the state of a task, and of the tasks scheduled after it if it's finished.
+  fn handle(&self, task_state : TaskState)
+  {
+       let mut task_state = self.results.store_state(task_state);
+       let id = match &task_state
        {
-         TaskState::Waiting(task) => task, 
-         TaskState::Launched(task) => task, 
-         TaskState::Finished(task, _) => task, 
+         TaskState::Waiting(task) => task.id, 
+         TaskState::Launched(task) => task.id, 
+         TaskState::Finished(task, _) => task.id, 
        };
 
        let mut tasks = self.tasks.write().unwrap(); //we don't want to lock the tasks map when waiting on the channel, if we do that before the block the tasks will be locked on write during a potential infinite time
-       tasks.insert(task.id, task_state.clone());
-       self.task_update.send(task.id).unwrap();
-    }
+       //a running task sent again as launched is a progress report
+       let previous = tasks.get(&id);
+       //a task given up by the watchdog is already finished, what its worker send later is ignored
+       if matches!(previous, Some(TaskState::Finished(..)))
+       {
+         return
+       }
+       let progress = matches!((&task_state, previous), (TaskState::Launched(_), Some(TaskState::Launched(_))));
+       if let (TaskState::Finished(task, _), Some(TaskState::Launched(previous))) = (&mut task_state, previous)
+       {
+         task.progress = task.progress.take().or_else(|| previous.progress.clone());
+       }
+
+       match &task_state
+       {
+         _ if progress => (),
+         TaskState::Launched(_) => { self.started.write().unwrap().insert(id, Instant::now()); },
+         TaskState::Finished(..) =>
+         {
+           self.started.write().unwrap().remove(&id);
+           self.cancellations.write().unwrap().remove(&id);
+         },
+         _ => { self.started.write().unwrap().remove(&id); },
+       }
+
+       tasks.insert(id, task_state.clone());
+       drop(tasks);
+       //released after the task is marked as finished, so the watchdog doesn't give up a task it still sees launched
+       if matches!(task_state, TaskState::Finished(..))
+       {
+         let mut claims = self.claims.lock().unwrap();
+         if claims.get(&id) == Some(&Claim::Worker)
+         {
+           claims.remove(&id);
+         }
+       }
+       match progress
+       {
+         true => self.events.update(TaskEvent::Progress(id)),
+         false => self.events.update(TaskEvent::from(&task_state)),
+       }
+       self.task_update.send(id).unwrap();
+
+       if let TaskState::Finished(_, result) = &task_state
+       {
+         for failed in self.deferred.release(id, result)
+         {
+           self.handle(failed);
+         }
+       }
   }
 }
 
 /// Boxed PluginInstance. 
 type BoxPluginInstance = Box<dyn PluginInstance + Sync + Send>;
 
+/// Task sent to the workers with its plugin and its cancellation token.
+type QueuedTask = (Task, BoxPluginInstance, CancellationToken);
+
+/// [QueuedTask] ordered by priority then by id.
+struct Queued(QueuedTask);
+
+impl Queued
+{
+  fn key(&self) -> (Priority, Reverse<TaskId>)
+  {
+    (self.0.0.priority, Reverse(self.0.0.id))
+  }
+}
+
+impl PartialEq for Queued
+{
+  fn eq(&self, other : &Self) -> bool
+  {
+    self.key() == other.key()
+  }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued
+{
+  fn partial_cmp(&self, other : &Self) -> Option<Ordering>
+  {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Queued
+{
+  fn cmp(&self, other : &Self) -> Ordering
+  {
+    self.key().cmp(&other.key())
+  }
+}
+
+/// Tasks waiting for a worker, popped by order of [Priority] then by order of creation.
+#[derive(Default)]
+struct TaskQueue
+{
+  /// Queued tasks and true when the scheduler was dropped.
+  queue : Mutex<(BinaryHeap<Queued>, bool)>,
+  available : Condvar,
+}
+
+impl TaskQueue
+{
+  fn push(&self, task : QueuedTask)
+  {
+    self.queue.lock().unwrap().0.push(Queued(task));
+    self.available.notify_one();
+  }
+
+  /// Block until a task is available and return it, or return `None` when the queue is closed and empty.
+  fn pop(&self) -> Option<QueuedTask>
+  {
+    let mut queue = self.queue.lock().unwrap();
+    loop
+    {
+      if let Some(Queued(task)) = queue.0.pop()
+      {
+        return Some(task)
+      }
+      if queue.1
+      {
+        return None
+      }
+      queue = self.available.wait(queue).unwrap();
+    }
+  }
+
+  fn len(&self) -> usize
+  {
+    self.queue.lock().unwrap().0.len()
+  }
+
+  /// Change the priority of queued task `id`, return false if it's not queued.
+  fn set_priority(&self, id : TaskId, priority : Priority) -> bool
+  {
+    let mut queue = self.queue.lock().unwrap();
+    let mut tasks = std::mem::take(&mut queue.0).into_vec();
+    let found = tasks.iter_mut().find(|Queued(task)| task.0.id == id).map(|Queued(task)| task.0.priority = priority).is_some();
+    queue.0 = tasks.into();
+    found
+  }
+
+  /// Wake up the workers to stop them once the queue is empty.
+  fn close(&self)
+  {
+    self.queue.lock().unwrap().1 = true;
+    self.available.notify_all();
+  }
+}
+
+/// Queues of the worker pools and their number of workers.
+struct Pools
+{
+  pools : HashMap<String, (Arc<TaskQueue>, usize)>,
+}
+
+impl Pools
+{
+  /// Return the queue of the pool running `task`, or of the [DEFAULT_POOL] if its pool doesn't exist
+  /// like for a task restored from a scheduler with other pools.
+  fn queue(&self, task : &Task) -> &Arc<TaskQueue>
+  {
+    match task.pool.as_deref().and_then(|pool| self.pools.get(pool))
+    {
+      Some((queue, _)) => queue,
+      None => &self.pools[DEFAULT_POOL].0,
+    }
+  }
+
+  /// Send `task` to the workers of its pool.
+  fn push(&self, task : QueuedTask)
+  {
+    self.queue(&task.0).push(task)
+  }
+}
+
+/// A task waiting for the `pending` tasks it was scheduled after.
+struct Deferred
+{
+  task : QueuedTask,
+  pending : HashSet<TaskId>,
+  policy : FailurePolicy,
+}
+
+/// Tasks [scheduled after](TaskScheduler::schedule_after) other tasks, sent to the [TaskQueue] of their pool once these tasks are finished.
+struct DeferredTasks
+{
+  tasks : Mutex<HashMap<TaskId, Deferred>>,
+  pools : Arc<Pools>,
+}
+
+impl DeferredTasks
+{
+  fn new(pools : Arc<Pools>) -> Self
+  {
+    DeferredTasks{ tasks : Mutex::new(HashMap::new()), pools }
+  }
+
+  /// Queue `task` once its `dependencies` are finished, return the state of the task finished without being run if a dependency already failed.
+  fn defer(&self, task : QueuedTask, dependencies : &[TaskId], policy : FailurePolicy, states : &RwLock<HashMap<TaskId, TaskState>>) -> Option<TaskState>
+  {
+    //locked before reading the states so a dependency finishing meanwhile release the task
+    let mut tasks = self.tasks.lock().unwrap();
+    let mut pending = HashSet::new();
+    for dependency in dependencies
+    {
+      match states.read().unwrap().get(dependency)
+      {
+        Some(TaskState::Finished(_, Err(err))) if policy == FailurePolicy::Propagate => return Some(DeferredTasks::failed(task.0, *dependency, err)),
+        Some(TaskState::Finished(..)) => (),
+        _ => { pending.insert(*dependency); },
+      }
+    }
+    match pending.is_empty()
+    {
+      true => self.pools.push(task),
+      false => { tasks.insert(task.0.id, Deferred{ task, pending, policy }); },
+    }
+    None
+  }
+
+  /// Update the tasks waiting for task `id` finished with `result` : queue the tasks whose dependencies are all finished
+  /// and return the state of the tasks finished because `id` failed.
+  fn release(&self, id : TaskId, result : &TaskResult) -> Vec<TaskState>
+  {
+    let mut tasks = self.tasks.lock().unwrap();
+    let dependents : Vec<TaskId> = tasks.iter().filter(|(_, deferred)| deferred.pending.contains(&id)).map(|(dependent, _)| *dependent).collect();
+    let mut failed = Vec::new();
+    for dependent in dependents
+    {
+      let deferred = tasks.get_mut(&dependent).unwrap();
+      deferred.pending.remove(&id);
+      match result
+      {
+        Err(err) if deferred.policy == FailurePolicy::Propagate => failed.push(DeferredTasks::failed(tasks.remove(&dependent).unwrap().task.0, id, err)),
+        _ if deferred.pending.is_empty() => self.pools.push(tasks.remove(&dependent).unwrap().task),
+        _ => (),
+      }
+    }
+    failed
+  }
+
+  /// Remove task `id`, return it if it was waiting for its dependencies.
+  fn remove(&self, id : TaskId) -> Option<QueuedTask>
+  {
+    self.tasks.lock().unwrap().remove(&id).map(|deferred| deferred.task)
+  }
+
+  /// Return the dependencies not finished yet of each waiting task.
+  fn pending(&self) -> HashMap<TaskId, Vec<TaskId>>
+  {
+    self.tasks.lock().unwrap().iter().map(|(id, deferred)|
+    {
+      let mut pending : Vec<TaskId> = deferred.pending.iter().copied().collect();
+      pending.sort_unstable();
+      (*id, pending)
+    }).collect()
+  }
+
+  fn failed(task : Task, dependency : TaskId, error : &Error) -> TaskState
+  {
+    TaskState::Finished(task, Err(Arc::new(RustructError::DependencyFailed(format!("task {}", dependency), error.to_string()).into())))
+  }
+}
+
+/// Senders notified of the result of the tasks launched with [TaskScheduler::run].
+type Waiters = Arc<Mutex<HashMap<TaskId, Sender<TaskResult>>>>;
+
+/// Side finishing a running task : its worker once the plugin returned, or the watchdog when it gives up the task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Claim
+{
+  Worker,
+  Watchdog,
+}
+
+/// [Claim] of the running tasks, only the side claiming a task first finishes it.
+/// Worker claims are removed when the task is finished, watchdog claims when the worker of the task given up returns.
+type Claims = Arc<Mutex<HashMap<TaskId, Claim>>>;
+
+/// Cancel the running tasks that exceed their [timeout](Task::timeout), and finish with a [TimedOut](RustructError::TimedOut) error
+/// the tasks still running after the grace period. The worker of a task given up is replaced by a new worker.
+struct Watchdog
+{
+  /// Disconnected when the scheduler is dropped.
+  stop : Receiver<()>,
+  tasks : Arc<RwLock<HashMap<TaskId, TaskState>>>,
+  started : Arc<RwLock<HashMap<TaskId, Instant>>>,
+  cancellations : Arc<RwLock<HashMap<TaskId, CancellationToken>>>,
+  waiters : Waiters,
+  grace : Arc<RwLock<Duration>>,
+  pools : Arc<Pools>,
+  /// Worker cloned to replace the workers of the tasks given up.
+  worker : Worker,
+  next_worker_id : usize,
+}
+
+impl Watchdog
+{
+  fn run(mut self)
+  {
+    while let Err(RecvTimeoutError::Timeout) = self.stop.recv_timeout(WATCHDOG_INTERVAL)
+    {
+      self.check();
+    }
+  }
+
+  fn check(&mut self)
+  {
+    let started = self.started.read().unwrap().clone();
+    let grace = *self.grace.read().unwrap();
+    for (id, start) in started
+    {
+      let (task, timeout) = match self.tasks.read().unwrap().get(&id)
+      {
+        Some(TaskState::Launched(task)) => match task.timeout
+        {
+          Some(timeout) => (task.clone(), timeout),
+          None => continue,
+        },
+        _ => continue,
+      };
+
+      let elapsed = start.elapsed();
+      if elapsed < timeout
+      {
+        continue
+      }
+      let cancellation = self.cancellations.read().unwrap().get(&id).cloned();
+      if let Some(cancellation) = cancellation
+      {
+        if cancellation.cancel()
+        {
+          info!("task {}({}) timed out after {:?}", task.plugin_name, id, timeout);
+        }
+      }
+      if elapsed >= timeout + grace
+      {
+        self.give_up(task, timeout);
+      }
+    }
+  }
+
+  /// Finish `task` that doesn't stop after its cancellation and launch a new worker to replace its worker.
+  /// Nothing is done if the worker of the task already claimed it because its plugin returned.
+  fn give_up(&mut self, task : Task, timeout : Duration)
+  {
+    {
+      let mut claims = self.worker.claims.lock().unwrap();
+      if claims.contains_key(&task.id) || !matches!(self.tasks.read().unwrap().get(&task.id), Some(TaskState::Launched(_)))
+      {
+        return
+      }
+      claims.insert(task.id, Claim::Watchdog);
+    }
+    warn!("task {}({}) doesn't stop after its timeout, giving up", task.plugin_name, task.id);
+    let queue = self.pools.queue(&task).clone();
+    let result : TaskResult = Err(Arc::new(RustructError::TimedOut(task.id, timeout).into()));
+    if let Some(waiter) = self.waiters.lock().unwrap().remove(&task.id)
+    {
+      let _ = waiter.send(result.clone());
+    }
+    let _ = self.worker.sender.send(TaskState::Finished(task, result));
+
+    let worker = Worker{ id : self.next_worker_id, queue, ..self.worker.clone() };
+    self.next_worker_id += 1;
+    let _ = thread::Builder::new().name(format!("tap-worker-{}", worker.id)).spawn(move || worker.run());
+  }
+}
+
+/// Aggregate progress of the tasks of a [TaskGroup].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GroupProgress
+{
+  pub total : usize,
+  pub waiting : usize,
+  pub running : usize,
+  pub succeeded : usize,
+  pub failed : usize,
+  /// Mean percentage of the work done by the tasks, finished tasks count for 100.
+  pub percent : f32,
+}
+
+impl GroupProgress
+{
+  /// Return true if all the tasks of the group are finished.
+  pub fn is_finished(&self) -> bool
+  {
+    self.waiting == 0 && self.running == 0
+  }
+}
+
+/**
+ * A set of tasks joined, cancelled and followed as a unit, so frontends and pipelines sharing a scheduler only wait for their own tasks.
+ * Clones share the same tasks.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct TaskGroup
+{
+  tasks : Arc<Mutex<Vec<TaskId>>>,
+}
+
+impl TaskGroup
+{
+  pub fn new() -> Self
+  {
+    TaskGroup::default()
+  }
+
+  /// Add task `id` to the group.
+  pub fn add(&self, id : TaskId)
+  {
+    self.tasks.lock().unwrap().push(id);
+  }
+
+  pub fn task_ids(&self) -> Vec<TaskId>
+  {
+    self.tasks.lock().unwrap().clone()
+  }
+
+  pub fn len(&self) -> usize
+  {
+    self.tasks.lock().unwrap().len()
+  }
+
+  pub fn is_empty(&self) -> bool
+  {
+    self.len() == 0
+  }
+
+  /// [Schedule](TaskScheduler::schedule) a task on `scheduler` and add it to the group.
+  pub fn schedule(&self, scheduler : &TaskScheduler, plugin : Box<dyn PluginInstance + Sync + Send>, argument : PluginArgument, relaunch : bool) -> Result<TaskId, Error>
+  {
+    let id = scheduler.schedule(plugin, argument, relaunch)?;
+    self.add(id);
+    Ok(id)
+  }
+
+  /// Block until all the tasks of the group are finished, including the tasks added while joining.
+  pub fn join(&self, scheduler : &TaskScheduler)
+  {
+    let mut joined = 0;
+    loop
+    {
+      let ids = self.task_ids();
+      if joined == ids.len()
+      {
+        return
+      }
+      scheduler.join_tasks(&ids[joined..]);
+      joined = ids.len();
+    }
+  }
+
+  /// [Cancel](TaskScheduler::cancel) the unfinished tasks of the group, return the number of tasks cancelled.
+  pub fn cancel(&self, scheduler : &TaskScheduler) -> usize
+  {
+    self.task_ids().into_iter().filter(|id| matches!(scheduler.cancel(*id), Ok(true))).count()
+  }
+
+  /// Return the aggregate progress of the tasks of the group.
+  pub fn progress(&self, scheduler : &TaskScheduler) -> GroupProgress
+  {
+    let mut progress = GroupProgress{ total : self.len(), ..Default::default() };
+    let mut percent = 0.0;
+    for id in self.task_ids()
+    {
+      match scheduler.state(id)
+      {
+        Some(TaskState::Launched(task)) =>
+        {
+          progress.running += 1;
+          percent += task.progress.map_or(0.0, |progress| progress.percent);
+        },
+        Some(TaskState::Finished(_, result)) =>
+        {
+          match result.is_ok()
+          {
+            true => progress.succeeded += 1,
+            false => progress.failed += 1,
+          }
+          percent += 100.0;
+        },
+        _ => progress.waiting += 1,
+      }
+    }
+    progress.percent = match progress.total
+    {
+      0 => 100.0,
+      total => percent / total as f32,
+    };
+    progress
+  }
+
+  /// Return the [result](TaskScheduler::result) of each task of the group.
+  pub fn results(&self, scheduler : &TaskScheduler) -> Vec<(TaskId, TaskResult)>
+  {
+    self.task_ids().into_iter().map(|id| (id, scheduler.result(id))).collect()
+  }
+}
+
+/// Configuration of a [TaskScheduler] : the number of workers of the [default pool](DEFAULT_POOL) and the named pools,
+/// like an `io` pool running the plugins waiting on the disk and a `cpu` pool running the plugins decompressing data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskSchedulerBuilder
+{
+  pools : BTreeMap<String, usize>,
+}
+
+impl Default for TaskSchedulerBuilder
+{
+  fn default() -> Self
+  {
+    TaskSchedulerBuilder{ pools : BTreeMap::from([(DEFAULT_POOL.to_string(), num_cpus::get())]) }
+  }
+}
+
+impl TaskSchedulerBuilder
+{
+  /// Return a configuration with a default pool of one worker per cpu.
+  pub fn new() -> Self
+  {
+    TaskSchedulerBuilder::default()
+  }
+
+  /// Set the number of workers of the default pool, at least one.
+  pub fn workers(self, workers : usize) -> Self
+  {
+    self.pool(DEFAULT_POOL, workers)
+  }
+
+  /// Add pool `name` of `workers` workers, at least one. The tasks of a plugin are routed to the pool named
+  /// by [TaskScheduler::set_plugin_pool] or by its [PluginMetadata::pool](crate::plugin::PluginMetadata::pool), or to the default pool.
+  pub fn pool(mut self, name : &str, workers : usize) -> Self
+  {
+    self.pools.insert(name.to_string(), workers.max(1));
+    self
+  }
+
+  /// Return the name and number of workers of the pools.
+  pub fn pools(&self) -> Vec<(String, usize)>
+  {
+    self.pools.iter().map(|(name, workers)| (name.clone(), *workers)).collect()
+  }
+
+  /// Return a new scheduler running the tasks on `tree`.
+  pub fn build(self, tree : Tree) -> TaskScheduler
+  {
+    TaskScheduler::with_builder(tree, self)
+  }
+}
+
 /// The scheduler is in charge of running [Task] (plugin [instance](PluginInstance) and [argument](PluginArgument)).
 pub struct TaskScheduler
 {
-  ///This is used to send a new [Task] to a [worker](Worker), to then be executed.
-  new_task : Sender<(Task, BoxPluginInstance, Option<Sender<TaskResult>>)>,
+  ///Configuration of the workers pools.
+  builder : TaskSchedulerBuilder,
+  ///This is used to send a new [Task] to the [workers](Worker) of its pool, to then be executed.
+  pools : Arc<Pools>,
+  ///Pool of the tasks of each plugin.
+  routes : RwLock<HashMap<String, String>>,
+  ///Priority of the tasks of each plugin.
+  priorities : RwLock<HashMap<String, Priority>>,
+  ///Tasks waiting for the tasks they were scheduled after.
+  deferred : Arc<DeferredTasks>,
+  ///Send to the [TasksHandler] the state of the tasks finished without being run.
+  task_state : Sender<TaskState>,
   ///Receive update from the [TasksHandler] when the `task` [map](HashMap) is changed.
   task_update : Receiver<TaskId>,
   ///An arc ref to the [TasksHandler] `task` [map](HashMap).
   tasks : Arc<RwLock<HashMap<TaskId, TaskState>>>,
+  ///The tree on which tasks are run.
+  tree : Tree,
+  ///Id of the node under which [run summary](RunSummary) nodes are created, if enabled.
+  reports : Arc<RwLock<Option<TreeNodeId>>>,
+  ///Profiler shared with the [workers](Worker).
+  #[cfg_attr(not(feature = "profiler"), allow(dead_code))]
+  profiler : Arc<Profiler>,
+  ///Context passed to the plugins.
+  context : Arc<RwLock<CaseContext>>,
+  ///Store of the artifacts staged by the plugins.
+  blob_store : Arc<RwLock<BlobStore>>,
+  ///Block cache shared by the tasks.
+  block_cache : Arc<BlockCache>,
+  ///Services passed to the plugins.
+  services : Arc<RwLock<Services>>,
+  ///Store for the results too big to be kept in the `tasks` map.
+  results : Arc<ResultStore>,
+  ///Validator checking the nodes created by the tasks.
+  validator : Arc<Validator>,
+  ///Tagger applying the tag rules to the nodes created by the tasks.
+  tagger : Arc<Tagger>,
+  ///Computed attributes added to the nodes created by the tasks.
+  computed : Arc<ComputedAttributes>,
+  ///Time at which the running tasks were launched.
+  started : Arc<RwLock<HashMap<TaskId, Instant>>>,
+  ///Waiting or running tasks restored from a saved session, that are not in the workers queue.
+  restored : RwLock<HashSet<TaskId>>,
+  ///Id of the next registered task, greater than the id of all the restored tasks.
+  next_id : RwLock<TaskId>,
+  ///Send the tasks transitions.
+  events : EventChannel<TaskEvent>,
+  ///Cancellation tokens of the tasks not finished.
+  cancellations : Arc<RwLock<HashMap<TaskId, CancellationToken>>>,
+  ///Senders notified of the result of the tasks launched with [run](TaskScheduler::run).
+  waiters : Waiters,
+  ///Default timeout of the tasks of each plugin.
+  timeouts : RwLock<HashMap<String, Duration>>,
+  ///Time given to the timed out tasks to stop.
+  grace : Arc<RwLock<Duration>>,
+  ///Stop the watchdog when dropped.
+  _watchdog : Sender<()>,
+  ///Quota of the instances of each plugin.
+  quotas : Arc<RwLock<HashMap<String, Quota>>>,
+  ///Send the quotas exceeded by the tasks.
+  quota_events : EventChannel<QuotaEvent>,
+  ///Log of the launched tasks.
+  logs : Arc<RwLock<TaskLogs>>,
 }
 
 /// Provide different method to run, schedule and create new [task](Task).
 impl TaskScheduler
 {
-  /// Instantiate a new scheduler.
+  /// Instantiate a new scheduler with the [default configuration](TaskSchedulerBuilder::new).
   pub fn new(tree : Tree) -> Self
   {
-    let (new_task_sender, new_task_receiver) = unbounded();
+    TaskScheduler::with_builder(tree, TaskSchedulerBuilder::new())
+  }
+
+  fn with_builder(tree : Tree, builder : TaskSchedulerBuilder) -> Self
+  {
+    let pools = builder.pools.iter().map(|(name, workers)| (name.clone(), (Arc::new(TaskQueue::default()), *workers))).collect();
+    let pools = Arc::new(Pools{ pools });
     let (task_state_sender, task_state_receiver) = unbounded();
     let (task_update_sender, task_update_receiver) = unbounded();
 
     let tasks = Arc::new(RwLock::new(HashMap::new()));
-    let task_handler = TasksHandler::new(task_state_receiver, task_update_sender, tasks.clone());
+    let results = Arc::new(ResultStore::new());
+    let started = Arc::new(RwLock::new(HashMap::new()));
+    let events = EventChannel::new();
+    let cancellations = Arc::new(RwLock::new(HashMap::new()));
+    let deferred = Arc::new(DeferredTasks::new(pools.clone()));
+    let claims : Claims = Arc::new(Mutex::new(HashMap::new()));
+    let task_handler = TasksHandler{ task_state : task_state_receiver, task_update : task_update_sender, tasks : tasks.clone(), results : results.clone(),
+                                     started : started.clone(), events : events.clone(), cancellations : cancellations.clone(), deferred : deferred.clone(), claims : claims.clone() };
+
+    let reports = Arc::new(RwLock::new(None));
+    let profiler = Arc::new(Profiler::new());
+    let context = Arc::new(RwLock::new(CaseContext::default()));
+    let blob_store = Arc::new(RwLock::new(BlobStore::default()));
+    let block_cache = Arc::new(BlockCache::default());
+    let services = Arc::new(RwLock::new(Services::default()));
+    let validator = Arc::new(Validator::new());
+    let tagger = Arc::new(Tagger::new());
+    let computed = Arc::new(ComputedAttributes::new());
 
     TaskScheduler::launch_task_handler(task_handler);
-    TaskScheduler::launch_pool(&tree, num_cpus::get(), new_task_receiver, task_state_sender);
-    TaskScheduler{ new_task : new_task_sender , task_update : task_update_receiver, tasks }
+    let waiters : Waiters = Arc::new(Mutex::new(HashMap::new()));
+    let quotas = Arc::new(RwLock::new(HashMap::new()));
+    let quota_events = EventChannel::new();
+    let logs = Arc::new(RwLock::new(TaskLogs::default()));
+    let worker = Worker{ id : 0, tree : tree.clone(), queue : pools.pools[DEFAULT_POOL].0.clone(), sender : task_state_sender.clone(), reports : reports.clone(),
+                         profiler : profiler.clone(), context : context.clone(), blob_store : blob_store.clone(), block_cache : block_cache.clone(), services : services.clone(), validator : validator.clone(),
+                         tagger : tagger.clone(), computed : computed.clone(), waiters : waiters.clone(), claims,
+                         quotas : quotas.clone(), quota_events : quota_events.clone(), logs : logs.clone() };
+    let mut workers = 0;
+    for (queue, count) in pools.pools.values()
+    {
+      TaskScheduler::launch_pool(Worker{ queue : queue.clone(), ..worker.clone() }, workers, *count);
+      workers += count;
+    }
+
+    let (watchdog_sender, watchdog_receiver) = bounded(0);
+    let grace = Arc::new(RwLock::new(TIMEOUT_GRACE));
+    let watchdog = Watchdog{ stop : watchdog_receiver, tasks : tasks.clone(), started : started.clone(), cancellations : cancellations.clone(), waiters : waiters.clone(),
+                             grace : grace.clone(), pools : pools.clone(), worker, next_worker_id : workers };
+    let _ = thread::spawn(move || watchdog.run());
+
+    TaskScheduler{ builder, pools, routes : RwLock::new(HashMap::new()), priorities : RwLock::new(HashMap::new()), deferred, task_state : task_state_sender, task_update : task_update_receiver, tasks, tree, reports, profiler, context, blob_store, block_cache, services, results, validator, tagger, computed, started,
+                   restored : RwLock::new(HashSet::new()), next_id : RwLock::new(1), events, cancellations, waiters, timeouts : RwLock::new(HashMap::new()), grace, _watchdog : watchdog_sender,
+                   quotas, quota_events, logs }
+  }
+
+  /// Set the [CaseContext] passed to the next launched tasks.
+  pub fn set_context(&self, context : CaseContext)
+  {
+    *self.context.write().unwrap() = context;
+  }
+
+  /// Return the [CaseContext] passed to the tasks.
+  pub fn context(&self) -> CaseContext
+  {
+    self.context.read().unwrap().clone()
+  }
+
+  /// Set the [BlobStore] where the next launched tasks stage their artifacts.
+  pub fn set_blob_store(&self, blob_store : BlobStore)
+  {
+    *self.blob_store.write().unwrap() = blob_store;
+  }
+
+  /// Return the [BlobStore] passed to the tasks.
+  pub fn blob_store(&self) -> BlobStore
+  {
+    self.blob_store.read().unwrap().clone()
+  }
+
+  /// Return the [BlockCache] shared by the tasks.
+  pub fn block_cache(&self) -> Arc<BlockCache>
+  {
+    self.block_cache.clone()
+  }
+
+  /// Set the [Services] passed to the next launched tasks.
+  pub fn set_services(&self, services : Services)
+  {
+    *self.services.write().unwrap() = services;
+  }
+
+  /// Return the [Services] passed to the tasks.
+  pub fn services(&self) -> Services
+  {
+    self.services.read().unwrap().clone()
+  }
+
+  /// Enable sampling profiling of the next launched tasks with a sampling `frequency` in Hz, or disable it if `None`.
+  #[cfg(feature = "profiler")]
+  pub fn set_profiling(&self, frequency : Option<i32>)
+  {
+    self.profiler.set_frequency(frequency);
+  }
+
+  /// Return the flamegraph in SVG format of task `task_id`, if the task was profiled.
+  #[cfg(feature = "profiler")]
+  pub fn profile(&self, task_id : TaskId) -> Option<Vec<u8>>
+  {
+    self.profiler.profile(task_id)
+  }
+
+  /// Enable or disable the creation of a node containing the [run summary](RunSummary) of each finished task.
+  /// Nodes are created under `/root/Reports` and named after the plugin and the task id.
+  pub fn set_run_reports(&self, enable : bool) -> Result<()>
+  {
+    let mut reports = self.reports.write().unwrap();
+    if !enable
+    {
+      *reports = None;
+      return Ok(())
+    }
+
+    let reports_id = match self.tree.get_node_id("/root/Reports")
+    {
+      Some(reports_id) => reports_id,
+      None => self.tree.add_child(self.tree.root_id, Node::new("Reports"))?,
+    };
+    *reports = Some(reports_id);
+    Ok(())
   }
 
   fn launch_task_handler(task_handler : TasksHandler) 
@@ -126,33 +984,153 @@ impl TaskScheduler
     let _ = thread::spawn(move || {task_handler.update();} );
   }
 
-  fn launch_pool(tree : &Tree, thread_count : usize, receiver : Receiver<(Task, BoxPluginInstance, Option<Sender<TaskResult>>)>, task_state_sender : Sender<TaskState>) 
+  /// Launch `thread_count` clones of `worker` numbered from `first_id`.
+  fn launch_pool(worker : Worker, first_id : usize, thread_count : usize) 
   {  
-    for id in  0..thread_count
+    for id in first_id..first_id + thread_count
     {
-      let worker = Worker::new(id, tree.clone(), receiver.clone(), task_state_sender.clone());
+      let worker = Worker{ id, ..worker.clone() };
 
-      let _ = thread::spawn(move || 
+      //workers are named so the profiler can keep only the samples of the worker running the task
+      let _ = thread::Builder::new().name(format!("tap-worker-{}", id)).spawn(move || 
       {
         worker.run();
       });
     }
   }
 
+  /// Set the default [Priority] of the tasks of plugin `plugin_name`, used when no priority is passed when the task is submitted.
+  pub fn set_plugin_priority(&self, plugin_name : &str, priority : Option<Priority>)
+  {
+    let mut priorities = self.priorities.write().unwrap();
+    match priority
+    {
+      Some(priority) => priorities.insert(plugin_name.to_string(), priority),
+      None => priorities.remove(plugin_name),
+    };
+  }
+
+  /// Route the tasks of plugin `plugin_name` to pool `pool`, or to the pool of its [metadata](crate::plugin::PluginMetadata::pool) if `None`.
+  /// Return a [PoolNotFound](RustructError::PoolNotFound) error if there is no such pool.
+  pub fn set_plugin_pool(&self, plugin_name : &str, pool : Option<&str>) -> Result<()>
+  {
+    let mut routes = self.routes.write().unwrap();
+    match pool
+    {
+      Some(pool) if !self.pools.pools.contains_key(pool) => return Err(RustructError::PoolNotFound(pool.into()).into()),
+      Some(pool) => routes.insert(plugin_name.to_string(), pool.to_string()),
+      None => routes.remove(plugin_name),
+    };
+    Ok(())
+  }
+
+  /// Return the name and number of workers of the pools.
+  pub fn pools(&self) -> Vec<(String, usize)>
+  {
+    self.builder.pools()
+  }
+
+  /// Return the configuration of the scheduler, to build a new scheduler with the same pools.
+  pub fn builder(&self) -> TaskSchedulerBuilder
+  {
+    self.builder.clone()
+  }
+
+  /// Change the [Priority] of task `id` if it's still waiting for a worker, return false if it's already launched.
+  pub fn set_priority(&self, id : TaskId, priority : Priority) -> Result<bool>
+  {
+    let mut tasks = self.tasks.write().unwrap();
+    match tasks.get_mut(&id)
+    {
+      None => Err(RustructError::TaskNotFound(id).into()),
+      Some(TaskState::Waiting(task)) if self.pools.queue(task).set_priority(id, priority) => { task.priority = priority; Ok(true) },
+      Some(_) => Ok(false),
+    }
+  }
+
+  /// Set the default `timeout` of the tasks of plugin `plugin_name`, used when no timeout is passed when the task is submitted.
+  pub fn set_plugin_timeout(&self, plugin_name : &str, timeout : Option<Duration>)
+  {
+    let mut timeouts = self.timeouts.write().unwrap();
+    match timeout
+    {
+      Some(timeout) => timeouts.insert(plugin_name.to_string(), timeout),
+      None => timeouts.remove(plugin_name),
+    };
+  }
+
+  /// Set the [Quota] of the instances of plugin `plugin_name` launched after this call, or remove it if `None`.
+  pub fn set_plugin_quota(&self, plugin_name : &str, quota : Option<Quota>)
+  {
+    let mut quotas = self.quotas.write().unwrap();
+    match quota
+    {
+      Some(quota) => quotas.insert(plugin_name.to_string(), quota),
+      None => quotas.remove(plugin_name),
+    };
+  }
+
+  /// Return the records logged by task `id` since it was launched, or `None` if it was not launched.
+  /// Only the logs of the last [MAX_TASK_LOGS](crate::task_log::MAX_TASK_LOGS) launched tasks are kept.
+  pub fn task_logs(&self, id : TaskId) -> Option<TaskLog>
+  {
+    self.logs.read().unwrap().get(id).map(|logger| logger.task_log())
+  }
+
+  /// Return the channel sending the quotas exceeded by the tasks.
+  pub fn quota_events(&self) -> &EventChannel<QuotaEvent>
+  {
+    &self.quota_events
+  }
+
+  /// Set the time given to the timed out tasks to stop after their cancellation, before their result is set to a [TimedOut](RustructError::TimedOut) error
+  /// without waiting for them, the default is [TIMEOUT_GRACE].
+  pub fn set_timeout_grace(&self, grace : Duration)
+  {
+    *self.grace.write().unwrap() = grace;
+  }
+
+  /// [Register](TaskScheduler::register) a new task and send it to the workers.
+  fn push(&self, plugin: Box<dyn PluginInstance + Sync + Send>, argument : PluginArgument, relaunch : bool, timeout : Option<Duration>, priority : Option<Priority>,
+          waiter : Option<Sender<TaskResult>>) -> Result<TaskId, Error>
+  {
+    let task = self.register(plugin, argument, relaunch, timeout, priority, waiter)?;
+    let id = task.0.id;
+    //send new task to the pool
+    self.pools.push(task);
+    Ok(id)
+  }
+
   /// Create a new [task](Task) and add it to the the tasks list, if a waiter is present we will send it a message when the task is finished.
-  fn push(&self, plugin: Box<dyn PluginInstance + Sync + Send>, argument : PluginArgument, relaunch : bool, waiter : Option<Sender<TaskResult>>) -> Result<TaskId, Error>
+  /// The task use `timeout` and `priority` or the ones of its plugin.
+  fn register(&self, plugin: Box<dyn PluginInstance + Sync + Send>, argument : PluginArgument, relaunch : bool, timeout : Option<Duration>, priority : Option<Priority>,
+              waiter : Option<Sender<TaskResult>>) -> Result<QueuedTask, Error>
   {
     if relaunch || !self.exist(plugin.name(), &argument)
     {
       let mut tasks = self.tasks.write().unwrap();
-      let task_id = tasks.len() + 1;
-      let task = Task{ plugin_name : plugin.name().to_string(), argument, id : task_id as u32 };
+      let task_id =
+      {
+        let mut next_id = self.next_id.write().unwrap();
+        *next_id += 1;
+        *next_id - 1
+      };
+      let timeout = timeout.or_else(|| self.timeouts.read().unwrap().get(plugin.name()).copied());
+      let priority = priority.or_else(|| self.priorities.read().unwrap().get(plugin.name()).copied()).unwrap_or_default();
+      //a plugin routed to an unknown pool is run by the default pool
+      let pool = self.routes.read().unwrap().get(plugin.name()).cloned().or_else(|| plugin.pool().map(String::from))
+                   .filter(|pool| pool != DEFAULT_POOL && self.pools.pools.contains_key(pool));
+      let task = Task{ plugin_name : plugin.name().to_string(), argument, id : task_id, summary : None, progress : None, timeout, priority, pool };
       //XXX rather send a message to thread so it update the state herself ?
-      tasks.insert(task_id as u32, TaskState::Waiting(task.clone()));
-
-      //send new task to the pool
-      self.new_task.send((task, plugin, waiter)).unwrap();
-      Ok(task_id as u32)
+      tasks.insert(task_id, TaskState::Waiting(task.clone()));
+      self.events.update(TaskEvent::Waiting(task.id));
+      let cancellation = CancellationToken::new();
+      self.cancellations.write().unwrap().insert(task.id, cancellation.clone());
+      if let Some(waiter) = waiter
+      {
+        self.waiters.lock().unwrap().insert(task.id, waiter);
+      }
+      Ok((task, plugin, cancellation))
     } else {
       Err(RustructError::PluginAlreadyRunned.into())
     }
@@ -161,30 +1139,145 @@ impl TaskScheduler
   /// Create a new task and schedule it to be launched, return a task id or an error if task already exist.
   pub fn schedule(&self, plugin: Box<dyn PluginInstance + Sync + Send>, argument : PluginArgument, relaunch : bool) -> Result<TaskId, Error>
   {
-    self.push(plugin, argument, relaunch, None)
+    self.push(plugin, argument, relaunch, None, None, None)
+  }
+
+  /// [Schedule](TaskScheduler::schedule) a task launched before the waiting tasks of lower `priority`.
+  pub fn schedule_with_priority(&self, plugin: Box<dyn PluginInstance + Sync + Send>, argument : PluginArgument, relaunch : bool, priority : Priority) -> Result<TaskId, Error>
+  {
+    self.push(plugin, argument, relaunch, None, Some(priority), None)
+  }
+
+  /// Create a new task launched once the tasks `dependencies` are finished, return a task id or an error if the task already exist or a dependency is not found.
+  /// If a dependency fails or is cancelled, the task is finished without being run or still run according to `policy`.
+  pub fn schedule_after(&self, dependencies : &[TaskId], plugin: Box<dyn PluginInstance + Sync + Send>, argument : PluginArgument, relaunch : bool,
+                        policy : FailurePolicy) -> Result<TaskId, Error>
+  {
+    if let Some(dependency) = dependencies.iter().find(|dependency| !self.tasks.read().unwrap().contains_key(dependency))
+    {
+      return Err(RustructError::TaskNotFound(*dependency).into())
+    }
+    let task = self.register(plugin, argument, relaunch, None, None, None)?;
+    let id = task.0.id;
+    if let Some(failed) = self.deferred.defer(task, dependencies, policy, &self.tasks)
+    {
+      self.task_state.send(failed).unwrap();
+    }
+    Ok(id)
+  }
+
+  /// [Schedule](TaskScheduler::schedule) a task cancelled if it runs longer than `timeout`.
+  pub fn schedule_with_timeout(&self, plugin: Box<dyn PluginInstance + Sync + Send>, argument : PluginArgument, relaunch : bool, timeout : Duration) -> Result<TaskId, Error>
+  {
+    self.push(plugin, argument, relaunch, Some(timeout), None, None)
   }
 
   /// Create a new [task](Task) and block until the [task](Task) is finished, return a [plugin result](PluginResult) or an error, if [task](Task) exist or if execution of the [task](Task) failed.
   pub fn run(&self, plugin : Box<dyn PluginInstance + Sync + Send>, argument : PluginArgument, relaunch : bool) -> Result<PluginResult, Arc<Error>>
+  {
+    self.run_until(plugin, argument, relaunch, None)
+  }
+
+  /// [Run](TaskScheduler::run) a task cancelled if it runs longer than `timeout`,
+  /// a [TimedOut](RustructError::TimedOut) error is returned after the grace period even if the plugin doesn't stop.
+  pub fn run_with_timeout(&self, plugin : Box<dyn PluginInstance + Sync + Send>, argument : PluginArgument, relaunch : bool, timeout : Duration) -> Result<PluginResult, Arc<Error>>
+  {
+    self.run_until(plugin, argument, relaunch, Some(timeout))
+  }
+
+  fn run_until(&self, plugin : Box<dyn PluginInstance + Sync + Send>, argument : PluginArgument, relaunch : bool, timeout : Option<Duration>) -> Result<PluginResult, Arc<Error>>
   {
     let (sender, receiver) = bounded(1);
-    let result = self.push(plugin, argument, relaunch, Some(sender));
+    let result = self.push(plugin, argument, relaunch, timeout, None, Some(sender));
     
     match result
     {
-      Ok(_id) => receiver.recv().unwrap(),
+      Ok(id) =>
+      {
+        let result = receiver.recv().unwrap();
+        //wait for the task map to be updated so the task summary is available
+        self.join_tasks(&[id]);
+        result
+      },
       Err(err) => Err(Arc::new(err)), //send it as a module error but it's a TaskSched error
     }
   }
 
+  /// Schedule plugin `name` after running the plugins it [depends](crate::plugin::Dependency::Plugin) on, in the order resolved by the `plugins_db`.
+  /// Dependencies are run with the same `argument`, a dependency already scheduled with this argument is waited rather than relaunched.
+  /// Return an error if a dependency can't be resolved or failed.
+  pub fn schedule_with_dependencies(&self, plugins_db : &PluginsDB, name : &str, argument : PluginArgument, relaunch : bool) -> Result<TaskId, Error>
+  {
+    let mut order = plugins_db.resolve(&[name])?;
+    let name = order.pop().unwrap();
+    for dependency in order
+    {
+      let result = match self.find(dependency, &argument)
+      {
+        Some(id) => self.wait(id, None),
+        None => self.run(plugins_db.instantiate(dependency).unwrap(), argument.clone(), false),
+      };
+      if let Err(err) = result
+      {
+        return Err(RustructError::DependencyFailed(dependency.to_string(), err.to_string()).into())
+      }
+    }
+    self.schedule(plugins_db.instantiate(name).unwrap(), argument, relaunch)
+  }
+
+  /// Block until task `id` is finished or `timeout` elapsed and return its [result](TaskScheduler::result),
+  /// a [TaskNotFinished](RustructError::TaskNotFinished) error if the timeout elapsed or a [TaskNotFound](RustructError::TaskNotFound) error.
+  pub fn wait(&self, id : TaskId, timeout : Option<Duration>) -> TaskResult
+  {
+    //subscribed before checking the state so the end of the task can't be missed
+    let events = self.events.subscribe();
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop
+    {
+      match self.state(id)
+      {
+        Some(TaskState::Finished(..)) | None => return self.result(id),
+        Some(_) => (),
+      }
+      let received = match deadline
+      {
+        Some(deadline) => events.receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())).is_ok(),
+        None => events.receiver.recv().is_ok(),
+      };
+      if !received
+      {
+        return self.result(id)
+      }
+    }
+  }
+
+  /// Block until the tasks `ids` are finished, the tasks scheduled by others are not waited unlike [join](TaskScheduler::join).
+  pub fn join_tasks(&self, ids : &[TaskId])
+  {
+    ids.iter().for_each(|id| { let _ = self.wait(*id, None); });
+  }
+
+  /// Return the [result](TaskScheduler::result) of task `id` without blocking, or `None` if the task is not finished yet.
+  pub fn try_result(&self, id : TaskId) -> Option<TaskResult>
+  {
+    match self.state(id)
+    {
+      Some(TaskState::Waiting(_)) | Some(TaskState::Launched(_)) => None,
+      _ => Some(self.result(id)),
+    }
+  }
+
   /// Check if all [task](Task) in the `tasks` [map](HashMap) are finished.
   pub fn tasks_are_finished(&self) -> bool
   {
     let tasks = self.tasks.read().unwrap();
-    for task in tasks.values()
+    let restored = self.restored.read().unwrap();
+    for (id, task) in tasks.iter()
     {
       match task
       {
+        //restored tasks are never run, they are finished only when repaired or cancelled
+        _ if restored.contains(id) => (),
         TaskState::Waiting(_) => return false,
         TaskState::Launched(_) => return false,
         TaskState::Finished(_, _) => (),
@@ -193,43 +1286,233 @@ impl TaskScheduler
     true 
   }
 
-  /// Wait until all scheduled [task](Task) are finished.
-  // if an other thread add task to the scheduler, a thread could wait for task to join
-  // be will be to have a join([task_id]) so we sure we wait only on our created tasks 
-  pub fn join(&self) 
+  /// Wait until all scheduled [task](Task) are finished.
+  /// If an other thread add tasks to the scheduler they are waited too, use [join_tasks](TaskScheduler::join_tasks) or a [TaskGroup] to wait only on our created tasks.
+  pub fn join(&self) 
+  {
+    if self.tasks_are_finished()
+    {
+      return 
+    }
+
+    for _ in self.task_update.iter()
+    {
+      //match if task is finished we can check if all are finished
+      if self.tasks_are_finished()
+      {
+        break
+      }
+    }
+  }
+
+  /// Cancel task `id` : a waiting task is finished with a [Cancelled](RustructError::Cancelled) error without being run,
+  /// a running task is notified through its [PluginEnvironment] and stop when its plugin check it.
+  /// Return false if the task is already finished.
+  pub fn cancel(&self, id : TaskId) -> Result<bool>
+  {
+    if self.restored.read().unwrap().contains(&id) && !matches!(self.state(id), Some(TaskState::Finished(..)))
+    {
+      self.fail_task(id, RustructError::Cancelled.into())?;
+      return Ok(true)
+    }
+    if let Some((task, _, cancellation)) = self.deferred.remove(id)
+    {
+      cancellation.cancel();
+      self.task_state.send(TaskState::Finished(task, Err(Arc::new(RustructError::Cancelled.into())))).unwrap();
+      return Ok(true)
+    }
+
+    match self.cancellations.read().unwrap().get(&id)
+    {
+      Some(cancellation) => Ok(cancellation.cancel()),
+      None if self.tasks.read().unwrap().contains_key(&id) => Ok(false),
+      None => Err(RustructError::TaskNotFound(id).into()),
+    }
+  }
+
+  /// Return a [TaskState] corresponding to a task id, results stored on disk are read back from the [ResultStore].
+  pub fn task(&self, id : TaskId) -> Option<TaskState>
+  {
+    self.state(id).map(|state| self.results.load_state(state))
+  }
+
+  /// Return the [TaskState] of task `id` as kept in the `tasks` map, without reading back its stored result.
+  fn state(&self, id : TaskId) -> Option<TaskState>
+  {
+    self.tasks.read().unwrap().get(&id).cloned()
+  }
+
+  /// Return the [result](TaskResult) of task `id`, results stored on disk are read back from the [ResultStore].
+  pub fn result(&self, id : TaskId) -> TaskResult
+  {
+    match self.task(id)
+    {
+      Some(TaskState::Finished(_, result)) => result,
+      Some(_) => Err(Arc::new(RustructError::TaskNotFinished(id).into())),
+      None => Err(Arc::new(RustructError::TaskNotFound(id).into())),
+    }
+  }
+
+  /// Set the size from which results are stored on disk, or keep all results in memory if `None`.
+  /// Default to [RESULT_THRESHOLD](crate::result_store::RESULT_THRESHOLD).
+  pub fn set_result_threshold(&self, threshold : Option<usize>)
+  {
+    self.results.set_threshold(threshold);
+  }
+
+  /// Return the number and size of the results kept in memory and stored on disk.
+  pub fn result_stats(&self) -> ResultStats
+  {
+    let mut stats = ResultStats::default();
+    for state in self.tasks.read().unwrap().values()
+    {
+      if let TaskState::Finished(task, Ok(result)) = state
+      {
+        if !self.results.contains(task.id)
+        {
+          stats.in_memory += 1;
+          stats.in_memory_bytes += result.len() as u64;
+        }
+      }
+    }
+    (stats.stored, stats.stored_bytes) = self.results.stats();
+    stats
+  }
+
+  /// Return the [Validator] checking the nodes created by the tasks.
+  pub fn validator(&self) -> Arc<Validator>
+  {
+    self.validator.clone()
+  }
+
+  /// Return the [Tagger] tagging the nodes created by the tasks.
+  pub fn tagger(&self) -> Arc<Tagger>
+  {
+    self.tagger.clone()
+  }
+
+  /// Return the [ComputedAttributes] added to the nodes created by the tasks.
+  pub fn computed(&self) -> Arc<ComputedAttributes>
+  {
+    self.computed.clone()
+  }
+
+  /// Return the number of tasks waiting for a free worker in all the pools.
+  pub fn queue_len(&self) -> usize
+  {
+    self.pools.pools.values().map(|(queue, _)| queue.len()).sum()
+  }
+
+  /// Return the id of the running tasks and for how long they are running.
+  pub fn running(&self) -> Vec<(TaskId, Duration)>
   {
-    if self.tasks_are_finished()
+    let mut running : Vec<(TaskId, Duration)> = self.started.read().unwrap().iter().map(|(id, start)| (*id, start.elapsed())).collect();
+    running.sort_unstable_by_key(|(id, _)| *id);
+    running
+  }
+
+  /// Return the last progress reported by task `id`, if it's running or finished and its plugin reported it.
+  pub fn progress(&self, id : TaskId) -> Option<TaskProgress>
+  {
+    match self.tasks.read().unwrap().get(&id)?
     {
-      return 
+      TaskState::Waiting(_) => None,
+      TaskState::Launched(task) | TaskState::Finished(task, _) => task.progress.clone(),
     }
+  }
 
-    for _ in self.task_update.iter()
+  /// Return the channel sending the tasks transitions, [subscribe_from](EventChannel::subscribe_from) replay the recent transitions.
+  pub fn events(&self) -> &EventChannel<TaskEvent>
+  {
+    &self.events
+  }
+
+  /// Explain why task `id` is or is not running, return [RustructError::TaskNotFound] if there is no such task.
+  pub fn explain(&self, id : TaskId) -> Result<TaskExplanation>
+  {
+    let blocked = self.deferred.pending();
+    let tasks = self.tasks.read().unwrap();
+    let explanation = match tasks.get(&id)
     {
-      //match if task is finished we can check if all are finished
-      if self.tasks_are_finished()
+      None => return Err(RustructError::TaskNotFound(id).into()),
+      Some(TaskState::Finished(_, result)) => TaskExplanation::Finished{ error : result.as_ref().err().map(|err| err.to_string()) },
+      Some(_) if self.restored.read().unwrap().contains(&id) => TaskExplanation::Orphaned,
+      Some(TaskState::Launched(_)) => match self.started.read().unwrap().get(&id)
       {
-        break
-      }
-    }
+        Some(start) => TaskExplanation::Running{ running_for : start.elapsed() },
+        None => TaskExplanation::Running{ running_for : Duration::ZERO },
+      },
+      Some(TaskState::Waiting(_)) if blocked.contains_key(&id) => TaskExplanation::Blocked{ dependencies : blocked[&id].clone() },
+      Some(TaskState::Waiting(task)) =>
+      {
+        //tasks are sent to the workers of their pool by order of priority then of id
+        let restored = self.restored.read().unwrap();
+        let key = (task.priority, Reverse(id));
+        let position = tasks.iter().filter(|(other, state)| match state
+        {
+          TaskState::Waiting(other_task) => other_task.pool == task.pool && (other_task.priority, Reverse(**other)) > key && !restored.contains(other) && !blocked.contains_key(other),
+          _ => false,
+        }).count();
+        let running = self.started.read().unwrap().keys().filter(|running| matches!(tasks.get(running), Some(TaskState::Launched(other_task)) if other_task.pool == task.pool)).count();
+        TaskExplanation::Queued{ position, running, workers : self.pools.pools[task.pool.as_deref().unwrap_or(DEFAULT_POOL)].1 }
+      },
+    };
+    Ok(explanation)
   }
 
-  /// Return a [TaskState] corresponding to a task id.
-  pub fn task(&self, id : TaskId) -> Option<TaskState>
+  /// Return the contention of the lock of the tasks map.
+  pub fn lock_stats(&self) -> LockStats
   {
-    self.tasks.read().unwrap().get(&id).cloned()
+    probe_lock("tasks", &self.tasks)
   }
 
   /// Return a vec of [TaskState] for corresponding task id.
   pub fn tasks(&self, ids : Vec<TaskId>) -> Vec<TaskState>
   {
-    let tasks = self.tasks.read().unwrap();
-    ids.iter().filter_map(|id| tasks.get(id).cloned()).collect()
+    ids.iter().filter_map(|id| self.task(*id)).collect()
   }
 
   /// Return a copy of all the [task state](TaskState) for all [task](Task) in the `tasks` map.
   pub fn to_vec(&self) -> Vec<TaskState>
   {
-    self.tasks.read().unwrap().values().cloned().collect()  
+    let states : Vec<TaskState> = self.tasks.read().unwrap().values().cloned().collect();
+    states.into_iter().map(|state| self.results.load_state(state)).collect()
+  }
+
+  /// Insert the [task states](TaskState) of a previously saved session, restored tasks are not run.
+  /// Tasks registered after are given ids greater than the ids of the restored tasks.
+  pub fn restore(&self, states : Vec<TaskState>)
+  {
+    let mut tasks = self.tasks.write().unwrap();
+    let mut next_id = self.next_id.write().unwrap();
+    for state in states
+    {
+      let id = match &state
+      {
+        TaskState::Waiting(task) | TaskState::Launched(task) | TaskState::Finished(task, _) => task.id,
+      };
+      if !matches!(state, TaskState::Finished(..))
+      {
+        self.restored.write().unwrap().insert(id);
+      }
+      *next_id = (*next_id).max(id + 1);
+      tasks.insert(id, self.results.store_state(state));
+    }
+  }
+
+  /// Mark task `id` as finished with `error`, this is used to repair tasks restored from a saved session that can't be run anymore.
+  /// The task is finished like the tasks run by the workers so the threads waiting on it are woken up, finished tasks are left unchanged.
+  pub fn fail_task(&self, id : TaskId, error : Error) -> Result<()>
+  {
+    let task = match self.state(id)
+    {
+      Some(TaskState::Waiting(task)) | Some(TaskState::Launched(task)) => task,
+      Some(TaskState::Finished(..)) => return Ok(()),
+      None => return Err(RustructError::TaskNotFound(id).into()),
+    };
+    self.task_state.send(TaskState::Finished(task, Err(Arc::new(error))))?;
+    self.join_tasks(&[id]);
+    Ok(())
   }
 
   /// Return the current count of [tasks](TaskState) added to the [scheduler](TaskScheduler).
@@ -241,12 +1524,18 @@ impl TaskScheduler
   /// Return all finished [task](TaskState) and their [result](TaskResult).
   pub fn tasks_finished(&self) -> Vec<(Task, TaskResult)>
   {
-     self.tasks.read().unwrap().values().filter_map(|task| match task { TaskState::Finished(task, res) => Some((task.clone(), res.clone())), _ => None} ).collect()
+     self.to_vec().into_iter().filter_map(|task| match task { TaskState::Finished(task, res) => Some((task, res)), _ => None} ).collect()
   }
 
   /// Check if a task with for same plugin and argument was already added to the scheduler.
   /// That's used to avoid relaunching same task twice.
   fn exist(&self, plugin_name : &str, argument : &str) -> bool
+  {
+    self.find(plugin_name, argument).is_some()
+  }
+
+  /// Return the id of a task running `plugin_name` with `argument`.
+  fn find(&self, plugin_name : &str, argument : &str) -> Option<TaskId>
   {
     for task_state in self.tasks.read().unwrap().values()
     {
@@ -256,74 +1545,162 @@ impl TaskScheduler
         {
           if plugin_name == task.plugin_name && argument == task.argument
           {
-            return true
+            return Some(task.id)
           }
         }
       }
     }
-    false
+    None
+  }
+}
+
+impl Drop for TaskScheduler
+{
+  /// Stop the workers once they launched the tasks still queued.
+  fn drop(&mut self)
+  {
+    self.pools.pools.values().for_each(|(queue, _)| queue.close());
   }
 }
 
 /**
  * A worker for running a [plugin instance](PluginInstance).
  **/
+#[derive(Clone)]
 pub struct Worker
 {
   /// Worker unique id.
   id : usize,
   /// Reference to the TAP Tree.
   tree : Tree,
-  /// Receive new Task to execute on that channel.
-  receiver : Receiver<(Task, BoxPluginInstance, Option<Sender<TaskResult>>)>,
+  /// Queue of the tasks to execute.
+  queue : Arc<TaskQueue>,
   /// Send result of a Task on that channel.
   sender : Sender<TaskState>,
+  /// Node under which run summary are added if enabled.
+  reports : Arc<RwLock<Option<TreeNodeId>>>,
+  /// Profiler used to sample the task execution if enabled.
+  profiler : Arc<Profiler>,
+  /// Context passed to the plugins.
+  context : Arc<RwLock<CaseContext>>,
+  /// Store where the plugins stage their artifacts.
+  blob_store : Arc<RwLock<BlobStore>>,
+  /// Block cache shared by the tasks.
+  block_cache : Arc<BlockCache>,
+  /// Services passed to the plugins.
+  services : Arc<RwLock<Services>>,
+  /// Check the nodes created by the task if live validation is enabled.
+  validator : Arc<Validator>,
+  /// Tag the nodes created by the task.
+  tagger : Arc<Tagger>,
+  /// Add the computed attributes to the nodes created by the task.
+  computed : Arc<ComputedAttributes>,
+  /// Senders notified of the result of the tasks.
+  waiters : Waiters,
+  /// Claims of the running tasks, the worker of a task given up by the watchdog stop when the task return.
+  claims : Claims,
+  /// Quota of the instances of each plugin.
+  quotas : Arc<RwLock<HashMap<String, Quota>>>,
+  /// Send the quotas exceeded by the tasks.
+  quota_events : EventChannel<QuotaEvent>,
+  /// Log of the launched tasks.
+  logs : Arc<RwLock<TaskLogs>>,
 }
 
 impl Worker
 {
-  /// Return a new [Worker].
-  fn new(id : usize, tree : Tree, receiver : Receiver<(Task, BoxPluginInstance, Option<Sender<TaskResult>>)>, sender : Sender<TaskState>) -> Self
+
+  /// Add the [RunSummary] of `task` as a report node if enabled.
+  fn report(&self, task : &mut Task, summary : RunSummary)
   {
-    Worker{id, tree, receiver, sender}
+    if let Some(reports_id) = *self.reports.read().unwrap()
+    {
+      let node = summary.to_node(format!("{}_{}", task.plugin_name, task.id));
+      if let Err(err) = self.tree.add_child(reports_id, node)
+      {
+        info!("can't add run report for task {}({}) : {}", task.plugin_name, task.id, err);
+      }
+    }
+    task.summary = Some(summary);
   }
 
-  fn find_task(&self) -> (Task, BoxPluginInstance, Option<Sender<TaskResult>>)
+  /// Return the [ResourceUsage] of `task` limited by the quota of its plugin.
+  fn resources(&self, task : &Task) -> ResourceUsage
   {
-     loop
-     {
-       if let Ok(task) = self.receiver.recv()
-       {
-          return task;
-       }
-     }
+    let quota = self.quotas.read().unwrap().get(&task.plugin_name).copied().unwrap_or_default();
+    ResourceUsage::new(task.plugin_name.clone(), Some(task.id), quota, Some(self.quota_events.clone()))
   }
 
-  /// Loop and wait to receive a new task through the `receiver` channel then execute the plugin and send it's return value (result) via the `sender` channel.
+  /// Loop and wait to receive a new task from the `queue` then execute the plugin and send it's return value (result) via the `sender` channel.
+  /// Return when the scheduler is dropped.
   fn run(&self)
   {
-    loop
+    while let Some((mut task, mut plugin_instance, cancellation)) = self.queue.pop()
     {
-      let (task, mut plugin_instance, waiter) = self.find_task();
       self.sender.send(TaskState::Launched(task.clone())).unwrap();
       info!("task runned : {}({}) {} on worker {}", task.plugin_name, task.id, task.argument, self.id);
 
+      let logger = TaskLogger::new(task.plugin_name.clone());
+      self.logs.write().unwrap().insert(task.id, logger.clone());
+
       //add nodes to tree here if tree is not passed to modules
-      let environment = PluginEnvironment::new(self.tree.clone(), Some(self.sender.clone()));
+      let (tree, recorder) = self.tree.recorder();
+      let environment = PluginEnvironment::new(tree, Some(self.sender.clone())).with_context(self.context.read().unwrap().clone())
+                                                                                .with_blob_store(self.blob_store.read().unwrap().clone())
+                                                                                .with_block_cache(self.block_cache.clone())
+                                                                                .with_task(task.clone())
+                                                                                .with_cancellation(cancellation.clone())
+                                                                                .with_resources(self.resources(&task))
+                                                                                .with_logger(logger.clone())
+                                                                                .with_services(self.services.read().unwrap().clone());
       //pass sender to modules to update state with more info ? 
 
+      let sampling = self.profiler.start();
+      let start = Instant::now();
+
       //we catch unwindable panic in thread running plugin assuming no use of unsafe code
       let panic = std::panic::catch_unwind(AssertUnwindSafe(|| 
       {
-        plugin_instance.run(task.argument.clone(), environment)
+        //the files opened by the plugin are accounted to it by the metered builders
+        let _consumer = as_consumer(task.plugin_name.clone());
+        let _capture = capture_as(logger);
+        //a task cancelled while waiting is not run
+        cancellation.check()?;
+        plugin_instance.init(&environment)?;
+        //teardown is called even if run panicked, the panic is then caught as the other panics
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| plugin_instance.run(task.argument.clone(), environment)));
+        plugin_instance.teardown();
+        result.unwrap_or_else(|panic| std::panic::resume_unwind(panic))
       }));
 
+      if let Some(sampling) = sampling
+      {
+        self.profiler.finish(task.id, sampling);
+      }
+
       let result = match panic
       {
         Ok(result) => result,
         Err(err) => Err(anyhow::anyhow!("Error thread of task {}({}) {} panicked : {:?}", task.plugin_name, task.id, task.argument, err))
       };
 
+      //the task was finished by the watchdog and this worker replaced, else it's claimed so the watchdog can't give it up while it's post processed
+      {
+        let mut claims = self.claims.lock().unwrap();
+        if claims.remove(&task.id) == Some(Claim::Watchdog)
+        {
+          info!("task given up : {}({}) returned on worker {}", task.plugin_name, task.id, self.id);
+          return
+        }
+        claims.insert(task.id, Claim::Worker);
+      }
+      let result = match (result, task.timeout)
+      {
+        (Err(err), Some(timeout)) if cancellation.is_cancelled() && start.elapsed() >= timeout && matches!(err.downcast_ref(), Some(RustructError::Cancelled)) =>
+          Err(RustructError::TimedOut(task.id, timeout).into()),
+        (result, _) => result,
+      };
+
       let result = match result
       {
         Ok(result) => 
@@ -340,7 +1717,19 @@ impl Worker
       
       //info!("task finished : {}({}) {:?}", task.plugin_name, task.id);
       //info!("result for task : {}({}) {:?}", task.plugin_name, task.id, result);
-      if let Some(waiter) = waiter
+      let nodes = recorder.nodes();
+      //summary is created before the post processing so it only account the attributes added by the plugin
+      let mut summary = RunSummary::new(&self.tree, &nodes);
+      self.computed.apply(&self.tree, &nodes);
+      if self.validator.is_live()
+      {
+        self.validator.validate_nodes(&self.tree, &nodes, Some(&task.plugin_name));
+      }
+      self.tagger.tag_nodes(&self.tree, &nodes);
+      summary.count_warnings(&self.tree, &nodes);
+      self.report(&mut task, summary);
+      //the waiter is notified once the nodes are post processed so they are complete when run return
+      if let Some(waiter) = self.waiters.lock().unwrap().remove(&task.id)
       {
         waiter.send(result.clone()).unwrap()
       }
@@ -353,10 +1742,13 @@ impl Worker
 #[cfg(test)]
 mod tests
 {
-    use super::TaskScheduler;
-    use crate::plugin::PluginInfo;
+    use super::{TaskScheduler, TaskSchedulerBuilder, TaskState, TaskExplanation, TaskEvent, Task, Priority, FailurePolicy, DEFAULT_POOL};
+    use crate::plugin::{PluginInfo, PluginInstance, PluginArgument, PluginResult, PluginEnvironment};
     use crate::plugin_dummy;
     use crate::tree::Tree;
+    use crate::value::Value;
+    use crate::validation::{Rule, Check};
+    use crate::error::RustructError;
 
     use serde_json::json;
 
@@ -385,4 +1777,524 @@ mod tests
          () //we launch the same plugins 24 times, so must return result with error
        }
     }
+
+    #[test]
+    fn run_summary()
+    {
+       let tree = Tree::new();
+       let scheduler = TaskScheduler::new(tree.clone());
+       scheduler.set_run_reports(true).unwrap();
+       scheduler.validator().add_rule(Rule::new("first_offset", "offset", Check::Equal(Value::U64(1))));
+       scheduler.validator().set_live(true);
+
+       let plugin = plugin_dummy::Plugin::new().instantiate();
+       let arg = json!({ "parent" : tree.root_id, "file_name" : "/home/user/test.txt", "offset" : 0});
+       let id = scheduler.schedule(plugin, arg.to_string(), false).unwrap();
+       scheduler.join();
+
+       let summary = match scheduler.task(id).unwrap()
+       {
+         TaskState::Finished(task, _) => task.summary.unwrap(),
+         _ => panic!("task is not finished"),
+       };
+       assert!(summary.nodes_created == 4);
+       assert!(summary.attributes["offset"] == 1);
+       assert!(!summary.attributes.contains_key("warnings"));
+       assert!(summary.warnings == 1);
+
+       let report = tree.get_node("/root/Reports/dummy_1").unwrap();
+       assert!(report.value().get_value("nodes_created").unwrap().as_u64() == 4);
+
+       //the summary is available when run return
+       let arg = json!({ "parent" : tree.root_id, "file_name" : "/home/user/test2.txt", "offset" : 0});
+       scheduler.run(plugin_dummy::Plugin::new().instantiate(), arg.to_string(), false).unwrap();
+       assert!(matches!(scheduler.task(id + 1), Some(TaskState::Finished(task, _)) if task.summary.as_ref().is_some_and(|summary| summary.warnings == 1)));
+       assert!(tree.get_node("/root/Reports/dummy_2").is_some());
+       assert!(report.value().get_value("warnings").unwrap().as_u64() == 1);
+
+       let log = scheduler.task_logs(id).unwrap();
+       assert!(log.records.last().is_some_and(|record| record.target == "dummy" && record.message == "dummy finished with counter 1"));
+       assert!(scheduler.task_logs(id + 2).is_none());
+    }
+
+    #[test]
+    fn stored_results()
+    {
+       let tree = Tree::new();
+       let scheduler = TaskScheduler::new(tree.clone());
+       scheduler.set_result_threshold(Some(1));
+
+       let plugin = plugin_dummy::Plugin::new().instantiate();
+       let arg = json!({ "parent" : tree.root_id, "file_name" : "/home/user/test.txt", "offset" : 0});
+       let result = scheduler.run(plugin, arg.to_string(), false).unwrap();
+       scheduler.join();
+
+       assert!(matches!(scheduler.tasks.read().unwrap().get(&1), Some(TaskState::Finished(_, Ok(in_memory))) if in_memory.is_empty()));
+       assert!(scheduler.result(1).unwrap() == result);
+       assert!(matches!(scheduler.task(1), Some(TaskState::Finished(_, Ok(stored))) if stored == result));
+       assert!(matches!(&scheduler.to_vec()[..], [TaskState::Finished(_, Ok(stored))] if *stored == result));
+       assert!(matches!(&scheduler.tasks_finished()[..], [(_, Ok(stored))] if *stored == result));
+       assert!(scheduler.result(2).is_err());
+
+       let stats = scheduler.result_stats();
+       assert!(stats.in_memory == 0 && stats.stored == 1);
+       assert!(stats.stored_bytes == result.len() as u64);
+    }
+
+    #[test]
+    fn explain_tasks()
+    {
+       let tree = Tree::new();
+       let scheduler = TaskScheduler::new(tree.clone());
+       let arg = json!({ "parent" : tree.root_id, "file_name" : "/home/user/test.txt", "offset" : 0}).to_string();
+       let first = scheduler.schedule(plugin_dummy::Plugin::new().instantiate(), arg, false).unwrap();
+       let second = scheduler.schedule(plugin_dummy::Plugin::new().instantiate(), json!({ "file_name" : "/home/user/test.txt", "offset" : 0}).to_string(), false).unwrap();
+       assert!(matches!(scheduler.explain(second).unwrap(), TaskExplanation::Queued{ .. } | TaskExplanation::Running{ .. } | TaskExplanation::Finished{ .. }));
+       scheduler.join();
+
+       assert!(scheduler.explain(first).unwrap() == TaskExplanation::Finished{ error : None });
+       assert!(matches!(scheduler.explain(second).unwrap(), TaskExplanation::Finished{ error : Some(_) }));
+       assert!(scheduler.explain(100).is_err());
+
+       let task = Task{ id : 100, plugin_name : "dummy".into(), argument : "{}".into(), summary : None, progress : None, timeout : None, priority : Priority::Normal, pool : None };
+       scheduler.restore(vec![TaskState::Waiting(task)]);
+       assert!(scheduler.explain(100).unwrap() == TaskExplanation::Orphaned);
+    }
+
+    #[test]
+    fn replay_task_events()
+    {
+       let tree = Tree::new();
+       let scheduler = TaskScheduler::new(tree.clone());
+       let arg = json!({ "parent" : tree.root_id, "file_name" : "/home/user/test.txt", "offset" : 0}).to_string();
+       let id = scheduler.schedule(plugin_dummy::Plugin::new().instantiate(), arg, false).unwrap();
+       scheduler.join();
+
+       let events : Vec<TaskEvent> = scheduler.events().subscribe_from(1).events().into_iter().map(|event| event.event).collect();
+       assert!(events == vec![TaskEvent::Waiting(id), TaskEvent::Launched(id), TaskEvent::Finished{ id, success : true }]);
+       assert!(scheduler.events().last_seq() == 3);
+    }
+
+    struct ProgressPlugin;
+
+    impl PluginInstance for ProgressPlugin
+    {
+      fn name(&self) -> &'static str
+      {
+        "progress"
+      }
+
+      fn run(&mut self, _argument : PluginArgument, env : PluginEnvironment) -> anyhow::Result<PluginResult>
+      {
+        env.progress(10.0, "reading");
+        for done in 1..=1000
+        {
+          env.report_items(done, 1000);
+        }
+        Ok("{}".into())
+      }
+    }
+
+    #[test]
+    fn report_progress()
+    {
+       let scheduler = TaskScheduler::new(Tree::new());
+       let id = scheduler.schedule(Box::new(ProgressPlugin), "{}".into(), false).unwrap();
+       scheduler.join();
+
+       let progress = scheduler.progress(id).unwrap();
+       assert!(progress.percent == 100.0 && progress.items == Some((1000, 1000)) && progress.message.as_deref() == Some("reading"));
+       let events : Vec<TaskEvent> = scheduler.events().subscribe_from(1).events().into_iter().map(|event| event.event).collect();
+       //reports are throttled, the first and the last one are always sent
+       let reports = events.iter().filter(|event| **event == TaskEvent::Progress(id)).count();
+       assert!((2..1000).contains(&reports));
+       assert!(events.first() == Some(&TaskEvent::Waiting(id)) && events.last() == Some(&TaskEvent::Finished{ id, success : true }));
+       assert!(matches!(scheduler.explain(id).unwrap(), TaskExplanation::Finished{ error : None }));
+    }
+
+    struct LoopPlugin;
+
+    impl PluginInstance for LoopPlugin
+    {
+      fn name(&self) -> &'static str
+      {
+        "loop"
+      }
+
+      fn run(&mut self, _argument : PluginArgument, env : PluginEnvironment) -> anyhow::Result<PluginResult>
+      {
+        let (sender, receiver) = crossbeam::crossbeam_channel::bounded(1);
+        env.on_cancel(move || { let _ = sender.send(()); });
+        while !env.is_cancelled()
+        {
+          std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        receiver.recv()?;
+        env.cancellation.check()?;
+        Ok("{}".into())
+      }
+    }
+
+    #[test]
+    fn cancel_task()
+    {
+       let scheduler = TaskScheduler::new(Tree::new());
+       let id = scheduler.schedule(Box::new(LoopPlugin), "{}".into(), false).unwrap();
+       while !matches!(scheduler.task(id), Some(TaskState::Launched(_)))
+       {
+         std::thread::sleep(std::time::Duration::from_millis(1));
+       }
+       assert!(scheduler.cancel(id).unwrap() && !scheduler.cancel(id).unwrap());
+       scheduler.join();
+
+       let error = scheduler.result(id).unwrap_err();
+       assert!(matches!(error.downcast_ref::<RustructError>(), Some(RustructError::Cancelled)));
+       assert!(!scheduler.cancel(id).unwrap() && scheduler.cancel(100).is_err());
+
+       let task = Task{ id : 100, plugin_name : "loop".into(), argument : "{}".into(), summary : None, progress : None, timeout : None, priority : Priority::Normal, pool : None };
+       scheduler.restore(vec![TaskState::Waiting(task)]);
+       assert!(scheduler.cancel(100).unwrap() && scheduler.result(100).is_err());
+    }
+
+    #[test]
+    fn restore_keep_task_ids()
+    {
+       let scheduler = TaskScheduler::new(Tree::new());
+       let task = |id| Task{ id, plugin_name : "loop".into(), argument : format!("{{\"id\":{}}}", id), summary : None, progress : None, timeout : None, priority : Priority::Normal, pool : None };
+       scheduler.restore(vec![TaskState::Waiting(task(5)), TaskState::Finished(task(2), Ok("{}".into()))]);
+
+       let arg = json!({ "parent" : scheduler.tree.root_id, "file_name" : "/home/user/test.txt", "offset" : 0}).to_string();
+       //restored waiting tasks are not run, so only the scheduled tasks are joined
+       let id = scheduler.schedule(plugin_dummy::Plugin::new().instantiate(), arg.clone(), false).unwrap();
+       scheduler.join_tasks(&[id]);
+       assert!(id == 6);
+       assert!(matches!(scheduler.task(5), Some(TaskState::Waiting(task)) if task.plugin_name == "loop"));
+
+       scheduler.restore(vec![TaskState::Finished(task(3), Ok("{}".into()))]);
+       let id = scheduler.schedule(plugin_dummy::Plugin::new().instantiate(), arg, true).unwrap();
+       scheduler.join_tasks(&[id]);
+       assert!(id == 7);
+
+       //tasks restored from a scheduler with other pools use the default pool
+       let mut io_task = task(8);
+       io_task.pool = Some("io".into());
+       scheduler.restore(vec![TaskState::Waiting(io_task)]);
+       assert!(!scheduler.set_priority(8, Priority::High).unwrap());
+       assert!(matches!(scheduler.task(8), Some(TaskState::Waiting(task)) if task.priority == Priority::Normal));
+    }
+
+    /// Plugin ignoring its cancellation.
+    struct StuckPlugin;
+
+    impl PluginInstance for StuckPlugin
+    {
+      fn name(&self) -> &'static str
+      {
+        "stuck"
+      }
+
+      fn run(&mut self, _argument : PluginArgument, _env : PluginEnvironment) -> anyhow::Result<PluginResult>
+      {
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        Ok("{}".into())
+      }
+    }
+
+    #[test]
+    fn timeout_tasks()
+    {
+       use std::time::{Duration, Instant};
+
+       let scheduler = TaskScheduler::new(Tree::new());
+       let timed_out = |error : &anyhow::Error| matches!(error.downcast_ref::<RustructError>(), Some(RustructError::TimedOut(..)));
+
+       //a plugin checking its cancellation stop at the timeout
+       let id = scheduler.schedule_with_timeout(Box::new(LoopPlugin), "{}".into(), false, Duration::from_millis(50)).unwrap();
+       scheduler.join();
+       assert!(timed_out(&scheduler.result(id).unwrap_err()));
+
+       scheduler.set_plugin_timeout("loop", Some(Duration::from_millis(50)));
+       let id = scheduler.schedule(Box::new(LoopPlugin), "{\"plugin\" : true}".into(), false).unwrap();
+       scheduler.join();
+       assert!(matches!(scheduler.task(id), Some(TaskState::Finished(task, Err(_))) if task.timeout == Some(Duration::from_millis(50))));
+
+       //a plugin ignoring it is given up after the grace period
+       scheduler.set_timeout_grace(Duration::from_millis(100));
+       let start = Instant::now();
+       let error = scheduler.run_with_timeout(Box::new(StuckPlugin), "{}".into(), false, Duration::from_millis(50)).unwrap_err();
+       assert!(timed_out(&error) && start.elapsed() < Duration::from_secs(1));
+       scheduler.join();
+       assert!(matches!(scheduler.task(3), Some(TaskState::Finished(_, Err(_)))));
+
+       //its worker was replaced so tasks still run
+       let tree = Tree::new();
+       let argument = json!({"parent" : tree.root_id, "file_name" : "/file", "offset" : 0}).to_string();
+       assert!(scheduler.run(plugin_dummy::Plugin::new().instantiate(), argument, false).is_ok());
+    }
+
+    /// Plugin loading a dictionary service once per instance.
+    mod hooks
+    {
+        use crate::config_schema;
+        use crate::plugin;
+        use crate::plugin::{PluginInfo, PluginInstance, PluginConfig, PluginArgument, PluginResult, PluginEnvironment};
+
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use serde::{Serialize, Deserialize};
+        use schemars::JsonSchema;
+
+        plugin!("hooks", "Test", "Count the words of a dictionary", Hooks, Arguments, metadata : { pool : Some("io") }, init : load, teardown : unload);
+
+        pub struct Dictionary(pub Vec<&'static str>);
+
+        /// Number of instances loaded and unloaded.
+        #[derive(Default)]
+        pub struct Loads(pub AtomicUsize, pub AtomicUsize);
+
+        #[derive(Default)]
+        pub struct Hooks
+        {
+          dictionary : Option<Arc<Dictionary>>,
+          loads : Option<Arc<Loads>>,
+        }
+
+        #[derive(Debug, Serialize, Deserialize, Default, JsonSchema)]
+        pub struct Arguments
+        {
+        }
+
+        #[derive(Debug, Serialize, Deserialize, Default)]
+        pub struct Results
+        {
+          pub words : usize,
+        }
+
+        impl Hooks
+        {
+          fn load(&mut self, env : &PluginEnvironment) -> anyhow::Result<()>
+          {
+            self.dictionary = Some(env.services.require::<Dictionary>()?);
+            let loads = env.services.get_or_insert_with(Loads::default);
+            loads.0.fetch_add(1, Ordering::SeqCst);
+            self.loads = Some(loads);
+            Ok(())
+          }
+
+          fn unload(&mut self)
+          {
+            self.dictionary = None;
+            if let Some(loads) = self.loads.take()
+            {
+              loads.1.fetch_add(1, Ordering::SeqCst);
+            }
+          }
+
+          fn run(&mut self, _argument : Arguments, _env : PluginEnvironment) -> anyhow::Result<Results>
+          {
+            let words = self.dictionary.as_ref().map_or(0, |dictionary| dictionary.0.len());
+            if words == 0
+            {
+              panic!("empty dictionary");
+            }
+            Ok(Results{ words })
+          }
+        }
+    }
+
+    #[test]
+    fn plugin_lifecycle_hooks()
+    {
+       use hooks::{Dictionary, Loads};
+       use std::sync::atomic::Ordering;
+
+       let scheduler = TaskScheduler::new(Tree::new());
+       let plugin_info = hooks::Plugin::new();
+       let error = scheduler.run(plugin_info.instantiate(), "{}".into(), false).unwrap_err();
+       assert!(matches!(error.downcast_ref::<RustructError>(), Some(RustructError::ServiceNotFound(_))));
+
+       scheduler.services().insert(Dictionary(vec!["artifact", "parser"]));
+       for _ in 0..3
+       {
+         assert!(scheduler.run(plugin_info.instantiate(), "{}".into(), true).unwrap() == r#"{"words":2}"#);
+       }
+       let loads = scheduler.services().require::<Loads>().unwrap();
+       assert!(loads.0.load(Ordering::SeqCst) == 3 && loads.1.load(Ordering::SeqCst) == 3);
+
+       //instances are torn down even if run panicked
+       scheduler.services().insert(Dictionary(Vec::new()));
+       assert!(scheduler.run(plugin_info.instantiate(), "{}".into(), true).is_err());
+       assert!(loads.0.load(Ordering::SeqCst) == 4 && loads.1.load(Ordering::SeqCst) == 4);
+    }
+
+    #[test]
+    fn task_groups()
+    {
+       use super::TaskGroup;
+
+       let tree = Tree::new();
+       let scheduler = TaskScheduler::new(tree.clone());
+       let group = TaskGroup::new();
+       assert!(group.progress(&scheduler).is_finished() && group.progress(&scheduler).percent == 100.0);
+       for offset in 0..3
+       {
+         let argument = json!({"parent" : tree.root_id, "file_name" : "/file", "offset" : offset}).to_string();
+         group.schedule(&scheduler, plugin_dummy::Plugin::new().instantiate(), argument, false).unwrap();
+       }
+       //tasks outside the group are not waited
+       let other = scheduler.schedule(Box::new(LoopPlugin), "{}".into(), false).unwrap();
+       group.join(&scheduler);
+       let progress = group.progress(&scheduler);
+       assert!(progress.total == 3 && progress.succeeded == 3 && progress.is_finished() && progress.percent == 100.0);
+       assert!(group.results(&scheduler).iter().all(|(_, result)| result.is_ok()));
+       assert!(!matches!(scheduler.task(other), Some(TaskState::Finished(..))));
+
+       let shared = group.clone();
+       shared.add(other);
+       let progress = group.progress(&scheduler);
+       assert!(progress.total == 4 && !progress.is_finished() && progress.percent < 100.0);
+       assert!(group.cancel(&scheduler) == 1);
+       group.join(&scheduler);
+       let progress = group.progress(&scheduler);
+       assert!(progress.failed == 1 && progress.is_finished());
+    }
+
+    #[test]
+    fn wait_tasks()
+    {
+       use std::time::Duration;
+
+       let tree = Tree::new();
+       let scheduler = TaskScheduler::new(tree.clone());
+       let ids : Vec<_> = (0..4).map(|offset|
+       {
+         let argument = json!({"parent" : tree.root_id, "file_name" : "/file", "offset" : offset}).to_string();
+         scheduler.schedule(plugin_dummy::Plugin::new().instantiate(), argument, false).unwrap()
+       }).collect();
+       scheduler.join_tasks(&ids);
+       assert!(ids.iter().all(|id| matches!(scheduler.try_result(*id), Some(Ok(_)))));
+
+       let looping = scheduler.schedule(Box::new(LoopPlugin), "{}".into(), false).unwrap();
+       assert!(scheduler.try_result(looping).is_none());
+       let error = scheduler.wait(looping, Some(Duration::from_millis(50))).unwrap_err();
+       assert!(matches!(error.downcast_ref::<RustructError>(), Some(RustructError::TaskNotFinished(_))));
+       scheduler.cancel(looping).unwrap();
+       let error = scheduler.wait(looping, None).unwrap_err();
+       assert!(matches!(error.downcast_ref::<RustructError>(), Some(RustructError::Cancelled)));
+       assert!(scheduler.try_result(looping).unwrap().is_err());
+
+       let error = scheduler.wait(1000, None).unwrap_err();
+       assert!(matches!(error.downcast_ref::<RustructError>(), Some(RustructError::TaskNotFound(1000))));
+    }
+
+    #[test]
+    fn task_priorities()
+    {
+       use super::{TaskQueue, QueuedTask};
+       use crate::cancellation::CancellationToken;
+
+       let queued = |id : u32, priority : Priority| -> QueuedTask
+       {
+         let task = Task{ id, plugin_name : "loop".into(), argument : "{}".into(), summary : None, progress : None, timeout : None, priority, pool : None };
+         (task, Box::new(LoopPlugin), CancellationToken::new())
+       };
+       let queue = TaskQueue::default();
+       queue.push(queued(1, Priority::Low));
+       queue.push(queued(2, Priority::Normal));
+       queue.push(queued(3, Priority::High));
+       queue.push(queued(4, Priority::Normal));
+       queue.push(queued(5, Priority::High));
+       assert!(queue.set_priority(1, Priority::High) && !queue.set_priority(9, Priority::High));
+       queue.close();
+       let order : Vec<u32> = std::iter::from_fn(|| queue.pop()).map(|(task, _, _)| task.id).collect();
+       assert!(order == vec![1, 3, 5, 2, 4]);
+
+       //waiting tasks are explained in the order they will be launched
+       let workers = 2;
+       let scheduler = TaskSchedulerBuilder::new().workers(workers).build(Tree::new());
+       let looping : Vec<u32> = (0..workers).map(|worker|
+         scheduler.schedule(Box::new(LoopPlugin), json!({ "worker" : worker }).to_string(), false).unwrap()).collect();
+       while scheduler.running().len() < workers
+       {
+         std::thread::sleep(std::time::Duration::from_millis(1));
+       }
+       scheduler.set_plugin_priority("loop", Some(Priority::Low));
+       let low = scheduler.schedule(Box::new(LoopPlugin), "{}".into(), false).unwrap();
+       let high = scheduler.schedule_with_priority(Box::new(LoopPlugin), "{\"high\" : true}".into(), false, Priority::High).unwrap();
+       assert!(matches!(scheduler.task(low), Some(TaskState::Waiting(task)) if task.priority == Priority::Low));
+       let position = |id| match scheduler.explain(id).unwrap() { TaskExplanation::Queued{ position, .. } => position, _ => usize::MAX };
+       assert!(position(high) == 0 && position(low) == 1);
+       assert!(scheduler.set_priority(low, Priority::High).unwrap() && !scheduler.set_priority(looping[0], Priority::High).unwrap());
+       assert!(position(low) == 0 && position(high) == 1);
+
+       looping.iter().chain([low, high].iter()).for_each(|id| { scheduler.cancel(*id).unwrap(); });
+       scheduler.join();
+    }
+
+    #[test]
+    fn schedule_after_tasks()
+    {
+       let tree = Tree::new();
+       let scheduler = TaskScheduler::new(tree.clone());
+       let dummy = |offset : u32| json!({"parent" : tree.root_id, "file_name" : "/file", "offset" : offset}).to_string();
+
+       let looping = scheduler.schedule(Box::new(LoopPlugin), "{}".into(), false).unwrap();
+       let propagated = scheduler.schedule_after(&[looping], plugin_dummy::Plugin::new().instantiate(), dummy(0), false, FailurePolicy::Propagate).unwrap();
+       let chained = scheduler.schedule_after(&[propagated], plugin_dummy::Plugin::new().instantiate(), dummy(1), false, FailurePolicy::Ignore).unwrap();
+       let ignored = scheduler.schedule_after(&[looping], plugin_dummy::Plugin::new().instantiate(), dummy(2), false, FailurePolicy::Ignore).unwrap();
+       let cancelled = scheduler.schedule_after(&[looping, ignored], plugin_dummy::Plugin::new().instantiate(), dummy(3), false, FailurePolicy::Ignore).unwrap();
+       assert!(scheduler.schedule_after(&[1000], plugin_dummy::Plugin::new().instantiate(), dummy(4), false, FailurePolicy::Ignore).is_err());
+       assert!(scheduler.explain(cancelled).unwrap() == TaskExplanation::Blocked{ dependencies : vec![looping, ignored] });
+       assert!(scheduler.cancel(cancelled).unwrap());
+
+       scheduler.cancel(looping).unwrap();
+       scheduler.join_tasks(&[propagated, chained, ignored, cancelled]);
+       let error = scheduler.result(propagated).unwrap_err();
+       assert!(matches!(error.downcast_ref::<RustructError>(), Some(RustructError::DependencyFailed(..))));
+       //a failure is propagated to the tasks scheduled after, unless they ignore it
+       assert!(scheduler.result(chained).is_ok() && scheduler.result(ignored).is_ok());
+       let error = scheduler.result(cancelled).unwrap_err();
+       assert!(matches!(error.downcast_ref::<RustructError>(), Some(RustructError::Cancelled)));
+
+       //dependencies already finished
+       let after_success = scheduler.schedule_after(&[ignored], plugin_dummy::Plugin::new().instantiate(), dummy(5), false, FailurePolicy::Propagate).unwrap();
+       let after_failure = scheduler.schedule_after(&[looping], plugin_dummy::Plugin::new().instantiate(), dummy(6), false, FailurePolicy::Propagate).unwrap();
+       scheduler.join_tasks(&[after_success, after_failure]);
+       assert!(scheduler.result(after_success).is_ok() && scheduler.result(after_failure).is_err());
+    }
+
+    #[test]
+    fn worker_pools()
+    {
+       let tree = Tree::new();
+       let builder = TaskSchedulerBuilder::new().workers(1).pool("io", 1).pool("cpu", 0);
+       assert!(builder.pools() == vec![("cpu".to_string(), 1), (DEFAULT_POOL.to_string(), 1), ("io".to_string(), 1)]);
+       let scheduler = builder.build(tree.clone());
+       assert!(scheduler.pools().len() == 3 && scheduler.builder().pools() == scheduler.pools());
+       let error = scheduler.set_plugin_pool("loop", Some("gpu")).unwrap_err();
+       assert!(matches!(error.downcast_ref::<RustructError>(), Some(RustructError::PoolNotFound(_))));
+       scheduler.set_plugin_pool("loop", Some("io")).unwrap();
+
+       //the tasks of the default pool are run while the io worker is busy
+       let looping = scheduler.schedule(Box::new(LoopPlugin), "{}".into(), false).unwrap();
+       let waiting = scheduler.schedule(Box::new(LoopPlugin), "{\"waiting\" : true}".into(), false).unwrap();
+       let argument = json!({"parent" : tree.root_id, "file_name" : "/file", "offset" : 0}).to_string();
+       let dummy = scheduler.schedule(plugin_dummy::Plugin::new().instantiate(), argument, false).unwrap();
+       assert!(scheduler.wait(dummy, None).is_ok());
+       assert!(matches!(scheduler.task(dummy), Some(TaskState::Finished(task, _)) if task.pool.is_none()));
+       while !matches!(scheduler.task(looping), Some(TaskState::Launched(task)) if task.pool.as_deref() == Some("io"))
+       {
+         std::thread::sleep(std::time::Duration::from_millis(1));
+       }
+       assert!(scheduler.explain(waiting).unwrap() == TaskExplanation::Queued{ position : 0, running : 1, workers : 1 });
+
+       //routed by the metadata of the plugin
+       let routed = scheduler.schedule(hooks::Plugin::new().instantiate(), "{}".into(), false).unwrap();
+       assert!(matches!(scheduler.task(routed), Some(TaskState::Waiting(task)) if task.pool.as_deref() == Some("io")));
+       assert!(scheduler.queue_len() == 2);
+
+       scheduler.cancel(looping).unwrap();
+       scheduler.cancel(waiting).unwrap();
+       scheduler.join();
+    }
 }
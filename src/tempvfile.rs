@@ -0,0 +1,234 @@
+//! A [VFileBuilder] backed by a temporary file, used to keep big buffers out of memory.
+
+use std::fs::{self, File};
+use std::io::{Read, Write, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::vfile::{VFile, VFileBuilder, VFileWriter};
+
+use serde::{Serialize, Deserialize};
+use serde::de::{self, Deserializer};
+use serde::ser::{self, Serializer, SerializeMap};
+
+/// Temporary file removed when the last [TempVFileBuilder] referencing it is dropped.
+struct TempPath
+{
+  path : PathBuf,
+}
+
+impl TempPath
+{
+  /// Return a new unique path in the system temporary directory.
+  fn new() -> TempPath
+  {
+    TempPath{ path : std::env::temp_dir().join(format!("tap-blob-{}", uuid::Uuid::new_v4())) }
+  }
+}
+
+impl Drop for TempPath
+{
+  fn drop(&mut self)
+  {
+    let _ = fs::remove_file(&self.path);
+  }
+}
+
+/**
+ * Implement a [VFileBuilder] that write a buffer to a temporary file and read it back when opened.
+ * The temporary file is removed when the builder and all its clones are dropped.
+ */
+#[derive(Clone)]
+pub struct TempVFileBuilder
+{
+  path : Arc<TempPath>,
+  size : u64,
+}
+
+impl TempVFileBuilder
+{
+  /// Write `buffer` to a new file in the system temporary directory and return a builder reading it.
+  pub fn new(buffer : &[u8]) -> anyhow::Result<Arc<TempVFileBuilder>>
+  {
+    let mut writer = TempVFileWriter::new()?;
+    writer.write_all(buffer)?;
+    writer.into_builder()
+  }
+
+  /// Return the path of the temporary file.
+  pub fn path(&self) -> &Path
+  {
+    &self.path.path
+  }
+}
+
+#[typetag::serde]
+impl VFileBuilder for TempVFileBuilder
+{
+  fn open(&self) -> anyhow::Result<Box<dyn VFile>>
+  {
+    Ok(Box::new(File::open(&self.path.path)?))
+  }
+
+  fn size(&self) -> u64
+  {
+    self.size
+  }
+}
+
+/**
+ * [VFileWriter] writing to a temporary file, finished as a [TempVFileBuilder].
+ * The file is removed if the writer is dropped before being finished.
+ */
+pub struct TempVFileWriter
+{
+  path : TempPath,
+  file : File,
+}
+
+impl TempVFileWriter
+{
+  /// Create a new file in the system temporary directory and return a writer to it.
+  pub fn new() -> anyhow::Result<TempVFileWriter>
+  {
+    let path = TempPath::new();
+    let file = File::create(&path.path)?;
+    Ok(TempVFileWriter{ path, file })
+  }
+
+  /// Flush the written data and return a [TempVFileBuilder] reading it.
+  pub fn into_builder(mut self) -> anyhow::Result<Arc<TempVFileBuilder>>
+  {
+    self.file.flush()?;
+    let size = self.file.metadata()?.len();
+    Ok(Arc::new(TempVFileBuilder{ path : Arc::new(self.path), size }))
+  }
+}
+
+impl Write for TempVFileWriter
+{
+  fn write(&mut self, buf : &[u8]) -> std::io::Result<usize>
+  {
+    self.file.write(buf)
+  }
+
+  fn flush(&mut self) -> std::io::Result<()>
+  {
+    self.file.flush()
+  }
+}
+
+impl Seek for TempVFileWriter
+{
+  fn seek(&mut self, pos : SeekFrom) -> std::io::Result<u64>
+  {
+    self.file.seek(pos)
+  }
+}
+
+impl VFileWriter for TempVFileWriter
+{
+  fn finish(self : Box<Self>) -> anyhow::Result<Arc<dyn VFileBuilder>>
+  {
+    Ok(self.into_builder()?)
+  }
+}
+
+/// The temporary file is removed when the builder is dropped, so [TempVFileBuilder] is serialized with its content.
+impl Serialize for TempVFileBuilder
+{
+  fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where S: Serializer,
+  {
+     let mut data = Vec::new();
+     File::open(&self.path.path).and_then(|mut file| file.read_to_end(&mut data)).map_err(ser::Error::custom)?;
+
+     let mut map = serializer.serialize_map(Some(2))?;
+     map.serialize_entry("size", &self.size)?;
+     map.serialize_entry("data", &data)?;
+     map.end()
+  }
+}
+
+/// Content of a serialized [TempVFileBuilder].
+#[derive(Deserialize)]
+struct TempContent
+{
+  data : Vec<u8>,
+}
+
+/// [TempVFileBuilder] is deserialized by writing its content to a new temporary file.
+impl<'de> Deserialize<'de> for TempVFileBuilder
+{
+  fn deserialize<D>(deserializer: D) -> std::result::Result<TempVFileBuilder, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let content = TempContent::deserialize(deserializer)?;
+    let builder = TempVFileBuilder::new(&content.data).map_err(de::Error::custom)?;
+    Ok(builder.as_ref().clone())
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{TempVFileBuilder, TempVFileWriter};
+  use crate::vfile::{VFileBuilder, VFileWriter};
+  use std::io::{Read, Write, Seek, SeekFrom};
+  use std::sync::Arc;
+
+  #[test]
+  fn temp_file_removed_on_drop()
+  {
+    let builder = TempVFileBuilder::new(b"temporary").unwrap();
+    let path = builder.path().to_path_buf();
+    assert!(builder.size() == 9);
+
+    let mut content = Vec::new();
+    builder.open().unwrap().read_to_end(&mut content).unwrap();
+    assert!(content == b"temporary");
+
+    let clone = builder.clone();
+    drop(builder);
+    assert!(path.exists());
+    drop(clone);
+    assert!(!path.exists());
+  }
+
+  #[test]
+  fn write_temp_file()
+  {
+    let mut writer = TempVFileWriter::new().unwrap();
+    let path = writer.path.path.clone();
+    writer.write_all(b"0123456789").unwrap();
+    writer.seek(SeekFrom::Start(2)).unwrap();
+    writer.write_all(b"ab").unwrap();
+    let builder : Arc<dyn VFileBuilder> = Box::new(writer).finish().unwrap();
+    assert!(builder.size() == 10);
+
+    let mut content = Vec::new();
+    builder.open().unwrap().read_to_end(&mut content).unwrap();
+    assert!(content == b"01ab456789");
+    drop(builder);
+    assert!(!path.exists());
+
+    let writer = TempVFileWriter::new().unwrap();
+    let path = writer.path.path.clone();
+    drop(writer);
+    assert!(!path.exists());
+  }
+
+  #[test]
+  fn temp_file_serialization()
+  {
+    let builder : Arc<dyn VFileBuilder> = TempVFileBuilder::new(b"temporary").unwrap();
+    let json = serde_json::to_string(&builder).unwrap();
+    drop(builder);
+
+    let loaded : Arc<dyn VFileBuilder> = serde_json::from_str(&json).unwrap();
+    let mut content = Vec::new();
+    loaded.open().unwrap().read_to_end(&mut content).unwrap();
+    assert!(loaded.size() == 9 && content == b"temporary");
+  }
+}
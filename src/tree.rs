@@ -3,14 +3,20 @@
 
 use std::fmt;
 use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::collections::HashMap;
 
 use crate::value::Value;
 use crate::node::Node;
+use crate::refresh::Refreshable;
+use crate::reference::{self, ReferenceIndex, Reference, ReferenceReport, BrokenReference};
+use crate::diagnostics::{LockStats, probe_lock};
+use crate::access::{self, Access, Authorizer, Permissive};
 
 use indextree::{Arena, NodeId};
 use serde::{Serialize, Deserialize};
 use serde::ser::{Serializer, SerializeMap};
 use schemars::{JsonSchema};
+use uuid::Uuid;
 
 pub type TreeNodeId = NodeId;
 pub type TreeNode = Arc<Node>;
@@ -41,6 +47,184 @@ pub struct VecTreeNodeIdSchema
   pub ids : Vec<TreeNodeIdSchema>,
 }
 
+/// Kind of modification recorded for a [node](Node) or one of its attributes by the [DirtyTracker].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum NodeState
+{
+  /// The node was added to the tree.
+  Added,
+  /// The node or its attributes were updated.
+  Updated,
+  /// The node was removed from the tree.
+  Removed,
+}
+
+/**
+ * Keep track of the version at which each node of the [tree](Tree) was added and last modified.
+ * Each modification increment the tree `version`, this let caller ask which nodes changed since a given version.
+ */
+#[derive(Default)]
+pub struct DirtyTracker
+{
+  version : u64,
+  /// Version at which the node was added, last modified, and if it was removed.
+  nodes : HashMap<TreeNodeId, (u64, u64, bool)>,
+  /// Version at which each attribute of a node was added, last modified, and if it was removed.
+  attributes : HashMap<TreeNodeId, HashMap<String, (u64, u64, bool)>>,
+  /// Uuid of the removed nodes, which can't be read from the tree anymore.
+  removed : HashMap<TreeNodeId, Uuid>,
+}
+
+impl DirtyTracker
+{
+  /// Record a modification of `node_id` and return the new version.
+  fn mark(&mut self, node_id : TreeNodeId, state : NodeState) -> u64
+  {
+    self.version += 1;
+    let version = self.version;
+    let entry = self.nodes.entry(node_id).or_insert((version, version, false));
+
+    match state
+    {
+      NodeState::Added => *entry = (version, version, false),
+      NodeState::Updated => entry.1 = version,
+      NodeState::Removed => { entry.1 = version; entry.2 = true },
+    }
+    //attributes of an added node are all reported as added with the node
+    if state != NodeState::Updated
+    {
+      self.attributes.remove(&node_id);
+    }
+    if state == NodeState::Added
+    {
+      self.removed.remove(&node_id);
+    }
+    version
+  }
+
+  /// Record a modification of the attribute `name` of `node_id`, which also update the node.
+  /// Return the new version, or `None` if the node is not in the tree.
+  fn mark_attribute(&mut self, node_id : TreeNodeId, name : &str, state : NodeState) -> Option<u64>
+  {
+    match self.nodes.get(&node_id)
+    {
+      Some((_, _, false)) => (),
+      _ => return None,
+    }
+    let version = self.mark(node_id, NodeState::Updated);
+    //attributes without entry were added with the node
+    let entry = self.attributes.entry(node_id).or_default().entry(name.to_string()).or_insert((0, 0, false));
+
+    match state
+    {
+      NodeState::Added => *entry = (version, version, false),
+      NodeState::Updated => entry.1 = version,
+      NodeState::Removed => { entry.1 = version; entry.2 = true },
+    }
+    Some(version)
+  }
+
+  /// Return the current version.
+  pub fn version(&self) -> u64
+  {
+    self.version
+  }
+
+  /// Return all the nodes modified after `since` version and their [state](NodeState) relatively to that version.
+  pub fn changed_since(&self, since : u64) -> Vec<(TreeNodeId, u64, NodeState)>
+  {
+    let mut changes : Vec<(TreeNodeId, u64, NodeState)> = self.nodes.iter()
+      .filter(|(_, (_, modified, _))| *modified > since)
+      .map(|(node_id, (added, modified, removed))| 
+      {
+        let state = match (removed, *added > since)
+        {
+          (true, _) => NodeState::Removed,
+          (false, true) => NodeState::Added,
+          (false, false) => NodeState::Updated,
+        };
+        (*node_id, *modified, state)
+      })
+      .collect();
+    changes.sort_by_key(|change| change.1);
+    changes
+  }
+
+  /// Return the name of all the attributes of `node_id` modified after `since` version and their [state](NodeState) relatively to that version.
+  pub fn changed_attributes_since(&self, node_id : TreeNodeId, since : u64) -> Vec<(String, u64, NodeState)>
+  {
+    let attributes = match self.attributes.get(&node_id)
+    {
+      Some(attributes) => attributes,
+      None => return Vec::new(),
+    };
+    let mut changes : Vec<(String, u64, NodeState)> = attributes.iter()
+      .filter(|(_, (_, modified, _))| *modified > since)
+      .map(|(name, (added, modified, removed))|
+      {
+        let state = match (removed, *added > since)
+        {
+          (true, _) => NodeState::Removed,
+          (false, true) => NodeState::Added,
+          (false, false) => NodeState::Updated,
+        };
+        (name.clone(), *modified, state)
+      })
+      .collect();
+    changes.sort_by_key(|change| change.1);
+    changes
+  }
+
+  /// Return the uuid of the removed node `node_id`.
+  pub fn removed_uuid(&self, node_id : TreeNodeId) -> Option<Uuid>
+  {
+    self.removed.get(&node_id).cloned()
+  }
+}
+
+/// Number of nodes reported in [TreeStats::largest_nodes].
+pub const STATS_LARGEST_NODES : usize = 10;
+
+/**
+ * Memory statistics of a [Tree] returned by [Tree::stats].
+ * Sizes are approximations computed with [Node::deep_size].
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TreeStats
+{
+  /// Number of nodes.
+  pub nodes : usize,
+  /// Number of attributes of all the nodes.
+  pub attributes : usize,
+  /// Approximate memory used by the nodes and their attributes in bytes.
+  pub memory : usize,
+  /// Id and size of the nodes using the most memory, by decreasing size.
+  pub largest_nodes : Vec<(TreeNodeId, usize)>,
+}
+
+/**
+ * Record the id of the nodes added through a [Tree] returned by [Tree::recorder].
+ */
+#[derive(Clone, Default)]
+pub struct NodeRecorder
+{
+  nodes : Arc<RwLock<Vec<TreeNodeId>>>,
+}
+
+impl NodeRecorder
+{
+  /// Return the id of all recorded nodes in order of insertion.
+  pub fn nodes(&self) -> Vec<TreeNodeId>
+  {
+    self.nodes.read().unwrap().clone()
+  }
+
+  fn record(&self, node_id : TreeNodeId)
+  {
+    self.nodes.write().unwrap().push(node_id);
+  }
+}
+
 /**
  * One of the main structure of TAP.
  * Tt contain nodes, that contain [attribute](crate::attribute::Attribute) with [value](Value) of different type.
@@ -50,6 +234,11 @@ pub struct VecTreeNodeIdSchema
 pub struct Tree
 {
   tree : TreeArc,
+  dirty : Arc<RwLock<DirtyTracker>>,
+  recorder : Option<NodeRecorder>,
+  refreshables : Arc<RwLock<HashMap<TreeNodeId, Arc<dyn Refreshable>>>>,
+  references : Arc<ReferenceIndex>,
+  authorizer : Arc<RwLock<Arc<dyn Authorizer>>>,
   pub root_id : TreeNodeId,
 }
 
@@ -61,7 +250,102 @@ impl Tree
     let mut tree = Arena::new();
     let root_node = Arc::new(Node::new("root"));
     let root_id = tree.new_node(root_node);
-    Tree{ tree : Arc::new(RwLock::new(tree)), root_id } 
+    Tree{ tree : Arc::new(RwLock::new(tree)), dirty : Arc::new(RwLock::new(DirtyTracker::default())), recorder : None,
+          refreshables : Arc::new(RwLock::new(HashMap::new())), references : Arc::new(ReferenceIndex::new()),
+          authorizer : Arc::new(RwLock::new(Arc::new(Permissive))), root_id } 
+  }
+
+  /// Set the [Authorizer] checking the access of the [users](crate::access::User) to this tree and its clones.
+  pub fn set_authorizer(&self, authorizer : Arc<dyn Authorizer>)
+  {
+    *self.authorizer.write().unwrap() = authorizer;
+  }
+
+  /// Return the [Authorizer] of the tree.
+  pub fn authorizer(&self) -> Arc<dyn Authorizer>
+  {
+    self.authorizer.read().unwrap().clone()
+  }
+
+  /// Return an error if the [current user](crate::access::current_user) can't perform `access` on `node_id`, or on the whole tree if `node_id` is None.
+  pub fn authorize(&self, access : Access, node_id : Option<TreeNodeId>) -> anyhow::Result<()>
+  {
+    access::check(self.authorizer().as_ref(), access, self, node_id)
+  }
+
+  /// Return a clone of this tree that record the id of all nodes added through it or its clones, and the [NodeRecorder].
+  /// This is used to know which nodes were created by a plugin.
+  pub fn recorder(&self) -> (Tree, NodeRecorder)
+  {
+    let recorder = NodeRecorder::default();
+    let mut tree = self.clone();
+    tree.recorder = Some(recorder.clone());
+    (tree, recorder)
+  }
+
+  /// Return the current version of the tree, it's incremented each time a node is added, updated or removed.
+  pub fn version(&self) -> u64
+  {
+    self.dirty.read().unwrap().version()
+  }
+
+  /// Return the [node id](TreeNodeId), version and [state](NodeState) of all nodes modified after version `since`.
+  pub fn changed_since(&self, since : u64) -> Vec<(TreeNodeId, u64, NodeState)>
+  {
+    self.dirty.read().unwrap().changed_since(since)
+  }
+
+  /// Return the name, version and [state](NodeState) of all the attributes of `node_id` modified after version `since`.
+  /// Attributes changes are tracked once the node is added to the tree, they don't need to be [touched](Tree::touch).
+  pub fn changed_attributes_since(&self, node_id : TreeNodeId, since : u64) -> Vec<(String, u64, NodeState)>
+  {
+    self.dirty.read().unwrap().changed_attributes_since(node_id, since)
+  }
+
+  /// Return the [uuid](Node::uuid) of `node_id`, also for nodes removed from the tree.
+  pub fn node_uuid(&self, node_id : TreeNodeId) -> Option<Uuid>
+  {
+    match self.get_node_from_id(node_id)
+    {
+      Some(node) => Some(node.uuid()),
+      None => self.dirty.read().unwrap().removed_uuid(node_id),
+    }
+  }
+
+  /// Report the changes of the attributes of `node` added as `node_id` to the [DirtyTracker].
+  fn observe(&self, node_id : TreeNodeId, node : &Node)
+  {
+    let dirty = Arc::downgrade(&self.dirty);
+    node.value().set_observer(Some(Arc::new(move |name : &str, state : NodeState|
+    {
+      if let Some(dirty) = dirty.upgrade()
+      {
+        dirty.write().unwrap().mark_attribute(node_id, name, state);
+      }
+    })));
+  }
+
+  /// Mark `node_id` as updated, must be called when attributes of a node already in the tree are modified
+  /// so the change is visible to [changed_since](Tree::changed_since) and to exports.
+  pub fn touch(&self, node_id : TreeNodeId) -> u64
+  {
+    self.index_references(node_id);
+    self.dirty.write().unwrap().mark(node_id, NodeState::Updated)
+  }
+
+  /// Register `source` as the [Refreshable] source of the nodes under `node_id`.
+  pub fn set_refreshable(&self, node_id : TreeNodeId, source : Arc<dyn Refreshable>)
+  {
+    self.refreshables.write().unwrap().insert(node_id, source);
+  }
+
+  /// Return the [Refreshable] source registered on `node_id` or on its nearest ancestor, and the id of the node it was registered on.
+  pub fn refreshable(&self, node_id : TreeNodeId) -> Option<(TreeNodeId, Arc<dyn Refreshable>)>
+  {
+    let refreshables = self.refreshables.read().unwrap();
+    let tree = self.tree.read().unwrap();
+
+    node_id.ancestors(&tree).find_map(|ancestor_id| refreshables.get(&ancestor_id).map(|source| (ancestor_id, source.clone())))
   }
 
   /// Return the underlying [tree arena](TreeArena).
@@ -78,15 +362,28 @@ impl Tree
   }
 
   /// Add a node via it's [`node_id`](TreeNodeId) as child of the [`parent_id`](TreeNodeId) [node](Node).
-  pub fn add_child_from_id(&self, parent_id : NodeId, node_id : NodeId)
+  pub fn add_child_from_id(&self, parent_id : NodeId, node_id : NodeId) -> anyhow::Result<()>
   {
+    self.authorize(Access::Write, Some(parent_id))?;
     let mut tree = self.tree.write().unwrap();
     parent_id.append(node_id, &mut tree);
+    if let Some(node) = tree.get(node_id)
+    {
+      self.references.index(node_id, &node.get().value());
+      self.observe(node_id, node.get());
+    }
+    self.dirty.write().unwrap().mark(node_id, NodeState::Added);
+    if let Some(recorder) = &self.recorder
+    {
+      recorder.record(node_id);
+    }
+    Ok(())
   }
 
   /// Create a new [TreeNodeId] for [`node`](Node), add it as child of `parent_id` and return the new [node id](TreeNodeId.)
   pub fn add_child(&self, parent_id : NodeId, node : Node) -> anyhow::Result<TreeNodeId>
   {
+    self.authorize(Access::Write, Some(parent_id))?;
     let mut tree = self.tree.write().unwrap();
     //this is very slow
     //for child_id in parent_id.children(&tree) //check for same name
@@ -97,8 +394,16 @@ impl Tree
       //}
     //}
 
-    let node_id = tree.new_node(Arc::new(node));
+    let node = Arc::new(node);
+    let node_id = tree.new_node(node.clone());
     parent_id.append(node_id, &mut tree);
+    self.references.index(node_id, &node.value());
+    self.observe(node_id, &node);
+    self.dirty.write().unwrap().mark(node_id, NodeState::Added);
+    if let Some(recorder) = &self.recorder
+    {
+      recorder.record(node_id);
+    }
     //if event registered ? avoid to have a big queue ? 
     //self.node_event.update(node_id); //XXX ? 
     Ok(node_id)
@@ -152,6 +457,12 @@ impl Tree
     Some(root_id.descendants(&arena).collect())
   }
 
+  /// Return an iterator over `root_id` and its descendants in depth-first order, without collecting them. See [Walk].
+  pub fn walk(&self, root_id : TreeNodeId) -> Walk<'_>
+  {
+    Walk{ tree : self, root_id, names : Vec::new(), base : 0, current : None, skip : false, done : false }
+  }
+
   /// Return the name of the children for `node_id`. 
   pub fn children_name(&self, node_id : NodeId) -> Vec<String>
   {
@@ -195,23 +506,10 @@ impl Tree
   /// Return a path from a [node id](TreeNodeId).
   pub fn node_path(&self, node_id : TreeNodeId) -> Option<String>
   {
-    let mut path : String = String::new();
-
     let tree = self.tree.read().unwrap();
-    for next_node_id in node_id.ancestors(&tree)
-    {
-      let next_node = match tree.get(next_node_id)
-      {
-        Some(next_node) => next_node,
-        None => return None,
-      };
-      if next_node.is_removed() 
-      {
-        return None;
-      }
-      path = next_node.get().name().to_owned() + "/" + &path;
-    }
-    Some("/".to_owned() + &path[..path.len()-1])
+    let mut names = ancestors_name(&tree, node_id)?;
+    names.reverse();
+    Some("/".to_owned() + &names.join("/"))
   }
 
   /// Return a [node](TreeNode) from a [node id](NodeId).
@@ -230,13 +528,29 @@ impl Tree
   }
 
   /// Remove node and descendants from the tree.
-  pub fn remove(&self, node_id : NodeId) 
+  pub fn remove(&self, node_id : NodeId) -> anyhow::Result<()>
   {
+     self.authorize(Access::Write, Some(node_id))?;
      let mut tree = self.tree.write().unwrap();
      //XXX 
      //Please note that the node will not be removed from the internal arena storage, but marked as removed. Traversing the arena returns a plain iterator and contains removed elements too.
      //Node count will still be the same
+     let removed : Vec<(NodeId, Uuid)> = node_id.descendants(&tree).map(|removed_id|
+     {
+       let node = tree[removed_id].get();
+       node.value().set_observer(None);
+       (removed_id, node.uuid())
+     }).collect();
      node_id.remove_subtree(&mut tree);
+
+     let mut dirty = self.dirty.write().unwrap();
+     for (removed_id, uuid) in removed
+     {
+       self.references.forget(removed_id);
+       dirty.mark(removed_id, NodeState::Removed);
+       dirty.removed.insert(removed_id, uuid);
+     }
+     Ok(())
   }
 
   /// Return a [node](TreeNode) from a path.
@@ -361,11 +675,207 @@ impl Tree
     Some(current_node_id)
   }
 
+  /// Update the [ReferenceIndex] with the attributes of `node_id`.
+  fn index_references(&self, node_id : TreeNodeId)
+  {
+    if let Some(node) = self.get_node_from_id(node_id)
+    {
+      self.references.index(node_id, &node.value());
+    }
+  }
+
+  /// Return all the [references](Reference) to nodes stored in the attributes of the nodes.
+  pub fn references(&self) -> Vec<Reference>
+  {
+    let mut references = Vec::new();
+    for node_id in self.references.holders()
+    {
+      let node = match self.get_node_from_id(node_id)
+      {
+        Some(node) => node,
+        None => continue,
+      };
+      for name in self.references.locations(node_id)
+      {
+        let value = match node.value().get_value(&name)
+        {
+          Some(value) => value,
+          None => continue,
+        };
+        for (path, target) in reference::targets(&value)
+        {
+          let attribute = match path.is_empty()
+          {
+            true => name.clone(),
+            false => name.clone() + "/" + &path,
+          };
+          references.push(Reference{ node_id, attribute, target });
+        }
+      }
+    }
+    references
+  }
+
+  /// Return a [ReferenceReport] listing the references to nodes that were removed or that don't belong to this tree.
+  pub fn check_references(&self) -> ReferenceReport
+  {
+    let mut report = ReferenceReport::default();
+    for reference in self.references()
+    {
+      report.checked += 1;
+      if self.get_node_from_id(reference.target).is_none()
+      {
+        let path = self.node_path(reference.node_id).unwrap_or_default();
+        let attribute = reference.attribute.split('/').next().unwrap_or_default().to_string();
+        report.broken.push(BrokenReference{ node_id : reference.node_id, path, attribute, target : reference.target });
+      }
+    }
+    report
+  }
+
+  /// Update the references stored in the attributes of `holders` with `resolve`,
+  /// which return the new id of a referenced node or `None` if it's missing.
+  fn rewrite_references<F>(&self, holders : &[TreeNodeId], resolve : &F) -> ReferenceReport
+    where F : Fn(TreeNodeId) -> Option<TreeNodeId>
+  {
+    let mut report = ReferenceReport::default();
+    for node_id in holders
+    {
+      let node = match self.get_node_from_id(*node_id)
+      {
+        Some(node) => node,
+        None => continue,
+      };
+
+      let mut changed = false;
+      for name in self.references.locations(*node_id)
+      {
+        let value = match node.value().get_value(&name)
+        {
+          Some(value) => value,
+          None => continue,
+        };
+        let mut broken = Vec::new();
+        let (value, updated) = reference::rewrite(&value, resolve, &mut broken);
+        report.checked += reference::targets(&value).len() as u64 + broken.len() as u64;
+        report.updated += updated;
+        if updated != 0 || !broken.is_empty()
+        {
+          node.value().set_value(&name, value);
+          changed = true;
+        }
+        for target in broken
+        {
+          let path = self.node_path(*node_id).unwrap_or_default();
+          report.broken.push(BrokenReference{ node_id : *node_id, path, attribute : name.clone(), target });
+        }
+      }
+      if changed
+      {
+        self.touch(*node_id);
+      }
+    }
+    report
+  }
+
+  /// Update the references stored in the tree after nodes changed of id, `remap` contain the old and new id of the moved nodes.
+  /// This must be called by operations that recreate nodes (compaction, merges) so references don't dangle.
+  /// References to nodes that are not remapped and don't exist anymore are invalidated.
+  pub fn remap_references(&self, remap : &HashMap<TreeNodeId, TreeNodeId>) -> ReferenceReport
+  {
+    let resolve = |node_id : TreeNodeId| match remap.get(&node_id)
+    {
+      Some(new_id) => Some(*new_id),
+      None => self.get_node_from_id(node_id).map(|_| node_id),
+    };
+    self.rewrite_references(&self.references.holders(), &resolve)
+  }
+
+  /// Copy node `source_id` of tree `source` and its descendants as a child of `parent_id`.
+  /// References between the copied nodes are updated to the new ids, references to other nodes of `source` are invalidated
+  /// unless `source` is this tree. The subtree is listed before being copied, so it can be imported inside itself.
+  /// Return the id of the copy of `source_id` and the [ReferenceReport] of the copied nodes.
+  pub fn import_subtree(&self, parent_id : TreeNodeId, source : &Tree, source_id : TreeNodeId) -> anyhow::Result<(TreeNodeId, ReferenceReport)>
+  {
+    self.authorize(Access::Write, Some(parent_id))?;
+    let mut nodes = Vec::new();
+    let mut stack = vec![(source_id, None)];
+    while let Some((node_id, source_parent_id)) = stack.pop()
+    {
+      stack.extend(source.children_id(node_id).into_iter().rev().map(|child_id| (child_id, Some(node_id))));
+      nodes.push((node_id, source_parent_id));
+    }
+
+    let mut remap = HashMap::new();
+    for (node_id, source_parent_id) in nodes
+    {
+      let node = source.get_node_from_id(node_id).ok_or_else(|| anyhow::anyhow!("Node {} not found in source tree", node_id))?;
+      let copy = match node.raw_name()
+      {
+        Some(raw) => Node::with_raw_name(node.name(), raw),
+        None => Node::new(node.name()),
+      };
+      for attribute in node.value().attributes().iter()
+      {
+        copy.value().add_attribute(attribute.name().to_string(), attribute.value().clone(), attribute.description().map(|description| description.to_string()));
+      }
+
+      let copy_id = self.add_child(source_parent_id.map_or(parent_id, |source_parent_id| remap[&source_parent_id]), copy)?;
+      remap.insert(node_id, copy_id);
+    }
+
+    let holders : Vec<TreeNodeId> = remap.values().cloned().collect();
+    let same_tree = Arc::ptr_eq(&self.tree, &source.tree);
+    let resolve = |node_id : TreeNodeId| match remap.get(&node_id)
+    {
+      Some(new_id) => Some(*new_id),
+      None if same_tree => self.get_node_from_id(node_id).map(|_| node_id),
+      None => None,
+    };
+    let report = self.rewrite_references(&holders, &resolve);
+    Ok((remap[&source_id], report))
+  }
+
   /// Return number of [nodes](TreeNode) in the tree.
   pub fn count(&self) -> usize
   {
     self.tree.read().unwrap().count()
   }
+
+  /// Return the contention of the lock of the tree.
+  pub fn lock_stats(&self) -> LockStats
+  {
+    probe_lock("tree", &self.tree)
+  }
+
+  /// Return the memory [statistics](TreeStats) of the nodes of the tree.
+  pub fn stats(&self) -> TreeStats
+  {
+    let tree = self.tree.read().unwrap();
+    let mut stats = TreeStats::default();
+    let mut sizes = Vec::new();
+
+    for node_id in self.root_id.descendants(&tree)
+    {
+      let node = tree[node_id].get();
+      let size = node.deep_size();
+      stats.nodes += 1;
+      stats.attributes += node.value().count();
+      stats.memory += size;
+      sizes.push((node_id, size));
+      //keep memory bounded on huge trees
+      if sizes.len() >= STATS_LARGEST_NODES * 64
+      {
+        sizes.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+        sizes.truncate(STATS_LARGEST_NODES);
+      }
+    }
+
+    sizes.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+    sizes.truncate(STATS_LARGEST_NODES);
+    stats.largest_nodes = sizes;
+    stats
+  }
 }
 
 impl Default for Tree
@@ -380,15 +890,129 @@ impl fmt::Display for Tree
 {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result 
   {
-    let tree = self.tree.read().unwrap();
-    for node in self.root_id.descendants(&tree)
+    let mut walk = self.walk(self.root_id);
+    while let Some((node_id, _)) = walk.next()
     {
-      writeln!(f, "{} : {}", self.node_path(node).unwrap(),  tree[node].get() as &Node).unwrap();
+      if let Some(node) = self.get_node_from_id(node_id)
+      {
+        writeln!(f, "{} : {}", walk.path(), node)?;
+      }
     }
     Ok(())
   }
 }
 
+/// Return the name of `node_id` and of its ancestors, or None if one of them was removed.
+fn ancestors_name(tree : &TreeArena, node_id : TreeNodeId) -> Option<Vec<String>>
+{
+  let mut names = Vec::new();
+  for ancestor_id in node_id.ancestors(tree)
+  {
+    match tree.get(ancestor_id)
+    {
+      Some(ancestor) if !ancestor.is_removed() => names.push(ancestor.get().name().to_owned()),
+      _ => return None,
+    }
+  }
+  Some(names)
+}
+
+/**
+ * Depth-first iterator over a node and its descendants returned by [Tree::walk], yielding each node id with its depth
+ * relative to the walked node. The walk use the links of the tree rather than a stack or a recursion,
+ * so only the names of the current path are kept in memory, and the tree lock is only taken during each step :
+ * tasks can add nodes while a walk is in progress, removing the current node end the walk.
+ */
+pub struct Walk<'a>
+{
+  tree : &'a Tree,
+  root_id : TreeNodeId,
+  /// Name of the ancestors of the walked node and of the nodes down to the current one.
+  names : Vec<String>,
+  /// Number of ancestors of the walked node.
+  base : usize,
+  current : Option<(TreeNodeId, usize)>,
+  skip : bool,
+  done : bool,
+}
+
+impl Walk<'_>
+{
+  /// Don't walk the descendants of the last node returned.
+  pub fn skip_children(&mut self)
+  {
+    self.skip = true;
+  }
+
+  /// Return the path of the last node returned.
+  pub fn path(&self) -> String
+  {
+    "/".to_owned() + &self.names.join("/")
+  }
+
+  /// Return the node following `node_id` at `depth` in depth-first order, without going out of the walked node.
+  fn following(&self, tree : &TreeArena, node_id : TreeNodeId, depth : usize) -> Option<(TreeNodeId, usize)>
+  {
+    let node = tree.get(node_id).filter(|node| !node.is_removed())?;
+    if !self.skip
+    {
+      if let Some(child_id) = node.first_child()
+      {
+        return Some((child_id, depth + 1))
+      }
+    }
+
+    let (mut node_id, mut depth) = (node_id, depth);
+    while node_id != self.root_id
+    {
+      let node = tree.get(node_id)?;
+      if let Some(sibling_id) = node.next_sibling()
+      {
+        return Some((sibling_id, depth))
+      }
+      node_id = node.parent()?;
+      depth -= 1;
+    }
+    None
+  }
+}
+
+impl Iterator for Walk<'_>
+{
+  type Item = (TreeNodeId, usize);
+
+  fn next(&mut self) -> Option<Self::Item>
+  {
+    if self.done
+    {
+      return None
+    }
+
+    let tree = self.tree.arena();
+    let next = match self.current
+    {
+      None => ancestors_name(&tree, self.root_id).map(|mut names|
+      {
+        names.reverse();
+        self.base = names.len() - 1;
+        self.names = names;
+        (self.root_id, 0)
+      }),
+      Some((node_id, depth)) => self.following(&tree, node_id, depth).map(|(node_id, depth)|
+      {
+        self.names.truncate(self.base + depth);
+        self.names.push(tree[node_id].get().name().to_owned());
+        (node_id, depth)
+      }),
+    };
+
+    self.skip = false;
+    self.current = next;
+    self.done = next.is_none();
+    next
+  }
+}
+
 impl Serialize for Tree
 {
   fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -450,7 +1074,7 @@ impl AttributePath
 #[cfg(test)]
 mod tests
 {
-  use super::{Tree, AttributePath}; 
+  use super::{Tree, AttributePath, NodeState}; 
   use crate::node::Node;
   use crate::value::Value;
 
@@ -508,4 +1132,135 @@ mod tests
     assert!(attribute_path.get_node(&tree).unwrap().name() == "child1");
     assert!(attribute_path.get_value(&tree).unwrap().as_u32() == 0x1000);
   }
+
+  #[test]
+  fn changed_since_version()
+  {
+    let tree = Tree::new();
+    let first_id = tree.add_child(tree.root_id, Node::new("first")).unwrap();
+    let version = tree.version();
+
+    let second_id = tree.add_child(tree.root_id, Node::new("second")).unwrap();
+    tree.touch(first_id);
+
+    let changes = tree.changed_since(version);
+    assert!(changes.len() == 2);
+    assert!(changes[0].0 == second_id && changes[0].2 == NodeState::Added);
+    assert!(changes[1].0 == first_id && changes[1].2 == NodeState::Updated);
+
+    let version = tree.version();
+    tree.remove(first_id).unwrap();
+    let changes = tree.changed_since(version);
+    assert!(changes.len() == 1);
+    assert!(changes[0].0 == first_id && changes[0].2 == NodeState::Removed);
+  }
+
+  #[test]
+  fn tree_stats()
+  {
+    let tree = Tree::new();
+    let node = Node::new("big");
+    node.value().add_attribute("data", Value::Bytes(vec![0; 4096]), None);
+    let big_id = tree.add_child(tree.root_id, node).unwrap();
+    tree.add_child(tree.root_id, Node::new("small")).unwrap();
+
+    let stats = tree.stats();
+    assert!(stats.nodes == 3 && stats.attributes == 1);
+    assert!(stats.memory > 4096);
+    assert!(stats.largest_nodes[0].0 == big_id && stats.largest_nodes.len() == 3);
+  }
+
+  #[test]
+  fn update_references()
+  {
+    let source = Tree::new();
+    let outside_id = source.add_child(source.root_id, Node::new("outside")).unwrap();
+    let dir_id = source.add_child(source.root_id, Node::new("dir")).unwrap();
+    let file_id = source.add_child(dir_id, Node::new("file")).unwrap();
+    let link = Node::new("link");
+    link.value().add_attribute("target", Value::NodeId(file_id), None);
+    link.value().add_attribute("others", Value::Seq(vec![Value::AttributePath(AttributePath{ node_id : file_id, attribute_name : "size".into() }),
+                                                         Value::NodeId(outside_id)]), None);
+    let link_id = source.add_child(dir_id, link).unwrap();
+    assert!(source.references().len() == 3);
+    assert!(source.check_references().broken.is_empty());
+
+    let tree = Tree::new();
+    let (copy_id, report) = tree.import_subtree(tree.root_id, &source, dir_id).unwrap();
+    assert!(tree.node_path(copy_id).unwrap() == "/root/dir");
+    assert!(report.checked == 3 && report.updated == 2 && report.broken.len() == 1);
+    assert!(report.broken[0].target == outside_id && report.broken[0].attribute == "others");
+    let copy_file_id = tree.get_node_id("/root/dir/file").unwrap();
+    let copy_link = tree.get_node("/root/dir/link").unwrap();
+    assert!(matches!(copy_link.value().get_value("target").unwrap(), Value::NodeId(node_id) if node_id == copy_file_id));
+    assert!(matches!(copy_link.value().get_value("others").unwrap().as_vec()[1], Value::Unit));
+    //source is unchanged
+    assert!(matches!(source.get_node_from_id(link_id).unwrap().value().get_value("target").unwrap(), Value::NodeId(node_id) if node_id == file_id));
+
+    //removed nodes are reported and invalidated, moved nodes are updated
+    source.remove(outside_id).unwrap();
+    let report = source.check_references();
+    assert!(report.broken.len() == 1 && report.broken[0].path == "/root/dir/link");
+    let moved_id = source.add_child(source.root_id, Node::new("file")).unwrap();
+    let report = source.remap_references(&[(file_id, moved_id)].into_iter().collect());
+    assert!(report.updated == 2 && report.broken.len() == 1);
+    assert!(source.check_references().broken.is_empty());
+  }
+
+  #[test]
+  fn import_subtree_into_itself()
+  {
+    let tree = Tree::new();
+    let dir_id = tree.add_child(tree.root_id, Node::new("dir")).unwrap();
+    let file_id = tree.add_child(dir_id, Node::new("file")).unwrap();
+    let link = Node::new("link");
+    link.value().add_attribute("target", Value::NodeId(file_id), None);
+    tree.add_child(dir_id, link).unwrap();
+
+    let (copy_id, report) = tree.import_subtree(file_id, &tree, dir_id).unwrap();
+    assert!(tree.node_path(copy_id).unwrap() == "/root/dir/file/dir");
+    assert!(tree.count() == 7 && report.updated == 1);
+    let copy_file_id = tree.get_node_id("/root/dir/file/dir/file").unwrap();
+    assert!(tree.children_id(copy_file_id).is_empty());
+    assert!(matches!(tree.get_node("/root/dir/file/dir/link").unwrap().value().get_value("target").unwrap(), Value::NodeId(node_id) if node_id == copy_file_id));
+
+    tree.import_subtree(dir_id, &tree, dir_id).unwrap();
+    assert!(tree.count() == 13);
+  }
+
+  #[test]
+  fn walk_deep_tree()
+  {
+    let tree = Tree::new();
+    let mut parent_id = tree.root_id;
+    let mut leaf_path = String::from("/root");
+    for depth in 0..10000
+    {
+      parent_id = tree.add_child(parent_id, Node::new(format!("n{}", depth))).unwrap();
+      leaf_path += &format!("/n{}", depth);
+    }
+    let sibling_id = tree.add_child(tree.root_id, Node::new("sibling")).unwrap();
+
+    assert!(tree.node_path(parent_id).unwrap() == leaf_path);
+    assert!(tree.get_node_id(&leaf_path) == Some(parent_id));
+    assert!(tree.walk(tree.root_id).count() == 10002);
+
+    let mut walk = tree.walk(tree.root_id);
+    let mut last = None;
+    while let Some((node_id, depth)) = walk.next()
+    {
+      if depth == 9000
+      {
+        assert!(walk.path().matches('/').count() == 9001);
+        walk.skip_children();
+      }
+      last = Some((node_id, depth));
+    }
+    assert!(last == Some((sibling_id, 1)) && walk.path() == "/root/sibling");
+
+    let first_id = tree.children_id(tree.root_id)[0];
+    assert!(tree.walk(first_id).skip(9999).collect::<Vec<_>>() == vec![(parent_id, 9999)]);
+    tree.remove(first_id).unwrap();
+    assert!(tree.walk(first_id).next().is_none() && tree.stats().nodes == 2);
+  }
 }
@@ -0,0 +1,354 @@
+//! [Validator] check user defined [rules](Rule) on the attributes of the nodes created by the plugins
+//! (`size >= 0`, `modified` in the case time range, `name` not empty, ...), to catch parser bugs and corrupted data early.
+//! Violations are added as warnings on the nodes and kept in the validator to be reported.
+
+use std::sync::RwLock;
+use std::borrow::Cow;
+
+use crate::tree::{Tree, TreeNodeId};
+use crate::value::{Value, NumericOptions};
+
+use serde::{Serialize, Deserialize};
+
+/// Name of the attribute listing the rules violated by a node.
+pub const WARNINGS_ATTRIBUTE : &str = "warnings";
+
+/// Condition that must be true for the value of an attribute.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Check
+{
+  /// Attribute must exist.
+  Exists,
+  /// String, bytes, list, map and attributes must not be empty.
+  NotEmpty,
+  /// Value must be greater or equal.
+  Min(Value),
+  /// Value must be lower or equal.
+  Max(Value),
+  /// Value must be in the inclusive range.
+  Range(Value, Value),
+  /// Value must be equal.
+  Equal(Value),
+  /// Value converted to a string must contain this string.
+  Contains(String),
+}
+
+impl Check
+{
+  /// Return a message describing why `value` doesn't pass the check, `None` if the check pass.
+  /// Missing attributes only fail the [Exists](Check::Exists) check.
+  /// Strings are [parsed](Value::parse_numeric) when compared to numbers, so `"0x10"` or `"1,024"` can be checked against a range.
+  fn check(&self, value : Option<&Value>) -> Option<String>
+  {
+    let value = match (self, value)
+    {
+      (Check::Exists, None) => return Some("is missing".into()),
+      (_, None) => return None,
+      (_, Some(value)) => value,
+    };
+    let value = match self
+    {
+      Check::Min(bound) | Check::Max(bound) | Check::Range(bound, _) | Check::Equal(bound) => coerce(value, bound),
+      _ => Cow::Borrowed(value),
+    };
+    let value = value.as_ref();
+
+    match self
+    {
+      Check::Exists => None,
+      Check::NotEmpty => match value
+      {
+        Value::String(string) if string.is_empty() => Some("is empty".into()),
+        Value::Str(string) if string.is_empty() => Some("is empty".into()),
+        Value::Bytes(bytes) if bytes.is_empty() => Some("is empty".into()),
+        Value::Seq(values) if values.is_empty() => Some("is empty".into()),
+        Value::Map(values) if values.is_empty() => Some("is empty".into()),
+        Value::Attributes(attributes) if attributes.count() == 0 => Some("is empty".into()),
+        _ => None,
+      },
+      Check::Min(min) if value < min => Some(format!("{} is lower than {}", value, min)),
+      Check::Max(max) if value > max => Some(format!("{} is greater than {}", value, max)),
+      Check::Range(min, max) if value < min || value > max => Some(format!("{} is not between {} and {}", value, min, max)),
+      Check::Equal(expected) if value != expected => Some(format!("{} is not {}", value, expected)),
+      Check::Contains(string) if !value.to_string().contains(string.as_str()) => Some(format!("{} doesn't contain {}", value, string)),
+      _ => None,
+    }
+  }
+
+  /// Return true if `value` exists and pass the check.
+  pub fn matches(&self, value : Option<&Value>) -> bool
+  {
+    value.is_some() && self.check(value).is_none()
+  }
+}
+
+/// Return `value` parsed as a number if it's a string and `bound` is a number.
+fn coerce<'a>(value : &'a Value, bound : &Value) -> Cow<'a, Value>
+{
+  match (value, bound.is_numeric())
+  {
+    (Value::String(_) | Value::Str(_), true) => value.parse_numeric(&NumericOptions::default()).map(Cow::Owned).unwrap_or(Cow::Borrowed(value)),
+    _ => Cow::Borrowed(value),
+  }
+}
+
+/// Nodes a [Rule] apply to, all the conditions set must be true.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuleScope
+{
+  /// Nodes created by this plugin, only checked when validating the nodes of a task.
+  pub plugin : Option<String>,
+  /// Nodes which path start with this prefix.
+  pub path : Option<String>,
+  /// Nodes having this attribute, used to select a kind of node (files with a `data` attribute, ...).
+  pub has_attribute : Option<String>,
+}
+
+/// An invariant on the attribute of some nodes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule
+{
+  /// Name of the rule, used in the warnings.
+  pub name : String,
+  /// Nodes checked by this rule.
+  #[serde(default)]
+  pub scope : RuleScope,
+  /// Path of the checked value in the node attributes, as passed to [Value::get_path].
+  pub attribute : String,
+  /// Condition on the value.
+  pub check : Check,
+}
+
+impl Rule
+{
+  /// Return a new [Rule] named `name` checking `attribute` of all nodes.
+  pub fn new<N : Into<String>, A : Into<String>>(name : N, attribute : A, check : Check) -> Self
+  {
+    Rule{ name : name.into(), scope : RuleScope::default(), attribute : attribute.into(), check }
+  }
+
+  /// Restrict the rule to the nodes created by `plugin`.
+  pub fn for_plugin<S : Into<String>>(mut self, plugin : S) -> Self
+  {
+    self.scope.plugin = Some(plugin.into());
+    self
+  }
+
+  /// Restrict the rule to the nodes under `path`.
+  pub fn under<S : Into<String>>(mut self, path : S) -> Self
+  {
+    self.scope.path = Some(path.into());
+    self
+  }
+
+  /// Restrict the rule to the nodes having `attribute`.
+  pub fn with_attribute<S : Into<String>>(mut self, attribute : S) -> Self
+  {
+    self.scope.has_attribute = Some(attribute.into());
+    self
+  }
+}
+
+/// A node that doesn't respect a [Rule].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Violation
+{
+  /// Id of the node.
+  pub node_id : TreeNodeId,
+  /// Path of the node.
+  pub path : String,
+  /// Name of the violated rule.
+  pub rule : String,
+  /// Why the rule is violated.
+  pub message : String,
+}
+
+/// Result of a validation pass.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValidationReport
+{
+  /// Number of nodes checked.
+  pub nodes_checked : u64,
+  /// Rules violations found.
+  pub violations : Vec<Violation>,
+}
+
+/**
+ * Hold the [rules](Rule) and the [violations](Violation) found.
+ * If live checking is enabled the nodes created by each task are checked when the task finish.
+ */
+#[derive(Default)]
+pub struct Validator
+{
+  rules : RwLock<Vec<Rule>>,
+  violations : RwLock<Vec<Violation>>,
+  live : RwLock<bool>,
+}
+
+impl Validator
+{
+  /// Return a new [Validator] without rules.
+  pub fn new() -> Self
+  {
+    Validator::default()
+  }
+
+  /// Add a [Rule].
+  pub fn add_rule(&self, rule : Rule)
+  {
+    self.rules.write().unwrap().push(rule);
+  }
+
+  /// Return the [rules](Rule).
+  pub fn rules(&self) -> Vec<Rule>
+  {
+    self.rules.read().unwrap().clone()
+  }
+
+  /// Remove all the rules and violations.
+  pub fn clear(&self)
+  {
+    self.rules.write().unwrap().clear();
+    self.violations.write().unwrap().clear();
+  }
+
+  /// Enable or disable the checking of the nodes created by each task.
+  pub fn set_live(&self, enable : bool)
+  {
+    *self.live.write().unwrap() = enable;
+  }
+
+  /// Return true if live checking is enabled.
+  pub fn is_live(&self) -> bool
+  {
+    *self.live.read().unwrap()
+  }
+
+  /// Return all the violations found since the creation of the validator or the last [clear](Validator::clear).
+  pub fn violations(&self) -> Vec<Violation>
+  {
+    self.violations.read().unwrap().clone()
+  }
+
+  /// Check the nodes of `tree`, `plugin` is the name of the plugin that created the nodes if known.
+  /// Violations are added to the [WARNINGS_ATTRIBUTE] of the nodes and recorded in the validator.
+  pub fn validate_nodes(&self, tree : &Tree, nodes : &[TreeNodeId], plugin : Option<&str>) -> ValidationReport
+  {
+    let rules = self.rules.read().unwrap();
+    let mut report = ValidationReport::default();
+
+    if rules.is_empty()
+    {
+      return report
+    }
+
+    for node_id in nodes
+    {
+      let node = match tree.get_node_from_id(*node_id)
+      {
+        Some(node) => node,
+        None => continue,
+      };
+      let path = tree.node_path(*node_id).unwrap_or_default();
+      let attributes = Value::Attributes(node.value());
+      report.nodes_checked += 1;
+
+      let mut warnings = Vec::new();
+      for rule in rules.iter()
+      {
+        if rule.scope.plugin.is_some() && rule.scope.plugin.as_deref() != plugin
+        {
+          continue
+        }
+        if matches!(&rule.scope.path, Some(prefix) if !path.starts_with(prefix.as_str()))
+        {
+          continue
+        }
+        if matches!(&rule.scope.has_attribute, Some(name) if node.value().get_value(name).is_none())
+        {
+          continue
+        }
+
+        if let Some(message) = rule.check.check(attributes.get_path(&rule.attribute).as_ref())
+        {
+          let message = format!("{} {}", rule.attribute, message);
+          warnings.push(Value::from(format!("{} : {}", rule.name, message)));
+          report.violations.push(Violation{ node_id : *node_id, path : path.clone(), rule : rule.name.clone(), message });
+        }
+      }
+
+      if !warnings.is_empty()
+      {
+        if let Some(Value::Seq(previous)) = node.value().get_value(WARNINGS_ATTRIBUTE)
+        {
+          warnings.splice(0..0, previous.into_iter().filter(|warning| !warnings.contains(warning)).collect::<Vec<_>>());
+        }
+        node.value().remove_attribute(WARNINGS_ATTRIBUTE);
+        node.value().add_attribute(WARNINGS_ATTRIBUTE, Value::Seq(warnings), None);
+        tree.touch(*node_id);
+      }
+    }
+
+    self.violations.write().unwrap().extend(report.violations.iter().cloned());
+    report
+  }
+
+  /// Check all the nodes of `tree`, rules restricted to a plugin are ignored.
+  pub fn validate(&self, tree : &Tree) -> ValidationReport
+  {
+    let nodes : Vec<TreeNodeId> = tree.root_id.descendants(&tree.arena()).collect();
+    self.validate_nodes(tree, &nodes, None)
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{Validator, Rule, Check, WARNINGS_ATTRIBUTE};
+  use crate::tree::Tree;
+  use crate::node::Node;
+  use crate::value::Value;
+
+  #[test]
+  fn validate_rules()
+  {
+    let tree = Tree::new();
+    let file = Node::new("file");
+    file.value().add_attribute("name", Value::from(""), None);
+    file.value().add_attribute("size", Value::I64(-1), None);
+    let file_id = tree.add_child(tree.root_id, file).unwrap();
+    let dir = Node::new("dir");
+    dir.value().add_attribute("size", Value::I64(-1), None);
+    tree.add_child(tree.root_id, dir).unwrap();
+
+    let validator = Validator::new();
+    validator.add_rule(Rule::new("positive_size", "size", Check::Min(Value::U8(0))).with_attribute("name"));
+    validator.add_rule(Rule::new("named", "name", Check::NotEmpty));
+    validator.add_rule(Rule::new("has_size", "size", Check::Exists).under("/root/dir"));
+    validator.add_rule(Rule::new("fat_size", "size", Check::Range(Value::U8(0), Value::U8(10))).for_plugin("fat"));
+
+    let report = validator.validate(&tree);
+    assert!(report.nodes_checked == 3);
+    assert!(report.violations.len() == 2);
+    assert!(report.violations.iter().all(|violation| violation.node_id == file_id && violation.path == "/root/file"));
+
+    let warnings = tree.get_node("/root/file").unwrap().value().get_value(WARNINGS_ATTRIBUTE).unwrap().as_vec();
+    assert!(warnings.len() == 2);
+    assert!(warnings[0].as_string() == "positive_size : size -1 is lower than 0");
+
+    let report = validator.validate_nodes(&tree, &[file_id], Some("fat"));
+    assert!(report.violations.len() == 3);
+    assert!(tree.get_node("/root/file").unwrap().value().get_value(WARNINGS_ATTRIBUTE).unwrap().as_vec().len() == 3);
+    assert!(validator.violations().len() == 5);
+  }
+
+  #[test]
+  fn check_numeric_strings()
+  {
+    assert!(Check::Min(Value::U64(1024)).matches(Some(&Value::from("0x400"))));
+    assert!(Check::Range(Value::U64(1000), Value::U64(2000)).matches(Some(&Value::from("1,024"))));
+    assert!(Check::Equal(Value::U64(4096)).matches(Some(&Value::from("4 KB"))));
+    assert!(!Check::Max(Value::U64(10)).matches(Some(&Value::from("0x1A3F"))));
+    assert!(Check::Contains("0x".into()).matches(Some(&Value::from("0x1A3F"))));
+  }
+}
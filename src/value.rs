@@ -2,32 +2,65 @@
 
 use std::fmt;
 use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
 use std::sync::{Arc};
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::net::IpAddr;
 
 use crate::vfile::{VFileBuilder};
 use crate::tree::{TreeNodeId, AttributePath};
 use crate::attribute::Attributes;
-use crate::reflect::ReflectStruct;
+use crate::reflect::{ReflectStruct, ReflectEnum, registry};
+use crate::error::RustructError;
+use crate::tempvfile::TempVFileBuilder;
 
 use serde::{Serialize, Deserialize};
 use serde::ser::{Serializer};
-use chrono::{DateTime, Utc};
+use serde::de::{Deserializer, Visitor, SeqAccess, MapAccess, Error as DeError};
+use chrono::{DateTime, Utc, Duration};
 use std::borrow::Cow;
+use uuid::Uuid;
+
+pub mod tagged;
+pub mod codec;
+pub mod display;
+pub mod format;
+pub mod visit;
+pub mod schema;
+pub mod method;
+pub mod ser;
+pub mod de;
+pub mod passthrough;
+pub mod numeric;
+
+pub use display::{DisplayLimits, set_display_limits, display_limits};
+pub use method::{Method, Parameter};
+pub use ser::{to_value, to_attributes};
+pub use de::from_value;
+pub use numeric::NumericOptions;
+
+/// Size from which [Value::blob] store bytes in a temporary file rather than in memory.
+pub const BLOB_THRESHOLD : usize = 1024 * 1024;
 
 type ValueFunc = Arc<Box<dyn Fn() -> Value + Sync + Send>>;
 type ValueFuncArg = Arc<Box<dyn Fn(Value) -> Value + Sync + Send>>;
 
 /**
  *  [Value] is a clonable and serializable variant kind use as value of [Attribute](crate::attribute::Attribute).
+ *
+ *  Values are serialized untagged, so a deserialized value is the smallest variant able to contain it rather than its original variant.
+ *  Maps are deserialized as [Attributes], or as a [registered](registry) [ReflectStruct] when their keys are exactly the fields of a single registered type.
+ *  A [Duration](Value::Duration) is serialized as its number of nanoseconds and deserialized as an integer,
+ *  and formats without 128 bits integers like JSON deserialize a [U128](Value::U128) or [I128](Value::I128)
+ *  out of the 64 bits range as a floating point number.
+ *  Use [TaggedValue](tagged::TaggedValue) or the binary [codec] to keep the exact type of values.
  */
-#[derive(Deserialize,Serialize, Clone)]
+#[derive(Serialize, Clone)]
 #[serde(untagged)]
 pub enum Value 
 {
-    #[serde(skip_deserializing)] 
     Attributes(Attributes),
-    #[serde(skip_deserializing)]
     ReflectStruct(Arc<dyn ReflectStruct+ Sync + Send>),
     VFileBuilder(Arc< dyn VFileBuilder>),
     Bool(bool),
@@ -42,6 +75,9 @@ pub enum Value
     I32(i32),
     I64(i64),
 
+    U128(u128),
+    I128(i128),
+
     F32(f32),
     F64(f64),
   
@@ -56,17 +92,23 @@ pub enum Value
     Newtype(Box<Value>),
     Seq(Vec<Value>),
     Bytes(Vec<u8>),
+    Blob(Arc<dyn VFileBuilder>),
     DateTime(DateTime<Utc>),
+    #[serde(serialize_with="serialize_duration")]
+    Duration(Duration),
+    IpAddr(IpAddr),
+    Uuid(Uuid),
 
     Map(HashMap<String, Value>),
-    #[serde(skip_deserializing, serialize_with="serialize_func")] 
+    #[serde(serialize_with="serialize_func")] 
     Func(ValueFunc),
-    #[serde(skip_deserializing, serialize_with="serialize_value_func")] 
+    #[serde(serialize_with="serialize_value_func")] 
     FuncArg(ValueFuncArg, Box<Value>),
 
     NodeId(TreeNodeId),
     AttributePath(AttributePath),
-    //Enum(ReflectEnum),//Enum(ReflectStruct)
+    Enum(Arc<dyn ReflectEnum + Sync + Send>),
+    Method(Arc<Method>),
     //None,
 }
 
@@ -77,6 +119,15 @@ fn serialize_func<S>(func : &ValueFunc, serializer: S) -> Result<S::Ok, S::Error
    func().serialize(serializer)
 }
 
+/// Serialize a [Duration] as a number of nanoseconds.
+fn serialize_duration<S>(duration : &Duration, serializer : S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+{
+   let nanoseconds = duration.num_seconds() as i128 * 1_000_000_000 + duration.subsec_nanos() as i128;
+   serializer.serialize_i128(nanoseconds)
+}
+
 fn serialize_value_func<S>(func : &ValueFuncArg, arg : &Value, serializer : S) -> Result<S::Ok, S::Error>
   where 
     S: Serializer,
@@ -84,12 +135,279 @@ fn serialize_value_func<S>(func : &ValueFuncArg, arg : &Value, serializer : S) -
    func(Value::Newtype(Box::new(arg.clone()))).serialize(serializer)
 }
 
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor
+{
+  type Value = Value;
+
+  fn expecting(&self, formatter : &mut fmt::Formatter) -> fmt::Result
+  {
+    formatter.write_str("any value")
+  }
+
+  fn visit_bool<E : DeError>(self, v : bool) -> Result<Value, E>
+  {
+    Ok(Value::Bool(v))
+  }
+
+  fn visit_u64<E : DeError>(self, v : u64) -> Result<Value, E>
+  {
+    Ok(u8::try_from(v).map(Value::U8)
+      .or_else(|_| u16::try_from(v).map(Value::U16))
+      .or_else(|_| u32::try_from(v).map(Value::U32))
+      .unwrap_or(Value::U64(v)))
+  }
+
+  fn visit_i64<E : DeError>(self, v : i64) -> Result<Value, E>
+  {
+    if v >= 0
+    {
+      return self.visit_u64(v as u64)
+    }
+    Ok(i8::try_from(v).map(Value::I8)
+      .or_else(|_| i16::try_from(v).map(Value::I16))
+      .or_else(|_| i32::try_from(v).map(Value::I32))
+      .unwrap_or(Value::I64(v)))
+  }
+
+  fn visit_u128<E : DeError>(self, v : u128) -> Result<Value, E>
+  {
+    match u64::try_from(v)
+    {
+      Ok(v) => self.visit_u64(v),
+      Err(_) => Ok(Value::U128(v)),
+    }
+  }
+
+  fn visit_i128<E : DeError>(self, v : i128) -> Result<Value, E>
+  {
+    match (v >= 0, i64::try_from(v))
+    {
+      (true, _) => self.visit_u128(v as u128),
+      (false, Ok(v)) => self.visit_i64(v),
+      (false, Err(_)) => Ok(Value::I128(v)),
+    }
+  }
+
+  fn visit_f64<E : DeError>(self, v : f64) -> Result<Value, E>
+  {
+    Ok(Value::F64(v))
+  }
+
+  fn visit_char<E : DeError>(self, v : char) -> Result<Value, E>
+  {
+    Ok(Value::Char(v))
+  }
+
+  fn visit_str<E : DeError>(self, v : &str) -> Result<Value, E>
+  {
+    Ok(Value::String(v.to_string()))
+  }
+
+  fn visit_string<E : DeError>(self, v : String) -> Result<Value, E>
+  {
+    Ok(Value::String(v))
+  }
+
+  fn visit_bytes<E : DeError>(self, v : &[u8]) -> Result<Value, E>
+  {
+    Ok(Value::Bytes(v.to_vec()))
+  }
+
+  fn visit_byte_buf<E : DeError>(self, v : Vec<u8>) -> Result<Value, E>
+  {
+    Ok(Value::Bytes(v))
+  }
+
+  fn visit_unit<E : DeError>(self) -> Result<Value, E>
+  {
+    Ok(Value::Unit)
+  }
 
+  fn visit_none<E : DeError>(self) -> Result<Value, E>
+  {
+    Ok(Value::Option(None))
+  }
+
+  fn visit_some<D>(self, deserializer : D) -> Result<Value, D::Error>
+    where D : Deserializer<'de>,
+  {
+    Ok(Value::Option(Some(Box::new(Value::deserialize(deserializer)?))))
+  }
+
+  fn visit_newtype_struct<D>(self, deserializer : D) -> Result<Value, D::Error>
+    where D : Deserializer<'de>,
+  {
+    Ok(Value::Newtype(Box::new(Value::deserialize(deserializer)?)))
+  }
+
+  fn visit_seq<A>(self, mut access : A) -> Result<Value, A::Error>
+    where A : SeqAccess<'de>,
+  {
+    let mut values = Vec::with_capacity(access.size_hint().unwrap_or(0).min(4096));
+    while let Some(value) = access.next_element::<Value>()?
+    {
+      values.push(value);
+    }
+    Ok(Value::Seq(values))
+  }
+
+  fn visit_map<A>(self, mut access : A) -> Result<Value, A::Error>
+    where A : MapAccess<'de>,
+  {
+    let mut fields = Vec::with_capacity(access.size_hint().unwrap_or(0).min(4096));
+    while let Some(field) = access.next_entry::<String, Value>()?
+    {
+      fields.push(field);
+    }
+    Ok(Value::from_fields(fields))
+  }
+}
+
+/// [Value] is deserialized from any self describing format, see [Value] for the variants returned.
+impl<'de> Deserialize<'de> for Value
+{
+  fn deserialize<D>(deserializer : D) -> Result<Self, D::Error>
+    where D : Deserializer<'de>,
+  {
+    deserializer.deserialize_any(ValueVisitor)
+  }
+}
+
+
+/// Numeric representation of a [Value] used to compare numbers of different types.
+#[derive(Clone, Copy)]
+enum Numeric
+{
+  Signed(i128),
+  Unsigned(u128),
+  Float(f64),
+}
+
+impl Numeric
+{
+  /// Compare two integers without loss.
+  fn cmp_integer(a : Numeric, b : Numeric) -> Ordering
+  {
+    match (a, b)
+    {
+      (Numeric::Signed(a), Numeric::Signed(b)) => a.cmp(&b),
+      (Numeric::Unsigned(a), Numeric::Unsigned(b)) => a.cmp(&b),
+      (Numeric::Signed(a), Numeric::Unsigned(b)) => if a < 0 { Ordering::Less } else { (a as u128).cmp(&b) },
+      (Numeric::Unsigned(a), Numeric::Signed(b)) => if b < 0 { Ordering::Greater } else { a.cmp(&(b as u128)) },
+      _ => unreachable!(),
+    }
+  }
+
+  /// Compare an integer with a float, if the float is integral it's compared exactly with the integer.
+  fn cmp_float(integer : Numeric, float : f64) -> Option<Ordering>
+  {
+    let approx = match integer
+    {
+      Numeric::Signed(val) => val as f64,
+      Numeric::Unsigned(val) => val as f64,
+      Numeric::Float(_) => unreachable!(),
+    };
+
+    match approx.partial_cmp(&float)?
+    {
+      Ordering::Equal if float >= 0.0 => Some(Numeric::cmp_integer(integer, Numeric::Unsigned(float as u128))),
+      Ordering::Equal => Some(Numeric::cmp_integer(integer, Numeric::Signed(float as i128))),
+      ordering => Some(ordering),
+    }
+  }
+
+  fn partial_cmp(self, other : Numeric) -> Option<Ordering>
+  {
+    match (self, other)
+    {
+      (Numeric::Float(a), Numeric::Float(b)) => a.partial_cmp(&b),
+      (integer, Numeric::Float(b)) => Numeric::cmp_float(integer, b),
+      (Numeric::Float(a), integer) => Numeric::cmp_float(integer, a).map(Ordering::reverse),
+      (a, b) => Some(Numeric::cmp_integer(a, b)),
+    }
+  }
+
+  /// Hash the number so integers and integral floats of same value have the same hash.
+  fn hash<H : Hasher>(self, state : &mut H)
+  {
+    match self
+    {
+      Numeric::Signed(val) if val >= 0 => (val as u128).hash(state),
+      Numeric::Signed(val) => val.hash(state),
+      Numeric::Unsigned(val) => val.hash(state),
+      Numeric::Float(val) if val.fract() == 0.0 && val >= 0.0 && val <= u128::MAX as f64 => (val as u128).hash(state),
+      Numeric::Float(val) if val.fract() == 0.0 && val < 0.0 && val >= i128::MIN as f64 => (val as i128).hash(state),
+      Numeric::Float(val) => val.to_bits().hash(state),
+    }
+  }
+}
+
+/// Return true if both [Arc] point to the same object.
+fn same_arc<T : ?Sized, U : ?Sized>(a : &Arc<T>, b : &Arc<U>) -> bool
+{
+  Arc::as_ptr(a) as *const () == Arc::as_ptr(b) as *const ()
+}
+
+/**
+ * Comparison of [Value] is structural and follow these rules :
+ * - Numbers of any type are compared by value (`U8(1) == U64(1)`, `I32(-1) < U8(0)`, `U64(2) == F64(2.0)`).
+ * - `String` and `Str` are compared by content.
+ * - `Seq` and `Bytes` are compared element by element, `Map` and `Attributes` by name whatever their order.
+ * - `ReflectStruct` are equal if they have the same name and same field values.
+ * - `Enum` are equal if they have the same name, variant, numeric value and payload, and are ordered by numeric value.
+ * - `VFileBuilder`, `Blob`, `Func`, `FuncArg` and `Method` are equal only if they point to the same object.
+ * - Values of different kinds are never equal and are not ordered.
+ */
 impl std::cmp::PartialEq for Value
 {
   fn eq(&self, other : &Self) -> bool
   {
-     self == other 
+    if let (Some(a), Some(b)) = (self.numeric(), other.numeric())
+    {
+      return a.partial_cmp(b) == Some(Ordering::Equal)
+    }
+
+    if let (Some(a), Some(b)) = (self.str(), other.str())
+    {
+      return a == b
+    }
+
+    match (self, other)
+    {
+      (Value::Bool(a), Value::Bool(b)) => a == b,
+      (Value::Char(a), Value::Char(b)) => a == b,
+      (Value::Unit, Value::Unit) => true,
+      (Value::Option(a), Value::Option(b)) => a == b,
+      (Value::Newtype(a), Value::Newtype(b)) => a == b,
+      (Value::Seq(a), Value::Seq(b)) => a == b,
+      (Value::Bytes(a), Value::Bytes(b)) => a == b,
+      (Value::DateTime(a), Value::DateTime(b)) => a == b,
+      (Value::Duration(a), Value::Duration(b)) => a == b,
+      (Value::IpAddr(a), Value::IpAddr(b)) => a == b,
+      (Value::Uuid(a), Value::Uuid(b)) => a == b,
+      (Value::Map(a), Value::Map(b)) => a == b,
+      (Value::NodeId(a), Value::NodeId(b)) => a == b,
+      (Value::AttributePath(a), Value::AttributePath(b)) => a == b,
+      (Value::Attributes(a), Value::Attributes(b)) => a == b,
+      (Value::ReflectStruct(a), Value::ReflectStruct(b)) => 
+      {
+        same_arc(a, b) || (a.name() == b.name() && a.names() == b.names() && 
+                           a.names().iter().all(|name| a.get_value(name) == b.get_value(name)))
+      },
+      (Value::Enum(a), Value::Enum(b)) =>
+      {
+        same_arc(a, b) || (a.name() == b.name() && a.variant() == b.variant() && a.discriminant() == b.discriminant() &&
+                           a.payload() == b.payload())
+      },
+      (Value::VFileBuilder(a), Value::VFileBuilder(b)) => same_arc(a, b),
+      (Value::Blob(a), Value::Blob(b)) => same_arc(a, b),
+      (Value::Func(a), Value::Func(b)) => same_arc(a, b),
+      (Value::FuncArg(a, a_arg), Value::FuncArg(b, b_arg)) => same_arc(a, b) && a_arg == b_arg,
+      (Value::Method(a), Value::Method(b)) => same_arc(a, b),
+      _ => false,
+    }
   }
 }
 
@@ -97,26 +415,238 @@ impl std::cmp::PartialOrd for Value
 {
   fn partial_cmp(&self, other : &Self) -> Option<Ordering>
   {
-     if self == other
-     {
-       return Some(Ordering::Equal)
-     }
+    if let Some(ordering) = self.compare_numeric(other)
+    {
+      return Some(ordering)
+    }
 
-     if self > other
-     {
-      return Some(Ordering::Greater)
-     }
+    if let (Some(a), Some(b)) = (self.str(), other.str())
+    {
+      return Some(a.cmp(b))
+    }
 
-     if self < other
-     {
-       return Some(Ordering::Less)
-     }
+    match (self, other)
+    {
+      (Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
+      (Value::Char(a), Value::Char(b)) => a.partial_cmp(b),
+      (Value::Unit, Value::Unit) => Some(Ordering::Equal),
+      (Value::Option(a), Value::Option(b)) => a.partial_cmp(b),
+      (Value::Newtype(a), Value::Newtype(b)) => a.partial_cmp(b),
+      (Value::Seq(a), Value::Seq(b)) => a.partial_cmp(b),
+      (Value::Bytes(a), Value::Bytes(b)) => a.partial_cmp(b),
+      (Value::DateTime(a), Value::DateTime(b)) => a.partial_cmp(b),
+      (Value::Duration(a), Value::Duration(b)) => a.partial_cmp(b),
+      (Value::IpAddr(a), Value::IpAddr(b)) => a.partial_cmp(b),
+      (Value::Uuid(a), Value::Uuid(b)) => a.partial_cmp(b),
+      (Value::NodeId(a), Value::NodeId(b)) => a.partial_cmp(b),
+      (a, b) if a == b => Some(Ordering::Equal),
+      (Value::Enum(a), Value::Enum(b)) if a.name() == b.name() => a.discriminant().partial_cmp(&b.discriminant()),
+      _ => None,
+    }
+  }
+}
+
+/// [Hash] is consistent with [PartialEq] : equal values (like `U8(1)` and `F64(1.0)`) have the same hash.
+impl Hash for Value
+{
+  fn hash<H : Hasher>(&self, state : &mut H)
+  {
+    if let Some(numeric) = self.numeric()
+    {
+      state.write_u8(0);
+      return numeric.hash(state)
+    }
+
+    if let Some(string) = self.str()
+    {
+      state.write_u8(1);
+      return string.hash(state)
+    }
+
+    (self.type_id() as u8).hash(state);
+    match self
+    {
+      Value::Bool(val) => val.hash(state),
+      Value::Char(val) => val.hash(state),
+      Value::Option(val) => val.hash(state),
+      Value::Newtype(val) => val.hash(state),
+      Value::Seq(val) => val.hash(state),
+      Value::Bytes(val) => val.hash(state),
+      Value::DateTime(val) => val.hash(state),
+      Value::Duration(val) => val.hash(state),
+      Value::IpAddr(val) => val.hash(state),
+      Value::Uuid(val) => val.hash(state),
+      Value::NodeId(val) => val.hash(state),
+      Value::AttributePath(val) => { val.node_id.hash(state); val.attribute_name.hash(state) },
+      //Map and Attributes are compared whatever their order so we only hash their size
+      Value::Map(val) => val.len().hash(state),
+      Value::Attributes(val) => val.count().hash(state),
+      Value::ReflectStruct(val) => val.name().hash(state),
+      Value::Enum(val) => { val.name().hash(state); val.discriminant().hash(state) },
+      Value::VFileBuilder(val) => (Arc::as_ptr(val) as *const () as usize).hash(state),
+      Value::Blob(val) => (Arc::as_ptr(val) as *const () as usize).hash(state),
+      Value::Func(val) => (Arc::as_ptr(val) as *const () as usize).hash(state),
+      Value::FuncArg(val, arg) => { (Arc::as_ptr(val) as *const () as usize).hash(state); arg.hash(state) },
+      Value::Method(val) => (Arc::as_ptr(val) as *const () as usize).hash(state),
+      _ => (),
+    }
+  }
+}
+
+impl Value
+{
+  /// Return the value as a [Numeric] if it's a number.
+  fn numeric(&self) -> Option<Numeric>
+  {
+    match self
+    {
+      Value::U8(val) => Some(Numeric::Unsigned(*val as u128)),
+      Value::U16(val) => Some(Numeric::Unsigned(*val as u128)),
+      Value::U32(val) => Some(Numeric::Unsigned(*val as u128)),
+      Value::U64(val) => Some(Numeric::Unsigned(*val as u128)),
+      Value::USize(val) => Some(Numeric::Unsigned(*val as u128)),
+      Value::I8(val) => Some(Numeric::Signed(*val as i128)),
+      Value::I16(val) => Some(Numeric::Signed(*val as i128)),
+      Value::I32(val) => Some(Numeric::Signed(*val as i128)),
+      Value::I64(val) => Some(Numeric::Signed(*val as i128)),
+      Value::U128(val) => Some(Numeric::Unsigned(*val)),
+      Value::I128(val) => Some(Numeric::Signed(*val)),
+      Value::F32(val) => Some(Numeric::Float(*val as f64)),
+      Value::F64(val) => Some(Numeric::Float(*val)),
+      _ => None,
+    }
+  }
+
+  /// Return the value of a deserialized map of `fields`, a [VFileBuilder] if it's a serialized builder,
+  /// a [ReflectStruct] if its fields are the fields of a single [registered](registry) type, or [Attributes] otherwise.
+  fn from_fields(fields : Vec<(String, Value)>) -> Value
+  {
+    let mut attributes = Attributes::new();
+    for (name, value) in fields.iter()
+    {
+      attributes.add_attribute(name.clone(), value.clone(), None);
+    }
+
+    if attributes.get_value("type").is_some()
+    {
+      if let Ok(builder) = de::from_value::<Arc<dyn VFileBuilder>>(Value::Attributes(attributes.clone()))
+      {
+        return Value::VFileBuilder(builder)
+      }
+    }
+
+    let names : Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+    if let Some(reflect) = registry::find_by_fields(&names).and_then(|name| registry::rehydrate(name, fields.clone()))
+    {
+      return Value::ReflectStruct(reflect)
+    }
+    Value::Attributes(attributes)
+  }
+
+  /// Return the value as a [str] if it's a `String` or a `Str`.
+  fn str(&self) -> Option<&str>
+  {
+    match self
+    {
+      Value::String(val) => Some(val),
+      Value::Str(val) => Some(val),
+      _ => None,
+    }
+  }
+
+  /// Compare two numeric values whatever their types, integers are compared without loss.
+  /// Return `None` if one of the value is not a number or if a float is `NaN`.
+  pub fn compare_numeric(&self, other : &Value) -> Option<Ordering>
+  {
+    self.numeric()?.partial_cmp(other.numeric()?)
+  }
+
+  /// Return true if the value is an integer or a float.
+  pub fn is_numeric(&self) -> bool
+  {
+    self.numeric().is_some()
+  }
+
+  /// Convert any integer, or float without fractional part, to an [i128].
+  /// Return `None` if the value is not a number or doesn't fit.
+  pub fn to_i128(&self) -> Option<i128>
+  {
+    match self.numeric()?
+    {
+      Numeric::Signed(val) => Some(val),
+      Numeric::Unsigned(val) => i128::try_from(val).ok(),
+      Numeric::Float(val) if val.fract() == 0.0 && val >= i128::MIN as f64 && val < i128::MAX as f64 => Some(val as i128),
+      Numeric::Float(_) => None,
+    }
+  }
+
+  /// Convert any integer, or float without fractional part, to an [u128].
+  /// Return `None` if the value is not a number, is negative or doesn't fit.
+  pub fn to_u128(&self) -> Option<u128>
+  {
+    match self.numeric()?
+    {
+      Numeric::Signed(val) => u128::try_from(val).ok(),
+      Numeric::Unsigned(val) => Some(val),
+      Numeric::Float(val) if val.fract() == 0.0 && val >= 0.0 && val < u128::MAX as f64 => Some(val as u128),
+      Numeric::Float(_) => None,
+    }
+  }
+
+  /// Convert any integer, or float without fractional part, to an [i64].
+  /// Return `None` if the value is not a number or doesn't fit.
+  pub fn to_i64(&self) -> Option<i64>
+  {
+    i64::try_from(self.to_i128()?).ok()
+  }
+
+  /// Convert any integer, or float without fractional part, to an [u64].
+  /// Return `None` if the value is not a number, is negative or doesn't fit.
+  pub fn to_u64(&self) -> Option<u64>
+  {
+    u64::try_from(self.to_u128()?).ok()
+  }
+
+  /// Convert any number to an [f64], large integers are rounded to the nearest float.
+  /// Return `None` if the value is not a number.
+  pub fn to_f64(&self) -> Option<f64>
+  {
+    match self.numeric()?
+    {
+      Numeric::Signed(val) => Some(val as f64),
+      Numeric::Unsigned(val) => Some(val as f64),
+      Numeric::Float(val) => Some(val),
+    }
+  }
+
+  /// Convert any number to an [u64], negative numbers are clamped to 0, too large numbers to [u64::MAX] and floats are truncated.
+  /// Return `None` if the value is not a number or is `NaN`.
+  pub fn as_u64_lossy(&self) -> Option<u64>
+  {
+    match self.numeric()?
+    {
+      Numeric::Signed(val) => Some(val.clamp(0, u64::MAX as i128) as u64),
+      Numeric::Unsigned(val) => Some(val.min(u64::MAX as u128) as u64),
+      Numeric::Float(val) if val.is_nan() => None,
+      Numeric::Float(val) => Some(val as u64),
+    }
+  }
 
-     None
+  /// Convert any number to an [i64], too small or too large numbers are clamped to [i64::MIN] and [i64::MAX] and floats are truncated.
+  /// Return `None` if the value is not a number or is `NaN`.
+  pub fn as_i64_lossy(&self) -> Option<i64>
+  {
+    match self.numeric()?
+    {
+      Numeric::Signed(val) => Some(val.clamp(i64::MIN as i128, i64::MAX as i128) as i64),
+      Numeric::Unsigned(val) => Some(val.min(i64::MAX as u128) as i64),
+      Numeric::Float(val) if val.is_nan() => None,
+      Numeric::Float(val) => Some(val as i64),
+    }
   }
 }
 
-#[derive(Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[repr(u8)]
 pub enum ValueTypeId
 {
@@ -149,9 +679,35 @@ pub enum ValueTypeId
     FuncArg, 
     NodeId,
     AttributePath,
+    U128,
+    I128,
+    Duration,
+    IpAddr,
+    Uuid,
+    Blob,
+    Enum,
+    Method,
     //None,
 }
 
+impl TryFrom<u8> for ValueTypeId
+{
+  type Error = RustructError;
+
+  fn try_from(id : u8) -> Result<Self, Self::Error>
+  {
+    const IDS : [ValueTypeId; 37] = [ValueTypeId::Attributes, ValueTypeId::ReflectStruct, ValueTypeId::VFileBuilder, ValueTypeId::Bool,
+      ValueTypeId::U8, ValueTypeId::U16, ValueTypeId::U32, ValueTypeId::U64, ValueTypeId::I8, ValueTypeId::I16, ValueTypeId::I32, ValueTypeId::I64,
+      ValueTypeId::F32, ValueTypeId::F64, ValueTypeId::USize, ValueTypeId::Char, ValueTypeId::String, ValueTypeId::Str, ValueTypeId::Unit,
+      ValueTypeId::Option, ValueTypeId::Newtype, ValueTypeId::Seq, ValueTypeId::Bytes, ValueTypeId::DateTime, ValueTypeId::Map, ValueTypeId::Func,
+      ValueTypeId::FuncArg, ValueTypeId::NodeId, ValueTypeId::AttributePath, ValueTypeId::U128, ValueTypeId::I128, ValueTypeId::Duration,
+      ValueTypeId::IpAddr, ValueTypeId::Uuid, ValueTypeId::Blob, ValueTypeId::Enum,
+      ValueTypeId::Method];
+
+    IDS.get(id as usize).cloned().ok_or(RustructError::ValueTypeMismatch)
+  }
+}
+
 impl Value
 {
   #[inline]
@@ -171,6 +727,8 @@ impl Value
       Value::I16(_) => ValueTypeId::I16,
       Value::I32(_) => ValueTypeId::I32,
       Value::I64(_) => ValueTypeId::I64,
+      Value::U128(_) => ValueTypeId::U128,
+      Value::I128(_) => ValueTypeId::I128,
       Value::F32(_) => ValueTypeId::F32,
       Value::F64(_) => ValueTypeId::F64,
       Value::USize(_) => ValueTypeId::USize,
@@ -183,6 +741,12 @@ impl Value
       Value::Seq(_) => ValueTypeId::Seq, 
       Value::Bytes(_) => ValueTypeId::Bytes,
       Value::DateTime(_) => ValueTypeId::DateTime,
+      Value::Duration(_) => ValueTypeId::Duration,
+      Value::IpAddr(_) => ValueTypeId::IpAddr,
+      Value::Uuid(_) => ValueTypeId::Uuid,
+      Value::Blob(_) => ValueTypeId::Blob,
+      Value::Enum(_) => ValueTypeId::Enum,
+      Value::Method(_) => ValueTypeId::Method,
       Value::Map(_) => ValueTypeId::Map, 
       Value::Func(_) => ValueTypeId::Func, 
       Value::FuncArg(_, _) => ValueTypeId::FuncArg, 
@@ -272,6 +836,8 @@ as_from_primitive!(Value::I8, i8);
 as_from_primitive!(Value::I16, i16);
 as_from_primitive!(Value::I32, i32);
 as_from_primitive!(Value::I64, i64);
+as_from_primitive!(Value::U128, u128);
+as_from_primitive!(Value::I128, i128);
 as_from_primitive!(Value::F32, f32);
 as_from_primitive!(Value::F64, f64);
 as_from_primitive!(Value::USize, usize);
@@ -286,6 +852,9 @@ from_primitive!(Value::Newtype, Box<Value>);
 //from_primitive!(Value::Seq, Vec<Value>); //replaced by From<Vec<T>>
 //from_primitive!(Value::Bytes, Vec<u8>); //replaced by From Vec<T> 
 from_primitive!(Value::DateTime, DateTime<Utc>);
+from_primitive!(Value::Duration, Duration);
+from_primitive!(Value::IpAddr, IpAddr);
+from_primitive!(Value::Uuid, Uuid);
 
 from_primitive!(Value::Map, HashMap<String, Value>); //use map Value,Value and use generic like Seq
 from_primitive!(Value::VFileBuilder, Arc<dyn VFileBuilder>);
@@ -296,6 +865,8 @@ from_primitive!(Value::NodeId, TreeNodeId);
 from_primitive!(Value::AttributePath, AttributePath);
 from_primitive!(Value::Attributes, Attributes);
 from_primitive!(Value::ReflectStruct, Arc<dyn ReflectStruct + Sync + Send>);
+from_primitive!(Value::Enum, Arc<dyn ReflectEnum + Sync + Send>);
+from_primitive!(Value::Method, Arc<Method>);
 //from_primitive!(Value::Option, Option<Box<Value>>);
 //from_primitive!(Value::Option, Option<Value>);
 
@@ -450,6 +1021,46 @@ impl Value
     }
   }
 
+  #[inline]
+  pub fn as_enum(&self) -> Arc<dyn ReflectEnum + Sync + Send>
+  {
+    match self
+    {
+      Value::Enum(val) => val.clone(),
+      _ => panic!("Can't convert value to Enum"),
+    }
+  }
+
+  #[inline]
+  pub fn try_as_enum(&self) -> Option<Arc<dyn ReflectEnum + Sync + Send>>
+  {
+    match self
+    {
+      Value::Enum(val) => Some(val.clone()),
+      _ => None,
+    }
+  }
+
+  #[inline]
+  pub fn as_method(&self) -> Arc<Method>
+  {
+    match self
+    {
+      Value::Method(val) => val.clone(),
+      _ => panic!("Can't convert value to Method"),
+    }
+  }
+
+  #[inline]
+  pub fn try_as_method(&self) -> Option<Arc<Method>>
+  {
+    match self
+    {
+      Value::Method(val) => Some(val.clone()),
+      _ => None,
+    }
+  }
+
   #[inline]
   pub fn as_vfile_builder(&self) -> Arc<dyn VFileBuilder>
   {
@@ -470,6 +1081,42 @@ impl Value
     }
   }
 
+  #[inline]
+  pub fn as_blob(&self) -> Arc<dyn VFileBuilder>
+  {
+    match self
+    {
+      Value::Blob(val) => val.clone(),
+      _ => panic!("Can't convert value to Blob"),
+    }
+  }
+
+  #[inline]
+  pub fn try_as_blob(&self) -> Option<Arc<dyn VFileBuilder>>
+  {
+    match self
+    {
+      Value::Blob(val) => Some(val.clone()),
+      _ => None,
+    }
+  }
+
+  /// Return a `Bytes` value if `bytes` size is below [BLOB_THRESHOLD], or a `Blob` backed by a temporary file otherwise.
+  pub fn blob(bytes : Vec<u8>) -> anyhow::Result<Value>
+  {
+    Value::blob_with_threshold(bytes, BLOB_THRESHOLD)
+  }
+
+  /// Return a `Bytes` value if `bytes` size is below `threshold`, or a `Blob` backed by a temporary file otherwise.
+  pub fn blob_with_threshold(bytes : Vec<u8>, threshold : usize) -> anyhow::Result<Value>
+  {
+    if bytes.len() < threshold
+    {
+      return Ok(Value::Bytes(bytes))
+    }
+    Ok(Value::Blob(TempVFileBuilder::new(&bytes)?))
+  }
+
   #[inline]
   pub fn as_date_time(&self) -> DateTime<Utc> //ret as ref ? 
   {
@@ -489,125 +1136,186 @@ impl Value
       _ => None,
     }
   }
-}
 
+  #[inline]
+  pub fn as_duration(&self) -> Duration
+  {
+    match self
+    {
+      Value::Duration(val) => *val,
+      _ => panic!("Can't convert value to Duration"),
+    }
+  }
 
-impl std::string::ToString for Value
-{
   #[inline]
-  fn to_string(&self) -> String
+  pub fn try_as_duration(&self) -> Option<Duration>
   {
     match self
     {
-         //Value::None => String::from("None"),
-         Value::Bool(val) => val.to_string(),
+      Value::Duration(val) => Some(*val),
+      _ => None,
+    }
+  }
 
-         Value::U8(val) => val.to_string(),
-         Value::U16(val) => val.to_string(),
-         Value::U32(val) => val.to_string(),
-         Value::U64(val) => val.to_string(),
+  #[inline]
+  pub fn as_ip_addr(&self) -> IpAddr
+  {
+    match self
+    {
+      Value::IpAddr(val) => *val,
+      _ => panic!("Can't convert value to IpAddr"),
+    }
+  }
 
-         Value::I8(val) => val.to_string(),
-         Value::I16(val) => val.to_string(),
-         Value::I32(val) => val.to_string(),
-         Value::I64(val) => val.to_string(),
+  #[inline]
+  pub fn try_as_ip_addr(&self) -> Option<IpAddr>
+  {
+    match self
+    {
+      Value::IpAddr(val) => Some(*val),
+      _ => None,
+    }
+  }
 
-         Value::F32(val) => val.to_string(), 
-         Value::F64(val) => val.to_string(), 
+  #[inline]
+  pub fn as_uuid(&self) -> Uuid
+  {
+    match self
+    {
+      Value::Uuid(val) => *val,
+      _ => panic!("Can't convert value to Uuid"),
+    }
+  }
 
-         Value::USize(val) => val.to_string(), 
- 
-         Value::Char(val) => val.to_string(), 
-         Value::String(val) => val.to_string(),
-         Value::Str(val) => (*val).to_string(),
-          
-         Value::Unit => String::from("()"),
-         Value::Newtype(val) => val.to_string(),
-
-         Value::Func(func) => func().to_string(),
-         Value::FuncArg(func, arg) => func(Value::Newtype(arg.clone())).to_string(),//"Fn(".to_owned() + &arg.to_string() + ")",
-         
-         Value::Option(val) => format!("{:?}", val),
-         Value::Seq(val) => format!("{:?}", val),
-         Value::Bytes(val) => format!("{:?}", val),
-         Value::DateTime(val) => format!("{:?}", val),
-         Value::VFileBuilder(val) => format!("{:?}", val.size()), 
-         //{
-            //let mut file = val.open().unwrap(); //XXX return error
-            //let mut buffer = [0; 16];
-            //let _r = file.read(&mut buffer).unwrap();//XXX we can't use read_exact a buffer can be < 16
-            //buffer
-         //}),
-         Value::NodeId(val) => format!("{:?}", val),
-         Value::AttributePath(val) => format!("{:?}", val),
-         Value::Map(val) => format!("{:?}", val),
-         Value::Attributes(val) => format!("{:?}", val ),
-         Value::ReflectStruct(val) => format!("{:?}", val ),
+  #[inline]
+  pub fn try_as_uuid(&self) -> Option<Uuid>
+  {
+    match self
+    {
+      Value::Uuid(val) => Some(*val),
+      _ => None,
+    }
+  }
+
+  /// Return the inner value at `path`, a list of `/` separated keys (JSON-pointer style) used to navigate nested values.
+  /// Keys are index for `Seq`, key for `Map`, attribute name for `Attributes` and field name for `ReflectStruct`.
+  /// `Newtype`, `Option` and functions are traversed transparently, `~1` and `~0` can be used to escape `/` and `~` in keys.
+  /// Return `None` if a key is not found.
+  pub fn get_path(&self, path : &str) -> Option<Value>
+  {
+    let mut value = self.clone();
+
+    for key in path.split('/').filter(|key| !key.is_empty())
+    {
+      let key = key.replace("~1", "/").replace("~0", "~");
+      value = value.get_key(&key)?;
+    }
+    Some(value)
+  }
+
+  /// Return the approximate memory footprint of this value in bytes, including the heap memory of the values it contains.
+  /// Shared `Attributes` are accounted each time they are referenced,
+  /// memory owned by `ReflectStruct`, `Enum`, `VFileBuilder`, `Blob` and functions is not accounted.
+  pub fn deep_size(&self) -> usize
+  {
+    std::mem::size_of::<Value>() + self.heap_size()
+  }
+
+  /// Return the heap memory used by this value, without its own size.
+  pub(crate) fn heap_size(&self) -> usize
+  {
+    use std::mem::size_of;
+
+    match self
+    {
+      Value::String(val) => val.capacity(),
+      Value::Str(Cow::Owned(val)) => val.capacity(),
+      Value::Bytes(val) => val.capacity(),
+      Value::Seq(val) => val.capacity() * size_of::<Value>() + val.iter().map(|val| val.heap_size()).sum::<usize>(),
+      Value::Map(val) => val.capacity() * (size_of::<String>() + size_of::<Value>()) +
+                         val.iter().map(|(key, val)| key.capacity() + val.heap_size()).sum::<usize>(),
+      Value::Option(Some(val)) | Value::Newtype(val) => val.deep_size(),
+      Value::FuncArg(_, arg) => arg.deep_size(),
+      Value::Attributes(val) => val.deep_size() - size_of::<Attributes>(),
+      Value::AttributePath(val) => val.attribute_name.capacity(),
+      _ => 0,
+    }
+  }
+
+  /// Return the inner value `key` of a container value.
+  fn get_key(&self, key : &str) -> Option<Value>
+  {
+    match self
+    {
+      Value::Seq(val) => val.get(key.parse::<usize>().ok()?).cloned(),
+      Value::Map(val) => val.get(key).cloned(),
+      Value::Attributes(val) => val.get_value(key),
+      Value::ReflectStruct(val) => val.get_value(key),
+      Value::Newtype(val) => val.get_key(key),
+      Value::Option(Some(val)) => val.get_key(key),
+      Value::Func(func) => func().get_key(key),
+      Value::FuncArg(func, arg) => func(Value::Newtype(arg.clone())).get_key(key),
+      _ => None,
     }
   }
 }
 
-impl fmt::Debug for Value 
-{
-   #[inline]
-   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result 
-   {
-      match self {
-         //Value::None => write!(f, "None"),
-         Value::Bool(val) => write!(f, "{}", val),
-
-         Value::U8(val) => write!(f, "{}", val),
-         Value::U16(val) => write!(f, "{}", val),
-         Value::U32(val) => write!(f, "{}", val),
-         Value::U64(val) => write!(f, "{}", val),
-
-         Value::I8(val) => write!(f, "{}", val),
-         Value::I16(val) => write!(f, "{}", val),
-         Value::I32(val) => write!(f, "{}", val),
-         Value::I64(val) => write!(f, "{}", val),
-
-         Value::F32(val) => write!(f, "{}", val),
-         Value::F64(val) => write!(f, "{}", val),
-
-         Value::USize(val) => write!(f, "{}", val),
-
-         Value::Char(val) => write!(f, "'{}'", val),
-         Value::String(val) => write!(f, "\"{}\"", val),
-         Value::Str(val) => write!(f, "\"{}\"", val),
-         
-         Value::Unit => write!(f, "()"),
-         Value::Option(val) => write!(f, "{:?}", val),
-         Value::Newtype(val) => write!(f, "{:?}", val),
-         Value::Seq(val) => write!(f, "{:?}", val),
-         Value::Map(val) => write!(f, "{:?}", val),
-         Value::Bytes(val) => write!(f, "{:?}", val),
-         Value::DateTime(val) => write!(f, "{:?}", val),
-
-         Value::Func(func) => write!(f, "{:?}", func()),
-         Value::FuncArg(func, arg) => write!(f, "{:?}", func(Value::Newtype(arg.clone()))),
-         Value::VFileBuilder(val) => write!(f, "{:?}", 
-         { 
-           let mut file = match val.open()
-           {
-             Ok(file) => file,
-             Err(_err) => return write!(f, ""),//XXX ret some error ?
-           };
-           let mut buffer = [0; 16];
-           let _r = match file.read(&mut buffer)
-           {
-             Ok(buff) => buff,
-             Err(_err) => return write!(f, ""),//XXX ret some error ?
-           };
-           buffer
-         }),
-         Value::NodeId(val) => write!(f, "{:?}", val),
-         Value::AttributePath(val) => write!(f, "{:?}", val),
-         Value::Attributes(val) => write!(f, "{:?}", val),
-         Value::ReflectStruct(val) => write!(f, "{:?}", val),
+/// Implement [TryFrom]<[Value]> for an integer type, any number that fit in the type without loss can be converted.
+macro_rules! try_from_integer
+{
+  ( $t:ty, $widen:ident ) => 
+  {
+    impl TryFrom<Value> for $t
+    {
+      type Error = RustructError;
+
+      fn try_from(value : Value) -> Result<Self, Self::Error>
+      {
+        value.$widen().and_then(|val| <$t>::try_from(val).ok()).ok_or(RustructError::ValueTypeMismatch)
+      }
+    }
+  };
+}
+
+try_from_integer!(u8, to_u128);
+try_from_integer!(u16, to_u128);
+try_from_integer!(u32, to_u128);
+try_from_integer!(u64, to_u128);
+try_from_integer!(u128, to_u128);
+try_from_integer!(usize, to_u128);
+try_from_integer!(i8, to_i128);
+try_from_integer!(i16, to_i128);
+try_from_integer!(i32, to_i128);
+try_from_integer!(i64, to_i128);
+try_from_integer!(i128, to_i128);
+
+/// Implement [TryFrom]<[Value]> for a type stored in a single [Value] variant.
+macro_rules! try_from_variant
+{
+  ( $it:path, $t:ty ) => 
+  {
+    impl TryFrom<Value> for $t
+    {
+      type Error = RustructError;
+
+      fn try_from(value : Value) -> Result<Self, Self::Error>
+      {
+        match value
+        {
+          $it(val) => Ok(val),
+          _ => Err(RustructError::ValueTypeMismatch),
+        }
       }
-   }
+    }
+  };
 }
 
+try_from_variant!(Value::DateTime, DateTime<Utc>);
+try_from_variant!(Value::Duration, Duration);
+try_from_variant!(Value::IpAddr, IpAddr);
+try_from_variant!(Value::Uuid, Uuid);
+
+
 /*impl Serialize for Value
 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -665,3 +1373,255 @@ impl fmt::Debug for Value
         }
     }
 }*/
+
+#[cfg(test)]
+mod tests
+{
+  use super::Value;
+  use crate::attribute::Attributes;
+  use crate::reflect::{ReflectStruct, FieldInfo, registry};
+  use crate::zerovfile::ZeroVFileBuilder;
+  use std::sync::Arc;
+  use std::cmp::Ordering;
+  use std::collections::hash_map::DefaultHasher;
+  use std::hash::{Hash, Hasher};
+
+  fn hash(value : &Value) -> u64
+  {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+  }
+
+  #[test]
+  fn value_equality()
+  {
+    assert!(Value::U8(1) == Value::U64(1));
+    assert!(Value::I32(-1) != Value::U32(u32::MAX));
+    assert!(Value::U64(2) == Value::F64(2.0));
+    assert!(Value::U64((1 << 53) + 1) != Value::F64((1u64 << 53) as f64));
+    assert!(Value::from("test") == Value::from(String::from("test")));
+    assert!(Value::from("test") != Value::U8(0));
+    assert!(Value::Seq(vec![Value::U8(1), Value::from("a")]) == Value::Seq(vec![Value::U32(1), Value::from(String::from("a"))]));
+    assert!(Value::F64(f64::NAN) != Value::F64(f64::NAN));
+  }
+
+  #[test]
+  fn value_ordering()
+  {
+    assert!(Value::I8(-1) < Value::U8(0));
+    assert!(Value::U64(u64::MAX) > Value::I64(i64::MAX));
+    assert!(Value::F32(1.5) > Value::U8(1));
+    assert!(Value::from("a") < Value::from(String::from("b")));
+    assert!(Value::from("a").partial_cmp(&Value::U8(1)).is_none());
+    assert!(Value::U16(3).compare_numeric(&Value::F64(3.0)) == Some(Ordering::Equal));
+    assert!(Value::U16(3).compare_numeric(&Value::from("3")).is_none());
+  }
+
+  #[test]
+  fn value_numeric_coercion()
+  {
+    assert!(Value::U8(42).to_u64() == Some(42));
+    assert!(Value::I16(-1).to_u64().is_none());
+    assert!(Value::I16(-1).to_i128() == Some(-1));
+    assert!(Value::U64(u64::MAX).to_i64().is_none());
+    assert!(Value::F64(3.0).to_u64() == Some(3));
+    assert!(Value::F64(3.5).to_u64().is_none());
+    assert!(Value::USize(7).to_f64() == Some(7.0));
+    assert!(Value::from("7").to_f64().is_none());
+    assert!(Value::I32(-5).as_u64_lossy() == Some(0));
+    assert!(Value::F32(2.9).as_u64_lossy() == Some(2));
+    assert!(Value::U64(u64::MAX).as_i64_lossy() == Some(i64::MAX));
+    assert!(Value::F64(f64::NAN).as_u64_lossy().is_none());
+  }
+
+  #[test]
+  fn value_new_variants()
+  {
+    use std::convert::TryFrom;
+    use std::net::{IpAddr, Ipv6Addr};
+    use chrono::Duration;
+    use uuid::Uuid;
+
+    let big = Value::from(u128::MAX);
+    assert!(big.as_u128() == u128::MAX);
+    assert!(big > Value::U64(u64::MAX));
+    assert!(Value::I128(-1) == Value::I8(-1));
+    assert!(u8::try_from(Value::U128(255)).unwrap() == 255);
+    assert!(u8::try_from(Value::U128(256)).is_err());
+    assert!(i64::try_from(Value::U8(1)).unwrap() == 1);
+
+    let duration = Value::from(Duration::milliseconds(1500));
+    assert!(Duration::try_from(duration.clone()).unwrap() == Duration::milliseconds(1500));
+    assert!(serde_json::to_string(&duration).unwrap() == "1500000000");
+
+    let ip = Value::from(IpAddr::V6(Ipv6Addr::LOCALHOST));
+    assert!(ip.to_string() == "::1");
+    assert!(IpAddr::try_from(Value::U8(0)).is_err());
+
+    let uuid = Uuid::parse_str("67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap();
+    assert!(serde_json::to_string(&Value::from(uuid)).unwrap() == "\"67e55044-10b1-426f-9247-bb680e5fe0c8\"");
+    assert!(Value::from(uuid).try_as_uuid() == Some(uuid));
+  }
+
+  #[test]
+  fn value_untagged_round_trip()
+  {
+    use chrono::Duration;
+    use crate::value::tagged::TaggedValue;
+
+    let round_trip = |value : &Value| serde_json::from_str::<Value>(&serde_json::to_string(value).unwrap()).unwrap();
+
+    assert!(round_trip(&Value::U128(u64::MAX as u128)) == Value::U128(u64::MAX as u128));
+    assert!(round_trip(&Value::I128(i64::MIN as i128)) == Value::I128(i64::MIN as i128));
+    assert!(round_trip(&Value::U128(u128::MAX)).to_u128().is_none());
+    assert!(round_trip(&Value::Duration(Duration::milliseconds(-1500))) == Value::I64(-1_500_000_000));
+
+    //tagged values keep the exact type
+    for value in [Value::U128(u128::MAX), Value::I128(i128::MIN), Value::Duration(Duration::milliseconds(-1500))]
+    {
+      let json = serde_json::to_string(&TaggedValue::from(&value)).unwrap();
+      let decoded = Value::from(serde_json::from_str::<TaggedValue>(&json).unwrap());
+      assert!(decoded.type_id() == value.type_id());
+      assert!(format!("{:?}", decoded) == format!("{:?}", value));
+    }
+  }
+
+  #[derive(Debug)]
+  struct Header
+  {
+    magic : u32,
+    version : u8,
+  }
+
+  impl ReflectStruct for Header
+  {
+    fn name(&self) -> &'static str
+    {
+      "ValueHeader"
+    }
+
+    fn field_infos(&self) -> Vec<FieldInfo>
+    {
+      vec![FieldInfo::field("magic", None), FieldInfo::field("version", None)]
+    }
+
+    fn get_value(&self, name : &str) -> Option<Value>
+    {
+      match name
+      {
+        "magic" => Some(Value::U32(self.magic)),
+        "version" => Some(Value::U8(self.version)),
+        _ => None,
+      }
+    }
+  }
+
+  #[test]
+  fn value_deserialize()
+  {
+    let mut inner = Attributes::new();
+    inner.add_attribute("offset", Value::U64(0x1000), None);
+    let mut attributes = Attributes::new();
+    attributes.add_attribute("name", Value::from("file.txt"), None);
+    attributes.add_attribute("sizes", Value::Seq(vec![Value::U8(1), Value::I64(-2)]), None);
+    attributes.add_attribute("ratio", Value::F64(0.1), None);
+    attributes.add_attribute("inner", Value::Attributes(inner), None);
+    let value = Value::Attributes(attributes);
+
+    let loaded = serde_json::from_str::<Value>(&serde_json::to_string(&value).unwrap()).unwrap();
+    assert!(loaded == value);
+    assert!(loaded.as_attributes().names() == vec!["name", "sizes", "ratio", "inner"]);
+    assert!(matches!(loaded.as_attributes().get_value("ratio"), Some(Value::F64(ratio)) if ratio == 0.1));
+
+    let json = serde_json::to_string(&Value::ReflectStruct(Arc::new(Header{ magic : 0x1234, version : 2 }))).unwrap();
+    assert!(matches!(serde_json::from_str::<Value>(&json).unwrap(), Value::Attributes(_)));
+    registry::register(&Header{ magic : 0, version : 0 });
+    let reflect = serde_json::from_str::<Value>(&json).unwrap().as_reflect_struct();
+    assert!(reflect.name() == "ValueHeader");
+    assert!(reflect.get_value("magic").unwrap().to_u64() == Some(0x1234));
+
+    let builder = Value::VFileBuilder(Arc::new(ZeroVFileBuilder::new(512)));
+    let loaded = serde_json::from_str::<Value>(&serde_json::to_string(&builder).unwrap()).unwrap();
+    assert!(matches!(loaded, Value::VFileBuilder(builder) if builder.size() == 512));
+  }
+
+  #[test]
+  fn value_hash()
+  {
+    assert!(hash(&Value::U8(1)) == hash(&Value::I64(1)));
+    assert!(hash(&Value::U8(1)) == hash(&Value::F64(1.0)));
+    assert!(hash(&Value::from("test")) == hash(&Value::from(String::from("test"))));
+  }
+
+  #[test]
+  fn value_get_path()
+  {
+    use crate::attribute::Attributes;
+    use std::collections::HashMap;
+
+    let mut entry = Attributes::new();
+    entry.add_attribute("name", Value::from("file.txt"), None);
+    let mut map = HashMap::new();
+    map.insert("a/b".to_string(), Value::U8(1));
+    map.insert("entries".to_string(), Value::Seq(vec![Value::Unit, Value::Newtype(Box::new(Value::Attributes(entry)))]));
+    let value = Value::Map(map);
+
+    assert!(value.get_path("entries/1/name").unwrap().as_string() == "file.txt");
+    assert!(value.get_path("/entries/1/name").unwrap().as_string() == "file.txt");
+    assert!(value.get_path("a~1b").unwrap() == Value::U8(1));
+    assert!(value.get_path("").unwrap() == value);
+    assert!(value.get_path("entries/2").is_none());
+    assert!(value.get_path("entries/first").is_none());
+    assert!(value.get_path("a~1b/0").is_none());
+  }
+
+  #[test]
+  fn value_blob()
+  {
+    use std::io::Read;
+
+    assert!(Value::blob_with_threshold(vec![1; 16], 32).unwrap() == Value::Bytes(vec![1; 16]));
+
+    let blob = Value::blob_with_threshold(vec![1; 64], 32).unwrap();
+    let builder = blob.try_as_blob().unwrap();
+    assert!(builder.size() == 64);
+    let mut content = Vec::new();
+    builder.open().unwrap().read_to_end(&mut content).unwrap();
+    assert!(content == vec![1; 64]);
+    assert!(blob.to_string() == "Blob(64)");
+    assert!(blob == blob.clone());
+  }
+
+  #[test]
+  fn value_enum()
+  {
+    use crate::reflect::{ReflectEnum, EnumVariant};
+    use std::sync::Arc;
+
+    let directory = Value::from(Arc::new(EnumVariant::new("RecordType", "Directory", 1)) as Arc<dyn ReflectEnum + Sync + Send>);
+    let file = Value::Enum(Arc::new(EnumVariant::new("RecordType", "File", 2).with_payload(Value::U32(7))));
+
+    assert!(directory.to_string() == "Directory");
+    assert!(format!("{:?}", file) == "RecordType::File(7)");
+    assert!(directory.try_as_enum().unwrap().discriminant() == 1);
+    assert!(directory < file);
+    assert!(directory == Value::Enum(Arc::new(EnumVariant::new("RecordType", "Directory", 1))));
+    assert!(serde_json::to_string(&file).unwrap() == "{\"variant\":\"File\",\"value\":2,\"payload\":7}");
+  }
+
+  #[test]
+  fn value_deep_size()
+  {
+    use std::mem::size_of;
+    use crate::attribute::Attributes;
+
+    assert!(Value::U32(1).deep_size() == size_of::<Value>());
+    assert!(Value::String(String::with_capacity(100)).deep_size() == size_of::<Value>() + 100);
+    assert!(Value::Seq(vec![Value::Bytes(vec![0; 10])]).deep_size() == 2 * size_of::<Value>() + 10);
+
+    let mut attributes = Attributes::new();
+    attributes.add_attribute("data", Value::Bytes(vec![0; 1000]), None);
+    assert!(Value::Attributes(attributes).deep_size() > 1000 + size_of::<Value>());
+  }
+}
@@ -0,0 +1,619 @@
+//! Compact binary encoding of [Value], [Attributes] and [Node].
+//!
+//! Encoded data start with a `TAPV` magic followed by the format version.
+//! Each value is encoded as its [ValueTypeId] followed by its content, numbers are little endian
+//! and lengths are variable length integers (LEB128). Like the tagged serialization, values keep their exact type,
+//! but large `Bytes` and deep `Seq` are encoded and decoded much faster than with JSON.
+//! The content of `Blob` values is encoded like `Bytes` and decoded to a temporary file.
+
+use std::io::{Read, Write, Cursor};
+use std::sync::Arc;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::value::{Value, ValueTypeId};
+use crate::attribute::Attributes;
+use crate::node::Node;
+use crate::reflect::{registry, EnumVariant};
+use crate::vfile::VFileBuilder;
+use crate::tempvfile::TempVFileWriter;
+use crate::tree::{TreeNodeId, AttributePath};
+use crate::error::RustructError;
+
+use anyhow::Result;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use chrono::{TimeZone, Utc, Duration};
+use uuid::Uuid;
+
+/// Magic starting all encoded data.
+pub const MAGIC : &[u8; 4] = b"TAPV";
+/// Version of the encoding format.
+/// Version 2 add the raw name of nodes, version 3 add the uuid of nodes, data encoded with previous versions can still be decoded.
+pub const VERSION : u8 = 3;
+/// Maximum nesting of decoded values, protect against stack overflows on corrupted or malicious data.
+pub const MAX_DEPTH : usize = 128;
+
+/// Encode `value` with a versioned header.
+pub fn encode(value : &Value) -> Result<Vec<u8>>
+{
+  let mut buffer = header();
+  write_value(&mut buffer, value)?;
+  Ok(buffer)
+}
+
+/// Decode a [Value] encoded with [encode].
+pub fn decode(data : &[u8]) -> Result<Value>
+{
+  let mut reader = Cursor::new(data);
+  read_header(&mut reader)?;
+  read_value(&mut reader)
+}
+
+/// Encode `attributes` with a versioned header.
+pub fn encode_attributes(attributes : &Attributes) -> Result<Vec<u8>>
+{
+  let mut buffer = header();
+  write_attributes(&mut buffer, attributes)?;
+  Ok(buffer)
+}
+
+/// Decode [Attributes] encoded with [encode_attributes].
+pub fn decode_attributes(data : &[u8]) -> Result<Attributes>
+{
+  let mut reader = Cursor::new(data);
+  read_header(&mut reader)?;
+  read_attributes(&mut reader, 0)
+}
+
+/// Encode the name and attributes of `node` with a versioned header.
+pub fn encode_node(node : &Node) -> Result<Vec<u8>>
+{
+  let mut buffer = header();
+  write_str(&mut buffer, &node.name())?;
+  write_attributes(&mut buffer, &node.value())?;
+  match node.raw_name()
+  {
+    Some(raw) => { buffer.write_u8(1)?; write_bytes(&mut buffer, raw)?; },
+    None => buffer.write_u8(0)?,
+  }
+  buffer.write_all(node.uuid().as_bytes())?;
+  Ok(buffer)
+}
+
+/// Decode a [Node] encoded with [encode_node].
+pub fn decode_node(data : &[u8]) -> Result<Node>
+{
+  let mut reader = Cursor::new(data);
+  let version = read_header(&mut reader)?;
+  let name = read_string(&mut reader)?;
+  let read = read_attributes(&mut reader, 0)?;
+  let node = match version >= 2 && reader.read_u8()? != 0
+  {
+    true => Node::with_raw_name(name, &read_bytes(&mut reader)?),
+    false => Node::new(name),
+  };
+  let node = match version >= 3
+  {
+    true =>
+    {
+      let mut uuid = [0; 16];
+      reader.read_exact(&mut uuid)?;
+      node.with_uuid(Uuid::from_bytes(uuid))
+    },
+    false => node,
+  };
+  let mut attributes = node.value();
+  for attribute in read.attributes().iter()
+  {
+    attributes.add_attribute(attribute.name().to_string(), attribute.value().clone(), None);
+  }
+  Ok(node)
+}
+
+fn header() -> Vec<u8>
+{
+  let mut buffer = MAGIC.to_vec();
+  buffer.push(VERSION);
+  buffer
+}
+
+/// Read the header and return the format version.
+fn read_header<R : Read>(reader : &mut R) -> Result<u8>
+{
+  let mut magic = [0u8; 4];
+  reader.read_exact(&mut magic)?;
+  if &magic != MAGIC
+  {
+    return Err(RustructError::InvalidEncoding("bad magic".into()).into())
+  }
+
+  let version = reader.read_u8()?;
+  if version == 0 || version > VERSION
+  {
+    return Err(RustructError::InvalidEncoding(format!("unsupported version {}", version)).into())
+  }
+  Ok(version)
+}
+
+fn write_len<W : Write>(writer : &mut W, mut len : u64) -> Result<()>
+{
+  loop
+  {
+    let byte = (len & 0x7f) as u8;
+    len >>= 7;
+    if len == 0
+    {
+      writer.write_u8(byte)?;
+      return Ok(())
+    }
+    writer.write_u8(byte | 0x80)?;
+  }
+}
+
+fn read_len<R : Read>(reader : &mut R) -> Result<usize>
+{
+  let mut len : u64 = 0;
+  for shift in (0..64).step_by(7)
+  {
+    let byte = reader.read_u8()?;
+    len |= ((byte & 0x7f) as u64) << shift;
+    if byte & 0x80 == 0
+    {
+      return usize::try_from(len).map_err(|_| RustructError::InvalidEncoding("length overflow".into()).into())
+    }
+  }
+  Err(RustructError::InvalidEncoding("length overflow".into()).into())
+}
+
+fn write_bytes<W : Write>(writer : &mut W, bytes : &[u8]) -> Result<()>
+{
+  write_len(writer, bytes.len() as u64)?;
+  writer.write_all(bytes)?;
+  Ok(())
+}
+
+fn read_bytes<R : Read>(reader : &mut R) -> Result<Vec<u8>>
+{
+  let len = read_len(reader)?;
+  let mut bytes = Vec::new();
+  //don't trust len to allocate the buffer, data can be truncated or corrupted
+  reader.take(len as u64).read_to_end(&mut bytes)?;
+  if bytes.len() != len
+  {
+    return Err(RustructError::InvalidEncoding("truncated data".into()).into())
+  }
+  Ok(bytes)
+}
+
+/// Write the content of `builder` like bytes, without loading it in memory.
+fn write_blob<W : Write>(writer : &mut W, builder : &dyn VFileBuilder) -> Result<()>
+{
+  write_len(writer, builder.size())?;
+  let copied = std::io::copy(&mut builder.open()?.take(builder.size()), writer)?;
+  if copied != builder.size()
+  {
+    return Err(RustructError::InvalidEncoding("blob is smaller than its size".into()).into())
+  }
+  Ok(())
+}
+
+/// Read a blob written by [write_blob] to a temporary file.
+fn read_blob<R : Read>(reader : &mut R) -> Result<Arc<dyn VFileBuilder>>
+{
+  let len = read_len(reader)? as u64;
+  let mut writer = TempVFileWriter::new()?;
+  if std::io::copy(&mut reader.take(len), &mut writer)? != len
+  {
+    return Err(RustructError::InvalidEncoding("truncated data".into()).into())
+  }
+  Ok(writer.into_builder()?)
+}
+
+fn write_str<W : Write>(writer : &mut W, string : &str) -> Result<()>
+{
+  write_bytes(writer, string.as_bytes())
+}
+
+fn read_string<R : Read>(reader : &mut R) -> Result<String>
+{
+  Ok(String::from_utf8(read_bytes(reader)?)?)
+}
+
+/// Encode types that are only serializable with serde as a JSON string.
+fn write_json<W : Write, T : serde::Serialize + ?Sized>(writer : &mut W, value : &T) -> Result<()>
+{
+  write_bytes(writer, &serde_json::to_vec(value)?)
+}
+
+fn read_json<R : Read, T : serde::de::DeserializeOwned>(reader : &mut R) -> Result<T>
+{
+  Ok(serde_json::from_slice(&read_bytes(reader)?)?)
+}
+
+fn write_fields<'a, W : Write, I : Iterator<Item = (&'a str, &'a Value)>>(writer : &mut W, count : usize, fields : I) -> Result<()>
+{
+  write_len(writer, count as u64)?;
+  for (name, value) in fields
+  {
+    write_str(writer, name)?;
+    write_value(writer, value)?;
+  }
+  Ok(())
+}
+
+fn read_fields<R : Read>(reader : &mut R, depth : usize) -> Result<Vec<(String, Value)>>
+{
+  let count = read_len(reader)?;
+  let mut fields = Vec::new();
+  for _ in 0..count
+  {
+    fields.push((read_string(reader)?, read_nested_value(reader, depth)?));
+  }
+  Ok(fields)
+}
+
+fn write_attributes<W : Write>(writer : &mut W, attributes : &Attributes) -> Result<()>
+{
+  let attributes = attributes.attributes();
+  let attributes : Vec<_> = attributes.iter().collect();
+  write_fields(writer, attributes.len(), attributes.iter().map(|attribute| (attribute.name(), attribute.value())))
+}
+
+fn read_attributes<R : Read>(reader : &mut R, depth : usize) -> Result<Attributes>
+{
+  let mut attributes = Attributes::new();
+  for (name, value) in read_fields(reader, depth)?
+  {
+    attributes.add_attribute(name, value, None);
+  }
+  Ok(attributes)
+}
+
+/// Write `value` to `writer` without header.
+pub fn write_value<W : Write>(writer : &mut W, value : &Value) -> Result<()>
+{
+  //functions are evaluated and their result is encoded, methods are encoded as their signature
+  match value
+  {
+    Value::Func(func) => return write_value(writer, &func()),
+    Value::FuncArg(func, arg) => return write_value(writer, &func(Value::Newtype(arg.clone()))),
+    Value::Method(method) => return write_value(writer, &method.signature()),
+    _ => (),
+  }
+
+  writer.write_u8(value.type_id() as u8)?;
+  match value
+  {
+    Value::Attributes(attributes) => write_attributes(writer, attributes)?,
+    Value::ReflectStruct(reflect) =>
+    {
+      write_str(writer, reflect.name())?;
+      let attributes = reflect.attributes();
+      write_fields(writer, attributes.len(), attributes.iter().map(|attribute| (attribute.name(), attribute.value())))?;
+    },
+    Value::VFileBuilder(builder) => write_json(writer, builder)?,
+    Value::Blob(builder) => write_blob(writer, builder.as_ref())?,
+    Value::Bool(val) => writer.write_u8(*val as u8)?,
+    Value::U8(val) => writer.write_u8(*val)?,
+    Value::U16(val) => writer.write_u16::<LittleEndian>(*val)?,
+    Value::U32(val) => writer.write_u32::<LittleEndian>(*val)?,
+    Value::U64(val) => writer.write_u64::<LittleEndian>(*val)?,
+    Value::I8(val) => writer.write_i8(*val)?,
+    Value::I16(val) => writer.write_i16::<LittleEndian>(*val)?,
+    Value::I32(val) => writer.write_i32::<LittleEndian>(*val)?,
+    Value::I64(val) => writer.write_i64::<LittleEndian>(*val)?,
+    Value::U128(val) => writer.write_u128::<LittleEndian>(*val)?,
+    Value::I128(val) => writer.write_i128::<LittleEndian>(*val)?,
+    Value::F32(val) => writer.write_f32::<LittleEndian>(*val)?,
+    Value::F64(val) => writer.write_f64::<LittleEndian>(*val)?,
+    Value::USize(val) => writer.write_u64::<LittleEndian>(*val as u64)?,
+    Value::Char(val) => writer.write_u32::<LittleEndian>(*val as u32)?,
+    Value::String(val) => write_str(writer, val)?,
+    Value::Str(val) => write_str(writer, val)?,
+    Value::Unit => (),
+    Value::Option(val) => match val
+    {
+      Some(val) => { writer.write_u8(1)?; write_value(writer, val)? },
+      None => writer.write_u8(0)?,
+    },
+    Value::Newtype(val) => write_value(writer, val)?,
+    Value::Seq(val) =>
+    {
+      write_len(writer, val.len() as u64)?;
+      for val in val
+      {
+        write_value(writer, val)?;
+      }
+    },
+    Value::Bytes(val) => write_bytes(writer, val)?,
+    Value::DateTime(val) =>
+    {
+      writer.write_i64::<LittleEndian>(val.timestamp())?;
+      writer.write_u32::<LittleEndian>(val.timestamp_subsec_nanos())?;
+    },
+    Value::Duration(val) =>
+    {
+      writer.write_i64::<LittleEndian>(val.num_seconds())?;
+      writer.write_i32::<LittleEndian>(val.subsec_nanos())?;
+    },
+    Value::IpAddr(IpAddr::V4(val)) => { writer.write_u8(4)?; writer.write_all(&val.octets())? },
+    Value::IpAddr(IpAddr::V6(val)) => { writer.write_u8(6)?; writer.write_all(&val.octets())? },
+    Value::Uuid(val) => writer.write_all(val.as_bytes())?,
+    Value::Map(val) => write_fields(writer, val.len(), val.iter().map(|(key, val)| (key.as_str(), val)))?,
+    Value::NodeId(val) => write_json(writer, val)?,
+    Value::AttributePath(val) => write_json(writer, val)?,
+    Value::Enum(val) =>
+    {
+      write_str(writer, val.name())?;
+      write_str(writer, val.variant())?;
+      writer.write_i64::<LittleEndian>(val.discriminant())?;
+      match val.payload()
+      {
+        Some(payload) => { writer.write_u8(1)?; write_value(writer, &payload)? },
+        None => writer.write_u8(0)?,
+      }
+    },
+    Value::Func(_) | Value::FuncArg(_, _) | Value::Method(_) => unreachable!(),
+  }
+  Ok(())
+}
+
+/// Read a [Value] written by [write_value] from `reader`.
+/// Return an error if values are nested deeper than [MAX_DEPTH].
+pub fn read_value<R : Read>(reader : &mut R) -> Result<Value>
+{
+  read_nested_value(reader, 0)
+}
+
+/// Read a [Value] contained in `depth` other values.
+fn read_nested_value<R : Read>(reader : &mut R, depth : usize) -> Result<Value>
+{
+  if depth >= MAX_DEPTH
+  {
+    return Err(RustructError::InvalidEncoding(format!("values nested deeper than {}", MAX_DEPTH)).into())
+  }
+  let depth = depth + 1;
+  let type_id = ValueTypeId::try_from(reader.read_u8()?)?;
+
+  let value = match type_id
+  {
+    ValueTypeId::Attributes => Value::Attributes(read_attributes(reader, depth)?),
+    ValueTypeId::ReflectStruct =>
+    {
+      let name = read_string(reader)?;
+      let fields = read_fields(reader, depth)?;
+      match registry::rehydrate(&name, fields.clone())
+      {
+        Some(reflect) => Value::ReflectStruct(reflect),
+        None =>
+        {
+          let mut attributes = Attributes::new();
+          for (name, value) in fields
+          {
+            attributes.add_attribute(name, value, None);
+          }
+          Value::Attributes(attributes)
+        },
+      }
+    },
+    ValueTypeId::VFileBuilder => Value::VFileBuilder(read_json::<_, Arc<dyn VFileBuilder>>(reader)?),
+    ValueTypeId::Blob => Value::Blob(read_blob(reader)?),
+    ValueTypeId::Bool => Value::Bool(reader.read_u8()? != 0),
+    ValueTypeId::U8 => Value::U8(reader.read_u8()?),
+    ValueTypeId::U16 => Value::U16(reader.read_u16::<LittleEndian>()?),
+    ValueTypeId::U32 => Value::U32(reader.read_u32::<LittleEndian>()?),
+    ValueTypeId::U64 => Value::U64(reader.read_u64::<LittleEndian>()?),
+    ValueTypeId::I8 => Value::I8(reader.read_i8()?),
+    ValueTypeId::I16 => Value::I16(reader.read_i16::<LittleEndian>()?),
+    ValueTypeId::I32 => Value::I32(reader.read_i32::<LittleEndian>()?),
+    ValueTypeId::I64 => Value::I64(reader.read_i64::<LittleEndian>()?),
+    ValueTypeId::U128 => Value::U128(reader.read_u128::<LittleEndian>()?),
+    ValueTypeId::I128 => Value::I128(reader.read_i128::<LittleEndian>()?),
+    ValueTypeId::F32 => Value::F32(reader.read_f32::<LittleEndian>()?),
+    ValueTypeId::F64 => Value::F64(reader.read_f64::<LittleEndian>()?),
+    ValueTypeId::USize => Value::USize(usize::try_from(reader.read_u64::<LittleEndian>()?)?),
+    ValueTypeId::Char => Value::Char(char::from_u32(reader.read_u32::<LittleEndian>()?)
+                                      .ok_or_else(|| RustructError::InvalidEncoding("invalid char".into()))?),
+    ValueTypeId::String => Value::String(read_string(reader)?),
+    ValueTypeId::Str => Value::Str(Cow::Owned(read_string(reader)?)),
+    ValueTypeId::Unit => Value::Unit,
+    ValueTypeId::Option => match reader.read_u8()?
+    {
+      0 => Value::Option(None),
+      _ => Value::Option(Some(Box::new(read_nested_value(reader, depth)?))),
+    },
+    ValueTypeId::Newtype => Value::Newtype(Box::new(read_nested_value(reader, depth)?)),
+    ValueTypeId::Seq =>
+    {
+      let count = read_len(reader)?;
+      let mut seq = Vec::new();
+      for _ in 0..count
+      {
+        seq.push(read_nested_value(reader, depth)?);
+      }
+      Value::Seq(seq)
+    },
+    ValueTypeId::Bytes => Value::Bytes(read_bytes(reader)?),
+    ValueTypeId::DateTime =>
+    {
+      let seconds = reader.read_i64::<LittleEndian>()?;
+      let nanoseconds = reader.read_u32::<LittleEndian>()?;
+      Value::DateTime(Utc.timestamp_opt(seconds, nanoseconds).single()
+                        .ok_or_else(|| RustructError::InvalidEncoding("invalid date".into()))?)
+    },
+    ValueTypeId::Duration =>
+    {
+      let seconds = reader.read_i64::<LittleEndian>()?;
+      let nanoseconds = reader.read_i32::<LittleEndian>()?;
+      Value::Duration(Duration::seconds(seconds) + Duration::nanoseconds(nanoseconds as i64))
+    },
+    ValueTypeId::IpAddr => match reader.read_u8()?
+    {
+      4 =>
+      {
+        let mut octets = [0u8; 4];
+        reader.read_exact(&mut octets)?;
+        Value::IpAddr(IpAddr::V4(Ipv4Addr::from(octets)))
+      },
+      6 =>
+      {
+        let mut octets = [0u8; 16];
+        reader.read_exact(&mut octets)?;
+        Value::IpAddr(IpAddr::V6(Ipv6Addr::from(octets)))
+      },
+      _ => return Err(RustructError::InvalidEncoding("invalid ip address".into()).into()),
+    },
+    ValueTypeId::Uuid =>
+    {
+      let mut bytes = [0u8; 16];
+      reader.read_exact(&mut bytes)?;
+      Value::Uuid(Uuid::from_bytes(bytes))
+    },
+    ValueTypeId::Map => Value::Map(read_fields(reader, depth)?.into_iter().collect::<HashMap<String, Value>>()),
+    ValueTypeId::NodeId => Value::NodeId(read_json::<_, TreeNodeId>(reader)?),
+    ValueTypeId::AttributePath => Value::AttributePath(read_json::<_, AttributePath>(reader)?),
+    ValueTypeId::Enum =>
+    {
+      let name = read_string(reader)?;
+      let variant = EnumVariant::new(name, read_string(reader)?, reader.read_i64::<LittleEndian>()?);
+      Value::Enum(Arc::new(match reader.read_u8()?
+      {
+        0 => variant,
+        _ => variant.with_payload(read_nested_value(reader, depth)?),
+      }))
+    },
+    ValueTypeId::Func | ValueTypeId::FuncArg | ValueTypeId::Method => return Err(RustructError::InvalidEncoding("functions can't be decoded".into()).into()),
+  };
+  Ok(value)
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{encode, decode, encode_node, decode_node};
+  use crate::value::{Value, ValueTypeId};
+  use crate::node::Node;
+  use crate::attribute::Attributes;
+  use crate::reflect::EnumVariant;
+  use chrono::{Duration, Utc};
+  use std::sync::Arc;
+  use std::collections::HashMap;
+
+  #[test]
+  fn codec_round_trip()
+  {
+    let mut attributes = Attributes::new();
+    attributes.add_attribute("size", Value::USize(12), None);
+    let mut map = HashMap::new();
+    map.insert("key".to_string(), Value::I32(-3));
+
+    let values = vec![
+      Value::Bool(true),
+      Value::U8(1),
+      Value::U64(u64::MAX),
+      Value::I16(-1),
+      Value::U128(u128::MAX),
+      Value::I128(i128::MIN),
+      Value::F64(0.25),
+      Value::Char('é'),
+      Value::from("str"),
+      Value::String("string".into()),
+      Value::Unit,
+      Value::Option(None),
+      Value::Option(Some(Box::new(Value::U32(7)))),
+      Value::Seq(vec![Value::U16(2), Value::Bytes(vec![0; 1024])]),
+      Value::DateTime(Utc::now()),
+      Value::Duration(Duration::milliseconds(-1500)),
+      Value::IpAddr("::1".parse().unwrap()),
+      Value::Uuid(uuid::Uuid::new_v4()),
+      Value::Map(map),
+      Value::Attributes(attributes),
+      Value::Enum(Arc::new(EnumVariant::new("RecordType", "File", 2))),
+    ];
+
+    for value in values
+    {
+      let data = encode(&value).unwrap();
+      assert!(&data[0..4] == b"TAPV");
+      let decoded = decode(&data).unwrap();
+      assert!(decoded == value);
+    }
+    let func = Value::Func(Arc::new(Box::new(|| Value::U8(9))));
+    assert!(decode(&encode(&func).unwrap()).unwrap() == Value::U8(9));
+
+    assert!(decode(b"TAPV\x01\xff").is_err());
+    assert!(decode(b"JSON\x01\x03").is_err());
+  }
+
+  #[test]
+  fn codec_blob()
+  {
+    use std::io::Read;
+
+    let data : Vec<u8> = (0..=255).cycle().take(100_000).collect();
+    let blob = Value::blob_with_threshold(data.clone(), 0).unwrap();
+    let encoded = encode(&blob).unwrap();
+    //the temporary file of the blob is removed, the data must be in the encoding
+    drop(blob);
+
+    let builder = match decode(&encoded).unwrap()
+    {
+      Value::Blob(builder) => builder,
+      _ => panic!("decoded value is not a blob"),
+    };
+    let mut content = Vec::new();
+    builder.open().unwrap().read_to_end(&mut content).unwrap();
+    assert!(content == data);
+
+    let mut truncated = encode(&Value::Blob(builder)).unwrap();
+    truncated.truncate(truncated.len() - 1);
+    assert!(decode(&truncated).is_err());
+  }
+
+  #[test]
+  fn codec_max_depth()
+  {
+    use super::MAX_DEPTH;
+
+    let nested = |depth : usize| (0..depth).fold(Value::U8(1), |value, _| Value::Newtype(Box::new(value)));
+    assert!(decode(&encode(&nested(MAX_DEPTH - 1)).unwrap()).is_ok());
+    assert!(decode(&encode(&nested(MAX_DEPTH)).unwrap()).is_err());
+
+    //a long chain of sequence headers must not overflow the stack
+    let mut data = encode(&Value::Unit).unwrap();
+    data.truncate(5);
+    for _ in 0..100_000
+    {
+      data.extend_from_slice(&[ValueTypeId::Seq as u8, 1]);
+    }
+    assert!(decode(&data).is_err());
+  }
+
+  #[test]
+  fn codec_node()
+  {
+    let node = Node::new("file");
+    node.value().add_attribute("data", Value::Bytes(vec![0x41; 10]), None);
+
+    let decoded = decode_node(&encode_node(&node).unwrap()).unwrap();
+    assert!(decoded.name() == "file");
+    assert!(decoded.value() == node.value());
+    assert!(decoded.raw_name().is_none());
+    assert!(decoded.uuid() == node.uuid());
+
+    let node = Node::with_raw_name("caf\\xe9", b"caf\xe9");
+    let data = encode_node(&node).unwrap();
+    assert!(decode_node(&data).unwrap().raw_name() == Some(&b"caf\xe9"[..]));
+
+    //version 2 nodes have no uuid and version 1 nodes have no raw name
+    let mut data = encode_node(&Node::new("file")).unwrap();
+    data.truncate(data.len() - 16);
+    data[4] = 2;
+    assert!(decode_node(&data).unwrap().name() == "file");
+    data[4] = 1;
+    data.pop();
+    assert!(decode_node(&data).unwrap().name() == "file");
+  }
+}
@@ -0,0 +1,258 @@
+//! Conversion of a [Value] to any [Deserialize] type, the reverse of [to_value](crate::value::to_value),
+//! so plugins can take typed arguments without a round-trip through a JSON string.
+//!
+//! `Attributes` and `Map` are read as structs or maps, `Seq` as sequences and tuples, and enums from a variant name,
+//! an [EnumVariant](crate::reflect::EnumVariant) or a map with a single entry. The values that have no serde equivalent
+//! (builders, reflected structs, node ids, durations) are deserialized from their JSON serialization,
+//! except the builders of the fields using the [passthrough](crate::value::passthrough) helper that are passed as is.
+
+use std::fmt;
+
+use crate::value::{Value, passthrough};
+use crate::error::RustructError;
+
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor, EnumAccess, VariantAccess};
+use serde::de::value::{SeqDeserializer, MapDeserializer};
+use serde::forward_to_deserialize_any;
+
+impl de::Error for RustructError
+{
+  fn custom<T : fmt::Display>(msg : T) -> Self
+  {
+    RustructError::Serialize(msg.to_string())
+  }
+}
+
+type Result<T> = std::result::Result<T, RustructError>;
+
+/// Convert `value` to a `T`.
+pub fn from_value<T : DeserializeOwned>(value : Value) -> anyhow::Result<T>
+{
+  Ok(T::deserialize(ValueDeserializer(value))?)
+}
+
+impl<'de> IntoDeserializer<'de, RustructError> for Value
+{
+  type Deserializer = ValueDeserializer;
+
+  fn into_deserializer(self) -> ValueDeserializer
+  {
+    ValueDeserializer(self)
+  }
+}
+
+/// [Deserializer](de::Deserializer) reading a [Value].
+pub struct ValueDeserializer(pub Value);
+
+impl ValueDeserializer
+{
+  /// Deserialize the value from its JSON serialization.
+  fn deserialize_json<'de, V : Visitor<'de>>(self, visitor : V) -> Result<V::Value>
+  {
+    let json = serde_json::to_value(&self.0).map_err(de::Error::custom)?;
+    de::Deserializer::deserialize_any(json, visitor).map_err(de::Error::custom)
+  }
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer
+{
+  type Error = RustructError;
+
+  fn deserialize_any<V : Visitor<'de>>(self, visitor : V) -> Result<V::Value>
+  {
+    match self.0
+    {
+      Value::Bool(v) => visitor.visit_bool(v),
+      Value::U8(v) => visitor.visit_u8(v),
+      Value::U16(v) => visitor.visit_u16(v),
+      Value::U32(v) => visitor.visit_u32(v),
+      Value::U64(v) => visitor.visit_u64(v),
+      Value::USize(v) => visitor.visit_u64(v as u64),
+      Value::U128(v) => visitor.visit_u128(v),
+      Value::I8(v) => visitor.visit_i8(v),
+      Value::I16(v) => visitor.visit_i16(v),
+      Value::I32(v) => visitor.visit_i32(v),
+      Value::I64(v) => visitor.visit_i64(v),
+      Value::I128(v) => visitor.visit_i128(v),
+      Value::F32(v) => visitor.visit_f32(v),
+      Value::F64(v) => visitor.visit_f64(v),
+      Value::Char(v) => visitor.visit_char(v),
+      Value::String(v) => visitor.visit_string(v),
+      Value::Str(v) => visitor.visit_string(v.into_owned()),
+      Value::Unit => visitor.visit_unit(),
+      Value::Option(None) => visitor.visit_none(),
+      Value::Option(Some(v)) => visitor.visit_some(ValueDeserializer(*v)),
+      Value::Newtype(v) => visitor.visit_newtype_struct(ValueDeserializer(*v)),
+      Value::Bytes(v) => visitor.visit_byte_buf(v),
+      Value::Seq(v) => visitor.visit_seq(SeqDeserializer::new(v.into_iter())),
+      Value::Map(v) => visitor.visit_map(MapDeserializer::new(v.into_iter())),
+      Value::Attributes(attributes) =>
+      {
+        let entries : Vec<(String, Value)> = attributes.attributes().iter().map(|attribute| (attribute.name().to_string(), attribute.value().clone())).collect();
+        visitor.visit_map(MapDeserializer::new(entries.into_iter()))
+      },
+      Value::Enum(variant) => match variant.payload()
+      {
+        None => visitor.visit_string(variant.variant().to_string()),
+        Some(payload) => visitor.visit_map(MapDeserializer::new(std::iter::once((variant.variant().to_string(), payload)))),
+      },
+      Value::DateTime(v) => visitor.visit_string(v.to_rfc3339()),
+      Value::Uuid(v) => visitor.visit_string(v.to_string()),
+      Value::IpAddr(v) => visitor.visit_string(v.to_string()),
+      value => ValueDeserializer(value).deserialize_json(visitor),
+    }
+  }
+
+  fn deserialize_option<V : Visitor<'de>>(self, visitor : V) -> Result<V::Value>
+  {
+    match self.0
+    {
+      Value::Unit | Value::Option(None) => visitor.visit_none(),
+      Value::Option(Some(v)) => visitor.visit_some(ValueDeserializer(*v)),
+      value => visitor.visit_some(ValueDeserializer(value)),
+    }
+  }
+
+  fn deserialize_newtype_struct<V : Visitor<'de>>(self, name : &'static str, visitor : V) -> Result<V::Value>
+  {
+    match self.0
+    {
+      Value::VFileBuilder(builder) | Value::Blob(builder) if name == passthrough::TOKEN =>
+      {
+        passthrough::stash(builder);
+        let result = visitor.visit_newtype_struct(ValueDeserializer(Value::Unit));
+        passthrough::take();
+        result
+      },
+      Value::Newtype(v) => visitor.visit_newtype_struct(ValueDeserializer(*v)),
+      value => visitor.visit_newtype_struct(ValueDeserializer(value)),
+    }
+  }
+
+  fn deserialize_enum<V : Visitor<'de>>(self, _name : &'static str, _variants : &'static [&'static str], visitor : V) -> Result<V::Value>
+  {
+    match self.0
+    {
+      Value::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+      Value::Str(variant) => visitor.visit_enum(variant.into_owned().into_deserializer()),
+      Value::Enum(variant) => visitor.visit_enum(EnumDeserializer{ variant : variant.variant().to_string(), payload : variant.payload() }),
+      Value::Map(map) if map.len() == 1 =>
+      {
+        let (variant, payload) = map.into_iter().next().unwrap();
+        visitor.visit_enum(EnumDeserializer{ variant, payload : Some(payload) })
+      },
+      value => Err(RustructError::Serialize(format!("can't deserialize an enum from {:?}", value.type_id()))),
+    }
+  }
+
+  forward_to_deserialize_any!
+  {
+    bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+    bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+  }
+}
+
+/// Access to an enum variant and its payload.
+struct EnumDeserializer
+{
+  variant : String,
+  payload : Option<Value>,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer
+{
+  type Error = RustructError;
+  type Variant = VariantDeserializer;
+
+  fn variant_seed<V : de::DeserializeSeed<'de>>(self, seed : V) -> Result<(V::Value, VariantDeserializer)>
+  {
+    let variant = seed.deserialize(self.variant.into_deserializer())?;
+    Ok((variant, VariantDeserializer(self.payload)))
+  }
+}
+
+struct VariantDeserializer(Option<Value>);
+
+impl<'de> VariantAccess<'de> for VariantDeserializer
+{
+  type Error = RustructError;
+
+  fn unit_variant(self) -> Result<()>
+  {
+    match self.0
+    {
+      None | Some(Value::Unit) => Ok(()),
+      Some(payload) => Err(RustructError::Serialize(format!("unexpected {:?} payload for a unit variant", payload.type_id()))),
+    }
+  }
+
+  fn newtype_variant_seed<T : de::DeserializeSeed<'de>>(self, seed : T) -> Result<T::Value>
+  {
+    seed.deserialize(ValueDeserializer(self.0.unwrap_or(Value::Unit)))
+  }
+
+  fn tuple_variant<V : Visitor<'de>>(self, _len : usize, visitor : V) -> Result<V::Value>
+  {
+    de::Deserializer::deserialize_seq(ValueDeserializer(self.0.unwrap_or(Value::Seq(Vec::new()))), visitor)
+  }
+
+  fn struct_variant<V : Visitor<'de>>(self, _fields : &'static [&'static str], visitor : V) -> Result<V::Value>
+  {
+    de::Deserializer::deserialize_map(ValueDeserializer(self.0.unwrap_or(Value::Map(Default::default()))), visitor)
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::from_value;
+  use crate::value::{Value, to_value, passthrough};
+  use crate::vfile::VFileBuilder;
+  use crate::patternvfile::PatternVFileBuilder;
+
+  use std::sync::Arc;
+  use serde::{Serialize, Deserialize};
+
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  enum Kind
+  {
+    Boot,
+    Data(u32),
+    Extended{ start : u64, count : u8 },
+  }
+
+  #[derive(Serialize, Deserialize)]
+  struct Entry
+  {
+    name : String,
+    kinds : Vec<Kind>,
+    flags : (u8, bool),
+    size : Option<u64>,
+    id : uuid::Uuid,
+    #[serde(with = "passthrough")]
+    data : Arc<dyn VFileBuilder>,
+  }
+
+  #[test]
+  fn deserialize_from_value()
+  {
+    let data : Arc<dyn VFileBuilder> = Arc::new(PatternVFileBuilder::counter(7));
+    let entry = Entry{ name : "ext".into(), kinds : vec![Kind::Boot, Kind::Data(7), Kind::Extended{ start : 63, count : 2 }],
+                       flags : (0x80, true), size : Some(512), id : uuid::Uuid::new_v4(), data : data.clone() };
+
+    let value = to_value(&entry).unwrap();
+    assert!(matches!(value.get_path("data"), Some(Value::VFileBuilder(builder)) if Arc::ptr_eq(&builder, &data)));
+    let read : Entry = from_value(value).unwrap();
+    assert!(read.name == "ext" && read.kinds == entry.kinds && read.flags == (0x80, true) && read.size == Some(512) && read.id == entry.id);
+    assert!(Arc::ptr_eq(&read.data, &data));
+
+    //builders are still serialized with their type in JSON
+    let json = serde_json::to_string(&entry).unwrap();
+    let read : Entry = serde_json::from_str(&json).unwrap();
+    assert!(read.data.size() == 7 && !Arc::ptr_eq(&read.data, &data));
+
+    assert!(from_value::<Option<u32>>(Value::U32(3)).unwrap() == Some(3) && from_value::<Option<u32>>(Value::Option(None)).unwrap().is_none());
+    assert!(from_value::<Kind>(Value::Str("Boot".into())).unwrap() == Kind::Boot);
+    assert!(from_value::<u8>(Value::String("a".into())).is_err());
+  }
+}
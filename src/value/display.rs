@@ -0,0 +1,402 @@
+//! Display and Debug formatting of [Value].
+//!
+//! Formatting a huge `Seq`, `Bytes`, `Map` or `String` can produce gigabyte strings,
+//! so by default values are truncated according to the global [DisplayLimits].
+//! [Value::untruncated] can be used to format a value entirely.
+//! Serialization is never truncated, but the [exports](crate::export) apply the same limits by default with [Value::truncated].
+//! The data sent to [replicas](crate::replica) is never truncated so they stay lossless.
+
+use std::fmt;
+use std::sync::RwLock;
+use std::collections::HashMap;
+
+use crate::value::Value;
+use crate::attribute::Attributes;
+use crate::value::format::{write_hex, DEFAULT_DATETIME_FORMAT};
+use crate::vfile::preview::{preview, SNIPPET_SIZE};
+
+/// Maximum size of a [Value] when it's displayed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayLimits
+{
+  /// Maximum number of elements displayed for `Seq`, `Map` and `Attributes`.
+  pub max_elements : usize,
+  /// Maximum number of characters displayed for `String` and `Str`.
+  pub max_string_length : usize,
+  /// Maximum number of bytes displayed for `Bytes`.
+  pub max_bytes : usize,
+}
+
+impl DisplayLimits
+{
+  /// Return limits that never truncate.
+  pub const fn unlimited() -> Self
+  {
+    DisplayLimits{ max_elements : usize::MAX, max_string_length : usize::MAX, max_bytes : usize::MAX }
+  }
+}
+
+impl Default for DisplayLimits
+{
+  fn default() -> Self
+  {
+    DEFAULT_LIMITS
+  }
+}
+
+const DEFAULT_LIMITS : DisplayLimits = DisplayLimits{ max_elements : 256, max_string_length : 4096, max_bytes : 256 };
+
+static DISPLAY_LIMITS : RwLock<DisplayLimits> = RwLock::new(DEFAULT_LIMITS);
+
+/// Set the global limits used when a [Value] is displayed.
+pub fn set_display_limits(limits : DisplayLimits)
+{
+  *DISPLAY_LIMITS.write().unwrap() = limits;
+}
+
+/// Return the global limits used when a [Value] is displayed.
+pub fn display_limits() -> DisplayLimits
+{
+  *DISPLAY_LIMITS.read().unwrap()
+}
+
+/// Format a [Value] without applying the global [DisplayLimits].
+pub struct Untruncated<'a>(&'a Value);
+
+impl fmt::Display for Untruncated<'_>
+{
+  fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result
+  {
+    self.0.fmt_display(f, &DisplayLimits::unlimited())
+  }
+}
+
+impl fmt::Debug for Untruncated<'_>
+{
+  fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result
+  {
+    self.0.fmt_debug(f, &DisplayLimits::unlimited())
+  }
+}
+
+/// Write `more` if elements were not displayed.
+pub(crate) fn write_more<W : fmt::Write>(f : &mut W, more : usize, separator : &str) -> fmt::Result
+{
+  match more
+  {
+    0 => Ok(()),
+    more => write!(f, "{}... ({} more)", separator, more),
+  }
+}
+
+pub(crate) fn fmt_str<W : fmt::Write>(f : &mut W, val : &str, limits : &DisplayLimits) -> fmt::Result
+{
+  match val.char_indices().nth(limits.max_string_length)
+  {
+    Some((index, _)) => write!(f, "{}... ({} more)", &val[..index], val[index..].chars().count()),
+    None => write!(f, "{}", val),
+  }
+}
+
+fn fmt_seq(f : &mut fmt::Formatter, val : &[Value], limits : &DisplayLimits) -> fmt::Result
+{
+  write!(f, "[")?;
+  for (index, val) in val.iter().take(limits.max_elements).enumerate()
+  {
+    if index != 0
+    {
+      write!(f, ", ")?;
+    }
+    val.fmt_debug(f, limits)?;
+  }
+  write_more(f, val.len().saturating_sub(limits.max_elements), if val.is_empty() { "" } else { ", " })?;
+  write!(f, "]")
+}
+
+fn fmt_bytes(f : &mut fmt::Formatter, val : &[u8], limits : &DisplayLimits) -> fmt::Result
+{
+  let shown = &val[..val.len().min(limits.max_bytes)];
+  let mut list = format!("{:?}", shown);
+  list.pop();
+  write!(f, "{}", list)?;
+  write_more(f, val.len() - shown.len(), if shown.is_empty() { "" } else { ", " })?;
+  write!(f, "]")
+}
+
+fn fmt_map(f : &mut fmt::Formatter, val : &HashMap<String, Value>, limits : &DisplayLimits) -> fmt::Result
+{
+  write!(f, "{{")?;
+  for (index, (key, val)) in val.iter().take(limits.max_elements).enumerate()
+  {
+    if index != 0
+    {
+      write!(f, ", ")?;
+    }
+    write!(f, "{:?}: ", key)?;
+    val.fmt_debug(f, limits)?;
+  }
+  write_more(f, val.len().saturating_sub(limits.max_elements), if val.is_empty() { "" } else { ", " })?;
+  write!(f, "}}")
+}
+
+fn fmt_attributes(f : &mut fmt::Formatter, val : &Attributes, limits : &DisplayLimits) -> fmt::Result
+{
+  let attributes = val.attributes();
+  write!(f, "{{")?;
+  for attribute in attributes.iter().take(limits.max_elements)
+  {
+    write!(f, "\"{}\" : ", attribute.name())?;
+    attribute.value().fmt_debug(f, limits)?;
+    write!(f, ", ")?;
+  }
+  write_more(f, val.count().saturating_sub(limits.max_elements), "")?;
+  write!(f, "}}")
+}
+
+/// Return `val` truncated to the maximum string length of `limits`, or `None` if it's not too long.
+fn truncate_str(val : &str, limits : &DisplayLimits) -> Option<String>
+{
+  val.chars().nth(limits.max_string_length)?;
+  let mut truncated = String::new();
+  fmt_str(&mut truncated, val, limits).ok()?;
+  Some(truncated)
+}
+
+/// Return the element added at the end of a truncated value.
+fn more(count : usize) -> Value
+{
+  Value::String(format!("... ({} more)", count))
+}
+
+impl Value
+{
+  /// Return a wrapper that format this value entirely, without applying the global [DisplayLimits].
+  pub fn untruncated(&self) -> Untruncated<'_>
+  {
+    Untruncated(self)
+  }
+
+  /// Return a copy of this value truncated according to `limits` like when it's displayed, so it can be exported.
+  /// Truncated strings end with `... (N more)`, truncated `Seq` and `Bytes` end with an element of that form,
+  /// and truncated `Map` and `Attributes` with an entry named `...`. `Bytes` are returned as a `Seq` when truncated.
+  pub fn truncated(&self, limits : &DisplayLimits) -> Value
+  {
+    match self
+    {
+      Value::String(_) | Value::Str(_) => match self.str().and_then(|val| truncate_str(val, limits))
+      {
+        Some(truncated) => Value::String(truncated),
+        None => self.clone(),
+      },
+      Value::Option(Some(val)) => Value::Option(Some(Box::new(val.truncated(limits)))),
+      Value::Newtype(val) => Value::Newtype(Box::new(val.truncated(limits))),
+      Value::Seq(val) =>
+      {
+        let mut seq : Vec<Value> = val.iter().take(limits.max_elements).map(|val| val.truncated(limits)).collect();
+        if val.len() > seq.len()
+        {
+          seq.push(more(val.len() - seq.len()));
+        }
+        Value::Seq(seq)
+      },
+      Value::Bytes(val) if val.len() > limits.max_bytes =>
+      {
+        let mut seq : Vec<Value> = val[..limits.max_bytes].iter().map(|byte| Value::U8(*byte)).collect();
+        seq.push(more(val.len() - limits.max_bytes));
+        Value::Seq(seq)
+      },
+      Value::Map(val) =>
+      {
+        let mut map : HashMap<String, Value> = val.iter().take(limits.max_elements).map(|(key, val)| (key.clone(), val.truncated(limits))).collect();
+        if val.len() > map.len()
+        {
+          map.insert("...".into(), more(val.len() - map.len()));
+        }
+        Value::Map(map)
+      },
+      Value::Attributes(val) =>
+      {
+        let mut attributes = Attributes::new();
+        for attribute in val.attributes().iter().take(limits.max_elements)
+        {
+          attributes.add_attribute(attribute.name().to_string(), attribute.value().truncated(limits), None);
+        }
+        if val.count() > attributes.count()
+        {
+          let count = val.count() - attributes.count();
+          attributes.add_attribute("...", more(count), None);
+        }
+        Value::Attributes(attributes)
+      },
+      Value::ReflectStruct(val) =>
+      {
+        let mut attributes = Attributes::new();
+        for attribute in val.attributes()
+        {
+          attributes.add_attribute(attribute.name().to_string(), attribute.value().clone(), None);
+        }
+        Value::Attributes(attributes).truncated(limits)
+      },
+      Value::Func(func) => func().truncated(limits),
+      Value::FuncArg(func, arg) => func(Value::Newtype(arg.clone())).truncated(limits),
+      _ => self.clone(),
+    }
+  }
+
+  fn fmt_display(&self, f : &mut fmt::Formatter, limits : &DisplayLimits) -> fmt::Result
+  {
+    match self
+    {
+      Value::Char(val) => write!(f, "{}", val),
+      Value::String(val) => fmt_str(f, val, limits),
+      Value::Str(val) => fmt_str(f, val, limits),
+      Value::Newtype(val) => val.fmt_display(f, limits),
+      Value::Func(func) => func().fmt_display(f, limits),
+      Value::FuncArg(func, arg) => func(Value::Newtype(arg.clone())).fmt_display(f, limits),
+      Value::VFileBuilder(val) => write!(f, "{:?}", val.size()),
+      Value::Blob(val) => write!(f, "Blob({})", val.size()),
+      Value::Enum(val) => write!(f, "{}", val.variant()),
+      Value::Bytes(val) =>
+      {
+        let shown = &val[..val.len().min(limits.max_bytes)];
+        write_hex(f, shown)?;
+        write_more(f, val.len() - shown.len(), "")
+      },
+      Value::DateTime(val) => write!(f, "{}", val.format(DEFAULT_DATETIME_FORMAT)),
+      _ => self.fmt_debug(f, limits),
+    }
+  }
+
+  fn fmt_debug(&self, f : &mut fmt::Formatter, limits : &DisplayLimits) -> fmt::Result
+  {
+    match self
+    {
+      //Value::None => write!(f, "None"),
+      Value::Bool(val) => write!(f, "{}", val),
+
+      Value::U8(val) => write!(f, "{}", val),
+      Value::U16(val) => write!(f, "{}", val),
+      Value::U32(val) => write!(f, "{}", val),
+      Value::U64(val) => write!(f, "{}", val),
+
+      Value::I8(val) => write!(f, "{}", val),
+      Value::I16(val) => write!(f, "{}", val),
+      Value::I32(val) => write!(f, "{}", val),
+      Value::I64(val) => write!(f, "{}", val),
+      Value::U128(val) => write!(f, "{}", val),
+      Value::I128(val) => write!(f, "{}", val),
+
+      Value::F32(val) => write!(f, "{}", val),
+      Value::F64(val) => write!(f, "{}", val),
+
+      Value::USize(val) => write!(f, "{}", val),
+
+      Value::Char(val) => write!(f, "'{}'", val),
+      Value::String(val) => { write!(f, "\"")?; fmt_str(f, val, limits)?; write!(f, "\"") },
+      Value::Str(val) => { write!(f, "\"")?; fmt_str(f, val, limits)?; write!(f, "\"") },
+
+      Value::Unit => write!(f, "()"),
+      Value::Option(None) => write!(f, "None"),
+      Value::Option(Some(val)) => { write!(f, "Some(")?; val.fmt_debug(f, limits)?; write!(f, ")") },
+      Value::Newtype(val) => val.fmt_debug(f, limits),
+      Value::Seq(val) => fmt_seq(f, val, limits),
+      Value::Map(val) => fmt_map(f, val, limits),
+      Value::Bytes(val) => fmt_bytes(f, val, limits),
+      Value::DateTime(val) => write!(f, "{:?}", val),
+      Value::Duration(val) => write!(f, "{}", val),
+      Value::IpAddr(val) => write!(f, "{}", val),
+      Value::Uuid(val) => write!(f, "{}", val),
+      Value::Blob(val) => write!(f, "Blob({})", val.size()),
+
+      Value::Func(func) => func().fmt_debug(f, limits),
+      Value::FuncArg(func, arg) => func(Value::Newtype(arg.clone())).fmt_debug(f, limits),
+      Value::VFileBuilder(val) => match preview(val.as_ref(), 0, SNIPPET_SIZE)
+      {
+        Ok(dump) => write!(f, "{}", dump.snippet()),
+        Err(_err) => write!(f, ""),//XXX ret some error ?
+      },
+      Value::NodeId(val) => write!(f, "{:?}", val),
+      Value::AttributePath(val) => write!(f, "{:?}", val),
+      Value::Attributes(val) => fmt_attributes(f, val, limits),
+      Value::ReflectStruct(val) => write!(f, "{:?}", val),
+      Value::Method(val) => write!(f, "{:?}", val),
+      Value::Enum(val) =>
+      {
+        write!(f, "{}::{}", val.name(), val.variant())?;
+        match val.payload()
+        {
+          Some(payload) => { write!(f, "(")?; payload.fmt_debug(f, limits)?; write!(f, ")") },
+          None => Ok(()),
+        }
+      },
+    }
+  }
+}
+
+impl fmt::Display for Value
+{
+  #[inline]
+  fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result
+  {
+    self.fmt_display(f, &display_limits())
+  }
+}
+
+impl fmt::Debug for Value
+{
+  #[inline]
+  fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result
+  {
+    self.fmt_debug(f, &display_limits())
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::DisplayLimits;
+  use crate::value::Value;
+  use crate::attribute::Attributes;
+
+  #[test]
+  fn display_truncation()
+  {
+    let limits = DisplayLimits::default();
+
+    let seq = Value::Seq(vec![Value::U8(1); limits.max_elements + 2]);
+    assert!(seq.to_string().ends_with(", 1, ... (2 more)]"));
+    assert!(seq.untruncated().to_string().ends_with(", 1, 1, 1]"));
+
+    let bytes = Value::Bytes(vec![0; limits.max_bytes + 10]);
+    assert!(format!("{:?}", bytes).ends_with("0, ... (10 more)]"));
+    assert!(Value::Bytes(vec![1, 2]).to_string() == "0102");
+    assert!(format!("{:?}", Value::Bytes(vec![1, 2])) == "[1, 2]");
+
+    let string = Value::String("a".repeat(limits.max_string_length + 5));
+    assert!(string.to_string().ends_with("a... (5 more)"));
+    assert!(format!("{:?}", string).ends_with("a... (5 more)\""));
+    assert!(string.untruncated().to_string().len() == limits.max_string_length + 5);
+
+    assert!(Value::Option(Some(Box::new(Value::from("str")))).to_string() == "Some(\"str\")");
+    assert!(Value::Seq(vec![]).to_string() == "[]");
+  }
+
+  #[test]
+  fn truncated_value()
+  {
+    let limits = DisplayLimits{ max_elements : 2, max_string_length : 3, max_bytes : 1 };
+
+    let seq = Value::Seq(vec![Value::from("abcdef"), Value::U8(1), Value::U8(2)]).truncated(&limits);
+    assert!(format!("{:?}", seq.untruncated()) == "[\"abc... (3 more)\", 1, \"... (1 more)\"]");
+    assert!(format!("{:?}", Value::Bytes(vec![1, 2, 3]).truncated(&limits).untruncated()) == "[1, \"... (2 more)\"]");
+    assert!(matches!(Value::Bytes(vec![1]).truncated(&limits), Value::Bytes(_)));
+
+    let mut attributes = Attributes::new();
+    for name in ["a", "b", "c"]
+    {
+      attributes.add_attribute(name, Value::U8(0), None);
+    }
+    let truncated = Value::Attributes(attributes).truncated(&limits).as_attributes();
+    assert!(truncated.names() == vec!["a", "b", "..."]);
+  }
+}
@@ -0,0 +1,318 @@
+//! Human-friendly rendering of [Value] for frontends.
+//!
+//! [ValueFormat] let frontends choose how bytes, datetimes and sizes are rendered,
+//! the default format is also used by the [Display](std::fmt::Display) implementation of [Value].
+
+use std::fmt::{self, Write};
+
+use crate::value::{Value, DisplayLimits, display_limits};
+use crate::value::display::{fmt_str, write_more};
+
+use chrono::{DateTime, Utc, Local, FixedOffset};
+
+/// Default [strftime](chrono::format::strftime) format used to render datetimes.
+pub const DEFAULT_DATETIME_FORMAT : &str = "%Y-%m-%d %H:%M:%S%.f %Z";
+
+/// Rendering of `Bytes` values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BytesFormat
+{
+  /// List of decimal bytes : `[1, 2, 3]`.
+  List,
+  /// Continuous hexadecimal string : `010203`.
+  Hex,
+  /// Multi-line hex dump with offset, hexadecimal and ASCII columns.
+  HexDump,
+}
+
+/// Timezone in which datetimes are rendered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeZoneFormat
+{
+  Utc,
+  /// Timezone of the host.
+  Local,
+  /// Fixed offset in seconds east of UTC.
+  Offset(i32),
+}
+
+/// Configuration used to render a [Value] to a human-friendly [String].
+#[derive(Debug, Clone)]
+pub struct ValueFormat
+{
+  /// How `Bytes` are rendered.
+  pub bytes : BytesFormat,
+  /// Timezone in which `DateTime` are rendered.
+  pub timezone : TimeZoneFormat,
+  /// [strftime](chrono::format::strftime) format used to render `DateTime`.
+  pub datetime : String,
+  /// Name of the attributes containing a size, rendered as human size (`1.50 KiB`) by [ValueFormat::render_attribute].
+  pub size_attributes : Vec<String>,
+  /// Truncation limits.
+  pub limits : DisplayLimits,
+}
+
+impl Default for ValueFormat
+{
+  /// Return the default format using the global [DisplayLimits].
+  fn default() -> Self
+  {
+    ValueFormat{ bytes : BytesFormat::Hex, timezone : TimeZoneFormat::Utc, datetime : DEFAULT_DATETIME_FORMAT.into(),
+                 size_attributes : vec!["size".into()], limits : display_limits() }
+  }
+}
+
+impl ValueFormat
+{
+  /// Render `value`, container values are rendered recursively.
+  pub fn render(&self, value : &Value) -> String
+  {
+    let mut output = String::new();
+    let _ = self.write_value(&mut output, value);
+    output
+  }
+
+  /// Render the value of attribute `name`, integer values of size attributes are rendered as human size.
+  pub fn render_attribute(&self, name : &str, value : &Value) -> String
+  {
+    if self.size_attributes.iter().any(|size| size == name)
+    {
+      if let Some(size) = value.to_u64()
+      {
+        return human_size(size)
+      }
+    }
+    self.render(value)
+  }
+
+  /// Render `bytes` according to the [BytesFormat] and the limits.
+  pub fn render_bytes(&self, bytes : &[u8]) -> String
+  {
+    let mut output = String::new();
+    let _ = self.write_bytes(&mut output, bytes);
+    output
+  }
+
+  /// Render `datetime` in the configured timezone and format.
+  pub fn render_datetime(&self, datetime : &DateTime<Utc>) -> String
+  {
+    match self.timezone
+    {
+      TimeZoneFormat::Utc => datetime.format(&self.datetime).to_string(),
+      TimeZoneFormat::Local => datetime.with_timezone(&Local).format(&self.datetime).to_string(),
+      TimeZoneFormat::Offset(seconds) => match FixedOffset::east_opt(seconds)
+      {
+        Some(offset) => datetime.with_timezone(&offset).format(&self.datetime).to_string(),
+        None => datetime.format(&self.datetime).to_string(),
+      },
+    }
+  }
+
+  fn write_bytes<W : Write>(&self, output : &mut W, bytes : &[u8]) -> fmt::Result
+  {
+    let shown = &bytes[..bytes.len().min(self.limits.max_bytes)];
+    let more = bytes.len() - shown.len();
+
+    match self.bytes
+    {
+      BytesFormat::List =>
+      {
+        let mut list = format!("{:?}", shown);
+        list.pop();
+        write!(output, "{}", list)?;
+        write_more(output, more, if shown.is_empty() { "" } else { ", " })?;
+        write!(output, "]")
+      },
+      BytesFormat::Hex =>
+      {
+        write_hex(output, shown)?;
+        write_more(output, more, "")
+      },
+      BytesFormat::HexDump =>
+      {
+        write!(output, "{}", hex_dump(shown, 0))?;
+        write_more(output, more, "")
+      },
+    }
+  }
+
+  fn write_value<W : Write>(&self, output : &mut W, value : &Value) -> fmt::Result
+  {
+    match value
+    {
+      Value::String(val) => fmt_str(output, val, &self.limits),
+      Value::Str(val) => fmt_str(output, val, &self.limits),
+      Value::Bytes(val) => self.write_bytes(output, val),
+      Value::Blob(val) => write!(output, "Blob({})", human_size(val.size())),
+      Value::Enum(val) => match val.payload()
+      {
+        Some(payload) => write!(output, "{}({})", val.variant(), self.render(&payload)),
+        None => write!(output, "{}", val.variant()),
+      },
+      Value::DateTime(val) => write!(output, "{}", self.render_datetime(val)),
+      Value::Newtype(val) => self.write_value(output, val),
+      Value::Option(None) => write!(output, "None"),
+      Value::Option(Some(val)) => self.write_value(output, val),
+      Value::Func(func) => self.write_value(output, &func()),
+      Value::FuncArg(func, arg) => self.write_value(output, &func(Value::Newtype(arg.clone()))),
+      Value::Seq(val) =>
+      {
+        write!(output, "[")?;
+        for (index, val) in val.iter().take(self.limits.max_elements).enumerate()
+        {
+          if index != 0
+          {
+            write!(output, ", ")?;
+          }
+          self.write_value(output, val)?;
+        }
+        write_more(output, val.len().saturating_sub(self.limits.max_elements), if val.is_empty() { "" } else { ", " })?;
+        write!(output, "]")
+      },
+      Value::Map(val) =>
+      {
+        let mut keys : Vec<&String> = val.keys().collect();
+        keys.sort();
+        write!(output, "{{")?;
+        for (index, key) in keys.iter().take(self.limits.max_elements).enumerate()
+        {
+          if index != 0
+          {
+            write!(output, ", ")?;
+          }
+          write!(output, "{} : {}", key, self.render_attribute(key, &val[*key]))?;
+        }
+        write_more(output, keys.len().saturating_sub(self.limits.max_elements), if keys.is_empty() { "" } else { ", " })?;
+        write!(output, "}}")
+      },
+      Value::Attributes(val) =>
+      {
+        let attributes = val.attributes();
+        write!(output, "{{")?;
+        for (index, attribute) in attributes.iter().take(self.limits.max_elements).enumerate()
+        {
+          if index != 0
+          {
+            write!(output, ", ")?;
+          }
+          write!(output, "{} : {}", attribute.name(), self.render_attribute(attribute.name(), attribute.value()))?;
+        }
+        write_more(output, val.count().saturating_sub(self.limits.max_elements), if val.count() == 0 { "" } else { ", " })?;
+        write!(output, "}}")
+      },
+      Value::ReflectStruct(val) =>
+      {
+        write!(output, "{} {{", val.name())?;
+        for (index, attribute) in val.attributes().iter().enumerate()
+        {
+          if index != 0
+          {
+            write!(output, ", ")?;
+          }
+          write!(output, "{} : {}", attribute.name(), self.render_attribute(attribute.name(), attribute.value()))?;
+        }
+        write!(output, "}}")
+      },
+      _ => write!(output, "{}", value.untruncated()),
+    }
+  }
+}
+
+/// Write `bytes` as a continuous hexadecimal string.
+pub(crate) fn write_hex<W : Write>(output : &mut W, bytes : &[u8]) -> fmt::Result
+{
+  for byte in bytes
+  {
+    write!(output, "{:02x}", byte)?;
+  }
+  Ok(())
+}
+
+/// Return `size` in bytes as a human readable size using binary units (`1.50 KiB`).
+pub fn human_size(size : u64) -> String
+{
+  const UNITS : [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+  if size < 1024
+  {
+    return format!("{} B", size)
+  }
+
+  let mut value = size as f64;
+  let mut unit = 0;
+  while value >= 1024.0 && unit < UNITS.len() - 1
+  {
+    value /= 1024.0;
+    unit += 1;
+  }
+  format!("{:.2} {}", value, UNITS[unit])
+}
+
+/// Return a hex dump of `bytes`, with 16 bytes per line prefixed by their offset starting at `offset`.
+pub fn hex_dump(bytes : &[u8], offset : u64) -> String
+{
+  let mut output = String::new();
+
+  for (index, line) in bytes.chunks(16).enumerate()
+  {
+    let _ = writeln!(output, "{:08x}  {} |{}|", offset + index as u64 * 16, hex_columns(line), ascii_columns(line));
+  }
+  output
+}
+
+/// Return the hex column of a line of [hex_dump], padded to 16 bytes with an extra space after the 8th byte.
+pub fn hex_columns(line : &[u8]) -> String
+{
+  let mut output = String::new();
+  for column in 0..16
+  {
+    match line.get(column)
+    {
+      Some(byte) => { let _ = write!(output, "{:02x} ", byte); },
+      None => output.push_str("   "),
+    }
+    if column == 7
+    {
+      output.push(' ');
+    }
+  }
+  output
+}
+
+/// Return the ascii column of a line of [hex_dump], non printable bytes are replaced by `.`.
+pub fn ascii_columns(line : &[u8]) -> String
+{
+  line.iter().map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' }).collect()
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{ValueFormat, BytesFormat, TimeZoneFormat, human_size, hex_dump};
+  use crate::value::Value;
+  use crate::attribute::Attributes;
+  use chrono::{TimeZone, Utc};
+
+  #[test]
+  fn format_values()
+  {
+    let mut format = ValueFormat::default();
+    assert!(format.render(&Value::Bytes(vec![0xde, 0xad])) == "dead");
+    format.bytes = BytesFormat::List;
+    assert!(format.render(&Value::Bytes(vec![0xde, 0xad])) == "[222, 173]");
+
+    let datetime = Value::DateTime(Utc.with_ymd_and_hms(2021, 1, 2, 3, 4, 5).unwrap());
+    assert!(format.render(&datetime) == "2021-01-02 03:04:05 UTC");
+    format.timezone = TimeZoneFormat::Offset(3600);
+    assert!(format.render(&datetime) == "2021-01-02 04:04:05 +01:00");
+
+    let mut attributes = Attributes::new();
+    attributes.add_attribute("size", Value::U64(1536), None);
+    attributes.add_attribute("name", Value::from("file"), None);
+    assert!(format.render(&Value::Attributes(attributes)) == "{size : 1.50 KiB, name : file}");
+
+    assert!(human_size(10) == "10 B");
+    assert!(human_size(3 * 1024 * 1024 * 1024) == "3.00 GiB");
+    assert!(hex_dump(b"ABC", 0x10) == format!("00000010  41 42 43 {}  |ABC|\n", " ".repeat(3 * 13)));
+  }
+}
@@ -0,0 +1,240 @@
+//! [Method] is a callable [Value] taking a list of typed arguments,
+//! used by dynamic attributes to expose operations like `decode(codepage)` to clients.
+
+use std::fmt;
+use std::sync::Arc;
+use std::collections::HashMap;
+
+use crate::value::{Value, ValueTypeId};
+use crate::error::RustructError;
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+
+type MethodFunc = Arc<Box<dyn Fn(Vec<Value>) -> Result<Value> + Sync + Send>>;
+
+/// Parameter of a [Method].
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Parameter
+{
+  /// Name of the parameter.
+  pub name : String,
+  /// Type of the argument, any type is accepted if `None`.
+  pub type_id : Option<ValueTypeId>,
+  /// Description of the parameter.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub description : Option<String>,
+}
+
+impl Parameter
+{
+  /// Return a new [Parameter] named `name` accepting arguments of type `type_id`.
+  pub fn new<S : Into<String>>(name : S, type_id : ValueTypeId) -> Self
+  {
+    Parameter{ name : name.into(), type_id : Some(type_id), description : None }
+  }
+
+  /// Return a new [Parameter] named `name` accepting arguments of any type.
+  pub fn any<S : Into<String>>(name : S) -> Self
+  {
+    Parameter{ name : name.into(), type_id : None, description : None }
+  }
+
+  /// Set the description of the parameter.
+  pub fn with_description<S : Into<String>>(mut self, description : S) -> Self
+  {
+    self.description = Some(description.into());
+    self
+  }
+}
+
+impl fmt::Debug for Parameter
+{
+  fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result
+  {
+    match &self.type_id
+    {
+      Some(type_id) => write!(f, "{} : {:?}", self.name, type_id),
+      None => write!(f, "{}", self.name),
+    }
+  }
+}
+
+/**
+ * A named function with a list of [parameters](Parameter).
+ * Arguments are checked against the parameters and numbers and strings are converted to the parameter type before calling the function.
+ * Methods can't be evaluated without arguments, so they are serialized as their signature.
+ */
+#[derive(Clone)]
+pub struct Method
+{
+  name : String,
+  parameters : Vec<Parameter>,
+  func : MethodFunc,
+}
+
+impl Method
+{
+  /// Return a new [Method] named `name` calling `func` with arguments matching `parameters`.
+  pub fn new<S, F>(name : S, parameters : Vec<Parameter>, func : F) -> Self
+    where S : Into<String>,
+          F : Fn(Vec<Value>) -> Result<Value> + Sync + Send + 'static
+  {
+    Method{ name : name.into(), parameters, func : Arc::new(Box::new(func)) }
+  }
+
+  /// Return the name of the method.
+  pub fn name(&self) -> &str
+  {
+    &self.name
+  }
+
+  /// Return the parameters of the method.
+  pub fn parameters(&self) -> &[Parameter]
+  {
+    &self.parameters
+  }
+
+  /// Call the method with `arguments`, return an error if the number or the type of the arguments doesn't match the parameters.
+  pub fn call(&self, arguments : Vec<Value>) -> Result<Value>
+  {
+    if arguments.len() != self.parameters.len()
+    {
+      return Err(self.invalid(format!("expected {} arguments, got {}", self.parameters.len(), arguments.len())))
+    }
+
+    let arguments = arguments.into_iter().zip(self.parameters.iter()).map(|(argument, parameter)| match &parameter.type_id
+    {
+      None => Ok(argument),
+      Some(type_id) => coerce(&argument, type_id).ok_or_else(||
+        self.invalid(format!("{} expect {:?}, got {:?}", parameter.name, type_id, argument.type_id()))),
+    }).collect::<Result<Vec<Value>>>()?;
+
+    (self.func)(arguments)
+  }
+
+  /// Return the signature of the method as a `Map` with its `name` and `parameters`.
+  pub fn signature(&self) -> Value
+  {
+    let parameters = self.parameters.iter().map(|parameter|
+    {
+      let mut map = HashMap::new();
+      map.insert("name".to_string(), Value::from(parameter.name.clone()));
+      if let Some(type_id) = &parameter.type_id
+      {
+        map.insert("type".to_string(), Value::from(format!("{:?}", type_id)));
+      }
+      Value::Map(map)
+    }).collect();
+
+    let mut map = HashMap::new();
+    map.insert("name".to_string(), Value::from(self.name.clone()));
+    map.insert("parameters".to_string(), Value::Seq(parameters));
+    Value::Map(map)
+  }
+
+  fn invalid(&self, reason : String) -> anyhow::Error
+  {
+    RustructError::InvalidArgument(self.name.clone(), reason).into()
+  }
+}
+
+impl fmt::Debug for Method
+{
+  fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result
+  {
+    write!(f, "{}(", self.name)?;
+    for (index, parameter) in self.parameters.iter().enumerate()
+    {
+      if index != 0
+      {
+        write!(f, ", ")?;
+      }
+      write!(f, "{:?}", parameter)?;
+    }
+    write!(f, ")")
+  }
+}
+
+/// A [Method] is serialized as its name and parameters.
+impl Serialize for Method
+{
+  fn serialize<S>(&self, serializer : S) -> Result<S::Ok, S::Error>
+    where S : serde::Serializer,
+  {
+    use serde::ser::SerializeStruct;
+
+    let mut state = serializer.serialize_struct("Method", 2)?;
+    state.serialize_field("name", &self.name)?;
+    state.serialize_field("parameters", &self.parameters)?;
+    state.end()
+  }
+}
+
+/// Convert `value` to `type_id`, numbers are converted if they fit and `String` and `Str` are interchangeable.
+fn coerce(value : &Value, type_id : &ValueTypeId) -> Option<Value>
+{
+  if value.type_id() == *type_id
+  {
+    return Some(value.clone())
+  }
+
+  Some(match type_id
+  {
+    ValueTypeId::U8 => Value::U8(value.to_u64()?.try_into().ok()?),
+    ValueTypeId::U16 => Value::U16(value.to_u64()?.try_into().ok()?),
+    ValueTypeId::U32 => Value::U32(value.to_u64()?.try_into().ok()?),
+    ValueTypeId::U64 => Value::U64(value.to_u64()?),
+    ValueTypeId::USize => Value::USize(value.to_u64()?.try_into().ok()?),
+    ValueTypeId::U128 => Value::U128(value.to_u128()?),
+    ValueTypeId::I8 => Value::I8(value.to_i64()?.try_into().ok()?),
+    ValueTypeId::I16 => Value::I16(value.to_i64()?.try_into().ok()?),
+    ValueTypeId::I32 => Value::I32(value.to_i64()?.try_into().ok()?),
+    ValueTypeId::I64 => Value::I64(value.to_i64()?),
+    ValueTypeId::I128 => Value::I128(value.to_i128()?),
+    ValueTypeId::F32 => Value::F32(value.to_f64()? as f32),
+    ValueTypeId::F64 => Value::F64(value.to_f64()?),
+    ValueTypeId::String => match value
+    {
+      Value::Str(val) => Value::String(val.to_string()),
+      _ => return None,
+    },
+    ValueTypeId::Str => match value
+    {
+      Value::String(val) => Value::Str(val.clone().into()),
+      _ => return None,
+    },
+    _ => return None,
+  })
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{Method, Parameter};
+  use crate::value::{Value, ValueTypeId};
+  use crate::context::decode_codepage;
+
+  use std::sync::Arc;
+
+  #[test]
+  fn call_method()
+  {
+    let bytes = b"caf\xe9".to_vec();
+    let decode = Method::new("decode", vec![Parameter::new("codepage", ValueTypeId::String)], move |arguments|
+      Ok(Value::from(decode_codepage(&arguments[0].as_string(), &bytes))));
+    let value = Value::Method(Arc::new(decode));
+
+    let method = value.as_method();
+    assert!(method.call(vec![Value::from("latin1")]).unwrap().as_string() == "café");
+    assert!(method.call(vec![]).is_err());
+    assert!(method.call(vec![Value::U8(1)]).is_err());
+    assert!(format!("{:?}", value) == "decode(codepage : String)");
+    assert!(serde_json::to_string(&value).unwrap() == r#"{"name":"decode","parameters":[{"name":"codepage","type_id":"String"}]}"#);
+
+    let add = Method::new("add", vec![Parameter::new("a", ValueTypeId::U32), Parameter::any("b")], |arguments|
+      Ok(Value::U64(arguments[0].as_u32() as u64 + arguments[1].to_u64().unwrap_or(0))));
+    assert!(add.call(vec![Value::U64(1), Value::U8(2)]).unwrap() == Value::U64(3));
+    assert!(add.call(vec![Value::I64(-1), Value::U8(2)]).is_err());
+  }
+}
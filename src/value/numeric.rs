@@ -0,0 +1,205 @@
+//! Parsing of numbers stored as strings by artifacts (`"0x1A3F"`, `"1,024"`, `"4 KB"`), see [Value::parse_numeric].
+
+use crate::value::Value;
+
+/// Options of [Value::parse_numeric].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumericOptions
+{
+  /// Accept hexadecimal numbers prefixed by `0x`, and binary and octal numbers prefixed by `0b` and `0o`.
+  pub radix_prefix : bool,
+  /// Thousands separators ignored between digits.
+  pub separators : Vec<char>,
+  /// Accept a size unit suffix (`B`, `KB`, `KiB`, `MB`, ... up to `EB`), case insensitive.
+  pub units : bool,
+  /// Multiplier between two units, `1024` by default as used by most artifacts, `1000` for SI units.
+  /// `KiB`, `MiB`, ... always use `1024`.
+  pub unit_base : u64,
+  /// Accept decimal numbers, returned as `F64` unless multiplied by a unit to an integer.
+  pub floats : bool,
+}
+
+impl Default for NumericOptions
+{
+  fn default() -> Self
+  {
+    NumericOptions{ radix_prefix : true, separators : vec![',', '_'], units : true, unit_base : 1024, floats : true }
+  }
+}
+
+impl NumericOptions
+{
+  /// Return options accepting only plain decimal integers.
+  pub fn strict() -> Self
+  {
+    NumericOptions{ radix_prefix : false, separators : Vec::new(), units : false, unit_base : 1024, floats : false }
+  }
+}
+
+/// Split a unit suffix from `string`, return the number part and the unit multiplier.
+fn split_unit<'a>(string : &'a str, options : &NumericOptions) -> Option<(&'a str, u64)>
+{
+  let lower = string.to_ascii_lowercase();
+  let digits_end = lower.trim_end_matches(|c : char| c.is_ascii_alphabetic()).len();
+  let unit = lower[digits_end..].to_string();
+  let number = string[..digits_end].trim_end();
+
+  if unit.is_empty()
+  {
+    return Some((number, 1))
+  }
+  if !options.units
+  {
+    return None
+  }
+
+  let (prefix, binary) = match unit.strip_suffix("ib")
+  {
+    Some(prefix) => (prefix, true),
+    None => (unit.strip_suffix('b').unwrap_or(unit.as_str()), false),
+  };
+  let exponent = match prefix
+  {
+    "" if !binary => 0,
+    "k" => 1,
+    "m" => 2,
+    "g" => 3,
+    "t" => 4,
+    "p" => 5,
+    "e" => 6,
+    _ => return None,
+  };
+  let base = if binary { 1024 } else { options.unit_base };
+  Some((number, base.checked_pow(exponent)?))
+}
+
+/// Parse `string` as a number with `options`, see [Value::parse_numeric].
+pub fn parse_numeric(string : &str, options : &NumericOptions) -> Option<Value>
+{
+  let string = string.trim();
+  let (negative, number) = match string.strip_prefix('-')
+  {
+    Some(number) => (true, number),
+    None => (false, string.strip_prefix('+').unwrap_or(string)),
+  };
+
+  //hexadecimal digits can't be distinguished from units, so units are only accepted for decimal numbers
+  let (radix, digits, multiplier) = match (options.radix_prefix, number.get(..2).map(|prefix| prefix.to_ascii_lowercase()).as_deref())
+  {
+    (true, Some("0x")) => (16, &number[2..], 1),
+    (true, Some("0b")) => (2, &number[2..], 1),
+    (true, Some("0o")) => (8, &number[2..], 1),
+    _ =>
+    {
+      let (number, multiplier) = split_unit(number, options)?;
+      (10, number, multiplier)
+    },
+  };
+
+  //separators are only accepted between digits
+  if digits.is_empty() || digits.starts_with(|c| options.separators.contains(&c)) || digits.ends_with(|c| options.separators.contains(&c))
+  {
+    return None
+  }
+  let digits : String = digits.chars().filter(|c| !options.separators.contains(c)).collect();
+
+  if radix == 10 && digits.contains('.')
+  {
+    if !options.floats
+    {
+      return None
+    }
+    let value = digits.parse::<f64>().ok()? * multiplier as f64;
+    let value = if negative { -value } else { value };
+    if value.fract() == 0.0 && value.abs() < u64::MAX as f64 && multiplier != 1
+    {
+      return Some(integer(negative, value.abs() as u128))
+    }
+    return Some(Value::F64(value))
+  }
+
+  if !digits.chars().all(|c| c.is_digit(radix))
+  {
+    return None
+  }
+  let value = u128::from_str_radix(&digits, radix).ok()?.checked_mul(multiplier as u128)?;
+  Some(integer(negative, value))
+}
+
+/// Return the smallest of `U64`, `I64`, `U128` or `I128` containing the integer.
+fn integer(negative : bool, value : u128) -> Value
+{
+  match negative
+  {
+    false => match u64::try_from(value)
+    {
+      Ok(value) => Value::U64(value),
+      Err(_) => Value::U128(value),
+    },
+    true => match i64::try_from(value).map(|value| -value)
+    {
+      Ok(value) => Value::I64(value),
+      Err(_) => Value::I128(i128::try_from(value).map(|value| -value).unwrap_or(i128::MIN)),
+    },
+  }
+}
+
+impl Value
+{
+  /// Return this value as a number : numbers are returned unchanged, strings are parsed according to `options`.
+  /// Return `None` if the value is not a number or a string that can be parsed as one.
+  pub fn parse_numeric(&self, options : &NumericOptions) -> Option<Value>
+  {
+    if self.is_numeric()
+    {
+      return Some(self.clone())
+    }
+    match self
+    {
+      Value::String(string) => parse_numeric(string, options),
+      Value::Str(string) => parse_numeric(string, options),
+      _ => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::NumericOptions;
+  use crate::value::Value;
+
+  fn parse(string : &str) -> Option<Value>
+  {
+    Value::from(string.to_string()).parse_numeric(&NumericOptions::default())
+  }
+
+  #[test]
+  fn parse_numeric_strings()
+  {
+    assert!(parse("0x1A3F") == Some(Value::U64(0x1a3f)));
+    assert!(parse(" 1,024 ") == Some(Value::U64(1024)));
+    assert!(parse("-42") == Some(Value::I64(-42)));
+    assert!(parse("0b101") == Some(Value::U64(5)));
+    assert!(parse("4 KB") == Some(Value::U64(4096)));
+    assert!(parse("1.5MiB") == Some(Value::U64(1536 * 1024)));
+    assert!(parse("2.5") == Some(Value::F64(2.5)));
+    assert!(parse("340282366920938463463374607431768211455") == Some(Value::U128(u128::MAX)));
+    assert!(matches!(parse("12 B"), Some(Value::U64(12))));
+
+    assert!(parse("").is_none());
+    assert!(parse("1,").is_none());
+    assert!(parse("0x").is_none());
+    assert!(parse("12 apples").is_none());
+    assert!(parse("0xZZ").is_none());
+    assert!(Value::Bool(true).parse_numeric(&NumericOptions::default()).is_none());
+    assert!(Value::U8(3).parse_numeric(&NumericOptions::strict()) == Some(Value::U8(3)));
+
+    let strict = NumericOptions::strict();
+    assert!(Value::from("0x10").parse_numeric(&strict).is_none());
+    assert!(Value::from("4 KB").parse_numeric(&strict).is_none());
+    assert!(Value::from("1024").parse_numeric(&strict) == Some(Value::U64(1024)));
+    let si = NumericOptions{ unit_base : 1000, ..NumericOptions::default() };
+    assert!(Value::from("4kb").parse_numeric(&si) == Some(Value::U64(4000)));
+  }
+}
@@ -0,0 +1,69 @@
+//! Serde helper for the `Arc<dyn VFileBuilder>` fields of plugins arguments and results, used with `#[serde(with = "tap::value::passthrough")]`.
+//!
+//! Converted with [to_value](crate::value::to_value) and [from_value](crate::value::from_value) the builder is passed as is
+//! in a [Value::VFileBuilder](crate::value::Value::VFileBuilder), keeping its identity and its caches.
+//! Other formats like JSON serialize it with its type tag as usual.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::vfile::VFileBuilder;
+
+use serde::{Deserialize, Serializer, Deserializer};
+use serde::de::Visitor;
+
+/// Name of the newtype struct wrapping the builder, recognized by the [Value](crate::value::Value) serializer and deserializer.
+pub(crate) const TOKEN : &str = "$tap::private::VFileBuilder";
+
+thread_local!
+{
+  /// Builder passed between the helper and the [Value](crate::value::Value) serializer or deserializer.
+  static STASH : RefCell<Option<Arc<dyn VFileBuilder>>> = const { RefCell::new(None) };
+}
+
+/// Put `builder` in the stash, to be taken by the helper or by the [Value](crate::value::Value) serializer.
+pub(crate) fn stash(builder : Arc<dyn VFileBuilder>)
+{
+  STASH.with(|stash| *stash.borrow_mut() = Some(builder));
+}
+
+/// Take the builder in the stash.
+pub(crate) fn take() -> Option<Arc<dyn VFileBuilder>>
+{
+  STASH.with(|stash| stash.borrow_mut().take())
+}
+
+pub fn serialize<S : Serializer>(builder : &Arc<dyn VFileBuilder>, serializer : S) -> Result<S::Ok, S::Error>
+{
+  stash(builder.clone());
+  let result = serializer.serialize_newtype_struct(TOKEN, builder);
+  take();
+  result
+}
+
+pub fn deserialize<'de, D : Deserializer<'de>>(deserializer : D) -> Result<Arc<dyn VFileBuilder>, D::Error>
+{
+  struct BuilderVisitor;
+
+  impl<'de> Visitor<'de> for BuilderVisitor
+  {
+    type Value = Arc<dyn VFileBuilder>;
+
+    fn expecting(&self, formatter : &mut fmt::Formatter) -> fmt::Result
+    {
+      formatter.write_str("a VFileBuilder")
+    }
+
+    fn visit_newtype_struct<D : Deserializer<'de>>(self, deserializer : D) -> Result<Self::Value, D::Error>
+    {
+      match take()
+      {
+        Some(builder) => Ok(builder),
+        None => Arc::<dyn VFileBuilder>::deserialize(deserializer),
+      }
+    }
+  }
+
+  deserializer.deserialize_newtype_struct(TOKEN, BuilderVisitor)
+}
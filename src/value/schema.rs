@@ -0,0 +1,81 @@
+//! [JsonSchema] of [Value], so plugin results embedding values can expose a complete schema to frontends.
+
+use crate::value::Value;
+
+use schemars::JsonSchema;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{Schema, SchemaObject, InstanceType, Metadata, SubschemaValidation, ArrayValidation, ObjectValidation};
+
+/// Return a schema of [InstanceType] `instance_type`.
+fn instance(instance_type : InstanceType) -> Schema
+{
+  SchemaObject{ instance_type : Some(instance_type.into()), ..Default::default() }.into()
+}
+
+/**
+ * [Value] is serialized untagged, so its schema is any JSON value where arrays and objects contain [Value].
+ * Numbers serialize as `integer` or `number`, `DateTime`, `IpAddr` and `Uuid` as `string`, `Unit` and `None` as `null`,
+ * `Attributes`, `ReflectStruct` and `Map` as `object`, `Seq` and `Bytes` as `array`.
+ */
+impl JsonSchema for Value
+{
+  fn schema_name() -> String
+  {
+    "Value".into()
+  }
+
+  fn json_schema(gen : &mut SchemaGenerator) -> Schema
+  {
+    let array = SchemaObject{ instance_type : Some(InstanceType::Array.into()),
+                              array : Some(Box::new(ArrayValidation{ items : Some(gen.subschema_for::<Value>().into()), ..Default::default() })),
+                              ..Default::default() };
+    let object = SchemaObject{ instance_type : Some(InstanceType::Object.into()),
+                               object : Some(Box::new(ObjectValidation{ additional_properties : Some(Box::new(gen.subschema_for::<Value>())), ..Default::default() })),
+                               ..Default::default() };
+
+    SchemaObject{
+      metadata : Some(Box::new(Metadata{ description : Some("A TAP value".into()), ..Default::default() })),
+      subschemas : Some(Box::new(SubschemaValidation{ any_of : Some(vec![
+        instance(InstanceType::Null),
+        instance(InstanceType::Boolean),
+        instance(InstanceType::Integer),
+        instance(InstanceType::Number),
+        instance(InstanceType::String),
+        array.into(),
+        object.into(),
+      ]), ..Default::default() })),
+      ..Default::default()
+    }.into()
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use crate::value::Value;
+  use crate::attribute::{Attribute, Attributes};
+
+  use schemars::{JsonSchema, schema_for};
+  use serde::Serialize;
+
+  #[derive(Serialize, JsonSchema)]
+  struct Results
+  {
+    value : Value,
+    attribute : Attribute,
+    attributes : Attributes,
+  }
+
+  #[test]
+  fn value_schema()
+  {
+    let schema = serde_json::to_value(schema_for!(Results)).unwrap();
+
+    assert!(schema["properties"]["value"]["$ref"] == "#/definitions/Value");
+    assert!(schema["definitions"]["Value"]["anyOf"].as_array().unwrap().len() == 7);
+    assert!(schema["definitions"]["Value"]["anyOf"][5]["items"]["$ref"] == "#/definitions/Value");
+    assert!(schema["definitions"]["Attributes"]["additionalProperties"]["$ref"] == "#/definitions/Value");
+    assert!(schema["definitions"]["Attribute"]["properties"]["name"]["type"] == "string");
+    assert!(schema["definitions"]["Attribute"]["properties"].get("description").is_none());
+  }
+}
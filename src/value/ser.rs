@@ -0,0 +1,384 @@
+//! Conversion of any [Serialize] type to [Value] and [Attributes], so plugins can add their parsed structures
+//! to a node without implementing [ReflectStruct](crate::reflect::ReflectStruct).
+//!
+//! Structs and maps are converted to `Attributes` and `Map`, sequences and tuples to `Seq`,
+//! unit enum variants to their name and other enum variants to an [EnumVariant] with the variant content as payload.
+
+use std::sync::Arc;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::value::{Value, passthrough};
+use crate::attribute::Attributes;
+use crate::reflect::EnumVariant;
+use crate::error::RustructError;
+
+use serde::ser::{self, Serialize};
+
+impl ser::Error for RustructError
+{
+  fn custom<T : std::fmt::Display>(msg : T) -> Self
+  {
+    RustructError::Serialize(msg.to_string())
+  }
+}
+
+type Result<T> = std::result::Result<T, RustructError>;
+
+/// Convert `value` to a [Value].
+pub fn to_value<T : Serialize + ?Sized>(value : &T) -> anyhow::Result<Value>
+{
+  Ok(value.serialize(ValueSerializer)?)
+}
+
+/// Convert `value` to [Attributes], `value` must be serialized as a struct or a map.
+pub fn to_attributes<T : Serialize + ?Sized>(value : &T) -> anyhow::Result<Attributes>
+{
+  match to_value(value)?
+  {
+    Value::Attributes(attributes) => Ok(attributes),
+    Value::Map(map) =>
+    {
+      let mut attributes = Attributes::new();
+      let mut entries : Vec<(String, Value)> = map.into_iter().collect();
+      entries.sort_by(|a, b| a.0.cmp(&b.0));
+      for (name, value) in entries
+      {
+        attributes.add_attribute(name, value, None);
+      }
+      Ok(attributes)
+    },
+    value => Err(RustructError::Serialize(format!("{:?} is not a struct or a map", value.type_id())).into()),
+  }
+}
+
+impl Attributes
+{
+  /// Convert `value` to a [Value] and add it as attribute `name`.
+  pub fn add_serialize<S, T>(&mut self, name : S, value : &T) -> anyhow::Result<()>
+    where S : Into<Cow<'static, str>>,
+          T : Serialize + ?Sized
+  {
+    self.add_attribute(name, to_value(value)?, None);
+    Ok(())
+  }
+}
+
+/// Return the key of a map entry as a string.
+fn key_to_string(key : Value) -> Result<String>
+{
+  match key
+  {
+    Value::String(key) => Ok(key),
+    Value::Str(key) => Ok(key.into_owned()),
+    Value::Char(key) => Ok(key.to_string()),
+    Value::Bool(_) | Value::U8(_) | Value::U16(_) | Value::U32(_) | Value::U64(_) | Value::U128(_) | Value::USize(_) |
+    Value::I8(_) | Value::I16(_) | Value::I32(_) | Value::I64(_) | Value::I128(_) => Ok(key.to_string()),
+    key => Err(RustructError::Serialize(format!("map key of type {:?} is not supported", key.type_id()))),
+  }
+}
+
+/// Return an enum variant value.
+fn variant(name : &'static str, index : u32, variant : &'static str, payload : Value) -> Value
+{
+  Value::Enum(Arc::new(EnumVariant::new(name, variant, index as i64).with_payload(payload)))
+}
+
+/// [Serializer](ser::Serializer) returning a [Value].
+pub struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer
+{
+  type Ok = Value;
+  type Error = RustructError;
+
+  type SerializeSeq = SerializeSeq;
+  type SerializeTuple = SerializeSeq;
+  type SerializeTupleStruct = SerializeSeq;
+  type SerializeTupleVariant = SerializeSeq;
+  type SerializeMap = SerializeMap;
+  type SerializeStruct = SerializeStruct;
+  type SerializeStructVariant = SerializeStruct;
+
+  fn serialize_bool(self, v : bool) -> Result<Value> { Ok(Value::Bool(v)) }
+  fn serialize_i8(self, v : i8) -> Result<Value> { Ok(Value::I8(v)) }
+  fn serialize_i16(self, v : i16) -> Result<Value> { Ok(Value::I16(v)) }
+  fn serialize_i32(self, v : i32) -> Result<Value> { Ok(Value::I32(v)) }
+  fn serialize_i64(self, v : i64) -> Result<Value> { Ok(Value::I64(v)) }
+  fn serialize_i128(self, v : i128) -> Result<Value> { Ok(Value::I128(v)) }
+  fn serialize_u8(self, v : u8) -> Result<Value> { Ok(Value::U8(v)) }
+  fn serialize_u16(self, v : u16) -> Result<Value> { Ok(Value::U16(v)) }
+  fn serialize_u32(self, v : u32) -> Result<Value> { Ok(Value::U32(v)) }
+  fn serialize_u64(self, v : u64) -> Result<Value> { Ok(Value::U64(v)) }
+  fn serialize_u128(self, v : u128) -> Result<Value> { Ok(Value::U128(v)) }
+  fn serialize_f32(self, v : f32) -> Result<Value> { Ok(Value::F32(v)) }
+  fn serialize_f64(self, v : f64) -> Result<Value> { Ok(Value::F64(v)) }
+  fn serialize_char(self, v : char) -> Result<Value> { Ok(Value::Char(v)) }
+  fn serialize_str(self, v : &str) -> Result<Value> { Ok(Value::String(v.to_string())) }
+  fn serialize_bytes(self, v : &[u8]) -> Result<Value> { Ok(Value::Bytes(v.to_vec())) }
+  fn serialize_none(self) -> Result<Value> { Ok(Value::Option(None)) }
+  fn serialize_unit(self) -> Result<Value> { Ok(Value::Unit) }
+  fn serialize_unit_struct(self, _name : &'static str) -> Result<Value> { Ok(Value::Unit) }
+
+  fn serialize_some<T : Serialize + ?Sized>(self, value : &T) -> Result<Value>
+  {
+    Ok(Value::Option(Some(Box::new(value.serialize(ValueSerializer)?))))
+  }
+
+  fn serialize_unit_variant(self, _name : &'static str, _index : u32, variant : &'static str) -> Result<Value>
+  {
+    Ok(Value::Str(Cow::Borrowed(variant)))
+  }
+
+  fn serialize_newtype_struct<T : Serialize + ?Sized>(self, name : &'static str, value : &T) -> Result<Value>
+  {
+    //builders serialized with the passthrough helper are kept as is
+    if name == passthrough::TOKEN
+    {
+      if let Some(builder) = passthrough::take()
+      {
+        return Ok(Value::VFileBuilder(builder))
+      }
+    }
+    value.serialize(ValueSerializer)
+  }
+
+  fn serialize_newtype_variant<T : Serialize + ?Sized>(self, name : &'static str, index : u32, variant : &'static str, value : &T) -> Result<Value>
+  {
+    Ok(self::variant(name, index, variant, value.serialize(ValueSerializer)?))
+  }
+
+  fn serialize_seq(self, len : Option<usize>) -> Result<SerializeSeq>
+  {
+    Ok(SerializeSeq{ values : Vec::with_capacity(len.unwrap_or(0)), variant : None })
+  }
+
+  fn serialize_tuple(self, len : usize) -> Result<SerializeSeq>
+  {
+    self.serialize_seq(Some(len))
+  }
+
+  fn serialize_tuple_struct(self, _name : &'static str, len : usize) -> Result<SerializeSeq>
+  {
+    self.serialize_seq(Some(len))
+  }
+
+  fn serialize_tuple_variant(self, name : &'static str, index : u32, variant : &'static str, len : usize) -> Result<SerializeSeq>
+  {
+    Ok(SerializeSeq{ values : Vec::with_capacity(len), variant : Some((name, index, variant)) })
+  }
+
+  fn serialize_map(self, len : Option<usize>) -> Result<SerializeMap>
+  {
+    Ok(SerializeMap{ map : HashMap::with_capacity(len.unwrap_or(0)), key : None })
+  }
+
+  fn serialize_struct(self, _name : &'static str, _len : usize) -> Result<SerializeStruct>
+  {
+    Ok(SerializeStruct{ attributes : Attributes::new(), variant : None })
+  }
+
+  fn serialize_struct_variant(self, name : &'static str, index : u32, variant : &'static str, _len : usize) -> Result<SerializeStruct>
+  {
+    Ok(SerializeStruct{ attributes : Attributes::new(), variant : Some((name, index, variant)) })
+  }
+}
+
+/// Serialize sequences, tuples and tuple variants.
+pub struct SerializeSeq
+{
+  values : Vec<Value>,
+  variant : Option<(&'static str, u32, &'static str)>,
+}
+
+impl SerializeSeq
+{
+  fn push<T : Serialize + ?Sized>(&mut self, value : &T) -> Result<()>
+  {
+    self.values.push(value.serialize(ValueSerializer)?);
+    Ok(())
+  }
+
+  fn finish(self) -> Result<Value>
+  {
+    match self.variant
+    {
+      Some((name, index, variant)) => Ok(self::variant(name, index, variant, Value::Seq(self.values))),
+      None => Ok(Value::Seq(self.values)),
+    }
+  }
+}
+
+impl ser::SerializeSeq for SerializeSeq
+{
+  type Ok = Value;
+  type Error = RustructError;
+
+  fn serialize_element<T : Serialize + ?Sized>(&mut self, value : &T) -> Result<()> { self.push(value) }
+  fn end(self) -> Result<Value> { self.finish() }
+}
+
+impl ser::SerializeTuple for SerializeSeq
+{
+  type Ok = Value;
+  type Error = RustructError;
+
+  fn serialize_element<T : Serialize + ?Sized>(&mut self, value : &T) -> Result<()> { self.push(value) }
+  fn end(self) -> Result<Value> { self.finish() }
+}
+
+impl ser::SerializeTupleStruct for SerializeSeq
+{
+  type Ok = Value;
+  type Error = RustructError;
+
+  fn serialize_field<T : Serialize + ?Sized>(&mut self, value : &T) -> Result<()> { self.push(value) }
+  fn end(self) -> Result<Value> { self.finish() }
+}
+
+impl ser::SerializeTupleVariant for SerializeSeq
+{
+  type Ok = Value;
+  type Error = RustructError;
+
+  fn serialize_field<T : Serialize + ?Sized>(&mut self, value : &T) -> Result<()> { self.push(value) }
+  fn end(self) -> Result<Value> { self.finish() }
+}
+
+/// Serialize maps.
+pub struct SerializeMap
+{
+  map : HashMap<String, Value>,
+  key : Option<String>,
+}
+
+impl ser::SerializeMap for SerializeMap
+{
+  type Ok = Value;
+  type Error = RustructError;
+
+  fn serialize_key<T : Serialize + ?Sized>(&mut self, key : &T) -> Result<()>
+  {
+    self.key = Some(key_to_string(key.serialize(ValueSerializer)?)?);
+    Ok(())
+  }
+
+  fn serialize_value<T : Serialize + ?Sized>(&mut self, value : &T) -> Result<()>
+  {
+    let key = self.key.take().ok_or_else(|| RustructError::Serialize("map value without key".into()))?;
+    self.map.insert(key, value.serialize(ValueSerializer)?);
+    Ok(())
+  }
+
+  fn end(self) -> Result<Value>
+  {
+    Ok(Value::Map(self.map))
+  }
+}
+
+/// Serialize structs and struct variants.
+pub struct SerializeStruct
+{
+  attributes : Attributes,
+  variant : Option<(&'static str, u32, &'static str)>,
+}
+
+impl SerializeStruct
+{
+  fn field<T : Serialize + ?Sized>(&mut self, name : &'static str, value : &T) -> Result<()>
+  {
+    self.attributes.add_attribute(name, value.serialize(ValueSerializer)?, None);
+    Ok(())
+  }
+
+  fn finish(self) -> Result<Value>
+  {
+    match self.variant
+    {
+      Some((name, index, variant)) => Ok(self::variant(name, index, variant, Value::Attributes(self.attributes))),
+      None => Ok(Value::Attributes(self.attributes)),
+    }
+  }
+}
+
+impl ser::SerializeStruct for SerializeStruct
+{
+  type Ok = Value;
+  type Error = RustructError;
+
+  fn serialize_field<T : Serialize + ?Sized>(&mut self, name : &'static str, value : &T) -> Result<()> { self.field(name, value) }
+  fn end(self) -> Result<Value> { self.finish() }
+}
+
+impl ser::SerializeStructVariant for SerializeStruct
+{
+  type Ok = Value;
+  type Error = RustructError;
+
+  fn serialize_field<T : Serialize + ?Sized>(&mut self, name : &'static str, value : &T) -> Result<()> { self.field(name, value) }
+  fn end(self) -> Result<Value> { self.finish() }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{to_value, to_attributes};
+  use crate::value::Value;
+  use crate::attribute::Attributes;
+
+  use std::collections::BTreeMap;
+  use serde::Serialize;
+
+  #[derive(Serialize)]
+  enum Kind
+  {
+    Boot,
+    Data(u32),
+    Extended{ start : u64, count : u8 },
+  }
+
+  #[derive(Serialize)]
+  struct Entry
+  {
+    name : String,
+    kind : Kind,
+    flags : (u8, bool),
+    size : Option<u64>,
+  }
+
+  #[derive(Serialize)]
+  struct Table
+  {
+    signature : [u8; 2],
+    entries : Vec<Entry>,
+    labels : BTreeMap<u32, String>,
+  }
+
+  #[test]
+  fn serialize_to_attributes()
+  {
+    let table = Table{ signature : [0x55, 0xaa],
+                       entries : vec![Entry{ name : "boot".into(), kind : Kind::Boot, flags : (0x80, true), size : None },
+                                      Entry{ name : "data".into(), kind : Kind::Data(7), flags : (0, false), size : Some(512) },
+                                      Entry{ name : "ext".into(), kind : Kind::Extended{ start : 63, count : 2 }, flags : (0, false), size : None }],
+                       labels : [(1, "system".to_string())].into_iter().collect() };
+
+    let mut attributes = Attributes::new();
+    attributes.add_serialize("table", &table).unwrap();
+    let value = Value::Attributes(attributes);
+    assert!(value.get_path("table/signature/1").unwrap().as_u8() == 0xaa);
+    assert!(value.get_path("table/entries/0/name").unwrap().as_string() == "boot");
+    assert!(value.get_path("table/entries/0/kind").unwrap().as_string() == "Boot");
+    assert!(value.get_path("table/entries/1/size").unwrap() == Value::Option(Some(Box::new(Value::U64(512)))));
+    assert!(value.get_path("table/labels/1").unwrap().as_string() == "system");
+
+    let kind = value.get_path("table/entries/2/kind").unwrap().as_enum();
+    assert!(kind.variant() == "Extended" && kind.discriminant() == 2);
+    assert!(kind.payload().unwrap().get_path("start").unwrap().as_u64() == 63);
+    assert!(to_value(&Kind::Data(7)).unwrap().as_enum().payload() == Some(Value::U32(7)));
+
+    assert!(to_attributes(&table).unwrap().count() == 3);
+    assert!(to_attributes(&table.labels).unwrap().get_value("1").unwrap().as_string() == "system");
+    assert!(to_attributes(&42u32).is_err());
+  }
+}
@@ -0,0 +1,348 @@
+//! Tagged (self-describing) serialization of [Value].
+//!
+//! The default serialization of [Value] is untagged, a `U8` and an `U64` are both serialized as a number,
+//! so the exact variant is lost and many variants can't be deserialized.
+//! [TaggedValue] keep the variant name with each value so values survive save/load and RPC transport exactly.
+
+use std::sync::Arc;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use crate::value::Value;
+use crate::vfile::VFileBuilder;
+use crate::attribute::Attributes;
+use crate::reflect::{EnumVariant, registry};
+use crate::tree::{TreeNodeId, AttributePath};
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc, Duration};
+use uuid::Uuid;
+
+/**
+ * Externally tagged mirror of [Value], each value is serialized as `{"Variant" : value}`.
+ * `Func` and `FuncArg` are evaluated and serialized as their result, `Method` is serialized as its [signature](crate::value::Method::signature),
+ * `ReflectStruct` is serialized with its name and deserialized with its [registered](crate::reflect::registry) factory or as a [ReflectBag](crate::reflect::ReflectBag),
+ * or as [Attributes] otherwise.
+ */
+#[derive(Serialize, Deserialize)]
+pub enum TaggedValue
+{
+  Attributes(Vec<(String, TaggedValue)>),
+  ReflectStruct{ name : String, fields : Vec<(String, TaggedValue)> },
+  VFileBuilder(Arc<dyn VFileBuilder>),
+  Bool(bool),
+
+  U8(u8),
+  U16(u16),
+  U32(u32),
+  U64(u64),
+
+  I8(i8),
+  I16(i16),
+  I32(i32),
+  I64(i64),
+
+  U128(u128),
+  I128(i128),
+
+  F32(f32),
+  F64(f64),
+
+  USize(usize),
+
+  Char(char),
+  String(String),
+  Str(String),
+
+  Unit,
+  Option(Option<Box<TaggedValue>>),
+  Newtype(Box<TaggedValue>),
+  Seq(Vec<TaggedValue>),
+  Bytes(Vec<u8>),
+  Blob(Arc<dyn VFileBuilder>),
+  DateTime(DateTime<Utc>),
+  Duration{ seconds : i64, nanoseconds : i32 },
+  IpAddr(IpAddr),
+  Uuid(Uuid),
+
+  Map(HashMap<String, TaggedValue>),
+
+  NodeId(TreeNodeId),
+  AttributePath(AttributePath),
+  Enum{ name : String, variant : String, discriminant : i64, payload : Option<Box<TaggedValue>> },
+}
+
+impl From<&Value> for TaggedValue
+{
+  fn from(value : &Value) -> Self
+  {
+    match value
+    {
+      Value::Attributes(attributes) => TaggedValue::Attributes(attributes.attributes().iter()
+                                         .map(|attribute| (attribute.name().to_string(), attribute.value().into())).collect()),
+      Value::ReflectStruct(reflect) => TaggedValue::ReflectStruct{ name : reflect.name().to_string(), fields : reflect.attributes().iter()
+                                         .map(|attribute| (attribute.name().to_string(), attribute.value().into())).collect() },
+      Value::VFileBuilder(builder) => TaggedValue::VFileBuilder(builder.clone()),
+      Value::Bool(val) => TaggedValue::Bool(*val),
+      Value::U8(val) => TaggedValue::U8(*val),
+      Value::U16(val) => TaggedValue::U16(*val),
+      Value::U32(val) => TaggedValue::U32(*val),
+      Value::U64(val) => TaggedValue::U64(*val),
+      Value::I8(val) => TaggedValue::I8(*val),
+      Value::I16(val) => TaggedValue::I16(*val),
+      Value::I32(val) => TaggedValue::I32(*val),
+      Value::I64(val) => TaggedValue::I64(*val),
+      Value::U128(val) => TaggedValue::U128(*val),
+      Value::I128(val) => TaggedValue::I128(*val),
+      Value::F32(val) => TaggedValue::F32(*val),
+      Value::F64(val) => TaggedValue::F64(*val),
+      Value::USize(val) => TaggedValue::USize(*val),
+      Value::Char(val) => TaggedValue::Char(*val),
+      Value::String(val) => TaggedValue::String(val.clone()),
+      Value::Str(val) => TaggedValue::Str(val.to_string()),
+      Value::Unit => TaggedValue::Unit,
+      Value::Option(val) => TaggedValue::Option(val.as_ref().map(|val| Box::new(val.as_ref().into()))),
+      Value::Newtype(val) => TaggedValue::Newtype(Box::new(val.as_ref().into())),
+      Value::Seq(val) => TaggedValue::Seq(val.iter().map(|val| val.into()).collect()),
+      Value::Bytes(val) => TaggedValue::Bytes(val.clone()),
+      Value::Blob(val) => TaggedValue::Blob(val.clone()),
+      Value::DateTime(val) => TaggedValue::DateTime(*val),
+      Value::Duration(val) => TaggedValue::Duration{ seconds : val.num_seconds(), nanoseconds : val.subsec_nanos() },
+      Value::IpAddr(val) => TaggedValue::IpAddr(*val),
+      Value::Uuid(val) => TaggedValue::Uuid(*val),
+      Value::Map(val) => TaggedValue::Map(val.iter().map(|(key, val)| (key.clone(), val.into())).collect()),
+      Value::Func(func) => (&func()).into(),
+      Value::FuncArg(func, arg) => (&func(Value::Newtype(arg.clone()))).into(),
+      Value::Method(method) => (&method.signature()).into(),
+      Value::NodeId(val) => TaggedValue::NodeId(*val),
+      Value::AttributePath(val) => TaggedValue::AttributePath(val.clone()),
+      Value::Enum(val) => TaggedValue::Enum{ name : val.name().to_string(), variant : val.variant().to_string(), discriminant : val.discriminant(),
+                                             payload : val.payload().map(|payload| Box::new((&payload).into())) },
+    }
+  }
+}
+
+impl From<TaggedValue> for Value
+{
+  fn from(value : TaggedValue) -> Self
+  {
+    match value
+    {
+      TaggedValue::Attributes(tagged) =>
+      {
+        let mut attributes = Attributes::new();
+        for (name, value) in tagged
+        {
+          attributes.add_attribute(name, Value::from(value), None);
+        }
+        Value::Attributes(attributes)
+      },
+      TaggedValue::ReflectStruct{ name, fields } =>
+      {
+        let fields : Vec<(String, Value)> = fields.into_iter().map(|(name, value)| (name, value.into())).collect();
+        match registry::rehydrate(&name, fields.clone())
+        {
+          Some(reflect) => Value::ReflectStruct(reflect),
+          None => 
+          {
+            let mut attributes = Attributes::new();
+            for (name, value) in fields
+            {
+              attributes.add_attribute(name, value, None);
+            }
+            Value::Attributes(attributes)
+          },
+        }
+      },
+      TaggedValue::VFileBuilder(builder) => Value::VFileBuilder(builder),
+      TaggedValue::Bool(val) => Value::Bool(val),
+      TaggedValue::U8(val) => Value::U8(val),
+      TaggedValue::U16(val) => Value::U16(val),
+      TaggedValue::U32(val) => Value::U32(val),
+      TaggedValue::U64(val) => Value::U64(val),
+      TaggedValue::I8(val) => Value::I8(val),
+      TaggedValue::I16(val) => Value::I16(val),
+      TaggedValue::I32(val) => Value::I32(val),
+      TaggedValue::I64(val) => Value::I64(val),
+      TaggedValue::U128(val) => Value::U128(val),
+      TaggedValue::I128(val) => Value::I128(val),
+      TaggedValue::F32(val) => Value::F32(val),
+      TaggedValue::F64(val) => Value::F64(val),
+      TaggedValue::USize(val) => Value::USize(val),
+      TaggedValue::Char(val) => Value::Char(val),
+      TaggedValue::String(val) => Value::String(val),
+      TaggedValue::Str(val) => Value::Str(Cow::Owned(val)),
+      TaggedValue::Unit => Value::Unit,
+      TaggedValue::Option(val) => Value::Option(val.map(|val| Box::new((*val).into()))),
+      TaggedValue::Newtype(val) => Value::Newtype(Box::new((*val).into())),
+      TaggedValue::Seq(val) => Value::Seq(val.into_iter().map(|val| val.into()).collect()),
+      TaggedValue::Bytes(val) => Value::Bytes(val),
+      TaggedValue::Blob(val) => Value::Blob(val),
+      TaggedValue::DateTime(val) => Value::DateTime(val),
+      TaggedValue::Duration{ seconds, nanoseconds } => Value::Duration(Duration::seconds(seconds) + Duration::nanoseconds(nanoseconds as i64)),
+      TaggedValue::IpAddr(val) => Value::IpAddr(val),
+      TaggedValue::Uuid(val) => Value::Uuid(val),
+      TaggedValue::Map(val) => Value::Map(val.into_iter().map(|(key, val)| (key, val.into())).collect()),
+      TaggedValue::NodeId(val) => Value::NodeId(val),
+      TaggedValue::AttributePath(val) => Value::AttributePath(val),
+      TaggedValue::Enum{ name, variant, discriminant, payload } =>
+      {
+        let variant = EnumVariant::new(name, variant, discriminant);
+        Value::Enum(Arc::new(match payload
+        {
+          Some(payload) => variant.with_payload((*payload).into()),
+          None => variant,
+        }))
+      },
+    }
+  }
+}
+
+impl Value
+{
+  /// Serialize this [Value] to a tagged JSON string, keeping the exact type of each value.
+  pub fn to_tagged_json(&self) -> Result<String>
+  {
+    Ok(serde_json::to_string(&TaggedValue::from(self))?)
+  }
+
+  /// Deserialize a [Value] from a tagged JSON string created by [Value::to_tagged_json].
+  pub fn from_tagged_json(json : &str) -> Result<Value>
+  {
+    let tagged : TaggedValue = serde_json::from_str(json)?;
+    Ok(tagged.into())
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use crate::value::Value;
+  use crate::attribute::Attributes;
+  use crate::reflect::{ReflectStruct, FieldInfo, EnumVariant, registry};
+  use chrono::Duration;
+  use std::sync::Arc;
+
+  #[test]
+  fn tagged_round_trip()
+  {
+    let mut attributes = Attributes::new();
+    attributes.add_attribute("size", Value::USize(12), None);
+
+    let values = vec![
+      Value::U8(1),
+      Value::U64(1),
+      Value::I16(-1),
+      Value::U128(u128::MAX),
+      Value::I128(i128::MIN),
+      Value::F32(0.5),
+      Value::from("str"),
+      Value::String("string".into()),
+      Value::Option(Some(Box::new(Value::Char('c')))),
+      Value::Seq(vec![Value::U16(2), Value::Bytes(vec![0, 1])]),
+      Value::Duration(Duration::milliseconds(-1500)),
+      Value::Uuid(uuid::Uuid::new_v4()),
+      Value::Attributes(attributes),
+      Value::Enum(Arc::new(EnumVariant::new("RecordType", "File", 2).with_payload(Value::U8(1)))),
+    ];
+
+    for value in values
+    {
+      let json = value.to_tagged_json().unwrap();
+      let loaded = Value::from_tagged_json(&json).unwrap();
+      assert!(loaded.type_id() == value.type_id());
+      assert!(loaded == value);
+    }
+  }
+
+  #[derive(Debug)]
+  struct Header
+  {
+    magic : u32,
+  }
+
+  impl ReflectStruct for Header
+  {
+    fn name(&self) -> &'static str
+    {
+      "TaggedHeader"
+    }
+
+    fn field_infos(&self) -> Vec<FieldInfo>
+    {
+      vec![FieldInfo::field("magic", Some("header magic"))]
+    }
+
+    fn get_value(&self, name : &str) -> Option<Value>
+    {
+      match name
+      {
+        "magic" => Some(Value::U32(self.magic)),
+        _ => None,
+      }
+    }
+  }
+
+  #[test]
+  fn tagged_reflect_struct()
+  {
+    let value = Value::ReflectStruct(Arc::new(Header{ magic : 0x1234 }));
+    let json = value.to_tagged_json().unwrap();
+
+    let loaded = Value::from_tagged_json(&json).unwrap();
+    assert!(loaded.as_attributes().get_value("magic").unwrap().as_u32() == 0x1234);
+
+    registry::register(&Header{ magic : 0 });
+    let loaded = Value::from_tagged_json(&json).unwrap();
+    let reflect = loaded.as_reflect_struct();
+    assert!(reflect.name() == "TaggedHeader");
+    assert!(reflect.descriptions() == vec![Some("header magic")]);
+    assert!(reflect.get_value("magic").unwrap().as_u32() == 0x1234);
+  }
+
+  #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+  struct Record
+  {
+    id : u64,
+    name : String,
+  }
+
+  impl ReflectStruct for Record
+  {
+    fn name(&self) -> &'static str
+    {
+      "TaggedRecord"
+    }
+
+    fn field_infos(&self) -> Vec<FieldInfo>
+    {
+      vec![FieldInfo::field("id", None), FieldInfo::field("name", None)]
+    }
+
+    fn get_value(&self, name : &str) -> Option<Value>
+    {
+      match name
+      {
+        "id" => Some(Value::U64(self.id)),
+        "name" => Some(Value::from(self.name.clone())),
+        _ => None,
+      }
+    }
+  }
+
+  #[test]
+  fn tagged_reflect_factory()
+  {
+    registry::register_deserializable(&Record{ id : 0, name : String::new() });
+    assert!(registry::schema_of("TaggedRecord").unwrap().schema.object.unwrap().properties.contains_key("name"));
+
+    let value = Value::ReflectStruct(Arc::new(Record{ id : 7, name : "mft".into() }));
+    let loaded = Value::from_tagged_json(&value.to_tagged_json().unwrap()).unwrap();
+    let reflect = loaded.as_reflect_struct();
+    let record = reflect.downcast_ref::<Record>().unwrap();
+    assert!(record.id == 7 && record.name == "mft");
+    assert!(reflect.downcast_ref::<Header>().is_none());
+  }
+}
@@ -0,0 +1,330 @@
+//! Recursive traversal of [Value].
+//!
+//! A [Visitor] is called for a value and all the values it contains (`Seq`, `Map`, `Attributes`, `ReflectStruct`, ...),
+//! with the path of each value relative to the root. Values are visited by reference when possible,
+//! only `ReflectStruct` fields and evaluated functions are created during the traversal.
+//! [VisitOptions] let callers limit the depth and the number of visited values.
+//! [Value::map] rewrite a value and the values it contains, for example to redact credentials before export.
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::value::Value;
+use crate::attribute::Attributes;
+use crate::reflect::EnumVariant;
+
+/// Key of a value inside its parent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSegment
+{
+  /// Index in a `Seq`.
+  Index(usize),
+  /// Key of a `Map`, name of an attribute or of a `ReflectStruct` field.
+  Key(String),
+}
+
+impl fmt::Display for PathSegment
+{
+  fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result
+  {
+    match self
+    {
+      PathSegment::Index(index) => write!(f, "{}", index),
+      PathSegment::Key(key) => write!(f, "{}", key.replace('~', "~0").replace('/', "~1")),
+    }
+  }
+}
+
+/// Return `path` as a `/` separated string that can be passed to [Value::get_path].
+pub fn path_to_string(path : &[PathSegment]) -> String
+{
+  path.iter().map(|segment| segment.to_string()).collect::<Vec<String>>().join("/")
+}
+
+/// Returned by a [Visitor] to control the traversal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VisitControl
+{
+  /// Visit the values contained in this value.
+  Continue,
+  /// Don't visit the values contained in this value.
+  SkipChildren,
+  /// Stop the traversal.
+  Stop,
+}
+
+/// Trait called for each value during a traversal.
+pub trait Visitor
+{
+  /// Visit `value` found at `path` from the root value.
+  fn visit(&mut self, path : &[PathSegment], value : &Value) -> VisitControl;
+}
+
+impl<F : FnMut(&[PathSegment], &Value) -> VisitControl> Visitor for F
+{
+  fn visit(&mut self, path : &[PathSegment], value : &Value) -> VisitControl
+  {
+    self(path, value)
+  }
+}
+
+/// Guards applied during a traversal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VisitOptions
+{
+  /// Values deeper than `max_depth` are not visited, the root is at depth 0.
+  pub max_depth : usize,
+  /// Maximum number of values visited.
+  pub max_values : usize,
+  /// Evaluate `Func` and `FuncArg` and visit their result, functions can be expensive so it's disabled by default.
+  pub evaluate_functions : bool,
+}
+
+impl Default for VisitOptions
+{
+  fn default() -> Self
+  {
+    VisitOptions{ max_depth : 64, max_values : usize::MAX, evaluate_functions : false }
+  }
+}
+
+/// Result of a traversal.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VisitStats
+{
+  /// Number of values visited.
+  pub visited : usize,
+  /// True if some values were not visited because of the [VisitOptions] guards.
+  pub truncated : bool,
+  /// True if the [Visitor] stopped the traversal.
+  pub stopped : bool,
+}
+
+struct Walker<'a, V : Visitor + ?Sized>
+{
+  visitor : &'a mut V,
+  options : VisitOptions,
+  path : Vec<PathSegment>,
+  stats : VisitStats,
+}
+
+impl<V : Visitor + ?Sized> Walker<'_, V>
+{
+  /// Return false if the traversal must stop.
+  fn walk(&mut self, value : &Value) -> bool
+  {
+    if self.stats.visited >= self.options.max_values
+    {
+      self.stats.truncated = true;
+      return false
+    }
+    self.stats.visited += 1;
+
+    match self.visitor.visit(&self.path, value)
+    {
+      VisitControl::Continue => (),
+      VisitControl::SkipChildren => return true,
+      VisitControl::Stop => { self.stats.stopped = true; return false },
+    }
+
+    if self.path.len() >= self.options.max_depth
+    {
+      if value.has_children()
+      {
+        self.stats.truncated = true;
+      }
+      return true
+    }
+
+    match value
+    {
+      Value::Seq(values) => values.iter().enumerate().all(|(index, value)| self.child(PathSegment::Index(index), value)),
+      Value::Map(values) => values.iter().all(|(key, value)| self.child(PathSegment::Key(key.clone()), value)),
+      Value::Attributes(attributes) => attributes.attributes().iter()
+                                         .all(|attribute| self.child(PathSegment::Key(attribute.name().to_string()), attribute.value())),
+      Value::ReflectStruct(reflect) => reflect.field_infos().iter()
+                                         .filter_map(|info| Some((info.name, reflect.get_value(info.name)?)))
+                                         .all(|(name, value)| self.child(PathSegment::Key(name.to_string()), &value)),
+      Value::Option(Some(value)) | Value::Newtype(value) => self.child(PathSegment::Index(0), value),
+      Value::Enum(reflect) => match reflect.payload()
+      {
+        Some(payload) => self.child(PathSegment::Index(0), &payload),
+        None => true,
+      },
+      Value::Func(func) if self.options.evaluate_functions => self.child(PathSegment::Index(0), &func()),
+      Value::FuncArg(func, arg) if self.options.evaluate_functions => self.child(PathSegment::Index(0), &func(Value::Newtype(arg.clone()))),
+      _ => true,
+    }
+  }
+
+  fn child(&mut self, segment : PathSegment, value : &Value) -> bool
+  {
+    self.path.push(segment);
+    let next = self.walk(value);
+    self.path.pop();
+    next
+  }
+}
+
+/// Visit `value` and all the values it contains with `visitor`, applying `options` guards.
+pub fn walk<V : Visitor + ?Sized>(value : &Value, visitor : &mut V, options : &VisitOptions) -> VisitStats
+{
+  let mut walker = Walker{ visitor, options : *options, path : Vec::new(), stats : VisitStats::default() };
+  walker.walk(value);
+  walker.stats
+}
+
+impl Value
+{
+  /// Visit this value and all the values it contains with `visitor`, using the default [VisitOptions].
+  pub fn walk<V : Visitor + ?Sized>(&self, visitor : &mut V) -> VisitStats
+  {
+    walk(self, visitor, &VisitOptions::default())
+  }
+
+  /// Return a copy of this value where each value for which `f` return `Some` is replaced by the returned value.
+  /// Values are passed to `f` before the values they contain, replaced values are not traversed.
+  /// Containers with no replaced values are shared with the original value, `ReflectStruct` with replaced fields
+  /// are converted to `Attributes`, `Func` and `FuncArg` are not evaluated.
+  pub fn map<F : FnMut(&Value) -> Option<Value>>(&self, f : &mut F) -> Value
+  {
+    map_value(self, f).unwrap_or_else(|| self.clone())
+  }
+
+  /// Return true if this value is a container that can contain other values.
+  fn has_children(&self) -> bool
+  {
+    matches!(self, Value::Seq(_) | Value::Map(_) | Value::Attributes(_) | Value::ReflectStruct(_) | Value::Enum(_) |
+                   Value::Option(Some(_)) | Value::Newtype(_) | Value::Func(_) | Value::FuncArg(_, _))
+  }
+}
+
+/// Return the mapped value or `None` if neither `value` nor the values it contains were replaced.
+fn map_value<F : FnMut(&Value) -> Option<Value>>(value : &Value, f : &mut F) -> Option<Value>
+{
+  if let Some(value) = f(value)
+  {
+    return Some(value)
+  }
+
+  match value
+  {
+    Value::Seq(values) => map_values(values.iter(), f).map(Value::Seq),
+    Value::Map(values) => map_values(values.values(), f).map(|mapped| Value::Map(values.keys().cloned().zip(mapped).collect())),
+    Value::Attributes(attributes) =>
+    {
+      let attributes = attributes.attributes();
+      let mapped = map_values(attributes.iter().map(|attribute| attribute.value()), f)?;
+      let mut result = Attributes::new();
+      for (attribute, value) in attributes.iter().zip(mapped)
+      {
+        result.add_attribute(attribute.name().to_string(), value, attribute.description().map(|description| description.to_string()));
+      }
+      Some(Value::Attributes(result))
+    },
+    Value::ReflectStruct(reflect) =>
+    {
+      let fields : Vec<(&'static str, Value)> = reflect.field_infos().iter().filter_map(|info| Some((info.name, reflect.get_value(info.name)?))).collect();
+      let mapped = map_values(fields.iter().map(|(_, value)| value), f)?;
+      let mut result = Attributes::new();
+      for ((name, _), value) in fields.iter().zip(mapped)
+      {
+        result.add_attribute(*name, value, None);
+      }
+      Some(Value::Attributes(result))
+    },
+    Value::Option(Some(value)) => map_value(value, f).map(|value| Value::Option(Some(Box::new(value)))),
+    Value::Newtype(value) => map_value(value, f).map(|value| Value::Newtype(Box::new(value))),
+    Value::Enum(reflect) =>
+    {
+      let payload = map_value(&reflect.payload()?, f)?;
+      Some(Value::Enum(Arc::new(EnumVariant::new(reflect.name(), reflect.variant(), reflect.discriminant()).with_payload(payload))))
+    },
+    _ => None,
+  }
+}
+
+/// Map each value of `values`, return `None` if no value was replaced.
+fn map_values<'a, I, F>(values : I, f : &mut F) -> Option<Vec<Value>>
+  where I : Iterator<Item = &'a Value>,
+        F : FnMut(&Value) -> Option<Value>
+{
+  let values : Vec<(&Value, Option<Value>)> = values.map(|value| (value, map_value(value, f))).collect();
+  if values.iter().all(|(_, mapped)| mapped.is_none())
+  {
+    return None
+  }
+  Some(values.into_iter().map(|(value, mapped)| mapped.unwrap_or_else(|| value.clone())).collect())
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{walk, path_to_string, PathSegment, VisitControl, VisitOptions};
+  use crate::value::Value;
+  use crate::attribute::Attributes;
+
+  fn value() -> Value
+  {
+    let mut entry = Attributes::new();
+    entry.add_attribute("name", Value::from("file.txt"), None);
+    entry.add_attribute("size", Value::U64(10), None);
+    Value::Seq(vec![Value::U8(1), Value::Attributes(entry), Value::Seq(vec![Value::U8(2)])])
+  }
+
+  #[test]
+  fn walk_values()
+  {
+    let value = value();
+    let mut paths = Vec::new();
+    let stats = value.walk(&mut |path : &[PathSegment], _value : &Value| { paths.push(path_to_string(path)); VisitControl::Continue });
+    assert!(stats.visited == 7 && !stats.truncated && !stats.stopped);
+    assert!(paths == vec!["", "0", "1", "1/name", "1/size", "2", "2/0"]);
+    assert!(value.get_path(&paths[3]).unwrap().as_string() == "file.txt");
+
+    let mut total = 0;
+    value.walk(&mut |_path : &[PathSegment], value : &Value| { total += value.to_u64().unwrap_or(0); VisitControl::Continue });
+    assert!(total == 13);
+
+    let stats = value.walk(&mut |path : &[PathSegment], _value : &Value| if path.len() == 1 { VisitControl::SkipChildren } else { VisitControl::Continue });
+    assert!(stats.visited == 4);
+
+    let stats = value.walk(&mut |path : &[PathSegment], _value : &Value| if path.len() == 2 { VisitControl::Stop } else { VisitControl::Continue });
+    assert!(stats.visited == 4 && stats.stopped);
+
+    let options = VisitOptions{ max_depth : 1, ..Default::default() };
+    let stats = walk(&value, &mut |_path : &[PathSegment], _value : &Value| VisitControl::Continue, &options);
+    assert!(stats.visited == 4 && stats.truncated);
+
+    let options = VisitOptions{ max_values : 2, ..Default::default() };
+    let stats = walk(&value, &mut |_path : &[PathSegment], _value : &Value| VisitControl::Continue, &options);
+    assert!(stats.visited == 2 && stats.truncated);
+  }
+
+  #[test]
+  fn redact_values()
+  {
+    let mut credentials = Attributes::new();
+    credentials.add_attribute("user", Value::from("admin"), None);
+    credentials.add_attribute("password", Value::from("secret"), None);
+    let value = Value::Seq(vec![Value::Attributes(credentials), Value::U8(1)]);
+
+    let redacted = value.map(&mut |value : &Value| match value
+    {
+      Value::Attributes(attributes) if attributes.get_value("password").is_some() =>
+      {
+        let mut attributes = Attributes::new();
+        attributes.add_attribute("password", Value::from("***"), None);
+        Some(Value::Attributes(attributes))
+      },
+      _ => None,
+    });
+    assert!(redacted.get_path("0/password").unwrap().as_string() == "***");
+    assert!(value.get_path("0/password").unwrap().as_string() == "secret");
+
+    let doubled = value.map(&mut |value : &Value| value.to_u64().map(|number| Value::U64(number * 2)));
+    assert!(doubled.get_path("1").unwrap().to_u64() == Some(2));
+    assert!(doubled.get_path("0/user").unwrap().as_string() == "admin");
+    assert!(value.map(&mut |_value : &Value| None) == value);
+  }
+}
@@ -0,0 +1,194 @@
+//! Versions of the plugins and the requirements used to select them in the [PluginsDB](crate::plugins_db::PluginsDB).
+//!
+//! Versions follow semver `major.minor.patch`, pre-release and build suffixes are ignored.
+//! A [VersionReq] is a comma separated list of comparators that must all match, like `>=1.2, <2` or `^0.3`,
+//! a version without operator is a caret requirement as in Cargo.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::RustructError;
+
+use anyhow::Result;
+
+/// A semver version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Version
+{
+  pub major : u64,
+  pub minor : u64,
+  pub patch : u64,
+}
+
+impl Version
+{
+  pub fn new(major : u64, minor : u64, patch : u64) -> Self
+  {
+    Version{ major, minor, patch }
+  }
+
+  /// Parse `version`, missing minor and patch numbers are 0.
+  pub fn parse(version : &str) -> Result<Self>
+  {
+    Ok(Version::parse_partial(version)?.0)
+  }
+
+  /// Parse `version` and return the number of its components.
+  fn parse_partial(version : &str) -> Result<(Self, usize)>
+  {
+    let invalid = || RustructError::InvalidVersion(version.to_string());
+    let numbers = version.trim().split(['-', '+']).next().unwrap_or("");
+    let numbers = numbers.split('.').map(|number| number.parse::<u64>()).collect::<Result<Vec<u64>, _>>().map_err(|_| invalid())?;
+    if numbers.is_empty() || numbers.len() > 3
+    {
+      return Err(invalid().into())
+    }
+    let number = |index : usize| numbers.get(index).copied().unwrap_or(0);
+    Ok((Version::new(number(0), number(1), number(2)), numbers.len()))
+  }
+}
+
+impl FromStr for Version
+{
+  type Err = anyhow::Error;
+
+  fn from_str(version : &str) -> Result<Self>
+  {
+    Version::parse(version)
+  }
+}
+
+impl fmt::Display for Version
+{
+  fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result
+  {
+    write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op
+{
+  Exact,
+  Greater,
+  GreaterEq,
+  Less,
+  LessEq,
+  Tilde,
+  Caret,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Comparator
+{
+  op : Op,
+  version : Version,
+  /// Number of components written in the requirement, `^1` allow any `1.x`.
+  parts : usize,
+}
+
+impl Comparator
+{
+  fn matches(&self, version : &Version) -> bool
+  {
+    let v = self.version;
+    match self.op
+    {
+      Op::Exact => match self.parts
+      {
+        1 => version.major == v.major,
+        2 => (version.major, version.minor) == (v.major, v.minor),
+        _ => *version == v,
+      },
+      Op::Greater => *version > v,
+      Op::GreaterEq => *version >= v,
+      Op::Less => *version < v,
+      Op::LessEq => *version <= v,
+      Op::Tilde => *version >= v && version.major == v.major && (self.parts == 1 || version.minor == v.minor),
+      Op::Caret => *version >= v && match (v.major, v.minor, self.parts)
+      {
+        (0, _, 1) => version.major == 0,
+        (0, 0, 2) => (version.major, version.minor) == (0, 0),
+        (0, 0, _) => *version == v,
+        (0, minor, _) => version.major == 0 && version.minor == minor,
+        (major, _, _) => version.major == major,
+      },
+    }
+  }
+}
+
+/// A requirement on a [Version], `*` or an empty requirement match any version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq
+{
+  requirement : String,
+  comparators : Vec<Comparator>,
+}
+
+impl VersionReq
+{
+  /// Return a requirement matching any version.
+  pub fn any() -> Self
+  {
+    VersionReq{ requirement : "*".into(), comparators : Vec::new() }
+  }
+
+  pub fn parse(requirement : &str) -> Result<Self>
+  {
+    let mut comparators = Vec::new();
+    for comparator in requirement.split(',').map(str::trim).filter(|comparator| !comparator.is_empty() && *comparator != "*")
+    {
+      let (op, version) = [(">=", Op::GreaterEq), ("<=", Op::LessEq), (">", Op::Greater), ("<", Op::Less), ("=", Op::Exact), ("~", Op::Tilde), ("^", Op::Caret)]
+        .iter().find_map(|(prefix, op)| comparator.strip_prefix(prefix).map(|version| (*op, version))).unwrap_or((Op::Caret, comparator));
+      let (version, parts) = Version::parse_partial(version).map_err(|_| RustructError::InvalidVersion(requirement.to_string()))?;
+      comparators.push(Comparator{ op, version, parts });
+    }
+    Ok(VersionReq{ requirement : requirement.trim().to_string(), comparators })
+  }
+
+  /// Return true if `version` match all the comparators.
+  pub fn matches(&self, version : &Version) -> bool
+  {
+    self.comparators.iter().all(|comparator| comparator.matches(version))
+  }
+}
+
+impl FromStr for VersionReq
+{
+  type Err = anyhow::Error;
+
+  fn from_str(requirement : &str) -> Result<Self>
+  {
+    VersionReq::parse(requirement)
+  }
+}
+
+impl fmt::Display for VersionReq
+{
+  fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result
+  {
+    write!(f, "{}", self.requirement)
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{Version, VersionReq};
+
+  #[test]
+  fn version_requirements()
+  {
+    assert!(Version::parse("1.2.3-beta+build").unwrap() == Version::new(1, 2, 3));
+    assert!(Version::parse("0.4").unwrap() < Version::parse("0.10.0").unwrap());
+    assert!(Version::parse("1.x").is_err() && Version::parse("").is_err() && Version::parse("1.2.3.4").is_err());
+
+    let matches = |requirement : &str, version : &str| VersionReq::parse(requirement).unwrap().matches(&Version::parse(version).unwrap());
+    assert!(matches("1.2", "1.9.0") && !matches("1.2", "1.1.9") && !matches("1.2", "2.0.0"));
+    assert!(matches("^0.3.1", "0.3.7") && !matches("^0.3.1", "0.4.0") && matches("^0", "0.9.0"));
+    assert!(matches("~1.2.1", "1.2.9") && !matches("~1.2.1", "1.3.0") && matches("~1", "1.7.0"));
+    assert!(matches("=1.2", "1.2.5") && !matches("=1.2.0", "1.2.5"));
+    assert!(matches(">=1.2, <2", "1.5.0") && !matches(">=1.2, <2", "2.0.0") && matches("*", "3.0.0"));
+    assert!(VersionReq::parse(">=one").is_err() && VersionReq::any().matches(&Version::default()));
+  }
+}
@@ -4,10 +4,29 @@
 //! as node can read on a VFile and generate a new one
 
 use std::io;
+use std::io::IoSliceMut;
 use std::io::Read;
 use std::io::Seek;
 use std::io::SeekFrom;
+use std::io::Write;
 use std::fmt;
+use std::sync::{Arc, Mutex};
+
+pub mod hash;
+pub mod scan;
+pub mod registry;
+pub mod preview;
+pub mod text;
+pub mod binary;
+pub mod export;
+pub mod compare;
+
+pub use registry::{BuilderRegistry, builder_registry, builder_key, register_builder, unregister_builder};
+pub use preview::{preview, HexDump, HexLine};
+pub use text::{Encoding, Lines, lines, read_cstring, read_string_exact};
+pub use binary::ReadBinary;
+pub use export::{export, resume_export};
+pub use compare::{compare, identical_prefix_len, similarity, Comparison, Signature};
 
 use anyhow::Result;
 use byteorder::{LittleEndian, ReadBytesExt};
@@ -22,6 +41,24 @@ pub trait VFileBuilder : Sync + Send
   fn open(&self) -> Result<Box<dyn VFile>>;
   /// Return the size of the created [VFile]
   fn size(&self) -> u64;
+  /// Return the runs of data and of holes reading as zeros of the created [VFile] sorted by offset,
+  /// the default is a single data extent covering the whole file.
+  fn extents(&self) -> Vec<Extent>
+  {
+    match self.size()
+    {
+      0 => Vec::new(),
+      size => vec![Extent{ range : 0..size, hole : false }],
+    }
+  }
+}
+
+/// A run of data of a [VFile], or a hole that read as zeros and don't need to be read or written by exporters.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Extent
+{
+  pub range : std::ops::Range<u64>,
+  pub hole : bool,
 }
 
 impl std::fmt::Debug for dyn VFileBuilder
@@ -69,8 +106,23 @@ impl std::fmt::Debug for dyn VFileBuilder
   }
 }*/
 
+/// Return the type name of the [VFileBuilder] implemented by the core in this build, used to serialize them.
+pub fn builder_types() -> Vec<&'static str>
+{
+  let mut types = vec!["FsVFileBuilder", "MappedVFileBuilder", "MemoryVFileBuilder", "TempVFileBuilder", "ZeroVFileBuilder",
+                       "PatternVFileBuilder", "RandomVFileBuilder", "SliceVFileBuilder", "OverlayVFileBuilder", "ConcatVFileBuilder", "StagingVFileBuilder",
+                       "BufferedVFileBuilder", "DecompressVFileBuilder", "CryptVFileBuilder", "MeteredVFileBuilder", "ChunkedCacheVFileBuilder"];
+  if cfg!(feature = "http")
+  {
+    types.push("HttpVFileBuilder");
+  }
+  types
+}
+
 /**
  *  A trait that implement [Read] + [Seek].
+ *  Positioned reads ([read_at](VFile::read_at)) don't move the cursor of the file, 
+ *  to share an open file between concurrent consumers use a [SharedVFile].
  */
 pub trait VFile : Read + Seek + Sync + Send 
 {
@@ -78,12 +130,149 @@ pub trait VFile : Read + Seek + Sync + Send
   {
     self.seek(SeekFrom::Current(0))
   }
+
+  /// Read at `offset` into `buf` and return the number of bytes read, the cursor is restored after the read.
+  fn read_at(&mut self, offset : u64, buf : &mut [u8]) -> io::Result<usize>
+  {
+    let pos = self.tell()?;
+    self.seek(SeekFrom::Start(offset))?;
+    let result = self.read(buf);
+    self.seek(SeekFrom::Start(pos))?;
+    result
+  }
+
+  /// Read exactly `buf.len()` bytes at `offset`, the cursor is restored after the read.
+  fn read_exact_at(&mut self, offset : u64, buf : &mut [u8]) -> io::Result<()>
+  {
+    let pos = self.tell()?;
+    self.seek(SeekFrom::Start(offset))?;
+    let result = self.read_exact(buf);
+    self.seek(SeekFrom::Start(pos))?;
+    result
+  }
+
+  /// Fill `bufs` in order with the data at `offset` and return the number of bytes read, 
+  /// less than the size of the buffers only at the end of the file. The cursor is restored after the read.
+  fn read_vectored_at(&mut self, offset : u64, bufs : &mut [IoSliceMut<'_>]) -> io::Result<usize>
+  {
+    let pos = self.tell()?;
+    self.seek(SeekFrom::Start(offset))?;
+    let mut total = 0;
+    let mut result = Ok(());
+    'bufs: for buf in bufs.iter_mut()
+    {
+      let mut filled = 0;
+      while filled < buf.len()
+      {
+        match self.read(&mut buf[filled..])
+        {
+          Ok(0) => break 'bufs,
+          Ok(size) => { filled += size; total += size },
+          Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+          Err(err) => { result = Err(err); break 'bufs },
+        }
+      }
+    }
+    self.seek(SeekFrom::Start(pos))?;
+    result.map(|_| total)
+  }
 }
 
 impl<T: Read + Seek + Sync + Send > VFile for T 
 {
 }
 
+/**
+ *  An open [VFile] shared between consumers, each clone has its own cursor.
+ *  Reads lock the file, seek to the cursor of the reader and read, so the consumers don't need to open the file again
+ *  or to coordinate their seeks. [read_at](SharedVFile::read_at) can be called concurrently from a shared reference.
+ */
+#[derive(Clone)]
+pub struct SharedVFile
+{
+  file : Arc<Mutex<Box<dyn VFile>>>,
+  size : u64,
+  pos : u64,
+}
+
+impl SharedVFile
+{
+  /// Share `file`.
+  pub fn new(mut file : Box<dyn VFile>) -> io::Result<Self>
+  {
+    let size = file.seek(SeekFrom::End(0))?;
+    Ok(SharedVFile{ file : Arc::new(Mutex::new(file)), size, pos : 0 })
+  }
+
+  /// Return the size of the file.
+  pub fn size(&self) -> u64
+  {
+    self.size
+  }
+
+  /// Read at `offset` into `buf` and return the number of bytes read.
+  pub fn read_at(&self, offset : u64, buf : &mut [u8]) -> io::Result<usize>
+  {
+    let mut file = self.file.lock().unwrap();
+    file.seek(SeekFrom::Start(offset))?;
+    file.read(buf)
+  }
+
+  /// Read exactly `buf.len()` bytes at `offset`.
+  pub fn read_exact_at(&self, offset : u64, buf : &mut [u8]) -> io::Result<()>
+  {
+    let mut file = self.file.lock().unwrap();
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(buf)
+  }
+
+  /// Fill `bufs` in order with the data at `offset` and return the number of bytes read.
+  pub fn read_vectored_at(&self, offset : u64, bufs : &mut [IoSliceMut<'_>]) -> io::Result<usize>
+  {
+    self.file.lock().unwrap().read_vectored_at(offset, bufs)
+  }
+}
+
+impl Read for SharedVFile
+{
+  fn read(&mut self, buf : &mut [u8]) -> io::Result<usize>
+  {
+    //not VFile::read_at which is implemented with this method
+    let size = SharedVFile::read_at(self, self.pos, buf)?;
+    self.pos += size as u64;
+    Ok(size)
+  }
+}
+
+impl Seek for SharedVFile
+{
+  fn seek(&mut self, pos : SeekFrom) -> io::Result<u64>
+  {
+    let pos = match pos
+    {
+      SeekFrom::Start(pos) => Some(pos),
+      SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+      SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+    };
+
+    match pos
+    {
+      Some(pos) => { self.pos = pos; Ok(pos) },
+      None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+    }
+  }
+}
+
+/**
+ *  A trait that implement [Write] + [Seek], used by plugins to stream decoded data into a new virtual file.
+ *  When finished the written content is returned as a [VFileBuilder] that can be added to the tree as a [Value::VFileBuilder](crate::value::Value::VFileBuilder).
+ */
+pub trait VFileWriter : Write + Seek + Sync + Send
+{
+  /// Flush the written data and return a [VFileBuilder] generating files containing it.
+  fn finish(self : Box<Self>) -> Result<Arc<dyn VFileBuilder>>;
+}
+
 // This is some helper function 
 
 /**
@@ -135,3 +324,57 @@ pub fn read_utf16_list<T : VFile>(file : &mut T, size : usize) -> Result<Vec<Str
 
   Ok(list)
 }
+
+#[cfg(test)]
+mod tests
+{
+  use super::{VFile, SharedVFile};
+
+  use std::io::{Cursor, IoSliceMut, Read, Seek, SeekFrom};
+  use std::sync::Arc;
+  use std::thread;
+
+  #[test]
+  fn positioned_reads()
+  {
+    let content : Vec<u8> = (0..=255u8).collect();
+    let mut file = Cursor::new(content.clone());
+    file.seek(SeekFrom::Start(10)).unwrap();
+
+    let mut buffer = [0; 4];
+    assert!(file.read_at(100, &mut buffer).unwrap() == 4 && buffer == [100, 101, 102, 103]);
+    file.read_exact_at(252, &mut buffer).unwrap();
+    assert!(file.read_exact_at(253, &mut buffer).is_err());
+    let (mut first, mut second) = ([0; 3], [0; 8]);
+    let mut bufs = [IoSliceMut::new(&mut first), IoSliceMut::new(&mut second)];
+    assert!(file.read_vectored_at(250, &mut bufs).unwrap() == 6);
+    assert!(first == [250, 251, 252] && second[..3] == [253, 254, 255]);
+    assert!(file.tell().unwrap() == 10);
+
+    let shared = Arc::new(SharedVFile::new(Box::new(Cursor::new(content.clone()))).unwrap());
+    let readers : Vec<_> = (0..4u64).map(|index|
+    {
+      let shared = shared.clone();
+      thread::spawn(move ||
+      {
+        let mut buffer = [0; 64];
+        for _ in 0..100
+        {
+          shared.read_exact_at(index * 64, &mut buffer).unwrap();
+          assert!(buffer[0] as u64 == index * 64 && buffer[63] as u64 == index * 64 + 63);
+        }
+      })
+    }).collect();
+    readers.into_iter().for_each(|reader| reader.join().unwrap());
+
+    let mut first = (*shared).clone();
+    let mut second = (*shared).clone();
+    first.seek(SeekFrom::Start(200)).unwrap();
+    second.read_exact(&mut buffer).unwrap();
+    first.read_exact(&mut buffer).unwrap();
+    assert!(buffer == [200, 201, 202, 203] && second.tell().unwrap() == 4);
+    let mut data = Vec::new();
+    first.read_to_end(&mut data).unwrap();
+    assert!(data[..] == content[204..]);
+  }
+}
@@ -0,0 +1,144 @@
+//! [ReadBinary] extension trait reading the composite types found in binary formats from any [Read],
+//! on top of the integer readers of [byteorder] : 24 bits integers, LEB128 varints, Windows GUID and FILETIME,
+//! fixed size arrays and [Value::Bytes].
+
+use std::io::{self, Read};
+
+use crate::value::Value;
+use crate::datetime::WindowsTimestamp;
+use crate::error::RustructError;
+
+use anyhow::Result;
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+/// Maximum size of a LEB128 encoded 64 bits integer.
+const LEB128_MAX_SIZE : usize = 10;
+
+/**
+ * Read binary structures, implemented for all the [Read] like [VFile](crate::vfile::VFile).
+ */
+pub trait ReadBinary : Read
+{
+  /// Read an unsigned 24 bits integer in the `B` byte order.
+  fn read_uint24<B : ByteOrder>(&mut self) -> io::Result<u32>
+  {
+    self.read_u24::<B>()
+  }
+
+  /// Read a signed 24 bits integer in the `B` byte order.
+  fn read_int24<B : ByteOrder>(&mut self) -> io::Result<i32>
+  {
+    self.read_i24::<B>()
+  }
+
+  /// Read an unsigned LEB128 variable length integer.
+  fn read_uleb128(&mut self) -> Result<u64>
+  {
+    let mut value : u64 = 0;
+    for index in 0..LEB128_MAX_SIZE
+    {
+      let byte = self.read_u8()?;
+      value |= ((byte & 0x7f) as u64).checked_shl(7 * index as u32).unwrap_or(0);
+      if byte & 0x80 == 0
+      {
+        return Ok(value)
+      }
+    }
+    Err(RustructError::InvalidEncoding(format!("LEB128 integer longer than {} bytes", LEB128_MAX_SIZE)).into())
+  }
+
+  /// Read a signed LEB128 variable length integer.
+  fn read_sleb128(&mut self) -> Result<i64>
+  {
+    let mut value : i64 = 0;
+    let mut shift = 0;
+    for _ in 0..LEB128_MAX_SIZE
+    {
+      let byte = self.read_u8()?;
+      value |= ((byte & 0x7f) as i64).checked_shl(shift).unwrap_or(0);
+      shift += 7;
+      if byte & 0x80 == 0
+      {
+        if shift < 64 && byte & 0x40 != 0
+        {
+          value |= -1 << shift;
+        }
+        return Ok(value)
+      }
+    }
+    Err(RustructError::InvalidEncoding(format!("LEB128 integer longer than {} bytes", LEB128_MAX_SIZE)).into())
+  }
+
+  /// Read a Windows GUID, its three first fields are little endian.
+  fn read_guid(&mut self) -> io::Result<Uuid>
+  {
+    let data1 = self.read_u32::<LittleEndian>()?;
+    let data2 = self.read_u16::<LittleEndian>()?;
+    let data3 = self.read_u16::<LittleEndian>()?;
+    let data4 = self.read_byte_array::<8>()?;
+    Ok(Uuid::from_fields(data1, data2, data3, &data4))
+  }
+
+  /// Read a Windows FILETIME, the number of 100 nanoseconds intervals since 1601 in little endian.
+  fn read_filetime(&mut self) -> Result<DateTime<Utc>>
+  {
+    WindowsTimestamp(self.read_u64::<LittleEndian>()?).to_datetime()
+  }
+
+  /// Read an array of `N` bytes.
+  fn read_byte_array<const N : usize>(&mut self) -> io::Result<[u8; N]>
+  {
+    let mut array = [0; N];
+    self.read_exact(&mut array)?;
+    Ok(array)
+  }
+
+  /// Read `size` bytes into a [Value::Bytes].
+  fn read_bytes_value(&mut self, size : usize) -> io::Result<Value>
+  {
+    let mut bytes = Vec::new();
+    self.take(size as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < size
+    {
+      return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("read {} bytes of {}", bytes.len(), size)))
+    }
+    Ok(Value::Bytes(bytes))
+  }
+}
+
+impl<R : Read + ?Sized> ReadBinary for R
+{
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::ReadBinary;
+  use crate::value::Value;
+
+  use std::io::Cursor;
+  use byteorder::{BigEndian, LittleEndian};
+
+  #[test]
+  fn read_binary()
+  {
+    let mut data = vec![0x01, 0x02, 0x03, 0xff, 0xff, 0xff];
+    data.extend_from_slice(&[0xe5, 0x8e, 0x26, 0xc0, 0xbb, 0x78, 0x7f]);
+    data.extend_from_slice(&[0x33, 0x22, 0x11, 0x00, 0x55, 0x44, 0x77, 0x66, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+    data.extend_from_slice(&132_223_104_000_000_000u64.to_le_bytes());
+    data.extend_from_slice(b"MAGIC\x01\x02");
+
+    let mut file = Cursor::new(data);
+    assert!(file.read_uint24::<BigEndian>().unwrap() == 0x010203 && file.read_int24::<LittleEndian>().unwrap() == -1);
+    assert!(file.read_uleb128().unwrap() == 624485 && file.read_sleb128().unwrap() == -123456 && file.read_sleb128().unwrap() == -1);
+    assert!(file.read_guid().unwrap().to_string() == "00112233-4455-6677-8899-aabbccddeeff");
+    assert!(file.read_filetime().unwrap().to_rfc3339() == "2020-01-01T00:00:00+00:00");
+    assert!(&file.read_byte_array::<5>().unwrap() == b"MAGIC");
+    assert!(file.read_bytes_value(2).unwrap() == Value::Bytes(vec![1, 2]));
+    assert!(file.read_bytes_value(1).is_err());
+
+    assert!(Cursor::new(vec![0x80; 11]).read_uleb128().is_err());
+  }
+}
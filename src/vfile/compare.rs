@@ -0,0 +1,180 @@
+//! Comparison of the content of two [VFileBuilder] : [compare] and [identical_prefix_len] read both builders side by side in one
+//! streaming pass, and [Signature] summarize a builder by its content-defined chunks (cut by the rolling hash of [dedup](crate::dedup))
+//! to estimate the [similarity] of contents that share data at different offsets.
+
+use std::collections::HashMap;
+
+use crate::vfile::VFileBuilder;
+use crate::io_tuner::read_full;
+use crate::dedup::{self, Chunk};
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+
+/// Size of the blocks read from each builder.
+const BLOCK_SIZE : usize = 64 * 1024;
+
+/// Result of [compare].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Comparison
+{
+  pub size_a : u64,
+  pub size_b : u64,
+  /// Offset of the first different byte, or of the end of the shortest builder if it's a prefix of the other.
+  pub first_difference : Option<u64>,
+  /// Number of different bytes at the same offset in both builders, the bytes past the end of the shortest builder are not counted.
+  pub different_bytes : u64,
+}
+
+impl Comparison
+{
+  /// Return true if both builders have the same content.
+  pub fn identical(&self) -> bool
+  {
+    self.first_difference.is_none()
+  }
+}
+
+/// Read `a` and `b` by blocks and call `callback` with the offset and the blocks of same size of both builders,
+/// until the end of the shortest builder or until `callback` return false.
+fn read_blocks<F>(a : &dyn VFileBuilder, b : &dyn VFileBuilder, mut callback : F) -> Result<()>
+  where F : FnMut(u64, &[u8], &[u8]) -> bool
+{
+  let (mut file_a, mut file_b) = (a.open()?, b.open()?);
+  let (mut block_a, mut block_b) = (vec![0; BLOCK_SIZE], vec![0; BLOCK_SIZE]);
+  let mut offset = 0;
+  loop
+  {
+    let size = read_full(&mut file_a, &mut block_a)?.min(read_full(&mut file_b, &mut block_b)?);
+    if size == 0 || !callback(offset, &block_a[..size], &block_b[..size]) || size < BLOCK_SIZE
+    {
+      return Ok(())
+    }
+    offset += size as u64;
+  }
+}
+
+/// Compare the content of `a` and `b` byte by byte.
+pub fn compare(a : &dyn VFileBuilder, b : &dyn VFileBuilder) -> Result<Comparison>
+{
+  let mut comparison = Comparison{ size_a : a.size(), size_b : b.size(), first_difference : None, different_bytes : 0 };
+  read_blocks(a, b, |offset, block_a, block_b|
+  {
+    let mut differences = block_a.iter().zip(block_b).enumerate().filter(|(_, (byte_a, byte_b))| byte_a != byte_b).map(|(index, _)| index);
+    if let Some(first) = differences.next()
+    {
+      comparison.first_difference.get_or_insert(offset + first as u64);
+      comparison.different_bytes += 1 + differences.count() as u64;
+    }
+    true
+  })?;
+
+  if comparison.first_difference.is_none() && comparison.size_a != comparison.size_b
+  {
+    comparison.first_difference = Some(comparison.size_a.min(comparison.size_b));
+  }
+  Ok(comparison)
+}
+
+/// Return the number of bytes at the start of `a` and `b` that are identical, reading them until the first difference.
+pub fn identical_prefix_len(a : &dyn VFileBuilder, b : &dyn VFileBuilder) -> Result<u64>
+{
+  let mut len = 0;
+  read_blocks(a, b, |_, block_a, block_b|
+  {
+    let same = block_a.iter().zip(block_b).take_while(|(byte_a, byte_b)| byte_a == byte_b).count();
+    len += same as u64;
+    same == block_a.len()
+  })?;
+  Ok(len)
+}
+
+/// Content-defined chunks of a builder, used to find the data shared by two builders whatever its offset.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signature
+{
+  pub size : u64,
+  /// Size of the chunks by hash, a chunk found several times is counted once.
+  pub chunks : HashMap<String, u64>,
+}
+
+impl Signature
+{
+  /// Read `builder` and return its signature.
+  pub fn new(builder : &dyn VFileBuilder) -> Result<Self>
+  {
+    let mut signature = Signature::default();
+    signature.size = dedup::split(&mut builder.open()?, |data|
+    {
+      let chunk = Chunk::new(data);
+      signature.chunks.insert(chunk.hash, chunk.size);
+      Ok(())
+    })?;
+    Ok(signature)
+  }
+
+  /// Return the size of the distinct chunks present in both signatures.
+  pub fn shared_bytes(&self, other : &Signature) -> u64
+  {
+    self.chunks.iter().filter(|(hash, _)| other.chunks.contains_key(*hash)).map(|(_, size)| size).sum()
+  }
+
+  /// Return the similarity of the contents between 0.0 and 1.0, the size of their shared chunks relatively to the size of their distinct chunks.
+  pub fn similarity(&self, other : &Signature) -> f64
+  {
+    let total : u64 = self.chunks.values().sum::<u64>() + other.chunks.values().sum::<u64>();
+    match total
+    {
+      0 => 1.0,
+      total => (2 * self.shared_bytes(other)) as f64 / total as f64,
+    }
+  }
+}
+
+/// Return the similarity of the contents of `a` and `b` between 0.0 and 1.0, see [Signature::similarity].
+pub fn similarity(a : &dyn VFileBuilder, b : &dyn VFileBuilder) -> Result<f64>
+{
+  Ok(Signature::new(a)?.similarity(&Signature::new(b)?))
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{compare, identical_prefix_len, similarity};
+  use crate::memoryvfile::MemoryVFileBuilder;
+  use crate::patternvfile::RandomVFileBuilder;
+  use crate::vfile::VFileBuilder;
+
+  use std::io::Read;
+
+  #[test]
+  fn compare_builders()
+  {
+    let mut content = Vec::new();
+    RandomVFileBuilder::new(11, 300000).open().unwrap().read_to_end(&mut content).unwrap();
+    let a = MemoryVFileBuilder::from_buffer(content.clone());
+    let mut changed = content.clone();
+    changed[100000] ^= 1;
+    changed[200001] ^= 1;
+    let b = MemoryVFileBuilder::from_buffer(changed);
+
+    let comparison = compare(a.as_ref(), b.as_ref()).unwrap();
+    assert!(!comparison.identical() && comparison.first_difference == Some(100000) && comparison.different_bytes == 2);
+    assert!(identical_prefix_len(a.as_ref(), b.as_ref()).unwrap() == 100000);
+    assert!(compare(a.as_ref(), a.as_ref()).unwrap().identical() && identical_prefix_len(a.as_ref(), a.as_ref()).unwrap() == 300000);
+
+    let prefix = MemoryVFileBuilder::from_buffer(content[..70000].to_vec());
+    let comparison = compare(prefix.as_ref(), a.as_ref()).unwrap();
+    assert!(comparison.first_difference == Some(70000) && comparison.different_bytes == 0);
+    assert!(identical_prefix_len(a.as_ref(), prefix.as_ref()).unwrap() == 70000);
+
+    //the same data shifted is still similar
+    let mut shifted = b"inserted header".to_vec();
+    shifted.extend_from_slice(&content);
+    let shifted = MemoryVFileBuilder::from_buffer(shifted);
+    assert!(identical_prefix_len(a.as_ref(), shifted.as_ref()).unwrap() == 0);
+    assert!(similarity(a.as_ref(), shifted.as_ref()).unwrap() > 0.8);
+    let other = RandomVFileBuilder::new(12, 300000);
+    assert!(similarity(a.as_ref(), &other).unwrap() == 0.0 && similarity(a.as_ref(), a.as_ref()).unwrap() == 1.0);
+  }
+}
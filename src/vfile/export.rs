@@ -0,0 +1,128 @@
+//! Export of the content of a [VFileBuilder] to a file of the host filesystem.
+//! Only the data [extents](VFileBuilder::extents) are read and written, the holes are skipped and left sparse when the filesystem support it.
+//! The data is written in order so an interrupted export can be continued with [resume_export].
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::vfile::VFileBuilder;
+use crate::io_tuner::{IoTuner, read_chunks};
+use crate::error::RustructError;
+
+use anyhow::Result;
+
+/// Export `builder` to `dest`, that is created or truncated.
+/// `progress` is called with the position reached in the builder and its size after each chunk,
+/// returning an error from it abort the export. Return the number of bytes of data written.
+pub fn export<P, F>(builder : &dyn VFileBuilder, dest : P, progress : F) -> Result<u64>
+  where P : AsRef<Path>,
+        F : FnMut(u64, u64) -> Result<()>
+{
+  let file = OpenOptions::new().write(true).create(true).truncate(true).open(dest)?;
+  export_from(builder, file, 0, progress)
+}
+
+/// Continue an interrupted [export] of `builder` to `dest`, from the end of the data already written.
+pub fn resume_export<P, F>(builder : &dyn VFileBuilder, dest : P, progress : F) -> Result<u64>
+  where P : AsRef<Path>,
+        F : FnMut(u64, u64) -> Result<()>
+{
+  let dest = dest.as_ref();
+  let file = OpenOptions::new().write(true).create(true).truncate(false).open(dest)?;
+  let exported = file.metadata()?.len();
+  if exported > builder.size()
+  {
+    return Err(RustructError::InvalidArgument(dest.display().to_string(), format!("is bigger than the exported file ({} > {})", exported, builder.size())).into())
+  }
+  export_from(builder, file, exported, progress)
+}
+
+/// Write the extents of `builder` after `start` to `dest`, the file size is only set at the end
+/// so the size of a partial export is the end of its last data written.
+fn export_from<F>(builder : &dyn VFileBuilder, mut dest : File, start : u64, mut progress : F) -> Result<u64>
+  where F : FnMut(u64, u64) -> Result<()>
+{
+  let size = builder.size();
+  let mut source = builder.open()?;
+  let mut tuner = IoTuner::default();
+  let mut written = 0;
+  progress(start, size)?;
+
+  for extent in builder.extents().into_iter().filter(|extent| extent.range.end > start)
+  {
+    let range = extent.range.start.max(start)..extent.range.end;
+    if extent.hole
+    {
+      progress(range.end, size)?;
+      continue
+    }
+
+    source.seek(SeekFrom::Start(range.start))?;
+    dest.seek(SeekFrom::Start(range.start))?;
+    let mut position = range.start;
+    read_chunks(&mut (&mut source).take(range.end - range.start), &mut tuner, |data|
+    {
+      dest.write_all(data)?;
+      position += data.len() as u64;
+      written += data.len() as u64;
+      progress(position, size)
+    })?;
+    if position != range.end
+    {
+      return Err(RustructError::Unknown(format!("export stopped at 0x{:x} before the end of the data at 0x{:x}", position, range.end)).into())
+    }
+  }
+
+  dest.set_len(size)?;
+  dest.sync_all()?;
+  Ok(written)
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{export, resume_export};
+  use crate::vfile::VFileBuilder;
+  use crate::mappedvfile::{FileRanges, MappedVFileBuilder};
+  use crate::patternvfile::RandomVFileBuilder;
+  use crate::error::RustructError;
+
+  use std::io::Read;
+  use std::sync::Arc;
+
+  #[test]
+  fn export_sparse()
+  {
+    let parent : Arc<dyn VFileBuilder> = Arc::new(RandomVFileBuilder::new(7, 300000));
+    let builder = MappedVFileBuilder::new(FileRanges::new());
+    builder.append(0..100000, 0, parent.clone()).unwrap();
+    builder.append_hole(100000..400000).unwrap();
+    builder.append(400000..600000, 100000, parent).unwrap();
+    let builder : Arc<dyn VFileBuilder> = Arc::new(builder);
+    let mut content = Vec::new();
+    builder.open().unwrap().read_to_end(&mut content).unwrap();
+
+    let dest = std::env::temp_dir().join(format!("tap-export-test-{}", uuid::Uuid::new_v4()));
+    let mut positions = Vec::new();
+    let written = export(builder.as_ref(), &dest, |position, size| { positions.push((position, size)); Ok(()) }).unwrap();
+    assert!(written == 300000 && std::fs::read(&dest).unwrap() == content);
+    assert!(positions.first() == Some(&(0, 600000)) && positions.last() == Some(&(600000, 600000)) && positions.contains(&(400000, 600000)));
+
+    //abort after the first chunk of the second extent then continue
+    let result = export(builder.as_ref(), &dest, |position, _| match position > 400000
+    {
+      true => Err(RustructError::Unknown("aborted".into()).into()),
+      false => Ok(()),
+    });
+    assert!(result.is_err());
+    let exported = std::fs::metadata(&dest).unwrap().len();
+    assert!(exported > 400000 && exported < 600000);
+    let written = resume_export(builder.as_ref(), &dest, |position, _| { assert!(position >= exported); Ok(()) }).unwrap();
+    assert!(written == 600000 - exported && std::fs::read(&dest).unwrap() == content);
+
+    std::fs::write(&dest, vec![0; 700000]).unwrap();
+    assert!(resume_export(builder.as_ref(), &dest, |_, _| Ok(())).is_err());
+    std::fs::remove_file(&dest).unwrap();
+  }
+}
@@ -0,0 +1,176 @@
+//! Hashing of the content of a [VFileBuilder], computing the digests of several algorithms in one streaming pass.
+//! The digests are returned as [Attributes] named by algorithm, holding the lowercase hexadecimal digest as a [Value::String],
+//! ready to be added to a node.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::vfile::VFileBuilder;
+use crate::value::Value;
+use crate::attribute::Attributes;
+use crate::io_tuner::{IoTuner, read_chunks};
+use crate::error::RustructError;
+
+use anyhow::Result;
+use md5::{Md5, Digest};
+use sha1::Sha1;
+use sha2::Sha256;
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+/// Hash algorithm supported by [hash].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm
+{
+  Md5,
+  Sha1,
+  Sha256,
+  Blake3,
+}
+
+impl HashAlgorithm
+{
+  /// Return the name of the algorithm, used as attribute name.
+  pub fn name(&self) -> &'static str
+  {
+    match self
+    {
+      HashAlgorithm::Md5 => "md5",
+      HashAlgorithm::Sha1 => "sha1",
+      HashAlgorithm::Sha256 => "sha256",
+      HashAlgorithm::Blake3 => "blake3",
+    }
+  }
+
+  fn hasher(&self) -> Hasher
+  {
+    match self
+    {
+      HashAlgorithm::Md5 => Hasher::Md5(Md5::new()),
+      HashAlgorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+      HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+      HashAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+    }
+  }
+}
+
+impl fmt::Display for HashAlgorithm
+{
+  fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result
+  {
+    write!(f, "{}", self.name())
+  }
+}
+
+impl FromStr for HashAlgorithm
+{
+  type Err = RustructError;
+
+  fn from_str(name : &str) -> std::result::Result<Self, Self::Err>
+  {
+    match name.to_lowercase().as_str()
+    {
+      "md5" => Ok(HashAlgorithm::Md5),
+      "sha1" => Ok(HashAlgorithm::Sha1),
+      "sha256" => Ok(HashAlgorithm::Sha256),
+      "blake3" => Ok(HashAlgorithm::Blake3),
+      _ => Err(RustructError::Parse("hash algorithm", name.to_string())),
+    }
+  }
+}
+
+enum Hasher
+{
+  Md5(Md5),
+  Sha1(Sha1),
+  Sha256(Sha256),
+  Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher
+{
+  fn update(&mut self, data : &[u8])
+  {
+    match self
+    {
+      Hasher::Md5(hasher) => hasher.update(data),
+      Hasher::Sha1(hasher) => hasher.update(data),
+      Hasher::Sha256(hasher) => hasher.update(data),
+      Hasher::Blake3(hasher) => { hasher.update(data); },
+    }
+  }
+
+  fn hex_digest(self) -> String
+  {
+    let digest = match self
+    {
+      Hasher::Md5(hasher) => hasher.finalize().to_vec(),
+      Hasher::Sha1(hasher) => hasher.finalize().to_vec(),
+      Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+      Hasher::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+    };
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+  }
+}
+
+/// Read `builder` once and return the digests of its content for each of `algorithms`, as [Attributes] named by algorithm.
+/// The content is read by chunks of `chunk_size` bytes, or by chunks sized with an [IoTuner] if `chunk_size` is 0.
+pub fn hash(builder : &dyn VFileBuilder, algorithms : &[HashAlgorithm], chunk_size : usize) -> Result<Attributes>
+{
+  let mut hashers : Vec<(HashAlgorithm, Hasher)> = Vec::new();
+  for algorithm in algorithms
+  {
+    if !hashers.iter().any(|(added, _)| added == algorithm)
+    {
+      hashers.push((*algorithm, algorithm.hasher()));
+    }
+  }
+
+  let mut tuner = match chunk_size
+  {
+    0 => IoTuner::default(),
+    chunk_size => IoTuner::fixed(chunk_size),
+  };
+  let mut file = builder.open()?;
+  read_chunks(&mut file, &mut tuner, |data|
+  {
+    hashers.iter_mut().for_each(|(_, hasher)| hasher.update(data));
+    Ok(())
+  })?;
+
+  let mut attributes = Attributes::new();
+  for (algorithm, hasher) in hashers
+  {
+    attributes.add_attribute(algorithm.name(), Value::String(hasher.hex_digest()), None);
+  }
+  Ok(attributes)
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{hash, HashAlgorithm};
+  use crate::memoryvfile::MemoryVFileBuilder;
+  use crate::value::Value;
+
+  #[test]
+  fn hash_content()
+  {
+    let builder = MemoryVFileBuilder::from_buffer(b"abc".to_vec());
+    let all = [HashAlgorithm::Md5, HashAlgorithm::Sha1, HashAlgorithm::Sha256, HashAlgorithm::Blake3];
+    let digests = hash(builder.as_ref(), &all, 1).unwrap();
+    assert!(digests.get_value("md5") == Some(Value::String("900150983cd24fb0d6963f7d28e17f72".into())));
+    assert!(digests.get_value("sha1") == Some(Value::String("a9993e364706816aba3e25717850c26c9cd0d89d".into())));
+    assert!(digests.get_value("sha256") == Some(Value::String("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".into())));
+    assert!(digests.get_value("blake3") == Some(Value::String("6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85".into())));
+
+    let content : Vec<u8> = (0..100000u32).map(|i| (i % 251) as u8).collect();
+    let builder = MemoryVFileBuilder::from_buffer(content);
+    let chunked = hash(builder.as_ref(), &[HashAlgorithm::Sha256, HashAlgorithm::Md5, HashAlgorithm::Sha256], 4096).unwrap();
+    assert!(chunked.count() == 2 && chunked == hash(builder.as_ref(), &[HashAlgorithm::Sha256, HashAlgorithm::Md5], 0).unwrap());
+
+    assert!("SHA1".parse::<HashAlgorithm>().unwrap() == HashAlgorithm::Sha1 && "crc".parse::<HashAlgorithm>().is_err());
+    assert!(serde_json::to_string(&HashAlgorithm::Blake3).unwrap() == "\"blake3\"");
+  }
+}
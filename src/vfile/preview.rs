@@ -0,0 +1,127 @@
+//! Hex dump of a range of the content of a [VFileBuilder], returned as structured lines (offset, hex and ascii columns)
+//! for the UIs and as a short one line snippet used when a [Value](crate::value::Value) holding a builder is formatted.
+
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::vfile::VFileBuilder;
+use crate::value::format::{hex_columns, ascii_columns};
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+/// Number of bytes of a line of [HexDump].
+pub const LINE_SIZE : usize = 16;
+
+/// Maximum number of bytes of [HexDump::snippet].
+pub const SNIPPET_SIZE : usize = 16;
+
+/// A line of a [HexDump].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct HexLine
+{
+  pub offset : u64,
+  /// Bytes in hexadecimal separated by a space.
+  pub hex : String,
+  /// Bytes as ascii, non printable bytes are replaced by `.`.
+  pub ascii : String,
+}
+
+/// Hex dump of `data`, read at `offset` of a builder of `size` bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct HexDump
+{
+  pub offset : u64,
+  /// Size of the builder.
+  pub size : u64,
+  pub data : Vec<u8>,
+}
+
+impl HexDump
+{
+  /// Return the lines of [LINE_SIZE] bytes of the dump.
+  pub fn lines(&self) -> Vec<HexLine>
+  {
+    self.data.chunks(LINE_SIZE).enumerate().map(|(index, line)| HexLine{
+      offset : self.offset + (index * LINE_SIZE) as u64,
+      hex : line.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" "),
+      ascii : ascii_columns(line),
+    }).collect()
+  }
+
+  /// Return true if the builder has data after the dump.
+  pub fn truncated(&self) -> bool
+  {
+    self.offset + (self.data.len() as u64) < self.size
+  }
+
+  /// Return the first [SNIPPET_SIZE] bytes on one line, like `4d 5a 90 00 |MZ..|`, followed by `...` if there is more data.
+  pub fn snippet(&self) -> String
+  {
+    let head = &self.data[..self.data.len().min(SNIPPET_SIZE)];
+    let more = match self.data.len() > SNIPPET_SIZE || self.truncated()
+    {
+      true => " ...",
+      false => "",
+    };
+    let hex = head.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" ");
+    format!("{} |{}|{}", hex, ascii_columns(head), more)
+  }
+}
+
+/// Display the dump like [hex_dump](crate::value::format::hex_dump).
+impl fmt::Display for HexDump
+{
+  fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result
+  {
+    for (index, line) in self.data.chunks(LINE_SIZE).enumerate()
+    {
+      writeln!(f, "{:08x}  {} |{}|", self.offset + (index * LINE_SIZE) as u64, hex_columns(line), ascii_columns(line))?;
+    }
+    Ok(())
+  }
+}
+
+/// Read `len` bytes of `builder` at `offset` and return their [HexDump], shorter at the end of the builder.
+pub fn preview(builder : &dyn VFileBuilder, offset : u64, len : usize) -> Result<HexDump>
+{
+  let size = builder.size();
+  let mut data = Vec::with_capacity((len as u64).min(size.saturating_sub(offset)) as usize);
+  if offset < size
+  {
+    let mut file = builder.open()?;
+    file.seek(SeekFrom::Start(offset))?;
+    file.take(len as u64).read_to_end(&mut data)?;
+  }
+  Ok(HexDump{ offset, size, data })
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::preview;
+  use crate::memoryvfile::MemoryVFileBuilder;
+  use crate::value::Value;
+  use crate::value::format::hex_dump;
+
+  #[test]
+  fn preview_hexdump()
+  {
+    let mut content = b"MZ\x90\x00".to_vec();
+    content.extend((0..60u8).map(|byte| byte + b'A'));
+    let builder = MemoryVFileBuilder::from_buffer(content.clone());
+
+    let dump = preview(builder.as_ref(), 0, 20).unwrap();
+    let lines = dump.lines();
+    assert!(lines.len() == 2 && lines[0].hex.starts_with("4d 5a 90 00 41") && lines[0].ascii.starts_with("MZ..AB") && lines[1].offset == 16);
+    assert!(dump.truncated() && dump.snippet().starts_with("4d 5a 90 00 41 42") && dump.snippet().ends_with("| ..."));
+    assert!(dump.to_string() == hex_dump(&content[..20], 0));
+
+    let end = preview(builder.as_ref(), 60, 100).unwrap();
+    assert!(end.data == content[60..] && !end.truncated() && end.snippet() == "79 7a 7b 7c |yz{||");
+    assert!(preview(builder.as_ref(), 1000, 10).unwrap().data.is_empty());
+
+    assert!(format!("{:?}", Value::VFileBuilder(builder)).starts_with("4d 5a 90 00"));
+  }
+}
@@ -0,0 +1,137 @@
+//! Identity of the [VFileBuilder] : a builder is identified by a name when it's registered in a [BuilderRegistry],
+//! or by an id generated the first time it's asked, stable as long as the builder is alive.
+//!
+//! Builders referencing other builders, like [MappedVFileBuilder](crate::mappedvfile::MappedVFileBuilder), serialize the
+//! registered parents by name and find them in the [process registry](builder_registry) when deserialized, so parents
+//! that can't be serialized or that are reopened from another source can be provided before a session is loaded.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, RwLock, Weak};
+
+use crate::vfile::VFileBuilder;
+
+/// Return a key identifying `builder` while it's alive, used to share the opened files and cached data of a builder.
+pub fn builder_key(builder : &Arc<dyn VFileBuilder>) -> usize
+{
+  Arc::as_ptr(builder) as *const () as usize
+}
+
+/// Generated ids and weak references of the builders, by [key](builder_key).
+type GeneratedIds = HashMap<usize, (Weak<dyn VFileBuilder>, String)>;
+
+/**
+ * Map the names and ids of builders to the builders.
+ */
+#[derive(Default)]
+pub struct BuilderRegistry
+{
+  /// Builders registered by name, they are kept alive by the registry.
+  named : RwLock<HashMap<String, Arc<dyn VFileBuilder>>>,
+  /// Generated ids of the builders by [key](builder_key), the builders are not kept alive.
+  generated : Mutex<GeneratedIds>,
+}
+
+impl BuilderRegistry
+{
+  pub fn new() -> Self
+  {
+    BuilderRegistry::default()
+  }
+
+  /// Register `builder` as `name`, replacing the builder registered with this name.
+  pub fn register<S : Into<String>>(&self, name : S, builder : Arc<dyn VFileBuilder>)
+  {
+    self.named.write().unwrap().insert(name.into(), builder);
+  }
+
+  /// Remove the builder registered as `name`.
+  pub fn unregister(&self, name : &str) -> Option<Arc<dyn VFileBuilder>>
+  {
+    self.named.write().unwrap().remove(name)
+  }
+
+  /// Return the builder registered as `name`, or the alive builder with the generated id `id`.
+  pub fn get(&self, id : &str) -> Option<Arc<dyn VFileBuilder>>
+  {
+    if let Some(builder) = self.named.read().unwrap().get(id)
+    {
+      return Some(builder.clone())
+    }
+    self.generated.lock().unwrap().values().find(|(_, generated)| generated == id).and_then(|(builder, _)| builder.upgrade())
+  }
+
+  /// Return the name `builder` was registered with.
+  pub fn name(&self, builder : &Arc<dyn VFileBuilder>) -> Option<String>
+  {
+    self.named.read().unwrap().iter().find(|(_, registered)| Arc::ptr_eq(registered, builder)).map(|(name, _)| name.clone())
+  }
+
+  /// Return the names of the registered builders, sorted.
+  pub fn names(&self) -> Vec<String>
+  {
+    let mut names : Vec<String> = self.named.read().unwrap().keys().cloned().collect();
+    names.sort();
+    names
+  }
+
+  /// Return the identifier of `builder` : its registered name, or an id generated the first time it's asked.
+  pub fn id(&self, builder : &Arc<dyn VFileBuilder>) -> String
+  {
+    if let Some(name) = self.name(builder)
+    {
+      return name
+    }
+
+    let mut generated = self.generated.lock().unwrap();
+    generated.retain(|_, (builder, _)| builder.strong_count() > 0);
+    generated.entry(builder_key(builder)).or_insert_with(|| (Arc::downgrade(builder), uuid::Uuid::new_v4().to_string())).1.clone()
+  }
+}
+
+/// Return the registry of the process, used to serialize and deserialize the references to builders.
+pub fn builder_registry() -> &'static BuilderRegistry
+{
+  static REGISTRY : OnceLock<BuilderRegistry> = OnceLock::new();
+  REGISTRY.get_or_init(BuilderRegistry::new)
+}
+
+/// Register `builder` as `name` in the [process registry](builder_registry).
+pub fn register_builder<S : Into<String>>(name : S, builder : Arc<dyn VFileBuilder>)
+{
+  builder_registry().register(name, builder)
+}
+
+/// Remove the builder registered as `name` from the [process registry](builder_registry).
+pub fn unregister_builder(name : &str) -> Option<Arc<dyn VFileBuilder>>
+{
+  builder_registry().unregister(name)
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{BuilderRegistry, builder_key};
+  use crate::vfile::VFileBuilder;
+  use crate::memoryvfile::MemoryVFileBuilder;
+
+  use std::sync::Arc;
+
+  #[test]
+  fn builder_identity()
+  {
+    let registry = BuilderRegistry::new();
+    let disk : Arc<dyn VFileBuilder> = MemoryVFileBuilder::from_buffer(vec![1; 10]);
+    let other : Arc<dyn VFileBuilder> = MemoryVFileBuilder::from_buffer(vec![1; 10]);
+    assert!(builder_key(&disk) == builder_key(&disk.clone()) && builder_key(&disk) != builder_key(&other));
+
+    let id = registry.id(&other);
+    assert!(registry.id(&other.clone()) == id && registry.id(&disk) != id && registry.name(&other).is_none());
+    assert!(registry.get(&id).is_some_and(|builder| Arc::ptr_eq(&builder, &other)));
+    drop(other);
+    assert!(registry.get(&id).is_none());
+
+    registry.register("disk", disk.clone());
+    assert!(registry.id(&disk) == "disk" && registry.names() == vec!["disk".to_string()]);
+    assert!(registry.unregister("disk").is_some() && registry.get("disk").is_none() && registry.id(&disk) != "disk");
+  }
+}
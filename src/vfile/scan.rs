@@ -0,0 +1,251 @@
+//! Search of byte patterns and regular expressions in the content of a [VFileBuilder].
+//!
+//! Byte patterns are searched together with an [Aho-Corasick](aho_corasick) automaton and report all their occurrences,
+//! even overlapping, regular expressions report their leftmost non-overlapping matches. Regular expressions match bytes :
+//! `.` match any byte and `\xFF` is the byte 0xff, not an UTF-8 encoded character.
+//! The content is read by chunks, the end of each chunk is kept to find the matches straddling two chunks,
+//! so matches of regular expressions longer than [ScanOptions::max_match_size] can be missed or truncated.
+
+use std::io::{ErrorKind, Read};
+
+use crate::vfile::VFileBuilder;
+use crate::error::RustructError;
+
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, Input};
+use anyhow::Result;
+use regex::bytes::{Regex, RegexBuilder};
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+/// A pattern searched by a [Scanner].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum Pattern
+{
+  /// Search this sequence of bytes.
+  Bytes(Vec<u8>),
+  /// Search the matches of this regular expression.
+  Regex(String),
+}
+
+/// Options of a [Scanner].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ScanOptions
+{
+  /// Ignore the case of the ASCII letters.
+  pub case_insensitive : bool,
+  /// Maximum size of a regular expression match, it's the size of the data kept between two chunks.
+  pub max_match_size : usize,
+  /// Size of the chunks read.
+  pub chunk_size : usize,
+  /// Stop after this number of matches.
+  pub max_matches : Option<usize>,
+}
+
+impl Default for ScanOptions
+{
+  fn default() -> Self
+  {
+    ScanOptions{ case_insensitive : false, max_match_size : 4096, chunk_size : 1024 * 1024, max_matches : None }
+  }
+}
+
+/// A match of a pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Match
+{
+  /// Index of the pattern matched.
+  pub pattern : usize,
+  /// Offset of the first byte of the match.
+  pub start : u64,
+  /// Offset following the last byte of the match.
+  pub end : u64,
+}
+
+/**
+ * Search a list of [Pattern] in [VFileBuilder], it can be reused to scan several builders.
+ */
+pub struct Scanner
+{
+  bytes : Option<(AhoCorasick, Vec<usize>)>,
+  regexes : Vec<(usize, Regex)>,
+  options : ScanOptions,
+  /// Size of the data kept between two chunks.
+  overlap : usize,
+}
+
+impl Scanner
+{
+  /// Return a scanner searching `patterns`, the matches are reported with the index of their pattern in `patterns`.
+  pub fn new(patterns : &[Pattern], options : ScanOptions) -> Result<Self>
+  {
+    let mut bytes = Vec::new();
+    let mut bytes_index = Vec::new();
+    let mut regexes = Vec::new();
+
+    for (index, pattern) in patterns.iter().enumerate()
+    {
+      match pattern
+      {
+        Pattern::Bytes(pattern) if pattern.is_empty() => return Err(RustructError::InvalidArgument("scan".into(), "empty pattern".into()).into()),
+        Pattern::Bytes(pattern) => { bytes.push(pattern.clone()); bytes_index.push(index) },
+        Pattern::Regex(regex) =>
+        {
+          let regex = RegexBuilder::new(regex).unicode(false).case_insensitive(options.case_insensitive).build()
+                                              .map_err(|err| RustructError::Parse("regex", err.to_string()))?;
+          regexes.push((index, regex));
+        },
+      }
+    }
+
+    let overlap = bytes.iter().map(|pattern| pattern.len()).max().unwrap_or(0).max(options.max_match_size).max(1);
+    let bytes = match bytes.is_empty()
+    {
+      true => None,
+      false => Some((AhoCorasickBuilder::new().ascii_case_insensitive(options.case_insensitive).build(&bytes)?, bytes_index)),
+    };
+    Ok(Scanner{ bytes, regexes, options, overlap })
+  }
+
+  /// Search the matches starting in `window[from..until]`, `window` start at `offset` in the content.
+  /// Matches are returned sorted by offset.
+  fn search(&self, window : &[u8], offset : u64, from : usize, until : usize) -> Vec<Match>
+  {
+    let mut matches = Vec::new();
+    let absolute = |pattern, start : usize, end : usize| Match{ pattern, start : offset + start as u64, end : offset + end as u64 };
+
+    if let Some((automaton, indexes)) = &self.bytes
+    {
+      for found in automaton.find_overlapping_iter(Input::new(window).range(from..))
+      {
+        if found.start() < until
+        {
+          matches.push(absolute(indexes[found.pattern().as_usize()], found.start(), found.end()));
+        }
+      }
+    }
+
+    for (index, regex) in self.regexes.iter()
+    {
+      let mut start = from;
+      while let Some(found) = regex.find_at(window, start)
+      {
+        if found.start() >= until
+        {
+          break
+        }
+        matches.push(absolute(*index, found.start(), found.end()));
+        //empty matches must advance
+        start = found.end().max(found.start() + 1);
+        if start > window.len()
+        {
+          break
+        }
+      }
+    }
+
+    matches.sort_by_key(|found| (found.start, found.pattern, found.end));
+    matches
+  }
+
+  /// Scan `builder` and call `callback` on each match in offset order until it returns false.
+  /// Return the number of matches found.
+  pub fn scan<F>(&self, builder : &dyn VFileBuilder, mut callback : F) -> Result<usize>
+    where F : FnMut(&Match) -> bool
+  {
+    let mut file = builder.open()?;
+    let mut buffer = vec![0; self.options.chunk_size.max(1)];
+    let mut window : Vec<u8> = Vec::new();
+    let mut window_start : u64 = 0;
+    //matches starting before this offset were reported
+    let mut reported : u64 = 0;
+    let mut count = 0;
+
+    loop
+    {
+      let size = match file.read(&mut buffer)
+      {
+        Ok(size) => size,
+        Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+        Err(err) => return Err(err.into()),
+      };
+      window.extend_from_slice(&buffer[..size]);
+
+      //the last window is searched until its end, others keep enough data to complete the matches starting at their end
+      let until = match size
+      {
+        0 => window.len(),
+        _ if window.len() > self.overlap => window.len() - self.overlap,
+        _ => continue,
+      };
+      let from = (reported - window_start) as usize;
+      for found in self.search(&window, window_start, from, until)
+      {
+        count += 1;
+        if !callback(&found) || Some(count) == self.options.max_matches
+        {
+          return Ok(count)
+        }
+      }
+      if size == 0
+      {
+        return Ok(count)
+      }
+
+      reported = window_start + until as u64;
+      //keep a byte before the next matches for the regex assertions like word boundaries
+      let drained = until.saturating_sub(1);
+      window.drain(..drained);
+      window_start += drained as u64;
+    }
+  }
+}
+
+/// Return the matches of `patterns` in `builder`, see [Scanner].
+pub fn scan(builder : &dyn VFileBuilder, patterns : &[Pattern], options : ScanOptions) -> Result<Vec<Match>>
+{
+  let mut matches = Vec::new();
+  Scanner::new(patterns, options)?.scan(builder, |found| { matches.push(*found); true })?;
+  Ok(matches)
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{scan, Scanner, Pattern, ScanOptions, Match};
+  use crate::memoryvfile::MemoryVFileBuilder;
+
+  #[test]
+  fn scan_patterns()
+  {
+    let mut content = vec![0u8; 10000];
+    content[4095..4099].copy_from_slice(b"\xff\xd8\xff\xe0");
+    content[6000..6011].copy_from_slice(b"PASSWORD=42");
+    content[9990..9994].copy_from_slice(b"PK\x03\x04");
+    let builder = MemoryVFileBuilder::from_buffer(content);
+
+    let patterns = vec![Pattern::Bytes(b"\xff\xd8\xff".to_vec()), Pattern::Regex(r"password=\d+".into()),
+                        Pattern::Bytes(b"PK\x03\x04".to_vec()), Pattern::Bytes(b"\xd8\xff\xe0".to_vec()), Pattern::Regex(r"\xFF\xD8".into())];
+    let options = ScanOptions{ case_insensitive : true, chunk_size : 4096, max_match_size : 64, ..Default::default() };
+    let matches = scan(builder.as_ref(), &patterns, options.clone()).unwrap();
+    assert!(matches == vec![Match{ pattern : 0, start : 4095, end : 4098 }, Match{ pattern : 4, start : 4095, end : 4097 },
+                            Match{ pattern : 3, start : 4096, end : 4099 }, Match{ pattern : 1, start : 6000, end : 6011 },
+                            Match{ pattern : 2, start : 9990, end : 9994 }]);
+
+    //every chunk size find the same matches
+    for chunk_size in [1, 7, 64, 65, 1000, 100000]
+    {
+      let options = ScanOptions{ chunk_size, ..options.clone() };
+      assert!(scan(builder.as_ref(), &patterns, options).unwrap() == matches);
+    }
+
+    let scanner = Scanner::new(&patterns, ScanOptions{ max_matches : Some(2), ..Default::default() }).unwrap();
+    assert!(scanner.scan(builder.as_ref(), |_| true).unwrap() == 2);
+    let mut first = None;
+    assert!(scanner.scan(builder.as_ref(), |found| { first = Some(found.start); false }).unwrap() == 1 && first == Some(4095));
+    let sensitive = scan(builder.as_ref(), &patterns[1..2], ScanOptions::default()).unwrap();
+    assert!(sensitive.is_empty());
+
+    assert!(Scanner::new(&[Pattern::Regex("(".into())], ScanOptions::default()).is_err());
+    assert!(Scanner::new(&[Pattern::Bytes(Vec::new())], ScanOptions::default()).is_err());
+  }
+}
@@ -0,0 +1,290 @@
+//! Decoding of the strings found in the files : UTF-8, UTF-16 in both byte orders and the code pages supported by [encoding_rs]
+//! (latin-1, windows-1252, shift-jis, ...), null-terminated string readers and a streaming [Lines] iterator for log parsers.
+//! Invalid sequences are replaced by U+FFFD rather than failing, as strings in evidence are often damaged.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::str::FromStr;
+
+use crate::error::RustructError;
+
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+
+/// Default maximum size of a line returned by [Lines], longer lines are split.
+pub const MAX_LINE_SIZE : usize = 1024 * 1024;
+
+/// Encoding of a text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding
+{
+  Utf8,
+  Utf16Le,
+  Utf16Be,
+  /// ISO-8859-1, each byte is the unicode code point of the same value.
+  Latin1,
+  Windows1252,
+  ShiftJis,
+}
+
+impl Encoding
+{
+  /// Return the name of the encoding.
+  pub fn name(&self) -> &'static str
+  {
+    match self
+    {
+      Encoding::Utf8 => "utf-8",
+      Encoding::Utf16Le => "utf-16le",
+      Encoding::Utf16Be => "utf-16be",
+      Encoding::Latin1 => "iso-8859-1",
+      Encoding::Windows1252 => "windows-1252",
+      Encoding::ShiftJis => "shift_jis",
+    }
+  }
+
+  /// Return the size of a code unit, and of the null terminator.
+  pub fn unit_size(&self) -> usize
+  {
+    match self
+    {
+      Encoding::Utf16Le | Encoding::Utf16Be => 2,
+      _ => 1,
+    }
+  }
+
+  /// Decode `bytes`, invalid sequences are replaced by U+FFFD.
+  pub fn decode(&self, bytes : &[u8]) -> String
+  {
+    match self
+    {
+      Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+      Encoding::Latin1 => bytes.iter().map(|byte| *byte as char).collect(),
+      Encoding::Utf16Le => encoding_rs::UTF_16LE.decode_without_bom_handling(bytes).0.into_owned(),
+      Encoding::Utf16Be => encoding_rs::UTF_16BE.decode_without_bom_handling(bytes).0.into_owned(),
+      Encoding::Windows1252 => encoding_rs::WINDOWS_1252.decode_without_bom_handling(bytes).0.into_owned(),
+      Encoding::ShiftJis => encoding_rs::SHIFT_JIS.decode_without_bom_handling(bytes).0.into_owned(),
+    }
+  }
+
+  /// Decode `bytes` until the first null code unit.
+  pub fn decode_until_null(&self, bytes : &[u8]) -> String
+  {
+    self.decode(&bytes[..null_position(bytes, self.unit_size()).unwrap_or(bytes.len())])
+  }
+}
+
+impl fmt::Display for Encoding
+{
+  fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result
+  {
+    write!(f, "{}", self.name())
+  }
+}
+
+impl FromStr for Encoding
+{
+  type Err = RustructError;
+
+  /// Parse an encoding name, the common aliases like `latin1`, `cp1252` or `sjis` are accepted.
+  fn from_str(name : &str) -> std::result::Result<Self, Self::Err>
+  {
+    match name.to_lowercase().replace('_', "-").as_str()
+    {
+      "utf-8" | "utf8" => Ok(Encoding::Utf8),
+      "utf-16le" | "utf16le" | "utf-16" | "utf16" => Ok(Encoding::Utf16Le),
+      "utf-16be" | "utf16be" => Ok(Encoding::Utf16Be),
+      "iso-8859-1" | "latin1" | "latin-1" => Ok(Encoding::Latin1),
+      "windows-1252" | "cp1252" => Ok(Encoding::Windows1252),
+      "shift-jis" | "sjis" | "cp932" => Ok(Encoding::ShiftJis),
+      _ => Err(RustructError::Parse("encoding", name.to_string())),
+    }
+  }
+}
+
+/// Return the offset of the first null code unit of `unit_size` bytes in `bytes`.
+fn null_position(bytes : &[u8], unit_size : usize) -> Option<usize>
+{
+  bytes.chunks_exact(unit_size).position(|unit| unit.iter().all(|byte| *byte == 0)).map(|index| index * unit_size)
+}
+
+/// Read `size` bytes from `file` and decode them with `encoding` until the first null code unit.
+pub fn read_string_exact<R : Read + ?Sized>(file : &mut R, size : usize, encoding : Encoding) -> Result<String>
+{
+  let mut data = vec![0; size];
+  file.read_exact(&mut data)?;
+  Ok(encoding.decode_until_null(&data))
+}
+
+/// Read a null-terminated string encoded with `encoding` from `file`, reading at most `max_size` bytes.
+/// The file is left after the terminator, an error is returned if it's not found.
+pub fn read_cstring<R : Read + Seek + ?Sized>(file : &mut R, max_size : usize, encoding : Encoding) -> Result<String>
+{
+  let unit_size = encoding.unit_size();
+  let mut data = Vec::new();
+  let mut buffer = [0; 256];
+
+  while data.len() < max_size
+  {
+    let size = buffer.len().min(max_size - data.len());
+    let read = file.read(&mut buffer[..size])?;
+    if read == 0
+    {
+      break
+    }
+    data.extend_from_slice(&buffer[..read]);
+
+    //the terminator is searched from the start of the last unit complete before this read
+    let from = (data.len() - read) / unit_size * unit_size;
+    if let Some(position) = null_position(&data[from..], unit_size).map(|position| from + position)
+    {
+      file.seek(SeekFrom::Current(-((data.len() - position - unit_size) as i64)))?;
+      return Ok(encoding.decode(&data[..position]))
+    }
+  }
+  Err(RustructError::InvalidEncoding(format!("no null terminator in {} bytes of {} string", data.len(), encoding)).into())
+}
+
+/**
+ * Iterator on the lines of a [Read] decoded with an [Encoding], the line terminators (`\n` or `\r\n`) are removed.
+ * The data is read by blocks, lines longer than the maximum line size are split.
+ */
+pub struct Lines<R>
+{
+  reader : BufReader<R>,
+  encoding : Encoding,
+  max_line_size : usize,
+}
+
+impl<R : Read> Lines<R>
+{
+  pub fn new(reader : R, encoding : Encoding) -> Self
+  {
+    Lines{ reader : BufReader::new(reader), encoding, max_line_size : MAX_LINE_SIZE }
+  }
+
+  /// Split the lines longer than `max_line_size` bytes.
+  pub fn with_max_line_size(mut self, max_line_size : usize) -> Self
+  {
+    let unit_size = self.encoding.unit_size();
+    self.max_line_size = (max_line_size / unit_size * unit_size).max(unit_size);
+    self
+  }
+
+  /// Read the bytes of the next line with its terminator, return false at the end of the data.
+  fn read_line(&mut self, line : &mut Vec<u8>) -> io::Result<bool>
+  {
+    let unit_size = self.encoding.unit_size();
+    let newline : &[u8] = match self.encoding
+    {
+      Encoding::Utf16Le => &[b'\n', 0],
+      Encoding::Utf16Be => &[0, b'\n'],
+      _ => b"\n",
+    };
+
+    while line.len() < self.max_line_size
+    {
+      let available = match self.reader.fill_buf()
+      {
+        Ok(available) => available,
+        Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+        Err(err) => return Err(err),
+      };
+      if available.is_empty()
+      {
+        break
+      }
+
+      //complete the unit split by the previous block before searching the terminator
+      let partial = line.len() % unit_size;
+      let take = match partial
+      {
+        0 => 0,
+        partial => (unit_size - partial).min(available.len()),
+      };
+      let units = &available[take..];
+      let end = units.chunks_exact(unit_size).position(|unit| unit == newline).map(|index| take + (index + 1) * unit_size);
+      let size = end.unwrap_or(available.len()).min(self.max_line_size - line.len());
+      line.extend_from_slice(&available[..size]);
+      self.reader.consume(size);
+      if end.is_some_and(|end| end == size)
+      {
+        return Ok(true)
+      }
+    }
+    Ok(!line.is_empty())
+  }
+}
+
+impl<R : Read> Iterator for Lines<R>
+{
+  type Item = Result<String>;
+
+  fn next(&mut self) -> Option<Self::Item>
+  {
+    let mut line = Vec::new();
+    match self.read_line(&mut line)
+    {
+      Ok(false) => None,
+      Err(err) => Some(Err(err.into())),
+      Ok(true) =>
+      {
+        let mut text = self.encoding.decode(&line);
+        if text.ends_with('\n')
+        {
+          text.pop();
+          if text.ends_with('\r')
+          {
+            text.pop();
+          }
+        }
+        Some(Ok(text))
+      },
+    }
+  }
+}
+
+/// Return an iterator on the lines of `reader` decoded with `encoding`.
+pub fn lines<R : Read>(reader : R, encoding : Encoding) -> Lines<R>
+{
+  Lines::new(reader, encoding)
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::{Encoding, read_string_exact, read_cstring, lines};
+
+  use std::io::{Cursor, Read};
+
+  #[test]
+  fn decode_text()
+  {
+    assert!(Encoding::Utf16Be.decode(&[0, b'h', 0, b'i']) == "hi" && Encoding::Utf16Le.decode(&[b'h', 0, b'i', 0]) == "hi");
+    assert!(Encoding::Latin1.decode(&[0xe9, 0x80]) == "\u{e9}\u{80}" && Encoding::Windows1252.decode(&[0xe9, 0x80]) == "\u{e9}\u{20ac}");
+    assert!(Encoding::ShiftJis.decode(&[0x93, 0xfa, 0x96, 0x7b]) == "\u{65e5}\u{672c}");
+    assert!("CP1252".parse::<Encoding>().unwrap() == Encoding::Windows1252 && "ebcdic".parse::<Encoding>().is_err());
+
+    let mut file = Cursor::new(b"abc\0\0\0def".to_vec());
+    assert!(read_string_exact(&mut file, 6, Encoding::Utf8).unwrap() == "abc");
+    let mut file = Cursor::new(b"config\0next\0\x00\x61\x00\x00\x00\x62".to_vec());
+    assert!(read_cstring(&mut file, 100, Encoding::Utf8).unwrap() == "config");
+    assert!(read_cstring(&mut file, 100, Encoding::Latin1).unwrap() == "next");
+    assert!(read_cstring(&mut file, 100, Encoding::Utf16Be).unwrap() == "a");
+    let mut rest = Vec::new();
+    file.read_to_end(&mut rest).unwrap();
+    assert!(rest == [0, 0x62]);
+    assert!(read_cstring(&mut Cursor::new(b"no terminator".to_vec()), 5, Encoding::Utf8).is_err());
+
+    let log = b"first\r\nsecond\n\nthird line".to_vec();
+    let read : Vec<String> = lines(Cursor::new(log), Encoding::Utf8).map(|line| line.unwrap()).collect();
+    assert!(read == vec!["first", "second", "", "third line"]);
+    let utf16 : Vec<u8> = "a\u{a0a}\nbc\r\n".encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect();
+    let read : Vec<String> = lines(Cursor::new(utf16), Encoding::Utf16Le).map(|line| line.unwrap()).collect();
+    assert!(read == vec!["a\u{a0a}", "bc"]);
+    let split : Vec<String> = lines(Cursor::new(b"abcdefg\nh".to_vec()), Encoding::Utf8).with_max_line_size(3).map(|line| line.unwrap()).collect();
+    assert!(split == vec!["abc", "def", "g", "h"]);
+  }
+}
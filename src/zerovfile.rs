@@ -3,18 +3,35 @@ use std::io::Seek;
 use std::io::SeekFrom;
 use std::io::{Error, ErrorKind};
 
-use crate::vfile::{VFile, VFileBuilder};
+use crate::vfile::{VFile, VFileBuilder, Extent};
 
 use anyhow::Result;
 use serde::{Serialize, Deserialize};
 
 /**
  * VFileBuilder implementation for ZeroVFile.
- * A VFile with an infinize size that return data set to 0 can be used in a MappedVFile to simulate sparse zone.
+ * A VFile of `size` bytes that return data set to 0 can be used in a MappedVFile to simulate sparse zone.
  */
 #[derive(Debug,Serialize,Deserialize)]
 pub struct ZeroVFileBuilder
 {
+  /// Builders serialized before the size was added were infinite.
+  #[serde(default = "unbounded")]
+  size : u64,
+}
+
+fn unbounded() -> u64
+{
+  u64::MAX
+}
+
+impl ZeroVFileBuilder
+{
+  /// Return a builder of files of `size` bytes set to 0.
+  pub fn new(size : u64) -> Self
+  {
+    ZeroVFileBuilder{ size }
+  }
 }
 
 #[typetag::serde]
@@ -22,31 +39,43 @@ impl VFileBuilder for ZeroVFileBuilder
 {
   fn open(&self) -> Result<Box<dyn VFile>>
   {
-    Ok(Box::new(ZeroVFile{ pos : 0}))
+    Ok(Box::new(ZeroVFile{ pos : 0, size : self.size }))
   }
 
   fn size(&self) -> u64
   {
-    //we're infinite ...
-    u64::MAX
+    self.size
+  }
+
+  /// The whole file is a hole.
+  fn extents(&self) -> Vec<Extent>
+  {
+    match self.size
+    {
+      0 => Vec::new(),
+      size => vec![Extent{ range : 0..size, hole : true }],
+    }
   }
 }
 
 /**
- * A VFile with an infinize size that return data set to 0 
+ * A VFile of `size` bytes that return data set to 0 
  * can be used in a MappedVFile to simulate sparse zone.
  */
 struct ZeroVFile
 {
-  pub pos : u64
+  pub pos : u64,
+  pub size : u64,
 }
 
 impl Read for ZeroVFile
 {
   fn read(&mut self, buf : &mut [u8]) -> std::io::Result<usize>
   {
-    //we can zero buf, but generally buffer are already zeroed
-    Ok(buf.len())
+    let size = (buf.len() as u64).min(self.size.saturating_sub(self.pos)) as usize;
+    buf[..size].fill(0);
+    self.pos += size as u64;
+    Ok(size)
   }
 }
 
@@ -54,13 +83,46 @@ impl Seek for ZeroVFile
 {
   fn seek(&mut self, pos : SeekFrom) -> std::io::Result<u64>
   {
-    let pos : u64 = match pos 
+    let pos = match pos 
     {
-      SeekFrom::Start(pos) => pos,
-      SeekFrom::End(_pos) =>  return Err(Error::new(ErrorKind::Other, "MappedVFile::Seek : Can't seek past end of file")),
-      SeekFrom::Current(pos) => (pos + self.pos as i64) as u64,
+      SeekFrom::Start(pos) => Some(pos),
+      SeekFrom::End(pos) => self.size.checked_add_signed(pos),
+      SeekFrom::Current(pos) => self.pos.checked_add_signed(pos),
     };
-    self.pos = pos;
-    Ok(self.pos)
+    match pos
+    {
+      Some(pos) => { self.pos = pos; Ok(self.pos) },
+      None => Err(Error::new(ErrorKind::InvalidInput, "ZeroVFile::Seek : Can't seek to a negative or overflowing position")),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests
+{
+  use super::ZeroVFileBuilder;
+  use crate::vfile::VFileBuilder;
+
+  use std::io::{Read, Seek, SeekFrom};
+
+  #[test]
+  fn read_zeros()
+  {
+    let builder = ZeroVFileBuilder::new(100);
+    let mut file = builder.open().unwrap();
+    let mut buffer = [0xff; 64];
+    assert!(file.read(&mut buffer).unwrap() == 64 && buffer == [0; 64]);
+    buffer.fill(0xff);
+    assert!(file.read(&mut buffer).unwrap() == 36 && buffer[..36] == [0; 36] && buffer[36..] == [0xff; 28]);
+    assert!(file.read(&mut buffer).unwrap() == 0);
+
+    assert!(file.seek(SeekFrom::End(-10)).unwrap() == 90 && file.read(&mut buffer).unwrap() == 10);
+    assert!(file.seek(SeekFrom::End(-101)).is_err() && file.seek(SeekFrom::Current(-200)).is_err());
+    let mut data = Vec::new();
+    file.seek(SeekFrom::Start(0)).unwrap();
+    assert!(file.read_to_end(&mut data).unwrap() == 100);
+
+    let loaded : ZeroVFileBuilder = serde_json::from_str("{}").unwrap();
+    assert!(loaded.size() == u64::MAX && builder.extents()[0].hole);
   }
 }